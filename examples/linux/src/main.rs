@@ -2,14 +2,15 @@
 
 use embassy_executor::Spawner;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
-use embassy_sync::mutex::Mutex;
 use embassy_time::Duration;
 use embassy_time::Timer;
 use heimlig::client::api::Api;
 use heimlig::common::jobs::{RequestType, Response};
-use heimlig::hsm::core::Builder;
+use heimlig::common::sync::Mutex;
+use heimlig::hsm::core::{Builder, ClientConfig};
 use heimlig::hsm::keystore::KeyInfo;
 use heimlig::hsm::workers::rng_worker::RngWorker;
+use heimlig::hsm::workers::QueuedWorker;
 use heimlig::integration::embassy::{
     RequestQueue, RequestQueueSink, RequestQueueSource, ResponseQueue, ResponseQueueSink,
     ResponseQueueSource,
@@ -41,18 +42,21 @@ async fn core_task(
         CriticalSectionRawMutex,
         RequestQueueSource<'_, '_, QUEUE_SIZE>,
         ResponseQueueSink<'_, '_, QUEUE_SIZE>,
+        MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
         RequestQueueSink<'_, '_, QUEUE_SIZE>,
         ResponseQueueSource<'_, '_, QUEUE_SIZE>,
-        MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
     >::new()
-    .with_client(core_req_rx, core_resp_tx)
+    .with_client(core_req_rx, core_resp_tx, ClientConfig::default())
     .expect("failed to add client")
     .with_worker(&[RequestType::GetRandom], core_req_tx, core_resp_rx)
     .expect("failed to add worker")
     .build();
 
     loop {
-        core.execute().await.expect("failed to forward request");
+        // Failing client and worker channels are handled by the core, only internal errors remain
+        if let Err(e) = core.execute().await {
+            error!("Core failed to process job: {e}");
+        }
         Timer::after(Duration::from_millis(100)).await;
     }
 }
@@ -67,11 +71,14 @@ async fn worker_task(
     let key_store: Mutex<CriticalSectionRawMutex, _> = Mutex::new(&mut key_store);
     let rng: Mutex<CriticalSectionRawMutex, _> =
         Mutex::new(rand_chacha::ChaCha20Rng::from_seed([0u8; 32]));
-    let mut rng_worker = RngWorker {
-        key_store: Some(&key_store),
-        rng: &rng,
+    let mut rng_worker = QueuedWorker {
+        worker: RngWorker {
+            key_store: Some(&key_store),
+            rng: &rng,
+        },
         requests: rng_req_rx,
         responses: rng_resp_tx,
+        cancellations: None,
     };
 
     loop {
@@ -10,11 +10,11 @@ use embassy_stm32::gpio::{Level, Output, Speed};
 use embassy_stm32::peripherals::RNG;
 use embassy_stm32::rng::{InterruptHandler, Rng};
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
-use embassy_sync::mutex::Mutex;
 use embassy_time::{Duration, Timer};
 use heimlig::client::api::Api;
-use heimlig::common::jobs::{RequestType, Response};
-use heimlig::hsm::core::Builder;
+use heimlig::common::jobs::Response;
+use heimlig::common::sync::Mutex;
+use heimlig::hsm::core::{Builder, ClientConfig};
 use heimlig::hsm::keystore::KeyInfo;
 use heimlig::hsm::workers::rng_worker::RngWorker;
 use heimlig::integration::embassy::{
@@ -35,8 +35,6 @@ static mut MEMORY: [u8; 256] = [0; 256];
 const QUEUE_SIZE: usize = 8;
 static mut CLIENT_TO_CORE: RequestQueue<QUEUE_SIZE> = RequestQueue::<QUEUE_SIZE>::new();
 static mut CORE_TO_CLIENT: ResponseQueue<QUEUE_SIZE> = ResponseQueue::<QUEUE_SIZE>::new();
-static mut CORE_TO_RNG_WORKER: RequestQueue<QUEUE_SIZE> = RequestQueue::<QUEUE_SIZE>::new();
-static mut RNG_WORKER_TO_CORE: ResponseQueue<QUEUE_SIZE> = ResponseQueue::<QUEUE_SIZE>::new();
 
 // Key store info
 const NUM_KEYS: usize = 0;
@@ -47,10 +45,6 @@ const KEY_INFOS: &[KeyInfo] = &[];
 async fn hsm_task(
     core_req_rx: RequestQueueSource<'static, 'static, QUEUE_SIZE>,
     core_resp_tx: ResponseQueueSink<'static, 'static, QUEUE_SIZE>,
-    core_req_tx: RequestQueueSink<'static, 'static, QUEUE_SIZE>,
-    core_resp_rx: ResponseQueueSource<'static, 'static, QUEUE_SIZE>,
-    rng_req_rx: RequestQueueSource<'static, 'static, QUEUE_SIZE>,
-    rng_resp_tx: ResponseQueueSink<'static, 'static, QUEUE_SIZE>,
     rng: Rng<'static, RNG>,
 ) {
     info!("HSM task started");
@@ -58,32 +52,27 @@ async fn hsm_task(
         .expect("failed to create key store");
     let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
     let rng: Mutex<NoopRawMutex, _> = Mutex::new(rng);
-    let mut rng_worker = RngWorker {
+    // The worker is hosted by the core and does not need queues of its own
+    let rng_worker = RngWorker {
         key_store: Some(&key_store),
         rng: &rng,
-        requests: rng_req_rx,
-        responses: rng_resp_tx,
     };
     let mut core = Builder::<
         NoopRawMutex,
         RequestQueueSource<'_, '_, QUEUE_SIZE>,
         ResponseQueueSink<'_, '_, QUEUE_SIZE>,
-        RequestQueueSink<'_, '_, QUEUE_SIZE>,
-        ResponseQueueSource<'_, '_, QUEUE_SIZE>,
         MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
     >::new()
-    .with_client(core_req_rx, core_resp_tx)
+    .with_client(core_req_rx, core_resp_tx, ClientConfig::default())
     .expect("failed to add client")
-    .with_worker(&[RequestType::GetRandom], core_req_tx, core_resp_rx)
+    .with_hosted_worker(rng_worker)
     .expect("failed to add worker")
     .build();
 
     loop {
-        core.execute().await.expect("failed to forward request");
-        rng_worker
-            .execute()
-            .await
-            .expect("failed to process request");
+        if let Err(e) = core.execute().await {
+            error!("Core failed to process job: {}", Debug2Format(&e));
+        }
         Timer::after(Duration::from_millis(100)).await;
     }
 }
@@ -174,20 +163,10 @@ async fn main(spawner: Spawner) {
     // Unsafe: Access to mutable static only happens here. Static lifetime is required by embassy tasks.
     let (client_req_tx, core_req_rx) = unsafe { CLIENT_TO_CORE.split() };
     let (core_resp_tx, client_resp_rx) = unsafe { CORE_TO_CLIENT.split() };
-    let (core_req_tx, rng_req_rx) = unsafe { CORE_TO_RNG_WORKER.split() };
-    let (rng_resp_tx, core_resp_rx) = unsafe { RNG_WORKER_TO_CORE.split() };
 
     // Start tasks
     spawner
-        .spawn(hsm_task(
-            core_req_rx,
-            core_resp_tx,
-            core_req_tx,
-            core_resp_rx,
-            rng_req_rx,
-            rng_resp_tx,
-            rng,
-        ))
+        .spawn(hsm_task(core_req_rx, core_resp_tx, rng))
         .expect("Failed to spawn HSM task");
     spawner
         .spawn(client_task(client_req_tx, client_resp_rx, led))
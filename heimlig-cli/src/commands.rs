@@ -0,0 +1,927 @@
+//! Commands of the command-line client.
+
+use crate::connection::{Connection, Error};
+use crate::encoding::{read_data, read_encoded, write_output, write_text};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use heimlig::common::jobs::{
+    self, HashAlgorithm, Padding, PrivateKeyFormat, PublicKeyFormat, Request, Response,
+    SignatureFormat,
+};
+use heimlig::crypto::hash;
+use heimlig::hsm::keystore::{self, Curve, KeyId, KeyInfo, KeyType};
+use std::path::{Path, PathBuf};
+
+/// Size of the nonces used for AES-GCM and ChaCha20-Poly1305.
+const AEAD_NONCE_SIZE: usize = 12;
+/// Size of the tags of AES-GCM, ChaCha20-Poly1305 and AES-CMAC.
+const TAG_SIZE: usize = 16;
+const AES_BLOCK_SIZE: usize = 16;
+/// Largest overhead of a DER encoded ECDSA signature compared to the raw signature.
+const DER_SIGNATURE_OVERHEAD: usize = 9;
+/// Large enough for every supported key in every export format.
+const EXPORT_BUFFER_SIZE: usize = 256;
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Manage the keys of the daemon
+    #[command(subcommand)]
+    Key(KeyCommand),
+    /// Encrypt a file. The output is the nonce or IV followed by the ciphertext and the tag.
+    Encrypt(CipherArgs),
+    /// Decrypt a file produced by `encrypt`
+    Decrypt(CipherArgs),
+    /// Sign a file with a key pair
+    Sign(SignArgs),
+    /// Verify the signature of a file
+    Verify(VerifyArgs),
+    /// Calculate or verify the HMAC of a file
+    Hmac(HmacArgs),
+    /// Calculate or verify the AES-CMAC of a file
+    Cmac(CmacArgs),
+    /// Get random bytes from the daemon
+    Random {
+        /// Number of bytes
+        size: usize,
+        /// Output file. Prints hex to stdout if omitted.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Hash a file. The digest is calculated locally without the daemon.
+    Hash {
+        #[arg(short, long, value_enum, default_value_t = Hash::Sha2_256)]
+        algorithm: Hash,
+        /// Input file, `-` for stdin
+        input: PathBuf,
+        /// Output file. Prints hex to stdout if omitted.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Run commands from a script with one command per line. Empty lines and lines starting
+    /// with `#` are skipped.
+    Batch {
+        /// Script file, `-` for stdin
+        #[arg(default_value = "-")]
+        script: PathBuf,
+        /// Continue with the next command if a command fails
+        #[arg(long)]
+        keep_going: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum KeyCommand {
+    /// List the keys of the daemon and whether they are set. Key IDs in the range that are not
+    /// defined by the daemon are skipped.
+    List {
+        /// First key ID of the range
+        #[arg(long, default_value = "0", value_parser = parse_key_id)]
+        first: KeyId,
+        /// Last key ID of the range
+        #[arg(long, default_value = "0x1ff", value_parser = parse_key_id)]
+        last: KeyId,
+    },
+    /// Generate a key in the daemon
+    Generate {
+        /// Key ID, decimal or hexadecimal with `0x` prefix
+        #[arg(short, long, value_parser = parse_key_id)]
+        key: KeyId,
+        /// Replace an existing key
+        #[arg(long)]
+        overwrite: bool,
+    },
+    /// Import a key from a hex, PEM or DER file
+    Import(ImportArgs),
+    /// Export a key
+    Export(ExportArgs),
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// Key ID, decimal or hexadecimal with `0x` prefix
+    #[arg(short, long, value_parser = parse_key_id)]
+    key: KeyId,
+    /// Symmetric key or private key of a key pair
+    input: PathBuf,
+    /// Public key of a raw private key
+    #[arg(long)]
+    public: Option<PathBuf>,
+    /// Format of the private key. Detected from the PEM label or the presence of `--public` and
+    /// PKCS#8 otherwise.
+    #[arg(long, value_enum)]
+    format: Option<PrivateFormat>,
+    /// Replace an existing key
+    #[arg(long)]
+    overwrite: bool,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Key ID, decimal or hexadecimal with `0x` prefix
+    #[arg(short, long, value_parser = parse_key_id)]
+    key: KeyId,
+    /// Export the private key of a key pair instead of the public key
+    #[arg(long)]
+    private: bool,
+    /// Format of the public key
+    #[arg(long, value_enum)]
+    format: Option<PublicFormat>,
+    /// Output file. Prints hex or PEM to stdout if omitted.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct CipherArgs {
+    /// Key ID, decimal or hexadecimal with `0x` prefix
+    #[arg(short, long, value_parser = parse_key_id)]
+    key: KeyId,
+    #[arg(short, long, value_enum, default_value_t = Cipher::AesGcm)]
+    algorithm: Cipher,
+    /// File with additional authenticated data
+    #[arg(long)]
+    aad: Option<PathBuf>,
+    /// Input file, `-` for stdin
+    input: PathBuf,
+    /// Output file. Prints hex to stdout if omitted.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct SignatureArgs {
+    /// Key ID, decimal or hexadecimal with `0x` prefix
+    #[arg(short, long, value_parser = parse_key_id)]
+    key: KeyId,
+    /// Hash of the message. Defaults to the hash of the curve.
+    #[arg(long, value_enum)]
+    hash: Option<Hash>,
+    /// The input file contains the digest of the message instead of the message
+    #[arg(long)]
+    prehashed: bool,
+    /// Use DER encoded signatures instead of raw `r || s` signatures
+    #[arg(long)]
+    der: bool,
+    /// Input file, `-` for stdin
+    input: PathBuf,
+}
+
+#[derive(Args)]
+pub struct SignArgs {
+    #[command(flatten)]
+    signature: SignatureArgs,
+    /// Output file. Prints hex to stdout if omitted.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    #[command(flatten)]
+    signature_args: SignatureArgs,
+    /// Hex, PEM or binary signature file
+    #[arg(long)]
+    signature: PathBuf,
+}
+
+#[derive(Args)]
+pub struct HmacArgs {
+    /// Key ID, decimal or hexadecimal with `0x` prefix
+    #[arg(short, long, value_parser = parse_key_id)]
+    key: KeyId,
+    #[arg(long, value_enum, default_value_t = Hash::Sha2_256)]
+    hash: Hash,
+    /// Input file, `-` for stdin
+    input: PathBuf,
+    /// Hex or binary tag file to verify instead of calculating the tag
+    #[arg(long)]
+    verify: Option<PathBuf>,
+    /// Output file. Prints hex to stdout if omitted.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct CmacArgs {
+    /// Key ID, decimal or hexadecimal with `0x` prefix
+    #[arg(short, long, value_parser = parse_key_id)]
+    key: KeyId,
+    /// Input file, `-` for stdin
+    input: PathBuf,
+    /// Hex or binary tag file to verify instead of calculating the tag
+    #[arg(long)]
+    verify: Option<PathBuf>,
+    /// Output file. Prints hex to stdout if omitted.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum Cipher {
+    AesGcm,
+    AesCbc,
+    ChachaPoly,
+}
+
+impl Cipher {
+    fn nonce_size(&self) -> usize {
+        match self {
+            Cipher::AesGcm | Cipher::ChachaPoly => AEAD_NONCE_SIZE,
+            Cipher::AesCbc => AES_BLOCK_SIZE,
+        }
+    }
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum Hash {
+    #[value(name = "sha2-256")]
+    Sha2_256,
+    #[value(name = "sha2-384")]
+    Sha2_384,
+    #[value(name = "sha2-512")]
+    Sha2_512,
+    #[value(name = "sha3-256")]
+    Sha3_256,
+    #[value(name = "sha3-384")]
+    Sha3_384,
+    #[value(name = "sha3-512")]
+    Sha3_512,
+    /// Only supported by the `hash` command
+    Blake3,
+}
+
+impl Hash {
+    fn algorithm(self) -> Result<HashAlgorithm, Error> {
+        match self {
+            Hash::Sha2_256 => Ok(HashAlgorithm::Sha2_256),
+            Hash::Sha2_384 => Ok(HashAlgorithm::Sha2_384),
+            Hash::Sha2_512 => Ok(HashAlgorithm::Sha2_512),
+            Hash::Sha3_256 => Ok(HashAlgorithm::Sha3_256),
+            Hash::Sha3_384 => Ok(HashAlgorithm::Sha3_384),
+            Hash::Sha3_512 => Ok(HashAlgorithm::Sha3_512),
+            Hash::Blake3 => Err(Error::Usage(
+                "BLAKE3 is only supported by the hash command".to_string(),
+            )),
+        }
+    }
+
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Hash::Sha2_256 => hash::sha256(data).to_vec(),
+            Hash::Sha2_384 => hash::sha384(data).to_vec(),
+            Hash::Sha2_512 => hash::sha512(data).to_vec(),
+            Hash::Sha3_256 => hash::sha3_256(data).to_vec(),
+            Hash::Sha3_384 => hash::sha3_384(data).to_vec(),
+            Hash::Sha3_512 => hash::sha3_512(data).to_vec(),
+            Hash::Blake3 => hash::blake3(data).to_vec(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum PrivateFormat {
+    /// Raw private scalar. Requires `--public`.
+    Raw,
+    /// PKCS#8 DER document
+    Pkcs8,
+    /// SEC1 DER document
+    Sec1,
+}
+
+impl From<PrivateFormat> for PrivateKeyFormat {
+    fn from(format: PrivateFormat) -> Self {
+        match format {
+            PrivateFormat::Raw => PrivateKeyFormat::Raw,
+            PrivateFormat::Pkcs8 => PrivateKeyFormat::Pkcs8Der,
+            PrivateFormat::Sec1 => PrivateKeyFormat::Sec1Der,
+        }
+    }
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum PublicFormat {
+    /// Uncompressed point without the SEC1 tag byte
+    Raw,
+    /// Uncompressed SEC1 point
+    Sec1,
+    /// Compressed SEC1 point
+    Sec1Compressed,
+    /// SubjectPublicKeyInfo DER document
+    Der,
+    /// SubjectPublicKeyInfo PEM document
+    Pem,
+}
+
+impl From<PublicFormat> for PublicKeyFormat {
+    fn from(format: PublicFormat) -> Self {
+        match format {
+            PublicFormat::Raw => PublicKeyFormat::Raw,
+            PublicFormat::Sec1 => PublicKeyFormat::Sec1Uncompressed,
+            PublicFormat::Sec1Compressed => PublicKeyFormat::Sec1Compressed,
+            PublicFormat::Der => PublicKeyFormat::SpkiDer,
+            PublicFormat::Pem => PublicKeyFormat::SpkiPem,
+        }
+    }
+}
+
+/// Parse a decimal or `0x` prefixed hexadecimal key ID.
+fn parse_key_id(value: &str) -> Result<KeyId, String> {
+    let id = match value.strip_prefix("0x") {
+        Some(digits) => u32::from_str_radix(digits, 16),
+        None => value.parse(),
+    };
+    id.map(KeyId).map_err(|e| e.to_string())
+}
+
+/// One line of a batch script.
+#[derive(Parser)]
+#[command(no_binary_name = true)]
+struct BatchLine {
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// State shared by the commands of a batch. The connection to the daemon is opened by the first
+/// command that needs it.
+pub struct Session {
+    socket: PathBuf,
+    connection: Option<Connection>,
+}
+
+impl Session {
+    pub fn new(socket: PathBuf) -> Self {
+        Session {
+            socket,
+            connection: None,
+        }
+    }
+
+    fn connection(&mut self) -> Result<&mut Connection, Error> {
+        if self.connection.is_none() {
+            self.connection = Some(Connection::open(&self.socket)?);
+        }
+        Ok(self
+            .connection
+            .as_mut()
+            .expect("connection was just opened"))
+    }
+
+    pub fn run(&mut self, command: Command) -> Result<(), Error> {
+        match command {
+            Command::Key(KeyCommand::List { first, last }) => {
+                list_keys(self.connection()?, first, last)
+            }
+            Command::Key(KeyCommand::Generate { key, overwrite }) => {
+                generate_key(self.connection()?, key, overwrite)
+            }
+            Command::Key(KeyCommand::Import(args)) => import_key(self.connection()?, args),
+            Command::Key(KeyCommand::Export(args)) => export_key(self.connection()?, args),
+            Command::Encrypt(args) => encrypt(self.connection()?, args),
+            Command::Decrypt(args) => decrypt(self.connection()?, args),
+            Command::Sign(args) => sign(self.connection()?, args),
+            Command::Verify(args) => verify(self.connection()?, args),
+            Command::Hmac(args) => hmac(self.connection()?, args),
+            Command::Cmac(args) => cmac(self.connection()?, args),
+            Command::Random { size, output } => {
+                let mut data = vec![0u8; size];
+                get_random(self.connection()?, &mut data)?;
+                write_output(&data, output.as_deref())
+            }
+            Command::Hash {
+                algorithm,
+                input,
+                output,
+            } => write_output(&algorithm.digest(&read_data(&input)?), output.as_deref()),
+            Command::Batch { script, keep_going } => self.batch(&script, keep_going),
+        }
+    }
+
+    fn batch(&mut self, script: &Path, keep_going: bool) -> Result<(), Error> {
+        let script = String::from_utf8(read_data(script)?)
+            .map_err(|_| Error::Usage("script is not valid UTF-8".to_string()))?;
+        let mut failures = 0;
+        for (index, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let result = shlex::split(line)
+                .ok_or_else(|| Error::Usage("unbalanced quotes".to_string()))
+                .and_then(|words| {
+                    BatchLine::try_parse_from(words).map_err(|e| Error::Usage(e.to_string()))
+                })
+                .and_then(|line| match line.command {
+                    Command::Batch { .. } => {
+                        Err(Error::Usage("batch scripts cannot be nested".to_string()))
+                    }
+                    command => self.run(command),
+                });
+            if let Err(e) = result {
+                eprintln!("line {}: {}", index + 1, e.to_string().trim_end());
+                failures += 1;
+                if !keep_going {
+                    break;
+                }
+            }
+        }
+        match failures {
+            0 => Ok(()),
+            failures => Err(Error::BatchFailed(failures)),
+        }
+    }
+}
+
+/// Query the type of the key `key_id` from the daemon.
+///
+/// returns: The key info or `None` if the daemon does not define the key ID.
+fn query_key_info(connection: &mut Connection, key_id: KeyId) -> Result<Option<KeyInfo>, Error> {
+    let mut reply = connection.call(|client_id, request_id| Request::GetKeyInfo {
+        client_id,
+        request_id,
+        deadline: None,
+        key_id,
+    })?;
+    match reply.response() {
+        Ok(Response::GetKeyInfo { key_info, .. }) => Ok(Some(key_info)),
+        Err(Error::Heimlig(jobs::Error::KeyStore(keystore::Error::InvalidKeyId))) => Ok(None),
+        Err(e) => Err(e),
+        Ok(_) => Err(Error::UnexpectedResponse),
+    }
+}
+
+fn key_info(connection: &mut Connection, key_id: KeyId) -> Result<KeyInfo, Error> {
+    query_key_info(connection, key_id)?
+        .ok_or_else(|| Error::Usage(format!("unknown key ID {}", key_id.0)))
+}
+
+fn describe(ty: KeyType) -> String {
+    match ty {
+        KeyType::Symmetric(size) => format!("{size} byte symmetric key"),
+        KeyType::Asymmetric(Curve::NistP256) => "NIST P-256 key pair".to_string(),
+        KeyType::Asymmetric(Curve::NistP384) => "NIST P-384 key pair".to_string(),
+        KeyType::KeyPair { algorithm, .. } => format!("{algorithm:?} key pair"),
+    }
+}
+
+fn list_keys(connection: &mut Connection, first: KeyId, last: KeyId) -> Result<(), Error> {
+    for key_id in first.0..=last.0 {
+        let Some(info) = query_key_info(connection, KeyId(key_id))? else {
+            continue;
+        };
+        let mut reply = connection.call(|client_id, request_id| Request::IsKeyAvailable {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: info.id,
+        })?;
+        let Response::IsKeyAvailable { is_available, .. } = reply.response()? else {
+            return Err(Error::UnexpectedResponse);
+        };
+        let state = if is_available { "set" } else { "empty" };
+        println!("{:#06x}  {:<24}{state}", info.id.0, describe(info.ty));
+    }
+    Ok(())
+}
+
+fn generate_key(connection: &mut Connection, key_id: KeyId, overwrite: bool) -> Result<(), Error> {
+    let info = key_info(connection, key_id)?;
+    let mut reply = connection.call(|client_id, request_id| {
+        if info.ty.is_symmetric() {
+            Request::GenerateSymmetricKey {
+                client_id,
+                request_id,
+                deadline: None,
+                key_id,
+                overwrite,
+            }
+        } else {
+            Request::GenerateKeyPair {
+                client_id,
+                request_id,
+                deadline: None,
+                key_id,
+                overwrite,
+            }
+        }
+    })?;
+    match reply.response()? {
+        Response::GenerateSymmetricKey { .. } | Response::GenerateKeyPair { .. } => Ok(()),
+        _ => Err(Error::UnexpectedResponse),
+    }
+}
+
+fn import_key(connection: &mut Connection, args: ImportArgs) -> Result<(), Error> {
+    let info = key_info(connection, args.key)?;
+    let key = read_encoded(&args.input)?;
+    if info.ty.is_symmetric() {
+        if args.public.is_some() || args.format.is_some() {
+            return Err(Error::Usage(
+                "symmetric keys are imported without --public and --format".to_string(),
+            ));
+        }
+        let mut reply = connection.call(|client_id, request_id| Request::ImportSymmetricKey {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: args.key,
+            data: &key.data,
+            overwrite: args.overwrite,
+        })?;
+        let Response::ImportSymmetricKey { .. } = reply.response()? else {
+            return Err(Error::UnexpectedResponse);
+        };
+        return Ok(());
+    }
+
+    let public_key = match &args.public {
+        Some(path) => read_encoded(path)?.data,
+        None => Vec::new(),
+    };
+    let format = match (args.format, key.label.as_deref()) {
+        (Some(format), _) => format.into(),
+        (None, Some("PRIVATE KEY")) => PrivateKeyFormat::Pkcs8Der,
+        (None, Some("EC PRIVATE KEY")) => PrivateKeyFormat::Sec1Der,
+        (None, Some(label)) => {
+            return Err(Error::Usage(format!("unsupported PEM label \"{label}\"")));
+        }
+        (None, None) if args.public.is_some() => PrivateKeyFormat::Raw,
+        (None, None) => PrivateKeyFormat::Pkcs8Der,
+    };
+    if (format == PrivateKeyFormat::Raw) != args.public.is_some() {
+        return Err(Error::Usage(
+            "--public is required for raw private keys and only allowed for them".to_string(),
+        ));
+    }
+    let mut reply = connection.call(|client_id, request_id| Request::ImportKeyPair {
+        client_id,
+        request_id,
+        deadline: None,
+        key_id: args.key,
+        public_key: &public_key,
+        private_key: &key.data,
+        format,
+        overwrite: args.overwrite,
+    })?;
+    let Response::ImportKeyPair { .. } = reply.response()? else {
+        return Err(Error::UnexpectedResponse);
+    };
+    Ok(())
+}
+
+fn export_key(connection: &mut Connection, args: ExportArgs) -> Result<(), Error> {
+    let info = key_info(connection, args.key)?;
+    let mut buffer = [0u8; EXPORT_BUFFER_SIZE];
+    if info.ty.is_symmetric() || args.private {
+        if args.format.is_some() {
+            return Err(Error::Usage(
+                "--format is only supported for public keys".to_string(),
+            ));
+        }
+        if info.ty.is_symmetric() && args.private {
+            return Err(Error::Usage(format!(
+                "key {} is a symmetric key",
+                args.key.0
+            )));
+        }
+        let mut reply = connection.call(|client_id, request_id| {
+            if info.ty.is_symmetric() {
+                Request::ExportSymmetricKey {
+                    client_id,
+                    request_id,
+                    deadline: None,
+                    key_id: args.key,
+                    data: &mut buffer,
+                }
+            } else {
+                Request::ExportPrivateKey {
+                    client_id,
+                    request_id,
+                    deadline: None,
+                    key_id: args.key,
+                    private_key: &mut buffer,
+                }
+            }
+        })?;
+        return match reply.response()? {
+            Response::ExportSymmetricKey { key, .. }
+            | Response::ExportPrivateKey {
+                private_key: key, ..
+            } => write_output(key, args.output.as_deref()),
+            _ => Err(Error::UnexpectedResponse),
+        };
+    }
+
+    let format = args.format.unwrap_or(PublicFormat::Raw);
+    let mut reply = connection.call(|client_id, request_id| Request::ExportPublicKey {
+        client_id,
+        request_id,
+        deadline: None,
+        key_id: args.key,
+        public_key: &mut buffer,
+        format: format.into(),
+    })?;
+    let Response::ExportPublicKey { public_key, .. } = reply.response()? else {
+        return Err(Error::UnexpectedResponse);
+    };
+    match format {
+        PublicFormat::Pem => write_text(public_key, args.output.as_deref()),
+        _ => write_output(public_key, args.output.as_deref()),
+    }
+}
+
+fn get_random(connection: &mut Connection, output: &mut [u8]) -> Result<(), Error> {
+    let size = output.len();
+    let mut reply = connection.call(|client_id, request_id| Request::GetRandom {
+        client_id,
+        request_id,
+        deadline: None,
+        output: &mut output[..],
+    })?;
+    let Response::GetRandom { data, .. } = reply.response()? else {
+        return Err(Error::UnexpectedResponse);
+    };
+    if data.len() != size {
+        return Err(Error::UnexpectedResponse);
+    }
+    output.copy_from_slice(data);
+    Ok(())
+}
+
+fn encrypt(connection: &mut Connection, args: CipherArgs) -> Result<(), Error> {
+    let mut buffer = read_data(&args.input)?;
+    let aad = match &args.aad {
+        Some(path) => read_data(path)?,
+        None => Vec::new(),
+    };
+    let mut nonce = vec![0u8; args.algorithm.nonce_size()];
+    get_random(connection, &mut nonce)?;
+    let mut output = nonce.clone();
+    match args.algorithm {
+        Cipher::AesGcm | Cipher::ChachaPoly => {
+            let mut tag = [0u8; TAG_SIZE];
+            let mut reply = connection.call(|client_id, request_id| match args.algorithm {
+                Cipher::AesGcm => Request::EncryptAesGcm {
+                    client_id,
+                    request_id,
+                    deadline: None,
+                    key_id: args.key,
+                    iv: &nonce,
+                    buffer: &mut buffer,
+                    aad: &aad,
+                    tag: &mut tag,
+                },
+                _ => Request::EncryptChaChaPoly {
+                    client_id,
+                    request_id,
+                    deadline: None,
+                    key_id: args.key,
+                    nonce: &nonce,
+                    buffer: &mut buffer,
+                    aad: &aad,
+                    tag: &mut tag,
+                },
+            })?;
+            let (Response::EncryptAesGcm { buffer, tag, .. }
+            | Response::EncryptChaChaPoly { buffer, tag, .. }) = reply.response()?
+            else {
+                return Err(Error::UnexpectedResponse);
+            };
+            output.extend_from_slice(buffer);
+            output.extend_from_slice(tag);
+        }
+        Cipher::AesCbc => {
+            if args.aad.is_some() {
+                return Err(Error::Usage(
+                    "AES-CBC does not support additional data".to_string(),
+                ));
+            }
+            let plaintext_size = buffer.len();
+            buffer.resize((plaintext_size / AES_BLOCK_SIZE + 1) * AES_BLOCK_SIZE, 0);
+            let mut reply = connection.call(|client_id, request_id| Request::EncryptAesCbc {
+                client_id,
+                request_id,
+                deadline: None,
+                key_id: args.key,
+                iv: &nonce,
+                buffer: &mut buffer,
+                plaintext_size,
+                padding: Padding::Pkcs7,
+            })?;
+            let Response::EncryptAesCbc { buffer, .. } = reply.response()? else {
+                return Err(Error::UnexpectedResponse);
+            };
+            output.extend_from_slice(buffer);
+        }
+    }
+    write_output(&output, args.output.as_deref())
+}
+
+fn decrypt(connection: &mut Connection, args: CipherArgs) -> Result<(), Error> {
+    let input = read_data(&args.input)?;
+    let aad = match &args.aad {
+        Some(path) => read_data(path)?,
+        None => Vec::new(),
+    };
+    let nonce_size = args.algorithm.nonce_size();
+    let tag_size = match args.algorithm {
+        Cipher::AesGcm | Cipher::ChachaPoly => TAG_SIZE,
+        Cipher::AesCbc => 0,
+    };
+    if input.len() < nonce_size + tag_size {
+        return Err(Error::Usage("input is too short".to_string()));
+    }
+    let (nonce, rest) = input.split_at(nonce_size);
+    let (ciphertext, tag) = rest.split_at(rest.len() - tag_size);
+    let mut buffer = ciphertext.to_vec();
+    let mut reply = connection.call(|client_id, request_id| match args.algorithm {
+        Cipher::AesGcm => Request::DecryptAesGcm {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: args.key,
+            iv: nonce,
+            buffer: &mut buffer,
+            aad: &aad,
+            tag,
+        },
+        Cipher::ChachaPoly => Request::DecryptChaChaPoly {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: args.key,
+            nonce,
+            buffer: &mut buffer,
+            aad: &aad,
+            tag,
+        },
+        Cipher::AesCbc => Request::DecryptAesCbc {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: args.key,
+            iv: nonce,
+            buffer: &mut buffer,
+            padding: Padding::Pkcs7,
+        },
+    })?;
+    match reply.response()? {
+        Response::DecryptAesGcm {
+            buffer: plaintext, ..
+        }
+        | Response::DecryptChaChaPoly {
+            buffer: plaintext, ..
+        }
+        | Response::DecryptAesCbc { plaintext, .. } => {
+            write_output(plaintext, args.output.as_deref())
+        }
+        _ => Err(Error::UnexpectedResponse),
+    }
+}
+
+impl SignatureArgs {
+    fn key_pair_info(&self, connection: &mut Connection) -> Result<KeyInfo, Error> {
+        let info = key_info(connection, self.key)?;
+        if !info.ty.is_asymmetric() {
+            return Err(Error::Usage(format!(
+                "key {} is not a key pair",
+                self.key.0
+            )));
+        }
+        Ok(info)
+    }
+
+    fn signature_format(&self) -> SignatureFormat {
+        if self.der {
+            SignatureFormat::Der
+        } else {
+            SignatureFormat::Raw
+        }
+    }
+}
+
+fn sign(connection: &mut Connection, args: SignArgs) -> Result<(), Error> {
+    let SignArgs { signature, output } = args;
+    let info = signature.key_pair_info(connection)?;
+    let hash_algorithm = signature.hash.map(Hash::algorithm).transpose()?;
+    let message = read_data(&signature.input)?;
+    let mut signature_size = 2 * info.ty.private_key_size();
+    if signature.der {
+        signature_size += DER_SIGNATURE_OVERHEAD;
+    }
+    let mut buffer = vec![0u8; signature_size];
+    let mut reply = connection.call(|client_id, request_id| Request::Sign {
+        client_id,
+        request_id,
+        deadline: None,
+        key_id: signature.key,
+        message: &message,
+        prehashed: signature.prehashed,
+        hash_algorithm,
+        signature_format: signature.signature_format(),
+        signature: &mut buffer,
+    })?;
+    let Response::Sign { signature, .. } = reply.response()? else {
+        return Err(Error::UnexpectedResponse);
+    };
+    write_output(signature, output.as_deref())
+}
+
+fn verify(connection: &mut Connection, args: VerifyArgs) -> Result<(), Error> {
+    let VerifyArgs {
+        signature_args,
+        signature,
+    } = args;
+    signature_args.key_pair_info(connection)?;
+    let hash_algorithm = signature_args.hash.map(Hash::algorithm).transpose()?;
+    let message = read_data(&signature_args.input)?;
+    let signature = read_encoded(&signature)?.data;
+    let mut reply = connection.call(|client_id, request_id| Request::Verify {
+        client_id,
+        request_id,
+        deadline: None,
+        key_id: signature_args.key,
+        message: &message,
+        prehashed: signature_args.prehashed,
+        hash_algorithm,
+        signature_format: signature_args.signature_format(),
+        signature: &signature,
+    })?;
+    let Response::Verify { verified, .. } = reply.response()? else {
+        return Err(Error::UnexpectedResponse);
+    };
+    report_verification(verified)
+}
+
+fn hmac(connection: &mut Connection, args: HmacArgs) -> Result<(), Error> {
+    let hash_algorithm = args.hash.algorithm()?;
+    let message = read_data(&args.input)?;
+    if let Some(path) = &args.verify {
+        let tag = read_encoded(path)?.data;
+        let mut reply = connection.call(|client_id, request_id| Request::VerifyHmac {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: args.key,
+            hash_algorithm,
+            message: &message,
+            tag: &tag,
+        })?;
+        let Response::VerifyHmac { verified, .. } = reply.response()? else {
+            return Err(Error::UnexpectedResponse);
+        };
+        return report_verification(verified);
+    }
+    let mut tag = vec![0u8; hash_algorithm.digest_size()];
+    let mut reply = connection.call(|client_id, request_id| Request::CalculateHmac {
+        client_id,
+        request_id,
+        deadline: None,
+        key_id: args.key,
+        hash_algorithm,
+        message: &message,
+        tag: &mut tag,
+    })?;
+    let Response::CalculateHmac { tag, .. } = reply.response()? else {
+        return Err(Error::UnexpectedResponse);
+    };
+    write_output(tag, args.output.as_deref())
+}
+
+fn cmac(connection: &mut Connection, args: CmacArgs) -> Result<(), Error> {
+    let message = read_data(&args.input)?;
+    if let Some(path) = &args.verify {
+        let tag = read_encoded(path)?.data;
+        let mut reply = connection.call(|client_id, request_id| Request::VerifyAesCmac {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: args.key,
+            message: &message,
+            tag: &tag,
+        })?;
+        let Response::VerifyAesCmac { verified, .. } = reply.response()? else {
+            return Err(Error::UnexpectedResponse);
+        };
+        return report_verification(verified);
+    }
+    let mut tag = [0u8; TAG_SIZE];
+    let mut reply = connection.call(|client_id, request_id| Request::CalculateAesCmac {
+        client_id,
+        request_id,
+        deadline: None,
+        key_id: args.key,
+        message: &message,
+        tag: &mut tag,
+    })?;
+    let Response::CalculateAesCmac { tag, .. } = reply.response()? else {
+        return Err(Error::UnexpectedResponse);
+    };
+    write_output(tag, args.output.as_deref())
+}
+
+fn report_verification(verified: bool) -> Result<(), Error> {
+    if !verified {
+        return Err(Error::VerificationFailed);
+    }
+    println!("OK");
+    Ok(())
+}
@@ -0,0 +1,97 @@
+//! Sequential request/response exchange with the daemon.
+
+use displaydoc::Display;
+use heimlig::common::jobs::{self, ClientId, Request, RequestId, Response};
+use heimlig::integration::wire::{self, read_frame, write_frame, FrameKind};
+use std::io;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+#[derive(Debug, Display)]
+pub enum Error {
+    /// I/O error: {0}
+    Io(io::Error),
+    /// Invalid frame: {0}
+    Wire(wire::Error),
+    /// Request failed: {0}
+    Heimlig(jobs::Error),
+    /// Request was cancelled by the daemon
+    Cancelled,
+    /// Unexpected response from the daemon
+    UnexpectedResponse,
+    /// {0}
+    Usage(String),
+    /// Verification failed
+    VerificationFailed,
+    /// {0} command(s) of the batch failed
+    BatchFailed(usize),
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<wire::Error> for Error {
+    fn from(error: wire::Error) -> Self {
+        Error::Wire(error)
+    }
+}
+
+/// Connection that sends one request at a time and waits for its response.
+pub struct Connection {
+    stream: UnixStream,
+    next_request_id: RequestId,
+}
+
+impl Connection {
+    pub fn open(socket_path: &Path) -> Result<Self, Error> {
+        Ok(Connection {
+            stream: UnixStream::connect(socket_path)?,
+            next_request_id: RequestId(0),
+        })
+    }
+
+    /// Send the request built by `build` and wait for its response.
+    ///
+    /// The client ID passed to `build` is a placeholder that is replaced by the daemon.
+    pub fn call<'data>(
+        &mut self,
+        build: impl FnOnce(ClientId, RequestId) -> Request<'data>,
+    ) -> Result<Reply, Error> {
+        let request_id = self.next_request_id;
+        self.next_request_id.increment();
+        let request = build(ClientId(0), request_id);
+        let mut payload = vec![0u8; wire::request_size(&request)?];
+        wire::encode_request(&request, &mut payload)?;
+        write_frame(&mut self.stream, FrameKind::Request, &payload)?;
+        let payload = read_frame(&mut self.stream, FrameKind::Response)?
+            .ok_or(io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        Ok(Reply {
+            request_id,
+            payload,
+        })
+    }
+}
+
+/// Encoded response to a request sent with [Connection::call].
+pub struct Reply {
+    request_id: RequestId,
+    payload: Vec<u8>,
+}
+
+impl Reply {
+    /// Decode the response. Error responses are returned as [Error::Heimlig].
+    pub fn response(&mut self) -> Result<Response<'_>, Error> {
+        let response = wire::decode_response(&mut self.payload)?;
+        if response.get_request_id() != self.request_id {
+            return Err(Error::UnexpectedResponse);
+        }
+        match response {
+            Response::Error { error, .. } => Err(Error::Heimlig(error)),
+            Response::Cancelled { .. } => Err(Error::Cancelled),
+            response => Ok(response),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Reading and writing of input and output files.
+//!
+//! Messages and plaintexts are always processed as raw bytes. Key material, signatures and tags
+//! can additionally be given as hex or PEM files. Files that are neither are used as they are,
+//! which covers DER documents and raw binary keys.
+
+use crate::connection::Error;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Contents of a file that can be encoded as hex, PEM or raw bytes.
+pub struct Encoded {
+    /// Type label of a PEM document, e.g. `PRIVATE KEY`. `None` for hex and raw files.
+    pub label: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Read a file as raw bytes. `-` reads from stdin.
+pub fn read_data(path: &Path) -> Result<Vec<u8>, Error> {
+    if path == Path::new("-") {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data)?;
+        return Ok(data);
+    }
+    Ok(fs::read(path)?)
+}
+
+/// Read a hex, PEM or raw binary file. The encoding is detected from the file contents.
+pub fn read_encoded(path: &Path) -> Result<Encoded, Error> {
+    let data = read_data(path)?;
+    let Ok(text) = std::str::from_utf8(&data) else {
+        return Ok(Encoded { label: None, data });
+    };
+    let text = text.trim();
+    if text.starts_with("-----BEGIN ") {
+        let (label, data) = pem_rfc7468::decode_vec(text.as_bytes())
+            .map_err(|e| Error::Usage(format!("{}: invalid PEM file: {e}", path.display())))?;
+        return Ok(Encoded {
+            label: Some(label.to_string()),
+            data,
+        });
+    }
+    if is_hex(text) {
+        let digits: String = text.split_whitespace().collect();
+        if let Ok(data) = hex::decode(digits) {
+            return Ok(Encoded { label: None, data });
+        }
+    }
+    Ok(Encoded { label: None, data })
+}
+
+fn is_hex(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_hexdigit() || c.is_ascii_whitespace())
+}
+
+/// Write binary output to a file or as hex to stdout if no file is given.
+pub fn write_output(data: &[u8], output: Option<&Path>) -> Result<(), Error> {
+    match output {
+        Some(path) => fs::write(path, data)?,
+        None => writeln!(io::stdout(), "{}", hex::encode(data))?,
+    }
+    Ok(())
+}
+
+/// Write textual output like PEM documents to a file or to stdout if no file is given.
+pub fn write_text(text: &[u8], output: Option<&Path>) -> Result<(), Error> {
+    match output {
+        Some(path) => fs::write(path, text)?,
+        None => io::stdout().write_all(text)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,30 @@
+mod commands;
+mod connection;
+mod encoding;
+
+use clap::Parser;
+use commands::{Command, Session};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Provision and test Heimlig through the Linux daemon
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Path of the Unix socket of the daemon
+    #[arg(short, long, global = true, default_value = "/tmp/heimlig.sock")]
+    socket: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match Session::new(args.socket).run(args.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,49 @@
+fn main() {
+    // Generate plain C header of the library. Plain C enums share one namespace, so variants are
+    // prefixed with the enum name.
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let include_dir = format!("{crate_dir}/target");
+    let mut config = cbindgen::Config::default();
+    config.enumeration.prefix_with_name = true;
+    config.usize_is_size_t = true;
+    config.parse.parse_deps = true;
+    config.parse.include = Some(vec!["heimlig".into()]);
+    // Detail codes of the error categories in status codes
+    config.export.include = [
+        "CryptoErrorRaw",
+        "KeyStoreErrorRaw",
+        "SheErrorRaw",
+        "LimitRaw",
+    ]
+    .map(String::from)
+    .to_vec();
+    config.export.exclude = vec!["KeyType_MAX_SYMMETRIC_KEY_SIZE".into()];
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_crate(&crate_dir)
+        .with_language(cbindgen::Language::C)
+        .with_no_includes()
+        .with_sys_include("stdbool.h")
+        .with_sys_include("stddef.h")
+        .with_sys_include("stdint.h")
+        .with_include_guard("HEIMLIG_CLIENT_H")
+        .generate()
+        .expect("Unable to generate bindings")
+        .write_to_file(format!("{include_dir}/HeimligClient.h"));
+
+    // C test programs run by the test harness in `tests/c_client.rs`. The harness only runs on the
+    // host.
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=tests/c");
+    if std::env::var("TARGET").unwrap() != std::env::var("HOST").unwrap() {
+        return;
+    }
+    cc::Build::new()
+        .file("tests/c/client_test.c")
+        .include(&include_dir)
+        .warnings_into_errors(true)
+        .cargo_metadata(false)
+        .compile("heimlig_client_c_tests");
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    println!("cargo:rustc-link-search=native={out_dir}");
+}
@@ -0,0 +1,249 @@
+//! Client attached to the request and response rings of the HSM.
+//!
+//! The client does not need an executor. It polls its ends of the rings and calls the `wait`
+//! function of its [HeimligNotifier] while the request ring is full or no response is available.
+//! It does not allocate either: C code provides the storage of the [HeimligClient].
+
+use crate::status::{
+    from_job_error, HeimligStatus, HEIMLIG_CANCELLED, HEIMLIG_CLIENT_POISONED,
+    HEIMLIG_INVALID_ARGUMENT, HEIMLIG_INVALID_RESPONSE, HEIMLIG_OK, HEIMLIG_TRANSPORT_ERROR,
+};
+use core::ffi::c_void;
+use core::mem::{align_of, size_of};
+use heimlig::common::jobs::{ClientId, Request, RequestId, Response};
+use heimlig::integration::raw_jobs::{RequestBuffers, RequestRaw, ResponseRaw};
+use heimlig::integration::shared_ring::{self, Doorbell, RingConsumer, RingProducer, Signal};
+
+/// Notification of the HSM. All functions get `context` as their first argument.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct HeimligNotifier {
+    pub context: *mut c_void,
+    /// Notify the HSM that requests were pushed or responses were popped, e.g. by raising an
+    /// inter-core interrupt.
+    pub ring: Option<unsafe extern "C" fn(context: *mut c_void)>,
+    /// Block until the HSM might have pushed a response or popped a request, e.g. by spinning, with
+    /// `WFE` or on an RTOS semaphore. Returning early is allowed.
+    pub wait: Option<unsafe extern "C" fn(context: *mut c_void)>,
+}
+
+/// Configuration of a client passed to [heimlig_client_init].
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct HeimligClientConfig {
+    /// ID of the client at the core. Sent with every request.
+    pub client_id: u32,
+    /// Ring of raw requests formatted by the HSM. Aligned to 64 bytes.
+    pub requests: *mut u8,
+    pub requests_size: usize,
+    /// Ring of raw responses formatted by the HSM. Aligned to 64 bytes.
+    pub responses: *mut u8,
+    pub responses_size: usize,
+    pub notifier: HeimligNotifier,
+}
+
+/// [HeimligNotifier] whose functions were checked to be present
+#[derive(Clone, Copy)]
+struct Notifier {
+    context: *mut c_void,
+    ring: unsafe extern "C" fn(context: *mut c_void),
+    wait: unsafe extern "C" fn(context: *mut c_void),
+}
+
+impl Notifier {
+    fn wait(&self) {
+        // SAFETY: The creator of the client guarantees that the function is valid.
+        unsafe { (self.wait)(self.context) }
+    }
+}
+
+impl Doorbell for Notifier {
+    fn ring(&self) {
+        // SAFETY: The creator of the client guarantees that the function is valid.
+        unsafe { (self.ring)(self.context) }
+    }
+}
+
+/// Only the async ends of a ring wait on their signal, which the client does not use.
+static UNUSED_SIGNAL: Signal = Signal::new();
+
+/// Number of 64-bit words of the storage of a [HeimligClient].
+pub const HEIMLIG_CLIENT_STORAGE_WORDS: usize = 32;
+
+/// Storage of a client of the HSM, e.g. a static or a local variable of C code. Initialized with
+/// [heimlig_client_init]. The contents are private to the library.
+///
+/// A client that failed while the HSM still processed its request is poisoned: the HSM may still
+/// access the buffers of that request, so they stay owned by the HSM and must not be reused. All
+/// further requests of the client fail with [HEIMLIG_CLIENT_POISONED].
+#[repr(C)]
+pub struct HeimligClient {
+    storage: [u64; HEIMLIG_CLIENT_STORAGE_WORDS],
+}
+
+/// Client behind the storage of a [HeimligClient]
+pub(crate) struct Client {
+    client_id: ClientId,
+    request_id: RequestId,
+    notifier: Notifier,
+    requests: RingProducer<'static, RequestRaw, Notifier>,
+    responses: RingConsumer<'static, ResponseRaw, Notifier>,
+    /// Set if a request was pushed but its response could not be received
+    poisoned: bool,
+}
+
+const _: () = assert!(
+    size_of::<Client>() <= size_of::<HeimligClient>()
+        && align_of::<Client>() <= align_of::<HeimligClient>()
+);
+
+impl Client {
+    /// Client and request ID of the next request.
+    pub(crate) fn next_ids(&mut self) -> (ClientId, RequestId) {
+        let request_id = self.request_id;
+        self.request_id.increment();
+        (self.client_id, request_id)
+    }
+
+    /// Send `request` and block until its response arrives. Error responses are returned as their
+    /// status code.
+    ///
+    /// If the response cannot be received after the request was pushed, the HSM may still access
+    /// the buffers of the request. The client is poisoned in this case and rejects all further
+    /// requests, so that the caller knows not to reuse the buffers.
+    pub(crate) fn call<'data>(
+        &mut self,
+        request: Request<'data>,
+    ) -> Result<Response<'data>, HeimligStatus> {
+        if self.poisoned {
+            return Err(HEIMLIG_CLIENT_POISONED);
+        }
+        let request_id = request.get_request_id();
+        let buffers = RequestBuffers::new(&request);
+        // Buffers larger than the raw size fields cannot be sent
+        let mut request: RequestRaw = request.try_into().map_err(|_| HEIMLIG_INVALID_ARGUMENT)?;
+        loop {
+            match self.requests.try_push(request) {
+                Ok(Ok(())) => break,
+                Ok(Err(rejected)) => {
+                    request = rejected;
+                    self.notifier.wait();
+                }
+                // The request was not pushed, so the HSM does not know its buffers
+                Err(_) => return Err(HEIMLIG_TRANSPORT_ERROR),
+            }
+        }
+        let response = loop {
+            match self.responses.try_pop() {
+                Ok(Some(response)) if response.request_id == request_id.0 => break response,
+                // Only one request is pending at a time, so responses of other requests are
+                // bogus
+                Ok(Some(_)) => {}
+                Ok(None) => self.notifier.wait(),
+                Err(e) => {
+                    self.poisoned = true;
+                    return Err(match e {
+                        shared_ring::Error::InvalidItem(_) => HEIMLIG_INVALID_RESPONSE,
+                        _ => HEIMLIG_TRANSPORT_ERROR,
+                    });
+                }
+            }
+        };
+        // The HSM may only answer with the buffers the client passed with its request
+        match response.verify(&buffers) {
+            Ok(Response::Error { error, .. }) => Err(from_job_error(error)),
+            Ok(Response::Cancelled { .. }) => Err(HEIMLIG_CANCELLED),
+            Ok(response) => Ok(response),
+            Err(_) => Err(HEIMLIG_INVALID_RESPONSE),
+        }
+    }
+}
+
+/// Initialize the storage `client` and attach the client to the rings of the HSM. The client
+/// holds no resources besides its storage, which can be reused once no request is pending.
+///
+/// returns: [HEIMLIG_OK], [HEIMLIG_INVALID_ARGUMENT] or [HEIMLIG_TRANSPORT_ERROR] if a ring is
+/// not formatted.
+///
+/// # Safety
+///
+/// All pointers must be null or valid. The rings must stay valid as long as the client is used and
+/// must not be attached by other clients. The functions of the notifier must be safe to call with
+/// its context as long as the client is used.
+#[no_mangle]
+pub unsafe extern "C" fn heimlig_client_init(
+    config: *const HeimligClientConfig,
+    client: *mut HeimligClient,
+) -> HeimligStatus {
+    // SAFETY: The caller guarantees that the pointer is null or valid.
+    let Some(config) = (unsafe { config.as_ref() }) else {
+        return HEIMLIG_INVALID_ARGUMENT;
+    };
+    let HeimligNotifier {
+        context,
+        ring: Some(ring),
+        wait: Some(wait),
+    } = config.notifier
+    else {
+        return HEIMLIG_INVALID_ARGUMENT;
+    };
+    if client.is_null() {
+        return HEIMLIG_INVALID_ARGUMENT;
+    }
+    let notifier = Notifier {
+        context,
+        ring,
+        wait,
+    };
+
+    // SAFETY: The caller guarantees that the rings stay valid and are only used by this client.
+    let ends = unsafe {
+        RingProducer::attach(
+            config.requests,
+            config.requests_size,
+            notifier,
+            &UNUSED_SIGNAL,
+        )
+        .and_then(|requests| {
+            RingConsumer::attach(
+                config.responses,
+                config.responses_size,
+                notifier,
+                &UNUSED_SIGNAL,
+            )
+            .map(|responses| (requests, responses))
+        })
+    };
+    let (requests, responses) = match ends {
+        Ok(ends) => ends,
+        Err(shared_ring::Error::InvalidAlignment | shared_ring::Error::InvalidSize) => {
+            return HEIMLIG_INVALID_ARGUMENT
+        }
+        Err(_) => return HEIMLIG_TRANSPORT_ERROR,
+    };
+    let new = Client {
+        client_id: ClientId(config.client_id),
+        request_id: RequestId::default(),
+        notifier,
+        requests,
+        responses,
+        poisoned: false,
+    };
+    // SAFETY: Checked to be non-null above. The caller guarantees that it is valid. The storage is
+    // large and aligned enough for a `Client`.
+    unsafe { client.cast::<Client>().write(new) };
+    HEIMLIG_OK
+}
+
+/// Client behind a pointer passed by C code.
+///
+/// # Safety
+///
+/// `client` must be null or a client initialized with [heimlig_client_init] that is not used
+/// anywhere else during `'a`.
+pub(crate) unsafe fn client_mut<'a>(
+    client: *mut HeimligClient,
+) -> Result<&'a mut Client, HeimligStatus> {
+    // SAFETY: Guaranteed by the caller. Initialized clients store a `Client`.
+    unsafe { client.cast::<Client>().as_mut() }.ok_or(HEIMLIG_INVALID_ARGUMENT)
+}
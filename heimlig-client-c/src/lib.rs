@@ -0,0 +1,34 @@
+//! C client library of Heimlig.
+//!
+//! C and C++ host applications link the static library and include the generated plain-C header
+//! `target/HeimligClient.h`. The library is `no_std` and does not allocate, so it also links into
+//! bare-metal hosts. Hosted platforms enable the `std` feature instead, which provides the
+//! critical sections and the panic handler of the standard library.
+//!
+//! A [HeimligClient](client::HeimligClient) attaches to two rings of
+//! [heimlig::integration::shared_ring] that the HSM formatted in shared memory: one for raw
+//! requests and one for raw responses. Every request function builds the raw request, pushes it
+//! into the request ring and blocks until the response arrives. Results are reported as
+//! [HeimligStatus](status::HeimligStatus) codes.
+//!
+//! Requests without a function of their own can still be sent with the raw types of
+//! `RawJobs.h` and `heimlig_call_blocking`.
+
+#![no_std]
+
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod client;
+pub mod requests;
+pub mod status;
+
+/// Bare-metal hosts have no way to report a panic of the library. The library does not panic on
+/// invalid input, so this only guards against bugs.
+#[cfg(not(feature = "std"))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        core::hint::spin_loop();
+    }
+}
@@ -0,0 +1,878 @@
+//! Blocking request functions.
+//!
+//! Every function sends a single request through the client and blocks until its response arrives.
+//! Only one request of a client may be pending at a time, so a client must not be used by several
+//! threads at once.
+//!
+//! Buffers are passed as pointer and size pairs. The pointer of an empty buffer may be null. The
+//! HSM accesses the buffers while the function blocks, so they have to be located in memory that
+//! the HSM can access. Buffers the HSM writes to must not overlap with other buffers of the same
+//! request.
+
+use crate::client::{client_mut, HeimligClient};
+use crate::status::{
+    from_result, HeimligStatus, HEIMLIG_INVALID_ARGUMENT, HEIMLIG_UNEXPECTED_RESPONSE,
+};
+use heimlig::common::jobs::{HashAlgorithm, Padding, Request, Response, SignatureFormat};
+use heimlig::hsm::keystore::KeyId;
+use heimlig::integration::raw_jobs;
+
+pub const HEIMLIG_SHA2_256: u32 = 0;
+pub const HEIMLIG_SHA2_384: u32 = 1;
+pub const HEIMLIG_SHA2_512: u32 = 2;
+pub const HEIMLIG_SHA3_256: u32 = 3;
+pub const HEIMLIG_SHA3_384: u32 = 4;
+pub const HEIMLIG_SHA3_512: u32 = 5;
+/// Selects the default hash algorithm of the curve in [heimlig_sign] and [heimlig_verify].
+pub const HEIMLIG_CURVE_DEFAULT_HASH: u32 = 0xFFFF_FFFF;
+
+pub const HEIMLIG_PKCS7: u32 = 0;
+pub const HEIMLIG_NO_PADDING: u32 = 1;
+
+/// Concatenation of the big-endian `r` and `s` values.
+pub const HEIMLIG_SIGNATURE_RAW: u32 = 0;
+/// ASN.1 DER encoded `Ecdsa-Sig-Value`.
+pub const HEIMLIG_SIGNATURE_DER: u32 = 1;
+
+// cbindgen only exports constants of this crate, so the codes of the raw ABI are repeated here.
+const _: () = assert!(
+    HEIMLIG_SHA2_256 == raw_jobs::SHA2_256
+        && HEIMLIG_SHA2_384 == raw_jobs::SHA2_384
+        && HEIMLIG_SHA2_512 == raw_jobs::SHA2_512
+        && HEIMLIG_SHA3_256 == raw_jobs::SHA3_256
+        && HEIMLIG_SHA3_384 == raw_jobs::SHA3_384
+        && HEIMLIG_SHA3_512 == raw_jobs::SHA3_512
+        && HEIMLIG_CURVE_DEFAULT_HASH == raw_jobs::CURVE_DEFAULT_HASH
+        && HEIMLIG_PKCS7 == raw_jobs::PKCS7
+        && HEIMLIG_NO_PADDING == raw_jobs::NO_PADDING
+        && HEIMLIG_SIGNATURE_RAW == raw_jobs::SIGNATURE_RAW
+        && HEIMLIG_SIGNATURE_DER == raw_jobs::SIGNATURE_DER
+);
+
+fn hash_algorithm(code: u32) -> Result<HashAlgorithm, HeimligStatus> {
+    HashAlgorithm::try_from(code).map_err(|_| HEIMLIG_INVALID_ARGUMENT)
+}
+
+/// Hash algorithm of signatures. `None` selects the default of the curve.
+fn signature_hash_algorithm(code: u32) -> Result<Option<HashAlgorithm>, HeimligStatus> {
+    match code {
+        HEIMLIG_CURVE_DEFAULT_HASH => Ok(None),
+        code => hash_algorithm(code).map(Some),
+    }
+}
+
+fn padding(code: u32) -> Result<Padding, HeimligStatus> {
+    Padding::try_from(code).map_err(|_| HEIMLIG_INVALID_ARGUMENT)
+}
+
+fn signature_format(code: u32) -> Result<SignatureFormat, HeimligStatus> {
+    SignatureFormat::try_from(code).map_err(|_| HEIMLIG_INVALID_ARGUMENT)
+}
+
+/// Buffer the HSM reads from.
+///
+/// # Safety
+///
+/// `data` must be null or point to `size` readable bytes that are not written during `'a`.
+unsafe fn slice<'a>(data: *const u8, size: usize) -> Result<&'a [u8], HeimligStatus> {
+    if size > u32::MAX as usize || (data.is_null() && size != 0) {
+        return Err(HEIMLIG_INVALID_ARGUMENT);
+    }
+    if size == 0 {
+        return Ok(&[]);
+    }
+    // SAFETY: Guaranteed by the caller
+    Ok(unsafe { core::slice::from_raw_parts(data, size) })
+}
+
+/// Buffer the HSM writes to.
+///
+/// # Safety
+///
+/// `data` must be null or point to `size` writable bytes that are not accessed otherwise during
+/// `'a`.
+unsafe fn slice_mut<'a>(data: *mut u8, size: usize) -> Result<&'a mut [u8], HeimligStatus> {
+    if size > u32::MAX as usize || (data.is_null() && size != 0) {
+        return Err(HEIMLIG_INVALID_ARGUMENT);
+    }
+    if size == 0 {
+        return Ok(&mut []);
+    }
+    // SAFETY: Guaranteed by the caller
+    Ok(unsafe { core::slice::from_raw_parts_mut(data, size) })
+}
+
+/// Fill `output` with random bytes.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+#[no_mangle]
+pub unsafe extern "C" fn heimlig_get_random(
+    client: *mut HeimligClient,
+    output: *mut u8,
+    output_size: usize,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, output) = unsafe { (client_mut(client)?, slice_mut(output, output_size)?) };
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::GetRandom {
+            client_id,
+            request_id,
+            deadline: None,
+            output,
+        })? {
+            Response::GetRandom { .. } => Ok(()),
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Generate a random symmetric key in the key slot `key_id`.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn heimlig_generate_symmetric_key(
+    client: *mut HeimligClient,
+    key_id: u32,
+    overwrite: bool,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let client = unsafe { client_mut(client)? };
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::GenerateSymmetricKey {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: KeyId(key_id),
+            overwrite,
+        })? {
+            Response::GenerateSymmetricKey { .. } => Ok(()),
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Generate a random key pair in the key slot `key_id`. The curve is given by the type of the slot.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn heimlig_generate_key_pair(
+    client: *mut HeimligClient,
+    key_id: u32,
+    overwrite: bool,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let client = unsafe { client_mut(client)? };
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::GenerateKeyPair {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: KeyId(key_id),
+            overwrite,
+        })? {
+            Response::GenerateKeyPair { .. } => Ok(()),
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Store `key` in the key slot `key_id`.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+#[no_mangle]
+pub unsafe extern "C" fn heimlig_import_symmetric_key(
+    client: *mut HeimligClient,
+    key_id: u32,
+    key: *const u8,
+    key_size: usize,
+    overwrite: bool,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, data) = unsafe { (client_mut(client)?, slice(key, key_size)?) };
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::ImportSymmetricKey {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: KeyId(key_id),
+            data,
+            overwrite,
+        })? {
+            Response::ImportSymmetricKey { .. } => Ok(()),
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Check whether the key slot `key_id` contains a key.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. `is_available` must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn heimlig_is_key_available(
+    client: *mut HeimligClient,
+    key_id: u32,
+    is_available: *mut bool,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, result) = unsafe { (client_mut(client)?, is_available.as_mut()) };
+        let result = result.ok_or(HEIMLIG_INVALID_ARGUMENT)?;
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::IsKeyAvailable {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: KeyId(key_id),
+        })? {
+            Response::IsKeyAvailable { is_available, .. } => {
+                *result = is_available;
+                Ok(())
+            }
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Encrypt `buffer` in place with AES-GCM and the key in slot `key_id`. The authentication tag is
+/// written to `tag`.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn heimlig_encrypt_aes_gcm(
+    client: *mut HeimligClient,
+    key_id: u32,
+    iv: *const u8,
+    iv_size: usize,
+    aad: *const u8,
+    aad_size: usize,
+    buffer: *mut u8,
+    buffer_size: usize,
+    tag: *mut u8,
+    tag_size: usize,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, iv, aad, buffer, tag) = unsafe {
+            (
+                client_mut(client)?,
+                slice(iv, iv_size)?,
+                slice(aad, aad_size)?,
+                slice_mut(buffer, buffer_size)?,
+                slice_mut(tag, tag_size)?,
+            )
+        };
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::EncryptAesGcm {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: KeyId(key_id),
+            iv,
+            buffer,
+            aad,
+            tag,
+        })? {
+            Response::EncryptAesGcm { .. } => Ok(()),
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Decrypt `buffer` in place with AES-GCM and the key in slot `key_id` after checking the
+/// authentication `tag`.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn heimlig_decrypt_aes_gcm(
+    client: *mut HeimligClient,
+    key_id: u32,
+    iv: *const u8,
+    iv_size: usize,
+    aad: *const u8,
+    aad_size: usize,
+    buffer: *mut u8,
+    buffer_size: usize,
+    tag: *const u8,
+    tag_size: usize,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, iv, aad, buffer, tag) = unsafe {
+            (
+                client_mut(client)?,
+                slice(iv, iv_size)?,
+                slice(aad, aad_size)?,
+                slice_mut(buffer, buffer_size)?,
+                slice(tag, tag_size)?,
+            )
+        };
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::DecryptAesGcm {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: KeyId(key_id),
+            iv,
+            buffer,
+            aad,
+            tag,
+        })? {
+            Response::DecryptAesGcm { .. } => Ok(()),
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Encrypt `buffer` in place with AES-GCM and the given `key`. The authentication tag is written
+/// to `tag`.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn heimlig_encrypt_aes_gcm_external_key(
+    client: *mut HeimligClient,
+    key: *const u8,
+    key_size: usize,
+    iv: *const u8,
+    iv_size: usize,
+    aad: *const u8,
+    aad_size: usize,
+    buffer: *mut u8,
+    buffer_size: usize,
+    tag: *mut u8,
+    tag_size: usize,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, key, iv, aad, buffer, tag) = unsafe {
+            (
+                client_mut(client)?,
+                slice(key, key_size)?,
+                slice(iv, iv_size)?,
+                slice(aad, aad_size)?,
+                slice_mut(buffer, buffer_size)?,
+                slice_mut(tag, tag_size)?,
+            )
+        };
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::EncryptAesGcmExternalKey {
+            client_id,
+            request_id,
+            deadline: None,
+            key,
+            iv,
+            buffer,
+            aad,
+            tag,
+        })? {
+            Response::EncryptAesGcm { .. } => Ok(()),
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Decrypt `buffer` in place with AES-GCM and the given `key` after checking the authentication
+/// `tag`.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn heimlig_decrypt_aes_gcm_external_key(
+    client: *mut HeimligClient,
+    key: *const u8,
+    key_size: usize,
+    iv: *const u8,
+    iv_size: usize,
+    aad: *const u8,
+    aad_size: usize,
+    buffer: *mut u8,
+    buffer_size: usize,
+    tag: *const u8,
+    tag_size: usize,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, key, iv, aad, buffer, tag) = unsafe {
+            (
+                client_mut(client)?,
+                slice(key, key_size)?,
+                slice(iv, iv_size)?,
+                slice(aad, aad_size)?,
+                slice_mut(buffer, buffer_size)?,
+                slice(tag, tag_size)?,
+            )
+        };
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::DecryptAesGcmExternalKey {
+            client_id,
+            request_id,
+            deadline: None,
+            key,
+            iv,
+            buffer,
+            aad,
+            tag,
+        })? {
+            Response::DecryptAesGcm { .. } => Ok(()),
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Encrypt the first `plaintext_size` bytes of `buffer` in place with AES-CBC and the key in slot
+/// `key_id`. `padding` is [HEIMLIG_PKCS7] or [HEIMLIG_NO_PADDING]. The buffer has to be large
+/// enough for the padded ciphertext, whose size is written to `ciphertext_size`.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+/// `ciphertext_size` must be null or valid.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn heimlig_encrypt_aes_cbc(
+    client: *mut HeimligClient,
+    key_id: u32,
+    iv: *const u8,
+    iv_size: usize,
+    padding: u32,
+    buffer: *mut u8,
+    buffer_size: usize,
+    plaintext_size: usize,
+    ciphertext_size: *mut usize,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, iv, buffer, result) = unsafe {
+            (
+                client_mut(client)?,
+                slice(iv, iv_size)?,
+                slice_mut(buffer, buffer_size)?,
+                ciphertext_size.as_mut(),
+            )
+        };
+        let result = result.ok_or(HEIMLIG_INVALID_ARGUMENT)?;
+        let padding = self::padding(padding)?;
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::EncryptAesCbc {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: KeyId(key_id),
+            iv,
+            buffer,
+            plaintext_size,
+            padding,
+        })? {
+            Response::EncryptAesCbc { buffer, .. } => {
+                *result = buffer.len();
+                Ok(())
+            }
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Decrypt `buffer` in place with AES-CBC and the key in slot `key_id`. `padding` is
+/// [HEIMLIG_PKCS7] or [HEIMLIG_NO_PADDING]. The size of the plaintext at the start of the buffer
+/// is written to `plaintext_size`.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+/// `plaintext_size` must be null or valid.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn heimlig_decrypt_aes_cbc(
+    client: *mut HeimligClient,
+    key_id: u32,
+    iv: *const u8,
+    iv_size: usize,
+    padding: u32,
+    buffer: *mut u8,
+    buffer_size: usize,
+    plaintext_size: *mut usize,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, iv, buffer, result) = unsafe {
+            (
+                client_mut(client)?,
+                slice(iv, iv_size)?,
+                slice_mut(buffer, buffer_size)?,
+                plaintext_size.as_mut(),
+            )
+        };
+        let result = result.ok_or(HEIMLIG_INVALID_ARGUMENT)?;
+        let padding = self::padding(padding)?;
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::DecryptAesCbc {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: KeyId(key_id),
+            iv,
+            buffer,
+            padding,
+        })? {
+            Response::DecryptAesCbc { plaintext, .. } => {
+                *result = plaintext.len();
+                Ok(())
+            }
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Calculate the AES-CMAC `tag` of `message` with the key in slot `key_id`.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+#[no_mangle]
+pub unsafe extern "C" fn heimlig_calculate_aes_cmac(
+    client: *mut HeimligClient,
+    key_id: u32,
+    message: *const u8,
+    message_size: usize,
+    tag: *mut u8,
+    tag_size: usize,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, message, tag) = unsafe {
+            (
+                client_mut(client)?,
+                slice(message, message_size)?,
+                slice_mut(tag, tag_size)?,
+            )
+        };
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::CalculateAesCmac {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: KeyId(key_id),
+            message,
+            tag,
+        })? {
+            Response::CalculateAesCmac { .. } => Ok(()),
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Check the AES-CMAC `tag` of `message` with the key in slot `key_id`. The result is written to
+/// `verified`.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+/// `verified` must be null or valid.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn heimlig_verify_aes_cmac(
+    client: *mut HeimligClient,
+    key_id: u32,
+    message: *const u8,
+    message_size: usize,
+    tag: *const u8,
+    tag_size: usize,
+    verified: *mut bool,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, message, tag, result) = unsafe {
+            (
+                client_mut(client)?,
+                slice(message, message_size)?,
+                slice(tag, tag_size)?,
+                verified.as_mut(),
+            )
+        };
+        let result = result.ok_or(HEIMLIG_INVALID_ARGUMENT)?;
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::VerifyAesCmac {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: KeyId(key_id),
+            message,
+            tag,
+        })? {
+            Response::VerifyAesCmac { verified, .. } => {
+                *result = verified;
+                Ok(())
+            }
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Calculate the HMAC `tag` of `message` with the key in slot `key_id`. `hash_algorithm` is one of
+/// the `HEIMLIG_SHA*` codes.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn heimlig_calculate_hmac(
+    client: *mut HeimligClient,
+    key_id: u32,
+    hash_algorithm: u32,
+    message: *const u8,
+    message_size: usize,
+    tag: *mut u8,
+    tag_size: usize,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, message, tag) = unsafe {
+            (
+                client_mut(client)?,
+                slice(message, message_size)?,
+                slice_mut(tag, tag_size)?,
+            )
+        };
+        let hash_algorithm = self::hash_algorithm(hash_algorithm)?;
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::CalculateHmac {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: KeyId(key_id),
+            hash_algorithm,
+            message,
+            tag,
+        })? {
+            Response::CalculateHmac { .. } => Ok(()),
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Check the HMAC `tag` of `message` with the key in slot `key_id`. `hash_algorithm` is one of the
+/// `HEIMLIG_SHA*` codes. The result is written to `verified`.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+/// `verified` must be null or valid.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn heimlig_verify_hmac(
+    client: *mut HeimligClient,
+    key_id: u32,
+    hash_algorithm: u32,
+    message: *const u8,
+    message_size: usize,
+    tag: *const u8,
+    tag_size: usize,
+    verified: *mut bool,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, message, tag, result) = unsafe {
+            (
+                client_mut(client)?,
+                slice(message, message_size)?,
+                slice(tag, tag_size)?,
+                verified.as_mut(),
+            )
+        };
+        let result = result.ok_or(HEIMLIG_INVALID_ARGUMENT)?;
+        let hash_algorithm = self::hash_algorithm(hash_algorithm)?;
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::VerifyHmac {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: KeyId(key_id),
+            hash_algorithm,
+            message,
+            tag,
+        })? {
+            Response::VerifyHmac { verified, .. } => {
+                *result = verified;
+                Ok(())
+            }
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Sign `message` with the private key in slot `key_id`. If `prehashed` is set, `message` is the
+/// digest of the message. `hash_algorithm` is one of the `HEIMLIG_SHA*` codes or
+/// [HEIMLIG_CURVE_DEFAULT_HASH], `signature_format` is [HEIMLIG_SIGNATURE_RAW] or
+/// [HEIMLIG_SIGNATURE_DER]. The size of the signature at the start of `signature` is written to
+/// `written_size`.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+/// `written_size` must be null or valid.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn heimlig_sign(
+    client: *mut HeimligClient,
+    key_id: u32,
+    message: *const u8,
+    message_size: usize,
+    prehashed: bool,
+    hash_algorithm: u32,
+    signature_format: u32,
+    signature: *mut u8,
+    signature_size: usize,
+    written_size: *mut usize,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, message, signature, result) = unsafe {
+            (
+                client_mut(client)?,
+                slice(message, message_size)?,
+                slice_mut(signature, signature_size)?,
+                written_size.as_mut(),
+            )
+        };
+        let result = result.ok_or(HEIMLIG_INVALID_ARGUMENT)?;
+        let hash_algorithm = signature_hash_algorithm(hash_algorithm)?;
+        let signature_format = self::signature_format(signature_format)?;
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::Sign {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: KeyId(key_id),
+            message,
+            prehashed,
+            hash_algorithm,
+            signature_format,
+            signature,
+        })? {
+            Response::Sign { signature, .. } => {
+                *result = signature.len();
+                Ok(())
+            }
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Check the `signature` of `message` with the public key in slot `key_id`. The parameters are
+/// the ones of [heimlig_sign]. The result is written to `verified`.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+/// `verified` must be null or valid.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn heimlig_verify(
+    client: *mut HeimligClient,
+    key_id: u32,
+    message: *const u8,
+    message_size: usize,
+    prehashed: bool,
+    hash_algorithm: u32,
+    signature_format: u32,
+    signature: *const u8,
+    signature_size: usize,
+    verified: *mut bool,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, message, signature, result) = unsafe {
+            (
+                client_mut(client)?,
+                slice(message, message_size)?,
+                slice(signature, signature_size)?,
+                verified.as_mut(),
+            )
+        };
+        let result = result.ok_or(HEIMLIG_INVALID_ARGUMENT)?;
+        let hash_algorithm = signature_hash_algorithm(hash_algorithm)?;
+        let signature_format = self::signature_format(signature_format)?;
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::Verify {
+            client_id,
+            request_id,
+            deadline: None,
+            key_id: KeyId(key_id),
+            message,
+            prehashed,
+            hash_algorithm,
+            signature_format,
+            signature,
+        })? {
+            Response::Verify { verified, .. } => {
+                *result = verified;
+                Ok(())
+            }
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
+
+/// Derive the `shared_secret` of the private key in slot `private_key_id` and the `public_key` of
+/// the other party with ECDH.
+///
+/// # Safety
+///
+/// `client` must be null or a valid client. All buffers must be null or valid for their size.
+#[no_mangle]
+pub unsafe extern "C" fn heimlig_ecdh(
+    client: *mut HeimligClient,
+    private_key_id: u32,
+    public_key: *const u8,
+    public_key_size: usize,
+    shared_secret: *mut u8,
+    shared_secret_size: usize,
+) -> HeimligStatus {
+    from_result((|| {
+        // SAFETY: Guaranteed by the caller
+        let (client, public_key, shared_secret) = unsafe {
+            (
+                client_mut(client)?,
+                slice(public_key, public_key_size)?,
+                slice_mut(shared_secret, shared_secret_size)?,
+            )
+        };
+        let (client_id, request_id) = client.next_ids();
+        match client.call(Request::Ecdh {
+            client_id,
+            request_id,
+            deadline: None,
+            public_key,
+            private_key_id: KeyId(private_key_id),
+            shared_secret,
+        })? {
+            Response::Ecdh { .. } => Ok(()),
+            _ => Err(HEIMLIG_UNEXPECTED_RESPONSE),
+        }
+    })())
+}
@@ -0,0 +1,91 @@
+//! Status codes returned by the functions of the library.
+//!
+//! Errors reported by the HSM are grouped into categories. The category is stored in the second
+//! byte (see [HEIMLIG_ERROR_CATEGORY_MASK]). For the crypto, key store, SHE and limit categories,
+//! the lowest byte is the code of the detailed error, e.g.
+//! `HEIMLIG_ERROR_CRYPTO | CryptoErrorRaw_Decrypt`.
+
+use heimlig::common::jobs;
+use heimlig::integration::raw_errors::JobErrorRaw;
+
+pub type HeimligStatus = u32;
+
+/// The request was processed successfully.
+pub const HEIMLIG_OK: HeimligStatus = 0;
+/// A pointer argument is null, a buffer is larger than `u32::MAX` bytes or the code of an
+/// algorithm or format is unknown.
+pub const HEIMLIG_INVALID_ARGUMENT: HeimligStatus = 1;
+/// A ring is not formatted for the items of the library or its indices are corrupted. If the
+/// request was already pushed, the client is poisoned (see [HEIMLIG_CLIENT_POISONED]).
+pub const HEIMLIG_TRANSPORT_ERROR: HeimligStatus = 2;
+/// The response ring contained an invalid response, e.g. one of another major ABI version. If it
+/// was popped while waiting for the response, the client is poisoned (see
+/// [HEIMLIG_CLIENT_POISONED]). Also returned for responses referring to memory outside of the
+/// writable buffers of the request.
+pub const HEIMLIG_INVALID_RESPONSE: HeimligStatus = 3;
+/// The HSM answered with a response of another request type.
+pub const HEIMLIG_UNEXPECTED_RESPONSE: HeimligStatus = 4;
+/// The HSM did not process the request.
+pub const HEIMLIG_CANCELLED: HeimligStatus = 5;
+/// A previous request of the client failed after it was sent to the HSM. The HSM may still access
+/// the buffers of that request, so they must not be reused. The client does not send any further
+/// requests.
+pub const HEIMLIG_CLIENT_POISONED: HeimligStatus = 6;
+
+/// Selects the category of an error reported by the HSM.
+pub const HEIMLIG_ERROR_CATEGORY_MASK: HeimligStatus = 0xFF00;
+/// Category of errors of the HSM without details.
+pub const HEIMLIG_ERROR_JOB: HeimligStatus = 0x100;
+/// No worker found for the request type.
+pub const HEIMLIG_ERROR_NO_WORKER_FOR_REQUEST: HeimligStatus = HEIMLIG_ERROR_JOB | 0x01;
+/// A worker encountered a request type that it cannot handle.
+pub const HEIMLIG_ERROR_UNEXPECTED_REQUEST_TYPE: HeimligStatus = HEIMLIG_ERROR_JOB | 0x02;
+/// The amount of requested data was too large.
+pub const HEIMLIG_ERROR_REQUEST_TOO_LARGE: HeimligStatus = HEIMLIG_ERROR_JOB | 0x03;
+/// The HSM has no key store.
+pub const HEIMLIG_ERROR_NO_KEY_STORE: HeimligStatus = HEIMLIG_ERROR_JOB | 0x04;
+/// The HSM failed to pass the request on.
+pub const HEIMLIG_ERROR_SEND: HeimligStatus = HEIMLIG_ERROR_JOB | 0x05;
+/// A channel of the HSM was closed.
+pub const HEIMLIG_ERROR_STREAM_TERMINATED: HeimligStatus = HEIMLIG_ERROR_JOB | 0x06;
+/// The worker responsible for the request is unavailable.
+pub const HEIMLIG_ERROR_WORKER_UNAVAILABLE: HeimligStatus = HEIMLIG_ERROR_JOB | 0x07;
+/// A buffer of the request is outside the memory regions of the client.
+pub const HEIMLIG_ERROR_ACCESS_DENIED: HeimligStatus = HEIMLIG_ERROR_JOB | 0x08;
+/// The HSM does not support cancellations.
+pub const HEIMLIG_ERROR_CANCELLATION_UNSUPPORTED: HeimligStatus = HEIMLIG_ERROR_JOB | 0x09;
+/// The request to cancel is not pending at a worker or too many requests are cancelled.
+pub const HEIMLIG_ERROR_NOT_CANCELLABLE: HeimligStatus = HEIMLIG_ERROR_JOB | 0x0A;
+/// Category of cryptographic errors. The lowest byte is a `CryptoErrorRaw`.
+pub const HEIMLIG_ERROR_CRYPTO: HeimligStatus = 0x200;
+/// Category of key store errors. The lowest byte is a `KeyStoreErrorRaw`.
+pub const HEIMLIG_ERROR_KEY_STORE: HeimligStatus = 0x300;
+/// Category of SHE errors. The lowest byte is a `SheErrorRaw`.
+pub const HEIMLIG_ERROR_SHE: HeimligStatus = 0x400;
+/// Category of exceeded client limits. The lowest byte is a `LimitRaw`.
+pub const HEIMLIG_ERROR_LIMIT_EXCEEDED: HeimligStatus = 0x500;
+
+/// Status code of an error reported by the HSM.
+pub(crate) fn from_job_error(error: jobs::Error) -> HeimligStatus {
+    match JobErrorRaw::from(error) {
+        JobErrorRaw::NoWorkerForRequest => HEIMLIG_ERROR_NO_WORKER_FOR_REQUEST,
+        JobErrorRaw::UnexpectedRequestType => HEIMLIG_ERROR_UNEXPECTED_REQUEST_TYPE,
+        JobErrorRaw::RequestTooLarge => HEIMLIG_ERROR_REQUEST_TOO_LARGE,
+        JobErrorRaw::NoKeyStore => HEIMLIG_ERROR_NO_KEY_STORE,
+        JobErrorRaw::Send => HEIMLIG_ERROR_SEND,
+        JobErrorRaw::StreamTerminated => HEIMLIG_ERROR_STREAM_TERMINATED,
+        JobErrorRaw::WorkerUnavailable => HEIMLIG_ERROR_WORKER_UNAVAILABLE,
+        JobErrorRaw::AccessDenied => HEIMLIG_ERROR_ACCESS_DENIED,
+        JobErrorRaw::CancellationUnsupported => HEIMLIG_ERROR_CANCELLATION_UNSUPPORTED,
+        JobErrorRaw::NotCancellable => HEIMLIG_ERROR_NOT_CANCELLABLE,
+        JobErrorRaw::Crypto(e) => HEIMLIG_ERROR_CRYPTO | e as HeimligStatus,
+        JobErrorRaw::KeyStore(e) => HEIMLIG_ERROR_KEY_STORE | e as HeimligStatus,
+        JobErrorRaw::She(e) => HEIMLIG_ERROR_SHE | e as HeimligStatus,
+        JobErrorRaw::LimitExceeded(l) => HEIMLIG_ERROR_LIMIT_EXCEEDED | l as HeimligStatus,
+    }
+}
+
+/// Convert the result of a library function to its status code.
+pub(crate) fn from_result(result: Result<(), HeimligStatus>) -> HeimligStatus {
+    result.err().unwrap_or(HEIMLIG_OK)
+}
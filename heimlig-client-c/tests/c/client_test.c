@@ -0,0 +1,362 @@
+/*
+ * C test programs of the client library. The harness in `tests/c_client.rs` runs a Heimlig core
+ * on the rings described by `config` and calls one test function per Rust test. A test function
+ * returns 0 on success or the line of the first failed check.
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "HeimligClient.h"
+
+/* Key slots of the core run by the harness */
+#define AES_128_KEY_ID 0
+#define AES_256_KEY_ID 1
+#define NIST_P256_KEY_ID 2
+#define UNKNOWN_KEY_ID 42
+
+#define CHECK(condition)                                                                   \
+    do {                                                                                   \
+        if (!(condition)) {                                                                \
+            fprintf(stderr, "%s:%d: Check failed: %s\n", __FILE__, __LINE__, #condition);  \
+            return __LINE__;                                                               \
+        }                                                                                  \
+    } while (0)
+
+#define CHECK_STATUS(call, expected)                                                       \
+    do {                                                                                   \
+        HeimligStatus status_ = (call);                                                    \
+        if (status_ != (HeimligStatus)(expected)) {                                        \
+            fprintf(stderr, "%s:%d: %s returned 0x%x instead of 0x%x\n", __FILE__, __LINE__, \
+                    #call, (unsigned)status_, (unsigned)(expected));                       \
+            return __LINE__;                                                               \
+        }                                                                                  \
+    } while (0)
+
+static const uint8_t IV[12] = {0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xA, 0xB, 0xC};
+static const uint8_t AAD[] = "Additional authenticated data";
+static const uint8_t PLAINTEXT[] = "Plaintext of the C test programs";
+
+static int all_zero(const uint8_t *data, size_t size) {
+    for (size_t i = 0; i < size; i++) {
+        if (data[i] != 0) {
+            return 0;
+        }
+    }
+    return 1;
+}
+
+int heimlig_test_get_random(const HeimligClientConfig *config) {
+    HeimligClient storage;
+    HeimligClient *client = &storage;
+    uint8_t output[32] = {0};
+
+    CHECK_STATUS(heimlig_client_init(config, client), HEIMLIG_OK);
+    CHECK_STATUS(heimlig_get_random(client, output, sizeof(output)), HEIMLIG_OK);
+    CHECK(!all_zero(output, sizeof(output)));
+    /* Requests without data are allowed */
+    CHECK_STATUS(heimlig_get_random(client, NULL, 0), HEIMLIG_OK);
+    return 0;
+}
+
+int heimlig_test_aes_gcm(const HeimligClientConfig *config) {
+    HeimligClient storage;
+    HeimligClient *client = &storage;
+    const uint8_t key[16] = {0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6,
+                             0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF, 0x4F, 0x3C};
+    uint8_t buffer[sizeof(PLAINTEXT)];
+    uint8_t tag[16] = {0};
+    bool is_available = true;
+
+    CHECK_STATUS(heimlig_client_init(config, client), HEIMLIG_OK);
+    CHECK_STATUS(heimlig_is_key_available(client, AES_128_KEY_ID, &is_available), HEIMLIG_OK);
+    CHECK(!is_available);
+    CHECK_STATUS(heimlig_import_symmetric_key(client, AES_128_KEY_ID, key, sizeof(key), false),
+                 HEIMLIG_OK);
+    CHECK_STATUS(heimlig_is_key_available(client, AES_128_KEY_ID, &is_available), HEIMLIG_OK);
+    CHECK(is_available);
+
+    memcpy(buffer, PLAINTEXT, sizeof(buffer));
+    CHECK_STATUS(heimlig_encrypt_aes_gcm(client, AES_128_KEY_ID, IV, sizeof(IV), AAD, sizeof(AAD),
+                                         buffer, sizeof(buffer), tag, sizeof(tag)),
+                 HEIMLIG_OK);
+    CHECK(memcmp(buffer, PLAINTEXT, sizeof(buffer)) != 0);
+    CHECK_STATUS(heimlig_decrypt_aes_gcm(client, AES_128_KEY_ID, IV, sizeof(IV), AAD, sizeof(AAD),
+                                         buffer, sizeof(buffer), tag, sizeof(tag)),
+                 HEIMLIG_OK);
+    CHECK(memcmp(buffer, PLAINTEXT, sizeof(buffer)) == 0);
+
+    /* Without additional data */
+    CHECK_STATUS(heimlig_encrypt_aes_gcm(client, AES_128_KEY_ID, IV, sizeof(IV), NULL, 0, buffer,
+                                         sizeof(buffer), tag, sizeof(tag)),
+                 HEIMLIG_OK);
+    tag[0] ^= 0xFF;
+    CHECK_STATUS(heimlig_decrypt_aes_gcm(client, AES_128_KEY_ID, IV, sizeof(IV), NULL, 0, buffer,
+                                         sizeof(buffer), tag, sizeof(tag)),
+                 HEIMLIG_ERROR_CRYPTO | CryptoErrorRaw_Decrypt);
+    return 0;
+}
+
+int heimlig_test_aes_gcm_external_key(const HeimligClientConfig *config) {
+    HeimligClient storage;
+    HeimligClient *client = &storage;
+    uint8_t key[32];
+    uint8_t buffer[sizeof(PLAINTEXT)];
+    uint8_t tag[16] = {0};
+
+    CHECK_STATUS(heimlig_client_init(config, client), HEIMLIG_OK);
+    CHECK_STATUS(heimlig_get_random(client, key, sizeof(key)), HEIMLIG_OK);
+    memcpy(buffer, PLAINTEXT, sizeof(buffer));
+    CHECK_STATUS(heimlig_encrypt_aes_gcm_external_key(client, key, sizeof(key), IV, sizeof(IV), AAD,
+                                                      sizeof(AAD), buffer, sizeof(buffer), tag,
+                                                      sizeof(tag)),
+                 HEIMLIG_OK);
+    CHECK_STATUS(heimlig_decrypt_aes_gcm_external_key(client, key, sizeof(key), IV, sizeof(IV), AAD,
+                                                      sizeof(AAD), buffer, sizeof(buffer), tag,
+                                                      sizeof(tag)),
+                 HEIMLIG_OK);
+    CHECK(memcmp(buffer, PLAINTEXT, sizeof(buffer)) == 0);
+
+    CHECK_STATUS(heimlig_encrypt_aes_gcm_external_key(client, key, 7, IV, sizeof(IV), AAD,
+                                                      sizeof(AAD), buffer, sizeof(buffer), tag,
+                                                      sizeof(tag)),
+                 HEIMLIG_ERROR_CRYPTO | CryptoErrorRaw_InvalidSymmetricKeySize);
+    return 0;
+}
+
+int heimlig_test_generated_key(const HeimligClientConfig *config) {
+    HeimligClient storage;
+    HeimligClient *client = &storage;
+    uint8_t buffer[sizeof(PLAINTEXT)];
+    uint8_t tag[16] = {0};
+
+    CHECK_STATUS(heimlig_client_init(config, client), HEIMLIG_OK);
+    CHECK_STATUS(heimlig_generate_symmetric_key(client, AES_256_KEY_ID, false), HEIMLIG_OK);
+    CHECK_STATUS(heimlig_generate_symmetric_key(client, AES_256_KEY_ID, false),
+                 HEIMLIG_ERROR_KEY_STORE | KeyStoreErrorRaw_KeyAlreadyExists);
+    memcpy(buffer, PLAINTEXT, sizeof(buffer));
+    CHECK_STATUS(heimlig_encrypt_aes_gcm(client, AES_256_KEY_ID, IV, sizeof(IV), AAD, sizeof(AAD),
+                                         buffer, sizeof(buffer), tag, sizeof(tag)),
+                 HEIMLIG_OK);
+    CHECK_STATUS(heimlig_decrypt_aes_gcm(client, AES_256_KEY_ID, IV, sizeof(IV), AAD, sizeof(AAD),
+                                         buffer, sizeof(buffer), tag, sizeof(tag)),
+                 HEIMLIG_OK);
+    CHECK(memcmp(buffer, PLAINTEXT, sizeof(buffer)) == 0);
+    return 0;
+}
+
+int heimlig_test_aes_cbc(const HeimligClientConfig *config) {
+    HeimligClient storage;
+    HeimligClient *client = &storage;
+    const uint8_t key[16] = {0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6,
+                             0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF, 0x4F, 0x3C};
+    const uint8_t iv[16] = {0};
+    /* Room for the padding */
+    uint8_t buffer[sizeof(PLAINTEXT) + 16];
+    size_t ciphertext_size = 0;
+    size_t plaintext_size = 0;
+
+    CHECK_STATUS(heimlig_client_init(config, client), HEIMLIG_OK);
+    CHECK_STATUS(heimlig_import_symmetric_key(client, AES_128_KEY_ID, key, sizeof(key), false),
+                 HEIMLIG_OK);
+    memcpy(buffer, PLAINTEXT, sizeof(PLAINTEXT));
+    CHECK_STATUS(heimlig_encrypt_aes_cbc(client, AES_128_KEY_ID, iv, sizeof(iv), HEIMLIG_PKCS7,
+                                         buffer, sizeof(buffer), sizeof(PLAINTEXT),
+                                         &ciphertext_size),
+                 HEIMLIG_OK);
+    CHECK(ciphertext_size == (sizeof(PLAINTEXT) / 16 + 1) * 16);
+    CHECK_STATUS(heimlig_decrypt_aes_cbc(client, AES_128_KEY_ID, iv, sizeof(iv), HEIMLIG_PKCS7,
+                                         buffer, ciphertext_size, &plaintext_size),
+                 HEIMLIG_OK);
+    CHECK(plaintext_size == sizeof(PLAINTEXT));
+    CHECK(memcmp(buffer, PLAINTEXT, sizeof(PLAINTEXT)) == 0);
+
+    /* Without padding, only whole blocks are encrypted */
+    CHECK_STATUS(heimlig_encrypt_aes_cbc(client, AES_128_KEY_ID, iv, sizeof(iv),
+                                         HEIMLIG_NO_PADDING, buffer, 32, 32, &ciphertext_size),
+                 HEIMLIG_OK);
+    CHECK(ciphertext_size == 32);
+    HeimligStatus status = heimlig_encrypt_aes_cbc(client, AES_128_KEY_ID, iv, sizeof(iv),
+                                                   HEIMLIG_NO_PADDING, buffer, sizeof(buffer),
+                                                   sizeof(PLAINTEXT), &ciphertext_size);
+    CHECK((status & HEIMLIG_ERROR_CATEGORY_MASK) == HEIMLIG_ERROR_CRYPTO);
+    CHECK_STATUS(heimlig_encrypt_aes_cbc(client, AES_128_KEY_ID, iv, sizeof(iv), 42, buffer,
+                                         sizeof(buffer), sizeof(PLAINTEXT), &ciphertext_size),
+                 HEIMLIG_INVALID_ARGUMENT);
+    return 0;
+}
+
+int heimlig_test_macs(const HeimligClientConfig *config) {
+    HeimligClient storage;
+    HeimligClient *client = &storage;
+    const uint8_t key[16] = {0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6,
+                             0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF, 0x4F, 0x3C};
+    uint8_t cmac[16] = {0};
+    uint8_t hmac[32] = {0};
+    bool verified = false;
+
+    CHECK_STATUS(heimlig_client_init(config, client), HEIMLIG_OK);
+    CHECK_STATUS(heimlig_import_symmetric_key(client, AES_128_KEY_ID, key, sizeof(key), false),
+                 HEIMLIG_OK);
+    CHECK_STATUS(heimlig_calculate_aes_cmac(client, AES_128_KEY_ID, PLAINTEXT, sizeof(PLAINTEXT),
+                                            cmac, sizeof(cmac)),
+                 HEIMLIG_OK);
+    CHECK(!all_zero(cmac, sizeof(cmac)));
+    CHECK_STATUS(heimlig_verify_aes_cmac(client, AES_128_KEY_ID, PLAINTEXT, sizeof(PLAINTEXT),
+                                         cmac, sizeof(cmac), &verified),
+                 HEIMLIG_OK);
+    CHECK(verified);
+    cmac[0] ^= 0xFF;
+    CHECK_STATUS(heimlig_verify_aes_cmac(client, AES_128_KEY_ID, PLAINTEXT, sizeof(PLAINTEXT),
+                                         cmac, sizeof(cmac), &verified),
+                 HEIMLIG_OK);
+    CHECK(!verified);
+
+    CHECK_STATUS(heimlig_generate_symmetric_key(client, AES_256_KEY_ID, false), HEIMLIG_OK);
+    CHECK_STATUS(heimlig_calculate_hmac(client, AES_256_KEY_ID, HEIMLIG_SHA2_256, PLAINTEXT,
+                                        sizeof(PLAINTEXT), hmac, sizeof(hmac)),
+                 HEIMLIG_OK);
+    CHECK(!all_zero(hmac, sizeof(hmac)));
+    CHECK_STATUS(heimlig_verify_hmac(client, AES_256_KEY_ID, HEIMLIG_SHA2_256, PLAINTEXT,
+                                     sizeof(PLAINTEXT), hmac, sizeof(hmac), &verified),
+                 HEIMLIG_OK);
+    CHECK(verified);
+    CHECK_STATUS(heimlig_verify_hmac(client, AES_256_KEY_ID, HEIMLIG_SHA3_256, PLAINTEXT,
+                                     sizeof(PLAINTEXT), hmac, sizeof(hmac), &verified),
+                 HEIMLIG_OK);
+    CHECK(!verified);
+    return 0;
+}
+
+int heimlig_test_signatures(const HeimligClientConfig *config) {
+    HeimligClient storage;
+    HeimligClient *client = &storage;
+    /* Large enough for DER encoded P-256 signatures */
+    uint8_t signature[72] = {0};
+    size_t signature_size = 0;
+    uint8_t public_key[64] = {0};
+    uint8_t shared_secret[32] = {0};
+    bool verified = false;
+
+    CHECK_STATUS(heimlig_client_init(config, client), HEIMLIG_OK);
+    CHECK_STATUS(heimlig_generate_key_pair(client, NIST_P256_KEY_ID, false), HEIMLIG_OK);
+    CHECK_STATUS(heimlig_sign(client, NIST_P256_KEY_ID, PLAINTEXT, sizeof(PLAINTEXT), false,
+                              HEIMLIG_CURVE_DEFAULT_HASH, HEIMLIG_SIGNATURE_RAW, signature, 64,
+                              &signature_size),
+                 HEIMLIG_OK);
+    CHECK(signature_size == 64);
+    CHECK_STATUS(heimlig_verify(client, NIST_P256_KEY_ID, PLAINTEXT, sizeof(PLAINTEXT), false,
+                                HEIMLIG_CURVE_DEFAULT_HASH, HEIMLIG_SIGNATURE_RAW, signature,
+                                signature_size, &verified),
+                 HEIMLIG_OK);
+    CHECK(verified);
+
+    CHECK_STATUS(heimlig_sign(client, NIST_P256_KEY_ID, PLAINTEXT, sizeof(PLAINTEXT), false,
+                              HEIMLIG_SHA2_384, HEIMLIG_SIGNATURE_DER, signature,
+                              sizeof(signature), &signature_size),
+                 HEIMLIG_OK);
+    CHECK(signature_size <= sizeof(signature) && signature[0] == 0x30);
+    CHECK_STATUS(heimlig_verify(client, NIST_P256_KEY_ID, PLAINTEXT, sizeof(PLAINTEXT), false,
+                                HEIMLIG_SHA2_384, HEIMLIG_SIGNATURE_DER, signature,
+                                signature_size, &verified),
+                 HEIMLIG_OK);
+    CHECK(verified);
+    /* Signed with another hash algorithm */
+    CHECK_STATUS(heimlig_verify(client, NIST_P256_KEY_ID, PLAINTEXT, sizeof(PLAINTEXT), false,
+                                HEIMLIG_SHA2_256, HEIMLIG_SIGNATURE_DER, signature,
+                                signature_size, &verified),
+                 HEIMLIG_OK);
+    CHECK(!verified);
+
+    /* The core of the harness has no worker for ECDH */
+    CHECK_STATUS(heimlig_ecdh(client, NIST_P256_KEY_ID, public_key, sizeof(public_key),
+                              shared_secret, sizeof(shared_secret)),
+                 HEIMLIG_ERROR_NO_WORKER_FOR_REQUEST);
+    return 0;
+}
+
+int heimlig_test_errors(const HeimligClientConfig *config) {
+    HeimligClient storage;
+    HeimligClient *client = &storage;
+    HeimligClientConfig invalid = *config;
+    const uint8_t key[16] = {0};
+    uint8_t buffer[16] = {0};
+    uint8_t tag[16] = {0};
+
+    /* Invalid configurations */
+    CHECK_STATUS(heimlig_client_init(NULL, client), HEIMLIG_INVALID_ARGUMENT);
+    CHECK_STATUS(heimlig_client_init(config, NULL), HEIMLIG_INVALID_ARGUMENT);
+    invalid.notifier.wait = NULL;
+    CHECK_STATUS(heimlig_client_init(&invalid, client), HEIMLIG_INVALID_ARGUMENT);
+    invalid = *config;
+    invalid.requests += 8;
+    CHECK_STATUS(heimlig_client_init(&invalid, client), HEIMLIG_INVALID_ARGUMENT);
+    /* Memory that was not formatted as ring of requests */
+    invalid = *config;
+    invalid.requests = invalid.responses;
+    invalid.requests_size = invalid.responses_size;
+    CHECK_STATUS(heimlig_client_init(&invalid, client), HEIMLIG_TRANSPORT_ERROR);
+
+    /* Invalid arguments of requests */
+    CHECK_STATUS(heimlig_get_random(NULL, buffer, sizeof(buffer)), HEIMLIG_INVALID_ARGUMENT);
+    CHECK_STATUS(heimlig_client_init(config, client), HEIMLIG_OK);
+    CHECK_STATUS(heimlig_get_random(client, NULL, sizeof(buffer)), HEIMLIG_INVALID_ARGUMENT);
+    CHECK_STATUS(heimlig_is_key_available(client, AES_128_KEY_ID, NULL), HEIMLIG_INVALID_ARGUMENT);
+    CHECK_STATUS(heimlig_calculate_hmac(client, AES_128_KEY_ID, 42, buffer, sizeof(buffer), tag,
+                                        sizeof(tag)),
+                 HEIMLIG_INVALID_ARGUMENT);
+
+    /* Errors reported by the HSM */
+    CHECK_STATUS(heimlig_import_symmetric_key(client, UNKNOWN_KEY_ID, buffer, sizeof(buffer), false),
+                 HEIMLIG_ERROR_KEY_STORE | KeyStoreErrorRaw_InvalidKeyId);
+    HeimligStatus status = heimlig_encrypt_aes_gcm(client, AES_128_KEY_ID, IV, sizeof(IV), NULL, 0,
+                                                   buffer, sizeof(buffer), tag, sizeof(tag));
+    CHECK((status & HEIMLIG_ERROR_CATEGORY_MASK) == HEIMLIG_ERROR_KEY_STORE);
+    CHECK_STATUS(heimlig_encrypt_aes_gcm_external_key(client, key, sizeof(key), IV, 3, NULL, 0,
+                                                      buffer, sizeof(buffer), tag, sizeof(tag)),
+                 HEIMLIG_ERROR_CRYPTO | CryptoErrorRaw_InvalidIvSize);
+    /* The client keeps working after errors */
+    CHECK_STATUS(heimlig_get_random(client, buffer, sizeof(buffer)), HEIMLIG_OK);
+    return 0;
+}
+
+int heimlig_test_many_requests(const HeimligClientConfig *config) {
+    HeimligClient storage;
+    HeimligClient *client = &storage;
+    uint8_t output[64];
+
+    CHECK_STATUS(heimlig_client_init(config, client), HEIMLIG_OK);
+    /* More requests than slots in the rings */
+    for (int i = 0; i < 100; i++) {
+        CHECK_STATUS(heimlig_get_random(client, output, 1 + i % sizeof(output)), HEIMLIG_OK);
+    }
+    return 0;
+}
+
+int heimlig_test_poisoned(const HeimligClientConfig *config) {
+    HeimligClient storage;
+    HeimligClient *client = &storage;
+    /* Stays owned by the HSM after the failed request */
+    static uint8_t output[16];
+
+    CHECK_STATUS(heimlig_client_init(config, client), HEIMLIG_OK);
+    /* The request is pushed before the corrupted response ring is noticed */
+    CHECK_STATUS(heimlig_get_random(client, output, sizeof(output)), HEIMLIG_TRANSPORT_ERROR);
+    CHECK_STATUS(heimlig_get_random(client, NULL, 0), HEIMLIG_CLIENT_POISONED);
+    CHECK_STATUS(heimlig_generate_symmetric_key(client, AES_128_KEY_ID, false),
+                 HEIMLIG_CLIENT_POISONED);
+    return 0;
+}
+
+int heimlig_test_invalid_response(const HeimligClientConfig *config) {
+    HeimligClient storage;
+    HeimligClient *client = &storage;
+    uint8_t output[16] = {0};
+
+    CHECK_STATUS(heimlig_client_init(config, client), HEIMLIG_OK);
+    /* The harness answers with random data outside of `output` */
+    CHECK_STATUS(heimlig_get_random(client, output, sizeof(output)), HEIMLIG_INVALID_RESPONSE);
+    CHECK(all_zero(output, sizeof(output)));
+    return 0;
+}
@@ -0,0 +1,331 @@
+//! Test harness running the C test programs of `tests/c` against a Heimlig core.
+//!
+//! Every test formats a request and a response ring, starts a core with a key store, an RNG, an
+//! AES, an HMAC and an ECC worker on its own thread and calls one C test function with the
+//! configuration of the rings. The core and its memory live until the end of the test process.
+
+use core::ffi::{c_int, c_void};
+use futures::executor::block_on;
+use futures::{sink, stream, Sink, Stream};
+use heimlig::common::jobs::{ClientId, Request, RequestId, Response};
+use heimlig::hsm::core::{Builder, ClientConfig};
+use heimlig::hsm::keystore::{Curve, KeyId, KeyInfo, KeyPermissions, KeyType};
+use heimlig::hsm::workers::{
+    aes_worker::AesWorker, ecc_worker::EccWorker, hmac_worker::HmacWorker, rng_worker::RngWorker,
+};
+use heimlig::integration::memory_key_store::MemoryKeyStore;
+use heimlig::integration::raw_jobs::{RequestRaw, ResponseRaw};
+use heimlig::integration::shared_ring::{
+    init_ring, ring_size, RingConsumer, RingProducer, Signal, CACHE_LINE_SIZE,
+};
+use heimlig::integration::std_sync::{Mutex, StdRawMutex};
+use heimlig_client_c::client::{HeimligClientConfig, HeimligNotifier};
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+use std::pin::Pin;
+use std::thread;
+
+#[link(name = "heimlig_client_c_tests", kind = "static")]
+extern "C" {
+    fn heimlig_test_get_random(config: *const HeimligClientConfig) -> c_int;
+    fn heimlig_test_aes_gcm(config: *const HeimligClientConfig) -> c_int;
+    fn heimlig_test_aes_gcm_external_key(config: *const HeimligClientConfig) -> c_int;
+    fn heimlig_test_generated_key(config: *const HeimligClientConfig) -> c_int;
+    fn heimlig_test_aes_cbc(config: *const HeimligClientConfig) -> c_int;
+    fn heimlig_test_macs(config: *const HeimligClientConfig) -> c_int;
+    fn heimlig_test_signatures(config: *const HeimligClientConfig) -> c_int;
+    fn heimlig_test_errors(config: *const HeimligClientConfig) -> c_int;
+    fn heimlig_test_many_requests(config: *const HeimligClientConfig) -> c_int;
+    fn heimlig_test_poisoned(config: *const HeimligClientConfig) -> c_int;
+    fn heimlig_test_invalid_response(config: *const HeimligClientConfig) -> c_int;
+}
+
+type CTest = unsafe extern "C" fn(config: *const HeimligClientConfig) -> c_int;
+
+const CAPACITY: usize = 4;
+const REQUESTS_SIZE: usize = ring_size::<RequestRaw>(CAPACITY);
+const RESPONSES_SIZE: usize = ring_size::<ResponseRaw>(CAPACITY);
+
+const KEY_INFOS: [KeyInfo; 3] = [
+    KeyInfo {
+        id: KeyId(0),
+        ty: KeyType::Symmetric(16),
+        permissions: KeyPermissions {
+            import: true,
+            export_private: false,
+            overwrite: false,
+            delete: false,
+        },
+    },
+    KeyInfo {
+        id: KeyId(1),
+        ty: KeyType::Symmetric(32),
+        permissions: KeyPermissions {
+            import: false,
+            export_private: false,
+            overwrite: false,
+            delete: false,
+        },
+    },
+    KeyInfo {
+        id: KeyId(2),
+        ty: KeyType::Asymmetric(Curve::NistP256),
+        permissions: KeyPermissions {
+            import: false,
+            export_private: false,
+            overwrite: false,
+            delete: false,
+        },
+    },
+];
+const TOTAL_KEY_SIZE: usize = 16 + 32 + KeyType::Asymmetric(Curve::NistP256).key_size();
+
+type KeyStore = MemoryKeyStore<TOTAL_KEY_SIZE, 3>;
+type RequestSource = Pin<Box<dyn Stream<Item = Request<'static>>>>;
+type ResponseSink = Pin<Box<dyn Sink<Response<'static>, Error = ()>>>;
+
+/// Stand-in for a shared RAM region
+#[repr(C, align(64))]
+struct Region<const SIZE: usize>([u8; SIZE]);
+
+/// Wake-ups of the core side of the rings
+#[derive(Default)]
+struct Signals {
+    /// Notified when the client pushed requests
+    requests: Signal,
+    /// Notified when the client popped responses
+    responses: Signal,
+    /// Notified by the core. The C client polls instead of waiting on it.
+    client: Signal,
+}
+
+/// Doorbell of the C client
+unsafe extern "C" fn ring(context: *mut c_void) {
+    // SAFETY: The context is the leaked `Signals` of the test.
+    let signals = unsafe { &*context.cast::<Signals>() };
+    signals.requests.notify();
+    signals.responses.notify();
+}
+
+unsafe extern "C" fn wait(_context: *mut c_void) {
+    thread::yield_now();
+}
+
+fn leak<T>(value: T) -> &'static mut T {
+    Box::leak(Box::new(value))
+}
+
+/// Format the rings and return the configuration of the C client.
+fn format_rings() -> (HeimligClientConfig, &'static Signals) {
+    let requests = leak(Region([0u8; REQUESTS_SIZE])).0.as_mut_ptr();
+    let responses = leak(Region([0u8; RESPONSES_SIZE])).0.as_mut_ptr();
+    let signals: &'static Signals = leak(Signals::default());
+    // SAFETY: The regions are leaked and only accessed through the ring ends.
+    unsafe {
+        init_ring::<RequestRaw>(requests, REQUESTS_SIZE, CAPACITY).expect("failed to init ring");
+        init_ring::<ResponseRaw>(responses, RESPONSES_SIZE, CAPACITY).expect("failed to init ring");
+    }
+    let config = HeimligClientConfig {
+        client_id: 0,
+        requests,
+        requests_size: REQUESTS_SIZE,
+        responses,
+        responses_size: RESPONSES_SIZE,
+        notifier: HeimligNotifier {
+            context: (signals as *const Signals).cast_mut().cast(),
+            ring: Some(ring),
+            wait: Some(wait),
+        },
+    };
+    (config, signals)
+}
+
+/// Format the rings, start a core serving them and return the configuration of the C client.
+fn start_core() -> HeimligClientConfig {
+    let (config, signals) = format_rings();
+    // SAFETY: The regions are leaked. The C client attaches the other ends.
+    let (consumer, producer) = unsafe {
+        (
+            RingConsumer::<RequestRaw, _>::attach(
+                config.requests,
+                REQUESTS_SIZE,
+                &signals.client,
+                &signals.requests,
+            )
+            .expect("failed to attach consumer"),
+            RingProducer::<ResponseRaw, _>::attach(
+                config.responses,
+                RESPONSES_SIZE,
+                &signals.client,
+                &signals.responses,
+            )
+            .expect("failed to attach producer"),
+        )
+    };
+
+    thread::spawn(move || {
+        // The core and the C test run in the same address space, so all buffers are accessible.
+        let request_source: RequestSource =
+            Box::pin(stream::unfold(consumer, |mut consumer| async move {
+                loop {
+                    let Ok(raw) = consumer.pop().await else {
+                        continue;
+                    };
+                    if let Ok(request) = raw.verify(&|_: *const u8, _: u32| true) {
+                        return Some((request, consumer));
+                    }
+                }
+            }));
+        let response_sink: ResponseSink = Box::pin(sink::unfold(
+            producer,
+            |mut producer, response: Response<'static>| async move {
+                let response = response.try_into().map_err(|_| ())?;
+                producer.push(response).await.map_err(|_| ())?;
+                Ok(producer)
+            },
+        ));
+        let key_store = leak(KeyStore::try_new(&KEY_INFOS).expect("failed to create key store"));
+        let key_store: &'static Mutex<_> = leak(Mutex::new(key_store));
+        let rng: &'static Mutex<_> = leak(Mutex::new(ChaCha20Rng::from_seed([1u8; 32])));
+        let rng_worker = RngWorker {
+            rng,
+            key_store: Some(key_store),
+        };
+        let aes_worker = AesWorker {
+            key_store,
+            she_slots: None,
+        };
+        let hmac_worker = HmacWorker { key_store };
+        let ecc_worker = EccWorker { rng, key_store };
+        let mut core = Builder::<StdRawMutex, _, _, _>::new()
+            .with_keystore(key_store)
+            .with_client(request_source, response_sink, ClientConfig::default())
+            .expect("failed to add client")
+            .with_hosted_worker(rng_worker)
+            .and_then(|builder| builder.with_hosted_worker(aes_worker))
+            .and_then(|builder| builder.with_hosted_worker(hmac_worker))
+            .and_then(|builder| builder.with_hosted_worker(ecc_worker))
+            .expect("failed to add hosted workers")
+            .build();
+        block_on(async {
+            loop {
+                core.execute().await.expect("failed to process request");
+            }
+        })
+    });
+
+    config
+}
+
+fn run_with(test: CTest, config: HeimligClientConfig) {
+    // SAFETY: The configuration describes rings that live until the end of the process.
+    let failed_line = unsafe { test(&config) };
+    assert_eq!(failed_line, 0, "C test failed in line {failed_line}");
+}
+
+fn run(test: CTest) {
+    run_with(test, start_core());
+}
+
+#[test]
+fn get_random() {
+    run(heimlig_test_get_random);
+}
+
+#[test]
+fn aes_gcm() {
+    run(heimlig_test_aes_gcm);
+}
+
+#[test]
+fn aes_gcm_external_key() {
+    run(heimlig_test_aes_gcm_external_key);
+}
+
+#[test]
+fn generated_key() {
+    run(heimlig_test_generated_key);
+}
+
+#[test]
+fn aes_cbc() {
+    run(heimlig_test_aes_cbc);
+}
+
+#[test]
+fn macs() {
+    run(heimlig_test_macs);
+}
+
+#[test]
+fn signatures() {
+    run(heimlig_test_signatures);
+}
+
+#[test]
+fn errors() {
+    run(heimlig_test_errors);
+}
+
+#[test]
+fn many_requests() {
+    run(heimlig_test_many_requests);
+}
+
+#[test]
+fn poisoned() {
+    // Nobody serves the rings. The head index of the response ring is corrupted, so the client
+    // fails after pushing its first request.
+    let (config, _signals) = format_rings();
+    // SAFETY: The region is leaked and aligned. The head index follows the header cache line.
+    unsafe {
+        config
+            .responses
+            .add(CACHE_LINE_SIZE)
+            .cast::<u32>()
+            .write_volatile(2 * CAPACITY as u32)
+    };
+    run_with(heimlig_test_poisoned, config);
+}
+
+#[test]
+fn invalid_response() {
+    // A fake HSM answers the first request with data outside of the buffer of the request
+    let (config, signals) = format_rings();
+    // SAFETY: The regions are leaked. The C client attaches the other ends.
+    let (mut consumer, mut producer) = unsafe {
+        (
+            RingConsumer::<RequestRaw, _>::attach(
+                config.requests,
+                REQUESTS_SIZE,
+                &signals.client,
+                &signals.requests,
+            )
+            .expect("failed to attach consumer"),
+            RingProducer::<ResponseRaw, _>::attach(
+                config.responses,
+                RESPONSES_SIZE,
+                &signals.client,
+                &signals.responses,
+            )
+            .expect("failed to attach producer"),
+        )
+    };
+    thread::spawn(move || {
+        block_on(async {
+            let request = consumer.pop().await.expect("failed to pop request");
+            let response: ResponseRaw = Response::GetRandom {
+                client_id: ClientId(request.client_id),
+                request_id: RequestId(request.request_id),
+                data: leak([1u8; 16]),
+            }
+            .try_into()
+            .expect("failed to convert response");
+            producer
+                .push(response)
+                .await
+                .expect("failed to push response");
+        })
+    });
+    run_with(heimlig_test_invalid_response, config);
+}
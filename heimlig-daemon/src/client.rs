@@ -0,0 +1,39 @@
+//! Blocking client connection to the daemon.
+
+use heimlig::common::jobs::Request;
+use heimlig::integration::wire::{self, read_frame, write_frame, FrameKind};
+use std::io;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+/// Connection to the daemon. Responses can arrive in a different order than their requests were
+/// sent. They have to be matched by their request ID.
+pub struct Client {
+    stream: UnixStream,
+}
+
+impl Client {
+    pub fn connect(socket_path: &Path) -> io::Result<Self> {
+        Ok(Client {
+            stream: UnixStream::connect(socket_path)?,
+        })
+    }
+
+    /// Send a request. The client ID of the request is ignored and replaced by the daemon.
+    pub fn send(&mut self, request: &Request) -> io::Result<()> {
+        let mut payload = vec![0u8; wire::request_size(request).map_err(invalid_data)?];
+        wire::encode_request(request, &mut payload).map_err(invalid_data)?;
+        write_frame(&mut self.stream, FrameKind::Request, &payload)
+    }
+
+    /// Receive the payload of the next response. It can be decoded with
+    /// [wire::decode_response].
+    pub fn recv(&mut self) -> io::Result<Vec<u8>> {
+        read_frame(&mut self.stream, FrameKind::Response)?
+            .ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+    }
+}
+
+fn invalid_data(error: wire::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
@@ -0,0 +1,307 @@
+//! Key store of the daemon that persists its keys in a file.
+//!
+//! The file is rewritten after every change of the key store. It starts with the magic bytes
+//! `"HKS2"` followed by one record per stored key:
+//!
+//! | Size | Field                 |
+//! |------|-----------------------|
+//! | 1    | Record type `0`       |
+//! | 4    | Key ID                |
+//! | 4    | Public key size       |
+//! | 4    | Private key size      |
+//! | n    | Public key            |
+//! | m    | Private key           |
+//!
+//! Symmetric keys are stored as private keys without a public key. The counters and protection
+//! flags of the SHE key slots are stored in one record per slot that was updated:
+//!
+//! | Size | Field                 |
+//! |------|-----------------------|
+//! | 1    | Record type `1`       |
+//! | 4    | Key ID of the slot    |
+//! | 4    | Counter               |
+//! | 1    | Flags                 |
+//! The keys are stored in plain
+//! text. The daemon is meant for development and testing, not to protect production keys.
+
+use heimlig::hsm::keystore::{
+    Curve, Error, InsecureKeyStore, KeyId, KeyInfo, KeyPermissions, KeyType,
+};
+use heimlig::hsm::she::{SheKeyFlags, SheSlotState, NUM_SLOTS};
+use heimlig::integration::memory_key_store::MemoryKeyStore;
+use log::error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const MAGIC: [u8; 4] = *b"HKS2";
+const KEY_RECORD: u8 = 0;
+const SHE_SLOT_RECORD: u8 = 1;
+
+const PERMISSIONS: KeyPermissions = KeyPermissions {
+    import: true,
+    export_private: true,
+    overwrite: true,
+    delete: true,
+};
+
+/// First key ID of the keys backing the SHE key slots
+pub const SHE_KEY_ID_OFFSET: u32 = 0x100;
+const SHE_KEY_SIZE: usize = 16;
+const NUM_GENERAL_PURPOSE_KEYS: usize = 8;
+const NUM_KEYS: usize = NUM_GENERAL_PURPOSE_KEYS + NUM_SLOTS;
+const STORAGE_SIZE: usize = 2 * 16 + 2 * 32 + 64 + 2 * 96 + 144 + NUM_SLOTS * SHE_KEY_SIZE;
+
+/// Keys available to the clients of the daemon: general purpose keys with the IDs 0 to 7 followed
+/// by the keys of the SHE slots
+pub const KEY_INFOS: [KeyInfo; NUM_KEYS] = {
+    let mut key_infos = [KeyInfo {
+        id: KeyId(0),
+        ty: KeyType::Symmetric(16),
+        permissions: PERMISSIONS,
+    }; NUM_KEYS];
+    let general_purpose: [KeyType; NUM_GENERAL_PURPOSE_KEYS] = [
+        KeyType::Symmetric(16),
+        KeyType::Symmetric(16),
+        KeyType::Symmetric(32),
+        KeyType::Symmetric(32),
+        KeyType::Symmetric(64),
+        KeyType::Asymmetric(Curve::NistP256),
+        KeyType::Asymmetric(Curve::NistP256),
+        KeyType::Asymmetric(Curve::NistP384),
+    ];
+    let mut i = 0;
+    while i < general_purpose.len() {
+        key_infos[i].id = KeyId(i as u32);
+        key_infos[i].ty = general_purpose[i];
+        i += 1;
+    }
+    let mut slot = 0;
+    while slot < NUM_SLOTS {
+        key_infos[i + slot].id = KeyId(SHE_KEY_ID_OFFSET + slot as u32);
+        key_infos[i + slot].ty = KeyType::Symmetric(SHE_KEY_SIZE);
+        slot += 1;
+    }
+    key_infos
+};
+
+/// Key IDs backing the SHE key slots
+pub fn she_key_ids() -> [KeyId; NUM_SLOTS] {
+    core::array::from_fn(|slot| KeyId(SHE_KEY_ID_OFFSET + slot as u32))
+}
+
+/// Key store with the fixed key layout [KEY_INFOS] that is written to a file on every change.
+/// It also keeps the SHE slot states of the keys returned by [she_key_ids].
+pub struct FileKeyStore {
+    keys: MemoryKeyStore<STORAGE_SIZE, NUM_KEYS>,
+    she_states: [Option<SheSlotState>; NUM_SLOTS],
+    path: PathBuf,
+}
+
+impl FileKeyStore {
+    /// Open the key store file at `path`. A missing file results in an empty key store.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let keys = MemoryKeyStore::try_new(&KEY_INFOS).map_err(invalid_data)?;
+        let mut key_store = FileKeyStore {
+            keys,
+            she_states: [None; NUM_SLOTS],
+            path: path.to_path_buf(),
+        };
+        match fs::read(path) {
+            Ok(contents) => key_store.load(&contents)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(key_store)
+    }
+
+    fn load(&mut self, contents: &[u8]) -> io::Result<()> {
+        let mut records = contents
+            .strip_prefix(&MAGIC)
+            .ok_or_else(|| invalid_data("invalid key store file"))?;
+        while !records.is_empty() {
+            match take(&mut records, 1)?[0] {
+                KEY_RECORD => self.load_key(&mut records)?,
+                SHE_SLOT_RECORD => {
+                    let id = KeyId(take_u32(&mut records)?);
+                    let counter = take_u32(&mut records)?;
+                    let flags = SheKeyFlags::from_bits(take(&mut records, 1)?[0]);
+                    let slot = she_slot(id).map_err(invalid_data)?;
+                    self.she_states[slot] = Some(SheSlotState { counter, flags });
+                }
+                _ => return Err(invalid_data("invalid key store record")),
+            }
+        }
+        Ok(())
+    }
+
+    fn load_key(&mut self, records: &mut &[u8]) -> io::Result<()> {
+        let id = KeyId(take_u32(records)?);
+        let public_key_size = take_u32(records)? as usize;
+        let private_key_size = take_u32(records)? as usize;
+        let public_key = take(records, public_key_size)?;
+        let private_key = take(records, private_key_size)?;
+        let key_info = self.keys.get_key_info(id).map_err(invalid_data)?;
+        if key_info.ty.is_symmetric() {
+            self.keys.import_symmetric_key_insecure(id, private_key)
+        } else {
+            self.keys
+                .import_key_pair_insecure(id, public_key, private_key)
+        }
+        .map_err(invalid_data)
+    }
+
+    fn store(&self) -> io::Result<()> {
+        let mut contents = MAGIC.to_vec();
+        let mut public_key = [0u8; KeyType::MAX_PUBLIC_KEY_SIZE];
+        let mut private_key = [0u8; KeyType::MAX_SYMMETRIC_KEY_SIZE];
+        for key_info in KEY_INFOS.iter() {
+            if !self.keys.is_key_available(key_info.id) {
+                continue;
+            }
+            let (public_key, private_key) = if key_info.ty.is_symmetric() {
+                let key = self
+                    .keys
+                    .export_symmetric_key_insecure(key_info.id, &mut private_key)
+                    .map_err(invalid_data)?;
+                (&[][..], key)
+            } else {
+                (
+                    self.keys
+                        .export_public_key_insecure(key_info.id, &mut public_key)
+                        .map_err(invalid_data)?,
+                    self.keys
+                        .export_private_key_insecure(key_info.id, &mut private_key)
+                        .map_err(invalid_data)?,
+                )
+            };
+            contents.push(KEY_RECORD);
+            contents.extend_from_slice(&key_info.id.0.to_le_bytes());
+            contents.extend_from_slice(&(public_key.len() as u32).to_le_bytes());
+            contents.extend_from_slice(&(private_key.len() as u32).to_le_bytes());
+            contents.extend_from_slice(public_key);
+            contents.extend_from_slice(private_key);
+        }
+        for (id, state) in she_key_ids().iter().zip(self.she_states) {
+            if let Some(state) = state {
+                contents.push(SHE_SLOT_RECORD);
+                contents.extend_from_slice(&id.0.to_le_bytes());
+                contents.extend_from_slice(&state.counter.to_le_bytes());
+                contents.push(state.flags.to_bits());
+            }
+        }
+        // Replace the file atomically to not lose all keys on a crash
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, contents)?;
+        fs::rename(tmp_path, &self.path)
+    }
+
+    /// Persist the key store after a successful change.
+    fn persist(&self, result: Result<(), Error>) -> Result<(), Error> {
+        result?;
+        if let Err(e) = self.store() {
+            // Keys stay usable until the daemon is restarted
+            error!(
+                "Failed to write key store file {}: {e}",
+                self.path.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl InsecureKeyStore for FileKeyStore {
+    fn get_key_info(&self, id: KeyId) -> Result<KeyInfo, Error> {
+        self.keys.get_key_info(id)
+    }
+
+    fn import_symmetric_key_insecure(&mut self, id: KeyId, data: &[u8]) -> Result<(), Error> {
+        let result = self.keys.import_symmetric_key_insecure(id, data);
+        self.persist(result)
+    }
+
+    fn import_key_pair_insecure(
+        &mut self,
+        id: KeyId,
+        public_key: &[u8],
+        private_key: &[u8],
+    ) -> Result<(), Error> {
+        let result = self
+            .keys
+            .import_key_pair_insecure(id, public_key, private_key);
+        self.persist(result)
+    }
+
+    fn export_symmetric_key_insecure<'data>(
+        &self,
+        id: KeyId,
+        dest: &'data mut [u8],
+    ) -> Result<&'data [u8], Error> {
+        self.keys.export_symmetric_key_insecure(id, dest)
+    }
+
+    fn export_public_key_insecure<'data>(
+        &self,
+        id: KeyId,
+        dest: &'data mut [u8],
+    ) -> Result<&'data [u8], Error> {
+        self.keys.export_public_key_insecure(id, dest)
+    }
+
+    fn export_private_key_insecure<'data>(
+        &self,
+        id: KeyId,
+        dest: &'data mut [u8],
+    ) -> Result<&'data [u8], Error> {
+        self.keys.export_private_key_insecure(id, dest)
+    }
+
+    fn delete_insecure(&mut self, id: KeyId) -> Result<(), Error> {
+        let result = self.keys.delete_insecure(id);
+        self.persist(result)
+    }
+
+    fn is_key_available(&self, id: KeyId) -> bool {
+        self.keys.is_key_available(id)
+    }
+
+    fn size(&self, id: KeyId) -> Result<usize, Error> {
+        self.keys.size(id)
+    }
+
+    fn store_she_slot_state(&mut self, id: KeyId, state: SheSlotState) -> Result<(), Error> {
+        let slot = she_slot(id)?;
+        self.she_states[slot] = Some(state);
+        self.persist(Ok(()))
+    }
+
+    fn she_slot_state(&self, id: KeyId) -> Option<SheSlotState> {
+        self.she_states[she_slot(id).ok()?]
+    }
+}
+
+/// Index of the SHE slot backed by the key `id`.
+fn she_slot(id: KeyId) -> Result<usize, Error> {
+    id.0.checked_sub(SHE_KEY_ID_OFFSET)
+        .map(|slot| slot as usize)
+        .filter(|slot| *slot < NUM_SLOTS)
+        .ok_or(Error::InvalidKeyId)
+}
+
+fn invalid_data<E: std::fmt::Display>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+fn take<'a>(records: &mut &'a [u8], size: usize) -> io::Result<&'a [u8]> {
+    if size > records.len() {
+        return Err(invalid_data("truncated key store file"));
+    }
+    let (data, rest) = records.split_at(size);
+    *records = rest;
+    Ok(data)
+}
+
+fn take_u32(records: &mut &[u8]) -> io::Result<u32> {
+    let bytes = take(records, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
@@ -0,0 +1,9 @@
+//! Linux daemon hosting the Heimlig core with all software workers.
+//!
+//! Clients connect to the daemon through a Unix domain socket and exchange requests and responses
+//! in the binary format of [heimlig::integration::wire]. Keys are kept in a
+//! [key_store::FileKeyStore].
+
+pub mod client;
+pub mod key_store;
+pub mod server;
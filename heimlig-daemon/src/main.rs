@@ -0,0 +1,35 @@
+use clap::Parser;
+use heimlig_daemon::server::Daemon;
+use log::{error, info};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Serve Heimlig to local clients over a Unix domain socket
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Path of the Unix socket to listen on
+    #[arg(short, long, default_value = "/tmp/heimlig.sock")]
+    socket: PathBuf,
+    /// File the keys are stored in
+    #[arg(short, long, default_value = "heimlig-keys.bin")]
+    key_store: PathBuf,
+}
+
+fn main() -> ExitCode {
+    simple_logger::init_with_level(log::Level::Info).expect("failed to initialize logger");
+    let args = Args::parse();
+    let daemon = match Daemon::bind(&args.socket, &args.key_store) {
+        Ok(daemon) => daemon,
+        Err(e) => {
+            error!("Failed to start daemon: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    info!("Listening on {}", args.socket.display());
+    if let Err(e) = daemon.run() {
+        error!("Daemon stopped: {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
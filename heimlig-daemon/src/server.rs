@@ -0,0 +1,344 @@
+//! Socket server forwarding the requests of connected clients to the Heimlig core.
+//!
+//! The core runs on its own thread and hosts all software workers. It is set up with a fixed
+//! number of client channels. Every accepted connection occupies one of them, so the index of the
+//! channel is the [ClientId] of the connection. Each connection is served by a reader thread
+//! decoding requests and a writer thread encoding responses. The buffers of a request are
+//! allocated when it is decoded and freed once its response was encoded.
+
+use crate::key_store::{she_key_ids, FileKeyStore};
+use futures::channel::{mpsc, oneshot};
+use futures::executor::block_on;
+use futures::future::{select, Either};
+use futures::{SinkExt, StreamExt};
+use heimlig::common::jobs::{ClientId, Request, Response};
+use heimlig::crypto::she::UID_SIZE;
+use heimlig::hsm::core::{Builder, ClientConfig};
+use heimlig::hsm::she::SheKeySlots;
+use heimlig::hsm::workers::{
+    aes_worker::AesWorker, chachapoly_worker::ChaChaPolyWorker, ecc_worker::EccWorker,
+    hmac_worker::HmacWorker, rng_worker::RngWorker, she_worker::SheWorker,
+};
+use heimlig::integration::std_sync::{Mutex, StdRawMutex};
+use heimlig::integration::wire::{self, read_frame, write_frame, FrameKind};
+use log::{debug, error, info, warn};
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use std::thread;
+
+/// Maximum number of simultaneously connected clients
+pub const MAX_CLIENTS: usize = 8;
+/// Number of requests and responses buffered per client
+const QUEUE_SIZE: usize = 16;
+/// Unique ID of the SHE implementation of the daemon
+pub const SHE_UID: [u8; UID_SIZE] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+
+type RequestSender = mpsc::Sender<Request<'static>>;
+type ResponseReceiver = mpsc::Receiver<Response<'static>>;
+
+/// Daemon-side ends of the channels of a client
+struct ClientChannels {
+    requests: RequestSender,
+    responses: ResponseReceiver,
+}
+
+/// Channels of all clients. A slot is empty while its client is connected.
+type Slots = [std::sync::Mutex<Option<ClientChannels>>; MAX_CLIENTS];
+
+pub struct Daemon {
+    listener: UnixListener,
+    slots: Arc<Slots>,
+}
+
+impl Daemon {
+    /// Open the key store, start the core and listen on the Unix socket at `socket_path`. A stale
+    /// socket file left behind by a previous instance is replaced.
+    pub fn bind(socket_path: &Path, key_store_path: &Path) -> io::Result<Self> {
+        let key_store = FileKeyStore::open(key_store_path)?;
+        match std::fs::remove_file(socket_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        let listener = UnixListener::bind(socket_path)?;
+
+        let mut core_channels = Vec::with_capacity(MAX_CLIENTS);
+        let slots: Slots = std::array::from_fn(|_| {
+            let (requests, core_requests) = mpsc::channel(QUEUE_SIZE);
+            let (core_responses, responses) = mpsc::channel(QUEUE_SIZE);
+            core_channels.push((core_requests, core_responses));
+            std::sync::Mutex::new(Some(ClientChannels {
+                requests,
+                responses,
+            }))
+        });
+        thread::Builder::new()
+            .name("core".into())
+            .spawn(move || run_core(key_store, core_channels))?;
+        Ok(Daemon {
+            listener,
+            slots: Arc::new(slots),
+        })
+    }
+
+    /// Accept client connections until the listener fails.
+    pub fn run(self) -> io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept()?;
+            let Some((client_id, channels)) = self.take_slot() else {
+                warn!("Rejected connection: Maximum number of clients reached");
+                continue;
+            };
+            info!("Client {} connected", client_id.0);
+            let slots = self.slots.clone();
+            thread::Builder::new()
+                .name(format!("client-{}", client_id.0))
+                .spawn(move || {
+                    let channels = serve(stream, client_id, channels);
+                    info!("Client {} disconnected", client_id.0);
+                    *lock(&slots[client_id.idx()]) = Some(channels);
+                })?;
+        }
+    }
+
+    fn take_slot(&self) -> Option<(ClientId, ClientChannels)> {
+        self.slots.iter().enumerate().find_map(|(i, slot)| {
+            lock(slot)
+                .take()
+                .map(|channels| (ClientId(i as u32), channels))
+        })
+    }
+}
+
+fn lock<T>(mutex: &std::sync::Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+fn run_core(
+    key_store: FileKeyStore,
+    channels: Vec<(
+        mpsc::Receiver<Request<'static>>,
+        mpsc::Sender<Response<'static>>,
+    )>,
+) {
+    let mut key_store = key_store;
+    let mut she_slots = SheKeySlots::new(SHE_UID, she_key_ids());
+    she_slots.restore_slot_states(&key_store);
+    let key_store: Mutex<_> = Mutex::new(&mut key_store);
+    let rng: Mutex<_> = Mutex::new(ChaCha20Rng::from_entropy());
+    let she_slots: Mutex<_> = Mutex::new(she_slots);
+    let rng_worker = RngWorker {
+        rng: &rng,
+        key_store: Some(&key_store),
+    };
+    let ecc_worker = EccWorker {
+        rng: &rng,
+        key_store: &key_store,
+    };
+    let aes_worker = AesWorker {
+        key_store: &key_store,
+        she_slots: Some(&she_slots),
+    };
+    let chachapoly_worker = ChaChaPolyWorker {
+        key_store: &key_store,
+    };
+    let hmac_worker = HmacWorker {
+        key_store: &key_store,
+    };
+    let she_worker = SheWorker {
+        key_store: &key_store,
+        slots: &she_slots,
+    };
+
+    let mut builder = Builder::<StdRawMutex, _, _, _>::new().with_keystore(&key_store);
+    for (requests, responses) in channels {
+        builder = builder
+            .with_client(requests, responses, ClientConfig::default())
+            .expect("failed to add client");
+    }
+    let mut core = builder
+        .with_hosted_worker(rng_worker)
+        .and_then(|builder| builder.with_hosted_worker(ecc_worker))
+        .and_then(|builder| builder.with_hosted_worker(aes_worker))
+        .and_then(|builder| builder.with_hosted_worker(chachapoly_worker))
+        .and_then(|builder| builder.with_hosted_worker(hmac_worker))
+        .and_then(|builder| builder.with_hosted_worker(she_worker))
+        .expect("failed to add hosted workers")
+        .build();
+
+    block_on(async {
+        loop {
+            if let Err(e) = core.execute().await {
+                error!("Core failed to process job: {e:?}");
+            }
+        }
+    })
+}
+
+/// Buffers of a request forwarded to the core
+struct Arena(NonNull<[u8]>);
+
+// SAFETY: The arena exclusively owns its allocation.
+unsafe impl Send for Arena {}
+
+impl Arena {
+    fn new(size: usize) -> Self {
+        Arena(NonNull::from(Box::leak(vec![0u8; size].into_boxed_slice())))
+    }
+
+    /// Access the buffers for the lifetime of the request they are passed with.
+    ///
+    /// # Safety
+    ///
+    /// Must be called at most once and the returned slice must not be used after [Arena::free].
+    unsafe fn data(&mut self) -> &'static mut [u8] {
+        self.0.as_mut()
+    }
+
+    /// # Safety
+    ///
+    /// The request whose buffers were taken from the arena and its response must not be used
+    /// anymore.
+    unsafe fn free(self) {
+        drop(Box::from_raw(self.0.as_ptr()))
+    }
+}
+
+/// Arenas of the requests of a client whose responses were not yet sent
+type PendingRequests = std::sync::Mutex<HashMap<u32, Arena>>;
+
+/// Serve a client until it disconnects and all of its requests were answered.
+fn serve(stream: UnixStream, client_id: ClientId, channels: ClientChannels) -> ClientChannels {
+    let ClientChannels {
+        mut requests,
+        responses,
+    } = channels;
+    let pending = PendingRequests::default();
+    let (closed_tx, closed_rx) = oneshot::channel();
+    let responses = thread::scope(|s| {
+        let writer = match stream.try_clone() {
+            Ok(writer) => writer,
+            Err(e) => {
+                error!("Failed to clone socket of client {}: {e}", client_id.0);
+                return responses;
+            }
+        };
+        let writer = s.spawn(|| write_responses(writer, responses, &pending, closed_rx));
+        if let Err(e) = read_requests(&stream, &mut requests, &pending) {
+            warn!("Closing connection to client {}: {e}", client_id.0);
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+        let _ = closed_tx.send(());
+        writer.join().expect("writer thread panicked")
+    });
+    ClientChannels {
+        requests,
+        responses,
+    }
+}
+
+fn read_requests(
+    mut stream: &UnixStream,
+    requests: &mut RequestSender,
+    pending: &PendingRequests,
+) -> io::Result<()> {
+    while let Some(payload) = read_frame(&mut stream, FrameKind::Request)? {
+        let data_size = wire::request_data_size(&payload).map_err(invalid_data)?;
+        if data_size > wire::MAX_PAYLOAD_SIZE {
+            return Err(invalid_data(wire::Error::TooLarge));
+        }
+        let mut arena = Arena::new(data_size);
+        // SAFETY: The arena is freed after the response to the request was received.
+        let request = match wire::decode_request(&payload, unsafe { arena.data() }) {
+            Ok(request) => request,
+            Err(e) => {
+                // SAFETY: The request was not decoded.
+                unsafe { arena.free() };
+                return Err(invalid_data(e));
+            }
+        };
+        let request_id = request.get_request_id();
+        debug!("Received {:?} request {}", request.get_type(), request_id.0);
+        {
+            let mut pending = lock(pending);
+            if pending.contains_key(&request_id.0) {
+                // SAFETY: The request is discarded.
+                unsafe { arena.free() };
+                return Err(invalid_data("request ID is already in use"));
+            }
+            pending.insert(request_id.0, arena);
+        }
+        if block_on(requests.send(request)).is_err() {
+            // The request was dropped together with the channel
+            if let Some(arena) = lock(pending).remove(&request_id.0) {
+                // SAFETY: See above
+                unsafe { arena.free() };
+            }
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "core stopped"));
+        }
+    }
+    Ok(())
+}
+
+/// Send responses to the client. After the client disconnected, responses are received until all
+/// pending requests are answered.
+fn write_responses(
+    mut stream: UnixStream,
+    mut responses: ResponseReceiver,
+    pending: &PendingRequests,
+    mut closed: oneshot::Receiver<()>,
+) -> ResponseReceiver {
+    let mut is_closed = false;
+    loop {
+        if is_closed && lock(pending).is_empty() {
+            return responses;
+        }
+        let response = if is_closed {
+            block_on(responses.next())
+        } else {
+            match block_on(select(responses.next(), &mut closed)) {
+                Either::Left((response, _)) => response,
+                Either::Right(_) => {
+                    is_closed = true;
+                    continue;
+                }
+            }
+        };
+        let Some(response) = response else {
+            error!("Core stopped sending responses");
+            return responses;
+        };
+        let request_id = response.get_request_id();
+        let payload = encode_response(&response);
+        if let Some(arena) = lock(pending).remove(&request_id.0) {
+            // SAFETY: The response referencing the buffers of the request is not used anymore.
+            unsafe { arena.free() };
+        }
+        match payload {
+            Ok(payload) => {
+                // The client may have closed only its sending side of the connection
+                if let Err(e) = write_frame(&mut stream, FrameKind::Response, &payload) {
+                    debug!("Failed to send response {}: {e}", request_id.0);
+                }
+            }
+            Err(e) => error!("Failed to encode response {}: {e}", request_id.0),
+        }
+    }
+}
+
+fn encode_response(response: &Response) -> Result<Vec<u8>, wire::Error> {
+    let mut payload = vec![0u8; wire::response_size(response)?];
+    wire::encode_response(response, &mut payload)?;
+    Ok(payload)
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
@@ -0,0 +1,279 @@
+use heimlig::common::jobs::{self, ClientId, Request, RequestId, Response};
+use heimlig::crypto::she::{
+    calculate_m1, calculate_m2, calculate_m3, M1_SIZE, M2_SIZE, M3_SIZE, M4_SIZE, M5_SIZE,
+};
+use heimlig::hsm::keystore::KeyId;
+use heimlig::hsm::she::{self, SheKeyFlags, SheKeyId};
+use heimlig::integration::wire::{decode_response, write_frame, FrameKind};
+use heimlig_daemon::client::Client;
+use heimlig_daemon::key_store::she_key_ids;
+use heimlig_daemon::server::{Daemon, SHE_UID};
+use std::io::Read;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::thread;
+use tempfile::TempDir;
+
+const AES_KEY_ID: KeyId = KeyId(2);
+const MASTER_ECU_KEY: [u8; 16] = [1u8; 16];
+
+struct TestDaemon {
+    dir: TempDir,
+}
+
+impl TestDaemon {
+    fn start() -> Self {
+        let dir = tempfile::tempdir().expect("failed to create temporary directory");
+        let test_daemon = TestDaemon { dir };
+        test_daemon.restart();
+        test_daemon
+    }
+
+    /// Start a new daemon on the same socket and key store file.
+    fn restart(&self) {
+        let daemon = Daemon::bind(&self.socket(), &self.dir.path().join("keys.bin"))
+            .expect("failed to start daemon");
+        thread::spawn(move || daemon.run());
+    }
+
+    fn socket(&self) -> PathBuf {
+        self.dir.path().join("heimlig.sock")
+    }
+
+    fn connect(&self) -> Client {
+        Client::connect(&self.socket()).expect("failed to connect")
+    }
+}
+
+fn call(client: &mut Client, request: Request) -> Vec<u8> {
+    client.send(&request).expect("failed to send request");
+    client.recv().expect("failed to receive response")
+}
+
+fn is_key_available(socket: &Path, key_id: KeyId) -> bool {
+    let mut client = Client::connect(socket).expect("failed to connect");
+    let mut payload = call(
+        &mut client,
+        Request::IsKeyAvailable {
+            client_id: ClientId(0),
+            request_id: RequestId(0),
+            deadline: None,
+            key_id,
+        },
+    );
+    let Ok(Response::IsKeyAvailable { is_available, .. }) = decode_response(&mut payload) else {
+        panic!("Unexpected response type")
+    };
+    is_available
+}
+
+/// Update the SHE slot `KEY_1` with the master ECU key.
+fn load_she_key(client: &mut Client, counter: u32, flags: SheKeyFlags) -> Result<(), jobs::Error> {
+    let auth_key = MASTER_ECU_KEY;
+    let mut m1 = [0u8; M1_SIZE];
+    let mut m2 = [0u8; M2_SIZE];
+    let mut m3 = [0u8; M3_SIZE];
+    calculate_m1(
+        &SHE_UID,
+        SheKeyId::Key1 as u8,
+        SheKeyId::MasterEcuKey as u8,
+        &mut m1,
+    )
+    .expect("failed to calculate M1");
+    calculate_m2(&auth_key, counter, flags.to_bits(), &[2u8; 16], &mut m2)
+        .expect("failed to calculate M2");
+    calculate_m3(&auth_key, &m1, &m2, &mut m3).expect("failed to calculate M3");
+    let mut m4 = [0u8; M4_SIZE];
+    let mut m5 = [0u8; M5_SIZE];
+    let mut payload = call(
+        client,
+        Request::LoadSheKey {
+            client_id: ClientId(0),
+            request_id: RequestId(counter),
+            deadline: None,
+            m1: &m1,
+            m2: &m2,
+            m3: &m3,
+            m4: &mut m4,
+            m5: &mut m5,
+        },
+    );
+    match decode_response(&mut payload) {
+        Ok(Response::LoadSheKey { .. }) => Ok(()),
+        Ok(Response::Error { error, .. }) => Err(error),
+        _ => panic!("Unexpected response type"),
+    }
+}
+
+#[test]
+fn get_random() {
+    let daemon = TestDaemon::start();
+    let mut client = daemon.connect();
+    let mut output = [0u8; 32];
+    let mut payload = call(
+        &mut client,
+        Request::GetRandom {
+            client_id: ClientId(0),
+            request_id: RequestId(7),
+            deadline: None,
+            output: &mut output,
+        },
+    );
+    let Ok(Response::GetRandom {
+        client_id: _,
+        request_id,
+        data,
+    }) = decode_response(&mut payload)
+    else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(request_id, RequestId(7));
+    assert_eq!(data.len(), 32);
+}
+
+#[test]
+fn clients_get_own_ids() {
+    let daemon = TestDaemon::start();
+    let mut first = daemon.connect();
+    let mut second = daemon.connect();
+    let mut client_ids = Vec::new();
+    for client in [&mut first, &mut second] {
+        let mut payload = call(
+            client,
+            Request::IsKeyAvailable {
+                client_id: ClientId(42),
+                request_id: RequestId(0),
+                deadline: None,
+                key_id: AES_KEY_ID,
+            },
+        );
+        let response = decode_response(&mut payload).expect("failed to decode response");
+        client_ids.push(response.get_client_id());
+    }
+    assert_ne!(client_ids[0], client_ids[1]);
+    assert!(!client_ids.contains(&ClientId(42)));
+}
+
+#[test]
+fn encrypt_with_persistent_key() {
+    let daemon = TestDaemon::start();
+    let mut client = daemon.connect();
+    let mut payload = call(
+        &mut client,
+        Request::GenerateSymmetricKey {
+            client_id: ClientId(0),
+            request_id: RequestId(0),
+            deadline: None,
+            key_id: AES_KEY_ID,
+            overwrite: false,
+        },
+    );
+    assert!(matches!(
+        decode_response(&mut payload),
+        Ok(Response::GenerateSymmetricKey { .. })
+    ));
+
+    let plaintext = *b"You Shall Not Pass!";
+    let iv = [0u8; 12];
+    let mut buffer = plaintext;
+    let mut tag = [0u8; 16];
+    let mut payload = call(
+        &mut client,
+        Request::EncryptAesGcm {
+            client_id: ClientId(0),
+            request_id: RequestId(1),
+            deadline: None,
+            key_id: AES_KEY_ID,
+            iv: &iv,
+            buffer: &mut buffer,
+            aad: &[],
+            tag: &mut tag,
+        },
+    );
+    let Ok(Response::EncryptAesGcm { buffer, tag, .. }) = decode_response(&mut payload) else {
+        panic!("Unexpected response type")
+    };
+    let mut ciphertext = buffer.to_vec();
+    let tag = tag.to_vec();
+    assert_ne!(ciphertext, plaintext);
+    drop(client);
+
+    // The key is still available to a new daemon instance
+    daemon.restart();
+    assert!(is_key_available(&daemon.socket(), AES_KEY_ID));
+    let mut client = daemon.connect();
+    let mut payload = call(
+        &mut client,
+        Request::DecryptAesGcm {
+            client_id: ClientId(0),
+            request_id: RequestId(2),
+            deadline: None,
+            key_id: AES_KEY_ID,
+            iv: &iv,
+            buffer: &mut ciphertext,
+            aad: &[],
+            tag: &tag,
+        },
+    );
+    let Ok(Response::DecryptAesGcm { buffer, .. }) = decode_response(&mut payload) else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(buffer, plaintext);
+}
+
+#[test]
+fn invalid_frame_closes_connection() {
+    let daemon = TestDaemon::start();
+    let mut stream = UnixStream::connect(daemon.socket()).expect("failed to connect");
+    write_frame(&mut stream, FrameKind::Request, &[0xFF; 32]).expect("failed to send frame");
+    let mut buf = [0u8; 1];
+    assert_eq!(stream.read(&mut buf).expect("failed to read"), 0);
+
+    // The daemon keeps serving other clients
+    assert!(!is_key_available(&daemon.socket(), AES_KEY_ID));
+}
+
+#[test]
+fn she_slot_states_survive_restart() {
+    let daemon = TestDaemon::start();
+    let mut client = daemon.connect();
+    let mut payload = call(
+        &mut client,
+        Request::ImportSymmetricKey {
+            client_id: ClientId(0),
+            request_id: RequestId(0),
+            deadline: None,
+            key_id: she_key_ids()[SheKeyId::MasterEcuKey.idx()],
+            data: &MASTER_ECU_KEY,
+            overwrite: false,
+        },
+    );
+    assert!(matches!(
+        decode_response(&mut payload),
+        Ok(Response::ImportSymmetricKey { .. })
+    ));
+    load_she_key(&mut client, 1, SheKeyFlags::default()).expect("failed to load SHE key");
+    drop(client);
+
+    // The counter is restored, so the update cannot be replayed
+    daemon.restart();
+    let mut client = daemon.connect();
+    assert_eq!(
+        load_she_key(&mut client, 1, SheKeyFlags::default()),
+        Err(jobs::Error::She(she::Error::KeyUpdateError))
+    );
+    let write_protected = SheKeyFlags {
+        write_protection: true,
+        ..Default::default()
+    };
+    load_she_key(&mut client, 2, write_protected).expect("failed to load SHE key");
+    drop(client);
+
+    // The protection flags are restored as well
+    daemon.restart();
+    let mut client = daemon.connect();
+    assert_eq!(
+        load_she_key(&mut client, 3, SheKeyFlags::default()),
+        Err(jobs::Error::She(she::Error::KeyWriteProtected))
+    );
+}
@@ -3,13 +3,37 @@ fn main() {
         // Generate C++ header for raw types
         let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
         cbindgen::Builder::new()
-            .with_crate(crate_dir)
+            .with_crate(&crate_dir)
             .with_language(cbindgen::Language::Cxx)
             .with_no_includes()
             .with_namespaces(&["hsm"])
             .with_include_guard("RAW_JOBS_H")
+            .exclude_item("she_execute")
+            .exclude_item("SHE_NUM_SLOTS")
+            .exclude_item("SheClientRaw")
+            .exclude_item("SheCommandRaw")
+            .exclude_item("SheErrorCodeRaw")
+            .exclude_item("SheResponseRaw")
             .generate()
             .expect("Unable to generate bindings")
             .write_to_file("target/RawJobs.h");
+
+        // Generate C header for the SHE command set facade. Plain C enums share one namespace, so
+        // variants are prefixed with the enum name. The transport is set up by the integrator, so
+        // it is opaque to SHE applications.
+        let mut she_config = cbindgen::Config::default();
+        she_config.enumeration.prefix_with_name = true;
+        she_config.after_includes =
+            Some("typedef struct BlockingTransportRaw BlockingTransportRaw;".into());
+        cbindgen::Builder::new()
+            .with_config(she_config)
+            .with_src(format!("{crate_dir}/src/integration/raw_she.rs"))
+            .with_language(cbindgen::Language::C)
+            .with_sys_include("stdint.h")
+            .with_no_includes()
+            .with_include_guard("SHE_COMMANDS_H")
+            .generate()
+            .expect("Unable to generate bindings")
+            .write_to_file("target/SheCommands.h");
     }
 }
@@ -1,4 +1,7 @@
-use crate::common::jobs::{ClientId, HashAlgorithm, Request, RequestId, Response};
+use crate::common::jobs::{
+    ClientId, HashAlgorithm, Padding, PrivateKeyFormat, PublicKeyFormat, Request, RequestId,
+    Response, SignatureFormat,
+};
 use crate::hsm::keystore::KeyId;
 use futures::{Sink, SinkExt, Stream, StreamExt};
 
@@ -12,6 +15,7 @@ pub struct Api<'data, Req: Sink<Request<'data>>, Resp: Stream<Item = Response<'d
     requests: Req,
     responses: Resp,
     request_id_counter: RequestId,
+    deadline: Option<u64>,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -19,6 +23,225 @@ pub enum SymmetricAlgorithm {
     ChaCha20Poly1305,
     AesGcm,
     AesCbc,
+    AesCbcNoPadding,
+    AesEcb,
+}
+
+impl SymmetricAlgorithm {
+    fn padding(&self) -> Padding {
+        match self {
+            SymmetricAlgorithm::AesCbcNoPadding => Padding::NoPadding,
+            _ => Padding::Pkcs7,
+        }
+    }
+
+    /// Build the request of [Api::encrypt_in_place].
+    pub(crate) fn encrypt_request<'data>(
+        &self,
+        key_id: KeyId,
+        nonce: &'data [u8],
+        plaintext_size: usize,
+        buffer: &'data mut [u8],
+        aad: &'data [u8],
+        tag: &'data mut [u8],
+    ) -> Request<'data> {
+        match self {
+            SymmetricAlgorithm::ChaCha20Poly1305 => Request::EncryptChaChaPoly {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                nonce,
+                buffer,
+                aad,
+                tag,
+            },
+            SymmetricAlgorithm::AesGcm => Request::EncryptAesGcm {
+                client_id: Default::default(),
+                request_id: Default::default(),
+                deadline: None,
+                key_id,
+                iv: nonce,
+                buffer,
+                aad,
+                tag,
+            },
+            SymmetricAlgorithm::AesCbc | SymmetricAlgorithm::AesCbcNoPadding => {
+                Request::EncryptAesCbc {
+                    client_id: Default::default(),
+                    request_id: Default::default(),
+                    deadline: None,
+                    key_id,
+                    iv: nonce,
+                    buffer,
+                    plaintext_size,
+                    padding: self.padding(),
+                }
+            }
+            SymmetricAlgorithm::AesEcb => Request::EncryptAesEcb {
+                client_id: Default::default(),
+                request_id: Default::default(),
+                deadline: None,
+                key_id,
+                buffer,
+            },
+        }
+    }
+
+    /// Build the request of [Api::encrypt_in_place_external_key].
+    pub(crate) fn encrypt_external_key_request<'data>(
+        &self,
+        key: &'data [u8],
+        nonce: &'data [u8],
+        plaintext_size: usize,
+        buffer: &'data mut [u8],
+        aad: &'data [u8],
+        tag: &'data mut [u8],
+    ) -> Request<'data> {
+        match self {
+            SymmetricAlgorithm::ChaCha20Poly1305 => Request::EncryptChaChaPolyExternalKey {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key,
+                nonce,
+                buffer,
+                aad,
+                tag,
+            },
+            SymmetricAlgorithm::AesGcm => Request::EncryptAesGcmExternalKey {
+                client_id: Default::default(),
+                request_id: Default::default(),
+                deadline: None,
+                key,
+                iv: nonce,
+                buffer,
+                aad,
+                tag,
+            },
+            SymmetricAlgorithm::AesCbc | SymmetricAlgorithm::AesCbcNoPadding => {
+                Request::EncryptAesCbcExternalKey {
+                    client_id: Default::default(),
+                    request_id: Default::default(),
+                    deadline: None,
+                    key,
+                    iv: nonce,
+                    buffer,
+                    plaintext_size,
+                    padding: self.padding(),
+                }
+            }
+            SymmetricAlgorithm::AesEcb => Request::EncryptAesEcbExternalKey {
+                client_id: Default::default(),
+                request_id: Default::default(),
+                deadline: None,
+                key,
+                buffer,
+            },
+        }
+    }
+
+    /// Build the request of [Api::decrypt_in_place].
+    pub(crate) fn decrypt_request<'data>(
+        &self,
+        key_id: KeyId,
+        nonce: &'data [u8],
+        buffer: &'data mut [u8],
+        aad: &'data [u8],
+        tag: &'data [u8],
+    ) -> Request<'data> {
+        match self {
+            SymmetricAlgorithm::ChaCha20Poly1305 => Request::DecryptChaChaPoly {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                nonce,
+                buffer,
+                aad,
+                tag,
+            },
+            SymmetricAlgorithm::AesGcm => Request::DecryptAesGcm {
+                client_id: Default::default(),
+                request_id: Default::default(),
+                deadline: None,
+                key_id,
+                iv: nonce,
+                buffer,
+                aad,
+                tag,
+            },
+            SymmetricAlgorithm::AesCbc | SymmetricAlgorithm::AesCbcNoPadding => {
+                Request::DecryptAesCbc {
+                    client_id: Default::default(),
+                    request_id: Default::default(),
+                    deadline: None,
+                    key_id,
+                    iv: nonce,
+                    buffer,
+                    padding: self.padding(),
+                }
+            }
+            SymmetricAlgorithm::AesEcb => Request::DecryptAesEcb {
+                client_id: Default::default(),
+                request_id: Default::default(),
+                deadline: None,
+                key_id,
+                buffer,
+            },
+        }
+    }
+
+    /// Build the request of [Api::decrypt_in_place_external_key].
+    pub(crate) fn decrypt_external_key_request<'data>(
+        &self,
+        key: &'data [u8],
+        nonce: &'data [u8],
+        buffer: &'data mut [u8],
+        aad: &'data [u8],
+        tag: &'data [u8],
+    ) -> Request<'data> {
+        match self {
+            SymmetricAlgorithm::ChaCha20Poly1305 => Request::DecryptChaChaPolyExternalKey {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key,
+                nonce,
+                buffer,
+                aad,
+                tag,
+            },
+            SymmetricAlgorithm::AesGcm => Request::DecryptAesGcmExternalKey {
+                client_id: Default::default(),
+                request_id: Default::default(),
+                deadline: None,
+                key,
+                iv: nonce,
+                buffer,
+                aad,
+                tag,
+            },
+            SymmetricAlgorithm::AesCbc | SymmetricAlgorithm::AesCbcNoPadding => {
+                Request::DecryptAesCbcExternalKey {
+                    client_id: Default::default(),
+                    request_id: Default::default(),
+                    deadline: None,
+                    key,
+                    iv: nonce,
+                    buffer,
+                    padding: self.padding(),
+                }
+            }
+            SymmetricAlgorithm::AesEcb => Request::DecryptAesEcbExternalKey {
+                client_id: Default::default(),
+                request_id: Default::default(),
+                deadline: None,
+                key,
+                buffer,
+            },
+        }
+    }
 }
 
 impl<
@@ -33,9 +256,16 @@ impl<
             requests,
             responses,
             request_id_counter: RequestId::default(),
+            deadline: None,
         }
     }
 
+    /// Set the deadline for all following requests in ticks of the core clock. Requests that are
+    /// not dispatched before their deadline are answered with [Response::Cancelled].
+    pub fn set_deadline(&mut self, deadline: Option<u64>) {
+        self.deadline = deadline;
+    }
+
     /// Attempt to poll a response and return it.
     pub async fn recv_response<'api>(&'api mut self) -> Option<Response<'data>> {
         self.responses.next().await
@@ -46,6 +276,7 @@ impl<
         let request = Request::GetRandom {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             output,
         };
         self.send_request(request).await
@@ -60,6 +291,7 @@ impl<
         let request = Request::GenerateSymmetricKey {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key_id,
             overwrite,
         };
@@ -75,6 +307,7 @@ impl<
         let request = Request::GenerateKeyPair {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key_id,
             overwrite,
         };
@@ -91,6 +324,7 @@ impl<
         let request = Request::ImportSymmetricKey {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key_id,
             data,
             overwrite,
@@ -99,19 +333,24 @@ impl<
     }
 
     /// Import an asymmetric key pair into the HSM.
+    /// For encoded private key formats, `public_key` has to be empty as it is derived from the
+    /// private key.
     pub async fn import_key_pair(
         &mut self,
         key_id: KeyId,
         public_key: &'data [u8],
         private_key: &'data [u8],
+        format: PrivateKeyFormat,
         overwrite: bool,
     ) -> Result<RequestId, Error> {
         let request = Request::ImportKeyPair {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key_id,
             public_key,
             private_key,
+            format,
             overwrite,
         };
         self.send_request(request).await
@@ -126,23 +365,27 @@ impl<
         let request = Request::ExportSymmetricKey {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key_id,
             data,
         };
         self.send_request(request).await
     }
 
-    /// Export an asymmetric public key that is stored in the HSM.
+    /// Export an asymmetric public key that is stored in the HSM in the given format.
     pub async fn export_public_key(
         &mut self,
         key_id: KeyId,
         public_key: &'data mut [u8],
+        format: PublicKeyFormat,
     ) -> Result<RequestId, Error> {
         let request = Request::ExportPublicKey {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key_id,
             public_key,
+            format,
         };
         self.send_request(request).await
     }
@@ -157,6 +400,7 @@ impl<
         let request = Request::ExportPrivateKey {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key_id,
             private_key,
         };
@@ -168,6 +412,18 @@ impl<
         let request = Request::IsKeyAvailable {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
+            key_id,
+        };
+        self.send_request(request).await
+    }
+
+    /// Query the type and the permissions of the key with the given `KeyId`.
+    pub async fn get_key_info(&mut self, key_id: KeyId) -> Result<RequestId, Error> {
+        let request = Request::GetKeyInfo {
+            client_id: ClientId::default(),
+            request_id: RequestId::default(),
+            deadline: None,
             key_id,
         };
         self.send_request(request).await
@@ -179,7 +435,7 @@ impl<
     ///
     /// * `algorithm`: The `SymmetricEncryptionAlgorithm` to be used
     /// * `key_id`: The key identifier to use
-    /// * `nonce`: The 'Number used once' to use
+    /// * `nonce`: The 'Number used once' to use (ignored by AES-ECB)
     /// * `plaintext_size`: Used for algorithms that require padding (e.g. AES-CBC) only.
     ///   Indicates the size of the actual plaintext located in `buffer` starting from the beginning.
     /// * `buffer`: The buffer containing the plaintext and room for padding (if needed)
@@ -196,34 +452,7 @@ impl<
         aad: &'data [u8],
         tag: &'data mut [u8],
     ) -> Result<RequestId, Error> {
-        let request = match algorithm {
-            SymmetricAlgorithm::ChaCha20Poly1305 => Request::EncryptChaChaPoly {
-                client_id: ClientId::default(),
-                request_id: RequestId::default(),
-                key_id,
-                nonce,
-                buffer,
-                aad,
-                tag,
-            },
-            SymmetricAlgorithm::AesGcm => Request::EncryptAesGcm {
-                client_id: Default::default(),
-                request_id: Default::default(),
-                key_id,
-                iv: nonce,
-                buffer,
-                aad,
-                tag,
-            },
-            SymmetricAlgorithm::AesCbc => Request::EncryptAesCbc {
-                client_id: Default::default(),
-                request_id: Default::default(),
-                key_id,
-                iv: nonce,
-                buffer,
-                plaintext_size,
-            },
-        };
+        let request = algorithm.encrypt_request(key_id, nonce, plaintext_size, buffer, aad, tag);
         self.send_request(request).await
     }
 
@@ -233,7 +462,7 @@ impl<
     ///
     /// * `algorithm`: The `SymmetricEncryptionAlgorithm` to be used
     /// * `key`: The key to use
-    /// * `nonce`: The 'Number used once' to use
+    /// * `nonce`: The 'Number used once' to use (ignored by AES-ECB)
     /// * `plaintext_size`: Used for algorithms that require padding (e.g. AES-CBC) only.
     ///   Indicates the size of the actual plaintext located in `buffer` starting from the beginning.
     /// * `buffer`: The buffer containing the plaintext and room for padding (if needed)
@@ -250,34 +479,8 @@ impl<
         aad: &'data [u8],
         tag: &'data mut [u8],
     ) -> Result<RequestId, Error> {
-        let request = match algorithm {
-            SymmetricAlgorithm::ChaCha20Poly1305 => Request::EncryptChaChaPolyExternalKey {
-                client_id: ClientId::default(),
-                request_id: RequestId::default(),
-                key,
-                nonce,
-                buffer,
-                aad,
-                tag,
-            },
-            SymmetricAlgorithm::AesGcm => Request::EncryptAesGcmExternalKey {
-                client_id: Default::default(),
-                request_id: Default::default(),
-                key,
-                iv: nonce,
-                buffer,
-                aad,
-                tag,
-            },
-            SymmetricAlgorithm::AesCbc => Request::EncryptAesCbcExternalKey {
-                client_id: Default::default(),
-                request_id: Default::default(),
-                key,
-                iv: nonce,
-                buffer,
-                plaintext_size,
-            },
-        };
+        let request =
+            algorithm.encrypt_external_key_request(key, nonce, plaintext_size, buffer, aad, tag);
         self.send_request(request).await
     }
 
@@ -287,7 +490,7 @@ impl<
     ///
     /// * `algorithm`: The `SymmetricEncryptionAlgorithm` to be used
     /// * `key_id`: The key identifier to use
-    /// * `nonce`: The 'Number used once' to use
+    /// * `nonce`: The 'Number used once' to use (ignored by AES-ECB)
     /// * `buffer`: The buffer containing the plaintext and room for padding (if needed)
     /// * `aad`: 'Additional authenticated data' to be used for tag computation
     /// * `tag`: The authentication tag used to authenticate the data
@@ -300,33 +503,7 @@ impl<
         aad: &'data [u8],
         tag: &'data [u8],
     ) -> Result<RequestId, Error> {
-        let request = match algorithm {
-            SymmetricAlgorithm::ChaCha20Poly1305 => Request::DecryptChaChaPoly {
-                client_id: ClientId::default(),
-                request_id: RequestId::default(),
-                key_id,
-                nonce,
-                buffer,
-                aad,
-                tag,
-            },
-            SymmetricAlgorithm::AesGcm => Request::DecryptAesGcm {
-                client_id: Default::default(),
-                request_id: Default::default(),
-                key_id,
-                iv: nonce,
-                buffer,
-                aad,
-                tag,
-            },
-            SymmetricAlgorithm::AesCbc => Request::DecryptAesCbc {
-                client_id: Default::default(),
-                request_id: Default::default(),
-                key_id,
-                iv: nonce,
-                buffer,
-            },
-        };
+        let request = algorithm.decrypt_request(key_id, nonce, buffer, aad, tag);
         self.send_request(request).await
     }
 
@@ -336,7 +513,7 @@ impl<
     ///
     /// * `algorithm`: The `SymmetricEncryptionAlgorithm` to be used
     /// * `key`: The key to use
-    /// * `nonce`: The 'Number used once' to use
+    /// * `nonce`: The 'Number used once' to use (ignored by AES-ECB)
     /// * `plaintext_size`: Used for algorithms that require padding (e.g. AES-CBC) only.
     ///   Indicates the size of the actual plaintext located in `buffer` starting from the beginning.
     /// * `buffer`: The buffer containing the plaintext and room for padding (if needed)
@@ -351,33 +528,7 @@ impl<
         aad: &'data [u8],
         tag: &'data [u8],
     ) -> Result<RequestId, Error> {
-        let request = match algorithm {
-            SymmetricAlgorithm::ChaCha20Poly1305 => Request::DecryptChaChaPolyExternalKey {
-                client_id: ClientId::default(),
-                request_id: RequestId::default(),
-                key,
-                nonce,
-                buffer,
-                aad,
-                tag,
-            },
-            SymmetricAlgorithm::AesGcm => Request::DecryptAesGcmExternalKey {
-                client_id: Default::default(),
-                request_id: Default::default(),
-                key,
-                iv: nonce,
-                buffer,
-                aad,
-                tag,
-            },
-            SymmetricAlgorithm::AesCbc => Request::DecryptAesCbcExternalKey {
-                client_id: Default::default(),
-                request_id: Default::default(),
-                key,
-                iv: nonce,
-                buffer,
-            },
-        };
+        let request = algorithm.decrypt_external_key_request(key, nonce, buffer, aad, tag);
         self.send_request(request).await
     }
 
@@ -391,6 +542,7 @@ impl<
         let request = Request::CalculateAesCmac {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key_id,
             message,
             tag,
@@ -408,6 +560,7 @@ impl<
         let request = Request::CalculateAesCmacExternalKey {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key,
             message,
             tag,
@@ -425,6 +578,7 @@ impl<
         let request = Request::VerifyAesCmac {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key_id,
             message,
             tag,
@@ -442,6 +596,7 @@ impl<
         let request = Request::VerifyAesCmacExternalKey {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key,
             message,
             tag,
@@ -459,6 +614,7 @@ impl<
         let request = Request::CalculateHmac {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key_id,
             hash_algorithm,
             message,
@@ -477,6 +633,7 @@ impl<
         let request = Request::CalculateHmacExternalKey {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key,
             hash_algorithm,
             message,
@@ -495,6 +652,7 @@ impl<
         let request = Request::VerifyHmac {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key_id,
             hash_algorithm,
             message,
@@ -513,6 +671,7 @@ impl<
         let request = Request::VerifyHmacExternalKey {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key,
             hash_algorithm,
             message,
@@ -527,14 +686,19 @@ impl<
         key_id: KeyId,
         message: &'data [u8],
         prehashed: bool,
+        hash_algorithm: Option<HashAlgorithm>,
+        signature_format: SignatureFormat,
         signature: &'data mut [u8],
     ) -> Result<RequestId, Error> {
         let request = Request::Sign {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key_id,
             message,
             prehashed,
+            hash_algorithm,
+            signature_format,
             signature,
         };
         self.send_request(request).await
@@ -546,14 +710,19 @@ impl<
         private_key: &'data [u8],
         message: &'data [u8],
         prehashed: bool,
+        hash_algorithm: Option<HashAlgorithm>,
+        signature_format: SignatureFormat,
         signature: &'data mut [u8],
     ) -> Result<RequestId, Error> {
         let request = Request::SignExternalKey {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             private_key,
             message,
             prehashed,
+            hash_algorithm,
+            signature_format,
             signature,
         };
         self.send_request(request).await
@@ -565,14 +734,19 @@ impl<
         key_id: KeyId,
         message: &'data [u8],
         prehashed: bool,
+        hash_algorithm: Option<HashAlgorithm>,
+        signature_format: SignatureFormat,
         signature: &'data [u8],
     ) -> Result<RequestId, Error> {
         let request = Request::Verify {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             key_id,
             message,
             prehashed,
+            hash_algorithm,
+            signature_format,
             signature,
         };
         self.send_request(request).await
@@ -584,25 +758,93 @@ impl<
         public_key: &'data [u8],
         message: &'data [u8],
         prehashed: bool,
+        hash_algorithm: Option<HashAlgorithm>,
+        signature_format: SignatureFormat,
         signature: &'data [u8],
     ) -> Result<RequestId, Error> {
         let request = Request::VerifyExternalKey {
             client_id: ClientId::default(),
             request_id: RequestId::default(),
+            deadline: None,
             public_key,
             message,
             prehashed,
+            hash_algorithm,
+            signature_format,
             signature,
         };
         self.send_request(request).await
     }
 
+    /// Load a key into a SHE key slot using the SHE memory update protocol (`CMD_LOAD_KEY`).
+    ///
+    /// # Arguments
+    ///
+    /// * `m1`, `m2`, `m3`: The key update messages generated by the key provider
+    /// * `m4`, `m5`: Buffers for the verification messages proving the successful update
+    pub async fn load_she_key(
+        &mut self,
+        m1: &'data [u8],
+        m2: &'data [u8],
+        m3: &'data [u8],
+        m4: &'data mut [u8],
+        m5: &'data mut [u8],
+    ) -> Result<RequestId, Error> {
+        let request = Request::LoadSheKey {
+            client_id: ClientId::default(),
+            request_id: RequestId::default(),
+            deadline: None,
+            m1,
+            m2,
+            m3,
+            m4,
+            m5,
+        };
+        self.send_request(request).await
+    }
+
+    /// Report the result of the secure boot process to the SHE (`CMD_BOOT_OK` or
+    /// `CMD_BOOT_FAILURE`).
+    pub async fn finish_she_boot(&mut self, success: bool) -> Result<RequestId, Error> {
+        let request = Request::FinishSheBoot {
+            client_id: ClientId::default(),
+            request_id: RequestId::default(),
+            deadline: None,
+            success,
+        };
+        self.send_request(request).await
+    }
+
+    /// Cancel a previously sent request. The cancelled request is answered with
+    /// [Response::Cancelled] unless it was already processed. Its buffers must not be reused before
+    /// a response for it was received.
+    pub async fn cancel(&mut self, target: RequestId) -> Result<RequestId, Error> {
+        let request = Request::Cancel {
+            client_id: ClientId::default(),
+            request_id: RequestId::default(),
+            deadline: None,
+            target,
+        };
+        self.send_request(request).await
+    }
+
+    /// Query the features of the HSM, e.g. the supported request types and algorithms.
+    pub async fn get_capabilities(&mut self) -> Result<RequestId, Error> {
+        let request = Request::GetCapabilities {
+            client_id: ClientId::default(),
+            request_id: RequestId::default(),
+            deadline: None,
+        };
+        self.send_request(request).await
+    }
+
     async fn send_request(
         &mut self,
         mut request_without_id: Request<'data>,
     ) -> Result<RequestId, Error> {
         let request_id = self.next_request_id();
         request_without_id.set_request_id(request_id);
+        request_without_id.set_deadline(self.deadline);
         self.requests
             .send(request_without_id)
             .await
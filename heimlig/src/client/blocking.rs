@@ -0,0 +1,147 @@
+//! Blocking client interface for host code without an async executor.
+//!
+//! [BlockingApi] sends a request and blocks the caller until the response of the request arrives.
+//! While the request is pending, the caller waits according to a [Wait] strategy: [Spin] busy
+//! waits, [Wfe] sleeps until the next event on Arm cores, and integrators can implement [Wait] on
+//! top of e.g. an RTOS semaphore. [block_on] blocks on any future the same way, e.g. on requests
+//! of a [SharedApi](crate::client::shared::SharedApi) that is used from several threads.
+//!
+//! C host code uses the raw version in [raw_blocking](crate::integration::raw_blocking).
+
+use crate::client::api::SymmetricAlgorithm;
+use crate::client::typed::{decrypted, define_typed_requests, encrypted, Error, TypedApi};
+use crate::common::jobs::{
+    Capabilities, ClientId, HashAlgorithm, PrivateKeyFormat, PublicKeyFormat, Request, RequestId,
+    Response, SignatureFormat,
+};
+use crate::hsm::keystore::{KeyId, KeyInfo};
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::pin;
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use futures::{Sink, Stream};
+
+/// Strategy to block the caller while a request is pending.
+pub trait Wait: Sync {
+    /// Block until [Wait::wake] is called. Returning early is allowed, the caller checks for
+    /// progress and waits again.
+    fn wait(&self);
+
+    /// Unblock a caller of [Wait::wait]. Called when the pending request can make progress,
+    /// possibly from an interrupt handler or another core.
+    fn wake(&self);
+}
+
+/// Busy waiting. Works everywhere, but keeps the core busy.
+#[derive(Debug, Default)]
+pub struct Spin;
+
+impl Wait for Spin {
+    fn wait(&self) {
+        core::hint::spin_loop();
+    }
+
+    fn wake(&self) {}
+}
+
+/// Sleep until the next event with the `WFE` instruction of Arm cores. Wakers signal an event with
+/// `SEV`, as do interrupts and other cores.
+#[cfg(any(target_arch = "arm", target_arch = "aarch64"))]
+#[derive(Debug, Default)]
+pub struct Wfe;
+
+#[cfg(any(target_arch = "arm", target_arch = "aarch64"))]
+impl Wait for Wfe {
+    fn wait(&self) {
+        // SAFETY: `wfe` only suspends the core until the next event.
+        unsafe { core::arch::asm!("wfe", options(nomem, nostack, preserves_flags)) }
+    }
+
+    fn wake(&self) {
+        // SAFETY: `sev` only signals an event to all cores.
+        unsafe { core::arch::asm!("sev", options(nomem, nostack, preserves_flags)) }
+    }
+}
+
+/// Waker functions that call [Wait::wake] of a `&'static W`.
+struct WaitWaker<W>(PhantomData<W>);
+
+impl<W: Wait + 'static> WaitWaker<W> {
+    const VTABLE: RawWakerVTable =
+        RawWakerVTable::new(Self::clone, Self::wake, Self::wake, Self::drop);
+
+    fn raw_waker(wait: &'static W) -> RawWaker {
+        RawWaker::new((wait as *const W).cast(), &Self::VTABLE)
+    }
+
+    fn clone(data: *const ()) -> RawWaker {
+        // SAFETY: `data` was created from a `&'static W` in `raw_waker`.
+        Self::raw_waker(unsafe { &*data.cast::<W>() })
+    }
+
+    fn wake(data: *const ()) {
+        // SAFETY: `data` was created from a `&'static W` in `raw_waker`.
+        unsafe { &*data.cast::<W>() }.wake();
+    }
+
+    fn drop(_data: *const ()) {}
+}
+
+/// Poll `future` to completion and block with `wait` whenever it is pending.
+pub fn block_on<W: Wait + 'static, F: Future>(wait: &'static W, future: F) -> F::Output {
+    // SAFETY: The vtable functions only use `data` as the `&'static W` it was created from.
+    let waker = unsafe { Waker::from_raw(WaitWaker::raw_waker(wait)) };
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        wait.wait();
+    }
+}
+
+/// An interface to the HSM core that blocks until the result of a request is available.
+///
+/// The methods offer the same typed requests as [TypedApi]. `requests` and `responses` are the
+/// queues of an HSM client. Responses are correlated with their requests by [RequestId].
+pub struct BlockingApi<'data, W, Req, Resp>
+where
+    W: Wait + 'static,
+    Req: Sink<Request<'data>> + Unpin,
+    Resp: Stream<Item = Response<'data>> + Unpin,
+{
+    api: TypedApi<'data, Req, Resp, 1>,
+    wait: &'static W,
+}
+
+impl<
+        'data,
+        W: Wait + 'static,
+        Req: Sink<Request<'data>> + Unpin,
+        Resp: Stream<Item = Response<'data>> + Unpin,
+    > BlockingApi<'data, W, Req, Resp>
+{
+    /// Create a new instance of the blocking HSM API. The caller blocks with `wait` while a
+    /// request is pending.
+    pub fn new(requests: Req, responses: Resp, wait: &'static W) -> Self {
+        BlockingApi {
+            api: TypedApi::new(requests, responses),
+            wait,
+        }
+    }
+
+    /// Set the deadline for all following requests in ticks of the core clock. Requests that are
+    /// not dispatched before their deadline fail with [Error::Cancelled].
+    pub fn set_deadline(&self, deadline: Option<u64>) {
+        self.api.set_deadline(deadline);
+    }
+
+    /// Send a request and block until its response arrives. Error responses are returned as
+    /// [Error]s.
+    pub fn call(&self, request: Request<'data>) -> Result<Response<'data>, Error> {
+        block_on(self.wait, self.api.call(request))
+    }
+
+    define_typed_requests!(blocking);
+}
@@ -1 +1,5 @@
 pub mod api;
+pub mod blocking;
+pub mod shared;
+pub mod she;
+pub mod typed;
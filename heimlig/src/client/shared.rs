@@ -0,0 +1,152 @@
+//! Client interface shared by several tasks.
+//!
+//! A [SharedClient] owns the request sink and the response stream of one HSM client. Any number
+//! of tasks send requests through [SharedApi] handles of the client, which are cheap to copy. The
+//! handles offer the same typed requests as [TypedApi]. Responses are passed to the waiting
+//! request by their [RequestId], no matter which task receives them from the stream.
+//!
+//! Requests are served in the order of their arrival: They wait in line for one of the
+//! `MAX_PENDING` slots of the client, and reserved requests are sent in the same order. Up to
+//! `MAX_WAITERS` requests can wait in line. Further requests wait for room in the line in no
+//! particular order. All state is statically sized, no allocator is needed.
+//!
+//! [TypedApi]: crate::client::typed::TypedApi
+
+use crate::client::api::SymmetricAlgorithm;
+use crate::client::typed::{
+    self, decrypted, define_typed_requests, encrypted, Dispatch, Dispatcher, Error,
+};
+use crate::common::jobs::{
+    Capabilities, ClientId, HashAlgorithm, PrivateKeyFormat, PublicKeyFormat, Request, RequestId,
+    Response, SignatureFormat,
+};
+use crate::common::sync::RawMutex;
+use crate::hsm::keystore::{KeyId, KeyInfo};
+use core::cell::UnsafeCell;
+use futures::{Sink, Stream};
+
+/// HSM client that is shared by several tasks through [SharedApi] handles.
+///
+/// `M` protects the client state. It has to be able to synchronize all tasks that use the client,
+/// e.g. a critical section if requests are sent from interrupt handlers.
+pub struct SharedClient<
+    'data,
+    M: RawMutex,
+    Req,
+    Resp,
+    const MAX_PENDING: usize = 8,
+    const MAX_WAITERS: usize = 8,
+> {
+    mutex: M,
+    dispatcher: UnsafeCell<Dispatcher<'data, Req, Resp, MAX_PENDING, MAX_WAITERS>>,
+}
+
+// SAFETY: The dispatcher is only accessed while `mutex` is locked.
+unsafe impl<
+        M: RawMutex + Sync,
+        Req: Send,
+        Resp: Send,
+        const MAX_PENDING: usize,
+        const MAX_WAITERS: usize,
+    > Sync for SharedClient<'_, M, Req, Resp, MAX_PENDING, MAX_WAITERS>
+{
+}
+
+impl<
+        'data,
+        M: RawMutex,
+        Req: Sink<Request<'data>> + Unpin,
+        Resp: Stream<Item = Response<'data>> + Unpin,
+        const MAX_PENDING: usize,
+        const MAX_WAITERS: usize,
+    > SharedClient<'data, M, Req, Resp, MAX_PENDING, MAX_WAITERS>
+{
+    /// Create a new shared client from the queues of an HSM client.
+    pub fn new(requests: Req, responses: Resp) -> Self {
+        SharedClient {
+            mutex: M::INIT,
+            dispatcher: UnsafeCell::new(Dispatcher::new(requests, responses)),
+        }
+    }
+
+    /// Create a new handle to send requests through this client.
+    pub fn api(&self) -> SharedApi<'_, 'data, M, Req, Resp, MAX_PENDING, MAX_WAITERS> {
+        SharedApi {
+            client: self,
+            deadline: None,
+        }
+    }
+}
+
+impl<'data, M: RawMutex, Req, Resp, const MAX_PENDING: usize, const MAX_WAITERS: usize>
+    Dispatch<'data, Req, Resp, MAX_PENDING, MAX_WAITERS>
+    for SharedClient<'data, M, Req, Resp, MAX_PENDING, MAX_WAITERS>
+{
+    fn with<R>(
+        &self,
+        f: impl FnOnce(&mut Dispatcher<'data, Req, Resp, MAX_PENDING, MAX_WAITERS>) -> R,
+    ) -> R {
+        self.mutex.lock(|| {
+            // SAFETY: The mutex is locked, so this is the only reference to the dispatcher.
+            // `with` is not called again while `f` runs.
+            f(unsafe { &mut *self.dispatcher.get() })
+        })
+    }
+}
+
+/// Handle of a [SharedClient] that returns the typed result of every request.
+///
+/// Handles are [Copy], every task can use its own handle. Several requests of the same handle can
+/// be outstanding at once.
+///
+/// Dropping the future of a request does not cancel the request. Its response is discarded when it
+/// arrives.
+pub struct SharedApi<
+    'client,
+    'data,
+    M: RawMutex,
+    Req,
+    Resp,
+    const MAX_PENDING: usize = 8,
+    const MAX_WAITERS: usize = 8,
+> {
+    client: &'client SharedClient<'data, M, Req, Resp, MAX_PENDING, MAX_WAITERS>,
+    deadline: Option<u64>,
+}
+
+impl<M: RawMutex, Req, Resp, const MAX_PENDING: usize, const MAX_WAITERS: usize> Clone
+    for SharedApi<'_, '_, M, Req, Resp, MAX_PENDING, MAX_WAITERS>
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M: RawMutex, Req, Resp, const MAX_PENDING: usize, const MAX_WAITERS: usize> Copy
+    for SharedApi<'_, '_, M, Req, Resp, MAX_PENDING, MAX_WAITERS>
+{
+}
+
+impl<
+        'data,
+        M: RawMutex,
+        Req: Sink<Request<'data>> + Unpin,
+        Resp: Stream<Item = Response<'data>> + Unpin,
+        const MAX_PENDING: usize,
+        const MAX_WAITERS: usize,
+    > SharedApi<'_, 'data, M, Req, Resp, MAX_PENDING, MAX_WAITERS>
+{
+    /// Set the deadline for all following requests of this handle in ticks of the core clock.
+    /// Requests that are not dispatched before their deadline fail with [Error::Cancelled].
+    pub fn set_deadline(&mut self, deadline: Option<u64>) {
+        self.deadline = deadline;
+    }
+
+    /// Send a request and wait for its response. Error responses are returned as [Error]s.
+    pub async fn call(&self, mut request: Request<'data>) -> Result<Response<'data>, Error> {
+        request.set_deadline(self.deadline);
+        typed::call(self.client, request).await
+    }
+
+    define_typed_requests!(async);
+}
@@ -0,0 +1,387 @@
+use crate::client::api::{self, Api, SymmetricAlgorithm};
+use crate::common::jobs::{Request, RequestId, Response};
+use crate::crypto::aes::{BLOCK_SIZE, CMAC_TAG_SIZE, IV_SIZE};
+use crate::crypto::she::{M1_SIZE, M2_SIZE, M3_SIZE, M4_SIZE, M5_SIZE};
+use crate::hsm::keystore::KeyId;
+use crate::hsm::she::{Error, SheKeyId, NUM_SLOTS};
+use futures::{Sink, Stream};
+
+/// Size of the random number returned by `CMD_RND`.
+pub const RND_SIZE: usize = 16;
+
+/// A command of the SHE (Secure Hardware Extension) command set.
+///
+/// All buffers are processed in-place. Their sizes follow the SHE specification: ECB operates on a
+/// single block, CBC on a multiple of the block size and MACs are always 128 bits long.
+#[derive(Debug)]
+pub enum SheCommand<'data> {
+    /// `CMD_ENC_ECB`
+    EncEcb {
+        key_id: SheKeyId,
+        buffer: &'data mut [u8],
+    },
+    /// `CMD_DEC_ECB`
+    DecEcb {
+        key_id: SheKeyId,
+        buffer: &'data mut [u8],
+    },
+    /// `CMD_ENC_CBC`
+    EncCbc {
+        key_id: SheKeyId,
+        iv: &'data [u8],
+        buffer: &'data mut [u8],
+    },
+    /// `CMD_DEC_CBC`
+    DecCbc {
+        key_id: SheKeyId,
+        iv: &'data [u8],
+        buffer: &'data mut [u8],
+    },
+    /// `CMD_GENERATE_MAC`
+    GenerateMac {
+        key_id: SheKeyId,
+        message: &'data [u8],
+        mac: &'data mut [u8],
+    },
+    /// `CMD_VERIFY_MAC`
+    VerifyMac {
+        key_id: SheKeyId,
+        message: &'data [u8],
+        mac: &'data [u8],
+    },
+    /// `CMD_LOAD_KEY`
+    LoadKey {
+        m1: &'data [u8],
+        m2: &'data [u8],
+        m3: &'data [u8],
+        m4: &'data mut [u8],
+        m5: &'data mut [u8],
+    },
+    /// `CMD_RND`
+    Rnd { output: &'data mut [u8] },
+    /// `CMD_BOOT_OK`
+    BootOk,
+    /// `CMD_BOOT_FAILURE`
+    BootFailure,
+}
+
+/// The result of a successfully executed [SheCommand].
+#[derive(Debug)]
+pub enum SheOutput<'data> {
+    /// The processed buffer of a cipher, MAC generation or random number command.
+    Data(&'data mut [u8]),
+    /// The verification status of `CMD_VERIFY_MAC`.
+    Verified(bool),
+    /// The M4 and M5 messages proving a successful `CMD_LOAD_KEY`.
+    KeyLoaded {
+        m4: &'data mut [u8],
+        m5: &'data mut [u8],
+    },
+    /// The command does not produce any output.
+    None,
+}
+
+/// An emulation of the SHE command set on top of the Heimlig [Api].
+///
+/// Commands are translated into Heimlig [Request]s and processed by the regular workers. The HSM
+/// core needs workers for the following request types:
+/// * `EncryptAesEcb`, `DecryptAesEcb`, `EncryptAesCbc`, `DecryptAesCbc`, `CalculateAesCmac` and
+///   `VerifyAesCmac` (e.g. `AesWorker`)
+/// * `GetRandom` (e.g. `RngWorker`)
+/// * `LoadSheKey` and `FinishSheBoot` (`SheWorker`)
+///
+/// The `SheWorker` and the AES workers share the `SheKeySlots` of the HSM. The AES workers check
+/// the boot protection, debugger protection and key usage flags of a slot before they use its key
+/// (see `AesWorker::she_slots`), so cipher and MAC commands are rejected like on a SHE.
+///
+/// Commands are executed one at a time. Every command waits for its response before returning, so
+/// the underlying [Api] must not be shared with other users.
+pub struct SheApi<'data, Req: Sink<Request<'data>>, Resp: Stream<Item = Response<'data>>> {
+    api: Api<'data, Req, Resp>,
+    key_ids: [KeyId; NUM_SLOTS],
+}
+
+impl<
+        'data,
+        ReqSink: Sink<Request<'data>> + core::marker::Unpin,
+        RespSrc: Stream<Item = Response<'data>> + core::marker::Unpin,
+    > SheApi<'data, ReqSink, RespSrc>
+{
+    /// Create a new instance of the SHE API.
+    ///
+    /// # Arguments
+    ///
+    /// * `api`: The Heimlig API used to send requests to the HSM.
+    /// * `key_ids`: The key store IDs backing the SHE slots, indexed by `SheKeyId`. These have to
+    ///   match the IDs used by the `SheWorker`.
+    pub fn new(api: Api<'data, ReqSink, RespSrc>, key_ids: [KeyId; NUM_SLOTS]) -> Self {
+        SheApi { api, key_ids }
+    }
+
+    /// Execute a single SHE command.
+    pub async fn execute(&mut self, command: SheCommand<'data>) -> Result<SheOutput<'data>, Error> {
+        match command {
+            SheCommand::EncEcb { key_id, buffer } => {
+                self.enc_ecb(key_id, buffer).await.map(SheOutput::Data)
+            }
+            SheCommand::DecEcb { key_id, buffer } => {
+                self.dec_ecb(key_id, buffer).await.map(SheOutput::Data)
+            }
+            SheCommand::EncCbc { key_id, iv, buffer } => {
+                self.enc_cbc(key_id, iv, buffer).await.map(SheOutput::Data)
+            }
+            SheCommand::DecCbc { key_id, iv, buffer } => {
+                self.dec_cbc(key_id, iv, buffer).await.map(SheOutput::Data)
+            }
+            SheCommand::GenerateMac {
+                key_id,
+                message,
+                mac,
+            } => self
+                .generate_mac(key_id, message, mac)
+                .await
+                .map(SheOutput::Data),
+            SheCommand::VerifyMac {
+                key_id,
+                message,
+                mac,
+            } => self
+                .verify_mac(key_id, message, mac)
+                .await
+                .map(SheOutput::Verified),
+            SheCommand::LoadKey { m1, m2, m3, m4, m5 } => self
+                .load_key(m1, m2, m3, m4, m5)
+                .await
+                .map(|(m4, m5)| SheOutput::KeyLoaded { m4, m5 }),
+            SheCommand::Rnd { output } => self.rnd(output).await.map(SheOutput::Data),
+            SheCommand::BootOk => self.boot_ok().await.map(|_| SheOutput::None),
+            SheCommand::BootFailure => self.boot_failure().await.map(|_| SheOutput::None),
+        }
+    }
+
+    /// Encrypt a single block in-place (`CMD_ENC_ECB`).
+    pub async fn enc_ecb(
+        &mut self,
+        key_id: SheKeyId,
+        buffer: &'data mut [u8],
+    ) -> Result<&'data mut [u8], Error> {
+        let key_id = self.crypto_key_id(key_id)?;
+        if buffer.len() != BLOCK_SIZE {
+            return Err(Error::GeneralError);
+        }
+        let request_id = self
+            .api
+            .encrypt_in_place(
+                SymmetricAlgorithm::AesEcb,
+                key_id,
+                &[],
+                BLOCK_SIZE,
+                buffer,
+                &[],
+                &mut [],
+            )
+            .await;
+        match self.recv_response(request_id).await? {
+            Response::EncryptAesEcb { buffer, .. } => Ok(buffer),
+            _ => Err(Error::GeneralError),
+        }
+    }
+
+    /// Decrypt a single block in-place (`CMD_DEC_ECB`).
+    pub async fn dec_ecb(
+        &mut self,
+        key_id: SheKeyId,
+        buffer: &'data mut [u8],
+    ) -> Result<&'data mut [u8], Error> {
+        let key_id = self.crypto_key_id(key_id)?;
+        if buffer.len() != BLOCK_SIZE {
+            return Err(Error::GeneralError);
+        }
+        let request_id = self
+            .api
+            .decrypt_in_place(SymmetricAlgorithm::AesEcb, key_id, &[], buffer, &[], &[])
+            .await;
+        match self.recv_response(request_id).await? {
+            Response::DecryptAesEcb { plaintext, .. } => Ok(plaintext),
+            _ => Err(Error::GeneralError),
+        }
+    }
+
+    /// Encrypt a buffer in-place in CBC mode without padding (`CMD_ENC_CBC`).
+    pub async fn enc_cbc(
+        &mut self,
+        key_id: SheKeyId,
+        iv: &'data [u8],
+        buffer: &'data mut [u8],
+    ) -> Result<&'data mut [u8], Error> {
+        let key_id = self.crypto_key_id(key_id)?;
+        check_cbc_sizes(iv, buffer)?;
+        let plaintext_size = buffer.len();
+        let request_id = self
+            .api
+            .encrypt_in_place(
+                SymmetricAlgorithm::AesCbcNoPadding,
+                key_id,
+                iv,
+                plaintext_size,
+                buffer,
+                &[],
+                &mut [],
+            )
+            .await;
+        match self.recv_response(request_id).await? {
+            Response::EncryptAesCbc { buffer, .. } => Ok(buffer),
+            _ => Err(Error::GeneralError),
+        }
+    }
+
+    /// Decrypt a buffer in-place in CBC mode without padding (`CMD_DEC_CBC`).
+    pub async fn dec_cbc(
+        &mut self,
+        key_id: SheKeyId,
+        iv: &'data [u8],
+        buffer: &'data mut [u8],
+    ) -> Result<&'data mut [u8], Error> {
+        let key_id = self.crypto_key_id(key_id)?;
+        check_cbc_sizes(iv, buffer)?;
+        let request_id = self
+            .api
+            .decrypt_in_place(
+                SymmetricAlgorithm::AesCbcNoPadding,
+                key_id,
+                iv,
+                buffer,
+                &[],
+                &[],
+            )
+            .await;
+        match self.recv_response(request_id).await? {
+            Response::DecryptAesCbc { plaintext, .. } => Ok(plaintext),
+            _ => Err(Error::GeneralError),
+        }
+    }
+
+    /// Calculate the AES-CMAC of a message (`CMD_GENERATE_MAC`).
+    pub async fn generate_mac(
+        &mut self,
+        key_id: SheKeyId,
+        message: &'data [u8],
+        mac: &'data mut [u8],
+    ) -> Result<&'data mut [u8], Error> {
+        let key_id = self.crypto_key_id(key_id)?;
+        if mac.len() != CMAC_TAG_SIZE {
+            return Err(Error::GeneralError);
+        }
+        let request_id = self.api.calculate_aes_cmac(key_id, message, mac).await;
+        match self.recv_response(request_id).await? {
+            Response::CalculateAesCmac { tag, .. } => Ok(tag),
+            _ => Err(Error::GeneralError),
+        }
+    }
+
+    /// Verify the AES-CMAC of a message (`CMD_VERIFY_MAC`).
+    ///
+    /// Only full-length (128 bit) MACs are supported.
+    pub async fn verify_mac(
+        &mut self,
+        key_id: SheKeyId,
+        message: &'data [u8],
+        mac: &'data [u8],
+    ) -> Result<bool, Error> {
+        let key_id = self.crypto_key_id(key_id)?;
+        if mac.len() != CMAC_TAG_SIZE {
+            return Err(Error::GeneralError);
+        }
+        let request_id = self.api.verify_aes_cmac(key_id, message, mac).await;
+        match self.recv_response(request_id).await? {
+            Response::VerifyAesCmac { verified, .. } => Ok(verified),
+            _ => Err(Error::GeneralError),
+        }
+    }
+
+    /// Update a key slot with the memory update protocol (`CMD_LOAD_KEY`).
+    pub async fn load_key(
+        &mut self,
+        m1: &'data [u8],
+        m2: &'data [u8],
+        m3: &'data [u8],
+        m4: &'data mut [u8],
+        m5: &'data mut [u8],
+    ) -> Result<(&'data mut [u8], &'data mut [u8]), Error> {
+        if m1.len() != M1_SIZE
+            || m2.len() != M2_SIZE
+            || m3.len() != M3_SIZE
+            || m4.len() != M4_SIZE
+            || m5.len() != M5_SIZE
+        {
+            return Err(Error::GeneralError);
+        }
+        let request_id = self.api.load_she_key(m1, m2, m3, m4, m5).await;
+        match self.recv_response(request_id).await? {
+            Response::LoadSheKey { m4, m5, .. } => Ok((m4, m5)),
+            _ => Err(Error::GeneralError),
+        }
+    }
+
+    /// Generate a random number (`CMD_RND`).
+    pub async fn rnd(&mut self, output: &'data mut [u8]) -> Result<&'data mut [u8], Error> {
+        if output.len() != RND_SIZE {
+            return Err(Error::GeneralError);
+        }
+        let request_id = self.api.get_random(output).await;
+        match self.recv_response(request_id).await? {
+            Response::GetRandom { data, .. } => Ok(data),
+            _ => Err(Error::GeneralError),
+        }
+    }
+
+    /// Report a successful secure boot (`CMD_BOOT_OK`).
+    pub async fn boot_ok(&mut self) -> Result<(), Error> {
+        self.finish_boot(true).await
+    }
+
+    /// Report a failed secure boot (`CMD_BOOT_FAILURE`).
+    pub async fn boot_failure(&mut self) -> Result<(), Error> {
+        self.finish_boot(false).await
+    }
+
+    async fn finish_boot(&mut self, success: bool) -> Result<(), Error> {
+        let request_id = self.api.finish_she_boot(success).await;
+        match self.recv_response(request_id).await? {
+            Response::FinishSheBoot { .. } => Ok(()),
+            _ => Err(Error::GeneralError),
+        }
+    }
+
+    /// Map a SHE slot to its key store ID. Only `KEY_1` to `KEY_10` and `RAM_KEY` can be used for
+    /// cipher and MAC commands.
+    fn crypto_key_id(&self, key_id: SheKeyId) -> Result<KeyId, Error> {
+        if !key_id.is_general_purpose() && key_id != SheKeyId::RamKey {
+            return Err(Error::KeyInvalid);
+        }
+        Ok(self.key_ids[key_id.idx()])
+    }
+
+    /// Wait for the response to the given request and convert errors to SHE error codes.
+    async fn recv_response(
+        &mut self,
+        request_id: Result<RequestId, api::Error>,
+    ) -> Result<Response<'data>, Error> {
+        let request_id = request_id.map_err(|_| Error::GeneralError)?;
+        let response = self.api.recv_response().await.ok_or(Error::GeneralError)?;
+        if response.get_request_id() != request_id {
+            return Err(Error::SequenceError);
+        }
+        match response {
+            Response::Error { error, .. } => Err(error.into()),
+            response => Ok(response),
+        }
+    }
+}
+
+fn check_cbc_sizes(iv: &[u8], buffer: &[u8]) -> Result<(), Error> {
+    if iv.len() != IV_SIZE || buffer.is_empty() || buffer.len() % BLOCK_SIZE != 0 {
+        return Err(Error::GeneralError);
+    }
+    Ok(())
+}
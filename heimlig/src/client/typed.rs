@@ -0,0 +1,1116 @@
+//! Typed client interface that resolves every request to its result.
+//!
+//! [Api] only sends requests and leaves matching the responses to the caller. [TypedApi] returns
+//! a future for every request instead. The future resolves to the typed result of the request
+//! once its response arrives. Many requests can be outstanding at the same time, e.g. by joining
+//! their futures. Responses are correlated by their [RequestId], so the order in which the HSM
+//! answers does not matter.
+//!
+//! [Api]: crate::client::api::Api
+
+use crate::client::api::SymmetricAlgorithm;
+use crate::common::jobs::{
+    self, CancelReason, Capabilities, ClientId, HashAlgorithm, PrivateKeyFormat, PublicKeyFormat,
+    Request, RequestId, Response, SignatureFormat,
+};
+use crate::common::sync::WakerRegistration;
+use crate::hsm::keystore::{KeyId, KeyInfo};
+use core::cell::{Cell, RefCell};
+use core::future::poll_fn;
+use core::pin::Pin;
+use core::task::{ready, Context, Poll, Waker};
+use futures::{Sink, Stream};
+use heapless::Deque;
+
+/// Errors of requests sent with [TypedApi].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The request could not be sent.
+    Send,
+    /// The response stream ended before the response was received.
+    Closed,
+    /// The HSM failed to process the request.
+    Hsm(jobs::Error),
+    /// The request was not processed.
+    Cancelled(CancelReason),
+    /// The HSM answered with a response of a different request type.
+    UnexpectedResponse,
+}
+
+/// State of a request sent through the [Dispatcher].
+enum Slot<'data> {
+    Free,
+    Waiting {
+        request_id: RequestId,
+        /// Whether the request was passed to the request sink
+        sent: bool,
+        waker: WakerRegistration,
+    },
+    Ready(Response<'data>),
+    /// The response was returned, but the slot was not released yet.
+    Done,
+}
+
+/// Request waiting for a free [Slot].
+struct Waiter {
+    ticket: u32,
+    waker: Waker,
+}
+
+/// Correlates the responses of the HSM with the outstanding requests.
+///
+/// Every outstanding request occupies one of the `MAX_PENDING` slots from before it is sent until
+/// its result is returned or its future is dropped. A response that arrives while another request
+/// polls the response stream is stored in the slot of its request. Responses of dropped requests
+/// are discarded.
+///
+/// Requests are served in the order in which they arrive: Up to `MAX_WAITERS` requests wait in
+/// line for a free slot, and reserved requests are sent in the order of their request IDs.
+/// Requests arriving while the line is full wait until there is room in it.
+pub(crate) struct Dispatcher<'data, Req, Resp, const MAX_PENDING: usize, const MAX_WAITERS: usize> {
+    requests: Req,
+    responses: Resp,
+    request_id_counter: RequestId,
+    slots: [Slot<'data>; MAX_PENDING],
+    /// Requests waiting for a free slot in the order of their arrival
+    waiters: Deque<Waiter, MAX_WAITERS>,
+    /// Requests waiting for room in `waiters`
+    overflow: WakerRegistration,
+    next_ticket: u32,
+    /// Slot of the request that is currently passed to the request sink
+    sender: Option<usize>,
+    closed: bool,
+}
+
+impl<
+        'data,
+        Req: Sink<Request<'data>> + Unpin,
+        Resp: Stream<Item = Response<'data>> + Unpin,
+        const MAX_PENDING: usize,
+        const MAX_WAITERS: usize,
+    > Dispatcher<'data, Req, Resp, MAX_PENDING, MAX_WAITERS>
+{
+    pub(crate) fn new(requests: Req, responses: Resp) -> Self {
+        Dispatcher {
+            requests,
+            responses,
+            request_id_counter: RequestId::default(),
+            slots: core::array::from_fn(|_| Slot::Free),
+            waiters: Deque::new(),
+            overflow: WakerRegistration::new(),
+            next_ticket: 0,
+            sender: None,
+            closed: false,
+        }
+    }
+
+    /// Reserve a slot for `request` and assign its request ID. Requests that have to wait for a
+    /// free slot get a `ticket` for their place in line.
+    ///
+    /// returns: The index of the reserved slot.
+    pub(crate) fn poll_reserve(
+        &mut self,
+        cx: &mut Context<'_>,
+        request: &mut Request,
+        ticket: &mut Option<u32>,
+    ) -> Poll<usize> {
+        let first_in_line = match ticket {
+            None => self.waiters.is_empty(),
+            Some(ticket) => self
+                .waiters
+                .front()
+                .is_some_and(|waiter| waiter.ticket == *ticket),
+        };
+        let free_slot = self
+            .slots
+            .iter()
+            .position(|slot| matches!(slot, Slot::Free));
+        if let (true, Some(slot)) = (first_in_line, free_slot) {
+            if ticket.take().is_some() {
+                self.waiters.pop_front();
+                self.overflow.wake();
+            }
+            let request_id = self.request_id_counter;
+            self.request_id_counter.increment();
+            request.set_request_id(request_id);
+            self.slots[slot] = Slot::Waiting {
+                request_id,
+                sent: false,
+                waker: WakerRegistration::new(),
+            };
+            self.wake_first_waiter();
+            return Poll::Ready(slot);
+        }
+        match ticket {
+            Some(ticket) => {
+                if let Some(waiter) = self
+                    .waiters
+                    .iter_mut()
+                    .find(|waiter| waiter.ticket == *ticket)
+                {
+                    if !waiter.waker.will_wake(cx.waker()) {
+                        waiter.waker = cx.waker().clone();
+                    }
+                }
+            }
+            None => {
+                let waiter = Waiter {
+                    ticket: self.next_ticket,
+                    waker: cx.waker().clone(),
+                };
+                match self.waiters.push_back(waiter) {
+                    Ok(()) => {
+                        *ticket = Some(self.next_ticket);
+                        self.next_ticket = self.next_ticket.wrapping_add(1);
+                    }
+                    Err(_) => self.overflow.register(cx.waker()),
+                }
+            }
+        }
+        Poll::Pending
+    }
+
+    /// Leave the line of requests waiting for a free slot.
+    pub(crate) fn cancel_wait(&mut self, ticket: u32) {
+        for _ in 0..self.waiters.len() {
+            if let Some(waiter) = self.waiters.pop_front() {
+                if waiter.ticket != ticket {
+                    // Cannot fail as one element was just removed
+                    let _ = self.waiters.push_back(waiter);
+                }
+            }
+        }
+        self.overflow.wake();
+        self.wake_first_waiter();
+    }
+
+    /// Send the request of `slot` if it was not sent yet and flush the request sink. Only one
+    /// request is passed to the sink at a time.
+    pub(crate) fn poll_send(
+        &mut self,
+        cx: &mut Context<'_>,
+        slot: usize,
+        request: &mut Option<Request<'data>>,
+    ) -> Poll<Result<(), Error>> {
+        match self.sender {
+            Some(sender) if sender != slot => {
+                if let Slot::Waiting { waker, .. } = &mut self.slots[slot] {
+                    waker.register(cx.waker());
+                }
+                return Poll::Pending;
+            }
+            _ => self.sender = Some(slot),
+        }
+        let result = ready!(Self::poll_send_request(
+            Pin::new(&mut self.requests),
+            cx,
+            request
+        ));
+        if let Slot::Waiting { sent, .. } = &mut self.slots[slot] {
+            *sent = true;
+        }
+        self.sender = None;
+        self.wake_next_sender();
+        Poll::Ready(result)
+    }
+
+    fn poll_send_request(
+        mut requests: Pin<&mut Req>,
+        cx: &mut Context<'_>,
+        request: &mut Option<Request<'data>>,
+    ) -> Poll<Result<(), Error>> {
+        if request.is_some() {
+            ready!(requests.as_mut().poll_ready(cx)).map_err(|_| Error::Send)?;
+            if let Some(request) = request.take() {
+                requests
+                    .as_mut()
+                    .start_send(request)
+                    .map_err(|_| Error::Send)?;
+            }
+        }
+        requests.poll_flush(cx).map_err(|_| Error::Send)
+    }
+
+    /// Wait for the response of the request in `slot`. Responses of other requests are stored in
+    /// their slots.
+    pub(crate) fn poll_response(
+        &mut self,
+        cx: &mut Context<'_>,
+        slot: usize,
+    ) -> Poll<Result<Response<'data>, Error>> {
+        loop {
+            if let Slot::Ready(_) = self.slots[slot] {
+                let Slot::Ready(response) = core::mem::replace(&mut self.slots[slot], Slot::Done)
+                else {
+                    unreachable!()
+                };
+                return Poll::Ready(Ok(response));
+            }
+            if self.closed {
+                return Poll::Ready(Err(Error::Closed));
+            }
+            match Pin::new(&mut self.responses).poll_next(cx) {
+                Poll::Ready(Some(response)) => self.store(response),
+                Poll::Ready(None) => self.closed = true,
+                Poll::Pending => {
+                    if let Slot::Waiting { waker, .. } = &mut self.slots[slot] {
+                        waker.register(cx.waker());
+                    }
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+
+    /// Free the slot of a completed or dropped request.
+    pub(crate) fn release(&mut self, slot: usize) {
+        self.slots[slot] = Slot::Free;
+        self.wake_first_waiter();
+        if self.sender == Some(slot) {
+            self.sender = None;
+            self.wake_next_sender();
+        }
+        // The released request might have been the only one polling the response stream
+        if let Some(Slot::Waiting { waker, .. }) = self
+            .slots
+            .iter_mut()
+            .find(|slot| matches!(slot, Slot::Waiting { sent: true, .. }))
+        {
+            waker.wake();
+        }
+    }
+
+    fn store(&mut self, response: Response<'data>) {
+        let request_id = response.get_request_id();
+        let slot = self
+            .slots
+            .iter_mut()
+            .find(|slot| matches!(slot, Slot::Waiting { request_id: id, .. } if *id == request_id));
+        // Responses of dropped requests are discarded
+        if let Some(slot) = slot {
+            if let Slot::Waiting { mut waker, .. } = core::mem::replace(slot, Slot::Ready(response))
+            {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Wake the first request in line if a slot is free for it.
+    fn wake_first_waiter(&mut self) {
+        if self.slots.iter().any(|slot| matches!(slot, Slot::Free)) {
+            if let Some(waiter) = self.waiters.front() {
+                waiter.waker.wake_by_ref();
+            }
+        }
+    }
+
+    /// Wake the oldest request that was not sent yet.
+    fn wake_next_sender(&mut self) {
+        let counter = self.request_id_counter.0;
+        let next = self
+            .slots
+            .iter_mut()
+            .filter_map(|slot| match slot {
+                Slot::Waiting {
+                    request_id,
+                    sent: false,
+                    waker,
+                } => Some((counter.wrapping_sub(request_id.0), waker)),
+                _ => None,
+            })
+            .max_by_key(|(age, _)| *age);
+        if let Some((_, waker)) = next {
+            waker.wake();
+        }
+    }
+}
+
+/// Converts error responses into [Error]s.
+pub(crate) fn check_response(response: Response<'_>) -> Result<Response<'_>, Error> {
+    match response {
+        Response::Error { error, .. } => Err(Error::Hsm(error)),
+        Response::Cancelled { reason, .. } => Err(Error::Cancelled(reason)),
+        response => Ok(response),
+    }
+}
+
+/// Exclusive access to a [Dispatcher] that is shared by the requests of a client interface.
+pub(crate) trait Dispatch<'data, Req, Resp, const MAX_PENDING: usize, const MAX_WAITERS: usize> {
+    fn with<R>(
+        &self,
+        f: impl FnOnce(&mut Dispatcher<'data, Req, Resp, MAX_PENDING, MAX_WAITERS>) -> R,
+    ) -> R;
+}
+
+impl<'data, Req, Resp, const MAX_PENDING: usize, const MAX_WAITERS: usize>
+    Dispatch<'data, Req, Resp, MAX_PENDING, MAX_WAITERS>
+    for RefCell<Dispatcher<'data, Req, Resp, MAX_PENDING, MAX_WAITERS>>
+{
+    fn with<R>(
+        &self,
+        f: impl FnOnce(&mut Dispatcher<'data, Req, Resp, MAX_PENDING, MAX_WAITERS>) -> R,
+    ) -> R {
+        f(&mut self.borrow_mut())
+    }
+}
+
+/// Runs a closure when dropped.
+struct OnDrop<F: FnMut()>(F);
+
+impl<F: FnMut()> Drop for OnDrop<F> {
+    fn drop(&mut self) {
+        (self.0)()
+    }
+}
+
+/// Send a request through `dispatch` and wait for its response. Error responses are returned as
+/// [Error]s.
+///
+/// If the returned future is dropped, the request gives up its place in line or its slot.
+pub(crate) async fn call<'data, D, Req, Resp, const MAX_PENDING: usize, const MAX_WAITERS: usize>(
+    dispatch: &D,
+    mut request: Request<'data>,
+) -> Result<Response<'data>, Error>
+where
+    D: Dispatch<'data, Req, Resp, MAX_PENDING, MAX_WAITERS>,
+    Req: Sink<Request<'data>> + Unpin,
+    Resp: Stream<Item = Response<'data>> + Unpin,
+{
+    let ticket = Cell::new(None);
+    let slot = Cell::new(None);
+    let _guard = OnDrop(|| {
+        dispatch.with(|dispatcher| {
+            if let Some(ticket) = ticket.get() {
+                dispatcher.cancel_wait(ticket);
+            }
+            if let Some(slot) = slot.get() {
+                dispatcher.release(slot);
+            }
+        })
+    });
+    let reserved = poll_fn(|cx| {
+        dispatch.with(|dispatcher| {
+            let mut waiting = ticket.get();
+            let result = dispatcher.poll_reserve(cx, &mut request, &mut waiting);
+            ticket.set(waiting);
+            result
+        })
+    })
+    .await;
+    slot.set(Some(reserved));
+    let mut request = Some(request);
+    poll_fn(|cx| dispatch.with(|dispatcher| dispatcher.poll_send(cx, reserved, &mut request)))
+        .await?;
+    let response =
+        poll_fn(|cx| dispatch.with(|dispatcher| dispatcher.poll_response(cx, reserved))).await?;
+    check_response(response)
+}
+
+/// Typed request methods of a client interface.
+///
+/// Expands to one method per request type. Each method builds its request and passes it to the
+/// `call` method of the interface, which has to be defined next to the macro invocation. With
+/// `async`, the methods are `async` and await `call`. With `blocking`, `call` returns the result
+/// directly.
+macro_rules! define_typed_requests {
+    (async) => {
+        $crate::client::typed::define_typed_requests!(@methods [async] [.await]);
+    };
+    (blocking) => {
+        $crate::client::typed::define_typed_requests!(@methods [] []);
+    };
+    (@methods [$($async:tt)*] [$($await:tt)*]) => {
+        /// Request random bytes and write them to the provided buffer.
+        pub $($async)* fn get_random(&self, output: &'data mut [u8]) -> Result<&'data mut [u8], Error> {
+            let request = Request::GetRandom {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                output,
+            };
+            match self.call(request)$($await)*? {
+                Response::GetRandom { data, .. } => Ok(data),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Generate a symmetric key and store it in the HSM.
+        pub $($async)* fn generate_symmetric_key(
+            &self,
+            key_id: KeyId,
+            overwrite: bool,
+        ) -> Result<(), Error> {
+            let request = Request::GenerateSymmetricKey {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                overwrite,
+            };
+            match self.call(request)$($await)*? {
+                Response::GenerateSymmetricKey { .. } => Ok(()),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Generate an asymmetric key pair and store it in the HSM.
+        pub $($async)* fn generate_key_pair(&self, key_id: KeyId, overwrite: bool) -> Result<(), Error> {
+            let request = Request::GenerateKeyPair {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                overwrite,
+            };
+            match self.call(request)$($await)*? {
+                Response::GenerateKeyPair { .. } => Ok(()),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Import a symmetric key into the HSM.
+        pub $($async)* fn import_symmetric_key(
+            &self,
+            key_id: KeyId,
+            data: &'data [u8],
+            overwrite: bool,
+        ) -> Result<(), Error> {
+            let request = Request::ImportSymmetricKey {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                data,
+                overwrite,
+            };
+            match self.call(request)$($await)*? {
+                Response::ImportSymmetricKey { .. } => Ok(()),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Import an asymmetric key pair into the HSM.
+        /// For encoded private key formats, `public_key` has to be empty as it is derived from the
+        /// private key.
+        pub $($async)* fn import_key_pair(
+            &self,
+            key_id: KeyId,
+            public_key: &'data [u8],
+            private_key: &'data [u8],
+            format: PrivateKeyFormat,
+            overwrite: bool,
+        ) -> Result<(), Error> {
+            let request = Request::ImportKeyPair {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                public_key,
+                private_key,
+                format,
+                overwrite,
+            };
+            match self.call(request)$($await)*? {
+                Response::ImportKeyPair { .. } => Ok(()),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Export a symmetric key that is stored in the HSM.
+        ///
+        /// returns: The part of `data` containing the key.
+        pub $($async)* fn export_symmetric_key(
+            &self,
+            key_id: KeyId,
+            data: &'data mut [u8],
+        ) -> Result<&'data mut [u8], Error> {
+            let request = Request::ExportSymmetricKey {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                data,
+            };
+            match self.call(request)$($await)*? {
+                Response::ExportSymmetricKey { key, .. } => Ok(key),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Export an asymmetric public key that is stored in the HSM in the given format.
+        ///
+        /// returns: The part of `public_key` containing the encoded key.
+        pub $($async)* fn export_public_key(
+            &self,
+            key_id: KeyId,
+            public_key: &'data mut [u8],
+            format: PublicKeyFormat,
+        ) -> Result<&'data mut [u8], Error> {
+            let request = Request::ExportPublicKey {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                public_key,
+                format,
+            };
+            match self.call(request)$($await)*? {
+                Response::ExportPublicKey { public_key, .. } => Ok(public_key),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Export an asymmetric private key that is stored in the HSM.
+        /// This function only works for keys whose permission allow their private half to be exported.
+        ///
+        /// returns: The part of `private_key` containing the key.
+        pub $($async)* fn export_private_key(
+            &self,
+            key_id: KeyId,
+            private_key: &'data mut [u8],
+        ) -> Result<&'data mut [u8], Error> {
+            let request = Request::ExportPrivateKey {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                private_key,
+            };
+            match self.call(request)$($await)*? {
+                Response::ExportPrivateKey { private_key, .. } => Ok(private_key),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Check whether a key for the given `KeyId` is stored in the HSM
+        pub $($async)* fn is_key_available(&self, key_id: KeyId) -> Result<bool, Error> {
+            let request = Request::IsKeyAvailable {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+            };
+            match self.call(request)$($await)*? {
+                Response::IsKeyAvailable { is_available, .. } => Ok(is_available),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Query the type and the permissions of the key with the given `KeyId`.
+        pub $($async)* fn get_key_info(&self, key_id: KeyId) -> Result<KeyInfo, Error> {
+            let request = Request::GetKeyInfo {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+            };
+            match self.call(request)$($await)*? {
+                Response::GetKeyInfo { key_info, .. } => Ok(key_info),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Symmetrically encrypt a buffer in-place using a key stored in the HSM.
+        ///
+        /// See [Api::encrypt_in_place](crate::client::api::Api::encrypt_in_place) for the arguments.
+        ///
+        /// returns: The ciphertext and the tag. The tag is empty for algorithms without
+        /// authentication.
+        #[allow(clippy::too_many_arguments)]
+        pub $($async)* fn encrypt_in_place(
+            &self,
+            algorithm: SymmetricAlgorithm,
+            key_id: KeyId,
+            nonce: &'data [u8],
+            plaintext_size: usize,
+            buffer: &'data mut [u8],
+            aad: &'data [u8],
+            tag: &'data mut [u8],
+        ) -> Result<(&'data mut [u8], &'data mut [u8]), Error> {
+            let request =
+                algorithm.encrypt_request(key_id, nonce, plaintext_size, buffer, aad, tag);
+            encrypted(self.call(request)$($await)*?)
+        }
+
+        /// Symmetrically encrypt a buffer in-place using a caller-provided key.
+        ///
+        /// See [Api::encrypt_in_place_external_key](crate::client::api::Api::encrypt_in_place_external_key)
+        /// for the arguments.
+        ///
+        /// returns: The ciphertext and the tag. The tag is empty for algorithms without
+        /// authentication.
+        #[allow(clippy::too_many_arguments)]
+        pub $($async)* fn encrypt_in_place_external_key(
+            &self,
+            algorithm: SymmetricAlgorithm,
+            key: &'data [u8],
+            nonce: &'data [u8],
+            plaintext_size: usize,
+            buffer: &'data mut [u8],
+            aad: &'data [u8],
+            tag: &'data mut [u8],
+        ) -> Result<(&'data mut [u8], &'data mut [u8]), Error> {
+            let request = algorithm.encrypt_external_key_request(
+                key,
+                nonce,
+                plaintext_size,
+                buffer,
+                aad,
+                tag,
+            );
+            encrypted(self.call(request)$($await)*?)
+        }
+
+        /// Symmetrically decrypt a buffer in-place using a key stored in the HSM.
+        ///
+        /// See [Api::decrypt_in_place](crate::client::api::Api::decrypt_in_place) for the arguments.
+        ///
+        /// returns: The plaintext without padding.
+        pub $($async)* fn decrypt_in_place(
+            &self,
+            algorithm: SymmetricAlgorithm,
+            key_id: KeyId,
+            nonce: &'data [u8],
+            buffer: &'data mut [u8],
+            aad: &'data [u8],
+            tag: &'data [u8],
+        ) -> Result<&'data mut [u8], Error> {
+            let request = algorithm.decrypt_request(key_id, nonce, buffer, aad, tag);
+            decrypted(self.call(request)$($await)*?)
+        }
+
+        /// Symmetrically decrypt a buffer in-place using a caller-provided key.
+        ///
+        /// See [Api::decrypt_in_place_external_key](crate::client::api::Api::decrypt_in_place_external_key)
+        /// for the arguments.
+        ///
+        /// returns: The plaintext without padding.
+        pub $($async)* fn decrypt_in_place_external_key(
+            &self,
+            algorithm: SymmetricAlgorithm,
+            key: &'data [u8],
+            nonce: &'data [u8],
+            buffer: &'data mut [u8],
+            aad: &'data [u8],
+            tag: &'data [u8],
+        ) -> Result<&'data mut [u8], Error> {
+            let request = algorithm.decrypt_external_key_request(key, nonce, buffer, aad, tag);
+            decrypted(self.call(request)$($await)*?)
+        }
+
+        /// Calculate the AES-CMAC of a message using a key stored in the HSM.
+        pub $($async)* fn calculate_aes_cmac(
+            &self,
+            key_id: KeyId,
+            message: &'data [u8],
+            tag: &'data mut [u8],
+        ) -> Result<&'data mut [u8], Error> {
+            let request = Request::CalculateAesCmac {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                message,
+                tag,
+            };
+            match self.call(request)$($await)*? {
+                Response::CalculateAesCmac { tag, .. } => Ok(tag),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Calculate the AES-CMAC of a message using a caller-provided key.
+        pub $($async)* fn calculate_aes_cmac_external_key(
+            &self,
+            key: &'data [u8],
+            message: &'data [u8],
+            tag: &'data mut [u8],
+        ) -> Result<&'data mut [u8], Error> {
+            let request = Request::CalculateAesCmacExternalKey {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key,
+                message,
+                tag,
+            };
+            match self.call(request)$($await)*? {
+                Response::CalculateAesCmac { tag, .. } => Ok(tag),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Verify the AES-CMAC of a message using a key stored in the HSM.
+        pub $($async)* fn verify_aes_cmac(
+            &self,
+            key_id: KeyId,
+            message: &'data [u8],
+            tag: &'data [u8],
+        ) -> Result<bool, Error> {
+            let request = Request::VerifyAesCmac {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                message,
+                tag,
+            };
+            match self.call(request)$($await)*? {
+                Response::VerifyAesCmac { verified, .. } => Ok(verified),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Verify the AES-CMAC of a message using a caller-provided key.
+        pub $($async)* fn verify_aes_cmac_external_key(
+            &self,
+            key: &'data [u8],
+            message: &'data [u8],
+            tag: &'data [u8],
+        ) -> Result<bool, Error> {
+            let request = Request::VerifyAesCmacExternalKey {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key,
+                message,
+                tag,
+            };
+            match self.call(request)$($await)*? {
+                Response::VerifyAesCmac { verified, .. } => Ok(verified),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Calculate the HMAC of a message using a key stored in the HSM.
+        pub $($async)* fn calculate_hmac(
+            &self,
+            key_id: KeyId,
+            hash_algorithm: HashAlgorithm,
+            message: &'data [u8],
+            tag: &'data mut [u8],
+        ) -> Result<&'data mut [u8], Error> {
+            let request = Request::CalculateHmac {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                hash_algorithm,
+                message,
+                tag,
+            };
+            match self.call(request)$($await)*? {
+                Response::CalculateHmac { tag, .. } => Ok(tag),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Calculate the HMAC of a message using a caller-provided key.
+        pub $($async)* fn calculate_hmac_external_key(
+            &self,
+            key: &'data [u8],
+            hash_algorithm: HashAlgorithm,
+            message: &'data [u8],
+            tag: &'data mut [u8],
+        ) -> Result<&'data mut [u8], Error> {
+            let request = Request::CalculateHmacExternalKey {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key,
+                hash_algorithm,
+                message,
+                tag,
+            };
+            match self.call(request)$($await)*? {
+                Response::CalculateHmac { tag, .. } => Ok(tag),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Verify the HMAC of a message using a key stored in the HSM.
+        pub $($async)* fn verify_hmac(
+            &self,
+            key_id: KeyId,
+            hash_algorithm: HashAlgorithm,
+            message: &'data [u8],
+            tag: &'data [u8],
+        ) -> Result<bool, Error> {
+            let request = Request::VerifyHmac {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                hash_algorithm,
+                message,
+                tag,
+            };
+            match self.call(request)$($await)*? {
+                Response::VerifyHmac { verified, .. } => Ok(verified),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Verify the HMAC of a message using a caller-provided key.
+        pub $($async)* fn verify_hmac_external_key(
+            &self,
+            key: &'data [u8],
+            hash_algorithm: HashAlgorithm,
+            message: &'data [u8],
+            tag: &'data [u8],
+        ) -> Result<bool, Error> {
+            let request = Request::VerifyHmacExternalKey {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key,
+                hash_algorithm,
+                message,
+                tag,
+            };
+            match self.call(request)$($await)*? {
+                Response::VerifyHmac { verified, .. } => Ok(verified),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Sign a message using a key stored in the HSM.
+        ///
+        /// returns: The part of `signature` containing the signature.
+        pub $($async)* fn sign(
+            &self,
+            key_id: KeyId,
+            message: &'data [u8],
+            prehashed: bool,
+            hash_algorithm: Option<HashAlgorithm>,
+            signature_format: SignatureFormat,
+            signature: &'data mut [u8],
+        ) -> Result<&'data mut [u8], Error> {
+            let request = Request::Sign {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                message,
+                prehashed,
+                hash_algorithm,
+                signature_format,
+                signature,
+            };
+            match self.call(request)$($await)*? {
+                Response::Sign { signature, .. } => Ok(signature),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Sign a message using a caller-provided key.
+        ///
+        /// returns: The part of `signature` containing the signature.
+        pub $($async)* fn sign_external_key(
+            &self,
+            private_key: &'data [u8],
+            message: &'data [u8],
+            prehashed: bool,
+            hash_algorithm: Option<HashAlgorithm>,
+            signature_format: SignatureFormat,
+            signature: &'data mut [u8],
+        ) -> Result<&'data mut [u8], Error> {
+            let request = Request::SignExternalKey {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                private_key,
+                message,
+                prehashed,
+                hash_algorithm,
+                signature_format,
+                signature,
+            };
+            match self.call(request)$($await)*? {
+                Response::Sign { signature, .. } => Ok(signature),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Verify the signature of a message using a key stored in the HSM.
+        pub $($async)* fn verify(
+            &self,
+            key_id: KeyId,
+            message: &'data [u8],
+            prehashed: bool,
+            hash_algorithm: Option<HashAlgorithm>,
+            signature_format: SignatureFormat,
+            signature: &'data [u8],
+        ) -> Result<bool, Error> {
+            let request = Request::Verify {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                key_id,
+                message,
+                prehashed,
+                hash_algorithm,
+                signature_format,
+                signature,
+            };
+            match self.call(request)$($await)*? {
+                Response::Verify { verified, .. } => Ok(verified),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Verify the signature of a message using a caller-provided key.
+        pub $($async)* fn verify_external_key(
+            &self,
+            public_key: &'data [u8],
+            message: &'data [u8],
+            prehashed: bool,
+            hash_algorithm: Option<HashAlgorithm>,
+            signature_format: SignatureFormat,
+            signature: &'data [u8],
+        ) -> Result<bool, Error> {
+            let request = Request::VerifyExternalKey {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                public_key,
+                message,
+                prehashed,
+                hash_algorithm,
+                signature_format,
+                signature,
+            };
+            match self.call(request)$($await)*? {
+                Response::Verify { verified, .. } => Ok(verified),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Load a key into a SHE key slot using the SHE memory update protocol (`CMD_LOAD_KEY`).
+        ///
+        /// returns: The M4 and M5 verification messages.
+        pub $($async)* fn load_she_key(
+            &self,
+            m1: &'data [u8],
+            m2: &'data [u8],
+            m3: &'data [u8],
+            m4: &'data mut [u8],
+            m5: &'data mut [u8],
+        ) -> Result<(&'data mut [u8], &'data mut [u8]), Error> {
+            let request = Request::LoadSheKey {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                m1,
+                m2,
+                m3,
+                m4,
+                m5,
+            };
+            match self.call(request)$($await)*? {
+                Response::LoadSheKey { m4, m5, .. } => Ok((m4, m5)),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Report the result of the secure boot process to the SHE (`CMD_BOOT_OK` or
+        /// `CMD_BOOT_FAILURE`).
+        pub $($async)* fn finish_she_boot(&self, success: bool) -> Result<(), Error> {
+            let request = Request::FinishSheBoot {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+                success,
+            };
+            match self.call(request)$($await)*? {
+                Response::FinishSheBoot { .. } => Ok(()),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+
+        /// Query the features of the HSM, e.g. the supported request types and algorithms.
+        pub $($async)* fn get_capabilities(&self) -> Result<Capabilities, Error> {
+            let request = Request::GetCapabilities {
+                client_id: ClientId::default(),
+                request_id: RequestId::default(),
+                deadline: None,
+            };
+            match self.call(request)$($await)*? {
+                Response::GetCapabilities { capabilities, .. } => Ok(capabilities),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        }
+    };
+}
+pub(crate) use define_typed_requests;
+
+/// An interface to the HSM core that returns the typed result of every request.
+///
+/// All methods take `&self`, so several requests can be outstanding at once. Up to `MAX_PENDING`
+/// requests are sent at the same time. Further requests wait until a previous request completes.
+/// The API is meant to be used by a single task. See the [module documentation](self) for details.
+/// [SharedClient](crate::client::shared::SharedClient) serves several tasks.
+///
+/// Dropping the future of a request does not cancel the request. Its response is discarded when it
+/// arrives.
+pub struct TypedApi<'data, Req, Resp, const MAX_PENDING: usize = 8>
+where
+    Req: Sink<Request<'data>> + Unpin,
+    Resp: Stream<Item = Response<'data>> + Unpin,
+{
+    dispatcher: RefCell<Dispatcher<'data, Req, Resp, MAX_PENDING, MAX_PENDING>>,
+    deadline: Cell<Option<u64>>,
+}
+
+impl<
+        'data,
+        Req: Sink<Request<'data>> + Unpin,
+        Resp: Stream<Item = Response<'data>> + Unpin,
+        const MAX_PENDING: usize,
+    > TypedApi<'data, Req, Resp, MAX_PENDING>
+{
+    /// Create a new instance of the typed HSM API.
+    pub fn new(requests: Req, responses: Resp) -> Self {
+        TypedApi {
+            dispatcher: RefCell::new(Dispatcher::new(requests, responses)),
+            deadline: Cell::new(None),
+        }
+    }
+
+    /// Set the deadline for all following requests in ticks of the core clock. Requests that are
+    /// not dispatched before their deadline fail with [Error::Cancelled].
+    pub fn set_deadline(&self, deadline: Option<u64>) {
+        self.deadline.set(deadline);
+    }
+
+    /// Send a request and wait for its response. Error responses are returned as [Error]s.
+    pub async fn call(&self, mut request: Request<'data>) -> Result<Response<'data>, Error> {
+        request.set_deadline(self.deadline.get());
+        call(&self.dispatcher, request).await
+    }
+
+    define_typed_requests!(async);
+}
+
+pub(crate) fn encrypted(response: Response<'_>) -> Result<(&mut [u8], &mut [u8]), Error> {
+    match response {
+        Response::EncryptChaChaPoly { buffer, tag, .. }
+        | Response::EncryptAesGcm { buffer, tag, .. } => Ok((buffer, tag)),
+        Response::EncryptAesCbc { buffer, .. } | Response::EncryptAesEcb { buffer, .. } => {
+            Ok((buffer, &mut []))
+        }
+        _ => Err(Error::UnexpectedResponse),
+    }
+}
+
+pub(crate) fn decrypted(response: Response<'_>) -> Result<&mut [u8], Error> {
+    match response {
+        Response::DecryptChaChaPoly { buffer, .. } | Response::DecryptAesGcm { buffer, .. } => {
+            Ok(buffer)
+        }
+        Response::DecryptAesCbc { plaintext, .. } | Response::DecryptAesEcb { plaintext, .. } => {
+            Ok(plaintext)
+        }
+        _ => Err(Error::UnexpectedResponse),
+    }
+}
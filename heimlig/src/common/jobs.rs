@@ -1,7 +1,32 @@
 use displaydoc::Display;
 
 use crate::hsm::keystore;
-use crate::hsm::keystore::{Curve, KeyId};
+use crate::hsm::keystore::{Curve, KeyId, KeyInfo};
+use crate::hsm::she;
+use crate::integration::raw_jobs::AbiVersion;
+use heapless::Vec;
+use strum::{EnumCount, EnumIter};
+
+/// Maximum number of buffers referenced by a single request
+pub const MAX_BUFFERS: usize = 5;
+
+/// Fails to compile for requests with more than [MAX_BUFFERS] buffers.
+struct MaxBuffers<const N: usize>;
+
+impl<const N: usize> MaxBuffers<N> {
+    const CHECK: () = assert!(
+        N <= MAX_BUFFERS,
+        "request has more than MAX_BUFFERS buffers"
+    );
+}
+
+/// Collect the buffers of a request. The number of buffers is checked at compile time, so the
+/// buffers always fit.
+fn collect_buffers<const N: usize>(buffers: [(&[u8], bool); N]) -> Vec<(&[u8], bool), MAX_BUFFERS> {
+    #[allow(clippy::let_unit_value)]
+    let () = MaxBuffers::<N>::CHECK;
+    buffers.into_iter().collect()
+}
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Display)]
 pub enum Error {
@@ -21,6 +46,29 @@ pub enum Error {
     Crypto(crate::crypto::Error),
     /// A key store error occurred: {0}
     KeyStore(keystore::Error),
+    /// A SHE error occurred: {0}
+    She(she::Error),
+    /// A limit of the client was exceeded: {0}
+    LimitExceeded(Limit),
+    /// The worker responsible for the request is unavailable.
+    WorkerUnavailable,
+    /// A buffer of the request is outside the memory regions of the client.
+    AccessDenied,
+    /// The HSM does not support cancellations.
+    CancellationUnsupported,
+    /// The request to cancel is not pending at a worker or too many requests are cancelled.
+    NotCancellable,
+}
+
+/// Per-client limits enforced by the core.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Display)]
+pub enum Limit {
+    /// Too many requests of the client are already being processed by workers.
+    InFlight,
+    /// The request rate of the client for this request type was exceeded.
+    Rate,
+    /// The request carries more data than allowed for the client.
+    RequestSize,
 }
 
 impl From<keystore::Error> for Error {
@@ -29,6 +77,12 @@ impl From<keystore::Error> for Error {
     }
 }
 
+impl From<she::Error> for Error {
+    fn from(value: she::Error) -> Self {
+        Self::She(value)
+    }
+}
+
 impl From<crate::crypto::Error> for Error {
     fn from(value: crate::crypto::Error) -> Self {
         Self::Crypto(value)
@@ -95,68 +149,310 @@ pub enum HashAlgorithm {
     Sha3_512,
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+impl HashAlgorithm {
+    /// Size of the digest produced by the hash algorithm in bytes.
+    pub const fn digest_size(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha2_256 | HashAlgorithm::Sha3_256 => crate::crypto::hash::SHA256_SIZE,
+            HashAlgorithm::Sha2_384 | HashAlgorithm::Sha3_384 => crate::crypto::hash::SHA384_SIZE,
+            HashAlgorithm::Sha2_512 | HashAlgorithm::Sha3_512 => crate::crypto::hash::SHA512_SIZE,
+        }
+    }
+}
+
+/// Encoding of ECDSA signatures produced by `Sign` and expected by `Verify`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SignatureFormat {
+    /// Concatenation of the big-endian `r` and `s` values.
+    #[default]
+    Raw,
+    /// ASN.1 DER encoded `Ecdsa-Sig-Value` as used by X.509 and TLS.
+    Der,
+}
+
+/// Padding scheme of block cipher modes like AES-CBC.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Padding {
+    #[default]
+    Pkcs7,
+    /// The buffer size has to be a multiple of the block size.
+    NoPadding,
+}
+
+/// Encoding of private keys passed to `ImportKeyPair`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PrivateKeyFormat {
+    /// Raw private scalar. The public key has to be passed separately.
+    #[default]
+    Raw,
+    /// PKCS#8 `PrivateKeyInfo` DER document.
+    Pkcs8Der,
+    /// SEC1 `ECPrivateKey` DER document.
+    Sec1Der,
+    /// PEM document containing either a PKCS#8 or a SEC1 private key.
+    Pem,
+}
+
+/// Encoding of public keys returned by `ExportPublicKey`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PublicKeyFormat {
+    /// Uncompressed point without the SEC1 tag byte.
+    #[default]
+    Raw,
+    /// Uncompressed SEC1 point including the tag byte.
+    Sec1Uncompressed,
+    /// Compressed SEC1 point.
+    Sec1Compressed,
+    /// SubjectPublicKeyInfo DER document.
+    SpkiDer,
+    /// SubjectPublicKeyInfo PEM document.
+    SpkiPem,
+}
+
+/// Type of a [Request].
+///
+/// The discriminants are stable numeric codes that identify the request type in the raw ABI (see
+/// [crate::integration::raw_jobs]) and in [Capabilities]. Codes are assigned consecutively and are
+/// never changed or reused. New request types are appended.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, EnumCount, EnumIter)]
 pub enum RequestType {
-    GetRandom,
-    GenerateSymmetricKey,
-    GenerateKeyPair,
-    ImportSymmetricKey,
-    ImportKeyPair,
-    ExportSymmetricKey,
-    ExportPublicKey,
-    ExportPrivateKey,
-    IsKeyAvailable,
-    EncryptChaChaPoly,
-    EncryptChaChaPolyExternalKey,
-    DecryptChaChaPoly,
-    DecryptChaChaPolyExternalKey,
-    EncryptAesGcm,
-    EncryptAesGcmExternalKey,
-    DecryptAesGcm,
-    DecryptAesGcmExternalKey,
-    EncryptAesCbc,
-    EncryptAesCbcExternalKey,
-    DecryptAesCbc,
-    DecryptAesCbcExternalKey,
-    CalculateAesCmac,
-    CalculateAesCmacExternalKey,
-    VerifyAesCmac,
-    VerifyAesCmacExternalKey,
-    CalculateHmac,
-    CalculateHmacExternalKey,
-    VerifyHmac,
-    VerifyHmacExternalKey,
-    Sign,
-    SignExternalKey,
-    Verify,
-    VerifyExternalKey,
-    Ecdh,
-    EcdhExternalPrivateKey,
+    GetRandom = 0,
+    GenerateSymmetricKey = 1,
+    GenerateKeyPair = 2,
+    ImportSymmetricKey = 3,
+    ImportKeyPair = 4,
+    ExportSymmetricKey = 5,
+    ExportPublicKey = 6,
+    ExportPrivateKey = 7,
+    IsKeyAvailable = 8,
+    EncryptChaChaPoly = 9,
+    EncryptChaChaPolyExternalKey = 10,
+    DecryptChaChaPoly = 11,
+    DecryptChaChaPolyExternalKey = 12,
+    EncryptAesGcm = 13,
+    EncryptAesGcmExternalKey = 14,
+    DecryptAesGcm = 15,
+    DecryptAesGcmExternalKey = 16,
+    EncryptAesCbc = 17,
+    EncryptAesCbcExternalKey = 18,
+    DecryptAesCbc = 19,
+    DecryptAesCbcExternalKey = 20,
+    EncryptAesEcb = 21,
+    EncryptAesEcbExternalKey = 22,
+    DecryptAesEcb = 23,
+    DecryptAesEcbExternalKey = 24,
+    CalculateAesCmac = 25,
+    CalculateAesCmacExternalKey = 26,
+    VerifyAesCmac = 27,
+    VerifyAesCmacExternalKey = 28,
+    CalculateHmac = 29,
+    CalculateHmacExternalKey = 30,
+    VerifyHmac = 31,
+    VerifyHmacExternalKey = 32,
+    Sign = 33,
+    SignExternalKey = 34,
+    Verify = 35,
+    VerifyExternalKey = 36,
+    Ecdh = 37,
+    EcdhExternalPrivateKey = 38,
+    LoadSheKey = 39,
+    FinishSheBoot = 40,
+    Cancel = 41,
+    GetCapabilities = 42,
+    GetKeyInfo = 43,
+}
+
+/// Symmetric cipher or MAC as reported in [Capabilities].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, EnumIter)]
+pub enum SymmetricPrimitive {
+    ChaCha20Poly1305,
+    AesGcm,
+    AesCbc,
+    AesEcb,
+    AesCmac,
+    Hmac,
+}
+
+impl SymmetricPrimitive {
+    /// Request types that use the primitive
+    pub fn request_types(&self) -> &'static [RequestType] {
+        use RequestType::*;
+        match self {
+            SymmetricPrimitive::ChaCha20Poly1305 => &[
+                EncryptChaChaPoly,
+                EncryptChaChaPolyExternalKey,
+                DecryptChaChaPoly,
+                DecryptChaChaPolyExternalKey,
+            ],
+            SymmetricPrimitive::AesGcm => &[
+                EncryptAesGcm,
+                EncryptAesGcmExternalKey,
+                DecryptAesGcm,
+                DecryptAesGcmExternalKey,
+            ],
+            SymmetricPrimitive::AesCbc => &[
+                EncryptAesCbc,
+                EncryptAesCbcExternalKey,
+                DecryptAesCbc,
+                DecryptAesCbcExternalKey,
+            ],
+            SymmetricPrimitive::AesEcb => &[
+                EncryptAesEcb,
+                EncryptAesEcbExternalKey,
+                DecryptAesEcb,
+                DecryptAesEcbExternalKey,
+            ],
+            SymmetricPrimitive::AesCmac => &[
+                CalculateAesCmac,
+                CalculateAesCmacExternalKey,
+                VerifyAesCmac,
+                VerifyAesCmacExternalKey,
+            ],
+            SymmetricPrimitive::Hmac => &[
+                CalculateHmac,
+                CalculateHmacExternalKey,
+                VerifyHmac,
+                VerifyHmacExternalKey,
+            ],
+        }
+    }
+}
+
+/// Kind of a [KeyType](crate::hsm::keystore::KeyType) as reported in [Capabilities]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum KeyKind {
+    Symmetric,
+    Asymmetric,
+    KeyPair,
+}
+
+/// Defines a set of the values of a field-less enum. Bit `n` of the set stands for the value with
+/// discriminant `n`, which is also the code of the value in the raw ABI.
+macro_rules! enum_set {
+    ($(#[$meta:meta])* $set:ident($ty:ident)) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+        pub struct $set(u64);
+
+        impl $set {
+            pub const fn new() -> Self {
+                Self(0)
+            }
+
+            /// Set of the values whose bits are set in `bits`. Bits of unknown values are kept, e.g.
+            /// the ones of values added in a later version.
+            pub const fn from_bits(bits: u64) -> Self {
+                Self(bits)
+            }
+
+            pub const fn bits(&self) -> u64 {
+                self.0
+            }
+
+            pub const fn with(self, value: $ty) -> Self {
+                Self(self.0 | 1 << value as u64)
+            }
+
+            pub fn insert(&mut self, value: $ty) {
+                self.0 |= 1 << value as u64;
+            }
+
+            pub const fn contains(&self, value: $ty) -> bool {
+                self.0 & 1 << value as u64 != 0
+            }
+        }
+    };
+}
+
+enum_set!(
+    /// Set of request types
+    RequestTypes(RequestType)
+);
+enum_set!(
+    /// Set of hash algorithms
+    HashAlgorithms(HashAlgorithm)
+);
+enum_set!(
+    /// Set of elliptic curves
+    Curves(Curve)
+);
+enum_set!(
+    /// Set of symmetric primitives
+    SymmetricPrimitives(SymmetricPrimitive)
+);
+enum_set!(
+    /// Set of key kinds
+    KeyKinds(KeyKind)
+);
+
+// Every request type needs a bit in `RequestTypes`
+const _: () = assert!(RequestType::COUNT <= u64::BITS as usize);
+
+/// Features of the HSM as reported by [Request::GetCapabilities].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Capabilities {
+    /// Version of the raw ABI implemented by the HSM
+    pub abi_version: AbiVersion,
+    /// Request types handled by the core or one of its workers
+    pub request_types: RequestTypes,
+    /// Hash algorithms of HMAC, signature and verification requests
+    pub hash_algorithms: HashAlgorithms,
+    /// Curves of asymmetric keys, signatures and key agreements
+    pub curves: Curves,
+    /// Symmetric ciphers and MACs used by the supported request types
+    pub symmetric_primitives: SymmetricPrimitives,
+    /// Kinds of keys that can be imported into the key store
+    pub key_types: KeyKinds,
+    /// Whether the HSM has a key store. Without one, only requests with external keys succeed.
+    pub key_store: bool,
+    /// Size of the largest supported symmetric key in bytes
+    pub max_symmetric_key_size: usize,
+    /// Maximum size of all buffers of a single request of the client (see [Request::data_size])
+    /// or `None` if the size is not limited.
+    pub max_request_size: Option<usize>,
+}
+
+/// Reason why a request was not processed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CancelReason {
+    /// The client cancelled the request.
+    Cancelled,
+    /// The deadline of the request passed before it was dispatched.
+    Expired,
 }
 
 /// A request for the HSM to perform a cryptographic task.
+///
+/// Every request has an optional `deadline` in ticks of the core clock (see
+/// [crate::hsm::core::Builder::with_clock]). Requests that are still waiting to be dispatched after
+/// their deadline are dropped by the core and answered with [Response::Cancelled].
 #[derive(Debug)]
 pub enum Request<'data> {
     GetRandom {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         output: &'data mut [u8],
     },
     GenerateSymmetricKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         overwrite: bool,
     },
     GenerateKeyPair {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         overwrite: bool,
     },
     ImportSymmetricKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         data: &'data [u8],
         overwrite: bool,
@@ -164,37 +460,47 @@ pub enum Request<'data> {
     ImportKeyPair {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
+        /// Has to be empty for encoded private key formats. The public key is derived from the
+        /// private key in that case.
         public_key: &'data [u8],
         private_key: &'data [u8],
+        format: PrivateKeyFormat,
         overwrite: bool,
     },
     ExportSymmetricKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         data: &'data mut [u8],
     },
     ExportPublicKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         public_key: &'data mut [u8],
+        format: PublicKeyFormat,
     },
     ExportPrivateKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         private_key: &'data mut [u8],
     },
     IsKeyAvailable {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
     },
     EncryptChaChaPoly {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         nonce: &'data [u8],
         buffer: &'data mut [u8],
@@ -204,6 +510,7 @@ pub enum Request<'data> {
     EncryptChaChaPolyExternalKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key: &'data [u8],
         nonce: &'data [u8],
         buffer: &'data mut [u8],
@@ -213,6 +520,7 @@ pub enum Request<'data> {
     DecryptChaChaPoly {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         nonce: &'data [u8],
         buffer: &'data mut [u8],
@@ -222,6 +530,7 @@ pub enum Request<'data> {
     DecryptChaChaPolyExternalKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key: &'data [u8],
         nonce: &'data [u8],
         buffer: &'data mut [u8],
@@ -231,6 +540,7 @@ pub enum Request<'data> {
     EncryptAesGcm {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         iv: &'data [u8],
         buffer: &'data mut [u8],
@@ -240,6 +550,7 @@ pub enum Request<'data> {
     EncryptAesGcmExternalKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key: &'data [u8],
         iv: &'data [u8],
         buffer: &'data mut [u8],
@@ -249,6 +560,7 @@ pub enum Request<'data> {
     DecryptAesGcm {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         iv: &'data [u8],
         buffer: &'data mut [u8],
@@ -258,6 +570,7 @@ pub enum Request<'data> {
     DecryptAesGcmExternalKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key: &'data [u8],
         iv: &'data [u8],
         buffer: &'data mut [u8],
@@ -267,36 +580,73 @@ pub enum Request<'data> {
     EncryptAesCbc {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         iv: &'data [u8],
         buffer: &'data mut [u8],
         plaintext_size: usize,
+        padding: Padding,
     },
     EncryptAesCbcExternalKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key: &'data [u8],
         iv: &'data [u8],
         buffer: &'data mut [u8],
         plaintext_size: usize,
+        padding: Padding,
     },
     DecryptAesCbc {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         iv: &'data [u8],
         buffer: &'data mut [u8],
+        padding: Padding,
     },
     DecryptAesCbcExternalKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key: &'data [u8],
         iv: &'data [u8],
         buffer: &'data mut [u8],
+        padding: Padding,
+    },
+    EncryptAesEcb {
+        client_id: ClientId,
+        request_id: RequestId,
+        deadline: Option<u64>,
+        key_id: KeyId,
+        buffer: &'data mut [u8],
+    },
+    EncryptAesEcbExternalKey {
+        client_id: ClientId,
+        request_id: RequestId,
+        deadline: Option<u64>,
+        key: &'data [u8],
+        buffer: &'data mut [u8],
+    },
+    DecryptAesEcb {
+        client_id: ClientId,
+        request_id: RequestId,
+        deadline: Option<u64>,
+        key_id: KeyId,
+        buffer: &'data mut [u8],
+    },
+    DecryptAesEcbExternalKey {
+        client_id: ClientId,
+        request_id: RequestId,
+        deadline: Option<u64>,
+        key: &'data [u8],
+        buffer: &'data mut [u8],
     },
     CalculateAesCmac {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         message: &'data [u8],
         tag: &'data mut [u8],
@@ -304,6 +654,7 @@ pub enum Request<'data> {
     CalculateAesCmacExternalKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key: &'data [u8],
         message: &'data [u8],
         tag: &'data mut [u8],
@@ -311,6 +662,7 @@ pub enum Request<'data> {
     VerifyAesCmac {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         message: &'data [u8],
         tag: &'data [u8],
@@ -318,6 +670,7 @@ pub enum Request<'data> {
     VerifyAesCmacExternalKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key: &'data [u8],
         message: &'data [u8],
         tag: &'data [u8],
@@ -325,6 +678,7 @@ pub enum Request<'data> {
     CalculateHmac {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         hash_algorithm: HashAlgorithm,
         message: &'data [u8],
@@ -333,6 +687,7 @@ pub enum Request<'data> {
     CalculateHmacExternalKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key: &'data [u8],
         hash_algorithm: HashAlgorithm,
         message: &'data [u8],
@@ -341,6 +696,7 @@ pub enum Request<'data> {
     VerifyHmac {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         hash_algorithm: HashAlgorithm,
         message: &'data [u8],
@@ -349,6 +705,7 @@ pub enum Request<'data> {
     VerifyHmacExternalKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key: &'data [u8],
         hash_algorithm: HashAlgorithm,
         message: &'data [u8],
@@ -357,38 +714,53 @@ pub enum Request<'data> {
     Sign {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         message: &'data [u8],
         prehashed: bool,
+        /// Hash algorithm used to compute the digest of `message`. `None` selects the default hash
+        /// of the curve (SHA-256 for P-256 and SHA-384 for P-384).
+        hash_algorithm: Option<HashAlgorithm>,
+        signature_format: SignatureFormat,
         signature: &'data mut [u8],
     },
     SignExternalKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         private_key: &'data [u8],
         message: &'data [u8],
         prehashed: bool,
+        hash_algorithm: Option<HashAlgorithm>,
+        signature_format: SignatureFormat,
         signature: &'data mut [u8],
     },
     Verify {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         key_id: KeyId,
         message: &'data [u8],
         prehashed: bool,
+        hash_algorithm: Option<HashAlgorithm>,
+        signature_format: SignatureFormat,
         signature: &'data [u8],
     },
     VerifyExternalKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         public_key: &'data [u8],
         message: &'data [u8],
         prehashed: bool,
+        hash_algorithm: Option<HashAlgorithm>,
+        signature_format: SignatureFormat,
         signature: &'data [u8],
     },
     Ecdh {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         public_key: &'data [u8],
         private_key_id: KeyId,
         shared_secret: &'data mut [u8],
@@ -396,11 +768,55 @@ pub enum Request<'data> {
     EcdhExternalPrivateKey {
         client_id: ClientId,
         request_id: RequestId,
+        deadline: Option<u64>,
         curve: Curve,
         public_key: &'data [u8],
         private_key: &'data [u8],
         shared_secret: &'data mut [u8],
     },
+    LoadSheKey {
+        client_id: ClientId,
+        request_id: RequestId,
+        deadline: Option<u64>,
+        m1: &'data [u8],
+        m2: &'data [u8],
+        m3: &'data [u8],
+        m4: &'data mut [u8],
+        m5: &'data mut [u8],
+    },
+    FinishSheBoot {
+        client_id: ClientId,
+        request_id: RequestId,
+        deadline: Option<u64>,
+        success: bool,
+    },
+    /// Cancel the request `target` of the same client. Cancellation is best effort: a request
+    /// that is already being processed completes with its regular response, and so does a request
+    /// whose cancellation was evicted from the full registry (see [MAX_CANCELLATIONS]). HSMs without
+    /// a registry answer with [Error::NoWorkerForRequest].
+    ///
+    /// [MAX_CANCELLATIONS]: crate::hsm::cancellations::MAX_CANCELLATIONS
+    Cancel {
+        client_id: ClientId,
+        request_id: RequestId,
+        deadline: Option<u64>,
+        target: RequestId,
+    },
+    /// Query the features of the HSM, e.g. to check whether a request type is supported before
+    /// sending it.
+    GetCapabilities {
+        client_id: ClientId,
+        request_id: RequestId,
+        deadline: Option<u64>,
+    },
+    /// Query the type and the permissions of the key `key_id`. Unknown key IDs are answered with
+    /// [keystore::Error::InvalidKeyId].
+    GetKeyInfo {
+        client_id: ClientId,
+        request_id: RequestId,
+        deadline: Option<u64>,
+        key_id: KeyId,
+    },
 }
 
 impl RequestType {
@@ -415,6 +831,9 @@ impl RequestType {
                 | RequestType::ExportPublicKey
                 | RequestType::ExportPrivateKey
                 | RequestType::IsKeyAvailable
+                | RequestType::Cancel
+                | RequestType::GetCapabilities
+                | RequestType::GetKeyInfo
         )
     }
 
@@ -507,6 +926,16 @@ pub enum Response<'data> {
         request_id: RequestId,
         plaintext: &'data mut [u8], // Subslice of original buffer without padding
     },
+    EncryptAesEcb {
+        client_id: ClientId,
+        request_id: RequestId,
+        buffer: &'data mut [u8],
+    },
+    DecryptAesEcb {
+        client_id: ClientId,
+        request_id: RequestId,
+        plaintext: &'data mut [u8],
+    },
     CalculateAesCmac {
         client_id: ClientId,
         request_id: RequestId,
@@ -542,6 +971,37 @@ pub enum Response<'data> {
         request_id: RequestId,
         shared_secret: &'data mut [u8],
     },
+    LoadSheKey {
+        client_id: ClientId,
+        request_id: RequestId,
+        m4: &'data mut [u8],
+        m5: &'data mut [u8],
+    },
+    FinishSheBoot {
+        client_id: ClientId,
+        request_id: RequestId,
+    },
+    /// Acknowledges a [Request::Cancel]. It does not indicate whether the target was cancelled.
+    Cancel {
+        client_id: ClientId,
+        request_id: RequestId,
+    },
+    /// The request was not processed. All buffers passed with it can be reclaimed.
+    Cancelled {
+        client_id: ClientId,
+        request_id: RequestId,
+        reason: CancelReason,
+    },
+    GetCapabilities {
+        client_id: ClientId,
+        request_id: RequestId,
+        capabilities: Capabilities,
+    },
+    GetKeyInfo {
+        client_id: ClientId,
+        request_id: RequestId,
+        key_info: KeyInfo,
+    },
 }
 
 impl Request<'_> {
@@ -572,6 +1032,10 @@ impl Request<'_> {
             Request::EncryptAesCbcExternalKey { .. } => RequestType::EncryptAesCbcExternalKey,
             Request::DecryptAesCbc { .. } => RequestType::DecryptAesCbc,
             Request::DecryptAesCbcExternalKey { .. } => RequestType::DecryptAesCbcExternalKey,
+            Request::EncryptAesEcb { .. } => RequestType::EncryptAesEcb,
+            Request::EncryptAesEcbExternalKey { .. } => RequestType::EncryptAesEcbExternalKey,
+            Request::DecryptAesEcb { .. } => RequestType::DecryptAesEcb,
+            Request::DecryptAesEcbExternalKey { .. } => RequestType::DecryptAesEcbExternalKey,
             Request::CalculateAesCmac { .. } => RequestType::CalculateAesCmac,
             Request::CalculateAesCmacExternalKey { .. } => RequestType::CalculateAesCmacExternalKey,
             Request::VerifyAesCmac { .. } => RequestType::VerifyAesCmac,
@@ -586,6 +1050,194 @@ impl Request<'_> {
             Request::VerifyExternalKey { .. } => RequestType::VerifyExternalKey,
             Request::Ecdh { .. } => RequestType::Ecdh,
             Request::EcdhExternalPrivateKey { .. } => RequestType::EcdhExternalPrivateKey,
+            Request::LoadSheKey { .. } => RequestType::LoadSheKey,
+            Request::FinishSheBoot { .. } => RequestType::FinishSheBoot,
+            Request::Cancel { .. } => RequestType::Cancel,
+            Request::GetCapabilities { .. } => RequestType::GetCapabilities,
+            Request::GetKeyInfo { .. } => RequestType::GetKeyInfo,
+        }
+    }
+
+    /// Total size of all buffers referenced by the request in bytes.
+    pub fn data_size(&self) -> usize {
+        self.buffers().iter().map(|(buffer, _)| buffer.len()).sum()
+    }
+
+    /// All buffers referenced by the request. Buffers that the HSM writes to are marked with
+    /// `true`.
+    pub fn buffers(&self) -> Vec<(&[u8], bool), MAX_BUFFERS> {
+        match self {
+            Request::GenerateSymmetricKey { .. }
+            | Request::GenerateKeyPair { .. }
+            | Request::IsKeyAvailable { .. }
+            | Request::FinishSheBoot { .. }
+            | Request::Cancel { .. }
+            | Request::GetCapabilities { .. }
+            | Request::GetKeyInfo { .. } => collect_buffers([]),
+            Request::GetRandom { output: data, .. }
+            | Request::ExportSymmetricKey { data, .. }
+            | Request::ExportPublicKey {
+                public_key: data, ..
+            }
+            | Request::ExportPrivateKey {
+                private_key: data, ..
+            }
+            | Request::EncryptAesEcb { buffer: data, .. }
+            | Request::DecryptAesEcb { buffer: data, .. } => collect_buffers([(data, true)]),
+            Request::ImportSymmetricKey { data, .. } => collect_buffers([(data, false)]),
+            Request::ImportKeyPair {
+                public_key,
+                private_key,
+                ..
+            } => collect_buffers([(public_key, false), (private_key, false)]),
+            Request::EncryptChaChaPoly {
+                nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            }
+            | Request::EncryptAesGcm {
+                iv: nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => collect_buffers([(nonce, false), (buffer, true), (aad, false), (tag, true)]),
+            Request::EncryptChaChaPolyExternalKey {
+                key,
+                nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            }
+            | Request::EncryptAesGcmExternalKey {
+                key,
+                iv: nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => collect_buffers([
+                (key, false),
+                (nonce, false),
+                (buffer, true),
+                (aad, false),
+                (tag, true),
+            ]),
+            Request::DecryptChaChaPoly {
+                nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            }
+            | Request::DecryptAesGcm {
+                iv: nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => collect_buffers([(nonce, false), (buffer, true), (aad, false), (tag, false)]),
+            Request::DecryptChaChaPolyExternalKey {
+                key,
+                nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            }
+            | Request::DecryptAesGcmExternalKey {
+                key,
+                iv: nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => collect_buffers([
+                (key, false),
+                (nonce, false),
+                (buffer, true),
+                (aad, false),
+                (tag, false),
+            ]),
+            Request::EncryptAesCbc { iv, buffer, .. }
+            | Request::DecryptAesCbc { iv, buffer, .. } => {
+                collect_buffers([(iv, false), (buffer, true)])
+            }
+            Request::EncryptAesCbcExternalKey {
+                key, iv, buffer, ..
+            }
+            | Request::DecryptAesCbcExternalKey {
+                key, iv, buffer, ..
+            } => collect_buffers([(key, false), (iv, false), (buffer, true)]),
+            Request::EncryptAesEcbExternalKey { key, buffer, .. }
+            | Request::DecryptAesEcbExternalKey { key, buffer, .. } => {
+                collect_buffers([(key, false), (buffer, true)])
+            }
+            Request::CalculateAesCmac { message, tag, .. }
+            | Request::CalculateHmac { message, tag, .. }
+            | Request::Sign {
+                message,
+                signature: tag,
+                ..
+            } => collect_buffers([(message, false), (tag, true)]),
+            Request::VerifyAesCmac { message, tag, .. }
+            | Request::VerifyHmac { message, tag, .. }
+            | Request::Verify {
+                message,
+                signature: tag,
+                ..
+            } => collect_buffers([(message, false), (tag, false)]),
+            Request::CalculateAesCmacExternalKey {
+                key, message, tag, ..
+            }
+            | Request::CalculateHmacExternalKey {
+                key, message, tag, ..
+            }
+            | Request::SignExternalKey {
+                private_key: key,
+                message,
+                signature: tag,
+                ..
+            } => collect_buffers([(key, false), (message, false), (tag, true)]),
+            Request::VerifyAesCmacExternalKey {
+                key, message, tag, ..
+            }
+            | Request::VerifyHmacExternalKey {
+                key, message, tag, ..
+            }
+            | Request::VerifyExternalKey {
+                public_key: key,
+                message,
+                signature: tag,
+                ..
+            } => collect_buffers([(key, false), (message, false), (tag, false)]),
+            Request::Ecdh {
+                public_key,
+                shared_secret,
+                ..
+            } => collect_buffers([(public_key, false), (shared_secret, true)]),
+            Request::EcdhExternalPrivateKey {
+                public_key,
+                private_key,
+                shared_secret,
+                ..
+            } => collect_buffers([
+                (public_key, false),
+                (private_key, false),
+                (shared_secret, true),
+            ]),
+            Request::LoadSheKey {
+                m1, m2, m3, m4, m5, ..
+            } => collect_buffers([
+                (m1, false),
+                (m2, false),
+                (m3, false),
+                (m4, true),
+                (m5, true),
+            ]),
         }
     }
 
@@ -612,6 +1264,10 @@ impl Request<'_> {
             Request::EncryptAesCbcExternalKey { client_id, .. } => client_id,
             Request::DecryptAesCbc { client_id, .. } => client_id,
             Request::DecryptAesCbcExternalKey { client_id, .. } => client_id,
+            Request::EncryptAesEcb { client_id, .. } => client_id,
+            Request::EncryptAesEcbExternalKey { client_id, .. } => client_id,
+            Request::DecryptAesEcb { client_id, .. } => client_id,
+            Request::DecryptAesEcbExternalKey { client_id, .. } => client_id,
             Request::CalculateAesCmac { client_id, .. } => client_id,
             Request::CalculateAesCmacExternalKey { client_id, .. } => client_id,
             Request::VerifyAesCmac { client_id, .. } => client_id,
@@ -626,6 +1282,11 @@ impl Request<'_> {
             Request::VerifyExternalKey { client_id, .. } => client_id,
             Request::Ecdh { client_id, .. } => client_id,
             Request::EcdhExternalPrivateKey { client_id, .. } => client_id,
+            Request::LoadSheKey { client_id, .. } => client_id,
+            Request::FinishSheBoot { client_id, .. } => client_id,
+            Request::Cancel { client_id, .. } => client_id,
+            Request::GetCapabilities { client_id, .. } => client_id,
+            Request::GetKeyInfo { client_id, .. } => client_id,
         }
     }
 
@@ -652,6 +1313,10 @@ impl Request<'_> {
             Request::EncryptAesCbcExternalKey { request_id, .. } => request_id,
             Request::DecryptAesCbc { request_id, .. } => request_id,
             Request::DecryptAesCbcExternalKey { request_id, .. } => request_id,
+            Request::EncryptAesEcb { request_id, .. } => request_id,
+            Request::EncryptAesEcbExternalKey { request_id, .. } => request_id,
+            Request::DecryptAesEcb { request_id, .. } => request_id,
+            Request::DecryptAesEcbExternalKey { request_id, .. } => request_id,
             Request::CalculateAesCmac { request_id, .. } => request_id,
             Request::CalculateAesCmacExternalKey { request_id, .. } => request_id,
             Request::VerifyAesCmac { request_id, .. } => request_id,
@@ -666,6 +1331,11 @@ impl Request<'_> {
             Request::VerifyExternalKey { request_id, .. } => request_id,
             Request::Ecdh { request_id, .. } => request_id,
             Request::EcdhExternalPrivateKey { request_id, .. } => request_id,
+            Request::LoadSheKey { request_id, .. } => request_id,
+            Request::FinishSheBoot { request_id, .. } => request_id,
+            Request::Cancel { request_id, .. } => request_id,
+            Request::GetCapabilities { request_id, .. } => request_id,
+            Request::GetKeyInfo { request_id, .. } => request_id,
         }
     }
 
@@ -692,6 +1362,10 @@ impl Request<'_> {
             Request::EncryptAesCbcExternalKey { client_id, .. } => *client_id = new_client_id,
             Request::DecryptAesCbc { client_id, .. } => *client_id = new_client_id,
             Request::DecryptAesCbcExternalKey { client_id, .. } => *client_id = new_client_id,
+            Request::EncryptAesEcb { client_id, .. } => *client_id = new_client_id,
+            Request::EncryptAesEcbExternalKey { client_id, .. } => *client_id = new_client_id,
+            Request::DecryptAesEcb { client_id, .. } => *client_id = new_client_id,
+            Request::DecryptAesEcbExternalKey { client_id, .. } => *client_id = new_client_id,
             Request::CalculateAesCmac { client_id, .. } => *client_id = new_client_id,
             Request::CalculateAesCmacExternalKey { client_id, .. } => *client_id = new_client_id,
             Request::VerifyAesCmac { client_id, .. } => *client_id = new_client_id,
@@ -706,6 +1380,11 @@ impl Request<'_> {
             Request::VerifyExternalKey { client_id, .. } => *client_id = new_client_id,
             Request::Ecdh { client_id, .. } => *client_id = new_client_id,
             Request::EcdhExternalPrivateKey { client_id, .. } => *client_id = new_client_id,
+            Request::LoadSheKey { client_id, .. } => *client_id = new_client_id,
+            Request::FinishSheBoot { client_id, .. } => *client_id = new_client_id,
+            Request::Cancel { client_id, .. } => *client_id = new_client_id,
+            Request::GetCapabilities { client_id, .. } => *client_id = new_client_id,
+            Request::GetKeyInfo { client_id, .. } => *client_id = new_client_id,
         }
     }
 
@@ -736,6 +1415,10 @@ impl Request<'_> {
             Request::EncryptAesCbcExternalKey { request_id, .. } => *request_id = new_request_id,
             Request::DecryptAesCbc { request_id, .. } => *request_id = new_request_id,
             Request::DecryptAesCbcExternalKey { request_id, .. } => *request_id = new_request_id,
+            Request::EncryptAesEcb { request_id, .. } => *request_id = new_request_id,
+            Request::EncryptAesEcbExternalKey { request_id, .. } => *request_id = new_request_id,
+            Request::DecryptAesEcb { request_id, .. } => *request_id = new_request_id,
+            Request::DecryptAesEcbExternalKey { request_id, .. } => *request_id = new_request_id,
             Request::CalculateAesCmac { request_id, .. } => *request_id = new_request_id,
             Request::CalculateAesCmacExternalKey { request_id, .. } => *request_id = new_request_id,
             Request::VerifyAesCmac { request_id, .. } => *request_id = new_request_id,
@@ -750,6 +1433,109 @@ impl Request<'_> {
             Request::VerifyExternalKey { request_id, .. } => *request_id = new_request_id,
             Request::Ecdh { request_id, .. } => *request_id = new_request_id,
             Request::EcdhExternalPrivateKey { request_id, .. } => *request_id = new_request_id,
+            Request::LoadSheKey { request_id, .. } => *request_id = new_request_id,
+            Request::FinishSheBoot { request_id, .. } => *request_id = new_request_id,
+            Request::Cancel { request_id, .. } => *request_id = new_request_id,
+            Request::GetCapabilities { request_id, .. } => *request_id = new_request_id,
+            Request::GetKeyInfo { request_id, .. } => *request_id = new_request_id,
+        }
+    }
+
+    pub fn get_deadline(&self) -> Option<u64> {
+        *match self {
+            Request::GetRandom { deadline, .. } => deadline,
+            Request::GenerateSymmetricKey { deadline, .. } => deadline,
+            Request::GenerateKeyPair { deadline, .. } => deadline,
+            Request::ImportSymmetricKey { deadline, .. } => deadline,
+            Request::ImportKeyPair { deadline, .. } => deadline,
+            Request::ExportSymmetricKey { deadline, .. } => deadline,
+            Request::ExportPublicKey { deadline, .. } => deadline,
+            Request::ExportPrivateKey { deadline, .. } => deadline,
+            Request::IsKeyAvailable { deadline, .. } => deadline,
+            Request::EncryptChaChaPoly { deadline, .. } => deadline,
+            Request::EncryptChaChaPolyExternalKey { deadline, .. } => deadline,
+            Request::DecryptChaChaPoly { deadline, .. } => deadline,
+            Request::DecryptChaChaPolyExternalKey { deadline, .. } => deadline,
+            Request::EncryptAesGcm { deadline, .. } => deadline,
+            Request::EncryptAesGcmExternalKey { deadline, .. } => deadline,
+            Request::DecryptAesGcm { deadline, .. } => deadline,
+            Request::DecryptAesGcmExternalKey { deadline, .. } => deadline,
+            Request::EncryptAesCbc { deadline, .. } => deadline,
+            Request::EncryptAesCbcExternalKey { deadline, .. } => deadline,
+            Request::DecryptAesCbc { deadline, .. } => deadline,
+            Request::DecryptAesCbcExternalKey { deadline, .. } => deadline,
+            Request::EncryptAesEcb { deadline, .. } => deadline,
+            Request::EncryptAesEcbExternalKey { deadline, .. } => deadline,
+            Request::DecryptAesEcb { deadline, .. } => deadline,
+            Request::DecryptAesEcbExternalKey { deadline, .. } => deadline,
+            Request::CalculateAesCmac { deadline, .. } => deadline,
+            Request::CalculateAesCmacExternalKey { deadline, .. } => deadline,
+            Request::VerifyAesCmac { deadline, .. } => deadline,
+            Request::VerifyAesCmacExternalKey { deadline, .. } => deadline,
+            Request::CalculateHmac { deadline, .. } => deadline,
+            Request::CalculateHmacExternalKey { deadline, .. } => deadline,
+            Request::VerifyHmac { deadline, .. } => deadline,
+            Request::VerifyHmacExternalKey { deadline, .. } => deadline,
+            Request::Sign { deadline, .. } => deadline,
+            Request::SignExternalKey { deadline, .. } => deadline,
+            Request::Verify { deadline, .. } => deadline,
+            Request::VerifyExternalKey { deadline, .. } => deadline,
+            Request::Ecdh { deadline, .. } => deadline,
+            Request::EcdhExternalPrivateKey { deadline, .. } => deadline,
+            Request::LoadSheKey { deadline, .. } => deadline,
+            Request::FinishSheBoot { deadline, .. } => deadline,
+            Request::Cancel { deadline, .. } => deadline,
+            Request::GetCapabilities { deadline, .. } => deadline,
+            Request::GetKeyInfo { deadline, .. } => deadline,
+        }
+    }
+
+    pub fn set_deadline(&mut self, new_deadline: Option<u64>) {
+        match self {
+            Request::GetRandom { deadline, .. } => *deadline = new_deadline,
+            Request::GenerateSymmetricKey { deadline, .. } => *deadline = new_deadline,
+            Request::GenerateKeyPair { deadline, .. } => *deadline = new_deadline,
+            Request::ImportSymmetricKey { deadline, .. } => *deadline = new_deadline,
+            Request::ImportKeyPair { deadline, .. } => *deadline = new_deadline,
+            Request::ExportSymmetricKey { deadline, .. } => *deadline = new_deadline,
+            Request::ExportPublicKey { deadline, .. } => *deadline = new_deadline,
+            Request::ExportPrivateKey { deadline, .. } => *deadline = new_deadline,
+            Request::IsKeyAvailable { deadline, .. } => *deadline = new_deadline,
+            Request::EncryptChaChaPoly { deadline, .. } => *deadline = new_deadline,
+            Request::EncryptChaChaPolyExternalKey { deadline, .. } => *deadline = new_deadline,
+            Request::DecryptChaChaPoly { deadline, .. } => *deadline = new_deadline,
+            Request::DecryptChaChaPolyExternalKey { deadline, .. } => *deadline = new_deadline,
+            Request::EncryptAesGcm { deadline, .. } => *deadline = new_deadline,
+            Request::EncryptAesGcmExternalKey { deadline, .. } => *deadline = new_deadline,
+            Request::DecryptAesGcm { deadline, .. } => *deadline = new_deadline,
+            Request::DecryptAesGcmExternalKey { deadline, .. } => *deadline = new_deadline,
+            Request::EncryptAesCbc { deadline, .. } => *deadline = new_deadline,
+            Request::EncryptAesCbcExternalKey { deadline, .. } => *deadline = new_deadline,
+            Request::DecryptAesCbc { deadline, .. } => *deadline = new_deadline,
+            Request::DecryptAesCbcExternalKey { deadline, .. } => *deadline = new_deadline,
+            Request::EncryptAesEcb { deadline, .. } => *deadline = new_deadline,
+            Request::EncryptAesEcbExternalKey { deadline, .. } => *deadline = new_deadline,
+            Request::DecryptAesEcb { deadline, .. } => *deadline = new_deadline,
+            Request::DecryptAesEcbExternalKey { deadline, .. } => *deadline = new_deadline,
+            Request::CalculateAesCmac { deadline, .. } => *deadline = new_deadline,
+            Request::CalculateAesCmacExternalKey { deadline, .. } => *deadline = new_deadline,
+            Request::VerifyAesCmac { deadline, .. } => *deadline = new_deadline,
+            Request::VerifyAesCmacExternalKey { deadline, .. } => *deadline = new_deadline,
+            Request::CalculateHmac { deadline, .. } => *deadline = new_deadline,
+            Request::CalculateHmacExternalKey { deadline, .. } => *deadline = new_deadline,
+            Request::VerifyHmac { deadline, .. } => *deadline = new_deadline,
+            Request::VerifyHmacExternalKey { deadline, .. } => *deadline = new_deadline,
+            Request::Sign { deadline, .. } => *deadline = new_deadline,
+            Request::SignExternalKey { deadline, .. } => *deadline = new_deadline,
+            Request::Verify { deadline, .. } => *deadline = new_deadline,
+            Request::VerifyExternalKey { deadline, .. } => *deadline = new_deadline,
+            Request::Ecdh { deadline, .. } => *deadline = new_deadline,
+            Request::EcdhExternalPrivateKey { deadline, .. } => *deadline = new_deadline,
+            Request::LoadSheKey { deadline, .. } => *deadline = new_deadline,
+            Request::FinishSheBoot { deadline, .. } => *deadline = new_deadline,
+            Request::Cancel { deadline, .. } => *deadline = new_deadline,
+            Request::GetCapabilities { deadline, .. } => *deadline = new_deadline,
+            Request::GetKeyInfo { deadline, .. } => *deadline = new_deadline,
         }
     }
 }
@@ -773,6 +1559,8 @@ impl Response<'_> {
             Response::DecryptAesGcm { client_id, .. } => client_id,
             Response::EncryptAesCbc { client_id, .. } => client_id,
             Response::DecryptAesCbc { client_id, .. } => client_id,
+            Response::EncryptAesEcb { client_id, .. } => client_id,
+            Response::DecryptAesEcb { client_id, .. } => client_id,
             Response::CalculateAesCmac { client_id, .. } => client_id,
             Response::VerifyAesCmac { client_id, .. } => client_id,
             Response::CalculateHmac { client_id, .. } => client_id,
@@ -780,6 +1568,12 @@ impl Response<'_> {
             Response::Sign { client_id, .. } => client_id,
             Response::Verify { client_id, .. } => client_id,
             Response::Ecdh { client_id, .. } => client_id,
+            Response::LoadSheKey { client_id, .. } => client_id,
+            Response::FinishSheBoot { client_id, .. } => client_id,
+            Response::Cancel { client_id, .. } => client_id,
+            Response::Cancelled { client_id, .. } => client_id,
+            Response::GetCapabilities { client_id, .. } => client_id,
+            Response::GetKeyInfo { client_id, .. } => client_id,
         }
     }
 
@@ -801,6 +1595,8 @@ impl Response<'_> {
             Response::DecryptAesGcm { request_id, .. } => request_id,
             Response::EncryptAesCbc { request_id, .. } => request_id,
             Response::DecryptAesCbc { request_id, .. } => request_id,
+            Response::EncryptAesEcb { request_id, .. } => request_id,
+            Response::DecryptAesEcb { request_id, .. } => request_id,
             Response::CalculateAesCmac { request_id, .. } => request_id,
             Response::VerifyAesCmac { request_id, .. } => request_id,
             Response::CalculateHmac { request_id, .. } => request_id,
@@ -808,6 +1604,12 @@ impl Response<'_> {
             Response::Sign { request_id, .. } => request_id,
             Response::Verify { request_id, .. } => request_id,
             Response::Ecdh { request_id, .. } => request_id,
+            Response::LoadSheKey { request_id, .. } => request_id,
+            Response::FinishSheBoot { request_id, .. } => request_id,
+            Response::Cancel { request_id, .. } => request_id,
+            Response::Cancelled { request_id, .. } => request_id,
+            Response::GetCapabilities { request_id, .. } => request_id,
+            Response::GetKeyInfo { request_id, .. } => request_id,
         }
     }
 }
@@ -0,0 +1,226 @@
+//! Executor-agnostic synchronization primitives.
+//!
+//! The core and the workers share resources like the key store or the random number generator
+//! through the asynchronous [Mutex] of this module. The only platform specific part is the
+//! [RawMutex] that protects the short critical sections inside the [Mutex]. Adapters for embassy,
+//! RTIC and std can be found in [crate::integration]. Other executors or RTOSes only have to
+//! implement [RawMutex]. Waking uses [core::task::Waker], which every executor provides.
+
+use core::cell::UnsafeCell;
+use core::future::poll_fn;
+use core::ops::{Deref, DerefMut};
+use core::task::{Poll, Waker};
+
+/// Blocking lock protecting short critical sections.
+///
+/// # Safety
+///
+/// Implementations must guarantee mutual exclusion: While a closure passed to [RawMutex::lock]
+/// runs, no other closure passed to `lock` of the same instance may run, neither on another
+/// thread nor in an interrupt handler.
+pub unsafe trait RawMutex {
+    /// Unlocked instance of the mutex
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self;
+
+    /// Run `f` while holding the lock.
+    fn lock<R>(&self, f: impl FnOnce() -> R) -> R;
+}
+
+/// Task waiting to be woken up.
+///
+/// Only a single waker is stored. If a different waker is registered, the previous one is woken
+/// up so that its task can register again. This is correct for any number of waiting tasks, but
+/// avoids allocating room for all of them.
+#[derive(Debug, Default)]
+pub struct WakerRegistration {
+    waker: Option<Waker>,
+}
+
+impl WakerRegistration {
+    pub const fn new() -> Self {
+        Self { waker: None }
+    }
+
+    /// Register a waker to be woken up by the next call of [WakerRegistration::wake].
+    pub fn register(&mut self, waker: &Waker) {
+        match &self.waker {
+            Some(registered) if registered.will_wake(waker) => {}
+            Some(_) => {
+                if let Some(previous) = self.waker.replace(waker.clone()) {
+                    previous.wake();
+                }
+            }
+            None => self.waker = Some(waker.clone()),
+        }
+    }
+
+    /// Wake the registered waker, if any.
+    pub fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct State {
+    locked: bool,
+    waiting: WakerRegistration,
+}
+
+/// Asynchronous mutex. Tasks waiting for the lock are suspended instead of spinning.
+pub struct Mutex<M: RawMutex, T: ?Sized> {
+    raw: M,
+    /// Only accessed while `raw` is locked
+    state: UnsafeCell<State>,
+    /// Only accessed through a [MutexGuard]
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: The data is only accessed by the task holding the lock.
+unsafe impl<M: RawMutex + Send, T: ?Sized + Send> Send for Mutex<M, T> {}
+// SAFETY: The state is protected by the raw mutex and the data by the lock.
+unsafe impl<M: RawMutex + Sync, T: ?Sized + Send> Sync for Mutex<M, T> {}
+
+impl<M: RawMutex, T> Mutex<M, T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            raw: M::INIT,
+            state: UnsafeCell::new(State {
+                locked: false,
+                waiting: WakerRegistration::new(),
+            }),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<M: RawMutex, T: ?Sized> Mutex<M, T> {
+    /// Wait until the lock is available and acquire it.
+    pub async fn lock(&self) -> MutexGuard<'_, M, T> {
+        poll_fn(|cx| {
+            let acquired = self.with_state(|state| {
+                if state.locked {
+                    state.waiting.register(cx.waker());
+                    false
+                } else {
+                    state.locked = true;
+                    true
+                }
+            });
+            if acquired {
+                Poll::Ready(MutexGuard { mutex: self })
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Acquire the lock if it is available.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, M, T>> {
+        let acquired = self.with_state(|state| !core::mem::replace(&mut state.locked, true));
+        if acquired {
+            Some(MutexGuard { mutex: self })
+        } else {
+            None
+        }
+    }
+
+    /// Access the data without locking. The mutable borrow guarantees that nobody holds the lock.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        self.raw.lock(|| {
+            // SAFETY: The state is only accessed while the raw mutex is locked.
+            let state = unsafe { &mut *self.state.get() };
+            f(state)
+        })
+    }
+}
+
+/// Access to the data of a locked [Mutex]. The lock is released when the guard is dropped.
+pub struct MutexGuard<'a, M: RawMutex, T: ?Sized> {
+    mutex: &'a Mutex<M, T>,
+}
+
+impl<M: RawMutex, T: ?Sized> Drop for MutexGuard<'_, M, T> {
+    fn drop(&mut self) {
+        self.mutex.with_state(|state| {
+            state.locked = false;
+            state.waiting.wake();
+        })
+    }
+}
+
+impl<M: RawMutex, T: ?Sized> Deref for MutexGuard<'_, M, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The guard holds the lock, so there is no other reference to the data.
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<M: RawMutex, T: ?Sized> DerefMut for MutexGuard<'_, M, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: The guard holds the lock, so there is no other reference to the data.
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::Context;
+    use futures::task::noop_waker_ref;
+
+    /// Raw mutex for a single thread without interrupts
+    struct LocalRawMutex;
+
+    // SAFETY: The tests run on a single thread.
+    unsafe impl RawMutex for LocalRawMutex {
+        const INIT: Self = LocalRawMutex;
+
+        fn lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            f()
+        }
+    }
+
+    #[test]
+    fn lock_and_unlock() {
+        let mutex = Mutex::<LocalRawMutex, _>::new(1u32);
+        let mut guard = mutex.try_lock().expect("failed to lock");
+        *guard += 1;
+        assert!(mutex.try_lock().is_none());
+        // A failed attempt does not release the lock
+        assert!(mutex.try_lock().is_none());
+        drop(guard);
+        assert_eq!(*mutex.try_lock().expect("failed to lock"), 2);
+        assert_eq!(mutex.into_inner(), 2);
+    }
+
+    #[test]
+    fn waiting_task_acquires_released_lock() {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let mutex = Mutex::<LocalRawMutex, _>::new(0u32);
+        let guard = mutex.try_lock().expect("failed to lock");
+        let mut waiting = pin!(mutex.lock());
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+        drop(guard);
+        let Poll::Ready(mut guard) = waiting.as_mut().poll(&mut cx) else {
+            panic!("failed to acquire released lock")
+        };
+        *guard = 1;
+        drop(guard);
+        assert_eq!(*mutex.try_lock().expect("failed to lock"), 1);
+    }
+}
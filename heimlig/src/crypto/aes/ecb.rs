@@ -0,0 +1,221 @@
+use crate::crypto::Error;
+use aes::{
+    cipher::{BlockCipher, BlockDecrypt, BlockEncrypt, KeyInit, Unsigned},
+    Aes128, Aes192, Aes256,
+};
+
+/// Validation of key and buffer sizes.
+fn check_sizes<C>(key: &[u8], buffer: &[u8]) -> Result<(), Error>
+where
+    C: BlockCipher + KeyInit,
+{
+    if key.len() != C::KeySize::USIZE {
+        return Err(Error::InvalidSymmetricKeySize);
+    }
+    if buffer.len() % C::BlockSize::USIZE != 0 {
+        return Err(Error::InvalidBufferSize);
+    }
+    Ok(())
+}
+
+/// AES-ECB encryption: generic over an underlying AES implementation.
+fn encrypt_in_place<'data, C>(key: &[u8], buffer: &'data mut [u8]) -> Result<&'data [u8], Error>
+where
+    C: BlockEncrypt + BlockCipher + KeyInit,
+{
+    check_sizes::<C>(key, buffer)?;
+    let cipher = C::new(key.into());
+    for block in buffer.chunks_exact_mut(C::BlockSize::USIZE) {
+        cipher.encrypt_block(block.into());
+    }
+    Ok(buffer)
+}
+
+/// AES-ECB decryption: generic over an underlying AES implementation.
+fn decrypt_in_place<'data, C>(key: &[u8], buffer: &'data mut [u8]) -> Result<&'data [u8], Error>
+where
+    C: BlockDecrypt + BlockCipher + KeyInit,
+{
+    check_sizes::<C>(key, buffer)?;
+    let cipher = C::new(key.into());
+    for block in buffer.chunks_exact_mut(C::BlockSize::USIZE) {
+        cipher.decrypt_block(block.into());
+    }
+    Ok(buffer)
+}
+
+macro_rules! define_aes_ecb_impl {
+    (
+        $encryptor:ident,
+        $decryptor:ident,
+        $core:tt
+    ) => {
+        /// Encrypt a buffer in-place. No padding is applied, so the buffer size has to be a
+        /// multiple of the block size.
+        pub fn $encryptor<'data>(
+            key: &[u8],
+            buffer: &'data mut [u8],
+        ) -> Result<&'data [u8], Error> {
+            encrypt_in_place::<$core>(key, buffer)
+        }
+
+        /// Decrypt a buffer in-place. No padding is removed, so the buffer size has to be a
+        /// multiple of the block size.
+        pub fn $decryptor<'data>(
+            key: &[u8],
+            buffer: &'data mut [u8],
+        ) -> Result<&'data [u8], Error> {
+            decrypt_in_place::<$core>(key, buffer)
+        }
+    };
+}
+
+define_aes_ecb_impl!(aes128ecb_encrypt, aes128ecb_decrypt, Aes128);
+define_aes_ecb_impl!(aes192ecb_encrypt, aes192ecb_decrypt, Aes192);
+define_aes_ecb_impl!(aes256ecb_encrypt, aes256ecb_decrypt, Aes256);
+
+#[cfg(test)]
+mod test {
+    extern crate alloc;
+    use super::*;
+    use crate::crypto::aes::test::*;
+    use alloc::borrow::ToOwned;
+    use heapless::Vec;
+
+    macro_rules! define_aes_ecb_encrypt_decrypt_test {
+        (
+        $test_name:ident,
+        $cipher:ty,
+        $key:tt,
+        $plaintext:tt,
+        $ciphertext:tt
+    ) => {
+            #[test]
+            fn $test_name() {
+                let mut buffer = $plaintext.to_owned();
+                let encrypted =
+                    encrypt_in_place::<$cipher>($key, &mut buffer).expect("encryption error");
+                assert_eq!(encrypted, $ciphertext, "ciphertext mismatch");
+                let decrypted =
+                    decrypt_in_place::<$cipher>($key, &mut buffer).expect("decryption error");
+                assert_eq!(decrypted, $plaintext, "plaintext mismatch");
+            }
+        };
+    }
+
+    // Test vectors from NIST SP 800-38A, F.1
+    const NIST_KEY128: &[u8] = &[
+        0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f,
+        0x3c,
+    ];
+    const NIST_KEY192: &[u8] = &[
+        0x8e, 0x73, 0xb0, 0xf7, 0xda, 0x0e, 0x64, 0x52, 0xc8, 0x10, 0xf3, 0x2b, 0x80, 0x90, 0x79,
+        0xe5, 0x62, 0xf8, 0xea, 0xd2, 0x52, 0x2c, 0x6b, 0x7b,
+    ];
+    const NIST_KEY256: &[u8] = &[
+        0x60, 0x3d, 0xeb, 0x10, 0x15, 0xca, 0x71, 0xbe, 0x2b, 0x73, 0xae, 0xf0, 0x85, 0x7d, 0x77,
+        0x81, 0x1f, 0x35, 0x2c, 0x07, 0x3b, 0x61, 0x08, 0xd7, 0x2d, 0x98, 0x10, 0xa3, 0x09, 0x14,
+        0xdf, 0xf4,
+    ];
+    const NIST_PLAINTEXT: &[u8] = &[
+        0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17,
+        0x2a, 0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c, 0x9e, 0xb7, 0x6f, 0xac, 0x45, 0xaf,
+        0x8e, 0x51,
+    ];
+
+    define_aes_ecb_encrypt_decrypt_test!(
+        test_aes128ecb_encrypt_decrypt,
+        Aes128,
+        NIST_KEY128,
+        NIST_PLAINTEXT,
+        [
+            0x3a, 0xd7, 0x7b, 0xb4, 0x0d, 0x7a, 0x36, 0x60, 0xa8, 0x9e, 0xca, 0xf3, 0x24, 0x66,
+            0xef, 0x97, 0xf5, 0xd3, 0xd5, 0x85, 0x03, 0xb9, 0x69, 0x9d, 0xe7, 0x85, 0x89, 0x5a,
+            0x96, 0xfd, 0xba, 0xaf,
+        ]
+    );
+
+    define_aes_ecb_encrypt_decrypt_test!(
+        test_aes192ecb_encrypt_decrypt,
+        Aes192,
+        NIST_KEY192,
+        NIST_PLAINTEXT,
+        [
+            0xbd, 0x33, 0x4f, 0x1d, 0x6e, 0x45, 0xf2, 0x5f, 0xf7, 0x12, 0xa2, 0x14, 0x57, 0x1f,
+            0xa5, 0xcc, 0x97, 0x41, 0x04, 0x84, 0x6d, 0x0a, 0xd3, 0xad, 0x77, 0x34, 0xec, 0xb3,
+            0xec, 0xee, 0x4e, 0xef,
+        ]
+    );
+
+    define_aes_ecb_encrypt_decrypt_test!(
+        test_aes256ecb_encrypt_decrypt,
+        Aes256,
+        NIST_KEY256,
+        NIST_PLAINTEXT,
+        [
+            0xf3, 0xee, 0xd1, 0xbd, 0xb5, 0xd2, 0xa0, 0x3c, 0x06, 0x4b, 0x5a, 0x7e, 0x3d, 0xb1,
+            0x81, 0xf8, 0x59, 0x1c, 0xcb, 0x10, 0xd4, 0x10, 0xed, 0x26, 0xdc, 0x5b, 0xa7, 0x4a,
+            0x31, 0x36, 0x28, 0x70,
+        ]
+    );
+
+    macro_rules! define_aes_ecb_errors_test {
+        (
+        $test_name:ident,
+        $cipher:ty,
+        $key:tt,
+        $wrong_key_sizes:tt
+    ) => {
+            #[test]
+            fn $test_name() {
+                for size in $wrong_key_sizes {
+                    let mut buffer = PLAINTEXT_PADDED.to_owned();
+                    let mut wrong_key: Vec<u8, 256> = Vec::new();
+                    wrong_key.resize(size, 0).expect("Allocation error");
+                    assert_eq!(
+                        encrypt_in_place::<$cipher>(&wrong_key, &mut buffer),
+                        Err(Error::InvalidSymmetricKeySize)
+                    );
+                    assert_eq!(
+                        decrypt_in_place::<$cipher>(&wrong_key, &mut buffer),
+                        Err(Error::InvalidSymmetricKeySize)
+                    );
+                }
+
+                for size in [1, 15, 17, 65] {
+                    let mut not_padded_buffer: Vec<u8, 65> = Vec::new();
+                    not_padded_buffer.resize(size, 0).expect("Allocation error");
+                    assert_eq!(
+                        encrypt_in_place::<$cipher>($key, &mut not_padded_buffer),
+                        Err(Error::InvalidBufferSize)
+                    );
+                    assert_eq!(
+                        decrypt_in_place::<$cipher>($key, &mut not_padded_buffer),
+                        Err(Error::InvalidBufferSize)
+                    );
+                }
+            }
+        };
+    }
+
+    define_aes_ecb_errors_test!(
+        test_aes128ecb_errors,
+        Aes128,
+        KEY128,
+        [0, 1, 8, 24, 32, 128]
+    );
+
+    define_aes_ecb_errors_test!(
+        test_aes192ecb_errors,
+        Aes192,
+        KEY192,
+        [0, 1, 8, 16, 32, 192]
+    );
+
+    define_aes_ecb_errors_test!(
+        test_aes256ecb_errors,
+        Aes256,
+        KEY256,
+        [0, 1, 8, 16, 24, 256]
+    );
+}
@@ -1,6 +1,7 @@
 pub mod cbc;
 pub mod ccm;
 pub mod cmac;
+pub mod ecb;
 pub mod gcm;
 
 use aes::{
@@ -1,5 +1,28 @@
-use elliptic_curve::{Curve, CurveArithmetic, PublicKey, SecretKey};
+use crate::crypto::Error;
+use elliptic_curve::{
+    generic_array::{typenum::Unsigned, GenericArray},
+    pkcs8::{
+        der::{asn1::BitStringRef, Encode},
+        spki::{AlgorithmIdentifierRef, SubjectPublicKeyInfoRef},
+        AssociatedOid, DecodePrivateKey,
+    },
+    sec1::{EncodedPoint, FromEncodedPoint, ModulusSize, ToEncodedPoint},
+    AffinePoint, Curve, CurveArithmetic, FieldBytesSize, PublicKey, SecretKey,
+};
+use pem_rfc7468::LineEnding;
 use rand_chacha::rand_core::{CryptoRng, RngCore};
+use zeroize::Zeroizing;
+
+/// PEM label of PKCS#8 encoded private keys.
+const PKCS8_PEM_LABEL: &str = "PRIVATE KEY";
+/// PEM label of SEC1 encoded private keys.
+const SEC1_PEM_LABEL: &str = "EC PRIVATE KEY";
+/// PEM label of SubjectPublicKeyInfo encoded public keys.
+const SPKI_PEM_LABEL: &str = "PUBLIC KEY";
+/// Upper bound for DER encoded private keys of all supported curves.
+const MAX_PRIVATE_KEY_DER_SIZE: usize = 256;
+/// Upper bound for DER encoded public keys of all supported curves.
+const MAX_PUBLIC_KEY_DER_SIZE: usize = 128;
 
 /// Generate an elliptic curve key pair.   
 ///
@@ -15,3 +38,151 @@ where
     let public = private.public_key();
     (public, private)
 }
+
+/// Decode a private key from a PKCS#8 DER document.
+pub fn private_key_from_pkcs8_der<C>(der: &[u8]) -> Result<SecretKey<C>, Error>
+where
+    C: AssociatedOid + CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    SecretKey::<C>::from_pkcs8_der(der).map_err(|_| Error::InvalidPrivateKey)
+}
+
+/// Decode a private key from a SEC1 `ECPrivateKey` DER document.
+pub fn private_key_from_sec1_der<C>(der: &[u8]) -> Result<SecretKey<C>, Error>
+where
+    C: CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    SecretKey::<C>::from_sec1_der(der).map_err(|_| Error::InvalidPrivateKey)
+}
+
+/// Decode a private key from a PEM document. Both PKCS#8 (`PRIVATE KEY`) and SEC1
+/// (`EC PRIVATE KEY`) documents are supported.
+pub fn private_key_from_pem<C>(pem: &[u8]) -> Result<SecretKey<C>, Error>
+where
+    C: AssociatedOid + CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    let mut der = Zeroizing::new([0u8; MAX_PRIVATE_KEY_DER_SIZE]);
+    let (label, der) =
+        pem_rfc7468::decode(pem, der.as_mut_slice()).map_err(|_| Error::InvalidPrivateKey)?;
+    match label {
+        PKCS8_PEM_LABEL => private_key_from_pkcs8_der(der),
+        SEC1_PEM_LABEL => private_key_from_sec1_der(der),
+        _ => Err(Error::InvalidPrivateKey),
+    }
+}
+
+/// Write the raw private scalar and the uncompressed public point without the SEC1 tag byte of a
+/// key pair. This is the format used by the key store.
+///
+/// returns: The slices of `private_key` and `public_key` that were written to.
+pub fn key_pair_to_raw<'a, 'b, C>(
+    secret_key: &SecretKey<C>,
+    private_key: &'a mut [u8],
+    public_key: &'b mut [u8],
+) -> Result<(&'a [u8], &'b [u8]), Error>
+where
+    C: CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    let private_key_size = FieldBytesSize::<C>::USIZE;
+    let public_key_size = <FieldBytesSize<C> as ModulusSize>::UntaggedPointSize::USIZE;
+    if private_key.len() < private_key_size || public_key.len() < public_key_size {
+        return Err(Error::InvalidBufferSize);
+    }
+    let private_key = &mut private_key[..private_key_size];
+    private_key.copy_from_slice(&Zeroizing::new(secret_key.to_bytes()));
+    let public_key = &mut public_key[..public_key_size];
+    public_key.copy_from_slice(&secret_key.public_key().to_encoded_point(false).as_bytes()[1..]);
+    Ok((private_key, public_key))
+}
+
+/// Create a public key from an uncompressed point without the SEC1 tag byte. This is the format
+/// used by the key store.
+pub fn public_key_from_raw<C>(public_key: &[u8]) -> Result<PublicKey<C>, Error>
+where
+    C: CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    if public_key.len() != <FieldBytesSize<C> as ModulusSize>::UntaggedPointSize::USIZE {
+        return Err(Error::InvalidPublicKey);
+    }
+    let point = EncodedPoint::<C>::from_untagged_bytes(GenericArray::from_slice(public_key));
+    PublicKey::<C>::from_encoded_point(&point)
+        .into_option()
+        .ok_or(Error::InvalidPublicKey)
+}
+
+/// Encode a public key as SEC1 point including the tag byte.
+///
+/// returns: The slice of `dest` that was written to.
+pub fn public_key_to_sec1<'a, C>(
+    public_key: &PublicKey<C>,
+    compress: bool,
+    dest: &'a mut [u8],
+) -> Result<&'a [u8], Error>
+where
+    C: CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    let point = public_key.to_encoded_point(compress);
+    let dest = dest
+        .get_mut(..point.len())
+        .ok_or(Error::InvalidBufferSize)?;
+    dest.copy_from_slice(point.as_bytes());
+    Ok(dest)
+}
+
+/// Encode a public key as SubjectPublicKeyInfo DER document.
+///
+/// returns: The slice of `dest` that was written to.
+pub fn public_key_to_spki_der<'a, C>(
+    public_key: &PublicKey<C>,
+    dest: &'a mut [u8],
+) -> Result<&'a [u8], Error>
+where
+    C: AssociatedOid + CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    let point = public_key.to_encoded_point(false);
+    let spki = SubjectPublicKeyInfoRef {
+        algorithm: AlgorithmIdentifierRef {
+            oid: elliptic_curve::ALGORITHM_OID,
+            parameters: Some((&C::OID).into()),
+        },
+        subject_public_key: BitStringRef::from_bytes(point.as_bytes())
+            .map_err(|_| Error::InvalidPublicKey)?,
+    };
+    let written = spki
+        .encode_to_slice(dest)
+        .map_err(|_| Error::InvalidBufferSize)?;
+    Ok(written)
+}
+
+/// Encode a public key as SubjectPublicKeyInfo PEM document (`PUBLIC KEY`) with LF line endings.
+///
+/// returns: The slice of `dest` that was written to.
+pub fn public_key_to_spki_pem<'a, C>(
+    public_key: &PublicKey<C>,
+    dest: &'a mut [u8],
+) -> Result<&'a [u8], Error>
+where
+    C: AssociatedOid + CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    let mut der = [0u8; MAX_PUBLIC_KEY_DER_SIZE];
+    let der = public_key_to_spki_der(public_key, &mut der)?;
+    let written = pem_rfc7468::encode(SPKI_PEM_LABEL, LineEnding::LF, der, dest)
+        .map_err(|_| Error::InvalidBufferSize)?;
+    Ok(written.as_bytes())
+}
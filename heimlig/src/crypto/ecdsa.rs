@@ -1,6 +1,8 @@
 use crate::crypto::Error;
 
+use core::ops::Add;
 use ecdsa::{
+    der::{MaxOverhead, MaxSize},
     elliptic_curve::{
         generic_array::{typenum::Unsigned, ArrayLength, GenericArray},
         ops::Invert,
@@ -26,7 +28,7 @@ type PublicKeySize<C> = <FieldBytesSize<C> as ModulusSize>::UntaggedPointSize;
 type PublicKeyBytes<C> = GenericArray<u8, PublicKeySize<C>>;
 type DigestSize<C> = FieldBytesSize<C>;
 
-fn check_digest_and_signature_sizes<C>(digest: &[u8], signature: &[u8]) -> Result<(), Error>
+fn check_signature_size<C>(signature: &[u8]) -> Result<(), Error>
 where
     C: Curve,
     SignatureSize<C>: ArrayLength<u8>,
@@ -35,6 +37,16 @@ where
         return Err(Error::InvalidSignatureSize);
     }
 
+    Ok(())
+}
+
+fn check_digest_and_signature_sizes<C>(digest: &[u8], signature: &[u8]) -> Result<(), Error>
+where
+    C: Curve,
+    SignatureSize<C>: ArrayLength<u8>,
+{
+    check_signature_size::<C>(signature)?;
+
     if digest.len() != DigestSize::<C>::USIZE {
         return Err(Error::InvalidDigestSize);
     }
@@ -42,6 +54,25 @@ where
     Ok(())
 }
 
+/// Digests of arbitrary hash functions are truncated to the field size if they are longer.
+/// Shorter digests are rejected by the `ecdsa` crate if they are less than half the field size.
+fn check_truncated_digest_and_signature_sizes<C>(
+    digest: &[u8],
+    signature: &[u8],
+) -> Result<(), Error>
+where
+    C: Curve,
+    SignatureSize<C>: ArrayLength<u8>,
+{
+    check_signature_size::<C>(signature)?;
+
+    if digest.len() < DigestSize::<C>::USIZE / 2 {
+        return Err(Error::InvalidDigestSize);
+    }
+
+    Ok(())
+}
+
 fn sign<C>(private_key: &[u8], message: &[u8], signature: &mut [u8]) -> Result<(), Error>
 where
     C: Curve + CurveArithmetic + DigestPrimitive,
@@ -66,7 +97,31 @@ where
     SigningKey<C>: DigestSigner<C::Digest, Signature<C>>,
 {
     check_digest_and_signature_sizes::<C>(digest, signature)?;
+    sign_digest_unchecked::<C>(private_key, digest, signature)
+}
+
+fn sign_digest<C>(private_key: &[u8], digest: &[u8], signature: &mut [u8]) -> Result<(), Error>
+where
+    C: Curve + CurveArithmetic + DigestPrimitive,
+    Scalar<C>: Invert<Output = CtOption<Scalar<C>>> + SignPrimitive<C>,
+    SignatureSize<C>: ArrayLength<u8>,
+    SigningKey<C>: DigestSigner<C::Digest, Signature<C>>,
+{
+    check_truncated_digest_and_signature_sizes::<C>(digest, signature)?;
+    sign_digest_unchecked::<C>(private_key, digest, signature)
+}
 
+fn sign_digest_unchecked<C>(
+    private_key: &[u8],
+    digest: &[u8],
+    signature: &mut [u8],
+) -> Result<(), Error>
+where
+    C: Curve + CurveArithmetic + DigestPrimitive,
+    Scalar<C>: Invert<Output = CtOption<Scalar<C>>> + SignPrimitive<C>,
+    SignatureSize<C>: ArrayLength<u8>,
+    SigningKey<C>: DigestSigner<C::Digest, Signature<C>>,
+{
     let signing_key =
         SigningKey::<C>::from_slice(private_key).map_err(|_| Error::InvalidPrivateKey)?;
 
@@ -102,7 +157,33 @@ where
     VerifyingKey<C>: PrehashVerifier<Signature<C>>,
 {
     check_digest_and_signature_sizes::<C>(digest, signature)?;
+    verify_digest_unchecked::<C>(public_key, digest, signature)
+}
+
+fn verify_digest<C>(public_key: &[u8], digest: &[u8], signature: &[u8]) -> Result<(), Error>
+where
+    C: PrimeCurve + CurveArithmetic + DigestPrimitive,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+    SignatureSize<C>: ArrayLength<u8>,
+    VerifyingKey<C>: PrehashVerifier<Signature<C>>,
+{
+    check_truncated_digest_and_signature_sizes::<C>(digest, signature)?;
+    verify_digest_unchecked::<C>(public_key, digest, signature)
+}
 
+fn verify_digest_unchecked<C>(
+    public_key: &[u8],
+    digest: &[u8],
+    signature: &[u8],
+) -> Result<(), Error>
+where
+    C: PrimeCurve + CurveArithmetic + DigestPrimitive,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+    SignatureSize<C>: ArrayLength<u8>,
+    VerifyingKey<C>: PrehashVerifier<Signature<C>>,
+{
     if public_key.len() != PublicKeySize::<C>::USIZE {
         return Err(Error::InvalidPublicKey);
     }
@@ -120,6 +201,39 @@ where
         .map_err(|_| Error::InvalidSignature)
 }
 
+fn signature_to_der<C>(signature: &[u8], der: &mut [u8]) -> Result<usize, Error>
+where
+    C: PrimeCurve,
+    SignatureSize<C>: ArrayLength<u8>,
+    MaxSize<C>: ArrayLength<u8>,
+    <FieldBytesSize<C> as Add>::Output: Add<MaxOverhead> + ArrayLength<u8>,
+{
+    check_signature_size::<C>(signature)?;
+
+    let signature = Signature::<C>::from_slice(signature).map_err(|_| Error::InvalidSignature)?;
+    let encoded = signature.to_der();
+    der.get_mut(..encoded.len())
+        .ok_or(Error::InvalidSignatureSize)?
+        .copy_from_slice(encoded.as_bytes());
+
+    Ok(encoded.len())
+}
+
+fn signature_from_der<C>(der: &[u8], signature: &mut [u8]) -> Result<(), Error>
+where
+    C: PrimeCurve,
+    SignatureSize<C>: ArrayLength<u8>,
+    MaxSize<C>: ArrayLength<u8>,
+    <FieldBytesSize<C> as Add>::Output: Add<MaxOverhead> + ArrayLength<u8>,
+{
+    check_signature_size::<C>(signature)?;
+
+    let decoded = Signature::<C>::from_der(der).map_err(|_| Error::InvalidSignature)?;
+    signature.copy_from_slice(&decoded.to_bytes());
+
+    Ok(())
+}
+
 fn generate_key_pair<R, C>(rng: &mut R) -> (PrivateKeyBytes<C>, PublicKeyBytes<C>)
 where
     R: CryptoRng + RngCore,
@@ -149,8 +263,13 @@ macro_rules! define_nist_impl {
         $sign_prehashed:ident,
         $verify:ident,
         $verify_prehashed:ident,
+        $sign_digest:ident,
+        $verify_digest:ident,
+        $signature_to_der:ident,
+        $signature_from_der:ident,
         $generate_key_pair:ident,
         $signature_size:ident,
+        $der_signature_max_size:ident,
         $signature_size_str:expr,
         $digest_size:ident,
         $digest_size_str:expr,
@@ -164,6 +283,10 @@ macro_rules! define_nist_impl {
         /// signature size in bytes.
         pub const $signature_size: usize = SignatureSize::<$curve>::USIZE;
 
+        #[doc=$doc]
+        /// upper bound of DER encoded signatures in bytes.
+        pub const $der_signature_max_size: usize = MaxSize::<$curve>::USIZE;
+
         #[doc=$doc]
         /// digest size in bytes.
         pub const $digest_size: usize = DigestSize::<$curve>::USIZE;
@@ -314,6 +437,113 @@ macro_rules! define_nist_impl {
             verify_prehashed::<$curve>(public_key, digest, signature)
         }
 
+        #[doc=$doc]
+        /// signing the digest of an arbitrary hash function.
+        ///
+        ///  # Arguments
+        ///
+        /// * `private_key`: A slice containing private key bytes.
+        ///   The private key has to be `
+        #[doc=$private_key_size_str]
+        /// ` bytes long.
+        /// * `digest`: A slice containing the digest to sign bytes.
+        ///    Digests longer than `
+        #[doc=$digest_size_str]
+        /// ` bytes are truncated. Digests shorter than half of it are rejected.
+        /// * `signature`: A mutable slice where the computed signature will be stored.
+        ///   The signature slice length has to be `
+        #[doc=$signature_size_str]
+        /// ` bytes long.
+        ///
+        /// # Errors
+        ///
+        /// The function returns an error if:
+        /// * `InvalidSignatureSize`: The length of the `signature` is not `
+        #[doc=$signature_size_str]
+        /// ` bytes.
+        /// * `InvalidDigestSize`: The `digest` is too short.
+        /// * `InvalidPrivateKey`: The length of the `private_key` is not `
+        #[doc=$private_key_size_str]
+        /// ` bytes.
+        pub fn $sign_digest(
+            private_key: &[u8],
+            digest: &[u8],
+            signature: &mut [u8],
+        ) -> Result<(), Error> {
+            sign_digest::<$curve>(private_key, digest, signature)
+        }
+
+        #[doc=$doc]
+        /// verifying signature of the digest of an arbitrary hash function.
+        ///
+        ///  # Arguments
+        ///
+        /// * `public_key`: A slice containing public key bytes.
+        ///   The public key has to be `
+        #[doc=$public_key_size_str]
+        /// ` bytes long.
+        /// * `digest`: A slice containing the digest to verify bytes.
+        ///    Digests longer than `
+        #[doc=$digest_size_str]
+        /// ` bytes are truncated. Digests shorter than half of it are rejected.
+        /// * `signature`: A slice containing the signature to verify bytes.
+        ///   The signature slice length has to be `
+        #[doc=$signature_size_str]
+        /// ` bytes long.
+        ///
+        /// # Errors
+        ///
+        /// The function returns an error if:
+        /// * `InvalidSignatureSize`: The length of the `signature` is not `
+        #[doc=$signature_size_str]
+        /// ` bytes.
+        /// * `InvalidSignature`: `signature` contains invalid bytes.
+        /// * `InvalidDigestSize`: The `digest` is too short.
+        /// * `InvalidPublicKey`: The length of the `public_key` is not `
+        #[doc=$public_key_size_str]
+        /// ` bytes or `public_key` contains invalid bytes.
+        pub fn $verify_digest(
+            public_key: &[u8],
+            digest: &[u8],
+            signature: &[u8],
+        ) -> Result<(), Error> {
+            verify_digest::<$curve>(public_key, digest, signature)
+        }
+
+        #[doc=$doc]
+        /// conversion of a raw `r || s` signature to an ASN.1 DER `Ecdsa-Sig-Value`.
+        ///
+        /// # Returns
+        ///
+        /// The number of bytes written to `der`. At most `
+        #[doc=stringify!($der_signature_max_size)]
+        /// ` bytes are written.
+        ///
+        /// # Errors
+        ///
+        /// The function returns an error if:
+        /// * `InvalidSignatureSize`: The length of the `signature` is not `
+        #[doc=$signature_size_str]
+        /// ` bytes or `der` is too small for the encoded signature.
+        /// * `InvalidSignature`: `signature` contains invalid bytes.
+        pub fn $signature_to_der(signature: &[u8], der: &mut [u8]) -> Result<usize, Error> {
+            signature_to_der::<$curve>(signature, der)
+        }
+
+        #[doc=$doc]
+        /// conversion of an ASN.1 DER `Ecdsa-Sig-Value` to a raw `r || s` signature.
+        ///
+        /// # Errors
+        ///
+        /// The function returns an error if:
+        /// * `InvalidSignatureSize`: The length of the `signature` is not `
+        #[doc=$signature_size_str]
+        /// ` bytes.
+        /// * `InvalidSignature`: `der` is not a valid DER encoded signature.
+        pub fn $signature_from_der(der: &[u8], signature: &mut [u8]) -> Result<(), Error> {
+            signature_from_der::<$curve>(der, signature)
+        }
+
         #[doc=$doc]
         /// generate key pair function.
         ///
@@ -354,8 +584,13 @@ macro_rules! define_nist_impl {
         $sign_prehashed:ident,
         $verify:ident,
         $verify_prehashed:ident,
+        $sign_digest:ident,
+        $verify_digest:ident,
+        $signature_to_der:ident,
+        $signature_from_der:ident,
         $generate_key_pair:ident,
         $signature_size:ident,
+        $der_signature_max_size:ident,
         $digest_size:ident,
         $private_key_size:ident,
         $public_key_size:ident,
@@ -367,8 +602,13 @@ macro_rules! define_nist_impl {
             $sign_prehashed,
             $verify,
             $verify_prehashed,
+            $sign_digest,
+            $verify_digest,
+            $signature_to_der,
+            $signature_from_der,
             $generate_key_pair,
             $signature_size,
+            $der_signature_max_size,
             stringify!($signature_size),
             $digest_size,
             stringify!($digest_size),
@@ -387,8 +627,13 @@ define_nist_impl!(
     nist_p256_sign_prehashed,
     nist_p256_verify,
     nist_p256_verify_prehashed,
+    nist_p256_sign_digest,
+    nist_p256_verify_digest,
+    nist_p256_signature_to_der,
+    nist_p256_signature_from_der,
     nist_p256_generate_key_pair,
     NIST_P256_SIGNATURE_SIZE,
+    NIST_P256_DER_SIGNATURE_MAX_SIZE,
     NIST_P256_DIGEST_SIZE,
     NIST_P256_PRIVATE_KEY_SIZE,
     NIST_P256_PUBLIC_KEY_SIZE,
@@ -401,8 +646,13 @@ define_nist_impl!(
     nist_p384_sign_prehashed,
     nist_p384_verify,
     nist_p384_verify_prehashed,
+    nist_p384_sign_digest,
+    nist_p384_verify_digest,
+    nist_p384_signature_to_der,
+    nist_p384_signature_from_der,
     nist_p384_generate_key_pair,
     NIST_P384_SIGNATURE_SIZE,
+    NIST_P384_DER_SIGNATURE_MAX_SIZE,
     NIST_P384_DIGEST_SIZE,
     NIST_P384_PRIVATE_KEY_SIZE,
     NIST_P384_PUBLIC_KEY_SIZE,
@@ -629,4 +879,91 @@ mod test {
         NIST_P384_PRIVATE_KEY_SIZE,
         NIST_P384_PUBLIC_KEY_SIZE
     );
+
+    macro_rules! define_nist_der_and_digest_test {
+        (
+            $test_name:ident,
+            $sign_digest:ident,
+            $verify_digest:ident,
+            $signature_to_der:ident,
+            $signature_from_der:ident,
+            $generate_key_pair:ident,
+            $signature_size:ident,
+            $der_signature_max_size:ident,
+            $digest_size:ident
+        ) => {
+            #[test]
+            fn $test_name() {
+                let mut rng = rand_chacha::ChaCha20Rng::from_seed([0u8; 32]);
+                let (private_key, public_key) = $generate_key_pair(&mut rng);
+
+                // Digests of different hash functions are accepted
+                for digest in [
+                    &crate::crypto::hash::sha256(MESSAGE)[..],
+                    &crate::crypto::hash::sha384(MESSAGE)[..],
+                    &crate::crypto::hash::sha512(MESSAGE)[..],
+                ] {
+                    let mut signature = [0u8; $signature_size];
+                    $sign_digest(&private_key, digest, &mut signature)
+                        .expect("signing of digest failed");
+                    $verify_digest(&public_key, digest, &signature)
+                        .expect("verifying signature of digest failed");
+
+                    let mut der = [0u8; $der_signature_max_size];
+                    let der_size =
+                        $signature_to_der(&signature, &mut der).expect("DER encoding failed");
+                    assert_eq!(der[0], 0x30);
+                    let mut decoded = [0u8; $signature_size];
+                    $signature_from_der(&der[..der_size], &mut decoded)
+                        .expect("DER decoding failed");
+                    assert_eq!(signature, decoded);
+
+                    let mut too_small = [0u8; 8];
+                    assert_eq!(
+                        $signature_to_der(&signature, &mut too_small),
+                        Err(Error::InvalidSignatureSize)
+                    );
+                    assert_eq!(
+                        $signature_from_der(&der[..der_size - 1], &mut decoded),
+                        Err(Error::InvalidSignature)
+                    );
+                }
+
+                let mut signature = [0u8; $signature_size];
+                let short_digest = [0u8; $digest_size / 2 - 1];
+                assert_eq!(
+                    $sign_digest(&private_key, &short_digest, &mut signature),
+                    Err(Error::InvalidDigestSize)
+                );
+                assert_eq!(
+                    $verify_digest(&public_key, &short_digest, &signature),
+                    Err(Error::InvalidDigestSize)
+                );
+            }
+        };
+    }
+
+    define_nist_der_and_digest_test!(
+        nist_p256_der_and_digest_test,
+        nist_p256_sign_digest,
+        nist_p256_verify_digest,
+        nist_p256_signature_to_der,
+        nist_p256_signature_from_der,
+        nist_p256_generate_key_pair,
+        NIST_P256_SIGNATURE_SIZE,
+        NIST_P256_DER_SIGNATURE_MAX_SIZE,
+        NIST_P256_DIGEST_SIZE
+    );
+
+    define_nist_der_and_digest_test!(
+        nist_p384_der_and_digest_test,
+        nist_p384_sign_digest,
+        nist_p384_verify_digest,
+        nist_p384_signature_to_der,
+        nist_p384_signature_from_der,
+        nist_p384_generate_key_pair,
+        NIST_P384_SIGNATURE_SIZE,
+        NIST_P384_DER_SIGNATURE_MAX_SIZE,
+        NIST_P384_DIGEST_SIZE
+    );
 }
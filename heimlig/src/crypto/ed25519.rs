@@ -210,7 +210,7 @@ mod test {
         ed25519_calculate_public_key(&private_key, &mut public_key)
             .expect("public key calculation error");
 
-        signature[0] = signature[0] ^ 0xFF;
+        signature[0] ^= 0xFF;
 
         assert_eq!(
             ed25519_verify(&public_key, MESSAGE, &signature),
@@ -8,6 +8,7 @@ pub mod ecdsa;
 pub mod ed25519;
 pub mod hash;
 pub mod hmac;
+pub mod she;
 pub mod x25519;
 
 /// Common errors.
@@ -0,0 +1,416 @@
+use crate::crypto::aes::{
+    cmac::{aes128_cmac_calculate, aes128_cmac_verify},
+    BLOCK_SIZE, CMAC_TAG_SIZE, KEY128_SIZE,
+};
+use crate::crypto::Error;
+use aes::{
+    cipher::{BlockDecrypt, BlockEncrypt, KeyInit},
+    Aes128,
+};
+use zeroize::Zeroizing;
+
+/// Size of the unique device identifier (UID) in bytes.
+pub const UID_SIZE: usize = 15;
+/// Size of the M1 message in bytes.
+pub const M1_SIZE: usize = 16;
+/// Size of the M2 message in bytes.
+pub const M2_SIZE: usize = 32;
+/// Size of the M3 message in bytes.
+pub const M3_SIZE: usize = CMAC_TAG_SIZE;
+/// Size of the M4 message in bytes.
+pub const M4_SIZE: usize = 32;
+/// Size of the M5 message in bytes.
+pub const M5_SIZE: usize = CMAC_TAG_SIZE;
+/// Largest counter value that fits into the 28 bits reserved for it in M2 and M4.
+pub const MAX_COUNTER: u32 = (1 << 28) - 1;
+/// Largest flag value that fits into the 5 bits reserved for it in M2.
+pub const MAX_FLAGS: u8 = (1 << 5) - 1;
+
+/// Key derivation constant used to derive encryption keys (K1 and K3).
+pub const KEY_UPDATE_ENC_C: [u8; BLOCK_SIZE] = [
+    0x01, 0x01, 0x53, 0x48, 0x45, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xb0,
+];
+/// Key derivation constant used to derive MAC keys (K2 and K4).
+pub const KEY_UPDATE_MAC_C: [u8; BLOCK_SIZE] = [
+    0x01, 0x02, 0x53, 0x48, 0x45, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xb0,
+];
+
+/// Contents of an M2 message after decryption.
+#[derive(Clone, Debug)]
+pub struct M2Payload {
+    /// Counter value of the new key.
+    pub counter: u32,
+    /// Protection flags of the new key as a 5-bit value (WP, BP, DP, KU, WC from MSB to LSB).
+    pub flags: u8,
+    /// The new key.
+    pub key: Zeroizing<[u8; KEY128_SIZE]>,
+}
+
+/// Miyaguchi-Preneel compression based on AES-128.
+///
+/// # Arguments
+///
+/// * `input`: The already padded input. Its length has to be a multiple of `BLOCK_SIZE`.
+/// * `output`: A mutable slice where the compressed value will be stored.
+///   The output slice length has to be `BLOCK_SIZE` bytes long.
+///
+/// # Errors
+///
+/// The function returns an error if:
+/// * `InvalidBufferSize`: The length of `input` is not a multiple of `BLOCK_SIZE` or the length
+///   of `output` is not `BLOCK_SIZE`.
+pub fn mp_compress(input: &[u8], output: &mut [u8]) -> Result<(), Error> {
+    if input.len() % BLOCK_SIZE != 0 || output.len() != BLOCK_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    output.fill(0);
+    for chunk in input.chunks_exact(BLOCK_SIZE) {
+        let cipher = Aes128::new(output[..].into());
+        let mut block = Zeroizing::new([0u8; BLOCK_SIZE]);
+        block.copy_from_slice(chunk);
+        cipher.encrypt_block(block.as_mut_slice().into());
+        for ((out, encrypted), plain) in output.iter_mut().zip(block.iter()).zip(chunk) {
+            *out ^= encrypted ^ plain;
+        }
+    }
+    Ok(())
+}
+
+/// SHE key derivation function `KDF(K, C) = AES-MP(K | C)`.
+///
+/// # Arguments
+///
+/// * `key`: A slice containing key bytes. The key slice has to be `KEY128_SIZE` bytes long.
+/// * `constant`: The derivation constant, usually `KEY_UPDATE_ENC_C` or `KEY_UPDATE_MAC_C`.
+///
+/// # Errors
+///
+/// The function returns an error if:
+/// * `InvalidSymmetricKeySize`: The length of the `key` is not `KEY128_SIZE` bytes.
+pub fn kdf(key: &[u8], constant: &[u8; BLOCK_SIZE]) -> Result<Zeroizing<[u8; KEY128_SIZE]>, Error> {
+    if key.len() != KEY128_SIZE {
+        return Err(Error::InvalidSymmetricKeySize);
+    }
+    let mut input = Zeroizing::new([0u8; KEY128_SIZE + BLOCK_SIZE]);
+    input[..KEY128_SIZE].copy_from_slice(key);
+    input[KEY128_SIZE..].copy_from_slice(constant);
+    let mut output = Zeroizing::new([0u8; KEY128_SIZE]);
+    mp_compress(input.as_slice(), output.as_mut_slice())?;
+    Ok(output)
+}
+
+/// Assemble the M1 message from the device UID, the ID of the updated key and the ID of the
+/// authorizing key.
+///
+/// # Errors
+///
+/// The function returns an error if:
+/// * `InvalidBufferSize`: The length of `uid` is not `UID_SIZE` or the length of `m1` is not
+///   `M1_SIZE` bytes, or one of the IDs does not fit into 4 bits.
+pub fn calculate_m1(uid: &[u8], key_id: u8, auth_id: u8, m1: &mut [u8]) -> Result<(), Error> {
+    if uid.len() != UID_SIZE || m1.len() != M1_SIZE || key_id > 0xF || auth_id > 0xF {
+        return Err(Error::InvalidBufferSize);
+    }
+    m1[..UID_SIZE].copy_from_slice(uid);
+    m1[UID_SIZE] = (key_id << 4) | auth_id;
+    Ok(())
+}
+
+/// Split an M1 message into the device UID, the ID of the updated key and the ID of the
+/// authorizing key.
+pub fn parse_m1(m1: &[u8; M1_SIZE]) -> (&[u8], u8, u8) {
+    (&m1[..UID_SIZE], m1[UID_SIZE] >> 4, m1[UID_SIZE] & 0xF)
+}
+
+/// Calculate the encrypted M2 message carrying counter, flags and new key.
+///
+/// # Arguments
+///
+/// * `auth_key`: The authorizing key. It has to be `KEY128_SIZE` bytes long.
+/// * `counter`: The counter value of the new key. Must not exceed `MAX_COUNTER`.
+/// * `flags`: The protection flags of the new key. Must not exceed `MAX_FLAGS`.
+/// * `new_key`: The new key. It has to be `KEY128_SIZE` bytes long.
+/// * `m2`: A mutable slice where M2 will be stored. It has to be `M2_SIZE` bytes long.
+///
+/// # Errors
+///
+/// The function returns an error if:
+/// * `InvalidSymmetricKeySize`: The length of a key is not `KEY128_SIZE` bytes.
+/// * `InvalidBufferSize`: The length of `m2` is not `M2_SIZE` bytes or `counter` or `flags` are
+///   out of range.
+pub fn calculate_m2(
+    auth_key: &[u8],
+    counter: u32,
+    flags: u8,
+    new_key: &[u8],
+    m2: &mut [u8],
+) -> Result<(), Error> {
+    if new_key.len() != KEY128_SIZE {
+        return Err(Error::InvalidSymmetricKeySize);
+    }
+    if m2.len() != M2_SIZE || counter > MAX_COUNTER || flags > MAX_FLAGS {
+        return Err(Error::InvalidBufferSize);
+    }
+    let k1 = kdf(auth_key, &KEY_UPDATE_ENC_C)?;
+    let cipher = Aes128::new(k1.as_slice().into());
+
+    // CID (28 bits) | FID (5 bits) | zero padding (95 bits) | new key (128 bits)
+    let header = ((counter as u64) << 36) | ((flags as u64) << 31);
+    m2.fill(0);
+    m2[..8].copy_from_slice(&header.to_be_bytes());
+    m2[BLOCK_SIZE..].copy_from_slice(new_key);
+
+    // AES-CBC with a zero IV
+    let (first, second) = m2.split_at_mut(BLOCK_SIZE);
+    cipher.encrypt_block(first.into());
+    for (byte, chained) in second.iter_mut().zip(first.iter()) {
+        *byte ^= chained;
+    }
+    cipher.encrypt_block(second.into());
+    Ok(())
+}
+
+/// Decrypt an M2 message with the key derived from the authorizing key.
+///
+/// # Errors
+///
+/// The function returns an error if:
+/// * `InvalidSymmetricKeySize`: The length of `auth_key` is not `KEY128_SIZE` bytes.
+pub fn decrypt_m2(auth_key: &[u8], m2: &[u8; M2_SIZE]) -> Result<M2Payload, Error> {
+    let k1 = kdf(auth_key, &KEY_UPDATE_ENC_C)?;
+    let cipher = Aes128::new(k1.as_slice().into());
+    let mut plaintext = Zeroizing::new(*m2);
+
+    // AES-CBC with a zero IV
+    let (first, second) = plaintext.split_at_mut(BLOCK_SIZE);
+    cipher.decrypt_block(second.into());
+    for (byte, chained) in second.iter_mut().zip(m2[..BLOCK_SIZE].iter()) {
+        *byte ^= chained;
+    }
+    cipher.decrypt_block(first.into());
+
+    let mut header = [0u8; 8];
+    header.copy_from_slice(&first[..8]);
+    let header = u64::from_be_bytes(header);
+    let mut key = Zeroizing::new([0u8; KEY128_SIZE]);
+    key.copy_from_slice(second);
+    Ok(M2Payload {
+        counter: (header >> 36) as u32,
+        flags: ((header >> 31) as u8) & MAX_FLAGS,
+        key,
+    })
+}
+
+/// Calculate the M3 message (CMAC over M1 and M2 with the key derived from the authorizing key).
+///
+/// # Errors
+///
+/// The function returns an error if:
+/// * `InvalidSymmetricKeySize`: The length of `auth_key` is not `KEY128_SIZE` bytes.
+/// * `InvalidTagSize`: The length of `m3` is not `M3_SIZE` bytes.
+pub fn calculate_m3(
+    auth_key: &[u8],
+    m1: &[u8; M1_SIZE],
+    m2: &[u8; M2_SIZE],
+    m3: &mut [u8],
+) -> Result<(), Error> {
+    let k2 = kdf(auth_key, &KEY_UPDATE_MAC_C)?;
+    let mut message = [0u8; M1_SIZE + M2_SIZE];
+    message[..M1_SIZE].copy_from_slice(m1);
+    message[M1_SIZE..].copy_from_slice(m2);
+    aes128_cmac_calculate(k2.as_slice(), &message, m3)
+}
+
+/// Verify the M3 message of a key update.
+///
+/// # Returns
+///
+/// Verification result.
+///
+/// # Errors
+///
+/// The function returns an error if:
+/// * `InvalidSymmetricKeySize`: The length of `auth_key` is not `KEY128_SIZE` bytes.
+/// * `InvalidTagSize`: The length of `m3` is not `M3_SIZE` bytes.
+pub fn verify_m3(
+    auth_key: &[u8],
+    m1: &[u8; M1_SIZE],
+    m2: &[u8; M2_SIZE],
+    m3: &[u8],
+) -> Result<bool, Error> {
+    let k2 = kdf(auth_key, &KEY_UPDATE_MAC_C)?;
+    let mut message = [0u8; M1_SIZE + M2_SIZE];
+    message[..M1_SIZE].copy_from_slice(m1);
+    message[M1_SIZE..].copy_from_slice(m2);
+    aes128_cmac_verify(k2.as_slice(), &message, m3)
+}
+
+/// Calculate the M4 and M5 messages that prove a successful key update.
+///
+/// # Arguments
+///
+/// * `new_key`: The key that was just loaded. It has to be `KEY128_SIZE` bytes long.
+/// * `m1`: The M1 message of the key update.
+/// * `counter`: The counter value of the new key. Must not exceed `MAX_COUNTER`.
+/// * `m4`: A mutable slice where M4 will be stored. It has to be `M4_SIZE` bytes long.
+/// * `m5`: A mutable slice where M5 will be stored. It has to be `M5_SIZE` bytes long.
+///
+/// # Errors
+///
+/// The function returns an error if:
+/// * `InvalidSymmetricKeySize`: The length of `new_key` is not `KEY128_SIZE` bytes.
+/// * `InvalidBufferSize`: The length of `m4` is not `M4_SIZE` bytes or `counter` is out of range.
+/// * `InvalidTagSize`: The length of `m5` is not `M5_SIZE` bytes.
+pub fn calculate_m4_m5(
+    new_key: &[u8],
+    m1: &[u8; M1_SIZE],
+    counter: u32,
+    m4: &mut [u8],
+    m5: &mut [u8],
+) -> Result<(), Error> {
+    if m4.len() != M4_SIZE || counter > MAX_COUNTER {
+        return Err(Error::InvalidBufferSize);
+    }
+    if m5.len() != M5_SIZE {
+        return Err(Error::InvalidTagSize);
+    }
+    let k3 = kdf(new_key, &KEY_UPDATE_ENC_C)?;
+    let k4 = kdf(new_key, &KEY_UPDATE_MAC_C)?;
+
+    // UID | ID | AuthID | ENC_ECB(K3, CID (28 bits) | 1 | zero padding (99 bits))
+    m4.fill(0);
+    m4[..M1_SIZE].copy_from_slice(m1);
+    m4[M1_SIZE..M1_SIZE + 4].copy_from_slice(&((counter << 4) | 0x8).to_be_bytes());
+    Aes128::new(k3.as_slice().into()).encrypt_block((&mut m4[M1_SIZE..]).into());
+
+    aes128_cmac_calculate(k4.as_slice(), m4, m5)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Test vectors from the SHE specification (memory update protocol example)
+    const AUTH_KEY: [u8; KEY128_SIZE] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f,
+    ];
+    const NEW_KEY: [u8; KEY128_SIZE] = [
+        0x0f, 0x0e, 0x0d, 0x0c, 0x0b, 0x0a, 0x09, 0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01,
+        0x00,
+    ];
+    const UID: [u8; UID_SIZE] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    const KEY_ID: u8 = 4;
+    const AUTH_ID: u8 = 1;
+    const COUNTER: u32 = 1;
+    const FLAGS: u8 = 0;
+
+    #[test]
+    fn test_kdf() {
+        let k1 = kdf(&AUTH_KEY, &KEY_UPDATE_ENC_C).expect("failed to derive key");
+        assert_eq!(
+            k1.as_slice(),
+            hex::decode("118a46447a770d87828a69c222e2d17e").unwrap()
+        );
+        let k2 = kdf(&AUTH_KEY, &KEY_UPDATE_MAC_C).expect("failed to derive key");
+        assert_eq!(
+            k2.as_slice(),
+            hex::decode("2ebb2a3da62dbd64b18ba6493e9fbe22").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_key_update_messages() {
+        let mut m1 = [0u8; M1_SIZE];
+        let mut m2 = [0u8; M2_SIZE];
+        let mut m3 = [0u8; M3_SIZE];
+        let mut m4 = [0u8; M4_SIZE];
+        let mut m5 = [0u8; M5_SIZE];
+        calculate_m1(&UID, KEY_ID, AUTH_ID, &mut m1).expect("failed to calculate M1");
+        calculate_m2(&AUTH_KEY, COUNTER, FLAGS, &NEW_KEY, &mut m2).expect("failed to calculate M2");
+        calculate_m3(&AUTH_KEY, &m1, &m2, &mut m3).expect("failed to calculate M3");
+        calculate_m4_m5(&NEW_KEY, &m1, COUNTER, &mut m4, &mut m5)
+            .expect("failed to calculate M4 and M5");
+
+        assert_eq!(
+            m1.as_slice(),
+            hex::decode("00000000000000000000000000000141").unwrap()
+        );
+        assert_eq!(
+            m2.as_slice(),
+            hex::decode("2b111e2d93f486566bcbba1d7f7a9797c94643b050fc5d4d7de14cff682203c3")
+                .unwrap()
+        );
+        assert_eq!(
+            m3.as_slice(),
+            hex::decode("b9d745e5ace7d41860bc63c2b9f5bb46").unwrap()
+        );
+        assert_eq!(
+            m4.as_slice(),
+            hex::decode("00000000000000000000000000000141b472e8d8727d70d57295e74849a27917")
+                .unwrap()
+        );
+        assert_eq!(
+            m5.as_slice(),
+            hex::decode("820d8d95dc11b4668878160cb2a4e23e").unwrap()
+        );
+
+        assert!(verify_m3(&AUTH_KEY, &m1, &m2, &m3).expect("failed to verify M3"));
+        m3[0] ^= 1;
+        assert!(!verify_m3(&AUTH_KEY, &m1, &m2, &m3).expect("failed to verify M3"));
+
+        let (uid, key_id, auth_id) = parse_m1(&m1);
+        assert_eq!(uid, UID);
+        assert_eq!(key_id, KEY_ID);
+        assert_eq!(auth_id, AUTH_ID);
+
+        let payload = decrypt_m2(&AUTH_KEY, &m2).expect("failed to decrypt M2");
+        assert_eq!(payload.counter, COUNTER);
+        assert_eq!(payload.flags, FLAGS);
+        assert_eq!(*payload.key, NEW_KEY);
+    }
+
+    #[test]
+    fn test_m2_counter_and_flags() {
+        let mut m2 = [0u8; M2_SIZE];
+        calculate_m2(&AUTH_KEY, MAX_COUNTER, 0b10101, &NEW_KEY, &mut m2)
+            .expect("failed to calculate M2");
+        let payload = decrypt_m2(&AUTH_KEY, &m2).expect("failed to decrypt M2");
+        assert_eq!(payload.counter, MAX_COUNTER);
+        assert_eq!(payload.flags, 0b10101);
+    }
+
+    #[test]
+    fn test_she_errors() {
+        let mut m1 = [0u8; M1_SIZE];
+        let mut m2 = [0u8; M2_SIZE];
+        assert_eq!(
+            calculate_m1(&UID[1..], KEY_ID, AUTH_ID, &mut m1),
+            Err(Error::InvalidBufferSize)
+        );
+        assert_eq!(
+            calculate_m1(&UID, 0x10, AUTH_ID, &mut m1),
+            Err(Error::InvalidBufferSize)
+        );
+        assert_eq!(
+            calculate_m2(&AUTH_KEY[1..], COUNTER, FLAGS, &NEW_KEY, &mut m2),
+            Err(Error::InvalidSymmetricKeySize)
+        );
+        assert_eq!(
+            calculate_m2(&AUTH_KEY, MAX_COUNTER + 1, FLAGS, &NEW_KEY, &mut m2),
+            Err(Error::InvalidBufferSize)
+        );
+        assert_eq!(
+            calculate_m2(&AUTH_KEY, COUNTER, MAX_FLAGS + 1, &NEW_KEY, &mut m2),
+            Err(Error::InvalidBufferSize)
+        );
+        assert_eq!(
+            calculate_m4_m5(&NEW_KEY, &m1, COUNTER, &mut [0u8; M4_SIZE], &mut [0u8; 1]),
+            Err(Error::InvalidTagSize)
+        );
+        assert_eq!(
+            mp_compress(&[0u8; 17], &mut [0u8; BLOCK_SIZE]),
+            Err(Error::InvalidBufferSize)
+        );
+    }
+}
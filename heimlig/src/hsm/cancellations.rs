@@ -0,0 +1,112 @@
+use crate::common::jobs::{CancelReason, ClientId, Request, RequestId, Response};
+use core::cell::RefCell;
+use critical_section::Mutex;
+use heapless::Vec;
+
+/// Maximum number of cancelled requests that are remembered at the same time
+pub const MAX_CANCELLATIONS: usize = 16;
+
+/// Cancelled requests shared between the core and the workers.
+///
+/// The core registers the targets of [Request::Cancel] here. Workers check incoming requests
+/// against the registry and answer cancelled ones with [Response::Cancelled] instead of processing
+/// them. Entries are removed once the request is answered. If the registry is full, further
+/// cancellations are rejected until entries are removed.
+pub struct Cancellations {
+    requests: Mutex<RefCell<Vec<(ClientId, RequestId), MAX_CANCELLATIONS>>>,
+}
+
+impl Default for Cancellations {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cancellations {
+    pub const fn new() -> Self {
+        Self {
+            requests: Mutex::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Mark the given request as cancelled. Returns `false` if the registry is full.
+    pub fn cancel(&self, client_id: ClientId, request_id: RequestId) -> bool {
+        critical_section::with(|cs| {
+            let mut requests = self.requests.borrow_ref_mut(cs);
+            requests.contains(&(client_id, request_id))
+                || requests.push((client_id, request_id)).is_ok()
+        })
+    }
+
+    /// Check whether the given request was cancelled without removing it from the registry.
+    pub fn is_cancelled(&self, client_id: ClientId, request_id: RequestId) -> bool {
+        critical_section::with(|cs| {
+            self.requests
+                .borrow_ref(cs)
+                .contains(&(client_id, request_id))
+        })
+    }
+
+    /// Remove the given request from the registry. Returns whether it was cancelled.
+    pub fn take(&self, client_id: ClientId, request_id: RequestId) -> bool {
+        critical_section::with(|cs| {
+            let mut requests = self.requests.borrow_ref_mut(cs);
+            match requests
+                .iter()
+                .position(|&entry| entry == (client_id, request_id))
+            {
+                Some(index) => {
+                    requests.remove(index);
+                    true
+                }
+                None => false,
+            }
+        })
+    }
+
+    /// Remove the request from the registry and return the response reporting its cancellation if
+    /// it was cancelled.
+    pub fn take_cancelled<'data>(&self, request: &Request<'data>) -> Option<Response<'data>> {
+        let client_id = request.get_client_id();
+        let request_id = request.get_request_id();
+        self.take(client_id, request_id)
+            .then_some(Response::Cancelled {
+                client_id,
+                request_id,
+                reason: CancelReason::Cancelled,
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cancel_and_take() {
+        let cancellations = Cancellations::new();
+        assert!(cancellations.cancel(ClientId(0), RequestId(1)));
+        assert!(cancellations.is_cancelled(ClientId(0), RequestId(1)));
+        assert!(!cancellations.is_cancelled(ClientId(1), RequestId(1)));
+        assert!(!cancellations.take(ClientId(0), RequestId(2)));
+        assert!(cancellations.take(ClientId(0), RequestId(1)));
+        assert!(!cancellations.take(ClientId(0), RequestId(1)));
+    }
+
+    #[test]
+    fn full_registry_rejects_cancellations() {
+        let cancellations = Cancellations::new();
+        for id in 0..MAX_CANCELLATIONS as u32 {
+            assert!(cancellations.cancel(ClientId(0), RequestId(id)));
+        }
+        assert!(!cancellations.cancel(ClientId(1), RequestId(0)));
+        assert!(!cancellations.is_cancelled(ClientId(1), RequestId(0)));
+        // Cancelling a registered request again succeeds
+        assert!(cancellations.cancel(ClientId(0), RequestId(0)));
+        for id in 0..MAX_CANCELLATIONS as u32 {
+            assert!(cancellations.is_cancelled(ClientId(0), RequestId(id)));
+        }
+        assert!(cancellations.take(ClientId(0), RequestId(0)));
+        assert!(cancellations.cancel(ClientId(1), RequestId(0)));
+    }
+}
@@ -1,15 +1,39 @@
 use crate::common::jobs;
-use crate::common::jobs::{ClientId, Request, RequestId, RequestType, Response};
+use crate::common::jobs::{
+    CancelReason, Capabilities, ClientId, Curves, HashAlgorithm, HashAlgorithms, KeyKind, KeyKinds,
+    Limit, PrivateKeyFormat, PublicKeyFormat, Request, RequestId, RequestType, RequestTypes,
+    Response, SymmetricPrimitive, SymmetricPrimitives,
+};
+use crate::common::sync::{Mutex, RawMutex};
+use crate::crypto;
+use crate::crypto::ecc;
+use crate::hsm::cancellations::Cancellations;
 use crate::hsm::keystore;
-use core::future::poll_fn;
+use crate::hsm::keystore::{Curve, KeyId, KeyType};
+use crate::hsm::memory::MemoryRegions;
+use crate::hsm::workers::Worker;
+use crate::integration::raw_jobs::AbiVersion;
+use core::cmp::Reverse;
+use core::convert::Infallible;
+use core::future::{poll_fn, Future};
+use core::marker::PhantomData;
 use core::ops::DerefMut;
 use core::pin::Pin;
+use core::task::{Context, Poll};
 use displaydoc::Display;
-use embassy_futures::select::select_slice;
-use embassy_sync::blocking_mutex::raw::RawMutex;
-use embassy_sync::mutex::Mutex;
-use futures::{FutureExt, Sink, SinkExt, Stream, StreamExt};
+use elliptic_curve::{
+    pkcs8::AssociatedOid,
+    sec1::{FromEncodedPoint, ModulusSize, ToEncodedPoint},
+    AffinePoint, CurveArithmetic, FieldBytesSize, SecretKey,
+};
+use embassy_futures::select::{select, select_slice, Either};
+use futures::task::noop_waker_ref;
+use futures::{Sink, SinkExt, Stream, StreamExt};
 use heapless::Vec;
+use p256::NistP256;
+use p384::NistP384;
+use strum::IntoEnumIterator;
+use zeroize::Zeroizing;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Display)]
 pub enum Error {
@@ -23,10 +47,14 @@ pub enum Error {
     TooManyWorkers,
     /// Tried to add worker for invalid request type
     InvalidRequestType,
-    /// A channel for the given request type already exists
-    ChannelForRequestExists,
     /// Maximum number of request types for a single worker exceeded
     TooManyRequestTypes,
+    /// Tried to add client with a weight of zero
+    InvalidClientWeight,
+    /// Tried to add client with a rate limit that has a refill period of zero
+    InvalidRateLimit,
+    /// Tried to attach or detach a worker that was never added ({0:?})
+    InvalidWorkerId(WorkerId),
     /// An internal error occurred: {0}
     Internal(InternalError),
 }
@@ -44,8 +72,6 @@ pub enum InternalError {
     EmptyWorkerResponseQueue(WorkerId),
     /// The core encountered a request ({0:?}) type it cannot handle.
     UnexpectedCoreRequest(RequestType),
-    /// The client ID of the response that was determined to be processed next ({0:?}) did not match the one in the response queue ({1:?}).
-    ClientIdMismatch(ClientId, ClientId),
 }
 
 /// Used to index list of workers
@@ -70,6 +96,108 @@ impl WorkerId {
     }
 }
 
+/// Scheduling parameters and limits of a client.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClientConfig {
+    /// Pending requests of clients with a higher priority are processed first.
+    pub priority: u8,
+    /// Share of processed requests relative to other clients of the same priority. A client with
+    /// weight 3 gets three requests processed for every request of a client with weight 1.
+    pub weight: u8,
+    /// Requests exceeding these limits are rejected with [jobs::Error::LimitExceeded].
+    pub limits: ClientLimits,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            priority: 0,
+            weight: 1,
+            limits: ClientLimits::default(),
+        }
+    }
+}
+
+impl ClientConfig {
+    pub fn new(priority: u8, weight: u8) -> Self {
+        ClientConfig {
+            priority,
+            weight,
+            limits: ClientLimits::default(),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: ClientLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
+/// Limits the core enforces for the requests of a client. All limits are disabled by default.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ClientLimits {
+    /// Maximum number of requests forwarded to workers whose responses were not yet received.
+    pub max_in_flight: Option<u16>,
+    /// Maximum size of all buffers of a single request in bytes (see [Request::data_size]).
+    pub max_request_size: Option<usize>,
+    /// Token buckets limiting the rate of individual request types.
+    pub rates: Vec<RateLimit, MAX_RATE_LIMITS>,
+}
+
+/// Token bucket limiting the rate of a request type.
+///
+/// The bucket starts full with `burst` tokens and every accepted request of the given type takes
+/// one token. A token is added every `period` ticks of the clock passed to [Builder::with_clock].
+/// Without a clock the bucket is never refilled and `burst` acts as a fixed quota.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RateLimit {
+    pub request_type: RequestType,
+    pub burst: u32,
+    pub period: u64,
+}
+
+/// Diagnostic counters of a client.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ClientStats {
+    /// Number of accepted requests
+    pub accepted: u32,
+    /// Number of requests currently processed by workers
+    pub in_flight: u16,
+    /// Number of requests rejected because of [ClientLimits::max_in_flight]
+    pub rejected_in_flight: u32,
+    /// Number of requests rejected because of [ClientLimits::rates]
+    pub rejected_rate: u32,
+    /// Number of requests rejected because of [ClientLimits::max_request_size]
+    pub rejected_size: u32,
+}
+
+/// State of a [RateLimit] token bucket
+#[derive(Clone, Copy, Debug)]
+struct TokenBucket {
+    tokens: u32,
+    last_refill: u64,
+}
+
+impl TokenBucket {
+    fn new(limit: &RateLimit) -> Self {
+        TokenBucket {
+            tokens: limit.burst,
+            last_refill: 0,
+        }
+    }
+
+    /// Number of available tokens and time of the last refill at time `now`.
+    fn refilled(&self, limit: &RateLimit, now: u64) -> (u32, u64) {
+        let new_tokens = now.saturating_sub(self.last_refill) / limit.period;
+        let tokens = (self.tokens as u64 + new_tokens).min(limit.burst as u64) as u32;
+        if tokens == limit.burst {
+            (tokens, now)
+        } else {
+            (tokens, self.last_refill + new_tokens * limit.period)
+        }
+    }
+}
+
 enum Job {
     /// A client request should be forwarded to a worker
     ForwardRequest(ClientId, WorkerId),
@@ -77,46 +205,187 @@ enum Job {
     ForwardResponse(ClientId, WorkerId),
     /// The incoming request can be handled on the core without contacting a dedicated worker
     ProcessOnCore(ClientId),
-    /// The incoming request has no worker to handle it
-    RespondNoWorkerForRequest(ClientId),
+    /// The incoming request is handled by a worker hosted by the core
+    ProcessOnHostedWorker(ClientId),
+    /// The incoming request cannot be dispatched and is answered with an error
+    RespondError(ClientId, jobs::Error),
+    /// The incoming request exceeds a limit of the client
+    RejectRequest(ClientId, Limit),
+    /// The incoming request was cancelled or its deadline passed
+    DropRequest(ClientId, CancelReason),
+    /// A channel of the worker failed and the worker has to be detached
+    DetachWorker(WorkerId),
+    /// A channel of the client failed and the client has to be disconnected
+    DisconnectClient(ClientId),
+}
+
+/// Default maximum number of clients of a [Core]
+pub const DEFAULT_MAX_CLIENTS: usize = 8;
+/// Default maximum number of workers of a [Core]
+pub const DEFAULT_MAX_WORKERS: usize = 16;
+/// Default maximum number of different request types handled by a single worker
+pub const DEFAULT_MAX_REQUEST_TYPES: usize = 16;
+/// Maximum number of rate limits per client
+pub const MAX_RATE_LIMITS: usize = 4;
+/// Maximum number of requests forwarded to a single worker whose responses were not yet received.
+/// Workers with this many pending requests are not sent further requests.
+pub const MAX_PENDING_REQUESTS: usize = 16;
+/// Number of client requests the core processes before a client with pending requests that was
+/// not served in the meantime takes precedence over all others, regardless of its priority.
+pub const STARVATION_LIMIT: u32 = 16;
+/// Hash algorithms supported by all workers that take one
+const HASH_ALGORITHMS: HashAlgorithms = HashAlgorithms::new()
+    .with(HashAlgorithm::Sha2_256)
+    .with(HashAlgorithm::Sha2_384)
+    .with(HashAlgorithm::Sha2_512)
+    .with(HashAlgorithm::Sha3_256)
+    .with(HashAlgorithm::Sha3_384)
+    .with(HashAlgorithm::Sha3_512);
+/// Curves supported by the key store and the ECC worker
+const CURVES: Curves = Curves::new().with(Curve::NistP256).with(Curve::NistP384);
+/// Virtual time a client with weight 1 is charged per processed request
+const VIRTUAL_TIME_PER_REQUEST: u64 = 1 << 16;
+
+/// Workers hosted by a [Core].
+///
+/// Implemented for [NoHostedWorkers] and for pairs of hosted workers and another [Worker].
+/// [Builder::with_hosted_worker] builds up these pairs.
+pub trait HostedWorkers<'data> {
+    /// Whether one of the hosted workers can handle the request type
+    fn supports(&self, request_type: RequestType) -> bool;
+
+    /// Pass the request to the first hosted worker that can handle it.
+    fn handle(
+        &mut self,
+        request: Request<'data>,
+    ) -> impl Future<Output = Result<Response<'data>, jobs::Error>>;
 }
 
-// TODO: Can be made configurable once `generic_const_exprs` is stable
-// https://doc.rust-lang.org/beta/unstable-book/language-features/generic-const-exprs.html
-/// Maximum number of allowed clients
-pub const MAX_CLIENTS: usize = 8;
-/// Maximum number of allowed workers
-pub const MAX_WORKERS: usize = 16;
-// TODO: Can be made configurable once `core::mem::variant_count` is stable
-// https://github.com/rust-lang/rust/issues/73662
-/// Maximum number of different request types handles by a worker
-const MAX_REQUEST_TYPES: usize = 16;
-
-/// HSM core that waits for [Request]s from clients and send [Response]s once they are ready.   
+/// Core without hosted workers. All requests not handled by the core go to workers with queues.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoHostedWorkers;
+
+impl<'data> HostedWorkers<'data> for NoHostedWorkers {
+    fn supports(&self, _request_type: RequestType) -> bool {
+        false
+    }
+
+    async fn handle(&mut self, _request: Request<'data>) -> Result<Response<'data>, jobs::Error> {
+        Err(jobs::Error::NoWorkerForRequest)
+    }
+}
+
+impl<'data, H: HostedWorkers<'data>, W: Worker<'data>> HostedWorkers<'data> for (H, W) {
+    fn supports(&self, request_type: RequestType) -> bool {
+        self.0.supports(request_type) || self.1.request_types().contains(&request_type)
+    }
+
+    async fn handle(&mut self, request: Request<'data>) -> Result<Response<'data>, jobs::Error> {
+        if self.0.supports(request.get_type()) {
+            self.0.handle(request).await
+        } else {
+            self.1.handle(request).await
+        }
+    }
+}
+
+/// Queue type of a core without queued workers, e.g. if all workers are hosted. It cannot be
+/// instantiated, so [Builder::with_worker] cannot be called for such a core.
+pub struct NoWorkerQueue<'data>(Infallible, PhantomData<Response<'data>>);
+
+impl<'data> Sink<Request<'data>> for NoWorkerQueue<'data> {
+    type Error = Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.0 {}
+    }
+
+    fn start_send(self: Pin<&mut Self>, _item: Request<'data>) -> Result<(), Self::Error> {
+        match self.0 {}
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.0 {}
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.0 {}
+    }
+}
+
+impl<'data> Stream for NoWorkerQueue<'data> {
+    type Item = Response<'data>;
+
+    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.0 {}
+    }
+}
+
+/// HSM core that waits for [Request]s from clients and send [Response]s once they are ready.
+///
+/// The capacity of the core is fixed at compile time by its const generic parameters. All slots
+/// are allocated inline, whether they are used or not:
+///
+/// - `MAX_CLIENTS`: Maximum number of clients. Every slot holds the request source and response
+///   sink of a client, room for one peeked [Request], its [ClientConfig] (including
+///   [MAX_RATE_LIMITS] rate limits) and its scheduling state. Without the channel ends this is
+///   roughly 400 bytes per client on a 64-bit target.
+/// - `MAX_WORKERS`: Maximum number of workers. Every slot holds the request sink and response
+///   source of a worker, room for one peeked [Response] and the list of its pending requests
+///   ([MAX_PENDING_REQUESTS] times 8 bytes). Without the channel ends and request types this is
+///   roughly 260 bytes per worker on a 64-bit target.
+/// - `MAX_REQUEST_TYPES`: Maximum number of request types of a single worker. Costs one byte per
+///   worker slot and request type.
+///
+/// In addition, [Core::execute] keeps one future per client and per worker on the stack while it
+/// waits for the next job, so its stack usage grows linearly with `MAX_CLIENTS` and `MAX_WORKERS`.
 pub struct Core<
     'data,
     'keystore,
-    M: RawMutex, // TODO: Get rid of embassy specific mutex outside of integration code
+    M: RawMutex,
     ReqSrc: Stream<Item = Request<'data>>,
     RespSink: Sink<Response<'data>>,
-    ReqSink: Sink<Request<'data>>,
-    RespSrc: Stream<Item = Response<'data>>,
     KeyStore: keystore::KeyStore,
+    ReqSink: Sink<Request<'data>> = NoWorkerQueue<'data>,
+    RespSrc: Stream<Item = Response<'data>> = NoWorkerQueue<'data>,
+    const MAX_CLIENTS: usize = DEFAULT_MAX_CLIENTS,
+    const MAX_WORKERS: usize = DEFAULT_MAX_WORKERS,
+    const MAX_REQUEST_TYPES: usize = DEFAULT_MAX_REQUEST_TYPES,
+    Hosted: HostedWorkers<'data> = NoHostedWorkers,
 > {
     key_store: Option<&'keystore Mutex<M, &'keystore mut KeyStore>>,
     clients: Vec<ClientChannel<'data, ReqSrc, RespSink, M>, MAX_CLIENTS>,
-    workers: Vec<WorkerChannel<'data, ReqSink, RespSrc, M>, MAX_WORKERS>,
-    last_client_id: usize,
+    workers: Vec<WorkerChannel<'data, ReqSink, RespSrc, M, MAX_REQUEST_TYPES>, MAX_WORKERS>,
     last_worker_id: usize,
+    /// Virtual start time of the last processed client request
+    virtual_time: u64,
+    /// Number of processed client requests (wrapping)
+    served: u32,
+    clock: Option<fn() -> u64>,
+    cancellations: Option<&'data Cancellations>,
+    memory: Option<MemoryRegions<'data>>,
+    /// Workers called by the core itself instead of through queues
+    hosted: Hosted,
 }
 
 struct ClientChannel<
     'data,
     ReqSrc: Stream<Item = Request<'data>>,
     RespSink: Sink<Response<'data>>,
-    M: RawMutex, // TODO: Get rid of embassy specific mutex outside of integration code
+    M: RawMutex,
 > {
     id: ClientId, // Used to index list of clients in Core
+    config: ClientConfig,
+    /// Virtual finish time of the last processed request (weighted fair queuing)
+    virtual_time: u64,
+    /// Value of [Core::served] when the client became backlogged, i.e. when the core first saw a
+    /// request that is still pending. `None` while the client is idle.
+    backlogged_since: Option<u32>,
+    /// One bucket for each of the rate limits in the client config
+    buckets: Vec<TokenBucket, MAX_RATE_LIMITS>,
+    stats: ClientStats,
+    /// Cleared once a channel of the client failed. Disconnected clients are ignored.
+    connected: bool,
     requests: Mutex<M, futures::stream::Peekable<ReqSrc>>,
     responses: Mutex<M, RespSink>,
 }
@@ -126,10 +395,17 @@ struct WorkerChannel<
     'data,
     ReqSink: Sink<Request<'data>>,
     RespSrc: Stream<Item = Response<'data>>,
-    M: RawMutex, // TODO: Get rid of embassy specific mutex outside of integration code
+    M: RawMutex,
+    const MAX_REQUEST_TYPES: usize,
 > {
     pub id: WorkerId, // Used to index list of workers in Core
     pub req_types: Vec<RequestType, MAX_REQUEST_TYPES>,
+    /// Workers with a higher preference are chosen first if several workers can accept a request.
+    pub preference: u8,
+    /// Cleared once a channel of the worker failed. Detached workers receive no requests.
+    pub available: bool,
+    /// Requests forwarded to the worker whose responses were not yet received
+    pub pending: Vec<(ClientId, RequestId), MAX_PENDING_REQUESTS>,
     pub requests: Mutex<M, ReqSink>,
     pub responses: Mutex<M, futures::stream::Peekable<RespSrc>>,
 }
@@ -137,16 +413,25 @@ struct WorkerChannel<
 pub struct Builder<
     'data,
     'keystore,
-    M: RawMutex, // TODO: Get rid of embassy specific mutex outside of integration code
+    M: RawMutex,
     ReqSrc: Stream<Item = Request<'data>>,
     RespSink: Sink<Response<'data>>,
-    ReqSink: Sink<Request<'data>>,
-    RespSrc: Stream<Item = Response<'data>>,
     KeyStore: keystore::KeyStore,
+    ReqSink: Sink<Request<'data>> = NoWorkerQueue<'data>,
+    RespSrc: Stream<Item = Response<'data>> = NoWorkerQueue<'data>,
+    const MAX_CLIENTS: usize = DEFAULT_MAX_CLIENTS,
+    const MAX_WORKERS: usize = DEFAULT_MAX_WORKERS,
+    const MAX_REQUEST_TYPES: usize = DEFAULT_MAX_REQUEST_TYPES,
+    Hosted: HostedWorkers<'data> = NoHostedWorkers,
 > {
     key_store: Option<&'keystore Mutex<M, &'keystore mut KeyStore>>,
     clients: Vec<ClientChannel<'data, ReqSrc, RespSink, M>, MAX_CLIENTS>,
-    workers: Vec<WorkerChannel<'data, ReqSink, RespSrc, M>, MAX_WORKERS>,
+    workers: Vec<WorkerChannel<'data, ReqSink, RespSrc, M, MAX_REQUEST_TYPES>, MAX_WORKERS>,
+    clock: Option<fn() -> u64>,
+    cancellations: Option<&'data Cancellations>,
+    memory: Option<MemoryRegions<'data>>,
+    /// Workers called by the core itself instead of through queues
+    hosted: Hosted,
 }
 
 impl<
@@ -154,10 +439,26 @@ impl<
         M: RawMutex,
         ReqSrc: Stream<Item = Request<'data>> + Unpin,
         RespSink: Sink<Response<'data>> + Unpin,
+        KeyStore: keystore::KeyStore,
         ReqSink: Sink<Request<'data>> + Unpin,
         RespSrc: Stream<Item = Response<'data>> + Unpin,
-        KeyStore: keystore::KeyStore,
-    > Default for Builder<'data, '_, M, ReqSrc, RespSink, ReqSink, RespSrc, KeyStore>
+        const MAX_CLIENTS: usize,
+        const MAX_WORKERS: usize,
+        const MAX_REQUEST_TYPES: usize,
+    > Default
+    for Builder<
+        'data,
+        '_,
+        M,
+        ReqSrc,
+        RespSink,
+        KeyStore,
+        ReqSink,
+        RespSrc,
+        MAX_CLIENTS,
+        MAX_WORKERS,
+        MAX_REQUEST_TYPES,
+    >
 {
     fn default() -> Self {
         Builder::new()
@@ -170,18 +471,96 @@ impl<
         M: RawMutex,
         ReqSrc: Stream<Item = Request<'data>> + Unpin,
         RespSink: Sink<Response<'data>> + Unpin,
+        KeyStore: keystore::KeyStore,
         ReqSink: Sink<Request<'data>> + Unpin,
         RespSrc: Stream<Item = Response<'data>> + Unpin,
-        KeyStore: keystore::KeyStore,
-    > Builder<'data, 'keystore, M, ReqSrc, RespSink, ReqSink, RespSrc, KeyStore>
+        const MAX_CLIENTS: usize,
+        const MAX_WORKERS: usize,
+        const MAX_REQUEST_TYPES: usize,
+    >
+    Builder<
+        'data,
+        'keystore,
+        M,
+        ReqSrc,
+        RespSink,
+        KeyStore,
+        ReqSink,
+        RespSrc,
+        MAX_CLIENTS,
+        MAX_WORKERS,
+        MAX_REQUEST_TYPES,
+    >
 {
     pub fn new() -> Self {
         Builder {
             key_store: None,
             clients: Default::default(),
             workers: Default::default(),
+            clock: None,
+            cancellations: None,
+            memory: None,
+            hosted: NoHostedWorkers,
         }
     }
+}
+
+impl<
+        'data,
+        'keystore,
+        M: RawMutex,
+        ReqSrc: Stream<Item = Request<'data>> + Unpin,
+        RespSink: Sink<Response<'data>> + Unpin,
+        KeyStore: keystore::KeyStore,
+        ReqSink: Sink<Request<'data>> + Unpin,
+        RespSrc: Stream<Item = Response<'data>> + Unpin,
+        const MAX_CLIENTS: usize,
+        const MAX_WORKERS: usize,
+        const MAX_REQUEST_TYPES: usize,
+        Hosted: HostedWorkers<'data>,
+    >
+    Builder<
+        'data,
+        'keystore,
+        M,
+        ReqSrc,
+        RespSink,
+        KeyStore,
+        ReqSink,
+        RespSrc,
+        MAX_CLIENTS,
+        MAX_WORKERS,
+        MAX_REQUEST_TYPES,
+        Hosted,
+    >
+{
+    /// Set the registry of cancelled requests. It has to be shared with the workers so that they
+    /// can skip requests that were cancelled after they were forwarded. Without a registry,
+    /// [Request::Cancel] is answered with [jobs::Error::CancellationUnsupported].
+    ///
+    /// Only requests of the client that are pending at a worker can be cancelled. The registry
+    /// remembers at most [crate::hsm::cancellations::MAX_CANCELLATIONS] requests. Cancellations of
+    /// other requests or while the registry is full are answered with
+    /// [jobs::Error::NotCancellable].
+    pub fn with_cancellations(mut self, cancellations: &'data Cancellations) -> Self {
+        self.cancellations = Some(cancellations);
+        self
+    }
+
+    /// Restrict the buffers of client requests to the memory regions of the clients. Requests
+    /// with buffers outside the regions of the sending client are answered with
+    /// [jobs::Error::AccessDenied].
+    pub fn with_memory_regions(mut self, memory: MemoryRegions<'data>) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    /// Set the clock used to refill the [RateLimit] token buckets of the clients. It has to
+    /// return monotonically increasing ticks of an arbitrary but fixed duration.
+    pub fn with_clock(mut self, now: fn() -> u64) -> Self {
+        self.clock = Some(now);
+        self
+    }
 
     pub fn with_keystore(
         mut self,
@@ -191,10 +570,35 @@ impl<
         self
     }
 
-    pub fn with_client(mut self, requests: ReqSrc, responses: RespSink) -> Result<Self, Error> {
+    /// Add a client with the given scheduling parameters.
+    ///
+    /// If several clients have pending requests, the core processes those of the clients with the
+    /// highest priority first. Clients of the same priority share the core according to their
+    /// weights (weighted fair queuing). To prevent starvation, a client with pending requests that
+    /// was passed over for [STARVATION_LIMIT] requests since it became backlogged is served next
+    /// regardless of its priority.
+    pub fn with_client(
+        mut self,
+        requests: ReqSrc,
+        responses: RespSink,
+        config: ClientConfig,
+    ) -> Result<Self, Error> {
+        if config.weight == 0 {
+            return Err(Error::InvalidClientWeight);
+        }
+        if config.limits.rates.iter().any(|rate| rate.period == 0) {
+            return Err(Error::InvalidRateLimit);
+        }
+        let buckets = config.limits.rates.iter().map(TokenBucket::new).collect();
         self.clients
             .push(ClientChannel {
                 id: ClientId::from(self.clients.len() as u32),
+                config,
+                virtual_time: 0,
+                backlogged_since: None,
+                buckets,
+                stats: ClientStats::default(),
+                connected: true,
                 requests: Mutex::new(requests.peekable()),
                 responses: Mutex::new(responses),
             })
@@ -202,8 +606,30 @@ impl<
         Ok(self)
     }
 
+    /// Add a worker for the given request types. Workers are assigned consecutive [WorkerId]s in
+    /// the order they are added, starting from zero. The worker task usually drives a
+    /// [crate::hsm::workers::QueuedWorker] on the other ends of the queues.
+    ///
+    /// Several workers may handle the same request type. Requests are dispatched to whichever of
+    /// them is ready to accept a request first. Ready workers are chosen in a round-robin fashion.
     pub fn with_worker(
+        self,
+        req_types: &[RequestType],
+        requests: ReqSink,
+        responses: RespSrc,
+    ) -> Result<Self, Error> {
+        self.with_worker_preference(0, req_types, requests, responses)
+    }
+
+    /// Add a worker for the given request types with a preference.
+    ///
+    /// If several workers for a request type are ready, the one with the highest `preference` is
+    /// chosen. Workers with a lower preference only receive requests when all preferred workers
+    /// are busy. E.g. a hardware accelerator can be preferred over a software fallback that only
+    /// handles the overflow.
+    pub fn with_worker_preference(
         mut self,
+        preference: u8,
         req_types: &[RequestType],
         requests: ReqSink,
         responses: RespSrc,
@@ -211,17 +637,13 @@ impl<
         if req_types.iter().any(|r| r.is_handled_by_core()) {
             return Err(Error::InvalidRequestType);
         }
-        for channel in &mut self.workers {
-            for req_type in req_types {
-                if channel.req_types.contains(req_type) {
-                    return Err(Error::ChannelForRequestExists);
-                }
-            }
-        }
         self.workers
             .push(WorkerChannel {
                 id: self.workers.len().into(),
                 req_types: Vec::from_slice(req_types).map_err(|_| Error::TooManyRequestTypes)?,
+                preference,
+                available: true,
+                pending: Vec::new(),
                 requests: Mutex::new(requests),
                 responses: Mutex::new(responses.peekable()),
             })
@@ -229,13 +651,79 @@ impl<
         Ok(self)
     }
 
-    pub fn build(self) -> Core<'data, 'keystore, M, ReqSrc, RespSink, ReqSink, RespSrc, KeyStore> {
+    /// Add a worker that is hosted by the core. Instead of forwarding requests to the worker
+    /// through queues, the core calls [Worker::handle] itself. This saves the queues and the task
+    /// of the worker, but the core cannot process other requests while the worker is busy.
+    ///
+    /// Hosted workers take precedence over workers with queues for the same request type. If
+    /// several hosted workers handle the same request type, the one added first is used. Responses
+    /// that do not answer the handled request are replaced by [jobs::Error::WorkerUnavailable].
+    #[allow(clippy::type_complexity)]
+    pub fn with_hosted_worker<W: Worker<'data>>(
+        self,
+        worker: W,
+    ) -> Result<
+        Builder<
+            'data,
+            'keystore,
+            M,
+            ReqSrc,
+            RespSink,
+            KeyStore,
+            ReqSink,
+            RespSrc,
+            MAX_CLIENTS,
+            MAX_WORKERS,
+            MAX_REQUEST_TYPES,
+            (Hosted, W),
+        >,
+        Error,
+    > {
+        if worker
+            .request_types()
+            .iter()
+            .any(|r| r.is_handled_by_core())
+        {
+            return Err(Error::InvalidRequestType);
+        }
+        Ok(Builder {
+            key_store: self.key_store,
+            clients: self.clients,
+            workers: self.workers,
+            clock: self.clock,
+            cancellations: self.cancellations,
+            memory: self.memory,
+            hosted: (self.hosted, worker),
+        })
+    }
+
+    pub fn build(
+        self,
+    ) -> Core<
+        'data,
+        'keystore,
+        M,
+        ReqSrc,
+        RespSink,
+        KeyStore,
+        ReqSink,
+        RespSrc,
+        MAX_CLIENTS,
+        MAX_WORKERS,
+        MAX_REQUEST_TYPES,
+        Hosted,
+    > {
         Core {
             key_store: self.key_store,
             clients: self.clients,
             workers: self.workers,
-            last_client_id: 0,
             last_worker_id: 0,
+            virtual_time: 0,
+            served: 0,
+            clock: self.clock,
+            cancellations: self.cancellations,
+            memory: self.memory,
+            hosted: self.hosted,
         }
     }
 }
@@ -246,24 +734,330 @@ impl<
         M: RawMutex,
         ReqSrc: Stream<Item = Request<'data>> + Unpin,
         RespSink: Sink<Response<'data>> + Unpin,
+        KeyStore: keystore::KeyStore,
         ReqSink: Sink<Request<'data>> + Unpin,
         RespSrc: Stream<Item = Response<'data>> + Unpin,
-        KeyStore: keystore::KeyStore,
-    > Core<'data, 'keystore, M, ReqSrc, RespSink, ReqSink, RespSrc, KeyStore>
+        const MAX_CLIENTS: usize,
+        const MAX_WORKERS: usize,
+        const MAX_REQUEST_TYPES: usize,
+        Hosted: HostedWorkers<'data>,
+    >
+    Core<
+        'data,
+        'keystore,
+        M,
+        ReqSrc,
+        RespSink,
+        KeyStore,
+        ReqSink,
+        RespSrc,
+        MAX_CLIENTS,
+        MAX_WORKERS,
+        MAX_REQUEST_TYPES,
+        Hosted,
+    >
 {
     /// Drive the core to process the next client request or forward the next worker response.
     /// This method is supposed to be called by a system task that owns the core.
     pub async fn execute(&mut self) -> Result<(), Error> {
+        self.update_backlog();
         match self.next_job().await? {
             Job::ForwardRequest(client_id, worker_id) => {
+                self.charge_client(client_id)?;
                 self.forward_request(client_id, worker_id).await
             }
             Job::ForwardResponse(client_id, worker_id) => {
                 self.forward_response(client_id, worker_id).await
             }
-            Job::ProcessOnCore(client_id) => self.process_on_core(client_id).await,
-            Job::RespondNoWorkerForRequest(client_id) => {
-                self.respond_no_worker_for_request(client_id).await
+            Job::ProcessOnCore(client_id) => {
+                self.charge_client(client_id)?;
+                self.process_on_core(client_id).await
+            }
+            Job::ProcessOnHostedWorker(client_id) => {
+                self.charge_client(client_id)?;
+                self.process_on_hosted_worker(client_id).await
+            }
+            Job::RespondError(client_id, error) => {
+                self.charge_client(client_id)?;
+                self.respond_error(client_id, error).await
+            }
+            Job::RejectRequest(client_id, limit) => {
+                self.charge_client(client_id)?;
+                self.reject_request(client_id, limit).await
+            }
+            Job::DropRequest(client_id, reason) => {
+                self.charge_client(client_id)?;
+                self.drop_request(client_id, reason).await
+            }
+            Job::DetachWorker(worker_id) => self.detach_worker(worker_id).await,
+            Job::DisconnectClient(client_id) => {
+                self.disconnect_client(client_id);
+                Ok(())
+            }
+        }
+    }
+
+    /// Detach a worker. Requests pending at the worker are answered with
+    /// [jobs::Error::WorkerUnavailable] and no further requests are forwarded to it.
+    ///
+    /// The core detaches workers on its own once one of their channels fails. This method allows
+    /// the system to do the same, e.g. after a worker task was found to be stuck.
+    pub async fn detach_worker(&mut self, worker_id: WorkerId) -> Result<(), Error> {
+        let worker = self
+            .workers
+            .get_mut(worker_id.idx())
+            .ok_or(Error::InvalidWorkerId(worker_id))?;
+        worker.available = false;
+        let pending = core::mem::take(&mut worker.pending);
+        for (client_id, request_id) in pending {
+            if let Some(client) = self.clients.get_mut(client_id.idx()) {
+                client.stats.in_flight = client.stats.in_flight.saturating_sub(1);
+            }
+            if let Some(cancellations) = self.cancellations {
+                cancellations.take(client_id, request_id);
+            }
+            let response = Response::Error {
+                client_id,
+                request_id,
+                error: jobs::Error::WorkerUnavailable,
+            };
+            self.send_to_client(response).await?;
+        }
+        Ok(())
+    }
+
+    /// Attach new channels to a worker, e.g. after the worker task was restarted. A worker that is
+    /// still attached is detached first.
+    pub async fn attach_worker(
+        &mut self,
+        worker_id: WorkerId,
+        requests: ReqSink,
+        responses: RespSrc,
+    ) -> Result<(), Error> {
+        self.detach_worker(worker_id).await?;
+        let worker = &mut self.workers[worker_id.idx()];
+        worker.requests = Mutex::new(requests);
+        worker.responses = Mutex::new(responses.peekable());
+        worker.available = true;
+        Ok(())
+    }
+
+    /// Whether requests are forwarded to the given worker.
+    pub fn is_worker_available(&self, worker_id: WorkerId) -> bool {
+        self.workers
+            .get(worker_id.idx())
+            .is_some_and(|worker| worker.available)
+    }
+
+    /// Whether the channels of the given client are still working.
+    pub fn is_client_connected(&self, client_id: ClientId) -> bool {
+        self.clients
+            .get(client_id.idx())
+            .is_some_and(|client| client.connected)
+    }
+
+    /// Stop serving a client. Responses of its requests that are still processed by workers are
+    /// discarded.
+    fn disconnect_client(&mut self, client_id: ClientId) {
+        if let Some(client) = self.clients.get_mut(client_id.idx()) {
+            client.connected = false;
+        }
+    }
+
+    /// Diagnostic counters of the given client.
+    pub fn client_stats(&self, client_id: ClientId) -> Option<ClientStats> {
+        self.clients.get(client_id.idx()).map(|client| client.stats)
+    }
+
+    fn now(&self) -> u64 {
+        self.clock.map_or(0, |now| now())
+    }
+
+    /// Check whether the request should be dropped instead of being dispatched.
+    fn cancel_reason(&self, client_id: ClientId, request: &Request<'data>) -> Option<CancelReason> {
+        if request
+            .get_deadline()
+            .is_some_and(|deadline| self.now() > deadline)
+        {
+            return Some(CancelReason::Expired);
+        }
+        if self
+            .cancellations
+            .is_some_and(|c| c.is_cancelled(client_id, request.get_request_id()))
+        {
+            return Some(CancelReason::Cancelled);
+        }
+        None
+    }
+
+    /// Features of the HSM as reported to the client `client_id`. Request types are supported if
+    /// the core or an available worker can process them.
+    fn capabilities(&self, client_id: ClientId) -> Capabilities {
+        let mut request_types = RequestTypes::new();
+        for request_type in RequestType::iter() {
+            let supported = if request_type.is_handled_by_core() {
+                // Key management requests fail without a key store
+                match request_type {
+                    RequestType::Cancel => self.cancellations.is_some(),
+                    RequestType::GetCapabilities => true,
+                    _ => self.key_store.is_some(),
+                }
+            } else {
+                self.hosted.supports(request_type)
+                    || self
+                        .workers
+                        .iter()
+                        .any(|worker| worker.available && worker.req_types.contains(&request_type))
+            };
+            if supported {
+                request_types.insert(request_type);
+            }
+        }
+        let mut symmetric_primitives = SymmetricPrimitives::new();
+        for primitive in SymmetricPrimitive::iter() {
+            if primitive
+                .request_types()
+                .iter()
+                .any(|&request_type| request_types.contains(request_type))
+            {
+                symmetric_primitives.insert(primitive);
+            }
+        }
+        let mut key_types = KeyKinds::new();
+        if request_types.contains(RequestType::ImportSymmetricKey) {
+            key_types.insert(KeyKind::Symmetric);
+        }
+        if request_types.contains(RequestType::ImportKeyPair) {
+            key_types.insert(KeyKind::Asymmetric);
+            key_types.insert(KeyKind::KeyPair);
+        }
+        Capabilities {
+            abi_version: AbiVersion::CURRENT,
+            request_types,
+            hash_algorithms: HASH_ALGORITHMS,
+            curves: CURVES,
+            symmetric_primitives,
+            key_types,
+            key_store: self.key_store.is_some(),
+            max_symmetric_key_size: KeyType::MAX_SYMMETRIC_KEY_SIZE,
+            max_request_size: self
+                .clients
+                .get(client_id.idx())
+                .and_then(|client| client.config.limits.max_request_size),
+        }
+    }
+
+    /// Check whether the request would exceed one of the limits of the client.
+    fn check_limits(
+        &self,
+        client: &ClientChannel<'data, ReqSrc, RespSink, M>,
+        request: &Request<'data>,
+    ) -> Option<Limit> {
+        let limits = &client.config.limits;
+        if limits
+            .max_request_size
+            .is_some_and(|max| request.data_size() > max)
+        {
+            return Some(Limit::RequestSize);
+        }
+        let request_type = request.get_type();
+        if request_type.is_handled_by_worker()
+            && limits
+                .max_in_flight
+                .is_some_and(|max| client.stats.in_flight >= max)
+        {
+            return Some(Limit::InFlight);
+        }
+        let now = self.now();
+        let rate_exceeded = limits
+            .rates
+            .iter()
+            .zip(client.buckets.iter())
+            .filter(|(rate, _)| rate.request_type == request_type)
+            .any(|(rate, bucket)| bucket.refilled(rate, now).0 == 0);
+        if rate_exceeded {
+            return Some(Limit::Rate);
+        }
+        None
+    }
+
+    /// Take tokens for the accepted request from the buckets of the client.
+    fn accept_request(&mut self, client_id: ClientId, request_type: RequestType) {
+        let now = self.now();
+        let Some(client) = self.clients.get_mut(client_id.idx()) else {
+            return;
+        };
+        let rates = client.config.limits.rates.iter();
+        for (rate, bucket) in rates.zip(client.buckets.iter_mut()) {
+            if rate.request_type == request_type {
+                let (tokens, last_refill) = bucket.refilled(rate, now);
+                bucket.tokens = tokens.saturating_sub(1);
+                bucket.last_refill = last_refill;
+            }
+        }
+        client.stats.accepted = client.stats.accepted.wrapping_add(1);
+        if request_type.is_handled_by_worker() {
+            client.stats.in_flight = client.stats.in_flight.saturating_add(1);
+        }
+    }
+
+    /// Virtual start and finish time of the next request of the given client.
+    fn virtual_times(&self, client: &ClientChannel<'data, ReqSrc, RespSink, M>) -> (u64, u64) {
+        // Clients that were idle accumulate credit for at most one request
+        let start = client
+            .virtual_time
+            .max(self.virtual_time.saturating_sub(VIRTUAL_TIME_PER_REQUEST));
+        let finish = start + VIRTUAL_TIME_PER_REQUEST / client.config.weight as u64;
+        (start, finish)
+    }
+
+    /// Determine the order in which pending client requests are considered. Lower keys come first.
+    fn schedule_key(
+        &self,
+        client: &ClientChannel<'data, ReqSrc, RespSink, M>,
+    ) -> (bool, Reverse<u8>, u64, usize) {
+        let starved = client
+            .backlogged_since
+            .is_some_and(|since| self.served.wrapping_sub(since) >= STARVATION_LIMIT);
+        let (_start, finish) = self.virtual_times(client);
+        (
+            !starved,
+            Reverse(client.config.priority),
+            finish,
+            client.id.idx(),
+        )
+    }
+
+    /// Account for a request of the given client that is about to be processed.
+    fn charge_client(&mut self, client_id: ClientId) -> Result<(), Error> {
+        let client = self
+            .clients
+            .get(client_id.idx())
+            .ok_or(Error::Internal(InternalError::InvalidClientId(client_id)))?;
+        let (start, finish) = self.virtual_times(client);
+        let served = self.served;
+        self.virtual_time = self.virtual_time.max(start);
+        self.served = served.wrapping_add(1);
+        let client = &mut self.clients[client_id.idx()];
+        client.virtual_time = finish;
+        client.backlogged_since = None;
+        Ok(())
+    }
+
+    /// Record which idle clients have become backlogged since the last call. Clients only count as
+    /// starved while they are backlogged, so an idle client that sends a request is not preferred
+    /// over the others right away.
+    fn update_backlog(&mut self) {
+        let served = self.served;
+        let mut cx = Context::from_waker(noop_waker_ref());
+        for client in self
+            .clients
+            .iter_mut()
+            .filter(|client| client.connected && client.backlogged_since.is_none())
+        {
+            let requests = Pin::new(client.requests.get_mut());
+            if let Poll::Ready(Some(_)) = requests.poll_peek(&mut cx) {
+                client.backlogged_since = Some(served);
             }
         }
     }
@@ -272,71 +1066,124 @@ impl<
     /// If so, the found job will be returned to be performed by the caller.
     async fn next_job(&self) -> Result<Job, Error> {
         let mut workers: Vec<_, MAX_WORKERS> = self.workers.iter().collect();
-        let mut clients: Vec<_, MAX_CLIENTS> = self.clients.iter().collect();
+        let mut clients: Vec<_, MAX_CLIENTS> = self
+            .clients
+            .iter()
+            .filter(|client| client.connected)
+            .collect();
         workers.rotate_left(self.last_worker_id);
-        clients.rotate_left(self.last_client_id);
+        workers.retain(|worker| worker.available);
+        // If several clients have pending requests, the first one in this order is served
+        clients.sort_unstable_by_key(|client| self.schedule_key(client));
 
         // Futures to handle worker responses
         let process_response = workers.iter().map(|worker| async {
             // Check for incoming response from worker channels
             let mut responses = worker.responses.lock().await;
-            let response = Pin::new(responses.deref_mut())
-                .peek()
-                .await
-                .ok_or(Error::StreamTerminated)?;
+            let Some(response) = Pin::new(responses.deref_mut()).peek().await else {
+                return Ok(Job::DetachWorker(worker.id));
+            };
 
-            // Find client for received response
+            // Find client for received response. A worker answering unknown clients misbehaves.
             let client_id = response.get_client_id();
-            let client = self
-                .clients
-                .get(client_id.idx())
-                .ok_or(Error::Internal(InternalError::InvalidClientId(client_id)))?;
+            let Some(client) = self.clients.get(client_id.idx()) else {
+                return Ok(Job::DetachWorker(worker.id));
+            };
+            if !client.connected {
+                // Response is discarded right away
+                return Ok(Job::ForwardResponse(client_id, worker.id));
+            }
             let mut responses = client.responses.lock().await;
 
             // Check if client queue has room to accept the response
-            poll_fn(move |cx| responses.deref_mut().poll_ready_unpin(cx))
-                .await
-                .map_err(|_| Error::StreamTerminated)?;
-            Ok(Job::ForwardResponse(client_id, worker.id))
+            match poll_fn(move |cx| responses.deref_mut().poll_ready_unpin(cx)).await {
+                Ok(()) => Ok(Job::ForwardResponse(client_id, worker.id)),
+                Err(_) => Ok(Job::DisconnectClient(client_id)),
+            }
         });
 
         // Futures to handle client requests
         let process_requests = clients.iter().map(|client| async {
             // Check for incoming requests from client channels
             let mut requests = client.requests.lock().await;
-            let request = Pin::new(requests.deref_mut())
-                .peek()
-                .await
-                .ok_or(Error::StreamTerminated)?;
+            let Some(request) = Pin::new(requests.deref_mut()).peek().await else {
+                return Ok(Job::DisconnectClient(client.id));
+            };
             let request_type = request.get_type();
+            if let Some(reason) = self.cancel_reason(client.id, request) {
+                return Ok(Job::DropRequest(client.id, reason));
+            }
+            if self
+                .memory
+                .is_some_and(|memory| !memory.allows_request(client.id, request))
+            {
+                return Ok(Job::RespondError(client.id, jobs::Error::AccessDenied));
+            }
+            if let Some(limit) = self.check_limits(client, request) {
+                return Ok(Job::RejectRequest(client.id, limit));
+            }
             if request_type.is_handled_by_core() {
                 return Ok(Job::ProcessOnCore(client.id));
             }
+            if self.hosted.supports(request_type) {
+                return Ok(Job::ProcessOnHostedWorker(client.id));
+            }
 
-            // Find worker for received request
-            let worker = match self
-                .workers
-                .iter()
-                .find(|w| w.req_types.contains(&request_type))
-            {
-                None => return Ok(Job::RespondNoWorkerForRequest(client.id)),
-                Some(worker) => worker,
-            };
-            let mut requests = worker.requests.lock().await;
+            // Find workers for received request. Preferred workers come first, workers with equal
+            // preference are rotated to balance the load between them.
+            let mut candidates: Vec<usize, MAX_WORKERS> = (0..workers.len())
+                .filter(|&i| workers[i].req_types.contains(&request_type))
+                .collect();
+            if candidates.is_empty() {
+                let error = if self
+                    .workers
+                    .iter()
+                    .any(|worker| worker.req_types.contains(&request_type))
+                {
+                    jobs::Error::WorkerUnavailable
+                } else {
+                    jobs::Error::NoWorkerForRequest
+                };
+                return Ok(Job::RespondError(client.id, error));
+            }
+            candidates.sort_unstable_by_key(|&i| (Reverse(workers[i].preference), i));
 
-            // Check if worker queue has room to accept the request
-            poll_fn(move |cx| requests.deref_mut().poll_ready_unpin(cx))
-                .await
-                .map_err(|_| Error::StreamTerminated)?;
-            Ok(Job::ForwardRequest(client.id, worker.id))
+            // Wait for the first worker queue that has room to accept the request. If several are
+            // ready at once, the first one in the candidate list is chosen. Workers with too many
+            // pending requests are skipped. All clients lock the worker queues in the same order
+            // so they cannot deadlock.
+            let mut worker_requests: Vec<_, MAX_WORKERS> = Vec::new();
+            for &i in &candidates {
+                let requests = workers[i].requests.lock().await;
+                // Cannot fail, there are no more candidates than workers
+                let _ = worker_requests.push(requests);
+            }
+            let job = poll_fn(|cx| {
+                for (&i, requests) in candidates.iter().zip(worker_requests.iter_mut()) {
+                    let worker = workers[i];
+                    if worker.pending.is_full() {
+                        continue;
+                    }
+                    match requests.deref_mut().poll_ready_unpin(cx) {
+                        Poll::Ready(Ok(())) => {
+                            return Poll::Ready(Job::ForwardRequest(client.id, worker.id))
+                        }
+                        Poll::Ready(Err(_)) => return Poll::Ready(Job::DetachWorker(worker.id)),
+                        Poll::Pending => {}
+                    }
+                }
+                Poll::Pending
+            })
+            .await;
+            Ok(job)
         });
 
-        // Collect and execute all futures
-        let mut jobs: Vec<_, { MAX_WORKERS + MAX_CLIENTS }> = process_response
-            .map(|f| f.left_future())
-            .chain(process_requests.map(|f| f.right_future()))
-            .collect();
-        select_slice(&mut jobs).await.0
+        // Collect and execute all futures. Worker responses take precedence over client requests.
+        let mut responses: Vec<_, MAX_WORKERS> = process_response.collect();
+        let mut requests: Vec<_, MAX_CLIENTS> = process_requests.collect();
+        match select(select_slice(&mut responses), select_slice(&mut requests)).await {
+            Either::First((job, _)) | Either::Second((job, _)) => job,
+        }
     }
 
     async fn forward_response(
@@ -344,10 +1191,11 @@ impl<
         client_id: ClientId,
         worker_id: WorkerId,
     ) -> Result<(), Error> {
-        let response = self
+        let worker = self
             .workers
-            .get(worker_id.idx())
-            .ok_or(Error::Internal(InternalError::InvalidWorkerId(worker_id)))?
+            .get_mut(worker_id.idx())
+            .ok_or(Error::Internal(InternalError::InvalidWorkerId(worker_id)))?;
+        let response = worker
             .responses
             .lock()
             .await
@@ -358,11 +1206,24 @@ impl<
                 worker_id,
             )))?;
         if client_id != response.get_client_id() {
-            // Mismatch of computed and response client IDs
-            return Err(Error::Internal(InternalError::ClientIdMismatch(
-                client_id,
-                response.get_client_id(),
-            )));
+            // The response changed since it was peeked. The worker misbehaves, so its response is
+            // dropped and its pending requests are answered with an error.
+            return self.detach_worker(worker_id).await;
+        }
+        let request_id = response.get_request_id();
+        if let Some(index) = worker
+            .pending
+            .iter()
+            .position(|&entry| entry == (client_id, request_id))
+        {
+            worker.pending.remove(index);
+        }
+        if let Some(client) = self.clients.get_mut(client_id.idx()) {
+            client.stats.in_flight = client.stats.in_flight.saturating_sub(1);
+        }
+        // The request is done, a cancellation that came too late is obsolete
+        if let Some(cancellations) = self.cancellations {
+            cancellations.take(client_id, request_id);
         }
         self.send_to_client(response).await
     }
@@ -373,25 +1234,32 @@ impl<
         worker_id: WorkerId,
     ) -> Result<(), Error> {
         let request = self.recv_from_client(client_id).await?;
-        self.workers
-            .get(worker_id.idx())
-            .ok_or(Error::Internal(InternalError::InvalidWorkerId(worker_id)))?
-            .requests
-            .lock()
-            .await
-            .deref_mut()
-            .send(request)
-            .await
-            .map_err(|_e| Error::Send)
+        self.accept_request(client_id, request.get_type());
+        let worker = self
+            .workers
+            .get_mut(worker_id.idx())
+            .ok_or(Error::Internal(InternalError::InvalidWorkerId(worker_id)))?;
+        // Cannot fail, workers without room for pending requests are not chosen
+        let _ = worker.pending.push((client_id, request.get_request_id()));
+        let sent = worker.requests.lock().await.deref_mut().send(request).await;
+        if sent.is_err() {
+            // The request is pending now and answered when detaching the worker
+            return self.detach_worker(worker_id).await;
+        }
+        // Start with the next worker when looking for a worker the next time
+        self.last_worker_id = (worker_id.idx() + 1) % self.workers.len();
+        Ok(())
     }
 
     async fn process_on_core(&mut self, client_id: ClientId) -> Result<(), Error> {
         let request = self.recv_from_client(client_id).await?;
+        self.accept_request(client_id, request.get_type());
         let response = match request {
             Request::IsKeyAvailable {
                 client_id,
                 request_id,
                 key_id,
+                ..
             } => match self.key_store {
                 None => Ok(Self::no_key_store_response(client_id, request_id)),
                 Some(key_store) => {
@@ -403,12 +1271,32 @@ impl<
                     })
                 }
             },
+            Request::GetKeyInfo {
+                client_id,
+                request_id,
+                key_id,
+                ..
+            } => match self.key_store {
+                None => Ok(Self::no_key_store_response(client_id, request_id)),
+                Some(key_store) => {
+                    let result = key_store.lock().await.deref_mut().get_key_info(key_id);
+                    match result {
+                        Ok(key_info) => Ok(Response::GetKeyInfo {
+                            client_id,
+                            request_id,
+                            key_info,
+                        }),
+                        Err(e) => Ok(Self::key_store_error_response(client_id, request_id, e)),
+                    }
+                }
+            },
             Request::ImportSymmetricKey {
                 client_id,
                 request_id,
                 key_id,
                 data,
                 overwrite,
+                ..
             } => match self.key_store {
                 None => Ok(Self::no_key_store_response(client_id, request_id)),
                 Some(key_store) => {
@@ -432,14 +1320,18 @@ impl<
                 key_id,
                 public_key,
                 private_key,
+                format,
                 overwrite,
+                ..
             } => match self.key_store {
                 None => Ok(Self::no_key_store_response(client_id, request_id)),
                 Some(key_store) => {
-                    let result = key_store.lock().await.deref_mut().import_key_pair(
+                    let result = import_key_pair(
+                        &mut **key_store.lock().await,
                         key_id,
                         public_key,
                         private_key,
+                        format,
                         overwrite,
                     );
                     match result {
@@ -447,7 +1339,11 @@ impl<
                             client_id,
                             request_id,
                         }),
-                        Err(e) => Ok(Self::key_store_error_response(client_id, request_id, e)),
+                        Err(error) => Ok(Response::Error {
+                            client_id,
+                            request_id,
+                            error,
+                        }),
                     }
                 }
             },
@@ -456,6 +1352,7 @@ impl<
                 request_id,
                 key_id,
                 data,
+                ..
             } => match self.key_store {
                 None => Ok(Self::no_key_store_response(client_id, request_id)),
                 Some(key_store) => {
@@ -482,24 +1379,24 @@ impl<
                 request_id,
                 key_id,
                 public_key,
+                format,
+                ..
             } => match self.key_store {
                 None => Ok(Self::no_key_store_response(client_id, request_id)),
                 Some(key_store) => {
-                    let exported_key = key_store
-                        .lock()
-                        .await
-                        .deref_mut()
-                        .export_public_key(key_id, public_key);
-                    match exported_key {
-                        Ok(written) => {
-                            let exported_key_len = written.len();
-                            Ok(Response::ExportPublicKey {
-                                client_id,
-                                request_id,
-                                public_key: &mut public_key[..exported_key_len],
-                            })
-                        }
-                        Err(e) => Ok(Self::key_store_error_response(client_id, request_id, e)),
+                    let exported_key_len =
+                        export_public_key(&**key_store.lock().await, key_id, format, public_key);
+                    match exported_key_len {
+                        Ok(exported_key_len) => Ok(Response::ExportPublicKey {
+                            client_id,
+                            request_id,
+                            public_key: &mut public_key[..exported_key_len],
+                        }),
+                        Err(error) => Ok(Response::Error {
+                            client_id,
+                            request_id,
+                            error,
+                        }),
                     }
                 }
             },
@@ -508,6 +1405,7 @@ impl<
                 request_id,
                 key_id,
                 private_key,
+                ..
             } => match self.key_store {
                 None => Ok(Self::no_key_store_response(client_id, request_id)),
                 Some(key_store) => {
@@ -529,6 +1427,45 @@ impl<
                     }
                 }
             },
+            Request::Cancel {
+                client_id,
+                request_id,
+                target,
+                ..
+            } => {
+                let error = match self.cancellations {
+                    Some(cancellations) => {
+                        // Other requests were answered already or never reached a worker
+                        let pending = self
+                            .workers
+                            .iter()
+                            .any(|worker| worker.pending.contains(&(client_id, target)));
+                        (!pending || !cancellations.cancel(client_id, target))
+                            .then_some(jobs::Error::NotCancellable)
+                    }
+                    None => Some(jobs::Error::CancellationUnsupported),
+                };
+                Ok(match error {
+                    None => Response::Cancel {
+                        client_id,
+                        request_id,
+                    },
+                    Some(error) => Response::Error {
+                        client_id,
+                        request_id,
+                        error,
+                    },
+                })
+            }
+            Request::GetCapabilities {
+                client_id,
+                request_id,
+                ..
+            } => Ok(Response::GetCapabilities {
+                client_id,
+                request_id,
+                capabilities: self.capabilities(client_id),
+            }),
             _ => Err(Error::Internal(InternalError::UnexpectedCoreRequest(
                 request.get_type(),
             ))),
@@ -536,18 +1473,88 @@ impl<
         self.send_to_client(response).await
     }
 
-    async fn respond_no_worker_for_request(&mut self, client_id: ClientId) -> Result<(), Error> {
+    async fn process_on_hosted_worker(&mut self, client_id: ClientId) -> Result<(), Error> {
+        let request = self.recv_from_client(client_id).await?;
+        let request_id = request.get_request_id();
+        self.accept_request(client_id, request.get_type());
+        let response = match self.hosted.handle(request).await {
+            // A response addressed to another request would leave the requesting client waiting
+            Ok(response)
+                if response.get_client_id() == client_id
+                    && response.get_request_id() == request_id =>
+            {
+                response
+            }
+            Ok(_) => Response::Error {
+                client_id,
+                request_id,
+                error: jobs::Error::WorkerUnavailable,
+            },
+            Err(error) => Response::Error {
+                client_id,
+                request_id,
+                error,
+            },
+        };
+        if let Some(client) = self.clients.get_mut(client_id.idx()) {
+            client.stats.in_flight = client.stats.in_flight.saturating_sub(1);
+        }
+        self.send_to_client(response).await
+    }
+
+    async fn respond_error(
+        &mut self,
+        client_id: ClientId,
+        error: jobs::Error,
+    ) -> Result<(), Error> {
         // Remove request from queue even though we cannot handle it
         let request = self.recv_from_client(client_id).await?;
         let response = Response::Error {
             client_id,
             request_id: request.get_request_id(),
-            error: jobs::Error::NoWorkerForRequest,
+            error,
         };
         self.send_to_client(response).await
     }
 
-    async fn recv_from_client<'ch>(&self, client_id: ClientId) -> Result<Request<'data>, Error> {
+    async fn drop_request(
+        &mut self,
+        client_id: ClientId,
+        reason: CancelReason,
+    ) -> Result<(), Error> {
+        let request = self.recv_from_client(client_id).await?;
+        if let Some(cancellations) = self.cancellations {
+            cancellations.take(client_id, request.get_request_id());
+        }
+        let response = Response::Cancelled {
+            client_id,
+            request_id: request.get_request_id(),
+            reason,
+        };
+        self.send_to_client(response).await
+    }
+
+    async fn reject_request(&mut self, client_id: ClientId, limit: Limit) -> Result<(), Error> {
+        let request = self.recv_from_client(client_id).await?;
+        let client = self
+            .clients
+            .get_mut(client_id.idx())
+            .ok_or(Error::Internal(InternalError::InvalidClientId(client_id)))?;
+        let rejected = match limit {
+            Limit::InFlight => &mut client.stats.rejected_in_flight,
+            Limit::Rate => &mut client.stats.rejected_rate,
+            Limit::RequestSize => &mut client.stats.rejected_size,
+        };
+        *rejected = rejected.wrapping_add(1);
+        let response = Response::Error {
+            client_id,
+            request_id: request.get_request_id(),
+            error: jobs::Error::LimitExceeded(limit),
+        };
+        self.send_to_client(response).await
+    }
+
+    async fn recv_from_client(&self, client_id: ClientId) -> Result<Request<'data>, Error> {
         let mut request = self
             .clients
             .get(client_id.idx())
@@ -568,18 +1575,27 @@ impl<
         Ok(request)
     }
 
+    /// Send a response to a client. Responses to disconnected clients are discarded.
     async fn send_to_client(&mut self, response: Response<'data>) -> Result<(), Error> {
         let client_id = response.get_client_id();
-        self.clients
+        let client = self
+            .clients
             .get(client_id.idx())
-            .ok_or(Error::Internal(InternalError::InvalidClientId(client_id)))?
+            .ok_or(Error::Internal(InternalError::InvalidClientId(client_id)))?;
+        if !client.connected {
+            return Ok(());
+        }
+        let sent = client
             .responses
             .lock()
             .await
             .deref_mut()
             .send(response)
-            .await
-            .map_err(|_e| Error::Send)
+            .await;
+        if sent.is_err() {
+            self.disconnect_client(client_id);
+        }
+        Ok(())
     }
 
     fn no_key_store_response(client_id: ClientId, request_id: RequestId) -> Response<'data> {
@@ -602,3 +1618,117 @@ impl<
         }
     }
 }
+
+/// Import a key pair into the key store. Encoded private keys are converted to the raw format
+/// used by the key store first.
+fn import_key_pair<KeyStore: keystore::KeyStore>(
+    key_store: &mut KeyStore,
+    key_id: KeyId,
+    public_key: &[u8],
+    private_key: &[u8],
+    format: PrivateKeyFormat,
+    overwrite: bool,
+) -> Result<(), jobs::Error> {
+    if format == PrivateKeyFormat::Raw {
+        return key_store
+            .import_key_pair(key_id, public_key, private_key, overwrite)
+            .map_err(jobs::Error::KeyStore);
+    }
+    if !public_key.is_empty() {
+        return Err(jobs::Error::Crypto(crypto::Error::InvalidPublicKey));
+    }
+    let KeyType::Asymmetric(curve) = key_store.get_key_info(key_id)?.ty else {
+        return Err(jobs::Error::KeyStore(keystore::Error::InvalidKeyType));
+    };
+    let mut raw_private_key = Zeroizing::new([0u8; KeyType::MAX_PRIVATE_KEY_SIZE]);
+    let mut raw_public_key = [0u8; KeyType::MAX_PUBLIC_KEY_SIZE];
+    let (raw_private_key, raw_public_key) = match curve {
+        Curve::NistP256 => decode_key_pair::<NistP256>(
+            private_key,
+            format,
+            raw_private_key.as_mut_slice(),
+            &mut raw_public_key,
+        ),
+        Curve::NistP384 => decode_key_pair::<NistP384>(
+            private_key,
+            format,
+            raw_private_key.as_mut_slice(),
+            &mut raw_public_key,
+        ),
+    }?;
+    key_store
+        .import_key_pair(key_id, raw_public_key, raw_private_key, overwrite)
+        .map_err(jobs::Error::KeyStore)
+}
+
+/// Export a public key from the key store and encode it in the requested format.
+///
+/// returns: The number of bytes written to `dest`.
+fn export_public_key<KeyStore: keystore::KeyStore>(
+    key_store: &KeyStore,
+    key_id: KeyId,
+    format: PublicKeyFormat,
+    dest: &mut [u8],
+) -> Result<usize, jobs::Error> {
+    if format == PublicKeyFormat::Raw {
+        return Ok(key_store.export_public_key(key_id, dest)?.len());
+    }
+    let mut raw_public_key = [0u8; KeyType::MAX_PUBLIC_KEY_SIZE];
+    let raw_public_key = key_store.export_public_key(key_id, &mut raw_public_key)?;
+    let KeyType::Asymmetric(curve) = key_store.get_key_info(key_id)?.ty else {
+        return Err(jobs::Error::KeyStore(keystore::Error::InvalidKeyType));
+    };
+    let written = match curve {
+        Curve::NistP256 => encode_public_key::<NistP256>(raw_public_key, format, dest),
+        Curve::NistP384 => encode_public_key::<NistP384>(raw_public_key, format, dest),
+    }?;
+    Ok(written.len())
+}
+
+fn decode_key_pair<'a, 'b, C>(
+    private_key: &[u8],
+    format: PrivateKeyFormat,
+    raw_private_key: &'a mut [u8],
+    raw_public_key: &'b mut [u8],
+) -> Result<(&'a [u8], &'b [u8]), crypto::Error>
+where
+    C: AssociatedOid + CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    let secret_key = match format {
+        PrivateKeyFormat::Raw => {
+            SecretKey::<C>::from_slice(private_key).map_err(|_| crypto::Error::InvalidPrivateKey)?
+        }
+        PrivateKeyFormat::Pkcs8Der => ecc::private_key_from_pkcs8_der::<C>(private_key)?,
+        PrivateKeyFormat::Sec1Der => ecc::private_key_from_sec1_der::<C>(private_key)?,
+        PrivateKeyFormat::Pem => ecc::private_key_from_pem::<C>(private_key)?,
+    };
+    ecc::key_pair_to_raw(&secret_key, raw_private_key, raw_public_key)
+}
+
+fn encode_public_key<'a, C>(
+    raw_public_key: &[u8],
+    format: PublicKeyFormat,
+    dest: &'a mut [u8],
+) -> Result<&'a [u8], crypto::Error>
+where
+    C: AssociatedOid + CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    let public_key = ecc::public_key_from_raw::<C>(raw_public_key)?;
+    match format {
+        PublicKeyFormat::Raw => {
+            let dest = dest
+                .get_mut(..raw_public_key.len())
+                .ok_or(crypto::Error::InvalidBufferSize)?;
+            dest.copy_from_slice(raw_public_key);
+            Ok(dest)
+        }
+        PublicKeyFormat::Sec1Uncompressed => ecc::public_key_to_sec1(&public_key, false, dest),
+        PublicKeyFormat::Sec1Compressed => ecc::public_key_to_sec1(&public_key, true, dest),
+        PublicKeyFormat::SpkiDer => ecc::public_key_to_spki_der(&public_key, dest),
+        PublicKeyFormat::SpkiPem => ecc::public_key_to_spki_pem(&public_key, dest),
+    }
+}
@@ -1,3 +1,4 @@
+use crate::hsm::she::SheSlotState;
 use displaydoc::Display;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Display)]
@@ -30,10 +31,27 @@ pub enum Curve {
     NistP384,
 }
 
+/// Algorithm of a [KeyType::KeyPair]. Workers only use key pairs of the algorithms they implement.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum KeyPairAlgorithm {
+    Ed25519,
+    X25519,
+    /// Algorithm defined by the integrator
+    Other(u32),
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum KeyType {
     Symmetric(usize),
     Asymmetric(Curve),
+    /// Asymmetric key pair of an algorithm whose key layout is opaque to the key store, e.g.
+    /// Ed25519, X25519 or algorithms defined by the integrator. The sizes are upper bounds: Shorter keys (like
+    /// compressed points or private keys stored as seeds) can be stored as well.
+    KeyPair {
+        algorithm: KeyPairAlgorithm,
+        public_key_size: usize,
+        private_key_size: usize,
+    },
 }
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -67,7 +85,9 @@ impl Curve {
 
 impl KeyType {
     pub const MAX_SYMMETRIC_KEY_SIZE: usize = 64;
+    /// Largest public key of all supported curves. `KeyPair` keys may be larger.
     pub const MAX_PUBLIC_KEY_SIZE: usize = KeyType::Asymmetric(Curve::NistP384).public_key_size();
+    /// Largest private key of all supported curves. `KeyPair` keys may be larger.
     pub const MAX_PRIVATE_KEY_SIZE: usize = KeyType::Asymmetric(Curve::NistP384).private_key_size();
 
     pub const fn is_symmetric(&self) -> bool {
@@ -83,6 +103,9 @@ impl KeyType {
             KeyType::Asymmetric(c) => match c {
                 Curve::NistP256 | Curve::NistP384 => 2 * c.size(),
             },
+            KeyType::KeyPair {
+                public_key_size, ..
+            } => *public_key_size,
             _ => 0,
         }
     }
@@ -92,6 +115,9 @@ impl KeyType {
             KeyType::Asymmetric(c) => match c {
                 Curve::NistP256 | Curve::NistP384 => c.size(),
             },
+            KeyType::KeyPair {
+                private_key_size, ..
+            } => *private_key_size,
             _ => 0,
         }
     }
@@ -99,7 +125,39 @@ impl KeyType {
     pub const fn key_size(&self) -> usize {
         match self {
             KeyType::Symmetric(n) => *n,
-            KeyType::Asymmetric(_) => self.public_key_size() + self.private_key_size(),
+            KeyType::Asymmetric(_) | KeyType::KeyPair { .. } => {
+                self.public_key_size() + self.private_key_size()
+            }
+        }
+    }
+
+    /// Whether a key pair with the given sizes can be stored for this key type. Keys of supported
+    /// curves have to match their sizes exactly. Opaque key pairs may be shorter than their
+    /// maximum sizes but not empty.
+    pub const fn is_valid_key_pair_size(
+        &self,
+        public_key_size: usize,
+        private_key_size: usize,
+    ) -> bool {
+        match self {
+            KeyType::Symmetric(_) => false,
+            KeyType::Asymmetric(_) => {
+                public_key_size == self.public_key_size()
+                    && private_key_size == self.private_key_size()
+            }
+            KeyType::KeyPair { .. } => {
+                public_key_size + private_key_size > 0
+                    && public_key_size <= self.public_key_size()
+                    && private_key_size <= self.private_key_size()
+            }
+        }
+    }
+
+    /// The algorithm of a key pair of an algorithm that is opaque to the key store.
+    pub const fn key_pair_algorithm(&self) -> Option<KeyPairAlgorithm> {
+        match self {
+            KeyType::KeyPair { algorithm, .. } => Some(*algorithm),
+            _ => None,
         }
     }
 
@@ -186,6 +244,22 @@ pub trait InsecureKeyStore {
 
     /// Get the size of a key.
     fn size(&self, id: KeyId) -> Result<usize, Error>;
+
+    /// Store the state of the SHE slot backed by the key `id`.
+    ///
+    /// Called by [SheKeySlots::load_key] before the new key is written. Key stores that keep their
+    /// keys across resets must keep the slot states as well, otherwise old key updates can be
+    /// replayed after a reset. The default implementation does not store anything.
+    ///
+    /// [SheKeySlots::load_key]: crate::hsm::she::SheKeySlots::load_key
+    fn store_she_slot_state(&mut self, _id: KeyId, _state: SheSlotState) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// The SHE slot state stored for the key `id` or `None` if there is none.
+    fn she_slot_state(&self, _id: KeyId) -> Option<SheSlotState> {
+        None
+    }
 }
 
 pub trait KeyStore {
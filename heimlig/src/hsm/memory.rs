@@ -0,0 +1,158 @@
+use crate::common::jobs::{ClientId, Request};
+use crate::integration::raw_jobs::Validator;
+use displaydoc::Display;
+
+/// Access of a client to a [MemoryRegion].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Access {
+    /// The HSM only reads input buffers from the region.
+    ReadOnly,
+    /// The HSM reads input buffers from the region and writes output buffers to it.
+    ReadWrite,
+}
+
+/// Shared memory region in which a client passes buffers to the HSM.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MemoryRegion {
+    pub client_id: ClientId,
+    /// Address of the first byte of the region
+    pub start: usize,
+    /// Size of the region in bytes
+    pub size: usize,
+    pub access: Access,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Display)]
+pub enum Error {
+    /// Alignment is not a power of two.
+    InvalidAlignment,
+    /// Start or size of a region is not a multiple of the alignment.
+    UnalignedRegion,
+    /// A region is empty.
+    EmptyRegion,
+    /// A region exceeds the address space.
+    RegionOverflow,
+    /// Regions of different clients overlap.
+    OverlappingRegions,
+}
+
+/// Table of the shared memory regions of all clients.
+///
+/// Every buffer of a request has to lie within a single region of the client that sent the
+/// request. Output buffers additionally require [Access::ReadWrite]. Empty buffers do not access
+/// memory and are always allowed.
+///
+/// Regions of different clients must not overlap, so a client can never make the HSM read or
+/// write the memory of another client. Regions of the same client may overlap, e.g. to grant
+/// write access to a part of a read-only region.
+///
+/// Buffers may start at any address by default, because the software workers process them byte by
+/// byte. Integrators whose workers need aligned buffers, e.g. for DMA, can require an alignment
+/// with [MemoryRegions::with_buffer_alignment].
+#[derive(Copy, Clone, Debug)]
+pub struct MemoryRegions<'a> {
+    regions: &'a [MemoryRegion],
+    buffer_alignment: usize,
+}
+
+impl<'a> MemoryRegions<'a> {
+    /// Check the region table. Start and size of every region have to be multiples of `alignment`,
+    /// e.g. the granularity of the MPU that protects the regions.
+    pub fn new(regions: &'a [MemoryRegion], alignment: usize) -> Result<Self, Error> {
+        if !alignment.is_power_of_two() {
+            return Err(Error::InvalidAlignment);
+        }
+        for (i, region) in regions.iter().enumerate() {
+            if region.size == 0 {
+                return Err(Error::EmptyRegion);
+            }
+            if region.start % alignment != 0 || region.size % alignment != 0 {
+                return Err(Error::UnalignedRegion);
+            }
+            let end = region
+                .start
+                .checked_add(region.size)
+                .ok_or(Error::RegionOverflow)?;
+            if regions[..i].iter().any(|other| {
+                other.client_id != region.client_id
+                    // Regions before this one were already checked for overflows
+                    && other.start < end
+                    && region.start < other.start + other.size
+            }) {
+                return Err(Error::OverlappingRegions);
+            }
+        }
+        Ok(MemoryRegions {
+            regions,
+            buffer_alignment: 1,
+        })
+    }
+
+    /// Require the start address of every non-empty buffer to be a multiple of `alignment`.
+    pub fn with_buffer_alignment(self, alignment: usize) -> Result<Self, Error> {
+        if !alignment.is_power_of_two() {
+            return Err(Error::InvalidAlignment);
+        }
+        Ok(MemoryRegions {
+            buffer_alignment: alignment,
+            ..self
+        })
+    }
+
+    /// Check whether the HSM may access `size` bytes at `data` on behalf of `client_id`.
+    /// `writable` is set if the HSM writes to the memory.
+    pub fn allows(
+        &self,
+        client_id: ClientId,
+        data: *const u8,
+        size: usize,
+        writable: bool,
+    ) -> bool {
+        if size == 0 {
+            return true;
+        }
+        let start = data as usize;
+        if start % self.buffer_alignment != 0 {
+            return false;
+        }
+        let Some(end) = start.checked_add(size) else {
+            return false;
+        };
+        self.regions.iter().any(|region| {
+            region.client_id == client_id
+                && (region.access == Access::ReadWrite || !writable)
+                && region.start <= start
+                && end <= region.start + region.size
+        })
+    }
+
+    /// Check whether all buffers of `request` lie within the regions of `client_id`.
+    pub fn allows_request(&self, client_id: ClientId, request: &Request) -> bool {
+        request.buffers().iter().all(|(buffer, writable)| {
+            self.allows(client_id, buffer.as_ptr(), buffer.len(), *writable)
+        })
+    }
+
+    /// Validator for the raw requests of `client_id`.
+    pub fn client(&self, client_id: ClientId) -> ClientMemory<'a> {
+        ClientMemory {
+            regions: *self,
+            client_id,
+        }
+    }
+}
+
+/// Memory regions of a single client. Used to verify the raw requests of the client, see
+/// [RequestRaw::verify](crate::integration::raw_jobs::RequestRaw::verify).
+#[derive(Copy, Clone, Debug)]
+pub struct ClientMemory<'a> {
+    regions: MemoryRegions<'a>,
+    client_id: ClientId,
+}
+
+impl Validator for ClientMemory<'_> {
+    fn is_valid(&self, data: *const u8, size: u32, writable: bool) -> bool {
+        self.regions
+            .allows(self.client_id, data, size as usize, writable)
+    }
+}
@@ -1,3 +1,6 @@
+pub mod cancellations;
 pub mod core;
 pub mod keystore;
+pub mod memory;
+pub mod she;
 pub mod workers;
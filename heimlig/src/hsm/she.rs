@@ -0,0 +1,756 @@
+use crate::common::jobs;
+use crate::crypto::aes::KEY128_SIZE;
+use crate::crypto::she::{
+    calculate_m4_m5, decrypt_m2, parse_m1, verify_m3, M1_SIZE, M2_SIZE, M3_SIZE, M4_SIZE, M5_SIZE,
+    UID_SIZE,
+};
+use crate::hsm::keystore::{self, InsecureKeyStore, KeyId, KeyType};
+use displaydoc::Display;
+use zeroize::Zeroizing;
+
+/// Errors defined by the SHE specification. The discriminants are the SHE error codes.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Display)]
+pub enum Error {
+    /// The command sequence was invalid.
+    SequenceError = 0x1,
+    /// The key is not available (e.g. locked by boot or debugger protection).
+    KeyNotAvailable = 0x2,
+    /// The key ID is invalid for this command.
+    KeyInvalid = 0x3,
+    /// The key slot is empty.
+    KeyEmpty = 0x4,
+    /// Secure boot did not finish successfully.
+    NoSecureBoot = 0x5,
+    /// The key slot is write-protected.
+    KeyWriteProtected = 0x6,
+    /// The key update failed (invalid MAC, UID or counter).
+    KeyUpdateError = 0x7,
+    /// The random number generator was not seeded.
+    RngSeed = 0x8,
+    /// Debugging is not allowed.
+    NoDebugging = 0x9,
+    /// The SHE is busy.
+    Busy = 0xA,
+    /// Key storage failed.
+    MemoryFailure = 0xB,
+    /// An unspecified error occurred.
+    GeneralError = 0xC,
+}
+
+impl Error {
+    /// The numeric SHE error code (`ERC_*`).
+    pub const fn code(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Key slots defined by the SHE specification. The discriminants are the SHE key IDs.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SheKeyId {
+    SecretKey = 0x0,
+    MasterEcuKey = 0x1,
+    BootMacKey = 0x2,
+    BootMac = 0x3,
+    Key1 = 0x4,
+    Key2 = 0x5,
+    Key3 = 0x6,
+    Key4 = 0x7,
+    Key5 = 0x8,
+    Key6 = 0x9,
+    Key7 = 0xA,
+    Key8 = 0xB,
+    Key9 = 0xC,
+    Key10 = 0xD,
+    RamKey = 0xE,
+}
+
+/// Number of SHE key slots.
+pub const NUM_SLOTS: usize = SheKeyId::RamKey as usize + 1;
+
+impl SheKeyId {
+    pub const fn idx(&self) -> usize {
+        *self as usize
+    }
+
+    /// Whether this is one of the general purpose slots `KEY_1` to `KEY_10`.
+    pub const fn is_general_purpose(&self) -> bool {
+        let id = *self as u8;
+        id >= SheKeyId::Key1 as u8 && id <= SheKeyId::Key10 as u8
+    }
+
+    /// Whether `auth_id` may authorize a `CMD_LOAD_KEY` for this slot.
+    pub fn can_be_updated_by(&self, auth_id: SheKeyId) -> bool {
+        match self {
+            SheKeyId::SecretKey => false,
+            SheKeyId::MasterEcuKey => auth_id == SheKeyId::MasterEcuKey,
+            SheKeyId::BootMacKey => {
+                matches!(auth_id, SheKeyId::MasterEcuKey | SheKeyId::BootMacKey)
+            }
+            SheKeyId::BootMac => matches!(auth_id, SheKeyId::MasterEcuKey | SheKeyId::BootMacKey),
+            SheKeyId::RamKey => auth_id == SheKeyId::SecretKey || auth_id.is_general_purpose(),
+            _ => auth_id == SheKeyId::MasterEcuKey || auth_id == *self,
+        }
+    }
+}
+
+impl TryFrom<u8> for SheKeyId {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(SheKeyId::SecretKey),
+            0x1 => Ok(SheKeyId::MasterEcuKey),
+            0x2 => Ok(SheKeyId::BootMacKey),
+            0x3 => Ok(SheKeyId::BootMac),
+            0x4 => Ok(SheKeyId::Key1),
+            0x5 => Ok(SheKeyId::Key2),
+            0x6 => Ok(SheKeyId::Key3),
+            0x7 => Ok(SheKeyId::Key4),
+            0x8 => Ok(SheKeyId::Key5),
+            0x9 => Ok(SheKeyId::Key6),
+            0xA => Ok(SheKeyId::Key7),
+            0xB => Ok(SheKeyId::Key8),
+            0xC => Ok(SheKeyId::Key9),
+            0xD => Ok(SheKeyId::Key10),
+            0xE => Ok(SheKeyId::RamKey),
+            _ => Err(Error::KeyInvalid),
+        }
+    }
+}
+
+/// Protection flags of a SHE key slot.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SheKeyFlags {
+    /// The slot cannot be updated anymore.
+    pub write_protection: bool,
+    /// The key is only available after a successful secure boot.
+    pub boot_protection: bool,
+    /// The key is not available while a debugger is attached.
+    pub debugger_protection: bool,
+    /// The key is used for MAC operations instead of encryption.
+    pub key_usage: bool,
+    /// The key can be updated with a wildcard UID.
+    pub wildcard: bool,
+}
+
+impl SheKeyFlags {
+    /// Decode the 5-bit flag field used in M2 (WP, BP, DP, KU, WC from MSB to LSB).
+    pub const fn from_bits(bits: u8) -> Self {
+        SheKeyFlags {
+            write_protection: bits & 0b10000 != 0,
+            boot_protection: bits & 0b01000 != 0,
+            debugger_protection: bits & 0b00100 != 0,
+            key_usage: bits & 0b00010 != 0,
+            wildcard: bits & 0b00001 != 0,
+        }
+    }
+
+    /// Encode the flags into the 5-bit field used in M2.
+    pub const fn to_bits(&self) -> u8 {
+        (self.write_protection as u8) << 4
+            | (self.boot_protection as u8) << 3
+            | (self.debugger_protection as u8) << 2
+            | (self.key_usage as u8) << 1
+            | (self.wildcard as u8)
+    }
+}
+
+/// Metadata SHE keeps next to the key material of a slot.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SheSlotState {
+    pub counter: u32,
+    pub flags: SheKeyFlags,
+}
+
+/// The kind of command a key is used for.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SheKeyUsage {
+    /// Encryption and decryption (`CMD_ENC_*`, `CMD_DEC_*`)
+    Cipher,
+    /// MAC generation and verification (`CMD_GENERATE_MAC`, `CMD_VERIFY_MAC`)
+    Mac,
+}
+
+/// Progress of the secure boot process as reported by `CMD_BOOT_OK` and `CMD_BOOT_FAILURE`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SheBootStatus {
+    #[default]
+    InProgress,
+    Ok,
+    Failed,
+}
+
+/// SHE key slot model on top of a Heimlig key store.
+///
+/// Every SHE slot is backed by a 128-bit symmetric key in the key store, so the keys can be used by
+/// the regular AES workers. Counters and protection flags are kept here. Workers have to call
+/// [SheKeySlots::check_key_usage] before they use a key, so that boot protection, debugger
+/// protection and the key usage flag of the slots are honoured. Slot states are handed to the key
+/// store with every key update. Key stores that persist them allow restoring the states after a
+/// reset with `restore_slot_states()`.
+#[derive(Clone, Debug)]
+pub struct SheKeySlots {
+    uid: [u8; UID_SIZE],
+    key_ids: [KeyId; NUM_SLOTS],
+    states: [SheSlotState; NUM_SLOTS],
+    boot_status: SheBootStatus,
+    debugger_attached: bool,
+}
+
+impl SheKeySlots {
+    /// Create a new slot model.
+    ///
+    /// # Arguments
+    ///
+    /// * `uid`: The unique identifier of this device.
+    /// * `key_ids`: The key store IDs backing the SHE slots, indexed by `SheKeyId`.
+    pub fn new(uid: [u8; UID_SIZE], key_ids: [KeyId; NUM_SLOTS]) -> Self {
+        SheKeySlots {
+            uid,
+            key_ids,
+            states: Default::default(),
+            boot_status: SheBootStatus::default(),
+            debugger_attached: false,
+        }
+    }
+
+    pub fn uid(&self) -> &[u8; UID_SIZE] {
+        &self.uid
+    }
+
+    /// The key store ID backing a SHE slot.
+    pub fn key_id(&self, slot: SheKeyId) -> KeyId {
+        self.key_ids[slot.idx()]
+    }
+
+    pub fn slot_state(&self, slot: SheKeyId) -> SheSlotState {
+        self.states[slot.idx()]
+    }
+
+    pub fn restore_slot_state(&mut self, slot: SheKeyId, state: SheSlotState) {
+        self.states[slot.idx()] = state;
+    }
+
+    /// Restore the states of all slots from the key store. Slots without a stored state keep
+    /// their current state.
+    pub fn restore_slot_states<KeyStore: InsecureKeyStore + ?Sized>(
+        &mut self,
+        key_store: &KeyStore,
+    ) {
+        for (state, key_id) in self.states.iter_mut().zip(self.key_ids) {
+            if let Some(stored) = key_store.she_slot_state(key_id) {
+                *state = stored;
+            }
+        }
+    }
+
+    pub fn boot_status(&self) -> SheBootStatus {
+        self.boot_status
+    }
+
+    /// Finish the secure boot process (`CMD_BOOT_OK` or `CMD_BOOT_FAILURE`).
+    ///
+    /// The result can only be reported once. After a failed boot, boot-protected slots are locked.
+    pub fn finish_boot(&mut self, success: bool) -> Result<(), Error> {
+        if self.boot_status != SheBootStatus::InProgress {
+            return Err(Error::SequenceError);
+        }
+        self.boot_status = if success {
+            SheBootStatus::Ok
+        } else {
+            SheBootStatus::Failed
+        };
+        Ok(())
+    }
+
+    /// Report whether a debugger is attached. Keys of debugger-protected slots are not available
+    /// while it is.
+    pub fn set_debugger_attached(&mut self, attached: bool) {
+        self.debugger_attached = attached;
+    }
+
+    /// Check whether the key with the key store ID `key_id` may be used for `usage`.
+    ///
+    /// Keys that do not back a SHE slot are not restricted. Only `KEY_1` to `KEY_10` and `RAM_KEY`
+    /// can be used for cipher and MAC commands. Keys of general purpose slots with the key usage
+    /// flag can only be used for MACs, those without it only for ciphers. `RAM_KEY` can be used for
+    /// both. Boot-protected keys are not available after a failed boot and debugger-protected keys
+    /// are not available while a debugger is attached.
+    pub fn check_key_usage(&self, key_id: KeyId, usage: SheKeyUsage) -> Result<(), Error> {
+        let Some(slot) = self.slot(key_id) else {
+            return Ok(());
+        };
+        if !slot.is_general_purpose() && slot != SheKeyId::RamKey {
+            return Err(Error::KeyInvalid);
+        }
+        let flags = self.states[slot.idx()].flags;
+        if self.is_locked(slot) || (self.debugger_attached && flags.debugger_protection) {
+            return Err(Error::KeyNotAvailable);
+        }
+        if slot != SheKeyId::RamKey && flags.key_usage != (usage == SheKeyUsage::Mac) {
+            return Err(Error::KeyInvalid);
+        }
+        Ok(())
+    }
+
+    /// The SHE slot backed by the key store ID `key_id`.
+    fn slot(&self, key_id: KeyId) -> Option<SheKeyId> {
+        let idx = self.key_ids.iter().position(|id| *id == key_id)?;
+        SheKeyId::try_from(idx as u8).ok()
+    }
+
+    /// Perform the `CMD_LOAD_KEY` memory update protocol.
+    ///
+    /// Verifies M1 to M3, stores the new key in the key store and writes the proof messages M4 and
+    /// M5. The slot counter has to increase with every update (except for `RAM_KEY`) and
+    /// write-protected slots are rejected. Boot-protected slots are locked after a failed boot.
+    pub fn load_key<KeyStore: InsecureKeyStore + ?Sized>(
+        &mut self,
+        key_store: &mut KeyStore,
+        m1: &[u8; M1_SIZE],
+        m2: &[u8; M2_SIZE],
+        m3: &[u8; M3_SIZE],
+        m4: &mut [u8; M4_SIZE],
+        m5: &mut [u8; M5_SIZE],
+    ) -> Result<(), Error> {
+        let (uid, id, auth_id) = parse_m1(m1);
+        let id = SheKeyId::try_from(id)?;
+        let auth_id = SheKeyId::try_from(auth_id)?;
+        if !id.can_be_updated_by(auth_id) {
+            return Err(Error::KeyInvalid);
+        }
+        if self.is_locked(id) || self.is_locked(auth_id) {
+            return Err(Error::KeyNotAvailable);
+        }
+
+        // Authenticate the update
+        let mut auth_key = Zeroizing::new([0u8; KEY128_SIZE]);
+        let auth_key = self.export_key(key_store, auth_id, auth_key.as_mut_slice())?;
+        if !verify_m3(auth_key, m1, m2, m3).map_err(|_| Error::GeneralError)? {
+            return Err(Error::KeyUpdateError);
+        }
+        let payload = decrypt_m2(auth_key, m2).map_err(|_| Error::GeneralError)?;
+
+        // Check UID, protection flags and counter of the slot to be updated
+        let state = self.states[id.idx()];
+        let is_wildcard = uid.iter().all(|byte| *byte == 0);
+        if uid != self.uid && !(is_wildcard && state.flags.wildcard) {
+            return Err(Error::KeyUpdateError);
+        }
+        let key_id = self.key_ids[id.idx()];
+        let key_exists = key_store.is_key_available(key_id);
+        if key_exists && state.flags.write_protection {
+            return Err(Error::KeyWriteProtected);
+        }
+        if id != SheKeyId::RamKey && key_exists && payload.counter <= state.counter {
+            return Err(Error::KeyUpdateError);
+        }
+
+        let new_state = if id == SheKeyId::RamKey {
+            SheSlotState::default()
+        } else {
+            SheSlotState {
+                counter: payload.counter,
+                flags: SheKeyFlags::from_bits(payload.flags),
+            }
+        };
+        // Store the state first, so that a reset in between cannot roll back the counter
+        key_store.store_she_slot_state(key_id, new_state)?;
+        key_store.import_symmetric_key_insecure(key_id, payload.key.as_slice())?;
+        self.states[id.idx()] = new_state;
+
+        calculate_m4_m5(payload.key.as_slice(), m1, payload.counter, m4, m5)
+            .map_err(|_| Error::GeneralError)
+    }
+
+    fn is_locked(&self, slot: SheKeyId) -> bool {
+        self.boot_status == SheBootStatus::Failed && self.states[slot.idx()].flags.boot_protection
+    }
+
+    fn export_key<'a, KeyStore: InsecureKeyStore + ?Sized>(
+        &self,
+        key_store: &KeyStore,
+        slot: SheKeyId,
+        dest: &'a mut [u8],
+    ) -> Result<&'a [u8], Error> {
+        let key_id = self.key_ids[slot.idx()];
+        if key_store.get_key_info(key_id)?.ty != KeyType::Symmetric(KEY128_SIZE) {
+            return Err(Error::KeyInvalid);
+        }
+        Ok(key_store.export_symmetric_key_insecure(key_id, dest)?)
+    }
+}
+
+impl From<keystore::Error> for Error {
+    fn from(value: keystore::Error) -> Self {
+        match value {
+            keystore::Error::KeyNotFound => Error::KeyEmpty,
+            keystore::Error::InvalidKeyId | keystore::Error::InvalidKeyType => Error::KeyInvalid,
+            keystore::Error::NotAllowed => Error::KeyNotAvailable,
+            _ => Error::MemoryFailure,
+        }
+    }
+}
+
+impl From<jobs::Error> for Error {
+    fn from(value: jobs::Error) -> Self {
+        match value {
+            jobs::Error::She(e) => e,
+            jobs::Error::KeyStore(e) => e.into(),
+            jobs::Error::NoKeyStore => Error::MemoryFailure,
+            jobs::Error::LimitExceeded(_) => Error::Busy,
+            _ => Error::GeneralError,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::she::{calculate_m1, calculate_m2, calculate_m3};
+    use crate::hsm::keystore::{KeyInfo, KeyPermissions};
+    use crate::integration::memory_key_store::MemoryKeyStore;
+
+    const UID: [u8; UID_SIZE] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    const MASTER_ECU_KEY: [u8; KEY128_SIZE] = [1u8; KEY128_SIZE];
+    const KEY_INFOS: [KeyInfo; NUM_SLOTS] = {
+        let mut key_infos = [KeyInfo {
+            id: KeyId(0),
+            ty: KeyType::Symmetric(KEY128_SIZE),
+            permissions: KeyPermissions {
+                import: false,
+                export_private: false,
+                overwrite: false,
+                delete: false,
+            },
+        }; NUM_SLOTS];
+        let mut i = 0;
+        while i < NUM_SLOTS {
+            key_infos[i].id = KeyId(100 + i as u32);
+            i += 1;
+        }
+        key_infos
+    };
+
+    type KeyStore = MemoryKeyStore<{ NUM_SLOTS * KEY128_SIZE }, NUM_SLOTS>;
+
+    fn init() -> (SheKeySlots, KeyStore) {
+        let mut key_store = KeyStore::try_new(&KEY_INFOS).expect("failed to create key store");
+        key_store
+            .import_symmetric_key_insecure(KeyId(101), &MASTER_ECU_KEY)
+            .expect("failed to import master ECU key");
+        let key_ids = core::array::from_fn(|i| KeyId(100 + i as u32));
+        (SheKeySlots::new(UID, key_ids), key_store)
+    }
+
+    fn update_messages(
+        uid: &[u8],
+        id: SheKeyId,
+        auth_id: SheKeyId,
+        auth_key: &[u8],
+        counter: u32,
+        flags: SheKeyFlags,
+        new_key: &[u8],
+    ) -> ([u8; M1_SIZE], [u8; M2_SIZE], [u8; M3_SIZE]) {
+        let mut m1 = [0u8; M1_SIZE];
+        let mut m2 = [0u8; M2_SIZE];
+        let mut m3 = [0u8; M3_SIZE];
+        calculate_m1(uid, id as u8, auth_id as u8, &mut m1).expect("failed to calculate M1");
+        calculate_m2(auth_key, counter, flags.to_bits(), new_key, &mut m2)
+            .expect("failed to calculate M2");
+        calculate_m3(auth_key, &m1, &m2, &mut m3).expect("failed to calculate M3");
+        (m1, m2, m3)
+    }
+
+    fn load(
+        slots: &mut SheKeySlots,
+        key_store: &mut KeyStore,
+        (m1, m2, m3): ([u8; M1_SIZE], [u8; M2_SIZE], [u8; M3_SIZE]),
+    ) -> Result<(), Error> {
+        slots.load_key(
+            key_store,
+            &m1,
+            &m2,
+            &m3,
+            &mut [0; M4_SIZE],
+            &mut [0; M5_SIZE],
+        )
+    }
+
+    #[test]
+    fn flags_round_trip() {
+        for bits in 0..32 {
+            assert_eq!(SheKeyFlags::from_bits(bits).to_bits(), bits);
+        }
+    }
+
+    #[test]
+    fn load_key_and_counter() {
+        let (mut slots, mut key_store) = init();
+        let new_key = [2u8; KEY128_SIZE];
+        let flags = SheKeyFlags::default();
+        let messages = update_messages(
+            &UID,
+            SheKeyId::Key1,
+            SheKeyId::MasterEcuKey,
+            &MASTER_ECU_KEY,
+            1,
+            flags,
+            &new_key,
+        );
+        assert_eq!(load(&mut slots, &mut key_store, messages), Ok(()));
+        assert_eq!(slots.slot_state(SheKeyId::Key1).counter, 1);
+        let mut dest = [0u8; KEY128_SIZE];
+        key_store
+            .export_symmetric_key_insecure(slots.key_id(SheKeyId::Key1), &mut dest)
+            .expect("failed to export key");
+        assert_eq!(dest, new_key);
+
+        // Replay is rejected
+        assert_eq!(
+            load(&mut slots, &mut key_store, messages),
+            Err(Error::KeyUpdateError)
+        );
+
+        // The key can authorize its own update
+        let messages = update_messages(
+            &UID,
+            SheKeyId::Key1,
+            SheKeyId::Key1,
+            &new_key,
+            2,
+            flags,
+            &[3u8; KEY128_SIZE],
+        );
+        assert_eq!(load(&mut slots, &mut key_store, messages), Ok(()));
+        assert_eq!(slots.slot_state(SheKeyId::Key1).counter, 2);
+    }
+
+    #[test]
+    fn load_key_errors() {
+        let (mut slots, mut key_store) = init();
+        let new_key = [2u8; KEY128_SIZE];
+        let flags = SheKeyFlags::default();
+
+        // Wrong UID
+        let mut other_uid = UID;
+        other_uid[0] = 0xFF;
+        let messages = update_messages(
+            &other_uid,
+            SheKeyId::Key1,
+            SheKeyId::MasterEcuKey,
+            &MASTER_ECU_KEY,
+            1,
+            flags,
+            &new_key,
+        );
+        assert_eq!(
+            load(&mut slots, &mut key_store, messages),
+            Err(Error::KeyUpdateError)
+        );
+
+        // Wildcard UID without wildcard flag
+        let messages = update_messages(
+            &[0u8; UID_SIZE],
+            SheKeyId::Key1,
+            SheKeyId::MasterEcuKey,
+            &MASTER_ECU_KEY,
+            1,
+            flags,
+            &new_key,
+        );
+        assert_eq!(
+            load(&mut slots, &mut key_store, messages),
+            Err(Error::KeyUpdateError)
+        );
+
+        // Wrong authorizing key
+        let messages = update_messages(
+            &UID,
+            SheKeyId::Key1,
+            SheKeyId::MasterEcuKey,
+            &new_key,
+            1,
+            flags,
+            &new_key,
+        );
+        assert_eq!(
+            load(&mut slots, &mut key_store, messages),
+            Err(Error::KeyUpdateError)
+        );
+
+        // Empty authorizing key
+        let messages = update_messages(
+            &UID,
+            SheKeyId::Key2,
+            SheKeyId::Key2,
+            &new_key,
+            1,
+            flags,
+            &new_key,
+        );
+        assert_eq!(
+            load(&mut slots, &mut key_store, messages),
+            Err(Error::KeyEmpty)
+        );
+
+        // Authorization not allowed for slot
+        let messages = update_messages(
+            &UID,
+            SheKeyId::Key2,
+            SheKeyId::Key1,
+            &new_key,
+            1,
+            flags,
+            &new_key,
+        );
+        assert_eq!(
+            load(&mut slots, &mut key_store, messages),
+            Err(Error::KeyInvalid)
+        );
+    }
+
+    #[test]
+    fn write_protection_and_wildcard() {
+        let (mut slots, mut key_store) = init();
+        let new_key = [2u8; KEY128_SIZE];
+        let flags = SheKeyFlags {
+            write_protection: true,
+            wildcard: true,
+            ..Default::default()
+        };
+        let messages = update_messages(
+            &UID,
+            SheKeyId::Key3,
+            SheKeyId::MasterEcuKey,
+            &MASTER_ECU_KEY,
+            1,
+            flags,
+            &new_key,
+        );
+        assert_eq!(load(&mut slots, &mut key_store, messages), Ok(()));
+        assert_eq!(slots.slot_state(SheKeyId::Key3).flags, flags);
+
+        // Wildcard UID is accepted, but the slot is write-protected now
+        let messages = update_messages(
+            &[0u8; UID_SIZE],
+            SheKeyId::Key3,
+            SheKeyId::MasterEcuKey,
+            &MASTER_ECU_KEY,
+            2,
+            SheKeyFlags::default(),
+            &new_key,
+        );
+        assert_eq!(
+            load(&mut slots, &mut key_store, messages),
+            Err(Error::KeyWriteProtected)
+        );
+    }
+
+    #[test]
+    fn key_usage() {
+        let (mut slots, mut key_store) = init();
+        let mac_flags = SheKeyFlags {
+            key_usage: true,
+            debugger_protection: true,
+            ..Default::default()
+        };
+        let messages = update_messages(
+            &UID,
+            SheKeyId::Key5,
+            SheKeyId::MasterEcuKey,
+            &MASTER_ECU_KEY,
+            1,
+            mac_flags,
+            &[2u8; KEY128_SIZE],
+        );
+        assert_eq!(load(&mut slots, &mut key_store, messages), Ok(()));
+        let mac_key = slots.key_id(SheKeyId::Key5);
+        let cipher_key = slots.key_id(SheKeyId::Key6);
+        let ram_key = slots.key_id(SheKeyId::RamKey);
+
+        assert_eq!(slots.check_key_usage(mac_key, SheKeyUsage::Mac), Ok(()));
+        assert_eq!(
+            slots.check_key_usage(mac_key, SheKeyUsage::Cipher),
+            Err(Error::KeyInvalid)
+        );
+        assert_eq!(
+            slots.check_key_usage(cipher_key, SheKeyUsage::Cipher),
+            Ok(())
+        );
+        assert_eq!(
+            slots.check_key_usage(cipher_key, SheKeyUsage::Mac),
+            Err(Error::KeyInvalid)
+        );
+        assert_eq!(slots.check_key_usage(ram_key, SheKeyUsage::Cipher), Ok(()));
+        assert_eq!(slots.check_key_usage(ram_key, SheKeyUsage::Mac), Ok(()));
+        assert_eq!(
+            slots.check_key_usage(slots.key_id(SheKeyId::MasterEcuKey), SheKeyUsage::Cipher),
+            Err(Error::KeyInvalid)
+        );
+        // Keys outside of the SHE slots are not restricted
+        assert_eq!(slots.check_key_usage(KeyId(0), SheKeyUsage::Mac), Ok(()));
+
+        // Debugger-protected keys are not available while a debugger is attached
+        slots.set_debugger_attached(true);
+        assert_eq!(
+            slots.check_key_usage(mac_key, SheKeyUsage::Mac),
+            Err(Error::KeyNotAvailable)
+        );
+        assert_eq!(
+            slots.check_key_usage(cipher_key, SheKeyUsage::Cipher),
+            Ok(())
+        );
+        slots.set_debugger_attached(false);
+        assert_eq!(slots.check_key_usage(mac_key, SheKeyUsage::Mac), Ok(()));
+    }
+
+    #[test]
+    fn boot_protection() {
+        let (mut slots, mut key_store) = init();
+        let new_key = [2u8; KEY128_SIZE];
+        let flags = SheKeyFlags {
+            boot_protection: true,
+            ..Default::default()
+        };
+        let messages = update_messages(
+            &UID,
+            SheKeyId::Key4,
+            SheKeyId::MasterEcuKey,
+            &MASTER_ECU_KEY,
+            1,
+            flags,
+            &new_key,
+        );
+        assert_eq!(load(&mut slots, &mut key_store, messages), Ok(()));
+        let key_id = slots.key_id(SheKeyId::Key4);
+        assert_eq!(slots.check_key_usage(key_id, SheKeyUsage::Cipher), Ok(()));
+
+        // Boot result can only be reported once
+        assert_eq!(slots.boot_status(), SheBootStatus::InProgress);
+        assert_eq!(slots.finish_boot(false), Ok(()));
+        assert_eq!(slots.boot_status(), SheBootStatus::Failed);
+        assert_eq!(slots.finish_boot(true), Err(Error::SequenceError));
+
+        // Boot-protected slot is locked after a failed boot
+        assert_eq!(
+            slots.check_key_usage(key_id, SheKeyUsage::Cipher),
+            Err(Error::KeyNotAvailable)
+        );
+        let messages = update_messages(
+            &UID,
+            SheKeyId::Key4,
+            SheKeyId::MasterEcuKey,
+            &MASTER_ECU_KEY,
+            2,
+            flags,
+            &new_key,
+        );
+        assert_eq!(
+            load(&mut slots, &mut key_store, messages),
+            Err(Error::KeyNotAvailable)
+        );
+    }
+}
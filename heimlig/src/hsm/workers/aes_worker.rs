@@ -1,5 +1,6 @@
+use crate::common::sync::{Mutex, RawMutex};
 use crate::{
-    common::jobs::{ClientId, Error, Request, RequestId, Response},
+    common::jobs::{ClientId, Error, Padding, Request, RequestId, RequestType, Response},
     crypto::{
         self,
         aes::{
@@ -11,6 +12,10 @@ use crate::{
                 aes128_cmac_calculate, aes128_cmac_verify, aes192_cmac_calculate,
                 aes192_cmac_verify, aes256_cmac_calculate, aes256_cmac_verify,
             },
+            ecb::{
+                aes128ecb_decrypt, aes128ecb_encrypt, aes192ecb_decrypt, aes192ecb_encrypt,
+                aes256ecb_decrypt, aes256ecb_encrypt,
+            },
             gcm::{
                 aes128gcm_decrypt_in_place_detached, aes128gcm_encrypt_in_place_detached,
                 aes256gcm_decrypt_in_place_detached, aes256gcm_encrypt_in_place_detached,
@@ -19,185 +24,50 @@ use crate::{
         },
     },
     hsm::keystore::{self, KeyId, KeyInfo, KeyType},
+    hsm::she::{SheKeySlots, SheKeyUsage},
+    hsm::workers::Worker,
 };
-use cbc::cipher::block_padding::Pkcs7;
-use embassy_sync::{blocking_mutex::raw::RawMutex, mutex::Mutex};
-use futures::{Sink, SinkExt, Stream, StreamExt};
+use cbc::cipher::block_padding::{NoPadding, Pkcs7};
 use zeroize::Zeroizing;
 
+/// Request types handled by the worker
+const REQUEST_TYPES: &[RequestType] = &[
+    RequestType::EncryptAesGcm,
+    RequestType::EncryptAesGcmExternalKey,
+    RequestType::DecryptAesGcm,
+    RequestType::DecryptAesGcmExternalKey,
+    RequestType::EncryptAesCbc,
+    RequestType::EncryptAesCbcExternalKey,
+    RequestType::DecryptAesCbc,
+    RequestType::DecryptAesCbcExternalKey,
+    RequestType::EncryptAesEcb,
+    RequestType::EncryptAesEcbExternalKey,
+    RequestType::DecryptAesEcb,
+    RequestType::DecryptAesEcbExternalKey,
+    RequestType::CalculateAesCmac,
+    RequestType::CalculateAesCmacExternalKey,
+    RequestType::VerifyAesCmac,
+    RequestType::VerifyAesCmacExternalKey,
+];
+
 pub struct AesWorker<
-    'data,
     'keystore,
     M: RawMutex,
-    ReqSrc: Stream<Item = Request<'data>>,
-    RespSink: Sink<Response<'data>>,
     KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
 > {
     pub key_store: &'keystore Mutex<M, &'keystore mut KeyStore>,
-    pub requests: ReqSrc,
-    pub responses: RespSink,
+    /// SHE slot model whose protection flags are checked before the key of a SHE slot is used.
+    /// Has to be set if the key store contains the keys of SHE slots.
+    pub she_slots: Option<&'keystore Mutex<M, SheKeySlots>>,
 }
 
 impl<
         'data,
-        'rng,
         'keystore,
         M: RawMutex,
-        ReqSrc: Stream<Item = Request<'data>> + Unpin,
-        RespSink: Sink<Response<'data>> + Unpin,
         KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
-    > AesWorker<'data, 'keystore, M, ReqSrc, RespSink, KeyStore>
+    > AesWorker<'keystore, M, KeyStore>
 {
-    /// Drive the worker to process the next request.
-    /// This method is supposed to be called by a system task that owns this worker.
-    pub async fn execute(&mut self) -> Result<(), Error> {
-        let request = self.requests.next().await.ok_or(Error::StreamTerminated)?;
-        let response = match request {
-            Request::EncryptAesGcm {
-                client_id,
-                request_id,
-                key_id,
-                iv,
-                buffer,
-                aad,
-                tag,
-            } => {
-                self.encrypt_aes_gcm(client_id, request_id, key_id, iv, buffer, aad, tag)
-                    .await
-            }
-            Request::EncryptAesGcmExternalKey {
-                client_id,
-                request_id,
-                key,
-                iv,
-                buffer,
-                aad,
-                tag,
-            } => {
-                self.encrypt_aes_gcm_external_key(client_id, request_id, key, iv, buffer, aad, tag)
-                    .await
-            }
-            Request::DecryptAesGcm {
-                client_id,
-                request_id,
-                key_id,
-                iv,
-                buffer,
-                aad,
-                tag,
-            } => {
-                self.decrypt_aes_gcm(client_id, request_id, key_id, iv, buffer, aad, tag)
-                    .await
-            }
-            Request::DecryptAesGcmExternalKey {
-                client_id,
-                request_id,
-                key,
-                iv,
-                buffer,
-                aad,
-                tag,
-            } => {
-                self.decrypt_aes_gcm_external_key(client_id, request_id, key, iv, buffer, aad, tag)
-                    .await
-            }
-            Request::EncryptAesCbc {
-                client_id,
-                request_id,
-                key_id,
-                iv,
-                buffer,
-                plaintext_size,
-            } => {
-                self.encrypt_aes_cbc(client_id, request_id, key_id, iv, buffer, plaintext_size)
-                    .await
-            }
-            Request::EncryptAesCbcExternalKey {
-                client_id,
-                request_id,
-                key,
-                iv,
-                buffer,
-                plaintext_size,
-            } => {
-                self.encrypt_aes_cbc_external_key(
-                    client_id,
-                    request_id,
-                    key,
-                    iv,
-                    buffer,
-                    plaintext_size,
-                )
-                .await
-            }
-            Request::DecryptAesCbc {
-                client_id,
-                request_id,
-                key_id,
-                iv,
-                buffer,
-            } => {
-                self.decrypt_aes_cbc(client_id, request_id, key_id, iv, buffer)
-                    .await
-            }
-            Request::DecryptAesCbcExternalKey {
-                client_id,
-                request_id,
-                key,
-                iv,
-                buffer,
-            } => {
-                self.decrypt_aes_cbc_external_key(client_id, request_id, key, iv, buffer)
-                    .await
-            }
-            Request::CalculateAesCmac {
-                client_id,
-                request_id,
-                key_id,
-                message,
-                tag,
-            } => {
-                self.calculate_aes_cmac(client_id, request_id, key_id, message, tag)
-                    .await
-            }
-            Request::CalculateAesCmacExternalKey {
-                client_id,
-                request_id,
-                key,
-                message,
-                tag,
-            } => {
-                self.calculate_aes_cmac_external_key(client_id, request_id, key, message, tag)
-                    .await
-            }
-            Request::VerifyAesCmac {
-                client_id,
-                request_id,
-                key_id,
-                message,
-                tag,
-            } => {
-                self.verify_aes_cmac(client_id, request_id, key_id, message, tag)
-                    .await
-            }
-            Request::VerifyAesCmacExternalKey {
-                client_id,
-                request_id,
-                key,
-                message,
-                tag,
-            } => {
-                self.verify_aes_cmac_external_key(client_id, request_id, key, message, tag)
-                    .await
-            }
-            _ => Err(Error::UnexpectedRequestType)?,
-        };
-        self.responses
-            .send(response)
-            .await
-            .map_err(|_e| Error::Send)
-    }
-
     #[allow(clippy::too_many_arguments)]
     async fn encrypt_aes_gcm(
         &mut self,
@@ -378,6 +248,7 @@ impl<
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn encrypt_aes_cbc(
         &mut self,
         client_id: ClientId,
@@ -386,6 +257,7 @@ impl<
         iv: &[u8],
         buffer: &'data mut [u8],
         plaintext_size: usize,
+        padding: Padding,
     ) -> Response<'data> {
         let mut key_buffer = Zeroizing::new([0u8; KeyType::MAX_SYMMETRIC_KEY_SIZE]);
         let key_and_info = self
@@ -400,15 +272,24 @@ impl<
                 }
             }
             Ok((key, key_info)) => match key_info.ty {
-                KeyType::Symmetric(16) => {
-                    aes128cbc_encrypt::<Pkcs7>(key, iv, buffer, plaintext_size)
-                }
-                KeyType::Symmetric(24) => {
-                    aes192cbc_encrypt::<Pkcs7>(key, iv, buffer, plaintext_size)
-                }
-                KeyType::Symmetric(32) => {
-                    aes256cbc_encrypt::<Pkcs7>(key, iv, buffer, plaintext_size)
-                }
+                KeyType::Symmetric(16) => match padding {
+                    Padding::Pkcs7 => aes128cbc_encrypt::<Pkcs7>(key, iv, buffer, plaintext_size),
+                    Padding::NoPadding => {
+                        aes128cbc_encrypt::<NoPadding>(key, iv, buffer, plaintext_size)
+                    }
+                },
+                KeyType::Symmetric(24) => match padding {
+                    Padding::Pkcs7 => aes192cbc_encrypt::<Pkcs7>(key, iv, buffer, plaintext_size),
+                    Padding::NoPadding => {
+                        aes192cbc_encrypt::<NoPadding>(key, iv, buffer, plaintext_size)
+                    }
+                },
+                KeyType::Symmetric(32) => match padding {
+                    Padding::Pkcs7 => aes256cbc_encrypt::<Pkcs7>(key, iv, buffer, plaintext_size),
+                    Padding::NoPadding => {
+                        aes256cbc_encrypt::<NoPadding>(key, iv, buffer, plaintext_size)
+                    }
+                },
                 _ => {
                     return Response::Error {
                         client_id,
@@ -435,6 +316,7 @@ impl<
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn encrypt_aes_cbc_external_key(
         &mut self,
         client_id: ClientId,
@@ -443,11 +325,27 @@ impl<
         iv: &[u8],
         buffer: &'data mut [u8],
         plaintext_size: usize,
+        padding: Padding,
     ) -> Response<'data> {
         let result = match key.len() {
-            KEY128_SIZE => aes128cbc_encrypt::<Pkcs7>(key, iv, buffer, plaintext_size),
-            KEY192_SIZE => aes192cbc_encrypt::<Pkcs7>(key, iv, buffer, plaintext_size),
-            KEY256_SIZE => aes256cbc_encrypt::<Pkcs7>(key, iv, buffer, plaintext_size),
+            KEY128_SIZE => match padding {
+                Padding::Pkcs7 => aes128cbc_encrypt::<Pkcs7>(key, iv, buffer, plaintext_size),
+                Padding::NoPadding => {
+                    aes128cbc_encrypt::<NoPadding>(key, iv, buffer, plaintext_size)
+                }
+            },
+            KEY192_SIZE => match padding {
+                Padding::Pkcs7 => aes192cbc_encrypt::<Pkcs7>(key, iv, buffer, plaintext_size),
+                Padding::NoPadding => {
+                    aes192cbc_encrypt::<NoPadding>(key, iv, buffer, plaintext_size)
+                }
+            },
+            KEY256_SIZE => match padding {
+                Padding::Pkcs7 => aes256cbc_encrypt::<Pkcs7>(key, iv, buffer, plaintext_size),
+                Padding::NoPadding => {
+                    aes256cbc_encrypt::<NoPadding>(key, iv, buffer, plaintext_size)
+                }
+            },
             _ => {
                 return Response::Error {
                     client_id,
@@ -480,6 +378,7 @@ impl<
         key_id: KeyId,
         iv: &[u8],
         buffer: &'data mut [u8],
+        padding: Padding,
     ) -> Response<'data> {
         let mut key_buffer = Zeroizing::new([0u8; KeyType::MAX_SYMMETRIC_KEY_SIZE]);
         let key_and_info = self
@@ -494,9 +393,18 @@ impl<
                 }
             }
             Ok((key, key_info)) => match key_info.ty {
-                KeyType::Symmetric(16) => aes128cbc_decrypt::<Pkcs7>(key, iv, buffer),
-                KeyType::Symmetric(24) => aes192cbc_decrypt::<Pkcs7>(key, iv, buffer),
-                KeyType::Symmetric(32) => aes256cbc_decrypt::<Pkcs7>(key, iv, buffer),
+                KeyType::Symmetric(16) => match padding {
+                    Padding::Pkcs7 => aes128cbc_decrypt::<Pkcs7>(key, iv, buffer),
+                    Padding::NoPadding => aes128cbc_decrypt::<NoPadding>(key, iv, buffer),
+                },
+                KeyType::Symmetric(24) => match padding {
+                    Padding::Pkcs7 => aes192cbc_decrypt::<Pkcs7>(key, iv, buffer),
+                    Padding::NoPadding => aes192cbc_decrypt::<NoPadding>(key, iv, buffer),
+                },
+                KeyType::Symmetric(32) => match padding {
+                    Padding::Pkcs7 => aes256cbc_decrypt::<Pkcs7>(key, iv, buffer),
+                    Padding::NoPadding => aes256cbc_decrypt::<NoPadding>(key, iv, buffer),
+                },
                 _ => {
                     return Response::Error {
                         client_id,
@@ -530,11 +438,21 @@ impl<
         key: &[u8],
         iv: &[u8],
         buffer: &'data mut [u8],
+        padding: Padding,
     ) -> Response<'data> {
         let result = match key.len() {
-            KEY128_SIZE => aes128cbc_decrypt::<Pkcs7>(key, iv, buffer),
-            KEY192_SIZE => aes192cbc_decrypt::<Pkcs7>(key, iv, buffer),
-            KEY256_SIZE => aes256cbc_decrypt::<Pkcs7>(key, iv, buffer),
+            KEY128_SIZE => match padding {
+                Padding::Pkcs7 => aes128cbc_decrypt::<Pkcs7>(key, iv, buffer),
+                Padding::NoPadding => aes128cbc_decrypt::<NoPadding>(key, iv, buffer),
+            },
+            KEY192_SIZE => match padding {
+                Padding::Pkcs7 => aes192cbc_decrypt::<Pkcs7>(key, iv, buffer),
+                Padding::NoPadding => aes192cbc_decrypt::<NoPadding>(key, iv, buffer),
+            },
+            KEY256_SIZE => match padding {
+                Padding::Pkcs7 => aes256cbc_decrypt::<Pkcs7>(key, iv, buffer),
+                Padding::NoPadding => aes256cbc_decrypt::<NoPadding>(key, iv, buffer),
+            },
             _ => {
                 return Response::Error {
                     client_id,
@@ -560,6 +478,164 @@ impl<
         }
     }
 
+    async fn encrypt_aes_ecb(
+        &mut self,
+        client_id: ClientId,
+        request_id: RequestId,
+        key_id: KeyId,
+        buffer: &'data mut [u8],
+    ) -> Response<'data> {
+        let mut key_buffer = Zeroizing::new([0u8; KeyType::MAX_SYMMETRIC_KEY_SIZE]);
+        let key_and_info = self
+            .export_key_and_key_info(key_id, key_buffer.as_mut_slice())
+            .await;
+        let result = match key_and_info {
+            Err(e) => {
+                return Response::Error {
+                    client_id,
+                    request_id,
+                    error: Error::KeyStore(e),
+                }
+            }
+            Ok((key, key_info)) => match key_info.ty {
+                KeyType::Symmetric(16) => aes128ecb_encrypt(key, buffer),
+                KeyType::Symmetric(24) => aes192ecb_encrypt(key, buffer),
+                KeyType::Symmetric(32) => aes256ecb_encrypt(key, buffer),
+                _ => {
+                    return Response::Error {
+                        client_id,
+                        request_id,
+                        error: Error::KeyStore(keystore::Error::InvalidKeyType),
+                    }
+                }
+            },
+        };
+        match result {
+            Err(e) => Response::Error {
+                client_id,
+                request_id,
+                error: Error::Crypto(e),
+            },
+            Ok(_) => Response::EncryptAesEcb {
+                client_id,
+                request_id,
+                buffer,
+            },
+        }
+    }
+
+    async fn encrypt_aes_ecb_external_key(
+        &mut self,
+        client_id: ClientId,
+        request_id: RequestId,
+        key: &[u8],
+        buffer: &'data mut [u8],
+    ) -> Response<'data> {
+        let result = match key.len() {
+            KEY128_SIZE => aes128ecb_encrypt(key, buffer),
+            KEY192_SIZE => aes192ecb_encrypt(key, buffer),
+            KEY256_SIZE => aes256ecb_encrypt(key, buffer),
+            _ => {
+                return Response::Error {
+                    client_id,
+                    request_id,
+                    error: Error::Crypto(crypto::Error::InvalidSymmetricKeySize),
+                }
+            }
+        };
+        match result {
+            Err(e) => Response::Error {
+                client_id,
+                request_id,
+                error: Error::Crypto(e),
+            },
+            Ok(_) => Response::EncryptAesEcb {
+                client_id,
+                request_id,
+                buffer,
+            },
+        }
+    }
+
+    async fn decrypt_aes_ecb(
+        &mut self,
+        client_id: ClientId,
+        request_id: RequestId,
+        key_id: KeyId,
+        buffer: &'data mut [u8],
+    ) -> Response<'data> {
+        let mut key_buffer = Zeroizing::new([0u8; KeyType::MAX_SYMMETRIC_KEY_SIZE]);
+        let key_and_info = self
+            .export_key_and_key_info(key_id, key_buffer.as_mut_slice())
+            .await;
+        let result = match key_and_info {
+            Err(e) => {
+                return Response::Error {
+                    client_id,
+                    request_id,
+                    error: Error::KeyStore(e),
+                }
+            }
+            Ok((key, key_info)) => match key_info.ty {
+                KeyType::Symmetric(16) => aes128ecb_decrypt(key, buffer),
+                KeyType::Symmetric(24) => aes192ecb_decrypt(key, buffer),
+                KeyType::Symmetric(32) => aes256ecb_decrypt(key, buffer),
+                _ => {
+                    return Response::Error {
+                        client_id,
+                        request_id,
+                        error: Error::KeyStore(keystore::Error::InvalidKeyType),
+                    }
+                }
+            },
+        };
+        match result {
+            Err(e) => Response::Error {
+                client_id,
+                request_id,
+                error: Error::Crypto(e),
+            },
+            Ok(_) => Response::DecryptAesEcb {
+                client_id,
+                request_id,
+                plaintext: buffer,
+            },
+        }
+    }
+
+    async fn decrypt_aes_ecb_external_key(
+        &mut self,
+        client_id: ClientId,
+        request_id: RequestId,
+        key: &[u8],
+        buffer: &'data mut [u8],
+    ) -> Response<'data> {
+        let result = match key.len() {
+            KEY128_SIZE => aes128ecb_decrypt(key, buffer),
+            KEY192_SIZE => aes192ecb_decrypt(key, buffer),
+            KEY256_SIZE => aes256ecb_decrypt(key, buffer),
+            _ => {
+                return Response::Error {
+                    client_id,
+                    request_id,
+                    error: Error::Crypto(crypto::Error::InvalidSymmetricKeySize),
+                }
+            }
+        };
+        match result {
+            Err(e) => Response::Error {
+                client_id,
+                request_id,
+                error: Error::Crypto(e),
+            },
+            Ok(_) => Response::DecryptAesEcb {
+                client_id,
+                request_id,
+                plaintext: buffer,
+            },
+        }
+    }
+
     async fn calculate_aes_cmac(
         &mut self,
         client_id: ClientId,
@@ -722,6 +798,30 @@ impl<
         }
     }
 
+    /// Check the SHE slot protection of the key used by the request, if any.
+    async fn check_she_slot(&self, request: &Request<'data>) -> Result<(), Error> {
+        let Some(slots) = self.she_slots else {
+            return Ok(());
+        };
+        let (key_id, usage) = match request {
+            Request::EncryptAesGcm { key_id, .. }
+            | Request::DecryptAesGcm { key_id, .. }
+            | Request::EncryptAesCbc { key_id, .. }
+            | Request::DecryptAesCbc { key_id, .. }
+            | Request::EncryptAesEcb { key_id, .. }
+            | Request::DecryptAesEcb { key_id, .. } => (*key_id, SheKeyUsage::Cipher),
+            Request::CalculateAesCmac { key_id, .. } | Request::VerifyAesCmac { key_id, .. } => {
+                (*key_id, SheKeyUsage::Mac)
+            }
+            _ => return Ok(()),
+        };
+        slots
+            .lock()
+            .await
+            .check_key_usage(key_id, usage)
+            .map_err(Error::She)
+    }
+
     async fn export_key_and_key_info<'a>(
         &mut self,
         key_id: KeyId,
@@ -735,3 +835,231 @@ impl<
         ))
     }
 }
+
+impl<
+        'data,
+        'keystore,
+        M: RawMutex,
+        KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
+    > Worker<'data> for AesWorker<'keystore, M, KeyStore>
+{
+    fn request_types(&self) -> &[RequestType] {
+        REQUEST_TYPES
+    }
+
+    async fn handle(&mut self, request: Request<'data>) -> Result<Response<'data>, Error> {
+        if let Err(error) = self.check_she_slot(&request).await {
+            return Ok(Response::Error {
+                client_id: request.get_client_id(),
+                request_id: request.get_request_id(),
+                error,
+            });
+        }
+        let response = match request {
+            Request::EncryptAesGcm {
+                client_id,
+                request_id,
+                key_id,
+                iv,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => {
+                self.encrypt_aes_gcm(client_id, request_id, key_id, iv, buffer, aad, tag)
+                    .await
+            }
+            Request::EncryptAesGcmExternalKey {
+                client_id,
+                request_id,
+                key,
+                iv,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => {
+                self.encrypt_aes_gcm_external_key(client_id, request_id, key, iv, buffer, aad, tag)
+                    .await
+            }
+            Request::DecryptAesGcm {
+                client_id,
+                request_id,
+                key_id,
+                iv,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => {
+                self.decrypt_aes_gcm(client_id, request_id, key_id, iv, buffer, aad, tag)
+                    .await
+            }
+            Request::DecryptAesGcmExternalKey {
+                client_id,
+                request_id,
+                key,
+                iv,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => {
+                self.decrypt_aes_gcm_external_key(client_id, request_id, key, iv, buffer, aad, tag)
+                    .await
+            }
+            Request::EncryptAesCbc {
+                client_id,
+                request_id,
+                key_id,
+                iv,
+                buffer,
+                plaintext_size,
+                padding,
+                ..
+            } => {
+                self.encrypt_aes_cbc(
+                    client_id,
+                    request_id,
+                    key_id,
+                    iv,
+                    buffer,
+                    plaintext_size,
+                    padding,
+                )
+                .await
+            }
+            Request::EncryptAesCbcExternalKey {
+                client_id,
+                request_id,
+                key,
+                iv,
+                buffer,
+                plaintext_size,
+                padding,
+                ..
+            } => {
+                self.encrypt_aes_cbc_external_key(
+                    client_id,
+                    request_id,
+                    key,
+                    iv,
+                    buffer,
+                    plaintext_size,
+                    padding,
+                )
+                .await
+            }
+            Request::DecryptAesCbc {
+                client_id,
+                request_id,
+                key_id,
+                iv,
+                buffer,
+                padding,
+                ..
+            } => {
+                self.decrypt_aes_cbc(client_id, request_id, key_id, iv, buffer, padding)
+                    .await
+            }
+            Request::DecryptAesCbcExternalKey {
+                client_id,
+                request_id,
+                key,
+                iv,
+                buffer,
+                padding,
+                ..
+            } => {
+                self.decrypt_aes_cbc_external_key(client_id, request_id, key, iv, buffer, padding)
+                    .await
+            }
+            Request::EncryptAesEcb {
+                client_id,
+                request_id,
+                key_id,
+                buffer,
+                ..
+            } => {
+                self.encrypt_aes_ecb(client_id, request_id, key_id, buffer)
+                    .await
+            }
+            Request::EncryptAesEcbExternalKey {
+                client_id,
+                request_id,
+                key,
+                buffer,
+                ..
+            } => {
+                self.encrypt_aes_ecb_external_key(client_id, request_id, key, buffer)
+                    .await
+            }
+            Request::DecryptAesEcb {
+                client_id,
+                request_id,
+                key_id,
+                buffer,
+                ..
+            } => {
+                self.decrypt_aes_ecb(client_id, request_id, key_id, buffer)
+                    .await
+            }
+            Request::DecryptAesEcbExternalKey {
+                client_id,
+                request_id,
+                key,
+                buffer,
+                ..
+            } => {
+                self.decrypt_aes_ecb_external_key(client_id, request_id, key, buffer)
+                    .await
+            }
+            Request::CalculateAesCmac {
+                client_id,
+                request_id,
+                key_id,
+                message,
+                tag,
+                ..
+            } => {
+                self.calculate_aes_cmac(client_id, request_id, key_id, message, tag)
+                    .await
+            }
+            Request::CalculateAesCmacExternalKey {
+                client_id,
+                request_id,
+                key,
+                message,
+                tag,
+                ..
+            } => {
+                self.calculate_aes_cmac_external_key(client_id, request_id, key, message, tag)
+                    .await
+            }
+            Request::VerifyAesCmac {
+                client_id,
+                request_id,
+                key_id,
+                message,
+                tag,
+                ..
+            } => {
+                self.verify_aes_cmac(client_id, request_id, key_id, message, tag)
+                    .await
+            }
+            Request::VerifyAesCmacExternalKey {
+                client_id,
+                request_id,
+                key,
+                message,
+                tag,
+                ..
+            } => {
+                self.verify_aes_cmac_external_key(client_id, request_id, key, message, tag)
+                    .await
+            }
+            _ => Err(Error::UnexpectedRequestType)?,
+        };
+        Ok(response)
+    }
+}
@@ -1,95 +1,34 @@
-use crate::common::jobs::{ClientId, Error, Request, RequestId, Response};
+use crate::common::jobs::{ClientId, Error, Request, RequestId, RequestType, Response};
+use crate::common::sync::{Mutex, RawMutex};
 use crate::crypto;
 use crate::crypto::chacha20poly1305::KEY_SIZE;
 use crate::hsm::keystore::{self, KeyId};
-use embassy_sync::blocking_mutex::raw::RawMutex;
-use embassy_sync::mutex::Mutex;
-use futures::{Sink, SinkExt, Stream, StreamExt};
+use crate::hsm::workers::Worker;
 use zeroize::Zeroizing;
 
+/// Request types handled by the worker
+const REQUEST_TYPES: &[RequestType] = &[
+    RequestType::EncryptChaChaPoly,
+    RequestType::EncryptChaChaPolyExternalKey,
+    RequestType::DecryptChaChaPoly,
+    RequestType::DecryptChaChaPolyExternalKey,
+];
+
 pub struct ChaChaPolyWorker<
-    'data,
     'keystore,
     M: RawMutex,
-    ReqSrc: Stream<Item = Request<'data>>,
-    RespSink: Sink<Response<'data>>,
     KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
 > {
     pub key_store: &'keystore Mutex<M, &'keystore mut KeyStore>,
-    pub requests: ReqSrc,
-    pub responses: RespSink,
 }
 
 impl<
         'data,
         'keystore,
         M: RawMutex,
-        ReqSrc: Stream<Item = Request<'data>> + Unpin,
-        RespSink: Sink<Response<'data>> + Unpin,
         KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
-    > ChaChaPolyWorker<'data, 'keystore, M, ReqSrc, RespSink, KeyStore>
+    > ChaChaPolyWorker<'keystore, M, KeyStore>
 {
-    /// Drive the worker to process the next request.
-    /// This method is supposed to be called by a system task that owns this worker.
-    pub async fn execute(&mut self) -> Result<(), Error> {
-        let request = self.requests.next().await.ok_or(Error::StreamTerminated)?;
-        let response = match request {
-            Request::EncryptChaChaPoly {
-                client_id,
-                request_id,
-                key_id,
-                nonce,
-                buffer,
-                aad,
-                tag,
-            } => {
-                self.encrypt_internal_key(client_id, request_id, key_id, nonce, buffer, aad, tag)
-                    .await
-            }
-            Request::EncryptChaChaPolyExternalKey {
-                client_id,
-                request_id,
-                key,
-                nonce,
-                buffer,
-                aad,
-                tag,
-            } => {
-                self.encrypt_with_external_key(client_id, request_id, key, nonce, aad, buffer, tag)
-            }
-            Request::DecryptChaChaPoly {
-                client_id,
-                request_id,
-                key_id,
-                nonce,
-                buffer,
-                aad,
-                tag,
-            } => {
-                self.decrypt_with_internal_key(
-                    client_id, request_id, key_id, nonce, buffer, aad, tag,
-                )
-                .await
-            }
-            Request::DecryptChaChaPolyExternalKey {
-                client_id,
-                request_id,
-                key,
-                nonce,
-                buffer,
-                aad,
-                tag,
-            } => {
-                self.decrypt_with_external_key(client_id, request_id, key, nonce, aad, buffer, tag)
-            }
-            _ => Err(Error::UnexpectedRequestType)?,
-        };
-        self.responses
-            .send(response)
-            .await
-            .map_err(|_e| Error::Send)
-    }
-
     #[allow(clippy::too_many_arguments)]
     async fn encrypt_internal_key(
         &mut self,
@@ -223,3 +162,74 @@ impl<
         }
     }
 }
+
+impl<
+        'data,
+        'keystore,
+        M: RawMutex,
+        KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
+    > Worker<'data> for ChaChaPolyWorker<'keystore, M, KeyStore>
+{
+    fn request_types(&self) -> &[RequestType] {
+        REQUEST_TYPES
+    }
+
+    async fn handle(&mut self, request: Request<'data>) -> Result<Response<'data>, Error> {
+        let response = match request {
+            Request::EncryptChaChaPoly {
+                client_id,
+                request_id,
+                key_id,
+                nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => {
+                self.encrypt_internal_key(client_id, request_id, key_id, nonce, buffer, aad, tag)
+                    .await
+            }
+            Request::EncryptChaChaPolyExternalKey {
+                client_id,
+                request_id,
+                key,
+                nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => {
+                self.encrypt_with_external_key(client_id, request_id, key, nonce, aad, buffer, tag)
+            }
+            Request::DecryptChaChaPoly {
+                client_id,
+                request_id,
+                key_id,
+                nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => {
+                self.decrypt_with_internal_key(
+                    client_id, request_id, key_id, nonce, buffer, aad, tag,
+                )
+                .await
+            }
+            Request::DecryptChaChaPolyExternalKey {
+                client_id,
+                request_id,
+                key,
+                nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => {
+                self.decrypt_with_external_key(client_id, request_id, key, nonce, aad, buffer, tag)
+            }
+            _ => Err(Error::UnexpectedRequestType)?,
+        };
+        Ok(response)
+    }
+}
@@ -1,33 +1,45 @@
-use crate::common::jobs::{ClientId, Error, Request, RequestId, Response};
+use crate::common::jobs::{
+    ClientId, Error, HashAlgorithm, Request, RequestId, RequestType, Response, SignatureFormat,
+};
+use crate::common::sync::{Mutex, RawMutex};
 use crate::crypto;
 use crate::crypto::ecdsa::{
-    nist_p256_generate_key_pair, nist_p256_sign, nist_p256_sign_prehashed, nist_p256_verify,
-    nist_p256_verify_prehashed, nist_p384_generate_key_pair, nist_p384_sign,
-    nist_p384_sign_prehashed, nist_p384_verify, nist_p384_verify_prehashed,
+    nist_p256_generate_key_pair, nist_p256_sign, nist_p256_sign_digest, nist_p256_sign_prehashed,
+    nist_p256_signature_from_der, nist_p256_signature_to_der, nist_p256_verify,
+    nist_p256_verify_digest, nist_p256_verify_prehashed, nist_p384_generate_key_pair,
+    nist_p384_sign, nist_p384_sign_digest, nist_p384_sign_prehashed, nist_p384_signature_from_der,
+    nist_p384_signature_to_der, nist_p384_verify, nist_p384_verify_digest,
+    nist_p384_verify_prehashed,
 };
+use crate::crypto::hash;
 use crate::hsm::keystore;
 use crate::hsm::keystore::{Curve, KeyId, KeyInfo, KeyType};
+use crate::hsm::workers::Worker;
 use core::ops::DerefMut;
-use embassy_sync::blocking_mutex::raw::RawMutex;
-use embassy_sync::mutex::Mutex;
-use futures::{Sink, SinkExt, Stream, StreamExt};
 use rand_chacha::rand_core::{CryptoRng, RngCore};
 use zeroize::{Zeroize, Zeroizing};
 
+/// Size of the largest raw signature of all supported curves.
+const MAX_SIGNATURE_SIZE: usize = crypto::ecdsa::NIST_P384_SIGNATURE_SIZE;
+
+/// Request types handled by the worker
+const REQUEST_TYPES: &[RequestType] = &[
+    RequestType::GenerateKeyPair,
+    RequestType::Sign,
+    RequestType::SignExternalKey,
+    RequestType::Verify,
+    RequestType::VerifyExternalKey,
+];
+
 pub struct EccWorker<
-    'data,
     'rng,
     'keystore,
     M: RawMutex,
     R: CryptoRng + RngCore,
-    ReqSrc: Stream<Item = Request<'data>>,
-    RespSink: Sink<Response<'data>>,
     KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
 > {
     pub rng: &'rng Mutex<M, R>,
     pub key_store: &'keystore Mutex<M, &'keystore mut KeyStore>,
-    pub requests: ReqSrc,
-    pub responses: RespSink,
 }
 
 impl<
@@ -36,86 +48,9 @@ impl<
         'keystore,
         M: RawMutex,
         R: CryptoRng + RngCore,
-        ReqSrc: Stream<Item = Request<'data>> + Unpin,
-        RespSink: Sink<Response<'data>> + Unpin,
         KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
-    > EccWorker<'data, 'rng, 'keystore, M, R, ReqSrc, RespSink, KeyStore>
+    > EccWorker<'rng, 'keystore, M, R, KeyStore>
 {
-    /// Drive the worker to process the next request.
-    /// This method is supposed to be called by a system task that owns this worker.
-    pub async fn execute(&mut self) -> Result<(), Error> {
-        let request = self.requests.next().await.ok_or(Error::StreamTerminated)?;
-        let response = match request {
-            Request::GenerateKeyPair {
-                client_id,
-                request_id,
-                key_id,
-                overwrite,
-            } => {
-                self.generate_key_pair(client_id, request_id, key_id, overwrite)
-                    .await
-            }
-            Request::Sign {
-                client_id,
-                request_id,
-                key_id,
-                message,
-                prehashed,
-                signature,
-            } => {
-                self.sign(client_id, request_id, key_id, message, prehashed, signature)
-                    .await
-            }
-            Request::SignExternalKey {
-                client_id,
-                request_id,
-                private_key,
-                message,
-                prehashed,
-                signature,
-            } => {
-                self.sing_external_key(
-                    client_id,
-                    request_id,
-                    private_key,
-                    message,
-                    prehashed,
-                    signature,
-                )
-                .await
-            }
-            Request::Verify {
-                client_id,
-                request_id,
-                key_id,
-                message,
-                prehashed,
-                signature,
-            } => {
-                self.verify(client_id, request_id, key_id, message, prehashed, signature)
-                    .await
-            }
-            Request::VerifyExternalKey {
-                client_id,
-                request_id,
-                public_key,
-                message,
-                prehashed,
-                signature,
-            } => {
-                self.verify_external_key(
-                    client_id, request_id, public_key, message, prehashed, signature,
-                )
-                .await
-            }
-            _ => Err(Error::UnexpectedRequestType)?,
-        };
-        self.responses
-            .send(response)
-            .await
-            .map_err(|_e| Error::Send)
-    }
-
     async fn generate_key_pair(
         &mut self,
         client_id: ClientId,
@@ -197,6 +132,7 @@ impl<
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn sign(
         &mut self,
         client_id: ClientId,
@@ -204,6 +140,8 @@ impl<
         key_id: KeyId,
         message: &[u8],
         prehashed: bool,
+        hash_algorithm: Option<HashAlgorithm>,
+        signature_format: SignatureFormat,
         signature: &'data mut [u8],
     ) -> Response<'data> {
         let mut key_buffer = Zeroizing::new([0u8; KeyType::MAX_PRIVATE_KEY_SIZE]);
@@ -220,20 +158,15 @@ impl<
                 };
             }
             Ok((private_key, key_info)) => match key_info.ty {
-                KeyType::Asymmetric(Curve::NistP256) => {
-                    if prehashed {
-                        nist_p256_sign_prehashed(private_key, message, signature)
-                    } else {
-                        nist_p256_sign(private_key, message, signature)
-                    }
-                }
-                KeyType::Asymmetric(Curve::NistP384) => {
-                    if prehashed {
-                        nist_p384_sign_prehashed(private_key, message, signature)
-                    } else {
-                        nist_p384_sign(private_key, message, signature)
-                    }
-                }
+                KeyType::Asymmetric(curve) => sign_message(
+                    curve,
+                    private_key,
+                    message,
+                    prehashed,
+                    hash_algorithm,
+                    signature_format,
+                    signature,
+                ),
                 _ => {
                     return Response::Error {
                         client_id,
@@ -244,20 +177,10 @@ impl<
             },
         };
 
-        match result {
-            Err(e) => Response::Error {
-                client_id,
-                request_id,
-                error: Error::Crypto(e),
-            },
-            Ok(_) => Response::Sign {
-                client_id,
-                request_id,
-                signature,
-            },
-        }
+        sign_response(client_id, request_id, signature, result)
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn sing_external_key(
         &mut self,
         client_id: ClientId,
@@ -265,23 +188,13 @@ impl<
         private_key: &[u8],
         message: &[u8],
         prehashed: bool,
+        hash_algorithm: Option<HashAlgorithm>,
+        signature_format: SignatureFormat,
         signature: &'data mut [u8],
     ) -> Response<'data> {
-        let result = match private_key.len() {
-            crypto::ecdsa::NIST_P256_PRIVATE_KEY_SIZE => {
-                if prehashed {
-                    nist_p256_sign_prehashed(private_key, message, signature)
-                } else {
-                    nist_p256_sign(private_key, message, signature)
-                }
-            }
-            crypto::ecdsa::NIST_P384_PRIVATE_KEY_SIZE => {
-                if prehashed {
-                    nist_p384_sign_prehashed(private_key, message, signature)
-                } else {
-                    nist_p384_sign(private_key, message, signature)
-                }
-            }
+        let curve = match private_key.len() {
+            crypto::ecdsa::NIST_P256_PRIVATE_KEY_SIZE => Curve::NistP256,
+            crypto::ecdsa::NIST_P384_PRIVATE_KEY_SIZE => Curve::NistP384,
             _ => {
                 return Response::Error {
                     client_id,
@@ -291,20 +204,19 @@ impl<
             }
         };
 
-        match result {
-            Err(e) => Response::Error {
-                client_id,
-                request_id,
-                error: Error::Crypto(e),
-            },
-            Ok(_) => Response::Sign {
-                client_id,
-                request_id,
-                signature,
-            },
-        }
+        let result = sign_message(
+            curve,
+            private_key,
+            message,
+            prehashed,
+            hash_algorithm,
+            signature_format,
+            signature,
+        );
+        sign_response(client_id, request_id, signature, result)
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn verify(
         &mut self,
         client_id: ClientId,
@@ -312,6 +224,8 @@ impl<
         key_id: KeyId,
         message: &[u8],
         prehashed: bool,
+        hash_algorithm: Option<HashAlgorithm>,
+        signature_format: SignatureFormat,
         signature: &[u8],
     ) -> Response<'data> {
         let mut key_buffer = Zeroizing::new([0u8; KeyType::MAX_PUBLIC_KEY_SIZE]);
@@ -328,20 +242,15 @@ impl<
                 };
             }
             Ok((public_key, key_info)) => match key_info.ty {
-                KeyType::Asymmetric(Curve::NistP256) => {
-                    if prehashed {
-                        nist_p256_verify_prehashed(public_key, message, signature)
-                    } else {
-                        nist_p256_verify(public_key, message, signature)
-                    }
-                }
-                KeyType::Asymmetric(Curve::NistP384) => {
-                    if prehashed {
-                        nist_p384_verify_prehashed(public_key, message, signature)
-                    } else {
-                        nist_p384_verify(public_key, message, signature)
-                    }
-                }
+                KeyType::Asymmetric(curve) => verify_message(
+                    curve,
+                    public_key,
+                    message,
+                    prehashed,
+                    hash_algorithm,
+                    signature_format,
+                    signature,
+                ),
                 _ => {
                     return Response::Error {
                         client_id,
@@ -352,25 +261,10 @@ impl<
             },
         };
 
-        match result {
-            Err(crypto::Error::InvalidSignature) => Response::Verify {
-                client_id,
-                request_id,
-                verified: false,
-            },
-            Err(e) => Response::Error {
-                client_id,
-                request_id,
-                error: Error::Crypto(e),
-            },
-            Ok(_) => Response::Verify {
-                client_id,
-                request_id,
-                verified: true,
-            },
-        }
+        verify_response(client_id, request_id, result)
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn verify_external_key(
         &mut self,
         client_id: ClientId,
@@ -378,23 +272,13 @@ impl<
         public_key: &[u8],
         message: &[u8],
         prehashed: bool,
+        hash_algorithm: Option<HashAlgorithm>,
+        signature_format: SignatureFormat,
         signature: &[u8],
     ) -> Response<'data> {
-        let result = match public_key.len() {
-            crypto::ecdsa::NIST_P256_PUBLIC_KEY_SIZE => {
-                if prehashed {
-                    nist_p256_verify_prehashed(public_key, message, signature)
-                } else {
-                    nist_p256_verify(public_key, message, signature)
-                }
-            }
-            crypto::ecdsa::NIST_P384_PUBLIC_KEY_SIZE => {
-                if prehashed {
-                    nist_p384_verify_prehashed(public_key, message, signature)
-                } else {
-                    nist_p384_verify(public_key, message, signature)
-                }
-            }
+        let curve = match public_key.len() {
+            crypto::ecdsa::NIST_P256_PUBLIC_KEY_SIZE => Curve::NistP256,
+            crypto::ecdsa::NIST_P384_PUBLIC_KEY_SIZE => Curve::NistP384,
             _ => {
                 return Response::Error {
                     client_id,
@@ -404,23 +288,16 @@ impl<
             }
         };
 
-        match result {
-            Err(crypto::Error::InvalidSignature) => Response::Verify {
-                client_id,
-                request_id,
-                verified: false,
-            },
-            Err(e) => Response::Error {
-                client_id,
-                request_id,
-                error: Error::Crypto(e),
-            },
-            Ok(_) => Response::Verify {
-                client_id,
-                request_id,
-                verified: true,
-            },
-        }
+        let result = verify_message(
+            curve,
+            public_key,
+            message,
+            prehashed,
+            hash_algorithm,
+            signature_format,
+            signature,
+        );
+        verify_response(client_id, request_id, result)
     }
 
     async fn export_private_key_and_key_info<'a>(
@@ -452,6 +329,129 @@ impl<
     }
 }
 
+impl<
+        'data,
+        'rng,
+        'keystore,
+        M: RawMutex,
+        R: CryptoRng + RngCore,
+        KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
+    > Worker<'data> for EccWorker<'rng, 'keystore, M, R, KeyStore>
+{
+    fn request_types(&self) -> &[RequestType] {
+        REQUEST_TYPES
+    }
+
+    async fn handle(&mut self, request: Request<'data>) -> Result<Response<'data>, Error> {
+        let response = match request {
+            Request::GenerateKeyPair {
+                client_id,
+                request_id,
+                key_id,
+                overwrite,
+                ..
+            } => {
+                self.generate_key_pair(client_id, request_id, key_id, overwrite)
+                    .await
+            }
+            Request::Sign {
+                client_id,
+                request_id,
+                key_id,
+                message,
+                prehashed,
+                hash_algorithm,
+                signature_format,
+                signature,
+                ..
+            } => {
+                self.sign(
+                    client_id,
+                    request_id,
+                    key_id,
+                    message,
+                    prehashed,
+                    hash_algorithm,
+                    signature_format,
+                    signature,
+                )
+                .await
+            }
+            Request::SignExternalKey {
+                client_id,
+                request_id,
+                private_key,
+                message,
+                prehashed,
+                hash_algorithm,
+                signature_format,
+                signature,
+                ..
+            } => {
+                self.sing_external_key(
+                    client_id,
+                    request_id,
+                    private_key,
+                    message,
+                    prehashed,
+                    hash_algorithm,
+                    signature_format,
+                    signature,
+                )
+                .await
+            }
+            Request::Verify {
+                client_id,
+                request_id,
+                key_id,
+                message,
+                prehashed,
+                hash_algorithm,
+                signature_format,
+                signature,
+                ..
+            } => {
+                self.verify(
+                    client_id,
+                    request_id,
+                    key_id,
+                    message,
+                    prehashed,
+                    hash_algorithm,
+                    signature_format,
+                    signature,
+                )
+                .await
+            }
+            Request::VerifyExternalKey {
+                client_id,
+                request_id,
+                public_key,
+                message,
+                prehashed,
+                hash_algorithm,
+                signature_format,
+                signature,
+                ..
+            } => {
+                self.verify_external_key(
+                    client_id,
+                    request_id,
+                    public_key,
+                    message,
+                    prehashed,
+                    hash_algorithm,
+                    signature_format,
+                    signature,
+                )
+                .await
+            }
+            _ => Err(Error::UnexpectedRequestType)?,
+        };
+        Ok(response)
+    }
+}
+
 fn move_key_pair<'a, const N: usize, const M: usize>(
     mut private_key: [u8; N],
     mut public_key: [u8; M],
@@ -468,3 +468,172 @@ fn move_key_pair<'a, const N: usize, const M: usize>(
 
     (private_key_result, public_key_result)
 }
+
+/// Compute the digest of `message` if a hash algorithm was explicitly requested.
+///
+/// returns: `None` if the default hash of the curve should be used, the digest otherwise.
+fn digest_message<'a>(
+    message: &'a [u8],
+    prehashed: bool,
+    hash_algorithm: Option<HashAlgorithm>,
+    digest_buffer: &'a mut [u8; hash::SHA512_SIZE],
+) -> Result<Option<&'a [u8]>, crypto::Error> {
+    let Some(hash_algorithm) = hash_algorithm else {
+        return Ok(None);
+    };
+    if prehashed {
+        if message.len() != hash_algorithm.digest_size() {
+            return Err(crypto::Error::InvalidDigestSize);
+        }
+        return Ok(Some(message));
+    }
+    let digest = &mut digest_buffer[..hash_algorithm.digest_size()];
+    match hash_algorithm {
+        HashAlgorithm::Sha2_256 => digest.copy_from_slice(&hash::sha256(message)),
+        HashAlgorithm::Sha2_384 => digest.copy_from_slice(&hash::sha384(message)),
+        HashAlgorithm::Sha2_512 => digest.copy_from_slice(&hash::sha512(message)),
+        HashAlgorithm::Sha3_256 => digest.copy_from_slice(&hash::sha3_256(message)),
+        HashAlgorithm::Sha3_384 => digest.copy_from_slice(&hash::sha3_384(message)),
+        HashAlgorithm::Sha3_512 => digest.copy_from_slice(&hash::sha3_512(message)),
+    }
+    Ok(Some(digest))
+}
+
+/// Sign `message` and write the signature in the requested format to `signature`.
+///
+/// returns: The number of bytes written to `signature`.
+fn sign_message(
+    curve: Curve,
+    private_key: &[u8],
+    message: &[u8],
+    prehashed: bool,
+    hash_algorithm: Option<HashAlgorithm>,
+    signature_format: SignatureFormat,
+    signature: &mut [u8],
+) -> Result<usize, crypto::Error> {
+    let mut digest_buffer = [0u8; hash::SHA512_SIZE];
+    let digest = digest_message(message, prehashed, hash_algorithm, &mut digest_buffer)?;
+    let sign_raw = |raw_signature: &mut [u8]| match (curve, digest) {
+        (Curve::NistP256, Some(digest)) => {
+            nist_p256_sign_digest(private_key, digest, raw_signature)
+        }
+        (Curve::NistP256, None) if prehashed => {
+            nist_p256_sign_prehashed(private_key, message, raw_signature)
+        }
+        (Curve::NistP256, None) => nist_p256_sign(private_key, message, raw_signature),
+        (Curve::NistP384, Some(digest)) => {
+            nist_p384_sign_digest(private_key, digest, raw_signature)
+        }
+        (Curve::NistP384, None) if prehashed => {
+            nist_p384_sign_prehashed(private_key, message, raw_signature)
+        }
+        (Curve::NistP384, None) => nist_p384_sign(private_key, message, raw_signature),
+    };
+
+    match signature_format {
+        SignatureFormat::Raw => {
+            sign_raw(signature)?;
+            Ok(signature.len())
+        }
+        SignatureFormat::Der => {
+            let mut raw_signature = [0u8; MAX_SIGNATURE_SIZE];
+            let raw_signature = &mut raw_signature[..signature_size(curve)];
+            sign_raw(raw_signature)?;
+            match curve {
+                Curve::NistP256 => nist_p256_signature_to_der(raw_signature, signature),
+                Curve::NistP384 => nist_p384_signature_to_der(raw_signature, signature),
+            }
+        }
+    }
+}
+
+/// Verify a `signature` in the requested format of `message`.
+fn verify_message(
+    curve: Curve,
+    public_key: &[u8],
+    message: &[u8],
+    prehashed: bool,
+    hash_algorithm: Option<HashAlgorithm>,
+    signature_format: SignatureFormat,
+    signature: &[u8],
+) -> Result<(), crypto::Error> {
+    let mut digest_buffer = [0u8; hash::SHA512_SIZE];
+    let digest = digest_message(message, prehashed, hash_algorithm, &mut digest_buffer)?;
+
+    let mut raw_signature = [0u8; MAX_SIGNATURE_SIZE];
+    let signature = match signature_format {
+        SignatureFormat::Raw => signature,
+        SignatureFormat::Der => {
+            let raw_signature = &mut raw_signature[..signature_size(curve)];
+            match curve {
+                Curve::NistP256 => nist_p256_signature_from_der(signature, raw_signature)?,
+                Curve::NistP384 => nist_p384_signature_from_der(signature, raw_signature)?,
+            }
+            raw_signature
+        }
+    };
+
+    match (curve, digest) {
+        (Curve::NistP256, Some(digest)) => nist_p256_verify_digest(public_key, digest, signature),
+        (Curve::NistP256, None) if prehashed => {
+            nist_p256_verify_prehashed(public_key, message, signature)
+        }
+        (Curve::NistP256, None) => nist_p256_verify(public_key, message, signature),
+        (Curve::NistP384, Some(digest)) => nist_p384_verify_digest(public_key, digest, signature),
+        (Curve::NistP384, None) if prehashed => {
+            nist_p384_verify_prehashed(public_key, message, signature)
+        }
+        (Curve::NistP384, None) => nist_p384_verify(public_key, message, signature),
+    }
+}
+
+fn signature_size(curve: Curve) -> usize {
+    match curve {
+        Curve::NistP256 => crypto::ecdsa::NIST_P256_SIGNATURE_SIZE,
+        Curve::NistP384 => crypto::ecdsa::NIST_P384_SIGNATURE_SIZE,
+    }
+}
+
+fn sign_response<'data>(
+    client_id: ClientId,
+    request_id: RequestId,
+    signature: &'data mut [u8],
+    result: Result<usize, crypto::Error>,
+) -> Response<'data> {
+    match result {
+        Err(e) => Response::Error {
+            client_id,
+            request_id,
+            error: Error::Crypto(e),
+        },
+        Ok(size) => Response::Sign {
+            client_id,
+            request_id,
+            signature: &mut signature[..size],
+        },
+    }
+}
+
+fn verify_response<'data>(
+    client_id: ClientId,
+    request_id: RequestId,
+    result: Result<(), crypto::Error>,
+) -> Response<'data> {
+    match result {
+        Err(crypto::Error::InvalidSignature) => Response::Verify {
+            client_id,
+            request_id,
+            verified: false,
+        },
+        Err(e) => Response::Error {
+            client_id,
+            request_id,
+            error: Error::Crypto(e),
+        },
+        Ok(_) => Response::Verify {
+            client_id,
+            request_id,
+            verified: true,
+        },
+    }
+}
@@ -1,5 +1,6 @@
+use crate::common::sync::{Mutex, RawMutex};
 use crate::{
-    common::jobs::{ClientId, Error, HashAlgorithm, Request, RequestId, Response},
+    common::jobs::{ClientId, Error, HashAlgorithm, Request, RequestId, RequestType, Response},
     crypto::hmac::{
         hmac_sha2_256_calculate, hmac_sha2_256_verify, hmac_sha2_384_calculate,
         hmac_sha2_384_verify, hmac_sha2_512_calculate, hmac_sha2_512_verify,
@@ -7,102 +8,33 @@ use crate::{
         hmac_sha3_384_verify, hmac_sha3_512_calculate, hmac_sha3_512_verify,
     },
     hsm::keystore::{self, KeyId, KeyInfo, KeyType},
+    hsm::workers::Worker,
 };
-use embassy_sync::{blocking_mutex::raw::RawMutex, mutex::Mutex};
-use futures::{Sink, SinkExt, Stream, StreamExt};
 use zeroize::Zeroizing;
 
+/// Request types handled by the worker
+const REQUEST_TYPES: &[RequestType] = &[
+    RequestType::CalculateHmac,
+    RequestType::CalculateHmacExternalKey,
+    RequestType::VerifyHmac,
+    RequestType::VerifyHmacExternalKey,
+];
+
 pub struct HmacWorker<
-    'data,
     'keystore,
     M: RawMutex,
-    ReqSrc: Stream<Item = Request<'data>>,
-    RespSink: Sink<Response<'data>>,
     KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
 > {
     pub key_store: &'keystore Mutex<M, &'keystore mut KeyStore>,
-    pub requests: ReqSrc,
-    pub responses: RespSink,
 }
 
 impl<
         'data,
-        'rng,
         'keystore,
         M: RawMutex,
-        ReqSrc: Stream<Item = Request<'data>> + Unpin,
-        RespSink: Sink<Response<'data>> + Unpin,
         KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
-    > HmacWorker<'data, 'keystore, M, ReqSrc, RespSink, KeyStore>
+    > HmacWorker<'keystore, M, KeyStore>
 {
-    /// Drive the worker to process the next request.
-    /// This method is supposed to be called by a system task that owns this worker.
-    pub async fn execute(&mut self) -> Result<(), Error> {
-        let request = self.requests.next().await.ok_or(Error::StreamTerminated)?;
-        let response = match request {
-            Request::CalculateHmac {
-                client_id,
-                request_id,
-                key_id,
-                hash_algorithm,
-                message,
-                tag,
-            } => {
-                self.calculate_hmac(client_id, request_id, key_id, hash_algorithm, message, tag)
-                    .await
-            }
-            Request::CalculateHmacExternalKey {
-                client_id,
-                request_id,
-                key,
-                hash_algorithm,
-                message,
-                tag,
-            } => {
-                self.calculate_hmac_external_key(
-                    client_id,
-                    request_id,
-                    key,
-                    hash_algorithm,
-                    message,
-                    tag,
-                )
-                .await
-            }
-            Request::VerifyHmac {
-                client_id,
-                request_id,
-                key_id,
-                hash_algorithm,
-                message,
-                tag,
-            } => {
-                self.verify_hmac(client_id, request_id, key_id, hash_algorithm, message, tag)
-                    .await
-            }
-            Request::VerifyHmacExternalKey {
-                client_id,
-                request_id,
-                key,
-                hash_algorithm,
-                message,
-                tag,
-            } => {
-                self.verify_hmac_external_key(
-                    client_id,
-                    request_id,
-                    key,
-                    hash_algorithm,
-                    message,
-                    tag,
-                )
-                .await
-            }
-            _ => Err(Error::UnexpectedRequestType)?,
-        };
-        self.responses.send(response).await.map_err(|_| Error::Send)
-    }
-
     async fn calculate_hmac(
         &mut self,
         client_id: ClientId,
@@ -284,3 +216,84 @@ impl<
         ))
     }
 }
+
+impl<
+        'data,
+        'keystore,
+        M: RawMutex,
+        KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
+    > Worker<'data> for HmacWorker<'keystore, M, KeyStore>
+{
+    fn request_types(&self) -> &[RequestType] {
+        REQUEST_TYPES
+    }
+
+    async fn handle(&mut self, request: Request<'data>) -> Result<Response<'data>, Error> {
+        let response = match request {
+            Request::CalculateHmac {
+                client_id,
+                request_id,
+                key_id,
+                hash_algorithm,
+                message,
+                tag,
+                ..
+            } => {
+                self.calculate_hmac(client_id, request_id, key_id, hash_algorithm, message, tag)
+                    .await
+            }
+            Request::CalculateHmacExternalKey {
+                client_id,
+                request_id,
+                key,
+                hash_algorithm,
+                message,
+                tag,
+                ..
+            } => {
+                self.calculate_hmac_external_key(
+                    client_id,
+                    request_id,
+                    key,
+                    hash_algorithm,
+                    message,
+                    tag,
+                )
+                .await
+            }
+            Request::VerifyHmac {
+                client_id,
+                request_id,
+                key_id,
+                hash_algorithm,
+                message,
+                tag,
+                ..
+            } => {
+                self.verify_hmac(client_id, request_id, key_id, hash_algorithm, message, tag)
+                    .await
+            }
+            Request::VerifyHmacExternalKey {
+                client_id,
+                request_id,
+                key,
+                hash_algorithm,
+                message,
+                tag,
+                ..
+            } => {
+                self.verify_hmac_external_key(
+                    client_id,
+                    request_id,
+                    key,
+                    hash_algorithm,
+                    message,
+                    tag,
+                )
+                .await
+            }
+            _ => Err(Error::UnexpectedRequestType)?,
+        };
+        Ok(response)
+    }
+}
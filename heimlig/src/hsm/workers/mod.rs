@@ -1,5 +1,68 @@
+use crate::common::jobs::{Error, Request, RequestType, Response};
+use crate::hsm::cancellations::Cancellations;
+use core::future::Future;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+
 pub mod aes_worker;
 pub mod chachapoly_worker;
 pub mod ecc_worker;
 pub mod hmac_worker;
 pub mod rng_worker;
+pub mod she_worker;
+
+/// Common interface of all workers.
+///
+/// A worker can be deployed in two ways:
+/// - In its own task, receiving requests from the core through a queue. The worker is wrapped in a
+///   [QueuedWorker] together with its queues. The task calls [QueuedWorker::execute], which passes
+///   the requests to [Worker::handle].
+/// - Hosted by the core (see [crate::hsm::core::Builder::with_hosted_worker]). The core calls
+///   [Worker::handle] itself, so no queues or additional tasks are required.
+pub trait Worker<'data> {
+    /// Request types the worker can handle
+    fn request_types(&self) -> &[RequestType];
+
+    /// Process a request and return the response for it. Requests whose type is not contained in
+    /// [Worker::request_types] result in [Error::UnexpectedRequestType].
+    fn handle(
+        &mut self,
+        request: Request<'data>,
+    ) -> impl Future<Output = Result<Response<'data>, Error>>;
+}
+
+/// Worker running in its own task that exchanges requests and responses with the core through
+/// queues.
+pub struct QueuedWorker<
+    'data,
+    W: Worker<'data>,
+    ReqSrc: Stream<Item = Request<'data>>,
+    RespSink: Sink<Response<'data>>,
+> {
+    pub worker: W,
+    pub requests: ReqSrc,
+    pub responses: RespSink,
+    /// Requests found in this registry are answered with [Response::Cancelled] unprocessed.
+    pub cancellations: Option<&'data Cancellations>,
+}
+
+impl<
+        'data,
+        W: Worker<'data>,
+        ReqSrc: Stream<Item = Request<'data>> + Unpin,
+        RespSink: Sink<Response<'data>> + Unpin,
+    > QueuedWorker<'data, W, ReqSrc, RespSink>
+{
+    /// Drive the worker to process the next request.
+    /// This method is supposed to be called by a system task that owns this worker.
+    pub async fn execute(&mut self) -> Result<(), Error> {
+        let request = self.requests.next().await.ok_or(Error::StreamTerminated)?;
+        let response = match self.cancellations.and_then(|c| c.take_cancelled(&request)) {
+            Some(response) => response,
+            None => self.worker.handle(request).await?,
+        };
+        self.responses
+            .send(response)
+            .await
+            .map_err(|_e| Error::Send)
+    }
+}
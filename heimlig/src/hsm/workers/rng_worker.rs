@@ -1,25 +1,22 @@
-use crate::common::jobs::{ClientId, Error, Request, RequestId, Response};
+use crate::common::jobs::{ClientId, Error, Request, RequestId, RequestType, Response};
+use crate::common::sync::{Mutex, RawMutex};
 use crate::hsm::keystore::{self, KeyId};
-use embassy_sync::blocking_mutex::raw::RawMutex;
-use embassy_sync::mutex::Mutex;
-use futures::{Sink, SinkExt, Stream, StreamExt};
+use crate::hsm::workers::Worker;
 use rand_chacha::rand_core::{CryptoRng, RngCore};
 
+/// Request types handled by the worker
+const REQUEST_TYPES: &[RequestType] = &[RequestType::GetRandom, RequestType::GenerateSymmetricKey];
+
 pub struct RngWorker<
-    'data,
     'rng,
     'keystore,
     M: RawMutex,
     R: CryptoRng + RngCore,
-    ReqSrc: Stream<Item = Request<'data>>,
-    RespSink: Sink<Response<'data>>,
     KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
 > {
     pub rng: &'rng Mutex<M, R>,
     // TODO: Move sym. key generation to own worker and get rid of key store here?
     pub key_store: Option<&'keystore Mutex<M, &'keystore mut KeyStore>>,
-    pub requests: ReqSrc,
-    pub responses: RespSink,
 }
 
 impl<
@@ -28,46 +25,9 @@ impl<
         'keystore,
         M: RawMutex,
         R: CryptoRng + RngCore,
-        ReqSrc: Stream<Item = Request<'data>> + Unpin,
-        RespSink: Sink<Response<'data>> + Unpin,
         KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
-    > RngWorker<'data, 'rng, 'keystore, M, R, ReqSrc, RespSink, KeyStore>
+    > RngWorker<'rng, 'keystore, M, R, KeyStore>
 {
-    /// Drive the worker to process the next request.
-    /// This method is supposed to be called by a system task that owns this worker.
-    pub async fn execute(&mut self) -> Result<(), Error> {
-        let request = self.requests.next().await.ok_or(Error::StreamTerminated)?;
-        let response = match request {
-            Request::GetRandom {
-                client_id,
-                request_id,
-                output,
-            } => self.get_random(client_id, request_id, output).await,
-            Request::GenerateSymmetricKey {
-                client_id,
-                request_id,
-                key_id,
-                overwrite,
-            } => {
-                if let Some(key_store) = self.key_store {
-                    self.generate_symmetric_key(client_id, request_id, key_id, overwrite, key_store)
-                        .await
-                } else {
-                    Response::Error {
-                        client_id,
-                        request_id,
-                        error: Error::NoKeyStore,
-                    }
-                }
-            }
-            _ => Err(Error::UnexpectedRequestType)?,
-        };
-        self.responses
-            .send(response)
-            .await
-            .map_err(|_e| Error::Send)
-    }
-
     async fn get_random(
         &mut self,
         client_id: ClientId,
@@ -130,3 +90,48 @@ impl<
         }
     }
 }
+
+impl<
+        'data,
+        'rng,
+        'keystore,
+        M: RawMutex,
+        R: CryptoRng + RngCore,
+        KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
+    > Worker<'data> for RngWorker<'rng, 'keystore, M, R, KeyStore>
+{
+    fn request_types(&self) -> &[RequestType] {
+        REQUEST_TYPES
+    }
+
+    async fn handle(&mut self, request: Request<'data>) -> Result<Response<'data>, Error> {
+        let response = match request {
+            Request::GetRandom {
+                client_id,
+                request_id,
+                output,
+                ..
+            } => self.get_random(client_id, request_id, output).await,
+            Request::GenerateSymmetricKey {
+                client_id,
+                request_id,
+                key_id,
+                overwrite,
+                ..
+            } => {
+                if let Some(key_store) = self.key_store {
+                    self.generate_symmetric_key(client_id, request_id, key_id, overwrite, key_store)
+                        .await
+                } else {
+                    Response::Error {
+                        client_id,
+                        request_id,
+                        error: Error::NoKeyStore,
+                    }
+                }
+            }
+            _ => Err(Error::UnexpectedRequestType)?,
+        };
+        Ok(response)
+    }
+}
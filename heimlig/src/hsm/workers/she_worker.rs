@@ -0,0 +1,125 @@
+use crate::common::jobs::{ClientId, Error, Request, RequestId, RequestType, Response};
+use crate::common::sync::{Mutex, RawMutex};
+use crate::crypto;
+use crate::hsm::keystore;
+use crate::hsm::she::SheKeySlots;
+use crate::hsm::workers::Worker;
+use core::ops::DerefMut;
+
+/// Request types handled by the worker
+const REQUEST_TYPES: &[RequestType] = &[RequestType::LoadSheKey, RequestType::FinishSheBoot];
+
+/// Worker for the SHE (Secure Hardware Extension) key update protocol and secure boot status.
+pub struct SheWorker<
+    'keystore,
+    M: RawMutex,
+    KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
+> {
+    pub key_store: &'keystore Mutex<M, &'keystore mut KeyStore>,
+    /// Slot model shared with the workers that use the keys of the SHE slots (e.g. `AesWorker`)
+    pub slots: &'keystore Mutex<M, SheKeySlots>,
+}
+
+impl<
+        'data,
+        'keystore,
+        M: RawMutex,
+        KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
+    > SheWorker<'keystore, M, KeyStore>
+{
+    #[allow(clippy::too_many_arguments)]
+    async fn load_key(
+        &mut self,
+        client_id: ClientId,
+        request_id: RequestId,
+        m1: &[u8],
+        m2: &[u8],
+        m3: &[u8],
+        m4: &'data mut [u8],
+        m5: &'data mut [u8],
+    ) -> Response<'data> {
+        let (Ok(m1), Ok(m2), Ok(m3), Ok(m4_array), Ok(m5_array)) = (
+            m1.try_into(),
+            m2.try_into(),
+            m3.try_into(),
+            (&mut *m4).try_into(),
+            (&mut *m5).try_into(),
+        ) else {
+            return Response::Error {
+                client_id,
+                request_id,
+                error: Error::Crypto(crypto::Error::InvalidBufferSize),
+            };
+        };
+        let mut locked_key_store = self.key_store.lock().await;
+        let result = self.slots.lock().await.load_key(
+            *locked_key_store.deref_mut(),
+            m1,
+            m2,
+            m3,
+            m4_array,
+            m5_array,
+        );
+        match result {
+            Ok(()) => Response::LoadSheKey {
+                client_id,
+                request_id,
+                m4,
+                m5,
+            },
+            Err(e) => Response::Error {
+                client_id,
+                request_id,
+                error: Error::She(e),
+            },
+        }
+    }
+}
+
+impl<
+        'data,
+        'keystore,
+        M: RawMutex,
+        KeyStore: keystore::KeyStore + keystore::InsecureKeyStore + Send,
+    > Worker<'data> for SheWorker<'keystore, M, KeyStore>
+{
+    fn request_types(&self) -> &[RequestType] {
+        REQUEST_TYPES
+    }
+
+    async fn handle(&mut self, request: Request<'data>) -> Result<Response<'data>, Error> {
+        let response = match request {
+            Request::LoadSheKey {
+                client_id,
+                request_id,
+                m1,
+                m2,
+                m3,
+                m4,
+                m5,
+                ..
+            } => {
+                self.load_key(client_id, request_id, m1, m2, m3, m4, m5)
+                    .await
+            }
+            Request::FinishSheBoot {
+                client_id,
+                request_id,
+                success,
+                ..
+            } => match self.slots.lock().await.finish_boot(success) {
+                Ok(()) => Response::FinishSheBoot {
+                    client_id,
+                    request_id,
+                },
+                Err(e) => Response::Error {
+                    client_id,
+                    request_id,
+                    error: Error::She(e),
+                },
+            },
+            _ => Err(Error::UnexpectedRequestType)?,
+        };
+        Ok(response)
+    }
+}
@@ -1,4 +1,5 @@
 use crate::common::jobs::{Request, Response};
+use crate::common::sync;
 use core::cell::RefCell;
 use core::pin::Pin;
 use core::task::{Context, Poll};
@@ -60,6 +61,14 @@ impl<T, const QUEUE_SIZE: usize> AsyncQueue<T, QUEUE_SIZE> {
     }
 }
 
+impl<T, const QUEUE_SIZE: usize> AsyncQueueSource<'_, T, QUEUE_SIZE> {
+    fn dequeue(&mut self) -> Option<T> {
+        let item = self.consumer.dequeue();
+        critical_section::with(|cs| self.sender_waker.borrow_ref_mut(cs).wake());
+        item
+    }
+}
+
 pub struct AsyncQueueSource<'ch, T, const QUEUE_SIZE: usize> {
     consumer: Consumer<'ch, T, QUEUE_SIZE>,
     receiver_waker: &'ch Mutex<RefCell<WakerRegistration>>,
@@ -76,15 +85,15 @@ impl<T, const QUEUE_SIZE: usize> futures::Stream for AsyncQueueSource<'_, T, QUE
     type Item = T;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if let Some(item) = self.consumer.dequeue() {
-            critical_section::with(|cs| self.sender_waker.borrow_ref_mut(cs).wake());
-            Poll::Ready(Some(item))
-        } else {
-            critical_section::with(|cs| {
-                self.receiver_waker.borrow_ref_mut(cs).register(cx.waker())
-            });
-            Poll::Pending
+        if self.consumer.ready() {
+            return Poll::Ready(self.dequeue());
         }
+        critical_section::with(|cs| self.receiver_waker.borrow_ref_mut(cs).register(cx.waker()));
+        // Check again in case the sender ran on another core before the waker was registered
+        if self.consumer.ready() {
+            return Poll::Ready(self.dequeue());
+        }
+        Poll::Pending
     }
 }
 
@@ -93,16 +102,21 @@ impl<T, const QUEUE_SIZE: usize> futures::Sink<T> for AsyncQueueSink<'_, T, QUEU
 
     fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         if self.producer.ready() {
-            Poll::Ready(Ok(()))
-        } else {
-            critical_section::with(|cs| self.sender_waker.borrow_ref_mut(cs).register(cx.waker()));
-            Poll::Pending
+            return Poll::Ready(Ok(()));
+        }
+        critical_section::with(|cs| self.sender_waker.borrow_ref_mut(cs).register(cx.waker()));
+        // Check again in case the receiver ran on another core before the waker was registered
+        if self.producer.ready() {
+            return Poll::Ready(Ok(()));
         }
+        Poll::Pending
     }
 
     fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
-        critical_section::with(|cs| self.receiver_waker.borrow_ref_mut(cs).wake());
-        let _ = self.get_mut().producer.enqueue(item);
+        let this = self.get_mut();
+        let _ = this.producer.enqueue(item);
+        // Wake the receiver only after the item is in the queue
+        critical_section::with(|cs| this.receiver_waker.borrow_ref_mut(cs).wake());
         Ok(())
     }
 
@@ -114,3 +128,13 @@ impl<T, const QUEUE_SIZE: usize> futures::Sink<T> for AsyncQueueSink<'_, T, QUEU
         Poll::Ready(Ok(()))
     }
 }
+
+// SAFETY: Embassy raw mutexes guarantee mutual exclusion for the closures passed to `lock`.
+unsafe impl<M: embassy_sync::blocking_mutex::raw::RawMutex> sync::RawMutex for M {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = <M as embassy_sync::blocking_mutex::raw::RawMutex>::INIT;
+
+    fn lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        embassy_sync::blocking_mutex::raw::RawMutex::lock(self, f)
+    }
+}
@@ -1,4 +1,5 @@
 use crate::hsm::keystore::{Error, InsecureKeyStore, KeyId, KeyInfo};
+use core::ops::Range;
 use heapless::Vec;
 
 pub struct MemoryKeyStore<const STORAGE_SIZE: usize, const MAX_KEYS: usize> {
@@ -29,11 +30,9 @@ impl<const STORAGE_SIZE: usize, const NUM_KEYS: usize> InsecureKeyStore
         if data.len() != key_layout.info.ty.key_size() {
             return Err(Error::InvalidBufferSize);
         }
-        let offset = key_layout.offset;
-        let size = data.len();
-        let dest = &mut self.storage[offset..(offset + size)];
-        dest.copy_from_slice(data);
-        key_layout.actual_size = data.len();
+        key_layout.public_key_size = 0;
+        key_layout.private_key_size = data.len();
+        self.storage[key_layout.private_key_range()].copy_from_slice(data);
         Ok(())
     }
 
@@ -45,26 +44,19 @@ impl<const STORAGE_SIZE: usize, const NUM_KEYS: usize> InsecureKeyStore
     ) -> Result<(), Error> {
         let key_layout = self.layout.get_mut(id).ok_or(Error::InvalidKeyId)?;
         assert!(key_layout.info.ty.is_asymmetric());
-        if (public_key.len() != 2 * private_key.len())
-            || (public_key.len() + private_key.len() != key_layout.info.ty.key_size())
+        if !key_layout
+            .info
+            .ty
+            .is_valid_key_pair_size(public_key.len(), private_key.len())
         {
             return Err(Error::InvalidBufferSize);
         }
-        // Copy public key
-        {
-            let offset = key_layout.offset;
-            let size = public_key.len();
-            let dest = &mut self.storage[offset..(offset + size)];
-            dest.copy_from_slice(public_key);
-        }
-        // Copy private key
-        {
-            let offset = key_layout.offset + public_key.len();
-            let size = private_key.len();
-            let dest = &mut self.storage[offset..(offset + size)];
-            dest.copy_from_slice(private_key);
-        }
-        key_layout.actual_size = public_key.len() + private_key.len();
+        // Clear remains of a previous, possibly longer key
+        self.storage[key_layout.slot_range()].fill(0);
+        key_layout.public_key_size = public_key.len();
+        key_layout.private_key_size = private_key.len();
+        self.storage[key_layout.public_key_range()].copy_from_slice(public_key);
+        self.storage[key_layout.private_key_range()].copy_from_slice(private_key);
         Ok(())
     }
 
@@ -75,18 +67,7 @@ impl<const STORAGE_SIZE: usize, const NUM_KEYS: usize> InsecureKeyStore
     ) -> Result<&'data [u8], Error> {
         let key_layout = self.layout.get(id).ok_or(Error::InvalidKeyId)?;
         assert!(key_layout.info.ty.is_symmetric());
-        if key_layout.actual_size == 0 {
-            return Err(Error::KeyNotFound);
-        }
-        if dest.len() < key_layout.actual_size {
-            return Err(Error::InvalidBufferSize);
-        }
-        let offset = key_layout.offset;
-        let size = key_layout.actual_size;
-        let src = &self.storage[offset..(offset + size)];
-        let dest = &mut dest[..src.len()];
-        dest.copy_from_slice(src);
-        Ok(dest)
+        self.export_range(key_layout, key_layout.private_key_range(), dest)
     }
 
     fn export_public_key_insecure<'data>(
@@ -96,21 +77,7 @@ impl<const STORAGE_SIZE: usize, const NUM_KEYS: usize> InsecureKeyStore
     ) -> Result<&'data [u8], Error> {
         let key_layout = self.layout.get(id).ok_or(Error::InvalidKeyId)?;
         assert!(key_layout.info.ty.is_asymmetric());
-        if key_layout.actual_size == 0 {
-            return Err(Error::KeyNotFound);
-        }
-        // When saving a key, we ensure the public key is twice as long as the private key
-        assert_eq!(key_layout.actual_size % 3, 0);
-        let private_key_size = key_layout.actual_size / 3;
-        let public_key_size = 2 * private_key_size;
-        if dest.len() < public_key_size {
-            return Err(Error::InvalidBufferSize);
-        }
-        let offset = key_layout.offset;
-        let src = &self.storage[offset..(offset + public_key_size)];
-        let dest = &mut dest[..src.len()];
-        dest.copy_from_slice(src);
-        Ok(dest)
+        self.export_range(key_layout, key_layout.public_key_range(), dest)
     }
 
     fn export_private_key_insecure<'data>(
@@ -120,62 +87,85 @@ impl<const STORAGE_SIZE: usize, const NUM_KEYS: usize> InsecureKeyStore
     ) -> Result<&'data [u8], Error> {
         let key_layout = self.layout.get(id).ok_or(Error::InvalidKeyId)?;
         assert!(key_layout.info.ty.is_asymmetric());
-        if key_layout.actual_size == 0 {
-            return Err(Error::KeyNotFound);
-        }
-        // When saving a key, we ensure the public key is twice as long as the private key
-        assert_eq!(key_layout.actual_size % 3, 0);
-        let private_key_size = key_layout.actual_size / 3;
-        let public_key_size = 2 * private_key_size;
-        if dest.len() < private_key_size {
-            return Err(Error::InvalidBufferSize);
-        }
-        let offset = key_layout.offset + public_key_size;
-        let src = &self.storage[offset..(offset + private_key_size)];
-        let dest = &mut dest[..src.len()];
-        dest.copy_from_slice(src);
-        Ok(dest)
+        self.export_range(key_layout, key_layout.private_key_range(), dest)
     }
 
     fn delete_insecure(&mut self, id: KeyId) -> Result<(), Error> {
         let key_layout = self.layout.get_mut(id).ok_or(Error::InvalidKeyId)?;
-        if key_layout.actual_size == 0 {
+        if key_layout.actual_size() == 0 {
             return Err(Error::KeyNotFound);
         }
-        let offset = key_layout.offset;
-        let size = key_layout.actual_size;
-        let key = &mut self.storage[offset..(offset + size)];
-        key.fill(0);
-        key_layout.actual_size = 0;
+        self.storage[key_layout.slot_range()].fill(0);
+        key_layout.public_key_size = 0;
+        key_layout.private_key_size = 0;
         Ok(())
     }
 
     fn is_key_available(&self, id: KeyId) -> bool {
         match self.layout.get(id) {
             None => false,
-            Some(key_layout) => key_layout.actual_size > 0,
+            Some(key_layout) => key_layout.actual_size() > 0,
         }
     }
 
     fn size(&self, id: KeyId) -> Result<usize, Error> {
         let key_layout = self.layout.get(id).ok_or(Error::InvalidKeyId)?;
-        if key_layout.actual_size == 0 {
+        if key_layout.actual_size() == 0 {
             return Err(Error::KeyNotFound);
         }
-        Ok(key_layout.actual_size)
+        Ok(key_layout.actual_size())
+    }
+}
+
+impl<const STORAGE_SIZE: usize, const MAX_KEYS: usize> MemoryKeyStore<STORAGE_SIZE, MAX_KEYS> {
+    fn export_range<'data>(
+        &self,
+        key_layout: &KeyLayout,
+        range: Range<usize>,
+        dest: &'data mut [u8],
+    ) -> Result<&'data [u8], Error> {
+        if key_layout.actual_size() == 0 {
+            return Err(Error::KeyNotFound);
+        }
+        let src = &self.storage[range];
+        let dest = dest.get_mut(..src.len()).ok_or(Error::InvalidBufferSize)?;
+        dest.copy_from_slice(src);
+        Ok(dest)
     }
 }
 
 /// Internal layout data structure of the key store. Keys are saved at an offset in the internal key
-/// buffer. The public and private keys of an asymmetric keys are concatenated.
+/// buffer. Each slot reserves the maximum public key size followed by the maximum private key size
+/// of its key type. Symmetric keys are stored in the private key part.
 #[derive(Copy, Clone, Debug)]
 struct KeyLayout {
     /// Static information about this key
     info: KeyInfo,
     /// Offset at which this key start in the internal key buffer of the store.
     offset: usize,
-    /// The real size of this key (in contrast to its maximum size)
-    actual_size: usize,
+    /// The real size of the public key (in contrast to its maximum size)
+    public_key_size: usize,
+    /// The real size of the private or symmetric key (in contrast to its maximum size)
+    private_key_size: usize,
+}
+
+impl KeyLayout {
+    fn actual_size(&self) -> usize {
+        self.public_key_size + self.private_key_size
+    }
+
+    fn slot_range(&self) -> Range<usize> {
+        self.offset..(self.offset + self.info.ty.key_size())
+    }
+
+    fn public_key_range(&self) -> Range<usize> {
+        self.offset..(self.offset + self.public_key_size)
+    }
+
+    fn private_key_range(&self) -> Range<usize> {
+        let offset = self.offset + self.info.ty.public_key_size();
+        offset..(offset + self.private_key_size)
+    }
 }
 
 /// Keeps a sorted list of `KeyLayout`s
@@ -235,7 +225,8 @@ impl<const STORAGE_SIZE: usize, const MAX_KEYS: usize> TryFrom<&[KeyInfo]>
             let key_layout = KeyLayout {
                 info: *key_info,
                 offset,
-                actual_size: 0,
+                public_key_size: 0,
+                private_key_size: 0,
             };
             ret.inner
                 .push(key_layout)
@@ -249,7 +240,9 @@ impl<const STORAGE_SIZE: usize, const MAX_KEYS: usize> TryFrom<&[KeyInfo]>
 #[cfg(test)]
 pub(crate) mod test {
     use super::*;
-    use crate::hsm::keystore::{Curve, Error, KeyId, KeyInfo, KeyPermissions, KeyStore, KeyType};
+    use crate::hsm::keystore::{
+        Curve, Error, KeyId, KeyInfo, KeyPairAlgorithm, KeyPermissions, KeyStore, KeyType,
+    };
 
     const TOTAL_KEY_SIZE: usize = KEY1_INFO.ty.key_size() + KEY2_INFO.ty.key_size();
     const KEY1_INFO: KeyInfo = KeyInfo {
@@ -438,4 +431,120 @@ pub(crate) mod test {
             .import_symmetric_key(NO_EXPORT_OVERWRITE_NO_DELETE.id, &src_buffer, true)
             .is_ok());
     }
+
+    #[test]
+    fn variable_size_key_pairs() {
+        const ED25519_KEY_INFO: KeyInfo = KeyInfo {
+            id: KeyId(0),
+            ty: KeyType::KeyPair {
+                algorithm: KeyPairAlgorithm::Ed25519,
+                public_key_size: 32,
+                private_key_size: 32,
+            },
+            permissions: KeyPermissions {
+                import: true,
+                export_private: true,
+                overwrite: true,
+                delete: true,
+            },
+        };
+        // Algorithm of the integrator with the key sizes of ML-DSA-44 (FIPS 204). The private key can
+        // also be stored as its 32 byte seed.
+        const LARGE_KEY_INFO: KeyInfo = KeyInfo {
+            id: KeyId(2),
+            ty: KeyType::KeyPair {
+                algorithm: KeyPairAlgorithm::Other(44),
+                public_key_size: 1312,
+                private_key_size: 2560,
+            },
+            ..ED25519_KEY_INFO
+        };
+        let key_infos: [KeyInfo; 3] = [ED25519_KEY_INFO, KEY2_INFO, LARGE_KEY_INFO];
+        let mut dest_buffer = [0u8; LARGE_KEY_INFO.ty.private_key_size() + 1];
+        let mut key_store = MemoryKeyStore::<
+            {
+                ED25519_KEY_INFO.ty.key_size()
+                    + KEY2_INFO.ty.key_size()
+                    + LARGE_KEY_INFO.ty.key_size()
+            },
+            3,
+        >::try_new(&key_infos)
+        .expect("failed to create key store");
+        assert_eq!(
+            KeyStore::get_key_info(&key_store, LARGE_KEY_INFO.id)
+                .map(|info| info.ty.key_pair_algorithm()),
+            Ok(Some(KeyPairAlgorithm::Other(44)))
+        );
+
+        assert!(key_store
+            .import_key_pair(ED25519_KEY_INFO.id, &[1u8; 32], &[2u8; 32], false)
+            .is_ok());
+        assert_eq!(KeyStore::size(&key_store, ED25519_KEY_INFO.id), Ok(64));
+        assert_eq!(
+            key_store.export_public_key(ED25519_KEY_INFO.id, &mut dest_buffer),
+            Ok(&[1u8; 32][..])
+        );
+        assert_eq!(
+            key_store.export_private_key(ED25519_KEY_INFO.id, &mut dest_buffer),
+            Ok(&[2u8; 32][..])
+        );
+
+        // Expanded private key
+        assert!(key_store
+            .import_key_pair(LARGE_KEY_INFO.id, &[3u8; 1312], &[4u8; 2560], false)
+            .is_ok());
+        assert_eq!(
+            KeyStore::size(&key_store, LARGE_KEY_INFO.id),
+            Ok(1312 + 2560)
+        );
+        assert_eq!(
+            key_store
+                .export_public_key(LARGE_KEY_INFO.id, &mut dest_buffer)
+                .map(|key| key.len()),
+            Ok(1312)
+        );
+        assert_eq!(
+            key_store.export_private_key(LARGE_KEY_INFO.id, &mut dest_buffer[..2559]),
+            Err(Error::InvalidBufferSize)
+        );
+
+        // Overwriting with the seed of a private key does not leak the previous key
+        assert!(key_store
+            .import_key_pair(LARGE_KEY_INFO.id, &[5u8; 1312], &[6u8; 32], true)
+            .is_ok());
+        assert_eq!(
+            key_store.export_private_key(LARGE_KEY_INFO.id, &mut dest_buffer),
+            Ok(&[6u8; 32][..])
+        );
+        assert_eq!(
+            key_store.export_public_key(LARGE_KEY_INFO.id, &mut dest_buffer),
+            Ok(&[5u8; 1312][..])
+        );
+
+        // Sizes exceeding the slot or empty keys are rejected
+        let large_key = [0u8; 2561];
+        for (id, public_key_size, private_key_size) in [
+            (ED25519_KEY_INFO.id, 33, 32),
+            (ED25519_KEY_INFO.id, 32, 33),
+            (ED25519_KEY_INFO.id, 0, 0),
+            (LARGE_KEY_INFO.id, 1313, 2560),
+            (LARGE_KEY_INFO.id, 1312, 2561),
+        ] {
+            assert_eq!(
+                key_store.import_key_pair(
+                    id,
+                    &large_key[..public_key_size],
+                    &large_key[..private_key_size],
+                    true
+                ),
+                Err(Error::InvalidBufferSize)
+            );
+        }
+
+        // Keys of supported curves have to match their sizes exactly
+        assert_eq!(
+            key_store.import_key_pair(KEY2_INFO.id, &[0u8; 33], &[0u8; 32], false),
+            Err(Error::InvalidBufferSize)
+        );
+    }
 }
@@ -1,4 +1,11 @@
 pub mod embassy;
 pub mod memory_key_store;
+pub mod raw_blocking;
 pub mod raw_errors;
 pub mod raw_jobs;
+pub mod raw_she;
+pub mod rtic;
+pub mod shared_ring;
+#[cfg(feature = "std")]
+pub mod std_sync;
+pub mod wire;
@@ -0,0 +1,84 @@
+//! Blocking calls of raw requests for C host code without an async executor.
+//!
+//! The client provides its transport as a [BlockingTransportRaw] of C functions that send
+//! [RequestRaw]s, receive [ResponseRaw]s and wait for progress, e.g. by spinning, with `WFE` or on
+//! an RTOS semaphore. [heimlig_call_blocking] sends a request and blocks until its response
+//! arrives.
+//!
+//! The Rust version is [BlockingApi](crate::client::blocking::BlockingApi).
+
+use crate::integration::raw_jobs::{RequestRaw, ResponseRaw};
+use core::ffi::c_void;
+
+type CallStatusRaw = u32;
+
+/// The response of the request was received. It may be an error response.
+pub const CALL_OK: CallStatusRaw = 0;
+/// A pointer argument or a function of the transport is null.
+pub const CALL_INVALID_ARGUMENT: CallStatusRaw = 1;
+
+/// Transport of a blocking C client. All functions get `context` as their first argument.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct BlockingTransportRaw {
+    pub context: *mut c_void,
+    /// Enqueue a copy of `request`. Returns `false` if the queue is full.
+    pub send:
+        Option<unsafe extern "C" fn(context: *mut c_void, request: *const RequestRaw) -> bool>,
+    /// Dequeue the next response into `response`. Returns `false` if no response is available.
+    pub receive:
+        Option<unsafe extern "C" fn(context: *mut c_void, response: *mut ResponseRaw) -> bool>,
+    /// Block until the queues might have changed. Returning early is allowed.
+    pub wait: Option<unsafe extern "C" fn(context: *mut c_void)>,
+}
+
+/// Send `request` through `transport` and block until its response is written to `response`.
+///
+/// Responses are matched by the request ID. Responses of other requests are discarded, so only one
+/// request of the client may be pending at a time.
+///
+/// returns: [CALL_OK] when the response was received or [CALL_INVALID_ARGUMENT].
+///
+/// # Safety
+///
+/// All pointers must be null or valid. The functions of `transport` must uphold their contracts
+/// documented on [BlockingTransportRaw].
+#[no_mangle]
+pub unsafe extern "C" fn heimlig_call_blocking(
+    transport: *const BlockingTransportRaw,
+    request: *const RequestRaw,
+    response: *mut ResponseRaw,
+) -> CallStatusRaw {
+    // SAFETY: The caller guarantees that the pointers are null or valid.
+    let (Some(transport), Some(request)) =
+        (unsafe { transport.as_ref() }, unsafe { request.as_ref() })
+    else {
+        return CALL_INVALID_ARGUMENT;
+    };
+    let BlockingTransportRaw {
+        context,
+        send: Some(send),
+        receive: Some(receive),
+        wait: Some(wait),
+    } = *transport
+    else {
+        return CALL_INVALID_ARGUMENT;
+    };
+    if response.is_null() {
+        return CALL_INVALID_ARGUMENT;
+    }
+
+    // SAFETY: The caller guarantees that the transport functions are valid.
+    unsafe {
+        while !send(context, request) {
+            wait(context);
+        }
+        loop {
+            if !receive(context, response) {
+                wait(context);
+            } else if (*response).request_id == request.request_id {
+                return CALL_OK;
+            }
+        }
+    }
+}
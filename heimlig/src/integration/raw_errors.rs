@@ -1,6 +1,7 @@
 use crate::common::jobs;
 use crate::crypto;
 use crate::hsm::keystore;
+use crate::hsm::she;
 
 /// Raw version of jobs::Error
 #[repr(C, u8)]
@@ -22,6 +23,30 @@ pub enum JobErrorRaw {
     Crypto(CryptoErrorRaw),
     /// A key store error occurred.
     KeyStore(KeyStoreErrorRaw),
+    /// A SHE error occurred.
+    She(SheErrorRaw),
+    /// A limit of the client was exceeded.
+    LimitExceeded(LimitRaw),
+    /// The worker responsible for the request is unavailable.
+    WorkerUnavailable,
+    /// A buffer of the request is outside the memory regions of the client.
+    AccessDenied,
+    /// The HSM does not support cancellations.
+    CancellationUnsupported,
+    /// The request to cancel is not pending at a worker or too many requests are cancelled.
+    NotCancellable,
+}
+
+/// Raw version of jobs::Limit
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LimitRaw {
+    /// Too many requests of the client are already being processed by workers.
+    InFlight,
+    /// The request rate of the client for this request type was exceeded.
+    Rate,
+    /// The request carries more data than allowed for the client.
+    RequestSize,
 }
 
 /// Raw version of crypto::Error
@@ -80,6 +105,36 @@ pub enum KeyStoreErrorRaw {
     InvalidBufferSize,
 }
 
+/// Raw version of she::Error. The discriminants are the SHE error codes.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SheErrorRaw {
+    /// The command sequence was invalid.
+    SequenceError = 0x1,
+    /// The key is not available (e.g. locked by boot or debugger protection).
+    KeyNotAvailable = 0x2,
+    /// The key ID is invalid for this command.
+    KeyInvalid = 0x3,
+    /// The key slot is empty.
+    KeyEmpty = 0x4,
+    /// Secure boot did not finish successfully.
+    NoSecureBoot = 0x5,
+    /// The key slot is write-protected.
+    KeyWriteProtected = 0x6,
+    /// The key update failed (invalid MAC, UID or counter).
+    KeyUpdateError = 0x7,
+    /// The random number generator was not seeded.
+    RngSeed = 0x8,
+    /// Debugging is not allowed.
+    NoDebugging = 0x9,
+    /// The SHE is busy.
+    Busy = 0xA,
+    /// Key storage failed.
+    MemoryFailure = 0xB,
+    /// An unspecified error occurred.
+    GeneralError = 0xC,
+}
+
 impl From<jobs::Error> for JobErrorRaw {
     fn from(value: jobs::Error) -> Self {
         match value {
@@ -91,42 +146,113 @@ impl From<jobs::Error> for JobErrorRaw {
             jobs::Error::StreamTerminated => JobErrorRaw::StreamTerminated,
             jobs::Error::Crypto(e) => JobErrorRaw::Crypto(e.into()),
             jobs::Error::KeyStore(e) => JobErrorRaw::KeyStore(e.into()),
+            jobs::Error::She(e) => JobErrorRaw::She(e.into()),
+            jobs::Error::LimitExceeded(l) => JobErrorRaw::LimitExceeded(l.into()),
+            jobs::Error::WorkerUnavailable => JobErrorRaw::WorkerUnavailable,
+            jobs::Error::AccessDenied => JobErrorRaw::AccessDenied,
+            jobs::Error::CancellationUnsupported => JobErrorRaw::CancellationUnsupported,
+            jobs::Error::NotCancellable => JobErrorRaw::NotCancellable,
         }
     }
 }
 
-impl From<crypto::Error> for CryptoErrorRaw {
-    fn from(value: crypto::Error) -> Self {
+impl From<JobErrorRaw> for jobs::Error {
+    fn from(value: JobErrorRaw) -> Self {
         match value {
-            crypto::Error::Encrypt => CryptoErrorRaw::Encrypt,
-            crypto::Error::Decrypt => CryptoErrorRaw::Decrypt,
-            crypto::Error::Sign => CryptoErrorRaw::Sign,
-            crypto::Error::Verify => CryptoErrorRaw::Verify,
-            crypto::Error::InvalidSymmetricKeySize => CryptoErrorRaw::InvalidSymmetricKeySize,
-            crypto::Error::InvalidIvSize => CryptoErrorRaw::InvalidIvSize,
-            crypto::Error::InvalidTagSize => CryptoErrorRaw::InvalidTagSize,
-            crypto::Error::InvalidBufferSize => CryptoErrorRaw::InvalidBufferSize,
-            crypto::Error::InvalidPadding => CryptoErrorRaw::InvalidPadding,
-            crypto::Error::InvalidPrivateKey => CryptoErrorRaw::InvalidPrivateKey,
-            crypto::Error::InvalidPublicKey => CryptoErrorRaw::InvalidPublicKey,
-            crypto::Error::InvalidSignatureSize => CryptoErrorRaw::InvalidSignatureSize,
-            crypto::Error::InvalidSignature => CryptoErrorRaw::InvalidSignature,
-            crypto::Error::InvalidDigestSize => CryptoErrorRaw::InvalidDigestSize,
+            JobErrorRaw::NoWorkerForRequest => jobs::Error::NoWorkerForRequest,
+            JobErrorRaw::UnexpectedRequestType => jobs::Error::UnexpectedRequestType,
+            JobErrorRaw::RequestTooLarge => jobs::Error::RequestTooLarge,
+            JobErrorRaw::NoKeyStore => jobs::Error::NoKeyStore,
+            JobErrorRaw::Send => jobs::Error::Send,
+            JobErrorRaw::StreamTerminated => jobs::Error::StreamTerminated,
+            JobErrorRaw::Crypto(e) => jobs::Error::Crypto(e.into()),
+            JobErrorRaw::KeyStore(e) => jobs::Error::KeyStore(e.into()),
+            JobErrorRaw::She(e) => jobs::Error::She(e.into()),
+            JobErrorRaw::LimitExceeded(l) => jobs::Error::LimitExceeded(l.into()),
+            JobErrorRaw::WorkerUnavailable => jobs::Error::WorkerUnavailable,
+            JobErrorRaw::AccessDenied => jobs::Error::AccessDenied,
+            JobErrorRaw::CancellationUnsupported => jobs::Error::CancellationUnsupported,
+            JobErrorRaw::NotCancellable => jobs::Error::NotCancellable,
         }
     }
 }
 
-impl From<keystore::Error> for KeyStoreErrorRaw {
-    fn from(value: keystore::Error) -> Self {
-        match value {
-            keystore::Error::NotAllowed => KeyStoreErrorRaw::NotAllowed,
-            keystore::Error::KeyNotFound => KeyStoreErrorRaw::KeyNotFound,
-            keystore::Error::KeyAlreadyExists => KeyStoreErrorRaw::KeyAlreadyExists,
-            keystore::Error::KeyStoreTooSmall => KeyStoreErrorRaw::KeyStoreTooSmall,
-            keystore::Error::DuplicateIds => KeyStoreErrorRaw::DuplicateIds,
-            keystore::Error::InvalidKeyId => KeyStoreErrorRaw::InvalidKeyId,
-            keystore::Error::InvalidKeyType => KeyStoreErrorRaw::InvalidKeyType,
-            keystore::Error::InvalidBufferSize => KeyStoreErrorRaw::InvalidBufferSize,
+/// Conversions in both directions between a field-less enum and its raw version, which has the
+/// same variants.
+macro_rules! mirror_enum {
+    ($ty:ty, $raw:ident, [$($variant:ident),* $(,)?]) => {
+        impl From<$ty> for $raw {
+            fn from(value: $ty) -> Self {
+                type Type = $ty;
+                match value {
+                    $(Type::$variant => $raw::$variant,)*
+                }
+            }
         }
-    }
+
+        impl From<$raw> for $ty {
+            fn from(value: $raw) -> Self {
+                match value {
+                    $($raw::$variant => Self::$variant,)*
+                }
+            }
+        }
+    };
 }
+
+mirror_enum!(jobs::Limit, LimitRaw, [InFlight, Rate, RequestSize,]);
+
+mirror_enum!(
+    crypto::Error,
+    CryptoErrorRaw,
+    [
+        Encrypt,
+        Decrypt,
+        Sign,
+        Verify,
+        InvalidSymmetricKeySize,
+        InvalidIvSize,
+        InvalidTagSize,
+        InvalidBufferSize,
+        InvalidPadding,
+        InvalidPrivateKey,
+        InvalidPublicKey,
+        InvalidSignatureSize,
+        InvalidSignature,
+        InvalidDigestSize,
+    ]
+);
+
+mirror_enum!(
+    keystore::Error,
+    KeyStoreErrorRaw,
+    [
+        NotAllowed,
+        KeyNotFound,
+        KeyAlreadyExists,
+        KeyStoreTooSmall,
+        DuplicateIds,
+        InvalidKeyId,
+        InvalidKeyType,
+        InvalidBufferSize,
+    ]
+);
+
+mirror_enum!(
+    she::Error,
+    SheErrorRaw,
+    [
+        SequenceError,
+        KeyNotAvailable,
+        KeyInvalid,
+        KeyEmpty,
+        NoSecureBoot,
+        KeyWriteProtected,
+        KeyUpdateError,
+        RngSeed,
+        NoDebugging,
+        Busy,
+        MemoryFailure,
+        GeneralError,
+    ]
+);
@@ -1,17 +1,34 @@
-use crate::common::jobs::{HashAlgorithm, Request, Response};
-use crate::hsm::keystore::{Curve, KeyId};
+use crate::common::jobs::{
+    self, CancelReason, Capabilities, ClientId, Curves, HashAlgorithm, HashAlgorithms, KeyKinds,
+    Padding, PrivateKeyFormat, PublicKeyFormat, Request, RequestId, RequestTypes, Response,
+    SignatureFormat, SymmetricPrimitives,
+};
+use crate::hsm::keystore::{Curve, KeyId, KeyInfo, KeyPairAlgorithm, KeyPermissions, KeyType};
 use crate::integration::raw_errors::JobErrorRaw;
+use core::cell::RefCell;
 use core::mem::{offset_of, MaybeUninit};
 use core::slice;
 use strum::EnumCount;
 
 type ClientIdRaw = u32;
 type RequestIdRaw = u32;
+type DeadlineRaw = u64;
 type KeyIdRaw = u32;
 type CurveRaw = u32;
 type HashAlgorithmRaw = u32;
+type PaddingRaw = u32;
+type PrivateKeyFormatRaw = u32;
+type PublicKeyFormatRaw = u32;
+type SignatureFormatRaw = u32;
+type CancelReasonRaw = u32;
+type KeyTypeRaw = u32;
+type KeyPairAlgorithmRaw = u32;
+type SymmetricPrimitiveRaw = u32;
 type BoolRaw = u32; // 0 == false, 1 == true
 
+/// Value of the `deadline` field of requests without a deadline.
+pub const NO_DEADLINE: DeadlineRaw = u64::MAX;
+
 pub const NIST_P256: CurveRaw = 0;
 pub const NIST_P384: CurveRaw = 1;
 
@@ -21,6 +38,138 @@ pub const SHA2_512: HashAlgorithmRaw = 2;
 pub const SHA3_256: HashAlgorithmRaw = 3;
 pub const SHA3_384: HashAlgorithmRaw = 4;
 pub const SHA3_512: HashAlgorithmRaw = 5;
+/// Selects the default hash algorithm of the curve in `Sign` and `Verify` requests.
+pub const CURVE_DEFAULT_HASH: HashAlgorithmRaw = 0xFFFF_FFFF;
+
+pub const PKCS7: PaddingRaw = 0;
+pub const NO_PADDING: PaddingRaw = 1;
+
+pub const PRIVATE_KEY_RAW: PrivateKeyFormatRaw = 0;
+pub const PRIVATE_KEY_PKCS8_DER: PrivateKeyFormatRaw = 1;
+pub const PRIVATE_KEY_SEC1_DER: PrivateKeyFormatRaw = 2;
+pub const PRIVATE_KEY_PEM: PrivateKeyFormatRaw = 3;
+
+pub const PUBLIC_KEY_RAW: PublicKeyFormatRaw = 0;
+pub const PUBLIC_KEY_SEC1_UNCOMPRESSED: PublicKeyFormatRaw = 1;
+pub const PUBLIC_KEY_SEC1_COMPRESSED: PublicKeyFormatRaw = 2;
+pub const PUBLIC_KEY_SPKI_DER: PublicKeyFormatRaw = 3;
+pub const PUBLIC_KEY_SPKI_PEM: PublicKeyFormatRaw = 4;
+
+pub const SIGNATURE_RAW: SignatureFormatRaw = 0;
+pub const SIGNATURE_DER: SignatureFormatRaw = 1;
+
+pub const CANCEL_REASON_CANCELLED: CancelReasonRaw = 0;
+pub const CANCEL_REASON_EXPIRED: CancelReasonRaw = 1;
+
+pub const KEY_TYPE_SYMMETRIC: KeyTypeRaw = 0;
+pub const KEY_TYPE_ASYMMETRIC: KeyTypeRaw = 1;
+pub const KEY_TYPE_KEY_PAIR: KeyTypeRaw = 2;
+
+pub const KEY_PAIR_ED25519: KeyPairAlgorithmRaw = 0;
+pub const KEY_PAIR_X25519: KeyPairAlgorithmRaw = 1;
+/// Algorithm defined by the integrator. The algorithm is stored in `other_algorithm`.
+pub const KEY_PAIR_OTHER: KeyPairAlgorithmRaw = 0xFFFF_FFFF;
+
+pub const KEY_PERMISSION_IMPORT: u32 = 1 << 0;
+pub const KEY_PERMISSION_EXPORT_PRIVATE: u32 = 1 << 1;
+pub const KEY_PERMISSION_OVERWRITE: u32 = 1 << 2;
+pub const KEY_PERMISSION_DELETE: u32 = 1 << 3;
+
+pub const SYMMETRIC_CHACHA20_POLY1305: SymmetricPrimitiveRaw = 0;
+pub const SYMMETRIC_AES_GCM: SymmetricPrimitiveRaw = 1;
+pub const SYMMETRIC_AES_CBC: SymmetricPrimitiveRaw = 2;
+pub const SYMMETRIC_AES_ECB: SymmetricPrimitiveRaw = 3;
+pub const SYMMETRIC_AES_CMAC: SymmetricPrimitiveRaw = 4;
+pub const SYMMETRIC_HMAC: SymmetricPrimitiveRaw = 5;
+
+/// First word of all versioned requests and responses, the bytes `HMLG` in little-endian byte
+/// order.
+pub const ABI_MAGIC: u32 = 0x474C_4D48;
+
+/// Major version of the raw ABI. Incremented on changes that break existing clients, e.g. when the
+/// layout of a request or response changes.
+pub const ABI_VERSION_MAJOR: u16 = 1;
+/// Minor version of the raw ABI. Incremented when request or response types are added.
+pub const ABI_VERSION_MINOR: u16 = 1;
+
+/// Version of the raw ABI that a client was built for or that the HSM implements.
+///
+/// Requests and responses carry the version at the very beginning, where it stays in all future
+/// versions of the ABI. Both sides reject requests and responses of another major version with
+/// [ValidationError::UnsupportedAbiVersion]. Clients built for an older minor version keep
+/// working, because request and response codes are never changed or reused. Clients built for a
+/// newer minor version can use `GetCapabilities` to find out which requests the HSM supports.
+/// Requests unknown to the HSM are rejected with [ValidationError::InvalidTagValue].
+///
+/// The version starts with [ABI_MAGIC]. Requests and responses of clients built before the ABI was
+/// versioned start with the client ID instead and are rejected as well.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AbiVersion {
+    pub magic: u32,
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl AbiVersion {
+    /// Version of the raw ABI implemented by this crate
+    pub const CURRENT: AbiVersion = AbiVersion::new(ABI_VERSION_MAJOR, ABI_VERSION_MINOR);
+
+    pub const fn new(major: u16, minor: u16) -> Self {
+        AbiVersion {
+            magic: ABI_MAGIC,
+            major,
+            minor,
+        }
+    }
+
+    /// Whether requests and responses of this version can be exchanged with ones of the current
+    /// version.
+    pub const fn is_supported(&self) -> bool {
+        self.magic == ABI_MAGIC && self.major == ABI_VERSION_MAJOR
+    }
+}
+
+/// Raw version of [Capabilities]. The sets of request types, hash algorithms, curves, symmetric
+/// primitives and key types are bit masks: Bit `n` is set if the request type or value with code
+/// `n` is supported.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CapabilitiesRaw {
+    pub abi_version: AbiVersion,
+    pub key_store: BoolRaw,
+    pub request_types: u64,
+    pub hash_algorithms: u64,
+    pub curves: u64,
+    /// Bit mask of the `SYMMETRIC_*` codes
+    pub symmetric_primitives: u64,
+    /// Bit mask of the `KEY_TYPE_*` codes
+    pub key_types: u64,
+    pub max_symmetric_key_size: u32,
+    /// `u32::MAX` if the size is not limited
+    pub max_request_size: u32,
+}
+
+/// Raw version of [KeyInfo]. Fields that do not apply to the key type are zero.
+///
+/// | `key_type`            | Fields                                                        |
+/// |-----------------------|---------------------------------------------------------------|
+/// | `KEY_TYPE_SYMMETRIC`  | `private_key_size`                                            |
+/// | `KEY_TYPE_ASYMMETRIC` | `curve`                                                       |
+/// | `KEY_TYPE_KEY_PAIR`   | `algorithm`, `other_algorithm`, `public_key_size`, `private_key_size` |
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct KeyInfoRaw {
+    pub key_id: KeyIdRaw,
+    pub key_type: KeyTypeRaw,
+    pub curve: CurveRaw,
+    pub algorithm: KeyPairAlgorithmRaw,
+    pub other_algorithm: u32,
+    pub public_key_size: u32,
+    pub private_key_size: u32,
+    /// Bit mask of the `KEY_PERMISSION_*` constants
+    pub permissions: u32,
+}
 
 /// A pair of a raw request and a raw response. This is a convenience type for integrators to
 /// allocate all necessary memory for a request and its response in one go.
@@ -31,7 +180,6 @@ pub struct RequestResponseRawPair {
     pub response: ResponseRaw,
 }
 
-// TODO: Raw types could be generated by a macro from the non-raw type
 /// Raw request as it is written by clients to shared memory. This type is supposed to be synced
 /// with non-Rust (e.g. C++) clients via cbindgen.
 ///
@@ -45,60 +193,78 @@ pub struct RequestResponseRawPair {
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub struct RequestRaw {
+    pub abi_version: AbiVersion,
     pub client_id: ClientIdRaw,
     pub request_id: RequestIdRaw,
+    pub deadline: DeadlineRaw,
     data: RequestDataRaw,
 }
 
-/// Raw request as it is written by clients to shared memory. This type is supposed to be synced
+/// Raw response as it is written by clients to shared memory. This type is supposed to be synced
 /// with non-Rust (e.g. C++) clients via cbindgen.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ResponseRaw {
+    pub abi_version: AbiVersion,
+    pub client_id: ClientIdRaw,
+    pub request_id: RequestIdRaw,
+    data: ResponseDataRaw,
+}
+
+/// Raw request as it is written by clients to shared memory. This type is supposed to be
+/// synced with non-Rust (e.g. C++) clients via cbindgen.
+///
+/// The discriminants are the codes of the [RequestType](crate::common::jobs::RequestType)s.
+/// Like those, they are assigned consecutively and never changed or reused.
 #[repr(C, u8)]
 #[derive(Clone, Copy, Debug, EnumCount)]
 pub enum RequestDataRaw {
     GetRandom {
         output_data: *mut u8,
         output_size: u32,
-    },
+    } = 0,
     GenerateSymmetricKey {
         key_id: KeyIdRaw,
         overwrite: BoolRaw,
-    },
+    } = 1,
     GenerateKeyPair {
         key_id: KeyIdRaw,
         overwrite: BoolRaw,
-    },
+    } = 2,
     ImportSymmetricKey {
         key_id: KeyIdRaw,
         data_data: *const u8,
         data_size: u32,
         overwrite: BoolRaw,
-    },
+    } = 3,
     ImportKeyPair {
         key_id: KeyIdRaw,
         public_key_data: *const u8,
         public_key_size: u32,
         private_key_data: *const u8,
         private_key_size: u32,
+        format: PrivateKeyFormatRaw,
         overwrite: BoolRaw,
-    },
+    } = 4,
     ExportSymmetricKey {
         key_id: KeyIdRaw,
         data_data: *mut u8,
         data_size: u32,
-    },
+    } = 5,
     ExportPublicKey {
         key_id: KeyIdRaw,
         public_key_data: *mut u8,
         public_key_size: u32,
-    },
+        format: PublicKeyFormatRaw,
+    } = 6,
     ExportPrivateKey {
         key_id: KeyIdRaw,
         private_key_data: *mut u8,
         private_key_size: u32,
-    },
+    } = 7,
     IsKeyAvailable {
         key_id: KeyIdRaw,
-    },
+    } = 8,
     EncryptChaChaPoly {
         key_id: KeyIdRaw,
         nonce_data: *const u8,
@@ -109,7 +275,7 @@ pub enum RequestDataRaw {
         aad_size: u32,
         tag_data: *mut u8,
         tag_size: u32,
-    },
+    } = 9,
     EncryptChaChaPolyExternalKey {
         key_data: *const u8,
         key_size: u32,
@@ -121,7 +287,7 @@ pub enum RequestDataRaw {
         aad_size: u32,
         tag_data: *mut u8,
         tag_size: u32,
-    },
+    } = 10,
     DecryptChaChaPoly {
         key_id: KeyIdRaw,
         nonce_data: *const u8,
@@ -132,7 +298,7 @@ pub enum RequestDataRaw {
         aad_size: u32,
         tag_data: *const u8,
         tag_size: u32,
-    },
+    } = 11,
     DecryptChaChaPolyExternalKey {
         key_data: *const u8,
         key_size: u32,
@@ -144,7 +310,7 @@ pub enum RequestDataRaw {
         aad_size: u32,
         tag_data: *const u8,
         tag_size: u32,
-    },
+    } = 12,
     EncryptAesGcm {
         key_id: KeyIdRaw,
         iv_data: *const u8,
@@ -155,7 +321,7 @@ pub enum RequestDataRaw {
         aad_size: u32,
         tag_data: *mut u8,
         tag_size: u32,
-    },
+    } = 13,
     EncryptAesGcmExternalKey {
         key_data: *const u8,
         key_size: u32,
@@ -167,7 +333,7 @@ pub enum RequestDataRaw {
         aad_size: u32,
         tag_data: *mut u8,
         tag_size: u32,
-    },
+    } = 14,
     DecryptAesGcm {
         key_id: KeyIdRaw,
         iv_data: *const u8,
@@ -178,7 +344,7 @@ pub enum RequestDataRaw {
         aad_size: u32,
         tag_data: *const u8,
         tag_size: u32,
-    },
+    } = 15,
     DecryptAesGcmExternalKey {
         key_data: *const u8,
         key_size: u32,
@@ -190,7 +356,7 @@ pub enum RequestDataRaw {
         aad_size: u32,
         tag_data: *const u8,
         tag_size: u32,
-    },
+    } = 16,
     EncryptAesCbc {
         key_id: KeyIdRaw,
         iv_data: *const u8,
@@ -198,7 +364,8 @@ pub enum RequestDataRaw {
         buffer_data: *mut u8,
         buffer_size: u32,
         plaintext_size: u32,
-    },
+        padding: PaddingRaw,
+    } = 17,
     EncryptAesCbcExternalKey {
         key_data: *const u8,
         key_size: u32,
@@ -207,14 +374,16 @@ pub enum RequestDataRaw {
         buffer_data: *mut u8,
         buffer_size: u32,
         plaintext_size: u32,
-    },
+        padding: PaddingRaw,
+    } = 18,
     DecryptAesCbc {
         key_id: KeyIdRaw,
         iv_data: *const u8,
         iv_size: u32,
         buffer_data: *mut u8,
         buffer_size: u32,
-    },
+        padding: PaddingRaw,
+    } = 19,
     DecryptAesCbcExternalKey {
         key_data: *const u8,
         key_size: u32,
@@ -222,14 +391,37 @@ pub enum RequestDataRaw {
         iv_size: u32,
         buffer_data: *mut u8,
         buffer_size: u32,
-    },
+        padding: PaddingRaw,
+    } = 20,
+    EncryptAesEcb {
+        key_id: KeyIdRaw,
+        buffer_data: *mut u8,
+        buffer_size: u32,
+    } = 21,
+    EncryptAesEcbExternalKey {
+        key_data: *const u8,
+        key_size: u32,
+        buffer_data: *mut u8,
+        buffer_size: u32,
+    } = 22,
+    DecryptAesEcb {
+        key_id: KeyIdRaw,
+        buffer_data: *mut u8,
+        buffer_size: u32,
+    } = 23,
+    DecryptAesEcbExternalKey {
+        key_data: *const u8,
+        key_size: u32,
+        buffer_data: *mut u8,
+        buffer_size: u32,
+    } = 24,
     CalculateAesCmac {
         key_id: KeyIdRaw,
         message_data: *const u8,
         message_size: u32,
         tag_data: *mut u8,
         tag_size: u32,
-    },
+    } = 25,
     CalculateAesCmacExternalKey {
         key_data: *const u8,
         key_size: u32,
@@ -237,14 +429,14 @@ pub enum RequestDataRaw {
         message_size: u32,
         tag_data: *mut u8,
         tag_size: u32,
-    },
+    } = 26,
     VerifyAesCmac {
         key_id: KeyIdRaw,
         message_data: *const u8,
         message_size: u32,
         tag_data: *const u8,
         tag_size: u32,
-    },
+    } = 27,
     VerifyAesCmacExternalKey {
         key_data: *const u8,
         key_size: u32,
@@ -252,7 +444,7 @@ pub enum RequestDataRaw {
         message_size: u32,
         tag_data: *const u8,
         tag_size: u32,
-    },
+    } = 28,
     CalculateHmac {
         key_id: KeyIdRaw,
         hash_algorithm: HashAlgorithmRaw,
@@ -260,7 +452,7 @@ pub enum RequestDataRaw {
         message_size: u32,
         tag_data: *mut u8,
         tag_size: u32,
-    },
+    } = 29,
     CalculateHmacExternalKey {
         key_data: *const u8,
         key_size: u32,
@@ -269,7 +461,7 @@ pub enum RequestDataRaw {
         message_size: u32,
         tag_data: *mut u8,
         tag_size: u32,
-    },
+    } = 30,
     VerifyHmac {
         key_id: KeyIdRaw,
         hash_algorithm: HashAlgorithmRaw,
@@ -277,7 +469,7 @@ pub enum RequestDataRaw {
         message_size: u32,
         tag_data: *const u8,
         tag_size: u32,
-    },
+    } = 31,
     VerifyHmacExternalKey {
         key_data: *const u8,
         key_size: u32,
@@ -286,48 +478,56 @@ pub enum RequestDataRaw {
         message_size: u32,
         tag_data: *const u8,
         tag_size: u32,
-    },
+    } = 32,
     Sign {
         key_id: KeyIdRaw,
         message_data: *const u8,
         message_size: u32,
         prehashed: BoolRaw,
+        hash_algorithm: HashAlgorithmRaw,
+        signature_format: SignatureFormatRaw,
         signature_data: *mut u8,
         signature_size: u32,
-    },
+    } = 33,
     SignExternalKey {
         key_data: *const u8,
         key_size: u32,
         message_data: *const u8,
         message_size: u32,
         prehashed: BoolRaw,
+        hash_algorithm: HashAlgorithmRaw,
+        signature_format: SignatureFormatRaw,
         signature_data: *mut u8,
         signature_size: u32,
-    },
+    } = 34,
     Verify {
         key_id: KeyIdRaw,
         message_data: *const u8,
         message_size: u32,
         prehashed: BoolRaw,
+        hash_algorithm: HashAlgorithmRaw,
+        signature_format: SignatureFormatRaw,
         signature_data: *const u8,
         signature_size: u32,
-    },
+    } = 35,
     VerifyExternalKey {
         key_data: *const u8,
         key_size: u32,
         message_data: *const u8,
         message_size: u32,
         prehashed: BoolRaw,
+        hash_algorithm: HashAlgorithmRaw,
+        signature_format: SignatureFormatRaw,
         signature_data: *const u8,
         signature_size: u32,
-    },
+    } = 36,
     Ecdh {
         public_key_data: *const u8,
         public_key_size: u32,
         private_key_id: KeyIdRaw,
         shared_secret_data: *mut u8,
         shared_secret_size: u32,
-    },
+    } = 37,
     EcdhExternalPrivateKey {
         curve: CurveRaw,
         public_key_data: *const u8,
@@ -336,103 +536,143 @@ pub enum RequestDataRaw {
         private_key_size: u32,
         shared_secret_data: *mut u8,
         shared_secret_size: u32,
-    },
-}
-
-/// Raw response as it is written by clients to shared memory. This type is supposed to be synced
-/// with non-Rust (e.g. C++) clients via cbindgen.
-#[repr(C)]
-#[derive(Clone, Copy, Debug)]
-pub struct ResponseRaw {
-    pub client_id: ClientIdRaw,
-    pub request_id: RequestIdRaw,
-    data: ResponseDataRaw,
+    } = 38,
+    LoadSheKey {
+        m1_data: *const u8,
+        m1_size: u32,
+        m2_data: *const u8,
+        m2_size: u32,
+        m3_data: *const u8,
+        m3_size: u32,
+        m4_data: *mut u8,
+        m4_size: u32,
+        m5_data: *mut u8,
+        m5_size: u32,
+    } = 39,
+    FinishSheBoot {
+        success: BoolRaw,
+    } = 40,
+    Cancel {
+        target: RequestIdRaw,
+    } = 41,
+    GetCapabilities {} = 42,
+    GetKeyInfo {
+        key_id: KeyIdRaw,
+    } = 43,
 }
 
-/// Raw response as it is written by clients to shared memory. This type is supposed to be synced
-/// with non-Rust (e.g. C++) clients via cbindgen.
+/// Raw response as it is written by clients to shared memory. This type is supposed to be
+/// synced with non-Rust (e.g. C++) clients via cbindgen.
+///
+/// The discriminants are stable codes that are assigned consecutively and never changed or
+/// reused.
 #[repr(C, u8)]
 #[derive(Clone, Copy, Debug, EnumCount)]
 pub enum ResponseDataRaw {
     Error {
         error: JobErrorRaw,
-    },
+    } = 0,
     GetRandom {
         data_data: *mut u8,
         data_size: u32,
-    },
-    GenerateSymmetricKey {},
-    GenerateKeyPair {},
-    ImportSymmetricKey {},
-    ImportKeyPair {},
+    } = 1,
+    GenerateSymmetricKey {} = 2,
+    GenerateKeyPair {} = 3,
+    ImportSymmetricKey {} = 4,
+    ImportKeyPair {} = 5,
     ExportSymmetricKey {
         key_data: *mut u8,
         key_size: u32,
-    },
+    } = 6,
     ExportPublicKey {
         public_key_data: *mut u8,
         public_key_size: u32,
-    },
+    } = 7,
     ExportPrivateKey {
         private_key_data: *mut u8,
         private_key_size: u32,
-    },
+    } = 8,
     IsKeyAvailable {
         is_available: u32,
-    },
+    } = 9,
     EncryptChaChaPoly {
         buffer_data: *mut u8,
         buffer_size: u32,
         tag_data: *mut u8,
         tag_size: u32,
-    },
+    } = 10,
     DecryptChaChaPoly {
         buffer_data: *mut u8,
         buffer_size: u32,
-    },
+    } = 11,
     EncryptAesGcm {
         buffer_data: *mut u8,
         buffer_size: u32,
         tag_data: *mut u8,
         tag_size: u32,
-    },
+    } = 12,
     DecryptAesGcm {
         buffer_data: *mut u8,
         buffer_size: u32,
-    },
+    } = 13,
     EncryptAesCbc {
         buffer_data: *mut u8,
         buffer_size: u32,
-    },
+    } = 14,
     DecryptAesCbc {
         buffer_data: *mut u8,
         buffer_size: u32,
-    },
+    } = 15,
+    EncryptAesEcb {
+        buffer_data: *mut u8,
+        buffer_size: u32,
+    } = 16,
+    DecryptAesEcb {
+        buffer_data: *mut u8,
+        buffer_size: u32,
+    } = 17,
     CalculateAesCmac {
         tag_data: *mut u8,
         tag_size: u32,
-    },
+    } = 18,
     VerifyAesCmac {
         verified: BoolRaw,
-    },
+    } = 19,
     CalculateHmac {
         tag_data: *mut u8,
         tag_size: u32,
-    },
+    } = 20,
     VerifyHmac {
         verified: BoolRaw,
-    },
+    } = 21,
     Sign {
         signature_data: *mut u8,
         signature_size: u32,
-    },
+    } = 22,
     Verify {
         verified: BoolRaw,
-    },
+    } = 23,
     Ecdh {
         shared_secret_data: *mut u8,
         shared_secret_size: u32,
-    },
+    } = 24,
+    LoadSheKey {
+        m4_data: *mut u8,
+        m4_size: u32,
+        m5_data: *mut u8,
+        m5_size: u32,
+    } = 25,
+    FinishSheBoot {} = 26,
+    Cancel {} = 27,
+    Cancelled {
+        reason: CancelReasonRaw,
+    } = 28,
+    GetCapabilities {
+        capabilities: CapabilitiesRaw,
+    } = 29,
+    GetKeyInfo {
+        key_info: KeyInfoRaw,
+    } = 30,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -440,6 +680,10 @@ pub enum ValidationError {
     InvalidPointer,
     InvalidTagValue,
     InvalidValue,
+    /// A mutable slice of the request overlaps with another slice of the same request.
+    OverlappingSlices,
+    /// The request or response was built for another major version of the ABI.
+    UnsupportedAbiVersion,
 }
 
 impl RequestResponseRawPair {
@@ -484,6 +728,19 @@ impl RequestRaw {
             core::ptr::copy(ptr.cast(), request.as_mut_ptr(), 1);
         }
 
+        // SAFETY: request is of type RequestRaw, so adding the offset of a field of such a struct
+        // still falls under the object behind the pointer. The version is valid for all values.
+        let abi_version: AbiVersion = unsafe {
+            *(request
+                .as_ptr()
+                .cast::<u8>()
+                .add(offset_of!(RequestRaw, abi_version))
+                .cast())
+        };
+        if !abi_version.is_supported() {
+            return Err(ValidationError::UnsupportedAbiVersion);
+        }
+
         // SAFETY: request is of type RequestRaw, so adding the offset of a field of such a struct
         // still falls under the object behind the pointer
         let tag: u8 = unsafe {
@@ -501,1615 +758,1106 @@ impl RequestRaw {
         // SAFETY: Besides the tag, which we checked, all other members are ok with any value
         Ok(unsafe { request.assume_init() })
     }
+}
 
-    pub fn verify<'data>(
-        self,
-        validator: &impl Fn(*const u8, u32) -> bool,
-    ) -> Result<Request<'data>, ValidationError> {
-        let client_id = self.client_id.into();
-        let request_id = self.request_id.into();
-        let request = match self.data {
-            RequestDataRaw::GetRandom {
-                output_data,
-                output_size,
-            } => Request::GetRandom {
-                client_id,
-                request_id,
-                output: check_mut_pointer_and_size(output_data, output_size, &validator)?,
-            },
-            RequestDataRaw::GenerateSymmetricKey { key_id, overwrite } => {
-                Request::GenerateSymmetricKey {
-                    client_id,
-                    request_id,
-                    key_id: key_id.into(),
-                    overwrite: bool_raw_to_bool(overwrite),
-                }
-            }
-            RequestDataRaw::GenerateKeyPair { key_id, overwrite } => Request::GenerateKeyPair {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                overwrite: bool_raw_to_bool(overwrite),
-            },
-            RequestDataRaw::ImportSymmetricKey {
-                key_id,
-                data_data,
-                data_size,
-                overwrite,
-            } => Request::ImportSymmetricKey {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                data: check_pointer_and_size(data_data, data_size, &validator)?,
-                overwrite: bool_raw_to_bool(overwrite),
-            },
-            RequestDataRaw::ImportKeyPair {
-                key_id,
-                public_key_data,
-                public_key_size,
-                private_key_data,
-                private_key_size,
-                overwrite,
-            } => Request::ImportKeyPair {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                public_key: check_pointer_and_size(public_key_data, public_key_size, &validator)?,
-                private_key: check_pointer_and_size(
-                    private_key_data,
-                    private_key_size,
-                    &validator,
-                )?,
-                overwrite: bool_raw_to_bool(overwrite),
-            },
-            RequestDataRaw::ExportSymmetricKey {
-                key_id,
-                data_data,
-                data_size,
-            } => Request::ExportSymmetricKey {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                data: check_mut_pointer_and_size(data_data, data_size, &validator)?,
-            },
-            RequestDataRaw::ExportPublicKey {
-                key_id,
-                public_key_data,
-                public_key_size,
-            } => Request::ExportPublicKey {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                public_key: check_mut_pointer_and_size(
-                    public_key_data,
-                    public_key_size,
-                    &validator,
-                )?,
-            },
-            RequestDataRaw::ExportPrivateKey {
-                key_id,
-                private_key_data,
-                private_key_size,
-            } => Request::ExportPrivateKey {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                private_key: check_mut_pointer_and_size(
-                    private_key_data,
-                    private_key_size,
-                    &validator,
-                )?,
-            },
-            RequestDataRaw::IsKeyAvailable { key_id } => Request::IsKeyAvailable {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-            },
-            RequestDataRaw::EncryptChaChaPoly {
-                key_id,
-                nonce_data,
-                nonce_size,
-                buffer_data,
-                buffer_size,
-                aad_data,
-                aad_size,
-                tag_data,
-                tag_size,
-            } => Request::EncryptChaChaPoly {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                nonce: check_pointer_and_size(nonce_data, nonce_size, &validator)?,
-                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
-                aad: check_pointer_and_size(aad_data, aad_size, &validator)?,
-                tag: check_mut_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::EncryptChaChaPolyExternalKey {
-                key_data,
-                key_size,
-                nonce_data,
-                nonce_size,
-                buffer_data,
-                buffer_size,
-                aad_data,
-                aad_size,
-                tag_data,
-                tag_size,
-            } => Request::EncryptChaChaPolyExternalKey {
-                client_id,
-                request_id,
-                key: check_pointer_and_size(key_data, key_size, &validator)?,
-                nonce: check_pointer_and_size(nonce_data, nonce_size, &validator)?,
-                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
-                aad: check_pointer_and_size(aad_data, aad_size, &validator)?,
-                tag: check_mut_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::DecryptChaChaPoly {
-                key_id,
-                nonce_data,
-                nonce_size,
-                buffer_data,
-                buffer_size,
-                aad_data,
-                aad_size,
-                tag_data,
-                tag_size,
-            } => Request::DecryptChaChaPoly {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                nonce: check_pointer_and_size(nonce_data, nonce_size, &validator)?,
-                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
-                aad: check_pointer_and_size(aad_data, aad_size, &validator)?,
-                tag: check_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::DecryptChaChaPolyExternalKey {
-                key_data,
-                key_size,
-                nonce_data,
-                nonce_size,
-                buffer_data,
-                buffer_size,
-                aad_data,
-                aad_size,
-                tag_data,
-                tag_size,
-            } => Request::DecryptChaChaPolyExternalKey {
-                client_id,
-                request_id,
-                key: check_pointer_and_size(key_data, key_size, &validator)?,
-                nonce: check_pointer_and_size(nonce_data, nonce_size, &validator)?,
-                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
-                aad: check_pointer_and_size(aad_data, aad_size, &validator)?,
-                tag: check_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::EncryptAesGcm {
-                key_id,
-                iv_data,
-                iv_size,
-                buffer_data,
-                buffer_size,
-                aad_data,
-                aad_size,
-                tag_data,
-                tag_size,
-            } => Request::EncryptAesGcm {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                iv: check_pointer_and_size(iv_data, iv_size, &validator)?,
-                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
-                aad: check_pointer_and_size(aad_data, aad_size, &validator)?,
-                tag: check_mut_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::EncryptAesGcmExternalKey {
-                key_data,
-                key_size,
-                iv_data,
-                iv_size,
-                buffer_data,
-                buffer_size,
-                aad_data,
-                aad_size,
-                tag_data,
-                tag_size,
-            } => Request::EncryptAesGcmExternalKey {
-                client_id,
-                request_id,
-                key: check_pointer_and_size(key_data, key_size, &validator)?,
-                iv: check_pointer_and_size(iv_data, iv_size, &validator)?,
-                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
-                aad: check_pointer_and_size(aad_data, aad_size, &validator)?,
-                tag: check_mut_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::DecryptAesGcm {
-                key_id,
-                iv_data,
-                iv_size,
-                buffer_data,
-                buffer_size,
-                aad_data,
-                aad_size,
-                tag_data,
-                tag_size,
-            } => Request::DecryptAesGcm {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                iv: check_pointer_and_size(iv_data, iv_size, &validator)?,
-                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
-                aad: check_pointer_and_size(aad_data, aad_size, &validator)?,
-                tag: check_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::DecryptAesGcmExternalKey {
-                key_data,
-                key_size,
-                iv_data,
-                iv_size,
-                buffer_data,
-                buffer_size,
-                aad_data,
-                aad_size,
-                tag_data,
-                tag_size,
-            } => Request::DecryptAesGcmExternalKey {
-                client_id,
-                request_id,
-                key: check_pointer_and_size(key_data, key_size, &validator)?,
-                iv: check_pointer_and_size(iv_data, iv_size, &validator)?,
-                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
-                aad: check_pointer_and_size(aad_data, aad_size, &validator)?,
-                tag: check_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::EncryptAesCbc {
-                key_id,
-                iv_data,
-                iv_size,
-                buffer_data,
-                buffer_size,
-                plaintext_size,
-            } => Request::EncryptAesCbc {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                iv: check_pointer_and_size(iv_data, iv_size, &validator)?,
-                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
-                plaintext_size: plaintext_size as usize,
-            },
-            RequestDataRaw::EncryptAesCbcExternalKey {
-                key_data,
-                key_size,
-                iv_data,
-                iv_size,
-                buffer_data,
-                buffer_size,
-                plaintext_size,
-            } => Request::EncryptAesCbcExternalKey {
-                client_id,
-                request_id,
-                key: check_pointer_and_size(key_data, key_size, &validator)?,
-                iv: check_pointer_and_size(iv_data, iv_size, &validator)?,
-                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
-                plaintext_size: plaintext_size as usize,
-            },
-            RequestDataRaw::DecryptAesCbc {
-                key_id,
-                iv_data,
-                iv_size,
-                buffer_data,
-                buffer_size,
-            } => Request::DecryptAesCbc {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                iv: check_pointer_and_size(iv_data, iv_size, &validator)?,
-                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
-            },
-            RequestDataRaw::DecryptAesCbcExternalKey {
-                key_data,
-                key_size,
-                iv_data,
-                iv_size,
-                buffer_data,
-                buffer_size,
-            } => Request::DecryptAesCbcExternalKey {
-                client_id,
-                request_id,
-                key: check_pointer_and_size(key_data, key_size, &validator)?,
-                iv: check_pointer_and_size(iv_data, iv_size, &validator)?,
-                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
-            },
-            RequestDataRaw::CalculateAesCmac {
-                key_id,
-                message_data,
-                message_size,
-                tag_data,
-                tag_size,
-            } => Request::CalculateAesCmac {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                message: check_pointer_and_size(message_data, message_size, &validator)?,
-                tag: check_mut_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::CalculateAesCmacExternalKey {
-                key_data,
-                key_size,
-                message_data,
-                message_size,
-                tag_data,
-                tag_size,
-            } => Request::CalculateAesCmacExternalKey {
-                client_id,
-                request_id,
-                key: check_pointer_and_size(key_data, key_size, &validator)?,
-                message: check_pointer_and_size(message_data, message_size, &validator)?,
-                tag: check_mut_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::VerifyAesCmac {
-                key_id,
-                message_data,
-                message_size,
-                tag_data,
-                tag_size,
-            } => Request::VerifyAesCmac {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                message: check_pointer_and_size(message_data, message_size, &validator)?,
-                tag: check_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::VerifyAesCmacExternalKey {
-                key_data,
-                key_size,
-                message_data,
-                message_size,
-                tag_data,
-                tag_size,
-            } => Request::VerifyAesCmacExternalKey {
-                client_id,
-                request_id,
-                key: check_pointer_and_size(key_data, key_size, &validator)?,
-                message: check_pointer_and_size(message_data, message_size, &validator)?,
-                tag: check_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::CalculateHmac {
-                key_id,
-                hash_algorithm,
-                message_data,
-                message_size,
-                tag_data,
-                tag_size,
-            } => Request::CalculateHmac {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                hash_algorithm: hash_algorithm.try_into()?,
-                message: check_pointer_and_size(message_data, message_size, &validator)?,
-                tag: check_mut_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::CalculateHmacExternalKey {
-                key_data,
-                key_size,
-                hash_algorithm,
-                message_data,
-                message_size,
-                tag_data,
-                tag_size,
-            } => Request::CalculateHmacExternalKey {
-                client_id,
-                request_id,
-                key: check_pointer_and_size(key_data, key_size, &validator)?,
-                hash_algorithm: hash_algorithm.try_into()?,
-                message: check_pointer_and_size(message_data, message_size, &validator)?,
-                tag: check_mut_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::VerifyHmac {
-                key_id,
-                hash_algorithm,
-                message_data,
-                message_size,
-                tag_data,
-                tag_size,
-            } => Request::VerifyHmac {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                hash_algorithm: hash_algorithm.try_into()?,
-                message: check_pointer_and_size(message_data, message_size, &validator)?,
-                tag: check_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::VerifyHmacExternalKey {
-                key_data,
-                key_size,
-                hash_algorithm,
-                message_data,
-                message_size,
-                tag_data,
-                tag_size,
-            } => Request::VerifyHmacExternalKey {
-                client_id,
-                request_id,
-                key: check_pointer_and_size(key_data, key_size, &validator)?,
-                hash_algorithm: hash_algorithm.try_into()?,
-                message: check_pointer_and_size(message_data, message_size, &validator)?,
-                tag: check_pointer_and_size(tag_data, tag_size, &validator)?,
-            },
-            RequestDataRaw::Sign {
-                key_id,
-                message_data,
-                message_size,
-                prehashed,
-                signature_data,
-                signature_size,
-            } => Request::Sign {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                message: check_pointer_and_size(message_data, message_size, &validator)?,
-                prehashed: bool_raw_to_bool(prehashed),
-                signature: check_mut_pointer_and_size(signature_data, signature_size, &validator)?,
-            },
-            RequestDataRaw::SignExternalKey {
-                key_data,
-                key_size,
-                message_data,
-                message_size,
-                prehashed,
-                signature_data,
-                signature_size,
-            } => Request::SignExternalKey {
-                client_id,
-                request_id,
-                private_key: check_pointer_and_size(key_data, key_size, &validator)?,
-                message: check_pointer_and_size(message_data, message_size, &validator)?,
-                prehashed: bool_raw_to_bool(prehashed),
-                signature: check_mut_pointer_and_size(signature_data, signature_size, &validator)?,
-            },
-            RequestDataRaw::Verify {
-                key_id,
-                message_data,
-                message_size,
-                prehashed,
-                signature_data,
-                signature_size,
-            } => Request::Verify {
-                client_id,
-                request_id,
-                key_id: key_id.into(),
-                message: check_pointer_and_size(message_data, message_size, &validator)?,
-                prehashed: bool_raw_to_bool(prehashed),
-                signature: check_pointer_and_size(signature_data, signature_size, &validator)?,
-            },
-            RequestDataRaw::VerifyExternalKey {
-                key_data,
-                key_size,
-                message_data,
-                message_size,
-                prehashed,
-                signature_data,
-                signature_size,
-            } => Request::VerifyExternalKey {
-                client_id,
-                request_id,
-                public_key: check_pointer_and_size(key_data, key_size, &validator)?,
-                message: check_pointer_and_size(message_data, message_size, &validator)?,
-                prehashed: bool_raw_to_bool(prehashed),
-                signature: check_pointer_and_size(signature_data, signature_size, &validator)?,
-            },
-            RequestDataRaw::Ecdh {
-                public_key_data,
-                public_key_size,
-                private_key_id,
-                shared_secret_data,
-                shared_secret_size,
-            } => Request::Ecdh {
-                client_id,
-                request_id,
-                public_key: check_pointer_and_size(public_key_data, public_key_size, &validator)?,
-                private_key_id: private_key_id.into(),
-                shared_secret: check_mut_pointer_and_size(
-                    shared_secret_data,
-                    shared_secret_size,
-                    &validator,
-                )?,
-            },
-            RequestDataRaw::EcdhExternalPrivateKey {
-                curve,
-                public_key_data,
-                public_key_size,
-                private_key_data,
-                private_key_size,
-                shared_secret_data,
-                shared_secret_size,
-            } => Request::EcdhExternalPrivateKey {
-                client_id,
-                request_id,
-                curve: curve.try_into()?,
-                public_key: check_pointer_and_size(public_key_data, public_key_size, &validator)?,
-                private_key: check_pointer_and_size(
-                    private_key_data,
-                    private_key_size,
-                    &validator,
-                )?,
-                shared_secret: check_mut_pointer_and_size(
-                    shared_secret_data,
-                    shared_secret_size,
-                    &validator,
-                )?,
-            },
+// Outgoing direction. No validation needed.
+impl ResponseRaw {
+    /// Create a `ResponseRaw` from a raw pointer.
+    ///
+    /// # Arguments
+    ///
+    /// * `ptr`: The pointer to the first byte of the `ResponseRaw`
+    ///
+    /// # Safety
+    ///
+    /// The provided pointer must be valid (non-null, aligned, point to valid memory) and point to a
+    /// valid `ResponseRaw` instance.  
+    ///
+    pub unsafe fn from_raw(ptr: *const u8) -> Result<Self, ValidationError> {
+        // We create a copy of the untrusted response on the stack to prevent a potential
+        // point-of-check/point-of-use issue
+        let mut response: MaybeUninit<ResponseRaw> = MaybeUninit::uninit();
+
+        // SAFETY: Pointer and size must be checked by integrator
+        unsafe {
+            core::ptr::copy(ptr.cast(), response.as_mut_ptr(), 1);
+        }
+
+        // SAFETY: response is of type ResponseRaw, so adding the offset of a field of such a struct
+        // still falls under the object behind the pointer. The version is valid for all values.
+        let abi_version: AbiVersion = unsafe {
+            *(response
+                .as_ptr()
+                .cast::<u8>()
+                .add(offset_of!(ResponseRaw, abi_version))
+                .cast())
+        };
+        if !abi_version.is_supported() {
+            return Err(ValidationError::UnsupportedAbiVersion);
+        }
+
+        // SAFETY: response is of type ResponseRaw, so adding the offset of a field of such a struct
+        // still falls under the object behind the pointer
+        let tag: u8 = unsafe {
+            *(response
+                .as_ptr()
+                .cast::<u8>()
+                .add(offset_of!(ResponseRaw, data)))
         };
-        Ok(request)
+
+        // Validate tag value. Invalid tag values cause UB when transmuted into an enum.
+        if tag >= ResponseDataRaw::COUNT as u8 {
+            return Err(ValidationError::InvalidTagValue);
+        }
+
+        // SAFETY: Besides the tag, which we checked, all other members are ok with any value
+        Ok(unsafe { response.assume_init() })
     }
 }
 
-// Outgoing direction. No validation needed.
-impl From<Request<'_>> for RequestRaw {
-    fn from(request: Request) -> Self {
-        match request {
-            Request::GetRandom {
-                client_id,
-                request_id,
-                output,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::GetRandom {
-                    output_data: output.as_mut_ptr(),
-                    output_size: output.len() as u32,
-                },
-            },
-            Request::GenerateSymmetricKey {
-                client_id,
-                request_id,
-                key_id,
-                overwrite,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::GenerateSymmetricKey {
-                    key_id: key_id.into(),
-                    overwrite: overwrite.into(),
-                },
-            },
-            Request::GenerateKeyPair {
-                client_id,
-                request_id,
-                key_id,
-                overwrite,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::GenerateKeyPair {
-                    key_id: key_id.into(),
-                    overwrite: overwrite.into(),
-                },
-            },
-            Request::ImportSymmetricKey {
-                client_id,
-                request_id,
-                key_id,
-                data,
-                overwrite,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::ImportSymmetricKey {
-                    key_id: key_id.into(),
-                    data_data: data.as_ptr(),
-                    data_size: data.len() as u32,
-                    overwrite: overwrite.into(),
-                },
-            },
-            Request::ImportKeyPair {
-                client_id,
-                request_id,
-                key_id,
-                public_key,
-                private_key,
-                overwrite,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::ImportKeyPair {
-                    key_id: key_id.into(),
-                    public_key_data: public_key.as_ptr(),
-                    public_key_size: public_key.len() as u32,
-                    private_key_data: private_key.as_ptr(),
-                    private_key_size: private_key.len() as u32,
-                    overwrite: overwrite.into(),
-                },
-            },
-            Request::ExportSymmetricKey {
-                client_id,
-                request_id,
-                key_id,
-                data,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::ExportSymmetricKey {
-                    key_id: key_id.into(),
-                    data_data: data.as_mut_ptr(),
-                    data_size: data.len() as u32,
-                },
-            },
-            Request::ExportPublicKey {
-                client_id,
-                request_id,
-                key_id,
-                public_key,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::ExportPublicKey {
-                    key_id: key_id.into(),
-                    public_key_data: public_key.as_mut_ptr(),
-                    public_key_size: public_key.len() as u32,
-                },
-            },
-            Request::ExportPrivateKey {
-                client_id,
-                request_id,
-                key_id,
-                private_key,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::ExportPrivateKey {
-                    key_id: key_id.into(),
-                    private_key_data: private_key.as_mut_ptr(),
-                    private_key_size: private_key.len() as u32,
-                },
-            },
-            Request::IsKeyAvailable {
-                client_id,
-                request_id,
-                key_id,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::IsKeyAvailable {
-                    key_id: key_id.into(),
-                },
-            },
-            Request::EncryptChaChaPoly {
-                client_id,
-                request_id,
-                key_id,
-                nonce,
-                buffer,
-                aad,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::EncryptChaChaPoly {
-                    key_id: key_id.into(),
-                    nonce_data: nonce.as_ptr(),
-                    nonce_size: nonce.len() as u32,
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                    aad_data: aad.as_ptr(),
-                    aad_size: aad.len() as u32,
-                    tag_data: tag.as_mut_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::EncryptChaChaPolyExternalKey {
-                client_id,
-                request_id,
-                key,
-                nonce,
-                buffer,
-                aad,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::EncryptChaChaPolyExternalKey {
-                    key_data: key.as_ptr(),
-                    key_size: key.len() as u32,
-                    nonce_data: nonce.as_ptr(),
-                    nonce_size: nonce.len() as u32,
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                    aad_data: aad.as_ptr(),
-                    aad_size: aad.len() as u32,
-                    tag_data: tag.as_mut_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::DecryptChaChaPoly {
-                client_id,
-                request_id,
-                key_id,
-                nonce,
-                buffer,
-                aad,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::DecryptChaChaPoly {
-                    key_id: key_id.into(),
-                    nonce_data: nonce.as_ptr(),
-                    nonce_size: nonce.len() as u32,
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                    aad_data: aad.as_ptr(),
-                    aad_size: aad.len() as u32,
-                    tag_data: tag.as_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::DecryptChaChaPolyExternalKey {
-                client_id,
-                request_id,
-                key,
-                nonce,
-                buffer,
-                aad,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::DecryptChaChaPolyExternalKey {
-                    key_data: key.as_ptr(),
-                    key_size: key.len() as u32,
-                    nonce_data: nonce.as_ptr(),
-                    nonce_size: nonce.len() as u32,
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                    aad_data: aad.as_ptr(),
-                    aad_size: aad.len() as u32,
-                    tag_data: tag.as_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::EncryptAesGcm {
-                client_id,
-                request_id,
-                key_id,
-                iv,
-                buffer,
-                aad,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::EncryptAesGcm {
-                    key_id: key_id.into(),
-                    iv_data: iv.as_ptr(),
-                    iv_size: iv.len() as u32,
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                    aad_data: aad.as_ptr(),
-                    aad_size: aad.len() as u32,
-                    tag_data: tag.as_mut_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::EncryptAesGcmExternalKey {
-                client_id,
-                request_id,
-                key,
-                iv,
-                buffer,
-                aad,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::EncryptAesGcmExternalKey {
-                    key_data: key.as_ptr(),
-                    key_size: key.len() as u32,
-                    iv_data: iv.as_ptr(),
-                    iv_size: iv.len() as u32,
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                    aad_data: aad.as_ptr(),
-                    aad_size: aad.len() as u32,
-                    tag_data: tag.as_mut_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::DecryptAesGcm {
-                client_id,
-                request_id,
-                key_id,
-                iv,
-                buffer,
-                aad,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::DecryptAesGcm {
-                    key_id: key_id.into(),
-                    iv_data: iv.as_ptr(),
-                    iv_size: iv.len() as u32,
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                    aad_data: aad.as_ptr(),
-                    aad_size: aad.len() as u32,
-                    tag_data: tag.as_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::DecryptAesGcmExternalKey {
-                client_id,
-                request_id,
-                key,
-                iv,
-                buffer,
-                aad,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::DecryptAesGcmExternalKey {
-                    key_data: key.as_ptr(),
-                    key_size: key.len() as u32,
-                    iv_data: iv.as_ptr(),
-                    iv_size: iv.len() as u32,
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                    aad_data: aad.as_ptr(),
-                    aad_size: aad.len() as u32,
-                    tag_data: tag.as_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::EncryptAesCbc {
-                client_id,
-                request_id,
-                key_id,
-                iv,
-                buffer,
-                plaintext_size,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::EncryptAesCbc {
-                    key_id: key_id.into(),
-                    iv_data: iv.as_ptr(),
-                    iv_size: iv.len() as u32,
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                    plaintext_size: plaintext_size as u32,
-                },
-            },
-            Request::EncryptAesCbcExternalKey {
-                client_id,
-                request_id,
-                key,
-                iv,
-                buffer,
-                plaintext_size,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::EncryptAesCbcExternalKey {
-                    key_data: key.as_ptr(),
-                    key_size: key.len() as u32,
-                    iv_data: iv.as_ptr(),
-                    iv_size: iv.len() as u32,
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                    plaintext_size: plaintext_size as u32,
-                },
-            },
-            Request::DecryptAesCbc {
-                client_id,
-                request_id,
-                key_id,
-                iv,
-                buffer,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::DecryptAesCbc {
-                    key_id: key_id.into(),
-                    iv_data: iv.as_ptr(),
-                    iv_size: iv.len() as u32,
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                },
-            },
-            Request::DecryptAesCbcExternalKey {
-                client_id,
-                request_id,
-                key,
-                iv,
-                buffer,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::DecryptAesCbcExternalKey {
-                    key_data: key.as_ptr(),
-                    key_size: key.len() as u32,
-                    iv_data: iv.as_ptr(),
-                    iv_size: iv.len() as u32,
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                },
-            },
-            Request::CalculateAesCmac {
-                client_id,
-                request_id,
-                key_id,
-                message,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::CalculateAesCmac {
-                    key_id: key_id.into(),
-                    message_data: message.as_ptr(),
-                    message_size: message.len() as u32,
-                    tag_data: tag.as_mut_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::CalculateAesCmacExternalKey {
-                client_id,
-                request_id,
-                key,
-                message,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::CalculateAesCmacExternalKey {
-                    key_data: key.as_ptr(),
-                    key_size: key.len() as u32,
-                    message_data: message.as_ptr(),
-                    message_size: message.len() as u32,
-                    tag_data: tag.as_mut_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::VerifyAesCmac {
-                client_id,
-                request_id,
-                key_id,
-                message,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::VerifyAesCmac {
-                    key_id: key_id.into(),
-                    message_data: message.as_ptr(),
-                    message_size: message.len() as u32,
-                    tag_data: tag.as_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::VerifyAesCmacExternalKey {
-                client_id,
-                request_id,
-                key,
-                message,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::VerifyAesCmacExternalKey {
-                    key_data: key.as_ptr(),
-                    key_size: key.len() as u32,
-                    message_data: message.as_ptr(),
-                    message_size: message.len() as u32,
-                    tag_data: tag.as_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::CalculateHmac {
-                client_id,
-                request_id,
-                key_id,
-                hash_algorithm,
-                message,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::CalculateHmac {
-                    key_id: key_id.into(),
-                    hash_algorithm: hash_algorithm.into(),
-                    message_data: message.as_ptr(),
-                    message_size: message.len() as u32,
-                    tag_data: tag.as_mut_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::CalculateHmacExternalKey {
-                client_id,
-                request_id,
-                key,
-                hash_algorithm,
-                message,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::CalculateHmacExternalKey {
-                    key_data: key.as_ptr(),
-                    key_size: key.len() as u32,
-                    hash_algorithm: hash_algorithm.into(),
-                    message_data: message.as_ptr(),
-                    message_size: message.len() as u32,
-                    tag_data: tag.as_mut_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::VerifyHmac {
-                client_id,
-                request_id,
-                key_id,
-                hash_algorithm,
-                message,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::VerifyHmac {
-                    key_id: key_id.into(),
-                    hash_algorithm: hash_algorithm.into(),
-                    message_data: message.as_ptr(),
-                    message_size: message.len() as u32,
-                    tag_data: tag.as_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::VerifyHmacExternalKey {
-                client_id,
-                request_id,
-                key,
-                hash_algorithm,
-                message,
-                tag,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::VerifyHmacExternalKey {
-                    key_data: key.as_ptr(),
-                    key_size: key.len() as u32,
-                    hash_algorithm: hash_algorithm.into(),
-                    message_data: message.as_ptr(),
-                    message_size: message.len() as u32,
-                    tag_data: tag.as_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Request::Sign {
-                client_id,
-                request_id,
-                key_id,
-                message,
-                prehashed,
-                signature,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::Sign {
-                    key_id: key_id.into(),
-                    message_data: message.as_ptr(),
-                    message_size: message.len() as u32,
-                    prehashed: prehashed.into(),
-                    signature_data: signature.as_mut_ptr(),
-                    signature_size: signature.len() as u32,
-                },
-            },
-            Request::SignExternalKey {
-                client_id,
-                request_id,
-                private_key: key,
-                message,
-                prehashed,
-                signature,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::SignExternalKey {
-                    key_data: key.as_ptr(),
-                    key_size: key.len() as u32,
-                    message_data: message.as_ptr(),
-                    message_size: message.len() as u32,
-                    prehashed: prehashed.into(),
-                    signature_data: signature.as_mut_ptr(),
-                    signature_size: signature.len() as u32,
-                },
-            },
-            Request::Verify {
-                client_id,
-                request_id,
-                key_id,
-                message,
-                prehashed,
-                signature,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::Verify {
-                    key_id: key_id.into(),
-                    message_data: message.as_ptr(),
-                    message_size: message.len() as u32,
-                    prehashed: prehashed.into(),
-                    signature_data: signature.as_ptr(),
-                    signature_size: signature.len() as u32,
-                },
-            },
-            Request::VerifyExternalKey {
-                client_id,
-                request_id,
-                public_key: key,
-                message,
-                prehashed,
-                signature,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::VerifyExternalKey {
-                    key_data: key.as_ptr(),
-                    key_size: key.len() as u32,
-                    message_data: message.as_ptr(),
-                    message_size: message.len() as u32,
-                    prehashed: prehashed.into(),
-                    signature_data: signature.as_ptr(),
-                    signature_size: signature.len() as u32,
-                },
-            },
-            Request::Ecdh {
-                client_id,
-                request_id,
-                public_key,
-                private_key_id,
-                shared_secret,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::Ecdh {
-                    public_key_data: public_key.as_ptr(),
-                    public_key_size: public_key.len() as u32,
-                    private_key_id: private_key_id.into(),
-                    shared_secret_data: shared_secret.as_mut_ptr(),
-                    shared_secret_size: shared_secret.len() as u32,
-                },
-            },
-            Request::EcdhExternalPrivateKey {
-                client_id,
-                request_id,
-                curve,
-                public_key,
-                private_key,
-                shared_secret,
-            } => RequestRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: RequestDataRaw::EcdhExternalPrivateKey {
-                    curve: curve.into(),
-                    public_key_data: public_key.as_ptr(),
-                    public_key_size: public_key.len() as u32,
-                    private_key_data: private_key.as_ptr(),
-                    private_key_size: private_key.len() as u32,
-                    shared_secret_data: shared_secret.as_mut_ptr(),
-                    shared_secret_size: shared_secret.len() as u32,
-                },
-            },
+/// Generates the conversions between a raw type and its non-raw version from a list of variants.
+///
+/// Every variant lists the fields of the non-raw variant and the typed fields of the raw variant
+/// they are stored in, followed by the discriminant of the raw variant:
+///
+/// - `slice(data: *const u8, size: u32)`: Pointer and size of a buffer the HSM reads from
+/// - `mut_slice(data: *mut u8, size: u32)`: Pointer and size of a buffer the HSM writes to
+/// - `value(raw: Type)`: Any other [RawField]
+///
+/// The raw data enums are declared separately, so cbindgen can read them without expanding this
+/// macro. All generated conversions match and construct the variants exhaustively, so a variant or
+/// field that is missing in the list, the raw declaration or the non-raw type fails to compile.
+/// Discriminants, field types and field order are compared with the declaration by
+/// `assert_declaration` in the round-trip tests.
+macro_rules! raw_conversions {
+    (
+        $raw:ident($data_raw:ident) <=> $typed:ident $headers:tt;
+        $(
+            $variant:ident {
+                $($field:ident: $kind:ident($($raw_field:ident: $raw_ty:ty),+)),* $(,)?
+            } = $code:literal
+        ),* $(,)?
+    ) => {
+        impl $raw {
+            /// Validate the raw data and convert it to its non-raw version. Buffers are checked by
+            /// `validator` and must not overlap with mutable buffers of the same request or
+            /// response.
+            ///
+            /// # Arguments
+            ///
+            /// * `validator`: Integrator-provided check whether a pointer and size pair points to
+            ///   memory the calling client is allowed to access, e.g. a function or the
+            ///   [ClientMemory](crate::hsm::memory::ClientMemory) of the client.
+            ///
+            /// # Safety
+            ///
+            /// Overlaps are only detected between the buffers of this request or response. The
+            /// returned slices live for `'data` and may alias buffers of other requests that are
+            /// still in flight, e.g. two pending requests of a client that write to the same
+            /// output buffer. The integrator must prevent this, e.g. by only accepting buffers in
+            /// memory that is dedicated to a single request, or by verifying and processing one
+            /// request of a client at a time.
+            pub fn verify<'data>(
+                self,
+                validator: &impl Validator,
+            ) -> Result<$typed<'data>, ValidationError> {
+                let validator = SliceChecker::new(validator);
+                raw_conversions!(@headers_from_raw self, $headers);
+                Ok(match self.data {
+                    $(
+                        $data_raw::$variant { $($($raw_field),+),* } => raw_conversions!(
+                            @variant $typed::$variant, $headers,
+                            $($field: field_from_raw!($kind, validator, $($raw_field),+),)*
+                        ),
+                    )*
+                })
+            }
+        }
+
+        // Outgoing direction. Buffers are trusted, only their sizes must fit into the raw fields.
+        impl TryFrom<$typed<'_>> for $raw {
+            type Error = ValidationError;
+
+            fn try_from(value: $typed) -> Result<Self, ValidationError> {
+                Ok(match value {
+                    $(
+                        raw_conversions!(@variant $typed::$variant, $headers, $($field,)*) => {
+                            $(let ($($raw_field,)+) = field_to_raw!($kind, $field);)*
+                            raw_conversions!(
+                                @raw $raw, $headers, $data_raw::$variant { $($($raw_field),+),* }
+                            )
+                        }
+                    )*
+                })
+            }
+        }
+
+        #[cfg(test)]
+        impl $data_raw {
+            /// Assert that the declaration of the variant matches the list of the conversions:
+            /// The discriminant is the listed code and the fields have the listed types and order.
+            fn assert_declaration(&self) {
+                match self {
+                    $(
+                        $data_raw::$variant { $($($raw_field),+),* } => {
+                            // A `repr(C, u8)` enum is laid out as its tag followed by a union of
+                            // `repr(C)` structs with the fields of the variants
+                            #[allow(dead_code)]
+                            #[repr(C)]
+                            struct Fields {
+                                $($($raw_field: $raw_ty),+),*
+                            }
+                            // SAFETY: The raw data enums are `repr(C, u8)`, so they start with
+                            // their tag
+                            assert_eq!(unsafe { *(self as *const Self).cast::<u8>() }, $code);
+                            $($(
+                                let field: &$raw_ty = $raw_field;
+                                assert_eq!(
+                                    field as *const $raw_ty as usize - self as *const Self as usize,
+                                    align_of::<Self>() + offset_of!(Fields, $raw_field)
+                                );
+                            )+)*
+                        }
+                    )*
+                }
+            }
+        }
+
+        impl $typed<'_> {
+            /// One instance of every variant with non-default values and distinct buffers taken
+            /// from `buffers`.
+            #[cfg(test)]
+            fn samples<'a>(
+                mut buffers: impl FnMut() -> &'a mut [u8],
+            ) -> [$typed<'a>; $data_raw::COUNT] {
+                raw_conversions!(@header_samples $headers);
+                [$(
+                    raw_conversions!(
+                        @variant $typed::$variant, $headers,
+                        $($field: field_sample!($kind, buffers),)*
+                    ),
+                )*]
+            }
         }
+    };
+    // The header fields are shared by all variants. They are passed around as a single token tree
+    // because macro_rules cannot repeat them inside the repetition of the variants.
+    (@variant $typed:ident::$variant:ident, { $($header:ident),* }, $($fields:tt)*) => {
+        $typed::$variant { $($header,)* $($fields)* }
+    };
+    (@headers_from_raw $self:ident, { $($header:ident),* }) => {
+        $(let $header = RawField::from_raw($self.$header)?;)*
+    };
+    (@raw $raw:ident, { $($header:ident),* }, $data:expr) => {
+        $raw {
+            abi_version: AbiVersion::CURRENT,
+            $($header: $header.to_raw()?,)*
+            data: $data,
+        }
+    };
+    (@header_samples { $($header:ident),* }) => {
+        $(let $header = test::Sample::sample();)*
+    };
+}
+
+macro_rules! field_from_raw {
+    (slice, $validator:ident, $data:ident, $size:ident) => {
+        check_pointer_and_size($data, $size, &$validator)?
+    };
+    (mut_slice, $validator:ident, $data:ident, $size:ident) => {
+        check_mut_pointer_and_size($data, $size, &$validator)?
+    };
+    (value, $validator:ident, $raw:ident) => {
+        RawField::from_raw($raw)?
+    };
+}
+
+macro_rules! field_to_raw {
+    (slice, $field:ident) => {
+        ($field.as_ptr(), $field.len().to_raw()?)
+    };
+    (mut_slice, $field:ident) => {
+        ($field.as_mut_ptr(), $field.len().to_raw()?)
+    };
+    (value, $field:ident) => {
+        ($field.to_raw()?,)
+    };
+}
+
+#[cfg(test)]
+macro_rules! field_sample {
+    (slice, $buffers:ident) => {
+        &*$buffers()
+    };
+    (mut_slice, $buffers:ident) => {
+        $buffers()
+    };
+    (value, $buffers:ident) => {
+        test::Sample::sample()
+    };
+}
+
+raw_conversions! {
+    RequestRaw(RequestDataRaw) <=> Request { client_id, request_id, deadline };
+    GetRandom {
+        output: mut_slice(output_data: *mut u8, output_size: u32),
+    } = 0,
+    GenerateSymmetricKey {
+        key_id: value(key_id: KeyIdRaw),
+        overwrite: value(overwrite: BoolRaw),
+    } = 1,
+    GenerateKeyPair {
+        key_id: value(key_id: KeyIdRaw),
+        overwrite: value(overwrite: BoolRaw),
+    } = 2,
+    ImportSymmetricKey {
+        key_id: value(key_id: KeyIdRaw),
+        data: slice(data_data: *const u8, data_size: u32),
+        overwrite: value(overwrite: BoolRaw),
+    } = 3,
+    ImportKeyPair {
+        key_id: value(key_id: KeyIdRaw),
+        public_key: slice(public_key_data: *const u8, public_key_size: u32),
+        private_key: slice(private_key_data: *const u8, private_key_size: u32),
+        format: value(format: PrivateKeyFormatRaw),
+        overwrite: value(overwrite: BoolRaw),
+    } = 4,
+    ExportSymmetricKey {
+        key_id: value(key_id: KeyIdRaw),
+        data: mut_slice(data_data: *mut u8, data_size: u32),
+    } = 5,
+    ExportPublicKey {
+        key_id: value(key_id: KeyIdRaw),
+        public_key: mut_slice(public_key_data: *mut u8, public_key_size: u32),
+        format: value(format: PublicKeyFormatRaw),
+    } = 6,
+    ExportPrivateKey {
+        key_id: value(key_id: KeyIdRaw),
+        private_key: mut_slice(private_key_data: *mut u8, private_key_size: u32),
+    } = 7,
+    IsKeyAvailable {
+        key_id: value(key_id: KeyIdRaw),
+    } = 8,
+    EncryptChaChaPoly {
+        key_id: value(key_id: KeyIdRaw),
+        nonce: slice(nonce_data: *const u8, nonce_size: u32),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+        aad: slice(aad_data: *const u8, aad_size: u32),
+        tag: mut_slice(tag_data: *mut u8, tag_size: u32),
+    } = 9,
+    EncryptChaChaPolyExternalKey {
+        key: slice(key_data: *const u8, key_size: u32),
+        nonce: slice(nonce_data: *const u8, nonce_size: u32),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+        aad: slice(aad_data: *const u8, aad_size: u32),
+        tag: mut_slice(tag_data: *mut u8, tag_size: u32),
+    } = 10,
+    DecryptChaChaPoly {
+        key_id: value(key_id: KeyIdRaw),
+        nonce: slice(nonce_data: *const u8, nonce_size: u32),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+        aad: slice(aad_data: *const u8, aad_size: u32),
+        tag: slice(tag_data: *const u8, tag_size: u32),
+    } = 11,
+    DecryptChaChaPolyExternalKey {
+        key: slice(key_data: *const u8, key_size: u32),
+        nonce: slice(nonce_data: *const u8, nonce_size: u32),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+        aad: slice(aad_data: *const u8, aad_size: u32),
+        tag: slice(tag_data: *const u8, tag_size: u32),
+    } = 12,
+    EncryptAesGcm {
+        key_id: value(key_id: KeyIdRaw),
+        iv: slice(iv_data: *const u8, iv_size: u32),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+        aad: slice(aad_data: *const u8, aad_size: u32),
+        tag: mut_slice(tag_data: *mut u8, tag_size: u32),
+    } = 13,
+    EncryptAesGcmExternalKey {
+        key: slice(key_data: *const u8, key_size: u32),
+        iv: slice(iv_data: *const u8, iv_size: u32),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+        aad: slice(aad_data: *const u8, aad_size: u32),
+        tag: mut_slice(tag_data: *mut u8, tag_size: u32),
+    } = 14,
+    DecryptAesGcm {
+        key_id: value(key_id: KeyIdRaw),
+        iv: slice(iv_data: *const u8, iv_size: u32),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+        aad: slice(aad_data: *const u8, aad_size: u32),
+        tag: slice(tag_data: *const u8, tag_size: u32),
+    } = 15,
+    DecryptAesGcmExternalKey {
+        key: slice(key_data: *const u8, key_size: u32),
+        iv: slice(iv_data: *const u8, iv_size: u32),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+        aad: slice(aad_data: *const u8, aad_size: u32),
+        tag: slice(tag_data: *const u8, tag_size: u32),
+    } = 16,
+    EncryptAesCbc {
+        key_id: value(key_id: KeyIdRaw),
+        iv: slice(iv_data: *const u8, iv_size: u32),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+        plaintext_size: value(plaintext_size: u32),
+        padding: value(padding: PaddingRaw),
+    } = 17,
+    EncryptAesCbcExternalKey {
+        key: slice(key_data: *const u8, key_size: u32),
+        iv: slice(iv_data: *const u8, iv_size: u32),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+        plaintext_size: value(plaintext_size: u32),
+        padding: value(padding: PaddingRaw),
+    } = 18,
+    DecryptAesCbc {
+        key_id: value(key_id: KeyIdRaw),
+        iv: slice(iv_data: *const u8, iv_size: u32),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+        padding: value(padding: PaddingRaw),
+    } = 19,
+    DecryptAesCbcExternalKey {
+        key: slice(key_data: *const u8, key_size: u32),
+        iv: slice(iv_data: *const u8, iv_size: u32),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+        padding: value(padding: PaddingRaw),
+    } = 20,
+    EncryptAesEcb {
+        key_id: value(key_id: KeyIdRaw),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+    } = 21,
+    EncryptAesEcbExternalKey {
+        key: slice(key_data: *const u8, key_size: u32),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+    } = 22,
+    DecryptAesEcb {
+        key_id: value(key_id: KeyIdRaw),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+    } = 23,
+    DecryptAesEcbExternalKey {
+        key: slice(key_data: *const u8, key_size: u32),
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+    } = 24,
+    CalculateAesCmac {
+        key_id: value(key_id: KeyIdRaw),
+        message: slice(message_data: *const u8, message_size: u32),
+        tag: mut_slice(tag_data: *mut u8, tag_size: u32),
+    } = 25,
+    CalculateAesCmacExternalKey {
+        key: slice(key_data: *const u8, key_size: u32),
+        message: slice(message_data: *const u8, message_size: u32),
+        tag: mut_slice(tag_data: *mut u8, tag_size: u32),
+    } = 26,
+    VerifyAesCmac {
+        key_id: value(key_id: KeyIdRaw),
+        message: slice(message_data: *const u8, message_size: u32),
+        tag: slice(tag_data: *const u8, tag_size: u32),
+    } = 27,
+    VerifyAesCmacExternalKey {
+        key: slice(key_data: *const u8, key_size: u32),
+        message: slice(message_data: *const u8, message_size: u32),
+        tag: slice(tag_data: *const u8, tag_size: u32),
+    } = 28,
+    CalculateHmac {
+        key_id: value(key_id: KeyIdRaw),
+        hash_algorithm: value(hash_algorithm: HashAlgorithmRaw),
+        message: slice(message_data: *const u8, message_size: u32),
+        tag: mut_slice(tag_data: *mut u8, tag_size: u32),
+    } = 29,
+    CalculateHmacExternalKey {
+        key: slice(key_data: *const u8, key_size: u32),
+        hash_algorithm: value(hash_algorithm: HashAlgorithmRaw),
+        message: slice(message_data: *const u8, message_size: u32),
+        tag: mut_slice(tag_data: *mut u8, tag_size: u32),
+    } = 30,
+    VerifyHmac {
+        key_id: value(key_id: KeyIdRaw),
+        hash_algorithm: value(hash_algorithm: HashAlgorithmRaw),
+        message: slice(message_data: *const u8, message_size: u32),
+        tag: slice(tag_data: *const u8, tag_size: u32),
+    } = 31,
+    VerifyHmacExternalKey {
+        key: slice(key_data: *const u8, key_size: u32),
+        hash_algorithm: value(hash_algorithm: HashAlgorithmRaw),
+        message: slice(message_data: *const u8, message_size: u32),
+        tag: slice(tag_data: *const u8, tag_size: u32),
+    } = 32,
+    Sign {
+        key_id: value(key_id: KeyIdRaw),
+        message: slice(message_data: *const u8, message_size: u32),
+        prehashed: value(prehashed: BoolRaw),
+        hash_algorithm: value(hash_algorithm: HashAlgorithmRaw),
+        signature_format: value(signature_format: SignatureFormatRaw),
+        signature: mut_slice(signature_data: *mut u8, signature_size: u32),
+    } = 33,
+    SignExternalKey {
+        private_key: slice(key_data: *const u8, key_size: u32),
+        message: slice(message_data: *const u8, message_size: u32),
+        prehashed: value(prehashed: BoolRaw),
+        hash_algorithm: value(hash_algorithm: HashAlgorithmRaw),
+        signature_format: value(signature_format: SignatureFormatRaw),
+        signature: mut_slice(signature_data: *mut u8, signature_size: u32),
+    } = 34,
+    Verify {
+        key_id: value(key_id: KeyIdRaw),
+        message: slice(message_data: *const u8, message_size: u32),
+        prehashed: value(prehashed: BoolRaw),
+        hash_algorithm: value(hash_algorithm: HashAlgorithmRaw),
+        signature_format: value(signature_format: SignatureFormatRaw),
+        signature: slice(signature_data: *const u8, signature_size: u32),
+    } = 35,
+    VerifyExternalKey {
+        public_key: slice(key_data: *const u8, key_size: u32),
+        message: slice(message_data: *const u8, message_size: u32),
+        prehashed: value(prehashed: BoolRaw),
+        hash_algorithm: value(hash_algorithm: HashAlgorithmRaw),
+        signature_format: value(signature_format: SignatureFormatRaw),
+        signature: slice(signature_data: *const u8, signature_size: u32),
+    } = 36,
+    Ecdh {
+        public_key: slice(public_key_data: *const u8, public_key_size: u32),
+        private_key_id: value(private_key_id: KeyIdRaw),
+        shared_secret: mut_slice(shared_secret_data: *mut u8, shared_secret_size: u32),
+    } = 37,
+    EcdhExternalPrivateKey {
+        curve: value(curve: CurveRaw),
+        public_key: slice(public_key_data: *const u8, public_key_size: u32),
+        private_key: slice(private_key_data: *const u8, private_key_size: u32),
+        shared_secret: mut_slice(shared_secret_data: *mut u8, shared_secret_size: u32),
+    } = 38,
+    LoadSheKey {
+        m1: slice(m1_data: *const u8, m1_size: u32),
+        m2: slice(m2_data: *const u8, m2_size: u32),
+        m3: slice(m3_data: *const u8, m3_size: u32),
+        m4: mut_slice(m4_data: *mut u8, m4_size: u32),
+        m5: mut_slice(m5_data: *mut u8, m5_size: u32),
+    } = 39,
+    FinishSheBoot {
+        success: value(success: BoolRaw),
+    } = 40,
+    Cancel {
+        target: value(target: RequestIdRaw),
+    } = 41,
+    GetCapabilities {} = 42,
+    GetKeyInfo {
+        key_id: value(key_id: KeyIdRaw),
+    } = 43,
+}
+
+raw_conversions! {
+    ResponseRaw(ResponseDataRaw) <=> Response { client_id, request_id };
+    Error {
+        error: value(error: JobErrorRaw),
+    } = 0,
+    GetRandom {
+        data: mut_slice(data_data: *mut u8, data_size: u32),
+    } = 1,
+    GenerateSymmetricKey {} = 2,
+    GenerateKeyPair {} = 3,
+    ImportSymmetricKey {} = 4,
+    ImportKeyPair {} = 5,
+    ExportSymmetricKey {
+        key: mut_slice(key_data: *mut u8, key_size: u32),
+    } = 6,
+    ExportPublicKey {
+        public_key: mut_slice(public_key_data: *mut u8, public_key_size: u32),
+    } = 7,
+    ExportPrivateKey {
+        private_key: mut_slice(private_key_data: *mut u8, private_key_size: u32),
+    } = 8,
+    IsKeyAvailable {
+        is_available: value(is_available: u32),
+    } = 9,
+    EncryptChaChaPoly {
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+        tag: mut_slice(tag_data: *mut u8, tag_size: u32),
+    } = 10,
+    DecryptChaChaPoly {
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+    } = 11,
+    EncryptAesGcm {
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+        tag: mut_slice(tag_data: *mut u8, tag_size: u32),
+    } = 12,
+    DecryptAesGcm {
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+    } = 13,
+    EncryptAesCbc {
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+    } = 14,
+    DecryptAesCbc {
+        plaintext: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+    } = 15,
+    EncryptAesEcb {
+        buffer: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+    } = 16,
+    DecryptAesEcb {
+        plaintext: mut_slice(buffer_data: *mut u8, buffer_size: u32),
+    } = 17,
+    CalculateAesCmac {
+        tag: mut_slice(tag_data: *mut u8, tag_size: u32),
+    } = 18,
+    VerifyAesCmac {
+        verified: value(verified: BoolRaw),
+    } = 19,
+    CalculateHmac {
+        tag: mut_slice(tag_data: *mut u8, tag_size: u32),
+    } = 20,
+    VerifyHmac {
+        verified: value(verified: BoolRaw),
+    } = 21,
+    Sign {
+        signature: mut_slice(signature_data: *mut u8, signature_size: u32),
+    } = 22,
+    Verify {
+        verified: value(verified: BoolRaw),
+    } = 23,
+    Ecdh {
+        shared_secret: mut_slice(shared_secret_data: *mut u8, shared_secret_size: u32),
+    } = 24,
+    LoadSheKey {
+        m4: mut_slice(m4_data: *mut u8, m4_size: u32),
+        m5: mut_slice(m5_data: *mut u8, m5_size: u32),
+    } = 25,
+    FinishSheBoot {} = 26,
+    Cancel {} = 27,
+    Cancelled {
+        reason: value(reason: CancelReasonRaw),
+    } = 28,
+    GetCapabilities {
+        capabilities: value(capabilities: CapabilitiesRaw),
+    } = 29,
+    GetKeyInfo {
+        key_info: value(key_info: KeyInfoRaw),
+    } = 30,
+}
+
+impl From<KeyId> for KeyIdRaw {
+    fn from(value: KeyId) -> Self {
+        value.0
     }
 }
 
-// Outgoing direction. No validation needed.
-impl ResponseRaw {
-    /// Create a `ResponseRaw` from a raw pointer.
-    ///
-    /// # Arguments
-    ///
-    /// * `ptr`: The pointer to the first byte of the `ResponseRaw`
-    ///
-    /// # Safety
-    ///
-    /// The provided pointer must be valid (non-null, aligned, point to valid memory) and point to a
-    /// valid `ResponseRaw` instance.  
-    ///
-    pub unsafe fn from_raw(ptr: *const u8) -> Result<Self, ValidationError> {
-        // We create a copy of the untrusted response on the stack to prevent a potential
-        // point-of-check/point-of-use issue
-        let mut response: MaybeUninit<ResponseRaw> = MaybeUninit::uninit();
+impl From<KeyIdRaw> for KeyId {
+    fn from(value: KeyIdRaw) -> Self {
+        KeyId(value)
+    }
+}
 
-        // SAFETY: Pointer and size must be checked by integrator
-        unsafe {
-            core::ptr::copy(ptr.cast(), response.as_mut_ptr(), 1);
+impl From<Curve> for CurveRaw {
+    fn from(value: Curve) -> Self {
+        match value {
+            Curve::NistP256 => NIST_P256,
+            Curve::NistP384 => NIST_P384,
         }
+    }
+}
 
-        // SAFETY: response is of type ResponseRaw, so adding the offset of a field of such a struct
-        // still falls under the object behind the pointer
-        let tag: u8 = unsafe {
-            *(response
-                .as_ptr()
-                .cast::<u8>()
-                .add(offset_of!(ResponseRaw, data)))
-        };
+impl TryFrom<CurveRaw> for Curve {
+    type Error = ValidationError;
 
-        // Validate tag value. Invalid tag values cause UB when transmuted into an enum.
-        if tag >= ResponseDataRaw::COUNT as u8 {
-            return Err(ValidationError::InvalidTagValue);
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            NIST_P256 => Ok(Self::NistP256),
+            NIST_P384 => Ok(Self::NistP384),
+            _ => Err(ValidationError::InvalidValue),
         }
+    }
+}
 
-        // SAFETY: Besides the tag, which we checked, all other members are ok with any value
-        Ok(unsafe { response.assume_init() })
+impl From<HashAlgorithm> for HashAlgorithmRaw {
+    fn from(value: HashAlgorithm) -> Self {
+        match value {
+            HashAlgorithm::Sha2_256 => SHA2_256,
+            HashAlgorithm::Sha2_384 => SHA2_384,
+            HashAlgorithm::Sha2_512 => SHA2_512,
+            HashAlgorithm::Sha3_256 => SHA3_256,
+            HashAlgorithm::Sha3_384 => SHA3_384,
+            HashAlgorithm::Sha3_512 => SHA3_512,
+        }
     }
 }
 
-impl From<Response<'_>> for ResponseRaw {
-    fn from(value: Response<'_>) -> Self {
+impl TryFrom<HashAlgorithmRaw> for HashAlgorithm {
+    type Error = ValidationError;
+
+    fn try_from(value: HashAlgorithmRaw) -> Result<Self, Self::Error> {
         match value {
-            Response::Error {
-                client_id,
-                request_id,
-                error,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::Error {
-                    error: error.into(),
-                },
-            },
-            Response::GetRandom {
-                client_id,
-                request_id,
-                data,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::GetRandom {
-                    data_data: data.as_mut_ptr(),
-                    data_size: data.len() as u32,
-                },
-            },
-            Response::GenerateSymmetricKey {
-                client_id,
-                request_id,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::GenerateSymmetricKey {},
-            },
-            Response::GenerateKeyPair {
-                client_id,
-                request_id,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::GenerateKeyPair {},
-            },
-            Response::ImportSymmetricKey {
-                client_id,
-                request_id,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::ImportSymmetricKey {},
-            },
-            Response::ImportKeyPair {
-                client_id,
-                request_id,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::ImportKeyPair {},
-            },
-            Response::ExportSymmetricKey {
-                client_id,
-                request_id,
-                key,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::ExportSymmetricKey {
-                    key_data: key.as_mut_ptr(),
-                    key_size: key.len() as u32,
-                },
-            },
-            Response::ExportPublicKey {
-                client_id,
-                request_id,
-                public_key,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::ExportPublicKey {
-                    public_key_data: public_key.as_mut_ptr(),
-                    public_key_size: public_key.len() as u32,
-                },
-            },
-            Response::ExportPrivateKey {
-                client_id,
-                request_id,
-                private_key,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::ExportPrivateKey {
-                    private_key_data: private_key.as_mut_ptr(),
-                    private_key_size: private_key.len() as u32,
-                },
-            },
-            Response::IsKeyAvailable {
-                client_id,
-                request_id,
-                is_available,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::IsKeyAvailable {
-                    is_available: is_available.into(),
-                },
-            },
-            Response::EncryptChaChaPoly {
-                client_id,
-                request_id,
-                buffer,
-                tag,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::EncryptChaChaPoly {
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                    tag_data: tag.as_mut_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Response::DecryptChaChaPoly {
-                client_id,
-                request_id,
-                buffer,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::DecryptChaChaPoly {
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                },
-            },
-            Response::EncryptAesGcm {
-                client_id,
-                request_id,
-                buffer,
-                tag,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::EncryptAesGcm {
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                    tag_data: tag.as_mut_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Response::DecryptAesGcm {
-                client_id,
-                request_id,
-                buffer,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::DecryptAesGcm {
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                },
-            },
-            Response::EncryptAesCbc {
-                client_id,
-                request_id,
-                buffer,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::EncryptAesCbc {
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                },
-            },
-            Response::DecryptAesCbc {
-                client_id,
-                request_id,
-                plaintext: buffer,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::DecryptAesCbc {
-                    buffer_data: buffer.as_mut_ptr(),
-                    buffer_size: buffer.len() as u32,
-                },
-            },
-            Response::CalculateAesCmac {
-                client_id,
-                request_id,
-                tag,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::CalculateAesCmac {
-                    tag_data: tag.as_mut_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Response::VerifyAesCmac {
-                client_id,
-                request_id,
-                verified,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::VerifyAesCmac {
-                    verified: verified.into(),
-                },
-            },
-            Response::CalculateHmac {
-                client_id,
-                request_id,
-                tag,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::CalculateHmac {
-                    tag_data: tag.as_mut_ptr(),
-                    tag_size: tag.len() as u32,
-                },
-            },
-            Response::VerifyHmac {
-                client_id,
-                request_id,
-                verified,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::VerifyHmac {
-                    verified: verified.into(),
-                },
-            },
-            Response::Sign {
-                client_id,
-                request_id,
-                signature,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::Sign {
-                    signature_data: signature.as_mut_ptr(),
-                    signature_size: signature.len() as u32,
-                },
-            },
-            Response::Verify {
-                client_id,
-                request_id,
-                verified,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::Verify {
-                    verified: verified.into(),
-                },
-            },
-            Response::Ecdh {
-                client_id,
-                request_id,
-                shared_secret,
-            } => ResponseRaw {
-                client_id: client_id.into(),
-                request_id: request_id.into(),
-                data: ResponseDataRaw::Ecdh {
-                    shared_secret_data: shared_secret.as_mut_ptr(),
-                    shared_secret_size: shared_secret.len() as u32,
-                },
-            },
+            SHA2_256 => Ok(Self::Sha2_256),
+            SHA2_384 => Ok(Self::Sha2_384),
+            SHA2_512 => Ok(Self::Sha2_512),
+            SHA3_256 => Ok(Self::Sha3_256),
+            SHA3_384 => Ok(Self::Sha3_384),
+            SHA3_512 => Ok(Self::Sha3_512),
+            _ => Err(ValidationError::InvalidValue),
+        }
+    }
+}
+
+impl From<Padding> for PaddingRaw {
+    fn from(value: Padding) -> Self {
+        match value {
+            Padding::Pkcs7 => PKCS7,
+            Padding::NoPadding => NO_PADDING,
+        }
+    }
+}
+
+impl TryFrom<PaddingRaw> for Padding {
+    type Error = ValidationError;
+
+    fn try_from(value: PaddingRaw) -> Result<Self, Self::Error> {
+        match value {
+            PKCS7 => Ok(Self::Pkcs7),
+            NO_PADDING => Ok(Self::NoPadding),
+            _ => Err(ValidationError::InvalidValue),
+        }
+    }
+}
+
+impl From<PrivateKeyFormat> for PrivateKeyFormatRaw {
+    fn from(value: PrivateKeyFormat) -> Self {
+        match value {
+            PrivateKeyFormat::Raw => PRIVATE_KEY_RAW,
+            PrivateKeyFormat::Pkcs8Der => PRIVATE_KEY_PKCS8_DER,
+            PrivateKeyFormat::Sec1Der => PRIVATE_KEY_SEC1_DER,
+            PrivateKeyFormat::Pem => PRIVATE_KEY_PEM,
         }
     }
 }
 
-impl From<KeyId> for KeyIdRaw {
-    fn from(value: KeyId) -> Self {
-        value.0
+impl TryFrom<PrivateKeyFormatRaw> for PrivateKeyFormat {
+    type Error = ValidationError;
+
+    fn try_from(value: PrivateKeyFormatRaw) -> Result<Self, Self::Error> {
+        match value {
+            PRIVATE_KEY_RAW => Ok(Self::Raw),
+            PRIVATE_KEY_PKCS8_DER => Ok(Self::Pkcs8Der),
+            PRIVATE_KEY_SEC1_DER => Ok(Self::Sec1Der),
+            PRIVATE_KEY_PEM => Ok(Self::Pem),
+            _ => Err(ValidationError::InvalidValue),
+        }
+    }
+}
+
+impl From<PublicKeyFormat> for PublicKeyFormatRaw {
+    fn from(value: PublicKeyFormat) -> Self {
+        match value {
+            PublicKeyFormat::Raw => PUBLIC_KEY_RAW,
+            PublicKeyFormat::Sec1Uncompressed => PUBLIC_KEY_SEC1_UNCOMPRESSED,
+            PublicKeyFormat::Sec1Compressed => PUBLIC_KEY_SEC1_COMPRESSED,
+            PublicKeyFormat::SpkiDer => PUBLIC_KEY_SPKI_DER,
+            PublicKeyFormat::SpkiPem => PUBLIC_KEY_SPKI_PEM,
+        }
+    }
+}
+
+impl TryFrom<PublicKeyFormatRaw> for PublicKeyFormat {
+    type Error = ValidationError;
+
+    fn try_from(value: PublicKeyFormatRaw) -> Result<Self, Self::Error> {
+        match value {
+            PUBLIC_KEY_RAW => Ok(Self::Raw),
+            PUBLIC_KEY_SEC1_UNCOMPRESSED => Ok(Self::Sec1Uncompressed),
+            PUBLIC_KEY_SEC1_COMPRESSED => Ok(Self::Sec1Compressed),
+            PUBLIC_KEY_SPKI_DER => Ok(Self::SpkiDer),
+            PUBLIC_KEY_SPKI_PEM => Ok(Self::SpkiPem),
+            _ => Err(ValidationError::InvalidValue),
+        }
+    }
+}
+
+impl From<SignatureFormat> for SignatureFormatRaw {
+    fn from(value: SignatureFormat) -> Self {
+        match value {
+            SignatureFormat::Raw => SIGNATURE_RAW,
+            SignatureFormat::Der => SIGNATURE_DER,
+        }
+    }
+}
+
+impl From<CancelReason> for CancelReasonRaw {
+    fn from(value: CancelReason) -> Self {
+        match value {
+            CancelReason::Cancelled => CANCEL_REASON_CANCELLED,
+            CancelReason::Expired => CANCEL_REASON_EXPIRED,
+        }
+    }
+}
+
+impl TryFrom<SignatureFormatRaw> for SignatureFormat {
+    type Error = ValidationError;
+
+    fn try_from(value: SignatureFormatRaw) -> Result<Self, Self::Error> {
+        match value {
+            SIGNATURE_RAW => Ok(Self::Raw),
+            SIGNATURE_DER => Ok(Self::Der),
+            _ => Err(ValidationError::InvalidValue),
+        }
+    }
+}
+
+impl TryFrom<CancelReasonRaw> for CancelReason {
+    type Error = ValidationError;
+
+    fn try_from(value: CancelReasonRaw) -> Result<Self, Self::Error> {
+        match value {
+            CANCEL_REASON_CANCELLED => Ok(Self::Cancelled),
+            CANCEL_REASON_EXPIRED => Ok(Self::Expired),
+            _ => Err(ValidationError::InvalidValue),
+        }
+    }
+}
+
+/// Conversion of a field of a request or response that is not a buffer to its raw version.
+trait RawField: Sized {
+    type Raw;
+
+    fn to_raw(self) -> Result<Self::Raw, ValidationError>;
+
+    fn from_raw(raw: Self::Raw) -> Result<Self, ValidationError>;
+}
+
+/// Implements [RawField] with the `From` conversions of types whose raw values are all valid and
+/// the `TryFrom` conversions of types that reject some raw values.
+macro_rules! raw_field {
+    ($($ty:ty => $raw:ty),* $(,)?) => {
+        $(
+            impl RawField for $ty {
+                type Raw = $raw;
+
+                fn to_raw(self) -> Result<$raw, ValidationError> {
+                    Ok(self.into())
+                }
+
+                fn from_raw(raw: $raw) -> Result<Self, ValidationError> {
+                    raw.try_into().map_err(|_| ValidationError::InvalidValue)
+                }
+            }
+        )*
+    };
+}
+
+raw_field! {
+    ClientId => ClientIdRaw,
+    RequestId => RequestIdRaw,
+    KeyId => KeyIdRaw,
+    Curve => CurveRaw,
+    HashAlgorithm => HashAlgorithmRaw,
+    Padding => PaddingRaw,
+    PrivateKeyFormat => PrivateKeyFormatRaw,
+    PublicKeyFormat => PublicKeyFormatRaw,
+    SignatureFormat => SignatureFormatRaw,
+    CancelReason => CancelReasonRaw,
+    jobs::Error => JobErrorRaw,
+}
+
+impl RawField for bool {
+    type Raw = BoolRaw;
+
+    fn to_raw(self) -> Result<BoolRaw, ValidationError> {
+        Ok(self.into())
+    }
+
+    fn from_raw(raw: BoolRaw) -> Result<Self, ValidationError> {
+        Ok(raw != 0)
+    }
+}
+
+impl RawField for usize {
+    type Raw = u32;
+
+    fn to_raw(self) -> Result<u32, ValidationError> {
+        u32::try_from(self).map_err(|_| ValidationError::InvalidValue)
+    }
+
+    fn from_raw(raw: u32) -> Result<Self, ValidationError> {
+        usize::try_from(raw).map_err(|_| ValidationError::InvalidValue)
     }
 }
 
-impl From<KeyIdRaw> for KeyId {
-    fn from(value: KeyIdRaw) -> Self {
-        KeyId(value)
+/// Deadline in ticks of the core clock
+impl RawField for Option<u64> {
+    type Raw = DeadlineRaw;
+
+    fn to_raw(self) -> Result<DeadlineRaw, ValidationError> {
+        Ok(self.unwrap_or(NO_DEADLINE))
+    }
+
+    fn from_raw(raw: DeadlineRaw) -> Result<Self, ValidationError> {
+        Ok((raw != NO_DEADLINE).then_some(raw))
     }
 }
 
-impl From<Curve> for CurveRaw {
-    fn from(value: Curve) -> Self {
-        match value {
-            Curve::NistP256 => NIST_P256,
-            Curve::NistP384 => NIST_P384,
+impl RawField for Capabilities {
+    type Raw = CapabilitiesRaw;
+
+    fn to_raw(self) -> Result<CapabilitiesRaw, ValidationError> {
+        Ok(CapabilitiesRaw {
+            abi_version: self.abi_version,
+            key_store: self.key_store.into(),
+            request_types: self.request_types.bits(),
+            hash_algorithms: self.hash_algorithms.bits(),
+            curves: self.curves.bits(),
+            symmetric_primitives: self.symmetric_primitives.bits(),
+            key_types: self.key_types.bits(),
+            max_symmetric_key_size: self.max_symmetric_key_size.to_raw()?,
+            max_request_size: self
+                .max_request_size
+                .map_or(u32::MAX, |size| size.min(u32::MAX as usize - 1) as u32),
+        })
+    }
+
+    fn from_raw(raw: CapabilitiesRaw) -> Result<Self, ValidationError> {
+        Ok(Capabilities {
+            abi_version: raw.abi_version,
+            key_store: RawField::from_raw(raw.key_store)?,
+            request_types: RequestTypes::from_bits(raw.request_types),
+            hash_algorithms: HashAlgorithms::from_bits(raw.hash_algorithms),
+            curves: Curves::from_bits(raw.curves),
+            symmetric_primitives: SymmetricPrimitives::from_bits(raw.symmetric_primitives),
+            key_types: KeyKinds::from_bits(raw.key_types),
+            max_symmetric_key_size: RawField::from_raw(raw.max_symmetric_key_size)?,
+            max_request_size: match raw.max_request_size {
+                u32::MAX => None,
+                size => Some(RawField::from_raw(size)?),
+            },
+        })
+    }
+}
+
+impl RawField for KeyInfo {
+    type Raw = KeyInfoRaw;
+
+    fn to_raw(self) -> Result<KeyInfoRaw, ValidationError> {
+        let mut raw = KeyInfoRaw {
+            key_id: self.id.into(),
+            key_type: KEY_TYPE_SYMMETRIC,
+            curve: 0,
+            algorithm: 0,
+            other_algorithm: 0,
+            public_key_size: 0,
+            private_key_size: 0,
+            permissions: 0,
+        };
+        for (granted, bit) in [
+            (self.permissions.import, KEY_PERMISSION_IMPORT),
+            (
+                self.permissions.export_private,
+                KEY_PERMISSION_EXPORT_PRIVATE,
+            ),
+            (self.permissions.overwrite, KEY_PERMISSION_OVERWRITE),
+            (self.permissions.delete, KEY_PERMISSION_DELETE),
+        ] {
+            if granted {
+                raw.permissions |= bit;
+            }
+        }
+        match self.ty {
+            KeyType::Symmetric(size) => raw.private_key_size = size.to_raw()?,
+            KeyType::Asymmetric(curve) => {
+                raw.key_type = KEY_TYPE_ASYMMETRIC;
+                raw.curve = curve.into();
+            }
+            KeyType::KeyPair {
+                algorithm,
+                public_key_size,
+                private_key_size,
+            } => {
+                raw.key_type = KEY_TYPE_KEY_PAIR;
+                raw.algorithm = match algorithm {
+                    KeyPairAlgorithm::Ed25519 => KEY_PAIR_ED25519,
+                    KeyPairAlgorithm::X25519 => KEY_PAIR_X25519,
+                    KeyPairAlgorithm::Other(other) => {
+                        raw.other_algorithm = other;
+                        KEY_PAIR_OTHER
+                    }
+                };
+                raw.public_key_size = public_key_size.to_raw()?;
+                raw.private_key_size = private_key_size.to_raw()?;
+            }
         }
+        Ok(raw)
+    }
+
+    fn from_raw(raw: KeyInfoRaw) -> Result<Self, ValidationError> {
+        let ty = match raw.key_type {
+            KEY_TYPE_SYMMETRIC => KeyType::Symmetric(RawField::from_raw(raw.private_key_size)?),
+            KEY_TYPE_ASYMMETRIC => KeyType::Asymmetric(raw.curve.try_into()?),
+            KEY_TYPE_KEY_PAIR => KeyType::KeyPair {
+                algorithm: match raw.algorithm {
+                    KEY_PAIR_ED25519 => KeyPairAlgorithm::Ed25519,
+                    KEY_PAIR_X25519 => KeyPairAlgorithm::X25519,
+                    KEY_PAIR_OTHER => KeyPairAlgorithm::Other(raw.other_algorithm),
+                    _ => return Err(ValidationError::InvalidValue),
+                },
+                public_key_size: RawField::from_raw(raw.public_key_size)?,
+                private_key_size: RawField::from_raw(raw.private_key_size)?,
+            },
+            _ => return Err(ValidationError::InvalidValue),
+        };
+        Ok(KeyInfo {
+            id: raw.key_id.into(),
+            ty,
+            permissions: KeyPermissions {
+                import: raw.permissions & KEY_PERMISSION_IMPORT != 0,
+                export_private: raw.permissions & KEY_PERMISSION_EXPORT_PRIVATE != 0,
+                overwrite: raw.permissions & KEY_PERMISSION_OVERWRITE != 0,
+                delete: raw.permissions & KEY_PERMISSION_DELETE != 0,
+            },
+        })
     }
 }
 
-impl TryFrom<CurveRaw> for Curve {
-    type Error = ValidationError;
+/// Hash algorithm of `Sign` and `Verify` requests. `None` selects the default of the curve.
+impl RawField for Option<HashAlgorithm> {
+    type Raw = HashAlgorithmRaw;
 
-    fn try_from(value: u32) -> Result<Self, Self::Error> {
-        match value {
-            NIST_P256 => Ok(Self::NistP256),
-            NIST_P384 => Ok(Self::NistP384),
-            _ => Err(ValidationError::InvalidValue),
+    fn to_raw(self) -> Result<HashAlgorithmRaw, ValidationError> {
+        Ok(self.map_or(CURVE_DEFAULT_HASH, Into::into))
+    }
+
+    fn from_raw(raw: HashAlgorithmRaw) -> Result<Self, ValidationError> {
+        match raw {
+            CURVE_DEFAULT_HASH => Ok(None),
+            raw => Ok(Some(raw.try_into()?)),
         }
     }
 }
 
-impl From<HashAlgorithm> for HashAlgorithmRaw {
-    fn from(value: HashAlgorithm) -> Self {
-        match value {
-            HashAlgorithm::Sha2_256 => SHA2_256,
-            HashAlgorithm::Sha2_384 => SHA2_384,
-            HashAlgorithm::Sha2_512 => SHA2_512,
-            HashAlgorithm::Sha3_256 => SHA3_256,
-            HashAlgorithm::Sha3_384 => SHA3_384,
-            HashAlgorithm::Sha3_512 => SHA3_512,
+/// Check of untrusted pointer and size pairs in raw requests.
+pub trait Validator {
+    /// Check whether the HSM may access `size` bytes at `data` on behalf of the client. `writable`
+    /// is set if the HSM writes to the memory.
+    fn is_valid(&self, data: *const u8, size: u32, writable: bool) -> bool;
+}
+
+/// Functions only check the location of the memory and do not distinguish read and write access.
+impl<F: Fn(*const u8, u32) -> bool> Validator for F {
+    fn is_valid(&self, data: *const u8, size: u32, _writable: bool) -> bool {
+        self(data, size)
+    }
+}
+
+/// Validator for the buffers of a response on the client side. A response may only refer to the
+/// buffers of the request it answers that the HSM is allowed to write to.
+#[derive(Clone, Debug, Default)]
+pub struct RequestBuffers {
+    regions: heapless::Vec<(usize, usize), { jobs::MAX_BUFFERS }>,
+}
+
+impl RequestBuffers {
+    /// Remember the writable buffers of `request`.
+    pub fn new(request: &Request) -> Self {
+        RequestBuffers {
+            regions: request
+                .buffers()
+                .iter()
+                .filter(|(_, writable)| *writable)
+                .map(|(buffer, _)| {
+                    let start = buffer.as_ptr() as usize;
+                    (start, start + buffer.len())
+                })
+                .collect(),
         }
     }
 }
 
-impl TryFrom<HashAlgorithmRaw> for HashAlgorithm {
-    type Error = ValidationError;
+impl Validator for RequestBuffers {
+    fn is_valid(&self, data: *const u8, size: u32, _writable: bool) -> bool {
+        let start = data as usize;
+        let Some(end) = start.checked_add(size as usize) else {
+            return false;
+        };
+        self.regions
+            .iter()
+            .any(|&(region_start, region_end)| region_start <= start && end <= region_end)
+    }
+}
 
-    fn try_from(value: HashAlgorithmRaw) -> Result<Self, Self::Error> {
-        match value {
-            SHA2_256 => Ok(Self::Sha2_256),
-            SHA2_384 => Ok(Self::Sha2_384),
-            SHA2_512 => Ok(Self::Sha2_512),
-            SHA3_256 => Ok(Self::Sha3_256),
-            SHA3_384 => Ok(Self::Sha3_384),
-            SHA3_512 => Ok(Self::Sha3_512),
-            _ => Err(ValidationError::InvalidValue),
+/// Maximum number of slices in a single request
+const MAX_SLICES: usize = 8;
+
+/// Memory region of a slice that was checked by a [SliceChecker].
+struct Region {
+    start: usize,
+    end: usize,
+    mutable: bool,
+}
+
+/// Checks the untrusted pointer and size pairs of a single request.
+///
+/// Besides asking the integrator-provided validator, the checker remembers the regions of all
+/// slices it handed out. A slice that overlaps with a previous slice is rejected if either of them
+/// is mutable, as aliasing a mutable slice is undefined behavior. Empty slices never overlap.
+/// Slices of other requests are not known to the checker and are not checked for overlaps.
+pub(crate) struct SliceChecker<'v, V: Validator> {
+    validator: &'v V,
+    regions: RefCell<heapless::Vec<Region, MAX_SLICES>>,
+}
+
+impl<'v, V: Validator> SliceChecker<'v, V> {
+    pub(crate) fn new(validator: &'v V) -> Self {
+        SliceChecker {
+            validator,
+            regions: RefCell::new(heapless::Vec::new()),
+        }
+    }
+
+    fn check(&self, data: *const u8, size: u32, mutable: bool) -> Result<(), ValidationError> {
+        if data.is_null() {
+            return Err(ValidationError::InvalidPointer);
+        }
+        let start = data as usize;
+        let end = start
+            .checked_add(size as usize)
+            .ok_or(ValidationError::InvalidPointer)?;
+        if !self.validator.is_valid(data, size, mutable) {
+            return Err(ValidationError::InvalidPointer);
+        }
+        if start == end {
+            return Ok(());
+        }
+        let mut regions = self.regions.borrow_mut();
+        if regions
+            .iter()
+            .any(|region| (mutable || region.mutable) && start < region.end && region.start < end)
+        {
+            return Err(ValidationError::OverlappingSlices);
         }
+        regions
+            .push(Region {
+                start,
+                end,
+                mutable,
+            })
+            // No request has more than `MAX_SLICES` slices
+            .map_err(|_| ValidationError::InvalidValue)
     }
 }
 
 /// Check an untrusted pointer and size pair using a provided validator function.
-fn check_pointer_and_size<'a>(
+pub(crate) fn check_pointer_and_size<'a>(
     data: *const u8,
     size: u32,
-    validator: &impl Fn(*const u8, u32) -> bool,
+    validator: &SliceChecker<impl Validator>,
 ) -> Result<&'a [u8], ValidationError> {
-    if data.is_null() {
-        return Err(ValidationError::InvalidPointer);
-    }
-    if !validator(data, size) {
-        return Err(ValidationError::InvalidPointer);
-    }
-    // SAFETY: Checked by integrator-provided validator
+    validator.check(data, size, false)?;
+    // SAFETY: Checked by integrator-provided validator. The slice does not overlap with mutable
+    // slices of the same request.
     Ok(unsafe { slice::from_raw_parts(data, size as usize) })
 }
 
 /// Check an untrusted pointer and size pair using a provided validator function.
-fn check_mut_pointer_and_size<'a>(
+pub(crate) fn check_mut_pointer_and_size<'a>(
     data: *mut u8,
     size: u32,
-    validator: &impl Fn(*const u8, u32) -> bool,
+    validator: &SliceChecker<impl Validator>,
 ) -> Result<&'a mut [u8], ValidationError> {
-    if data.is_null() {
-        return Err(ValidationError::InvalidPointer);
-    }
-    if !validator(data, size) {
-        return Err(ValidationError::InvalidPointer);
-    }
-    // SAFETY: Checked by integrator-provided validator
+    validator.check(data, size, true)?;
+    // SAFETY: Checked by integrator-provided validator. The slice does not overlap with other
+    // slices of the same request.
     Ok(unsafe { slice::from_raw_parts_mut(data, size as usize) })
 }
 
-fn bool_raw_to_bool(overwrite: BoolRaw) -> bool {
-    overwrite != 0
-}
-
 /// Function to trigger the generation of `RequestRaw` and `ResponseRaw` definition
 #[no_mangle]
 pub extern "C" fn trigger_cbindgen_request_response_raw(
@@ -2121,21 +1869,186 @@ pub extern "C" fn trigger_cbindgen_request_response_raw(
 
 #[cfg(test)]
 mod test {
+    extern crate std;
+
     use super::*;
     use crate::common::jobs::Request::GetRandom;
-    use crate::common::jobs::{ClientId, RequestId};
+    use crate::common::jobs::{KeyKind, RequestType, SymmetricPrimitive};
+    use crate::crypto;
+    use std::{format, vec};
+
+    /// Non-default value of a field for the round-trip tests.
+    pub(super) trait Sample {
+        fn sample() -> Self;
+    }
+
+    macro_rules! sample {
+        ($($ty:ty => $value:expr),* $(,)?) => {
+            $(
+                impl Sample for $ty {
+                    fn sample() -> Self {
+                        $value
+                    }
+                }
+            )*
+        };
+    }
+
+    sample! {
+        ClientId => ClientId(3),
+        RequestId => RequestId(5),
+        Option<u64> => Some(7),
+        KeyId => KeyId(11),
+        bool => true,
+        usize => 13,
+        Curve => Curve::NistP384,
+        HashAlgorithm => HashAlgorithm::Sha3_384,
+        Option<HashAlgorithm> => Some(HashAlgorithm::Sha2_512),
+        Padding => Padding::NoPadding,
+        PrivateKeyFormat => PrivateKeyFormat::Sec1Der,
+        PublicKeyFormat => PublicKeyFormat::Sec1Compressed,
+        SignatureFormat => SignatureFormat::Der,
+        CancelReason => CancelReason::Expired,
+        jobs::Error => jobs::Error::Crypto(crypto::Error::InvalidTagSize),
+        Capabilities => Capabilities {
+            abi_version: AbiVersion::new(1, 2),
+            request_types: RequestTypes::new()
+                .with(RequestType::Sign)
+                .with(RequestType::GetCapabilities),
+            hash_algorithms: HashAlgorithms::new().with(HashAlgorithm::Sha3_256),
+            curves: Curves::new().with(Curve::NistP384),
+            symmetric_primitives: SymmetricPrimitives::new().with(SymmetricPrimitive::AesCmac),
+            key_types: KeyKinds::new().with(KeyKind::KeyPair),
+            key_store: true,
+            max_symmetric_key_size: 17,
+            max_request_size: Some(19),
+        },
+        KeyInfo => KeyInfo {
+            id: KeyId(23),
+            ty: KeyType::KeyPair {
+                algorithm: KeyPairAlgorithm::Other(29),
+                public_key_size: 31,
+                private_key_size: 37,
+            },
+            permissions: KeyPermissions {
+                import: true,
+                export_private: false,
+                overwrite: true,
+                delete: false,
+            },
+        },
+    }
+
+    /// Tag of a raw request or response, which is the code of its type
+    fn tag<T>(data: &T) -> u8 {
+        // SAFETY: The raw data enums are `repr(C, u8)`, so they start with their tag
+        unsafe { *(data as *const T).cast::<u8>() }
+    }
+
+    /// Filled buffers of increasing size carved from `arena`.
+    fn buffers<'a>(arena: &'a mut [u8]) -> impl FnMut() -> &'a mut [u8] {
+        let mut rest = arena;
+        let mut size = 0;
+        move || {
+            size += 1;
+            let (buffer, tail) = core::mem::take(&mut rest).split_at_mut(size);
+            buffer.fill(size as u8);
+            rest = tail;
+            buffer
+        }
+    }
+
+    const ARENA_SIZE: usize = 16384;
+
+    #[test]
+    fn test_request_round_trip() {
+        let mut arena = vec![0u8; ARENA_SIZE];
+        let always_valid = |_data: *const u8, _size: u32| true;
+        for (code, request) in Request::samples(buffers(&mut arena))
+            .into_iter()
+            .enumerate()
+        {
+            let expected = format!("{:?}", request);
+            let expected_slices = slices(&request);
+            let request_type = request.get_type();
+            let request_raw: RequestRaw = request.try_into().expect("failed to convert request");
+            // Codes are consecutive and shared with the request types
+            assert_eq!(tag(&request_raw.data) as usize, code);
+            request_raw.data.assert_declaration();
+            assert_eq!(request_type as usize, code);
+            let request = request_raw
+                .verify(&always_valid)
+                .expect("failed to verify raw request");
+            assert_eq!(format!("{:?}", request), expected);
+            assert_eq!(slices(&request), expected_slices);
+            let request_raw_again: RequestRaw =
+                request.try_into().expect("failed to convert request");
+            assert_eq!(
+                format!("{:?}", request_raw_again),
+                format!("{:?}", request_raw)
+            );
+        }
+    }
+
+    #[test]
+    fn test_response_round_trip() {
+        let mut arena = vec![0u8; ARENA_SIZE];
+        let always_valid = |_data: *const u8, _size: u32| true;
+        for (code, response) in Response::samples(buffers(&mut arena))
+            .into_iter()
+            .enumerate()
+        {
+            let expected = format!("{:?}", response);
+            let response_raw: ResponseRaw =
+                response.try_into().expect("failed to convert response");
+            // Codes are consecutive, so tags can be validated by comparing them with the count
+            assert_eq!(tag(&response_raw.data) as usize, code);
+            response_raw.data.assert_declaration();
+            let response = response_raw
+                .verify(&always_valid)
+                .expect("failed to verify raw response");
+            assert_eq!(format!("{:?}", response), expected);
+            // The raw version also contains the buffer pointers
+            let response_raw_again: ResponseRaw =
+                response.try_into().expect("failed to convert response");
+            assert_eq!(
+                format!("{:?}", response_raw_again),
+                format!("{:?}", response_raw)
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_oversized_values() {
+        let oversized = u32::MAX as usize + 1;
+        assert_eq!(oversized.to_raw(), Err(ValidationError::InvalidValue));
+        let mut capabilities = Capabilities::sample();
+        capabilities.max_symmetric_key_size = oversized;
+        let response = Response::GetCapabilities {
+            client_id: ClientId::sample(),
+            request_id: RequestId::sample(),
+            capabilities,
+        };
+        assert_eq!(
+            ResponseRaw::try_from(response).unwrap_err(),
+            ValidationError::InvalidValue
+        );
+    }
 
     #[test]
     fn test_serialize_deserialize() {
         let client_id = ClientId(5);
         let request_id = RequestId(7);
         let mut output_buffer = [0u8; 16];
+        let deadline = Some(11);
         let request = GetRandom {
             client_id,
             request_id,
+            deadline,
             output: &mut output_buffer,
         };
-        let request_raw: RequestRaw = request.into();
+        let request_raw: RequestRaw = request.try_into().expect("failed to convert request");
         let request_raw_ptr = &request_raw as *const RequestRaw as *const u8;
         // SAFETY: RequestRaw is reconstructed from instance on the stack created just now
         let reconstructed_request_raw = unsafe { RequestRaw::from_raw(request_raw_ptr) }
@@ -2148,10 +2061,12 @@ mod test {
             GetRandom {
                 client_id: reconstructed_client_id,
                 request_id: reconstructed_request_id,
+                deadline: reconstructed_deadline,
                 output: reconstructed_output,
             } => {
                 assert_eq!(reconstructed_client_id, client_id);
                 assert_eq!(reconstructed_request_id, request_id);
+                assert_eq!(reconstructed_deadline, deadline);
                 assert_eq!(reconstructed_output.as_ptr(), output_buffer.as_ptr());
                 assert_eq!(reconstructed_output.len(), output_buffer.len());
             }
@@ -2177,10 +2092,20 @@ mod test {
         let request = GetRandom {
             client_id,
             request_id,
+            deadline: None,
             // SAFETY: Raw data format in shared memory: RequestResponseRawPair || output
             output: unsafe { slice::from_raw_parts_mut(output_start, OUTPUT_SIZE) },
         };
-        let request_raw = request.into();
+        let request_raw = request.try_into().expect("failed to convert request");
+        // SAFETY: Raw data format in shared memory: RequestResponseRawPair || output
+        unsafe {
+            for offset in [
+                offset_of!(RequestResponseRawPair, request) + offset_of!(RequestRaw, abi_version),
+                offset_of!(RequestResponseRawPair, response) + offset_of!(ResponseRaw, abi_version),
+            ] {
+                *request_response_start.add(offset).cast() = AbiVersion::CURRENT;
+            }
+        }
         let mut request_response_pair =
             // SAFETY: Raw data format in shared memory: RequestResponseRawPair || output
             unsafe { RequestResponseRawPair::from_raw(request_response_start) }
@@ -2213,6 +2138,85 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_abi_version() {
+        let request_raw: RequestRaw = Request::GetCapabilities {
+            client_id: ClientId(5),
+            request_id: RequestId(7),
+            deadline: None,
+        }
+        .try_into()
+        .expect("failed to convert request");
+        assert_eq!(request_raw.abi_version, AbiVersion::CURRENT);
+        let with_version = |major: u16, minor: u16, tag: u8| {
+            let mut request_raw = request_raw;
+            request_raw.abi_version = AbiVersion::new(major, minor);
+            let request_raw_ptr = (&mut request_raw as *mut RequestRaw).cast::<u8>();
+            // SAFETY: The pointer points to a RequestRaw on the stack, whose tag is overwritten
+            unsafe {
+                *request_raw_ptr.add(offset_of!(RequestRaw, data)) = tag;
+                RequestRaw::from_raw(request_raw_ptr)
+            }
+        };
+        let code = RequestType::GetCapabilities as u8;
+
+        // Clients of older and newer minor versions use the same layout
+        assert!(with_version(ABI_VERSION_MAJOR, 0, code).is_ok());
+        assert!(with_version(ABI_VERSION_MAJOR, ABI_VERSION_MINOR + 1, code).is_ok());
+        // Request types added in newer versions are unknown
+        assert_eq!(
+            with_version(
+                ABI_VERSION_MAJOR,
+                ABI_VERSION_MINOR + 1,
+                RequestType::COUNT as u8
+            )
+            .unwrap_err(),
+            ValidationError::InvalidTagValue
+        );
+        // The layout of other major versions is unknown, so the version is checked first
+        for major in [0, ABI_VERSION_MAJOR + 1] {
+            assert_eq!(
+                with_version(major, 0, 0xFF).unwrap_err(),
+                ValidationError::UnsupportedAbiVersion
+            );
+        }
+
+        let mut response_raw: ResponseRaw = Response::Cancel {
+            client_id: ClientId(5),
+            request_id: RequestId(7),
+        }
+        .try_into()
+        .expect("failed to convert response");
+        assert_eq!(response_raw.abi_version, AbiVersion::CURRENT);
+        response_raw.abi_version.major += 1;
+        // SAFETY: The pointer points to a ResponseRaw on the stack
+        let result = unsafe { ResponseRaw::from_raw((&response_raw as *const ResponseRaw).cast()) };
+        assert_eq!(result.unwrap_err(), ValidationError::UnsupportedAbiVersion);
+    }
+
+    #[test]
+    fn test_pre_version_layout() {
+        // Clients without an ABI version start requests with the client ID, the request ID and
+        // the deadline, followed by the tagged data. Responses lack the deadline.
+        let code = RequestType::GetCapabilities as u8;
+        for client_id in [0u32, 1, 2, ABI_MAGIC] {
+            let mut words = [0u64; size_of::<RequestRaw>().div_ceil(size_of::<u64>())];
+            words[0] = u64::from(client_id) | (7u64 << 32);
+            words[1] = u64::MAX;
+            words[2] = u64::from(code);
+            // SAFETY: The buffer is large enough and aligned for a RequestRaw
+            let result = unsafe { RequestRaw::from_raw(words.as_ptr().cast()) };
+            assert_eq!(result.unwrap_err(), ValidationError::UnsupportedAbiVersion);
+
+            let mut words = [0u64; size_of::<ResponseRaw>().div_ceil(size_of::<u64>())];
+            words[0] = u64::from(client_id) | (7u64 << 32);
+            words[1] = u64::from(code);
+            // SAFETY: The buffer is large enough and aligned for a ResponseRaw
+            let result = unsafe { ResponseRaw::from_raw(words.as_ptr().cast()) };
+            assert_eq!(result.unwrap_err(), ValidationError::UnsupportedAbiVersion);
+        }
+    }
+
     #[test]
     fn test_invalid_enum_tag() {
         let client_id = ClientId(5);
@@ -2229,10 +2233,11 @@ mod test {
         let request = GetRandom {
             client_id,
             request_id,
+            deadline: None,
             // SAFETY: Raw data format in shared memory: RequestResponseRawPair || output
             output: unsafe { slice::from_raw_parts_mut(output_start, OUTPUT_SIZE) },
         };
-        let request_raw: RequestRaw = request.into();
+        let request_raw: RequestRaw = request.try_into().expect("failed to convert request");
 
         // Invalidate enum tag of raw request
         // SAFETY: Populating RequestRaw member of RequestResponseRawPair and modifying its tag
@@ -2258,4 +2263,406 @@ mod test {
             },
         }
     }
+
+    #[test]
+    fn test_sign_options() {
+        let message = [0u8; 16];
+        let always_valid = |_data: *const u8, _size: u32| true;
+        for (hash_algorithm, signature_format) in [
+            (None, SignatureFormat::Raw),
+            (Some(HashAlgorithm::Sha3_384), SignatureFormat::Der),
+        ] {
+            let mut signature = [0u8; 72];
+            let request = Request::Sign {
+                client_id: ClientId(5),
+                request_id: RequestId(7),
+                deadline: None,
+                key_id: KeyId(3),
+                message: &message,
+                prehashed: false,
+                hash_algorithm,
+                signature_format,
+                signature: &mut signature,
+            };
+            let mut request_raw: RequestRaw =
+                request.try_into().expect("failed to convert request");
+            let Request::Sign {
+                hash_algorithm: reconstructed_hash_algorithm,
+                signature_format: reconstructed_signature_format,
+                ..
+            } = request_raw
+                .verify(&always_valid)
+                .expect("failed to verify raw request")
+            else {
+                panic!("Unexpected reconstructed request type")
+            };
+            assert_eq!(reconstructed_hash_algorithm, hash_algorithm);
+            assert_eq!(reconstructed_signature_format, signature_format);
+
+            let RequestDataRaw::Sign {
+                signature_format: raw_signature_format,
+                ..
+            } = &mut request_raw.data
+            else {
+                panic!("Unexpected raw request type")
+            };
+            *raw_signature_format = 2;
+            assert!(matches!(
+                request_raw.verify(&always_valid),
+                Err(ValidationError::InvalidValue)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_overlapping_slices() {
+        let mut memory = [0u8; 64];
+        let always_valid = |_data: *const u8, _size: u32| true;
+        let (iv, rest) = memory.split_at_mut(12);
+        let (buffer, tag) = rest.split_at_mut(32);
+        let request = Request::EncryptAesGcm {
+            client_id: ClientId(5),
+            request_id: RequestId(7),
+            deadline: None,
+            key_id: KeyId(3),
+            iv,
+            buffer,
+            aad: &[],
+            tag: &mut tag[..16],
+        };
+        let request_raw: RequestRaw = request.try_into().expect("failed to convert request");
+        assert!(request_raw.verify(&always_valid).is_ok());
+
+        let RequestDataRaw::EncryptAesGcm {
+            iv_data,
+            iv_size,
+            buffer_data,
+            ..
+        } = request_raw.data
+        else {
+            panic!("Unexpected raw request type")
+        };
+        let with_slices = |aad: (*const u8, u32), tag: (*mut u8, u32)| {
+            let mut request_raw = request_raw;
+            if let RequestDataRaw::EncryptAesGcm {
+                aad_data,
+                aad_size,
+                tag_data,
+                tag_size,
+                ..
+            } = &mut request_raw.data
+            {
+                (*aad_data, *aad_size) = aad;
+                (*tag_data, *tag_size) = tag;
+            }
+            request_raw.verify(&always_valid)
+        };
+        let tag = buffer_data.wrapping_add(32);
+        // Immutable slices may share memory
+        assert!(with_slices((iv_data, iv_size), (tag, 16)).is_ok());
+        // Mutable slices must not overlap with any other slice
+        assert!(matches!(
+            with_slices((iv_data, 0), (buffer_data.wrapping_add(16), 16)),
+            Err(ValidationError::OverlappingSlices)
+        ));
+        assert!(matches!(
+            with_slices((iv_data, 0), (iv_data.cast_mut(), 16)),
+            Err(ValidationError::OverlappingSlices)
+        ));
+        assert!(matches!(
+            with_slices((buffer_data, 16), (tag, 16)),
+            Err(ValidationError::OverlappingSlices)
+        ));
+        // Empty slices do not overlap
+        assert!(with_slices((iv_data, 0), (buffer_data, 0)).is_ok());
+    }
+
+    #[test]
+    fn test_request_buffers() {
+        let mut memory = [0u8; 64];
+        let (iv, rest) = memory.split_at_mut(12);
+        let (buffer, tag) = rest.split_at_mut(32);
+        let (iv_data, buffer_data, tag_data) = (iv.as_ptr(), buffer.as_ptr(), tag.as_ptr());
+        let request = Request::EncryptAesGcm {
+            client_id: ClientId(5),
+            request_id: RequestId(7),
+            deadline: None,
+            key_id: KeyId(3),
+            iv,
+            buffer,
+            aad: &[],
+            tag: &mut tag[..16],
+        };
+        let buffers = RequestBuffers::new(&request);
+        assert!(buffers.is_valid(buffer_data, 32, true));
+        assert!(buffers.is_valid(buffer_data.wrapping_add(8), 8, true));
+        assert!(buffers.is_valid(tag_data, 16, true));
+        // Read-only buffers and memory outside of the request are rejected
+        assert!(!buffers.is_valid(iv_data, 12, true));
+        assert!(!buffers.is_valid(tag_data, 17, true));
+        assert!(!buffers.is_valid(buffer_data, 48, true));
+        assert!(!buffers.is_valid(core::ptr::null(), 0, true));
+    }
+
+    /// Memory regions `(start, size, mutable)` of all slices of `request`.
+    fn slices(request: &Request) -> heapless::Vec<(usize, usize, bool), MAX_SLICES> {
+        fn region(slice: &[u8], mutable: bool) -> (usize, usize, bool) {
+            (slice.as_ptr() as usize, slice.len(), mutable)
+        }
+        let regions: &[(usize, usize, bool)] = match request {
+            Request::GenerateSymmetricKey { .. }
+            | Request::GenerateKeyPair { .. }
+            | Request::IsKeyAvailable { .. }
+            | Request::FinishSheBoot { .. }
+            | Request::Cancel { .. }
+            | Request::GetCapabilities { .. }
+            | Request::GetKeyInfo { .. } => &[],
+            Request::GetRandom { output: data, .. }
+            | Request::ExportSymmetricKey { data, .. }
+            | Request::ExportPublicKey {
+                public_key: data, ..
+            }
+            | Request::ExportPrivateKey {
+                private_key: data, ..
+            }
+            | Request::EncryptAesEcb { buffer: data, .. }
+            | Request::DecryptAesEcb { buffer: data, .. } => &[region(data, true)],
+            Request::ImportSymmetricKey { data, .. } => &[region(data, false)],
+            Request::ImportKeyPair {
+                public_key,
+                private_key,
+                ..
+            } => &[region(public_key, false), region(private_key, false)],
+            Request::EncryptChaChaPoly {
+                nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            }
+            | Request::EncryptAesGcm {
+                iv: nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => &[
+                region(nonce, false),
+                region(buffer, true),
+                region(aad, false),
+                region(tag, true),
+            ],
+            Request::EncryptChaChaPolyExternalKey {
+                key,
+                nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            }
+            | Request::EncryptAesGcmExternalKey {
+                key,
+                iv: nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => &[
+                region(key, false),
+                region(nonce, false),
+                region(buffer, true),
+                region(aad, false),
+                region(tag, true),
+            ],
+            Request::DecryptChaChaPoly {
+                nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            }
+            | Request::DecryptAesGcm {
+                iv: nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => &[
+                region(nonce, false),
+                region(buffer, true),
+                region(aad, false),
+                region(tag, false),
+            ],
+            Request::DecryptChaChaPolyExternalKey {
+                key,
+                nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            }
+            | Request::DecryptAesGcmExternalKey {
+                key,
+                iv: nonce,
+                buffer,
+                aad,
+                tag,
+                ..
+            } => &[
+                region(key, false),
+                region(nonce, false),
+                region(buffer, true),
+                region(aad, false),
+                region(tag, false),
+            ],
+            Request::EncryptAesCbc { iv, buffer, .. }
+            | Request::DecryptAesCbc { iv, buffer, .. } => {
+                &[region(iv, false), region(buffer, true)]
+            }
+            Request::EncryptAesCbcExternalKey {
+                key, iv, buffer, ..
+            }
+            | Request::DecryptAesCbcExternalKey {
+                key, iv, buffer, ..
+            } => &[region(key, false), region(iv, false), region(buffer, true)],
+            Request::EncryptAesEcbExternalKey { key, buffer, .. }
+            | Request::DecryptAesEcbExternalKey { key, buffer, .. } => {
+                &[region(key, false), region(buffer, true)]
+            }
+            Request::CalculateAesCmac { message, tag, .. }
+            | Request::CalculateHmac { message, tag, .. }
+            | Request::Sign {
+                message,
+                signature: tag,
+                ..
+            } => &[region(message, false), region(tag, true)],
+            Request::VerifyAesCmac { message, tag, .. }
+            | Request::VerifyHmac { message, tag, .. }
+            | Request::Verify {
+                message,
+                signature: tag,
+                ..
+            } => &[region(message, false), region(tag, false)],
+            Request::CalculateAesCmacExternalKey {
+                key, message, tag, ..
+            }
+            | Request::CalculateHmacExternalKey {
+                key, message, tag, ..
+            }
+            | Request::SignExternalKey {
+                private_key: key,
+                message,
+                signature: tag,
+                ..
+            } => &[
+                region(key, false),
+                region(message, false),
+                region(tag, true),
+            ],
+            Request::VerifyAesCmacExternalKey {
+                key, message, tag, ..
+            }
+            | Request::VerifyHmacExternalKey {
+                key, message, tag, ..
+            }
+            | Request::VerifyExternalKey {
+                public_key: key,
+                message,
+                signature: tag,
+                ..
+            } => &[
+                region(key, false),
+                region(message, false),
+                region(tag, false),
+            ],
+            Request::Ecdh {
+                public_key,
+                shared_secret,
+                ..
+            } => &[region(public_key, false), region(shared_secret, true)],
+            Request::EcdhExternalPrivateKey {
+                public_key,
+                private_key,
+                shared_secret,
+                ..
+            } => &[
+                region(public_key, false),
+                region(private_key, false),
+                region(shared_secret, true),
+            ],
+            Request::LoadSheKey {
+                m1, m2, m3, m4, m5, ..
+            } => &[
+                region(m1, false),
+                region(m2, false),
+                region(m3, false),
+                region(m4, true),
+                region(m5, true),
+            ],
+        };
+        heapless::Vec::from_slice(regions).expect("too many slices")
+    }
+
+    /// Fill raw requests of every type with random pointers into a small arena and random sizes.
+    /// Every verified request must be free of mutable slices that alias other slices.
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_fuzz_no_aliasing_slices() {
+        use rand_chacha::rand_core::{RngCore, SeedableRng};
+        use rand_chacha::ChaCha20Rng;
+
+        const ARENA_SIZE: usize = 64;
+        const ITERATIONS: usize = 2000;
+        let mut arena = [0u8; ARENA_SIZE];
+        let arena_start = arena.as_mut_ptr() as usize;
+        let in_arena = |data: *const u8, size: u32| {
+            let start = data as usize;
+            start >= arena_start && start + size as usize <= arena_start + ARENA_SIZE
+        };
+        let mut rng = ChaCha20Rng::from_seed([0u8; 32]);
+        let tag_word = offset_of!(RequestRaw, data) / size_of::<u64>();
+        let (mut verified, mut overlapping) = (0, 0);
+        for tag in 0..RequestDataRaw::COUNT as u64 {
+            for _ in 0..ITERATIONS {
+                let mut words = [0u64; size_of::<RequestRaw>() / size_of::<u64>()];
+                // SAFETY: The version is at the start of the request and `words` is large enough
+                unsafe { *words.as_mut_ptr().cast() = AbiVersion::CURRENT };
+                words[tag_word] = tag;
+                for word in &mut words[tag_word + 1..] {
+                    *word = if rng.next_u32() % 2 == 0 {
+                        (arena_start + rng.next_u32() as usize % ARENA_SIZE) as u64
+                    } else {
+                        // Small sizes and enum values in both halves of the word
+                        (rng.next_u32() % 24) as u64 | ((rng.next_u32() % 4) as u64) << 32
+                    };
+                }
+                // SAFETY: `words` is large enough for a `RequestRaw` and has a valid tag
+                let request_raw = unsafe { RequestRaw::from_raw(words.as_ptr().cast()) }
+                    .expect("failed to create raw request from pointer.");
+                match request_raw.verify(&in_arena) {
+                    Ok(request) => {
+                        verified += 1;
+                        let regions = slices(&request);
+                        for (i, &(start, size, mutable)) in regions.iter().enumerate() {
+                            for &(other_start, other_size, other_mutable) in &regions[i + 1..] {
+                                assert!(
+                                    !(mutable || other_mutable)
+                                        || size == 0
+                                        || other_size == 0
+                                        || start + size <= other_start
+                                        || other_start + other_size <= start,
+                                    "Aliasing slices in verified request {:?}",
+                                    request
+                                );
+                            }
+                        }
+                    }
+                    Err(ValidationError::OverlappingSlices) => overlapping += 1,
+                    Err(_) => {}
+                }
+            }
+        }
+        assert!(verified > 0);
+        assert!(overlapping > 0);
+    }
 }
@@ -0,0 +1,556 @@
+use crate::client::api::Api;
+use crate::client::she::{SheApi, SheCommand, SheOutput};
+use crate::common::jobs::{Request, Response};
+use crate::hsm::keystore::KeyId;
+use crate::hsm::she::{Error, SheKeyId, NUM_SLOTS};
+use crate::integration::raw_blocking::BlockingTransportRaw;
+use crate::integration::raw_jobs::{
+    check_mut_pointer_and_size, check_pointer_and_size, RequestBuffers, RequestRaw, ResponseRaw,
+    SliceChecker, ValidationError, Validator,
+};
+use core::cell::RefCell;
+use core::ffi::c_void;
+use core::future::Future;
+use core::mem::MaybeUninit;
+use core::pin::{pin, Pin};
+use core::task::{Context, Poll};
+use futures::task::noop_waker_ref;
+use futures::{Sink, Stream};
+use strum::EnumCount;
+
+type SheKeyIdRaw = u32;
+type BoolRaw = u32; // 0 == false, 1 == true
+
+/// Raw SHE command as it is written by legacy SHE applications. This type is supposed to be synced
+/// with non-Rust (e.g. C) clients via cbindgen. All buffers are processed in-place.
+#[repr(C, u8)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, EnumCount)]
+pub enum SheCommandRaw {
+    EncEcb {
+        key_id: SheKeyIdRaw,
+        buffer_data: *mut u8,
+        buffer_size: u32,
+    },
+    DecEcb {
+        key_id: SheKeyIdRaw,
+        buffer_data: *mut u8,
+        buffer_size: u32,
+    },
+    EncCbc {
+        key_id: SheKeyIdRaw,
+        iv_data: *const u8,
+        iv_size: u32,
+        buffer_data: *mut u8,
+        buffer_size: u32,
+    },
+    DecCbc {
+        key_id: SheKeyIdRaw,
+        iv_data: *const u8,
+        iv_size: u32,
+        buffer_data: *mut u8,
+        buffer_size: u32,
+    },
+    GenerateMac {
+        key_id: SheKeyIdRaw,
+        message_data: *const u8,
+        message_size: u32,
+        mac_data: *mut u8,
+        mac_size: u32,
+    },
+    VerifyMac {
+        key_id: SheKeyIdRaw,
+        message_data: *const u8,
+        message_size: u32,
+        mac_data: *const u8,
+        mac_size: u32,
+    },
+    LoadKey {
+        m1_data: *const u8,
+        m1_size: u32,
+        m2_data: *const u8,
+        m2_size: u32,
+        m3_data: *const u8,
+        m3_size: u32,
+        m4_data: *mut u8,
+        m4_size: u32,
+        m5_data: *mut u8,
+        m5_size: u32,
+    },
+    Rnd {
+        output_data: *mut u8,
+        output_size: u32,
+    },
+    BootOk,
+    BootFailure,
+}
+
+/// Raw SHE error code. The discriminants are the `ERC_*` codes of the SHE specification.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SheErrorCodeRaw {
+    NoError = 0x0,
+    SequenceError = 0x1,
+    KeyNotAvailable = 0x2,
+    KeyInvalid = 0x3,
+    KeyEmpty = 0x4,
+    NoSecureBoot = 0x5,
+    KeyWriteProtected = 0x6,
+    KeyUpdateError = 0x7,
+    RngSeed = 0x8,
+    NoDebugging = 0x9,
+    Busy = 0xA,
+    MemoryFailure = 0xB,
+    GeneralError = 0xC,
+}
+
+/// Raw result of a SHE command. Output data is written to the buffers of the command.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SheResponseRaw {
+    pub error_code: SheErrorCodeRaw,
+    /// Verification status of `VerifyMac`. Always false for other commands.
+    pub verified: BoolRaw,
+}
+
+impl SheCommandRaw {
+    /// Create a `SheCommandRaw` from a raw pointer.
+    ///
+    /// # Arguments
+    ///
+    /// * `ptr`: The pointer to the first byte of the `SheCommandRaw`
+    ///
+    /// # Safety
+    ///
+    /// The provided pointer must be valid (non-null, aligned, point to valid memory) and point to a
+    /// valid `SheCommandRaw` instance.
+    ///
+    pub unsafe fn from_raw(ptr: *const u8) -> Result<Self, ValidationError> {
+        // We create a copy of the untrusted command on the stack to prevent a potential
+        // point-of-check/point-of-use issue
+        let mut command: MaybeUninit<SheCommandRaw> = MaybeUninit::uninit();
+
+        // SAFETY: Pointer and size must be checked by integrator
+        unsafe {
+            core::ptr::copy(ptr.cast(), command.as_mut_ptr(), 1);
+        }
+
+        // SAFETY: The tag is the first byte of a `repr(C, u8)` enum
+        let tag: u8 = unsafe { *command.as_ptr().cast::<u8>() };
+
+        // Validate tag value. Invalid tag values cause UB when transmuted into an enum.
+        if tag >= SheCommandRaw::COUNT as u8 {
+            return Err(ValidationError::InvalidTagValue);
+        }
+
+        // SAFETY: Besides the tag, which we checked, all other members are ok with any value
+        Ok(unsafe { command.assume_init() })
+    }
+
+    /// Validate the raw command and convert it to a `SheCommand`.
+    ///
+    /// # Arguments
+    ///
+    /// * `validator`: Integrator-provided check whether a pointer and size pair points to memory
+    ///   the calling application is allowed to access.
+    ///
+    /// # Safety
+    ///
+    /// Buffers must not overlap with mutable buffers of the same command, which is checked here.
+    /// Overlaps with buffers of other commands that are still in flight are not detected and must
+    /// be prevented by the integrator, e.g. by processing one command of an application at a time.
+    pub fn verify<'data>(
+        &self,
+        validator: impl Validator,
+    ) -> Result<SheCommand<'data>, ValidationError> {
+        let validator = SliceChecker::new(&validator);
+        match *self {
+            SheCommandRaw::EncEcb {
+                key_id,
+                buffer_data,
+                buffer_size,
+            } => Ok(SheCommand::EncEcb {
+                key_id: key_id_from_raw(key_id)?,
+                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
+            }),
+            SheCommandRaw::DecEcb {
+                key_id,
+                buffer_data,
+                buffer_size,
+            } => Ok(SheCommand::DecEcb {
+                key_id: key_id_from_raw(key_id)?,
+                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
+            }),
+            SheCommandRaw::EncCbc {
+                key_id,
+                iv_data,
+                iv_size,
+                buffer_data,
+                buffer_size,
+            } => Ok(SheCommand::EncCbc {
+                key_id: key_id_from_raw(key_id)?,
+                iv: check_pointer_and_size(iv_data, iv_size, &validator)?,
+                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
+            }),
+            SheCommandRaw::DecCbc {
+                key_id,
+                iv_data,
+                iv_size,
+                buffer_data,
+                buffer_size,
+            } => Ok(SheCommand::DecCbc {
+                key_id: key_id_from_raw(key_id)?,
+                iv: check_pointer_and_size(iv_data, iv_size, &validator)?,
+                buffer: check_mut_pointer_and_size(buffer_data, buffer_size, &validator)?,
+            }),
+            SheCommandRaw::GenerateMac {
+                key_id,
+                message_data,
+                message_size,
+                mac_data,
+                mac_size,
+            } => Ok(SheCommand::GenerateMac {
+                key_id: key_id_from_raw(key_id)?,
+                message: check_pointer_and_size(message_data, message_size, &validator)?,
+                mac: check_mut_pointer_and_size(mac_data, mac_size, &validator)?,
+            }),
+            SheCommandRaw::VerifyMac {
+                key_id,
+                message_data,
+                message_size,
+                mac_data,
+                mac_size,
+            } => Ok(SheCommand::VerifyMac {
+                key_id: key_id_from_raw(key_id)?,
+                message: check_pointer_and_size(message_data, message_size, &validator)?,
+                mac: check_pointer_and_size(mac_data, mac_size, &validator)?,
+            }),
+            SheCommandRaw::LoadKey {
+                m1_data,
+                m1_size,
+                m2_data,
+                m2_size,
+                m3_data,
+                m3_size,
+                m4_data,
+                m4_size,
+                m5_data,
+                m5_size,
+            } => Ok(SheCommand::LoadKey {
+                m1: check_pointer_and_size(m1_data, m1_size, &validator)?,
+                m2: check_pointer_and_size(m2_data, m2_size, &validator)?,
+                m3: check_pointer_and_size(m3_data, m3_size, &validator)?,
+                m4: check_mut_pointer_and_size(m4_data, m4_size, &validator)?,
+                m5: check_mut_pointer_and_size(m5_data, m5_size, &validator)?,
+            }),
+            SheCommandRaw::Rnd {
+                output_data,
+                output_size,
+            } => Ok(SheCommand::Rnd {
+                output: check_mut_pointer_and_size(output_data, output_size, &validator)?,
+            }),
+            SheCommandRaw::BootOk => Ok(SheCommand::BootOk),
+            SheCommandRaw::BootFailure => Ok(SheCommand::BootFailure),
+        }
+    }
+}
+
+fn key_id_from_raw(key_id: SheKeyIdRaw) -> Result<SheKeyId, ValidationError> {
+    u8::try_from(key_id)
+        .ok()
+        .and_then(|key_id| SheKeyId::try_from(key_id).ok())
+        .ok_or(ValidationError::InvalidValue)
+}
+
+impl From<Error> for SheErrorCodeRaw {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::SequenceError => Self::SequenceError,
+            Error::KeyNotAvailable => Self::KeyNotAvailable,
+            Error::KeyInvalid => Self::KeyInvalid,
+            Error::KeyEmpty => Self::KeyEmpty,
+            Error::NoSecureBoot => Self::NoSecureBoot,
+            Error::KeyWriteProtected => Self::KeyWriteProtected,
+            Error::KeyUpdateError => Self::KeyUpdateError,
+            Error::RngSeed => Self::RngSeed,
+            Error::NoDebugging => Self::NoDebugging,
+            Error::Busy => Self::Busy,
+            Error::MemoryFailure => Self::MemoryFailure,
+            Error::GeneralError => Self::GeneralError,
+        }
+    }
+}
+
+impl From<Result<SheOutput<'_>, Error>> for SheResponseRaw {
+    fn from(value: Result<SheOutput<'_>, Error>) -> Self {
+        match value {
+            Ok(SheOutput::Verified(verified)) => SheResponseRaw {
+                error_code: SheErrorCodeRaw::NoError,
+                verified: verified.into(),
+            },
+            Ok(_) => SheResponseRaw {
+                error_code: SheErrorCodeRaw::NoError,
+                verified: false.into(),
+            },
+            Err(e) => SheResponseRaw {
+                error_code: e.into(),
+                verified: false.into(),
+            },
+        }
+    }
+}
+
+/// Number of SHE key slots, see [NUM_SLOTS].
+pub const SHE_NUM_SLOTS: usize = 15;
+const _: () = assert!(SHE_NUM_SLOTS == NUM_SLOTS);
+
+/// SHE client of a C application.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SheClientRaw {
+    /// Transport to the HSM. Requests of the client must only be sent by [she_execute].
+    pub transport: *const BlockingTransportRaw,
+    /// The key store IDs backing the SHE slots, indexed by the SHE key ID. These have to match the
+    /// IDs used by the `SheWorker`.
+    pub key_ids: [u32; SHE_NUM_SLOTS],
+}
+
+/// Execute `command` on the HSM of `client` and block until it is finished.
+///
+/// Output data is written to the buffers of the command. Invalid arguments and commands, as well
+/// as responses that refer to other memory than the buffers of the command, result in
+/// `SheErrorCodeRaw_GeneralError`.
+///
+/// # Safety
+///
+/// All pointers must be null or valid and the buffers of `command` must be valid for the duration
+/// of the call. The functions of the transport must uphold their contracts documented on
+/// [BlockingTransportRaw].
+#[no_mangle]
+pub unsafe extern "C" fn she_execute(
+    client: *const SheClientRaw,
+    command: *const SheCommandRaw,
+) -> SheResponseRaw {
+    let general_error = SheResponseRaw::from(Err(Error::GeneralError));
+    // SAFETY: The caller guarantees that the pointers are null or valid.
+    let Some(client) = (unsafe { client.as_ref() }) else {
+        return general_error;
+    };
+    // SAFETY: See above
+    let Some(&BlockingTransportRaw {
+        context,
+        send: Some(send),
+        receive: Some(receive),
+        wait: Some(wait),
+    }) = (unsafe { client.transport.as_ref() })
+    else {
+        return general_error;
+    };
+    if command.is_null() {
+        return general_error;
+    }
+    // SAFETY: The command is not null and valid as guaranteed by the caller. Its buffers belong to
+    // the calling application, so only their overlaps are checked.
+    let Ok(command) = (unsafe { SheCommandRaw::from_raw(command.cast()) })
+        .and_then(|command| command.verify(|_, _| true))
+    else {
+        return general_error;
+    };
+
+    let pending = RefCell::new(None);
+    let requests = RawRequests {
+        context,
+        send,
+        outgoing: None,
+        pending: &pending,
+    };
+    let responses = RawResponses {
+        context,
+        receive,
+        pending: &pending,
+    };
+    let mut she_api = SheApi::new(Api::new(requests, responses), client.key_ids.map(KeyId));
+    let mut future = pin!(she_api.execute(command));
+    let mut cx = Context::from_waker(noop_waker_ref());
+    loop {
+        if let Poll::Ready(result) = future.as_mut().poll(&mut cx) {
+            return result.into();
+        }
+        // SAFETY: The caller guarantees that the transport functions are valid.
+        unsafe { wait(context) };
+    }
+}
+
+/// The request that [she_execute] waits for.
+struct PendingRequest {
+    request_id: u32,
+    buffers: RequestBuffers,
+}
+
+/// Request sink of [she_execute] on top of the send function of a [BlockingTransportRaw].
+struct RawRequests<'a> {
+    context: *mut c_void,
+    send: unsafe extern "C" fn(*mut c_void, *const RequestRaw) -> bool,
+    outgoing: Option<RequestRaw>,
+    pending: &'a RefCell<Option<PendingRequest>>,
+}
+
+impl<'data> Sink<Request<'data>> for RawRequests<'_> {
+    type Error = ValidationError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, request: Request<'data>) -> Result<(), Self::Error> {
+        let buffers = RequestBuffers::new(&request);
+        let request = RequestRaw::try_from(request)?;
+        *self.pending.borrow_mut() = Some(PendingRequest {
+            request_id: request.request_id,
+            buffers,
+        });
+        self.outgoing = Some(request);
+        Ok(())
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        if let Some(request) = &self.outgoing {
+            // SAFETY: The caller of `she_execute` guarantees that the transport functions are valid.
+            if !unsafe { (self.send)(self.context, request) } {
+                return Poll::Pending;
+            }
+            self.outgoing = None;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// Response stream of [she_execute] on top of the receive function of a [BlockingTransportRaw].
+///
+/// Responses of other requests are discarded. The stream ends on a response that refers to memory
+/// outside of the buffers of its request.
+struct RawResponses<'a> {
+    context: *mut c_void,
+    receive: unsafe extern "C" fn(*mut c_void, *mut ResponseRaw) -> bool,
+    pending: &'a RefCell<Option<PendingRequest>>,
+}
+
+impl<'data> Stream for RawResponses<'data> {
+    type Item = Response<'data>;
+
+    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let pending = self.pending.borrow();
+        let Some(pending) = pending.as_ref() else {
+            return Poll::Ready(None);
+        };
+        let mut response: MaybeUninit<ResponseRaw> = MaybeUninit::uninit();
+        loop {
+            // SAFETY: The caller of `she_execute` guarantees that the transport functions are
+            // valid. The received response is only read after it was validated.
+            if !unsafe { (self.receive)(self.context, response.as_mut_ptr()) } {
+                return Poll::Pending;
+            }
+            // SAFETY: The transport wrote a response to the pointer above.
+            let Ok(response) = (unsafe { ResponseRaw::from_raw(response.as_ptr().cast()) }) else {
+                return Poll::Ready(None);
+            };
+            if response.request_id == pending.request_id {
+                return Poll::Ready(response.verify(&pending.buffers).ok());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn verify_she_command() {
+        let mut buffer = [0u8; 16];
+        let raw = SheCommandRaw::EncEcb {
+            key_id: SheKeyId::Key1 as u32,
+            buffer_data: buffer.as_mut_ptr(),
+            buffer_size: buffer.len() as u32,
+        };
+        // SAFETY: Pointer refers to a valid `SheCommandRaw` on the stack
+        let raw = unsafe { SheCommandRaw::from_raw((&raw as *const SheCommandRaw).cast()) }
+            .expect("failed to read raw command");
+        let SheCommand::EncEcb { key_id, buffer } = raw.verify(|_, _| true).unwrap() else {
+            panic!("Unexpected command type");
+        };
+        assert_eq!(key_id, SheKeyId::Key1);
+        assert_eq!(buffer.len(), 16);
+
+        let raw = SheCommandRaw::Rnd {
+            output_data: core::ptr::null_mut(),
+            output_size: 16,
+        };
+        assert_eq!(
+            raw.verify(|_, _| true).unwrap_err(),
+            ValidationError::InvalidPointer
+        );
+
+        let raw = SheCommandRaw::DecEcb {
+            key_id: 0xF,
+            buffer_data: buffer.as_mut_ptr(),
+            buffer_size: buffer.len() as u32,
+        };
+        assert_eq!(
+            raw.verify(|_, _| true).unwrap_err(),
+            ValidationError::InvalidValue
+        );
+    }
+
+    #[test]
+    fn invalid_she_command_tag() {
+        let mut raw: MaybeUninit<SheCommandRaw> = MaybeUninit::zeroed();
+        // SAFETY: The tag is the first byte of a `repr(C, u8)` enum
+        unsafe { *raw.as_mut_ptr().cast::<u8>() = SheCommandRaw::COUNT as u8 };
+        // SAFETY: Pointer is aligned and points to initialized memory of the right size
+        let result = unsafe { SheCommandRaw::from_raw(raw.as_ptr().cast()) };
+        assert_eq!(result.unwrap_err(), ValidationError::InvalidTagValue);
+    }
+
+    #[test]
+    fn she_response_from_result() {
+        let response: SheResponseRaw = Ok(SheOutput::Verified(true)).into();
+        assert_eq!(response.error_code, SheErrorCodeRaw::NoError);
+        assert_eq!(response.verified, 1);
+        let response: SheResponseRaw = Err(Error::KeyEmpty).into();
+        assert_eq!(response.error_code as u8, Error::KeyEmpty.code());
+        assert_eq!(response.verified, 0);
+    }
+
+    #[test]
+    fn overlapping_slices() {
+        let mut memory = [0u8; 32];
+        let message_data = memory.as_ptr();
+        let raw = SheCommandRaw::GenerateMac {
+            key_id: SheKeyId::Key1 as u32,
+            message_data,
+            message_size: 16,
+            mac_data: memory.as_mut_ptr().wrapping_add(8),
+            mac_size: 16,
+        };
+        assert_eq!(
+            raw.verify(|_, _| true).unwrap_err(),
+            ValidationError::OverlappingSlices
+        );
+
+        let raw = SheCommandRaw::VerifyMac {
+            key_id: SheKeyId::Key1 as u32,
+            message_data,
+            message_size: 16,
+            mac_data: message_data,
+            mac_size: 16,
+        };
+        assert!(raw.verify(|_, _| true).is_ok());
+    }
+}
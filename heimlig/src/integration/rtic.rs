@@ -0,0 +1,25 @@
+//! Adapter for the [RTIC](https://rtic.rs) framework.
+//!
+//! The core and the workers are driven by RTIC async software tasks. Resources shared between
+//! them (e.g. the key store) are protected by a [Mutex] with an [RticRawMutex]. Because RTIC
+//! tasks of different priorities preempt each other, the raw mutex masks interrupts through the
+//! critical section implementation of the target (e.g. `cortex-m` with the
+//! `critical-section-single-core` feature).
+
+use crate::common::sync;
+
+/// Raw mutex based on a critical section. Suitable for resources shared between RTIC tasks of
+/// any priority.
+pub struct RticRawMutex;
+
+// SAFETY: Closures run inside a critical section, which excludes all other tasks and interrupts.
+unsafe impl sync::RawMutex for RticRawMutex {
+    const INIT: Self = RticRawMutex;
+
+    fn lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        critical_section::with(|_cs| f())
+    }
+}
+
+/// Asynchronous mutex for resources shared between RTIC tasks
+pub type Mutex<T> = sync::Mutex<RticRawMutex, T>;
@@ -0,0 +1,420 @@
+//! Lock-free ring buffer of raw requests and responses in shared memory.
+//!
+//! On multi-core platforms, clients and the HSM core run on different cores and exchange
+//! [RequestRaw]s and [ResponseRaw]s through a region of shared RAM. A ring has exactly one
+//! [RingProducer] and one [RingConsumer], which may live on different cores or in different
+//! processes. Both ends attach to the same memory region, which one side formats with
+//! [init_ring] beforehand.
+//!
+//! Memory layout, starting at a [CACHE_LINE_SIZE] aligned address:
+//!
+//! | Offset                  | Content                                             |
+//! |-------------------------|-----------------------------------------------------|
+//! | 0                       | Magic value, capacity and item size                 |
+//! | [CACHE_LINE_SIZE]       | Head index, only written by the producer            |
+//! | 2 * [CACHE_LINE_SIZE]   | Tail index, only written by the consumer            |
+//! | 3 * [CACHE_LINE_SIZE]   | `capacity` slots, each padded to full cache lines   |
+//!
+//! Head and tail live in cache lines of their own so that the two cores do not invalidate each
+//! other's cache lines when updating their index. The region has to be cache coherent between
+//! the cores or mapped as non-cacheable.
+//!
+//! After pushing or popping items, an end rings its [Doorbell] to notify the other side, e.g. by
+//! raising an inter-core interrupt. The interrupt handler on the other side calls
+//! [Signal::notify] to wake the task waiting on its end.
+//!
+//! The ring only copies items. The buffers of a [RequestRaw] or [ResponseRaw] are referenced by
+//! pointers that are only meaningful in the address space of the client that wrote them. Ends in
+//! different processes usually map the shared memory at different addresses, so the pointers have
+//! to be translated (and validated) by the receiving side before they are used.
+
+use crate::common::sync::WakerRegistration;
+use crate::integration::raw_jobs::{
+    RequestRaw, RequestResponseRawPair, ResponseRaw, ValidationError,
+};
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::{Context, Poll};
+use critical_section::Mutex;
+use displaydoc::Display;
+
+/// Assumed size of a cache line in bytes. Large enough for common Cortex-A, Cortex-M7 and x86 CPUs.
+pub const CACHE_LINE_SIZE: usize = 64;
+
+/// Identifies a formatted ring ("HRNG")
+const MAGIC: u32 = 0x4852_4E47;
+
+const HEAD_OFFSET: usize = CACHE_LINE_SIZE;
+const TAIL_OFFSET: usize = 2 * CACHE_LINE_SIZE;
+const SLOTS_OFFSET: usize = 3 * CACHE_LINE_SIZE;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Display)]
+pub enum Error {
+    /// Memory region is not aligned to a cache line
+    InvalidAlignment,
+    /// Memory region is too small for the ring
+    InvalidSize,
+    /// Ring capacity has to be a power of two
+    InvalidCapacity,
+    /// Memory region does not contain a ring for items of this type
+    NotInitialized,
+    /// Head and tail indices are inconsistent
+    Corrupted,
+    /// Ring contained an invalid item: {0:?}
+    InvalidItem(ValidationError),
+}
+
+/// Item stored in a ring.
+///
+/// Items are read from memory that the other side can write arbitrarily, so they have to be
+/// validated while reading.
+pub trait RingItem: Copy {
+    /// Copy an item out of its slot.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be aligned and point to `size_of::<Self>()` readable bytes.
+    unsafe fn read(ptr: *const u8) -> Result<Self, ValidationError>;
+}
+
+impl RingItem for RequestRaw {
+    unsafe fn read(ptr: *const u8) -> Result<Self, ValidationError> {
+        // SAFETY: Guaranteed by the caller
+        unsafe { RequestRaw::from_raw(ptr) }
+    }
+}
+
+impl RingItem for ResponseRaw {
+    unsafe fn read(ptr: *const u8) -> Result<Self, ValidationError> {
+        // SAFETY: Guaranteed by the caller
+        unsafe { ResponseRaw::from_raw(ptr) }
+    }
+}
+
+impl RingItem for RequestResponseRawPair {
+    unsafe fn read(ptr: *const u8) -> Result<Self, ValidationError> {
+        // SAFETY: Guaranteed by the caller
+        unsafe { RequestResponseRawPair::from_raw(ptr) }
+    }
+}
+
+/// Notification of the other end of a ring.
+pub trait Doorbell {
+    /// Notify the other side that items were pushed or popped. Typically raises an inter-core
+    /// interrupt.
+    fn ring(&self);
+}
+
+/// Both ends live in the same address space (e.g. different threads) and are notified directly.
+impl Doorbell for &Signal {
+    fn ring(&self) {
+        self.notify()
+    }
+}
+
+/// Wakes the task waiting on the local end of a ring.
+pub struct Signal {
+    waker: Mutex<RefCell<WakerRegistration>>,
+}
+
+impl Default for Signal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Signal {
+    pub const fn new() -> Self {
+        Signal {
+            waker: Mutex::new(RefCell::new(WakerRegistration::new())),
+        }
+    }
+
+    /// Wake the waiting task, if any. To be called when the doorbell of the other side rings.
+    pub fn notify(&self) {
+        critical_section::with(|cs| self.waker.borrow_ref_mut(cs).wake());
+    }
+
+    fn register(&self, cx: &Context<'_>) {
+        critical_section::with(|cs| self.waker.borrow_ref_mut(cs).register(cx.waker()));
+    }
+}
+
+/// Distance between two slots in bytes
+const fn slot_size<T>() -> usize {
+    size_of::<T>().div_ceil(CACHE_LINE_SIZE) * CACHE_LINE_SIZE
+}
+
+/// Size of the memory region in bytes that is needed for a ring of `capacity` items of type `T`.
+///
+/// # Panics
+///
+/// Panics if the size does not fit into `usize`. See [checked_ring_size] for capacities that are
+/// not known at compile time.
+pub const fn ring_size<T>(capacity: usize) -> usize {
+    match checked_ring_size::<T>(capacity) {
+        Some(size) => size,
+        None => panic!("ring size overflows usize"),
+    }
+}
+
+/// Like [ring_size], but returns `None` if the size does not fit into `usize`.
+pub const fn checked_ring_size<T>(capacity: usize) -> Option<usize> {
+    match capacity.checked_mul(slot_size::<T>()) {
+        Some(slots_size) => slots_size.checked_add(SLOTS_OFFSET),
+        None => None,
+    }
+}
+
+/// Format a memory region as empty ring of `capacity` items of type `T`. Has to be called by one
+/// side before the ends attach to the ring.
+///
+/// # Safety
+///
+/// `memory` must point to `size` writable bytes that are not accessed by anybody else during the
+/// call.
+pub unsafe fn init_ring<T: RingItem>(
+    memory: *mut u8,
+    size: usize,
+    capacity: usize,
+) -> Result<(), Error> {
+    check_region::<T>(memory, size, capacity)?;
+    // SAFETY: The region was checked to be aligned and large enough for the header and indices.
+    unsafe {
+        let header = memory.cast::<u32>();
+        header.add(1).write_volatile(capacity as u32);
+        header.add(2).write_volatile(size_of::<T>() as u32);
+        (*memory.add(HEAD_OFFSET).cast::<AtomicU32>()).store(0, Ordering::Relaxed);
+        (*memory.add(TAIL_OFFSET).cast::<AtomicU32>()).store(0, Ordering::Relaxed);
+        // Publish the magic value last so that the other side never sees a partial header
+        (*memory.cast::<AtomicU32>()).store(MAGIC, Ordering::Release);
+    }
+    Ok(())
+}
+
+fn check_region<T>(memory: *const u8, size: usize, capacity: usize) -> Result<(), Error> {
+    if memory.is_null() || memory as usize % CACHE_LINE_SIZE != 0 {
+        return Err(Error::InvalidAlignment);
+    }
+    if align_of::<T>() > CACHE_LINE_SIZE {
+        return Err(Error::InvalidAlignment);
+    }
+    if !capacity.is_power_of_two() || capacity > u32::MAX as usize / 2 {
+        return Err(Error::InvalidCapacity);
+    }
+    // The capacity may come from a header written by the other side
+    let required_size = checked_ring_size::<T>(capacity).ok_or(Error::InvalidCapacity)?;
+    if size < required_size {
+        return Err(Error::InvalidSize);
+    }
+    Ok(())
+}
+
+/// Ring in shared memory as seen by one of its ends
+struct Ring<'a, T> {
+    memory: *mut u8,
+    capacity: u32,
+    _items: PhantomData<&'a T>,
+}
+
+impl<T: RingItem> Ring<'_, T> {
+    /// # Safety
+    ///
+    /// `memory` must point to `size` bytes that stay valid for the lifetime of the ring and are
+    /// only accessed through the ring ends.
+    unsafe fn attach(memory: *mut u8, size: usize) -> Result<Self, Error> {
+        if memory.is_null() || memory as usize % CACHE_LINE_SIZE != 0 {
+            return Err(Error::InvalidAlignment);
+        }
+        if size < SLOTS_OFFSET {
+            return Err(Error::InvalidSize);
+        }
+        // SAFETY: The region is aligned and large enough for the header.
+        let (magic, capacity, item_size) = unsafe {
+            let header = memory.cast::<u32>();
+            (
+                (*memory.cast::<AtomicU32>()).load(Ordering::Acquire),
+                header.add(1).read_volatile(),
+                header.add(2).read_volatile(),
+            )
+        };
+        if magic != MAGIC || item_size as usize != size_of::<T>() {
+            return Err(Error::NotInitialized);
+        }
+        check_region::<T>(memory, size, capacity as usize)?;
+        Ok(Ring {
+            memory,
+            capacity,
+            _items: PhantomData,
+        })
+    }
+
+    fn index(&self, offset: usize) -> &AtomicU32 {
+        // SAFETY: The region was checked in `attach` to contain aligned indices.
+        unsafe { &*self.memory.add(offset).cast::<AtomicU32>() }
+    }
+
+    fn head(&self) -> &AtomicU32 {
+        self.index(HEAD_OFFSET)
+    }
+
+    fn tail(&self) -> &AtomicU32 {
+        self.index(TAIL_OFFSET)
+    }
+
+    fn slot(&self, position: u32) -> *mut u8 {
+        let slot = (position & (self.capacity - 1)) as usize;
+        // SAFETY: The slot index is masked to the capacity, which was checked to fit the region.
+        unsafe { self.memory.add(SLOTS_OFFSET + slot * slot_size::<T>()) }
+    }
+
+    /// Number of occupied slots. Fails if the indices were corrupted by the other side.
+    fn len(&self, head: u32, tail: u32) -> Result<u32, Error> {
+        let len = head.wrapping_sub(tail);
+        if len > self.capacity {
+            return Err(Error::Corrupted);
+        }
+        Ok(len)
+    }
+}
+
+/// Sending end of a ring.
+pub struct RingProducer<'a, T, D: Doorbell> {
+    ring: Ring<'a, T>,
+    doorbell: D,
+    signal: &'a Signal,
+}
+
+impl<'a, T: RingItem, D: Doorbell> RingProducer<'a, T, D> {
+    /// Attach to the ring formatted by [init_ring] in the given memory region.
+    ///
+    /// * `doorbell`: Notifies the consumer about pushed items.
+    /// * `signal`: Notified by the consumer after it popped items.
+    ///
+    /// # Safety
+    ///
+    /// `memory` must point to `size` bytes that stay valid for `'a`. There must be no other
+    /// producer for the ring.
+    pub unsafe fn attach(
+        memory: *mut u8,
+        size: usize,
+        doorbell: D,
+        signal: &'a Signal,
+    ) -> Result<Self, Error> {
+        Ok(RingProducer {
+            // SAFETY: Guaranteed by the caller
+            ring: unsafe { Ring::attach(memory, size)? },
+            doorbell,
+            signal,
+        })
+    }
+
+    /// Push an item without waiting. Returns the item if the ring is full.
+    pub fn try_push(&mut self, item: T) -> Result<Result<(), T>, Error> {
+        let head = self.ring.head().load(Ordering::Relaxed);
+        let tail = self.ring.tail().load(Ordering::Acquire);
+        if self.ring.len(head, tail)? == self.ring.capacity {
+            return Ok(Err(item));
+        }
+        // SAFETY: The slot is free and only the single producer writes to free slots.
+        unsafe { self.ring.slot(head).cast::<T>().write_volatile(item) };
+        self.ring
+            .head()
+            .store(head.wrapping_add(1), Ordering::Release);
+        self.doorbell.ring();
+        Ok(Ok(()))
+    }
+
+    /// Push an item, waiting for a free slot if the ring is full.
+    pub async fn push(&mut self, item: T) -> Result<(), Error> {
+        let mut item = Some(item);
+        poll_fn(|cx| {
+            let Some(pending) = item.take() else {
+                return Poll::Ready(Ok(()));
+            };
+            // Register before trying so that a pop between both steps is not missed
+            self.signal.register(cx);
+            match self.try_push(pending)? {
+                Ok(()) => Poll::Ready(Ok(())),
+                Err(pending) => {
+                    item = Some(pending);
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+}
+
+/// Receiving end of a ring.
+pub struct RingConsumer<'a, T, D: Doorbell> {
+    ring: Ring<'a, T>,
+    doorbell: D,
+    signal: &'a Signal,
+}
+
+impl<'a, T: RingItem, D: Doorbell> RingConsumer<'a, T, D> {
+    /// Attach to the ring formatted by [init_ring] in the given memory region.
+    ///
+    /// * `doorbell`: Notifies the producer about popped items.
+    /// * `signal`: Notified by the producer after it pushed items.
+    ///
+    /// # Safety
+    ///
+    /// `memory` must point to `size` bytes that stay valid for `'a`. There must be no other
+    /// consumer for the ring.
+    pub unsafe fn attach(
+        memory: *mut u8,
+        size: usize,
+        doorbell: D,
+        signal: &'a Signal,
+    ) -> Result<Self, Error> {
+        Ok(RingConsumer {
+            // SAFETY: Guaranteed by the caller
+            ring: unsafe { Ring::attach(memory, size)? },
+            doorbell,
+            signal,
+        })
+    }
+
+    /// Pop an item without waiting. Returns `None` if the ring is empty. Invalid items are
+    /// removed from the ring and reported as [Error::InvalidItem].
+    pub fn try_pop(&mut self) -> Result<Option<T>, Error> {
+        let tail = self.ring.tail().load(Ordering::Relaxed);
+        let head = self.ring.head().load(Ordering::Acquire);
+        if self.ring.len(head, tail)? == 0 {
+            return Ok(None);
+        }
+        // SAFETY: The slot is occupied and the producer does not write to it until it is freed.
+        let item = unsafe { T::read(self.ring.slot(tail)) };
+        self.ring
+            .tail()
+            .store(tail.wrapping_add(1), Ordering::Release);
+        self.doorbell.ring();
+        item.map(Some).map_err(Error::InvalidItem)
+    }
+
+    /// Pop an item, waiting for one if the ring is empty.
+    pub async fn pop(&mut self) -> Result<T, Error> {
+        poll_fn(|cx| {
+            // Register before trying so that a push between both steps is not missed
+            self.signal.register(cx);
+            match self.try_pop() {
+                Ok(None) => Poll::Pending,
+                Ok(Some(item)) => Poll::Ready(Ok(item)),
+                Err(e) => Poll::Ready(Err(e)),
+            }
+        })
+        .await
+    }
+}
+
+// SAFETY: The ends only hold a pointer to the shared region, which is meant to be accessed from
+// different cores. Items are copied in and out of the region. Pointers inside of raw items are
+// only dereferenced after the receiving side validated them.
+unsafe impl<T, D: Doorbell + Send> Send for RingProducer<'_, T, D> {}
+// SAFETY: See above
+unsafe impl<T, D: Doorbell + Send> Send for RingConsumer<'_, T, D> {}
@@ -0,0 +1,47 @@
+//! Adapter for hosted platforms with the Rust standard library.
+//!
+//! The core and the workers can be driven by any `std` executor (e.g. `async-std` or `tokio`),
+//! also from different threads.
+
+use crate::common::sync;
+
+/// Raw mutex based on [std::sync::Mutex].
+pub struct StdRawMutex(std::sync::Mutex<()>);
+
+// SAFETY: Closures run while the std mutex is locked.
+unsafe impl sync::RawMutex for StdRawMutex {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = StdRawMutex(std::sync::Mutex::new(()));
+
+    fn lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        // The protected closures cannot leave the data in an inconsistent state when panicking
+        let _guard = self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        f()
+    }
+}
+
+/// Asynchronous mutex for resources shared between threads
+pub type Mutex<T> = sync::Mutex<StdRawMutex, T>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lock_from_threads() {
+        let mutex = Mutex::new(0u32);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        *async_std::task::block_on(mutex.lock()) += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(mutex.into_inner(), 400);
+    }
+}
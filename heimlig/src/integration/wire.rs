@@ -0,0 +1,1794 @@
+//! Serialization of requests and responses for transports that cannot share memory between
+//! client and core, like UART, SPI, sockets or mailboxes.
+//!
+//! Unlike [crate::integration::raw_jobs], the encoded messages contain the data of all buffers and
+//! no pointers. Encoding and decoding work on caller-provided buffers and do not allocate. Every
+//! message is a frame made of a fixed header followed by a payload:
+//!
+//! | Offset | Size | Field                                          |
+//! |--------|------|------------------------------------------------|
+//! | 0      | 2    | Magic bytes `"HL"`                             |
+//! | 2      | 1    | Format version ([VERSION])                     |
+//! | 3      | 1    | Frame kind (`1`: request, `2`: response)       |
+//! | 4      | 4    | Payload size                                   |
+//!
+//! All integers are encoded little endian. A request payload starts with the request type, client
+//! ID, request ID, deadline (`u64::MAX` if there is none) and the total size of the referenced
+//! buffers as returned by [Request::data_size]. The fields of the request follow in declaration
+//! order. Input buffers are encoded as a `u32` length followed by their contents, output buffers
+//! only by their length. A response payload starts with the response type, client ID and request
+//! ID, followed by its fields. Buffers of responses always carry their contents.
+//!
+//! Decoded requests borrow their buffers from an arena supplied by the caller, decoded responses
+//! borrow them from the payload itself.
+
+use crate::common::jobs::{
+    self, CancelReason, Capabilities, ClientId, Curves, HashAlgorithm, HashAlgorithms, KeyKinds,
+    Limit, Padding, PrivateKeyFormat, PublicKeyFormat, Request, RequestId, RequestType,
+    RequestTypes, Response, SignatureFormat, SymmetricPrimitives,
+};
+use crate::crypto;
+use crate::hsm::keystore::{
+    self, Curve, KeyId, KeyInfo, KeyPairAlgorithm, KeyPermissions, KeyType,
+};
+use crate::hsm::she;
+use crate::integration::raw_jobs::AbiVersion;
+use displaydoc::Display;
+
+/// Magic bytes at the start of every frame
+pub const MAGIC: [u8; 2] = *b"HL";
+/// Version of the format implemented by this module
+pub const VERSION: u8 = 1;
+/// Size of the frame header in bytes
+pub const HEADER_SIZE: usize = 8;
+/// Upper bound for payload sizes accepted by the decoder
+pub const MAX_PAYLOAD_SIZE: usize = 1024 * 1024;
+
+/// Deadline value encoding the absence of a deadline
+const NO_DEADLINE: u64 = u64::MAX;
+/// Hash algorithm value encoding the absence of a hash algorithm
+const NO_HASH_ALGORITHM: u8 = 0xFF;
+/// Size limit value encoding the absence of a limit
+const NO_SIZE_LIMIT: u32 = u32::MAX;
+/// Key pair algorithm code of [KeyPairAlgorithm::Other], followed by the `u32` of the algorithm
+const OTHER_KEY_PAIR_ALGORITHM: u8 = 0xFF;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Display)]
+pub enum Error {
+    /// The frame does not start with the magic bytes.
+    InvalidMagic,
+    /// Version {0} of the format is not supported.
+    UnsupportedVersion(u8),
+    /// The frame kind is unknown or unexpected.
+    InvalidKind,
+    /// The frame exceeds the maximum payload size.
+    TooLarge,
+    /// The payload ended before all fields were decoded.
+    Truncated,
+    /// The payload contains bytes after the last field.
+    TrailingData,
+    /// A field contains a value that is not defined by the format.
+    InvalidValue,
+    /// The destination buffer is too small.
+    BufferTooSmall,
+    /// The announced data size does not match the sizes of the buffers.
+    DataSizeMismatch,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FrameKind {
+    Request = 1,
+    Response = 2,
+}
+
+/// Header preceding every payload
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Header {
+    pub kind: FrameKind,
+    pub payload_size: usize,
+}
+
+impl Header {
+    pub fn encode(&self) -> Result<[u8; HEADER_SIZE], Error> {
+        if self.payload_size > MAX_PAYLOAD_SIZE {
+            return Err(Error::TooLarge);
+        }
+        let mut header = [0u8; HEADER_SIZE];
+        header[0..2].copy_from_slice(&MAGIC);
+        header[2] = VERSION;
+        header[3] = self.kind as u8;
+        header[4..8].copy_from_slice(&(self.payload_size as u32).to_le_bytes());
+        Ok(header)
+    }
+
+    pub fn decode(header: &[u8; HEADER_SIZE]) -> Result<Self, Error> {
+        if header[0..2] != MAGIC {
+            return Err(Error::InvalidMagic);
+        }
+        if header[2] != VERSION {
+            return Err(Error::UnsupportedVersion(header[2]));
+        }
+        let kind = match header[3] {
+            1 => FrameKind::Request,
+            2 => FrameKind::Response,
+            _ => return Err(Error::InvalidKind),
+        };
+        let payload_size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let payload_size = usize::try_from(payload_size).map_err(|_| Error::TooLarge)?;
+        if payload_size > MAX_PAYLOAD_SIZE {
+            return Err(Error::TooLarge);
+        }
+        Ok(Header { kind, payload_size })
+    }
+}
+
+/// Defines the conversion of an enum from and to its `u8` code in both directions.
+macro_rules! codes {
+    ($ty:ty, $encode:ident, $decode:ident, { $($variant:path = $code:literal),* $(,)? }) => {
+        fn $encode(value: $ty) -> u8 {
+            match value {
+                $($variant => $code,)*
+            }
+        }
+
+        fn $decode(code: u8) -> Result<$ty, Error> {
+            match code {
+                $($code => Ok($variant),)*
+                _ => Err(Error::InvalidValue),
+            }
+        }
+    };
+}
+
+codes!(RequestType, request_type_code, request_type, {
+    RequestType::GetRandom = 0,
+    RequestType::GenerateSymmetricKey = 1,
+    RequestType::GenerateKeyPair = 2,
+    RequestType::ImportSymmetricKey = 3,
+    RequestType::ImportKeyPair = 4,
+    RequestType::ExportSymmetricKey = 5,
+    RequestType::ExportPublicKey = 6,
+    RequestType::ExportPrivateKey = 7,
+    RequestType::IsKeyAvailable = 8,
+    RequestType::EncryptChaChaPoly = 9,
+    RequestType::EncryptChaChaPolyExternalKey = 10,
+    RequestType::DecryptChaChaPoly = 11,
+    RequestType::DecryptChaChaPolyExternalKey = 12,
+    RequestType::EncryptAesGcm = 13,
+    RequestType::EncryptAesGcmExternalKey = 14,
+    RequestType::DecryptAesGcm = 15,
+    RequestType::DecryptAesGcmExternalKey = 16,
+    RequestType::EncryptAesCbc = 17,
+    RequestType::EncryptAesCbcExternalKey = 18,
+    RequestType::DecryptAesCbc = 19,
+    RequestType::DecryptAesCbcExternalKey = 20,
+    RequestType::EncryptAesEcb = 21,
+    RequestType::EncryptAesEcbExternalKey = 22,
+    RequestType::DecryptAesEcb = 23,
+    RequestType::DecryptAesEcbExternalKey = 24,
+    RequestType::CalculateAesCmac = 25,
+    RequestType::CalculateAesCmacExternalKey = 26,
+    RequestType::VerifyAesCmac = 27,
+    RequestType::VerifyAesCmacExternalKey = 28,
+    RequestType::CalculateHmac = 29,
+    RequestType::CalculateHmacExternalKey = 30,
+    RequestType::VerifyHmac = 31,
+    RequestType::VerifyHmacExternalKey = 32,
+    RequestType::Sign = 33,
+    RequestType::SignExternalKey = 34,
+    RequestType::Verify = 35,
+    RequestType::VerifyExternalKey = 36,
+    RequestType::Ecdh = 37,
+    RequestType::EcdhExternalPrivateKey = 38,
+    RequestType::LoadSheKey = 39,
+    RequestType::FinishSheBoot = 40,
+    RequestType::Cancel = 41,
+    RequestType::GetCapabilities = 42,
+    RequestType::GetKeyInfo = 43,
+});
+
+codes!(HashAlgorithm, hash_algorithm_code, hash_algorithm, {
+    HashAlgorithm::Sha2_256 = 0,
+    HashAlgorithm::Sha2_384 = 1,
+    HashAlgorithm::Sha2_512 = 2,
+    HashAlgorithm::Sha3_256 = 3,
+    HashAlgorithm::Sha3_384 = 4,
+    HashAlgorithm::Sha3_512 = 5,
+});
+
+codes!(Padding, padding_code, padding, {
+    Padding::Pkcs7 = 0,
+    Padding::NoPadding = 1,
+});
+
+codes!(PrivateKeyFormat, private_key_format_code, private_key_format, {
+    PrivateKeyFormat::Raw = 0,
+    PrivateKeyFormat::Pkcs8Der = 1,
+    PrivateKeyFormat::Sec1Der = 2,
+    PrivateKeyFormat::Pem = 3,
+});
+
+codes!(PublicKeyFormat, public_key_format_code, public_key_format, {
+    PublicKeyFormat::Raw = 0,
+    PublicKeyFormat::Sec1Uncompressed = 1,
+    PublicKeyFormat::Sec1Compressed = 2,
+    PublicKeyFormat::SpkiDer = 3,
+    PublicKeyFormat::SpkiPem = 4,
+});
+
+codes!(SignatureFormat, signature_format_code, signature_format, {
+    SignatureFormat::Raw = 0,
+    SignatureFormat::Der = 1,
+});
+
+codes!(Curve, curve_code, curve, {
+    Curve::NistP256 = 0,
+    Curve::NistP384 = 1,
+});
+
+codes!(CancelReason, cancel_reason_code, cancel_reason, {
+    CancelReason::Cancelled = 0,
+    CancelReason::Expired = 1,
+});
+
+codes!(crypto::Error, crypto_error_code, crypto_error, {
+    crypto::Error::Encrypt = 0,
+    crypto::Error::Decrypt = 1,
+    crypto::Error::Sign = 2,
+    crypto::Error::Verify = 3,
+    crypto::Error::InvalidSymmetricKeySize = 4,
+    crypto::Error::InvalidIvSize = 5,
+    crypto::Error::InvalidTagSize = 6,
+    crypto::Error::InvalidBufferSize = 7,
+    crypto::Error::InvalidPadding = 8,
+    crypto::Error::InvalidPrivateKey = 9,
+    crypto::Error::InvalidPublicKey = 10,
+    crypto::Error::InvalidSignatureSize = 11,
+    crypto::Error::InvalidSignature = 12,
+    crypto::Error::InvalidDigestSize = 13,
+});
+
+codes!(keystore::Error, key_store_error_code, key_store_error, {
+    keystore::Error::NotAllowed = 0,
+    keystore::Error::KeyNotFound = 1,
+    keystore::Error::KeyAlreadyExists = 2,
+    keystore::Error::KeyStoreTooSmall = 3,
+    keystore::Error::DuplicateIds = 4,
+    keystore::Error::InvalidKeyId = 5,
+    keystore::Error::InvalidKeyType = 6,
+    keystore::Error::InvalidBufferSize = 7,
+});
+
+// SHE errors keep their codes from the specification
+codes!(she::Error, she_error_code, she_error, {
+    she::Error::SequenceError = 0x1,
+    she::Error::KeyNotAvailable = 0x2,
+    she::Error::KeyInvalid = 0x3,
+    she::Error::KeyEmpty = 0x4,
+    she::Error::NoSecureBoot = 0x5,
+    she::Error::KeyWriteProtected = 0x6,
+    she::Error::KeyUpdateError = 0x7,
+    she::Error::RngSeed = 0x8,
+    she::Error::NoDebugging = 0x9,
+    she::Error::Busy = 0xA,
+    she::Error::MemoryFailure = 0xB,
+    she::Error::GeneralError = 0xC,
+});
+
+codes!(Limit, limit_code, limit, {
+    Limit::InFlight = 0,
+    Limit::Rate = 1,
+    Limit::RequestSize = 2,
+});
+
+/// Key pair algorithms defined by Heimlig. [KeyPairAlgorithm::Other] is encoded separately.
+const KEY_PAIR_ALGORITHMS: [KeyPairAlgorithm; 2] =
+    [KeyPairAlgorithm::Ed25519, KeyPairAlgorithm::X25519];
+
+/// Encodes a permission set as a bit mask (import, export private, overwrite, delete from LSB).
+fn permission_bits(permissions: KeyPermissions) -> u8 {
+    u8::from(permissions.import)
+        | u8::from(permissions.export_private) << 1
+        | u8::from(permissions.overwrite) << 2
+        | u8::from(permissions.delete) << 3
+}
+
+fn permissions(bits: u8) -> Result<KeyPermissions, Error> {
+    if bits & !0xF != 0 {
+        return Err(Error::InvalidValue);
+    }
+    Ok(KeyPermissions {
+        import: bits & 1 != 0,
+        export_private: bits & 1 << 1 != 0,
+        overwrite: bits & 1 << 2 != 0,
+        delete: bits & 1 << 3 != 0,
+    })
+}
+
+/// Encodes an error as its kind followed by the detail code of nested errors.
+fn error_code(error: jobs::Error) -> [u8; 2] {
+    match error {
+        jobs::Error::NoWorkerForRequest => [0, 0],
+        jobs::Error::UnexpectedRequestType => [1, 0],
+        jobs::Error::RequestTooLarge => [2, 0],
+        jobs::Error::NoKeyStore => [3, 0],
+        jobs::Error::Send => [4, 0],
+        jobs::Error::StreamTerminated => [5, 0],
+        jobs::Error::Crypto(e) => [6, crypto_error_code(e)],
+        jobs::Error::KeyStore(e) => [7, key_store_error_code(e)],
+        jobs::Error::She(e) => [8, she_error_code(e)],
+        jobs::Error::LimitExceeded(limit) => [9, limit_code(limit)],
+        jobs::Error::WorkerUnavailable => [10, 0],
+        jobs::Error::AccessDenied => [11, 0],
+        jobs::Error::CancellationUnsupported => [12, 0],
+        jobs::Error::NotCancellable => [13, 0],
+    }
+}
+
+fn error([kind, detail]: [u8; 2]) -> Result<jobs::Error, Error> {
+    let no_detail = |error| {
+        if detail == 0 {
+            Ok(error)
+        } else {
+            Err(Error::InvalidValue)
+        }
+    };
+    match kind {
+        0 => no_detail(jobs::Error::NoWorkerForRequest),
+        1 => no_detail(jobs::Error::UnexpectedRequestType),
+        2 => no_detail(jobs::Error::RequestTooLarge),
+        3 => no_detail(jobs::Error::NoKeyStore),
+        4 => no_detail(jobs::Error::Send),
+        5 => no_detail(jobs::Error::StreamTerminated),
+        6 => Ok(jobs::Error::Crypto(crypto_error(detail)?)),
+        7 => Ok(jobs::Error::KeyStore(key_store_error(detail)?)),
+        8 => Ok(jobs::Error::She(she_error(detail)?)),
+        9 => Ok(jobs::Error::LimitExceeded(limit(detail)?)),
+        10 => no_detail(jobs::Error::WorkerUnavailable),
+        11 => no_detail(jobs::Error::AccessDenied),
+        12 => no_detail(jobs::Error::CancellationUnsupported),
+        13 => no_detail(jobs::Error::NotCancellable),
+        _ => Err(Error::InvalidValue),
+    }
+}
+
+/// Serializes fields into a buffer. Without a buffer, only the encoded size is determined.
+struct Writer<'buf> {
+    buf: Option<&'buf mut [u8]>,
+    pos: usize,
+}
+
+impl Writer<'_> {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let end = self.pos.checked_add(bytes.len()).ok_or(Error::TooLarge)?;
+        if let Some(buf) = self.buf.as_deref_mut() {
+            buf.get_mut(self.pos..end)
+                .ok_or(Error::BufferTooSmall)?
+                .copy_from_slice(bytes);
+        }
+        self.pos = end;
+        Ok(())
+    }
+
+    fn u8(&mut self, value: u8) -> Result<(), Error> {
+        self.put(&[value])
+    }
+
+    fn u32(&mut self, value: u32) -> Result<(), Error> {
+        self.put(&value.to_le_bytes())
+    }
+
+    fn u64(&mut self, value: u64) -> Result<(), Error> {
+        self.put(&value.to_le_bytes())
+    }
+
+    fn bool(&mut self, value: bool) -> Result<(), Error> {
+        self.u8(value.into())
+    }
+
+    fn size(&mut self, value: usize) -> Result<(), Error> {
+        self.u32(u32::try_from(value).map_err(|_| Error::TooLarge)?)
+    }
+
+    fn key_id(&mut self, key_id: KeyId) -> Result<(), Error> {
+        self.u32(key_id.0)
+    }
+
+    fn hash_algorithm(&mut self, hash_algorithm: Option<HashAlgorithm>) -> Result<(), Error> {
+        self.u8(hash_algorithm.map_or(NO_HASH_ALGORITHM, hash_algorithm_code))
+    }
+
+    /// The sets of capabilities are encoded as bit masks like in the raw ABI
+    fn capabilities(&mut self, capabilities: &Capabilities) -> Result<(), Error> {
+        self.put(&capabilities.abi_version.major.to_le_bytes())?;
+        self.put(&capabilities.abi_version.minor.to_le_bytes())?;
+        self.u64(capabilities.request_types.bits())?;
+        self.u64(capabilities.hash_algorithms.bits())?;
+        self.u64(capabilities.curves.bits())?;
+        self.u64(capabilities.symmetric_primitives.bits())?;
+        self.u64(capabilities.key_types.bits())?;
+        self.bool(capabilities.key_store)?;
+        self.size(capabilities.max_symmetric_key_size)?;
+        match capabilities.max_request_size {
+            None => self.u32(NO_SIZE_LIMIT),
+            Some(size) => self.size(size.min(NO_SIZE_LIMIT as usize - 1)),
+        }
+    }
+
+    /// A key info is encoded as the key ID, a key type code (`0`: symmetric, `1`: asymmetric, `2`:
+    /// key pair) followed by the parameters of the type and the permissions as a bit mask.
+    fn key_info(&mut self, key_info: &KeyInfo) -> Result<(), Error> {
+        self.key_id(key_info.id)?;
+        match key_info.ty {
+            KeyType::Symmetric(size) => {
+                self.u8(0)?;
+                self.size(size)?;
+            }
+            KeyType::Asymmetric(curve) => {
+                self.u8(1)?;
+                self.u8(curve_code(curve))?;
+            }
+            KeyType::KeyPair {
+                algorithm,
+                public_key_size,
+                private_key_size,
+            } => {
+                self.u8(2)?;
+                match algorithm {
+                    KeyPairAlgorithm::Other(algorithm) => {
+                        self.u8(OTHER_KEY_PAIR_ALGORITHM)?;
+                        self.u32(algorithm)?;
+                    }
+                    algorithm => {
+                        let code = KEY_PAIR_ALGORITHMS
+                            .iter()
+                            .position(|known| *known == algorithm)
+                            .ok_or(Error::InvalidValue)?;
+                        self.u8(code as u8)?;
+                    }
+                }
+                self.size(public_key_size)?;
+                self.size(private_key_size)?;
+            }
+        }
+        self.u8(permission_bits(key_info.permissions))
+    }
+
+    /// Buffer whose contents are transferred
+    fn input(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.size(data.len())?;
+        self.put(data)
+    }
+
+    /// Buffer whose contents are produced by the receiver
+    fn output(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.size(data.len())
+    }
+}
+
+/// Decoding of fixed-size fields
+trait Fields {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], Error>;
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    fn size(&mut self) -> Result<usize, Error> {
+        usize::try_from(self.u32()?).map_err(|_| Error::TooLarge)
+    }
+
+    fn key_id(&mut self) -> Result<KeyId, Error> {
+        Ok(KeyId(self.u32()?))
+    }
+
+    fn hash_algorithm(&mut self) -> Result<Option<HashAlgorithm>, Error> {
+        match self.u8()? {
+            NO_HASH_ALGORITHM => Ok(None),
+            code => Ok(Some(hash_algorithm(code)?)),
+        }
+    }
+
+    fn key_info(&mut self) -> Result<KeyInfo, Error> {
+        let id = self.key_id()?;
+        let ty = match self.u8()? {
+            0 => KeyType::Symmetric(self.size()?),
+            1 => KeyType::Asymmetric(curve(self.u8()?)?),
+            2 => {
+                let algorithm = match self.u8()? {
+                    OTHER_KEY_PAIR_ALGORITHM => KeyPairAlgorithm::Other(self.u32()?),
+                    code => *KEY_PAIR_ALGORITHMS
+                        .get(usize::from(code))
+                        .ok_or(Error::InvalidValue)?,
+                };
+                KeyType::KeyPair {
+                    algorithm,
+                    public_key_size: self.size()?,
+                    private_key_size: self.size()?,
+                }
+            }
+            _ => return Err(Error::InvalidValue),
+        };
+        Ok(KeyInfo {
+            id,
+            ty,
+            permissions: permissions(self.u8()?)?,
+        })
+    }
+
+    fn capabilities(&mut self) -> Result<Capabilities, Error> {
+        Ok(Capabilities {
+            abi_version: AbiVersion::new(
+                u16::from_le_bytes(self.bytes()?),
+                u16::from_le_bytes(self.bytes()?),
+            ),
+            request_types: RequestTypes::from_bits(self.u64()?),
+            hash_algorithms: HashAlgorithms::from_bits(self.u64()?),
+            curves: Curves::from_bits(self.u64()?),
+            symmetric_primitives: SymmetricPrimitives::from_bits(self.u64()?),
+            key_types: KeyKinds::from_bits(self.u64()?),
+            key_store: self.bool()?,
+            max_symmetric_key_size: self.size()?,
+            max_request_size: match self.u32()? {
+                NO_SIZE_LIMIT => None,
+                size => Some(usize::try_from(size).map_err(|_| Error::TooLarge)?),
+            },
+        })
+    }
+}
+
+/// Decodes a request payload and copies its buffers into an arena.
+struct RequestReader<'payload, 'data> {
+    payload: &'payload [u8],
+    arena: &'data mut [u8],
+}
+
+impl<'data> RequestReader<'_, 'data> {
+    fn alloc(&mut self, size: usize) -> Result<&'data mut [u8], Error> {
+        if size > self.arena.len() {
+            return Err(Error::DataSizeMismatch);
+        }
+        let (buffer, rest) = core::mem::take(&mut self.arena).split_at_mut(size);
+        self.arena = rest;
+        Ok(buffer)
+    }
+
+    fn in_out(&mut self) -> Result<&'data mut [u8], Error> {
+        let size = self.size()?;
+        if size > self.payload.len() {
+            return Err(Error::Truncated);
+        }
+        let (data, rest) = self.payload.split_at(size);
+        self.payload = rest;
+        let buffer = self.alloc(size)?;
+        buffer.copy_from_slice(data);
+        Ok(buffer)
+    }
+
+    fn input(&mut self) -> Result<&'data [u8], Error> {
+        Ok(self.in_out()?)
+    }
+
+    fn output(&mut self) -> Result<&'data mut [u8], Error> {
+        let size = self.size()?;
+        let buffer = self.alloc(size)?;
+        buffer.fill(0);
+        Ok(buffer)
+    }
+}
+
+impl Fields for RequestReader<'_, '_> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let (bytes, rest) = self.payload.split_first_chunk().ok_or(Error::Truncated)?;
+        self.payload = rest;
+        Ok(*bytes)
+    }
+}
+
+/// Decodes a response payload whose buffers are borrowed from the payload itself.
+struct ResponseReader<'data> {
+    payload: &'data mut [u8],
+}
+
+impl<'data> ResponseReader<'data> {
+    fn take(&mut self, size: usize) -> Result<&'data mut [u8], Error> {
+        if size > self.payload.len() {
+            return Err(Error::Truncated);
+        }
+        let (data, rest) = core::mem::take(&mut self.payload).split_at_mut(size);
+        self.payload = rest;
+        Ok(data)
+    }
+
+    fn buffer(&mut self) -> Result<&'data mut [u8], Error> {
+        let size = self.size()?;
+        self.take(size)
+    }
+}
+
+impl Fields for ResponseReader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut bytes = [0u8; N];
+        bytes.copy_from_slice(self.take(N)?);
+        Ok(bytes)
+    }
+}
+
+/// Size of the encoded request payload in bytes.
+pub fn request_size(request: &Request) -> Result<usize, Error> {
+    let mut writer = Writer { buf: None, pos: 0 };
+    write_request(request, &mut writer)?;
+    Ok(writer.pos)
+}
+
+/// Encode the payload of a request frame into `buf`.
+///
+/// returns: The number of bytes written to `buf`.
+pub fn encode_request(request: &Request, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut writer = Writer {
+        buf: Some(buf),
+        pos: 0,
+    };
+    write_request(request, &mut writer)?;
+    Ok(writer.pos)
+}
+
+/// Size of the arena required to decode the request payload.
+pub fn request_data_size(payload: &[u8]) -> Result<usize, Error> {
+    // Request type, client ID, request ID and deadline precede the data size
+    const OFFSET: usize = 1 + 4 + 4 + 8;
+    let mut reader = RequestReader {
+        payload: payload.get(OFFSET..).ok_or(Error::Truncated)?,
+        arena: &mut [],
+    };
+    reader.size()
+}
+
+/// Decode the payload of a request frame. The buffers of the request are allocated from `arena`,
+/// which has to be at least [request_data_size] bytes large.
+pub fn decode_request<'data>(
+    payload: &[u8],
+    arena: &'data mut [u8],
+) -> Result<Request<'data>, Error> {
+    let data_size = request_data_size(payload)?;
+    let arena = arena.get_mut(..data_size).ok_or(Error::BufferTooSmall)?;
+    let mut r = RequestReader { payload, arena };
+    let request = read_request(&mut r)?;
+    if !r.payload.is_empty() {
+        return Err(Error::TrailingData);
+    }
+    if !r.arena.is_empty() {
+        return Err(Error::DataSizeMismatch);
+    }
+    Ok(request)
+}
+
+/// Size of the encoded response payload in bytes.
+pub fn response_size(response: &Response) -> Result<usize, Error> {
+    let mut writer = Writer { buf: None, pos: 0 };
+    write_response(response, &mut writer)?;
+    Ok(writer.pos)
+}
+
+/// Encode the payload of a response frame into `buf`.
+///
+/// returns: The number of bytes written to `buf`.
+pub fn encode_response(response: &Response, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut writer = Writer {
+        buf: Some(buf),
+        pos: 0,
+    };
+    write_response(response, &mut writer)?;
+    Ok(writer.pos)
+}
+
+/// Decode the payload of a response frame. The buffers of the response point into `payload`.
+pub fn decode_response(payload: &mut [u8]) -> Result<Response<'_>, Error> {
+    let mut r = ResponseReader { payload };
+    let response = read_response(&mut r)?;
+    if !r.payload.is_empty() {
+        return Err(Error::TrailingData);
+    }
+    Ok(response)
+}
+
+/// Read a frame of the given kind from a blocking stream.
+///
+/// returns: The payload of the frame or `None` if the stream ended before the frame.
+#[cfg(feature = "std")]
+pub fn read_frame(
+    reader: &mut impl std::io::Read,
+    kind: FrameKind,
+) -> std::io::Result<Option<std::vec::Vec<u8>>> {
+    use std::io::ErrorKind;
+
+    let mut header = [0u8; HEADER_SIZE];
+    let mut filled = 0;
+    while filled < HEADER_SIZE {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    let header = Header::decode(&header).map_err(invalid_data)?;
+    if header.kind != kind {
+        return Err(invalid_data(Error::InvalidKind));
+    }
+    let mut payload = std::vec![0u8; header.payload_size];
+    reader.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+/// Write a frame of the given kind to a blocking stream.
+#[cfg(feature = "std")]
+pub fn write_frame(
+    writer: &mut impl std::io::Write,
+    kind: FrameKind,
+    payload: &[u8],
+) -> std::io::Result<()> {
+    let header = Header {
+        kind,
+        payload_size: payload.len(),
+    }
+    .encode()
+    .map_err(invalid_data)?;
+    let mut frame = std::vec::Vec::with_capacity(HEADER_SIZE + payload.len());
+    frame.extend_from_slice(&header);
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame)
+}
+
+#[cfg(feature = "std")]
+fn invalid_data(error: Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+}
+
+fn write_request(request: &Request, w: &mut Writer) -> Result<(), Error> {
+    w.u8(request_type_code(request.get_type()))?;
+    w.u32(request.get_client_id().0)?;
+    w.u32(request.get_request_id().0)?;
+    w.u64(request.get_deadline().unwrap_or(NO_DEADLINE))?;
+    w.size(request.data_size())?;
+    match request {
+        Request::GetRandom { output, .. } => w.output(output),
+        Request::GenerateSymmetricKey {
+            key_id, overwrite, ..
+        }
+        | Request::GenerateKeyPair {
+            key_id, overwrite, ..
+        } => {
+            w.key_id(*key_id)?;
+            w.bool(*overwrite)
+        }
+        Request::ImportSymmetricKey {
+            key_id,
+            data,
+            overwrite,
+            ..
+        } => {
+            w.key_id(*key_id)?;
+            w.input(data)?;
+            w.bool(*overwrite)
+        }
+        Request::ImportKeyPair {
+            key_id,
+            public_key,
+            private_key,
+            format,
+            overwrite,
+            ..
+        } => {
+            w.key_id(*key_id)?;
+            w.input(public_key)?;
+            w.input(private_key)?;
+            w.u8(private_key_format_code(*format))?;
+            w.bool(*overwrite)
+        }
+        Request::ExportSymmetricKey { key_id, data, .. } => {
+            w.key_id(*key_id)?;
+            w.output(data)
+        }
+        Request::ExportPublicKey {
+            key_id,
+            public_key,
+            format,
+            ..
+        } => {
+            w.key_id(*key_id)?;
+            w.output(public_key)?;
+            w.u8(public_key_format_code(*format))
+        }
+        Request::ExportPrivateKey {
+            key_id,
+            private_key,
+            ..
+        } => {
+            w.key_id(*key_id)?;
+            w.output(private_key)
+        }
+        Request::IsKeyAvailable { key_id, .. } => w.key_id(*key_id),
+        Request::EncryptChaChaPoly {
+            key_id,
+            nonce,
+            buffer,
+            aad,
+            tag,
+            ..
+        }
+        | Request::EncryptAesGcm {
+            key_id,
+            iv: nonce,
+            buffer,
+            aad,
+            tag,
+            ..
+        } => {
+            w.key_id(*key_id)?;
+            w.input(nonce)?;
+            w.input(buffer)?;
+            w.input(aad)?;
+            w.output(tag)
+        }
+        Request::EncryptChaChaPolyExternalKey {
+            key,
+            nonce,
+            buffer,
+            aad,
+            tag,
+            ..
+        }
+        | Request::EncryptAesGcmExternalKey {
+            key,
+            iv: nonce,
+            buffer,
+            aad,
+            tag,
+            ..
+        } => {
+            w.input(key)?;
+            w.input(nonce)?;
+            w.input(buffer)?;
+            w.input(aad)?;
+            w.output(tag)
+        }
+        Request::DecryptChaChaPoly {
+            key_id,
+            nonce,
+            buffer,
+            aad,
+            tag,
+            ..
+        }
+        | Request::DecryptAesGcm {
+            key_id,
+            iv: nonce,
+            buffer,
+            aad,
+            tag,
+            ..
+        } => {
+            w.key_id(*key_id)?;
+            w.input(nonce)?;
+            w.input(buffer)?;
+            w.input(aad)?;
+            w.input(tag)
+        }
+        Request::DecryptChaChaPolyExternalKey {
+            key,
+            nonce,
+            buffer,
+            aad,
+            tag,
+            ..
+        }
+        | Request::DecryptAesGcmExternalKey {
+            key,
+            iv: nonce,
+            buffer,
+            aad,
+            tag,
+            ..
+        } => {
+            w.input(key)?;
+            w.input(nonce)?;
+            w.input(buffer)?;
+            w.input(aad)?;
+            w.input(tag)
+        }
+        Request::EncryptAesCbc {
+            key_id,
+            iv,
+            buffer,
+            plaintext_size,
+            padding,
+            ..
+        } => {
+            w.key_id(*key_id)?;
+            w.input(iv)?;
+            w.input(buffer)?;
+            w.size(*plaintext_size)?;
+            w.u8(padding_code(*padding))
+        }
+        Request::EncryptAesCbcExternalKey {
+            key,
+            iv,
+            buffer,
+            plaintext_size,
+            padding,
+            ..
+        } => {
+            w.input(key)?;
+            w.input(iv)?;
+            w.input(buffer)?;
+            w.size(*plaintext_size)?;
+            w.u8(padding_code(*padding))
+        }
+        Request::DecryptAesCbc {
+            key_id,
+            iv,
+            buffer,
+            padding,
+            ..
+        } => {
+            w.key_id(*key_id)?;
+            w.input(iv)?;
+            w.input(buffer)?;
+            w.u8(padding_code(*padding))
+        }
+        Request::DecryptAesCbcExternalKey {
+            key,
+            iv,
+            buffer,
+            padding,
+            ..
+        } => {
+            w.input(key)?;
+            w.input(iv)?;
+            w.input(buffer)?;
+            w.u8(padding_code(*padding))
+        }
+        Request::EncryptAesEcb { key_id, buffer, .. }
+        | Request::DecryptAesEcb { key_id, buffer, .. } => {
+            w.key_id(*key_id)?;
+            w.input(buffer)
+        }
+        Request::EncryptAesEcbExternalKey { key, buffer, .. }
+        | Request::DecryptAesEcbExternalKey { key, buffer, .. } => {
+            w.input(key)?;
+            w.input(buffer)
+        }
+        Request::CalculateAesCmac {
+            key_id,
+            message,
+            tag,
+            ..
+        } => {
+            w.key_id(*key_id)?;
+            w.input(message)?;
+            w.output(tag)
+        }
+        Request::CalculateAesCmacExternalKey {
+            key, message, tag, ..
+        } => {
+            w.input(key)?;
+            w.input(message)?;
+            w.output(tag)
+        }
+        Request::VerifyAesCmac {
+            key_id,
+            message,
+            tag,
+            ..
+        } => {
+            w.key_id(*key_id)?;
+            w.input(message)?;
+            w.input(tag)
+        }
+        Request::VerifyAesCmacExternalKey {
+            key, message, tag, ..
+        } => {
+            w.input(key)?;
+            w.input(message)?;
+            w.input(tag)
+        }
+        Request::CalculateHmac {
+            key_id,
+            hash_algorithm,
+            message,
+            tag,
+            ..
+        } => {
+            w.key_id(*key_id)?;
+            w.hash_algorithm(Some(*hash_algorithm))?;
+            w.input(message)?;
+            w.output(tag)
+        }
+        Request::CalculateHmacExternalKey {
+            key,
+            hash_algorithm,
+            message,
+            tag,
+            ..
+        } => {
+            w.input(key)?;
+            w.hash_algorithm(Some(*hash_algorithm))?;
+            w.input(message)?;
+            w.output(tag)
+        }
+        Request::VerifyHmac {
+            key_id,
+            hash_algorithm,
+            message,
+            tag,
+            ..
+        } => {
+            w.key_id(*key_id)?;
+            w.hash_algorithm(Some(*hash_algorithm))?;
+            w.input(message)?;
+            w.input(tag)
+        }
+        Request::VerifyHmacExternalKey {
+            key,
+            hash_algorithm,
+            message,
+            tag,
+            ..
+        } => {
+            w.input(key)?;
+            w.hash_algorithm(Some(*hash_algorithm))?;
+            w.input(message)?;
+            w.input(tag)
+        }
+        Request::Sign {
+            key_id,
+            message,
+            prehashed,
+            hash_algorithm,
+            signature_format,
+            signature,
+            ..
+        } => {
+            w.key_id(*key_id)?;
+            w.input(message)?;
+            w.bool(*prehashed)?;
+            w.hash_algorithm(*hash_algorithm)?;
+            w.u8(signature_format_code(*signature_format))?;
+            w.output(signature)
+        }
+        Request::SignExternalKey {
+            private_key,
+            message,
+            prehashed,
+            hash_algorithm,
+            signature_format,
+            signature,
+            ..
+        } => {
+            w.input(private_key)?;
+            w.input(message)?;
+            w.bool(*prehashed)?;
+            w.hash_algorithm(*hash_algorithm)?;
+            w.u8(signature_format_code(*signature_format))?;
+            w.output(signature)
+        }
+        Request::Verify {
+            key_id,
+            message,
+            prehashed,
+            hash_algorithm,
+            signature_format,
+            signature,
+            ..
+        } => {
+            w.key_id(*key_id)?;
+            w.input(message)?;
+            w.bool(*prehashed)?;
+            w.hash_algorithm(*hash_algorithm)?;
+            w.u8(signature_format_code(*signature_format))?;
+            w.input(signature)
+        }
+        Request::VerifyExternalKey {
+            public_key,
+            message,
+            prehashed,
+            hash_algorithm,
+            signature_format,
+            signature,
+            ..
+        } => {
+            w.input(public_key)?;
+            w.input(message)?;
+            w.bool(*prehashed)?;
+            w.hash_algorithm(*hash_algorithm)?;
+            w.u8(signature_format_code(*signature_format))?;
+            w.input(signature)
+        }
+        Request::Ecdh {
+            public_key,
+            private_key_id,
+            shared_secret,
+            ..
+        } => {
+            w.input(public_key)?;
+            w.key_id(*private_key_id)?;
+            w.output(shared_secret)
+        }
+        Request::EcdhExternalPrivateKey {
+            curve,
+            public_key,
+            private_key,
+            shared_secret,
+            ..
+        } => {
+            w.u8(curve_code(*curve))?;
+            w.input(public_key)?;
+            w.input(private_key)?;
+            w.output(shared_secret)
+        }
+        Request::LoadSheKey {
+            m1, m2, m3, m4, m5, ..
+        } => {
+            w.input(m1)?;
+            w.input(m2)?;
+            w.input(m3)?;
+            w.output(m4)?;
+            w.output(m5)
+        }
+        Request::FinishSheBoot { success, .. } => w.bool(*success),
+        Request::Cancel { target, .. } => w.u32(target.0),
+        Request::GetCapabilities { .. } => Ok(()),
+        Request::GetKeyInfo { key_id, .. } => w.key_id(*key_id),
+    }
+}
+
+fn read_request<'data>(r: &mut RequestReader<'_, 'data>) -> Result<Request<'data>, Error> {
+    let ty = request_type(r.u8()?)?;
+    let client_id = ClientId(r.u32()?);
+    let request_id = RequestId(r.u32()?);
+    let deadline = match r.u64()? {
+        NO_DEADLINE => None,
+        deadline => Some(deadline),
+    };
+    let _data_size = r.size()?;
+    let request = match ty {
+        RequestType::GetRandom => Request::GetRandom {
+            client_id,
+            request_id,
+            deadline,
+            output: r.output()?,
+        },
+        RequestType::GenerateSymmetricKey => Request::GenerateSymmetricKey {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            overwrite: r.bool()?,
+        },
+        RequestType::GenerateKeyPair => Request::GenerateKeyPair {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            overwrite: r.bool()?,
+        },
+        RequestType::ImportSymmetricKey => Request::ImportSymmetricKey {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            data: r.input()?,
+            overwrite: r.bool()?,
+        },
+        RequestType::ImportKeyPair => Request::ImportKeyPair {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            public_key: r.input()?,
+            private_key: r.input()?,
+            format: private_key_format(r.u8()?)?,
+            overwrite: r.bool()?,
+        },
+        RequestType::ExportSymmetricKey => Request::ExportSymmetricKey {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            data: r.output()?,
+        },
+        RequestType::ExportPublicKey => Request::ExportPublicKey {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            public_key: r.output()?,
+            format: public_key_format(r.u8()?)?,
+        },
+        RequestType::ExportPrivateKey => Request::ExportPrivateKey {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            private_key: r.output()?,
+        },
+        RequestType::IsKeyAvailable => Request::IsKeyAvailable {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+        },
+        RequestType::EncryptChaChaPoly => Request::EncryptChaChaPoly {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            nonce: r.input()?,
+            buffer: r.in_out()?,
+            aad: r.input()?,
+            tag: r.output()?,
+        },
+        RequestType::EncryptChaChaPolyExternalKey => Request::EncryptChaChaPolyExternalKey {
+            client_id,
+            request_id,
+            deadline,
+            key: r.input()?,
+            nonce: r.input()?,
+            buffer: r.in_out()?,
+            aad: r.input()?,
+            tag: r.output()?,
+        },
+        RequestType::DecryptChaChaPoly => Request::DecryptChaChaPoly {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            nonce: r.input()?,
+            buffer: r.in_out()?,
+            aad: r.input()?,
+            tag: r.input()?,
+        },
+        RequestType::DecryptChaChaPolyExternalKey => Request::DecryptChaChaPolyExternalKey {
+            client_id,
+            request_id,
+            deadline,
+            key: r.input()?,
+            nonce: r.input()?,
+            buffer: r.in_out()?,
+            aad: r.input()?,
+            tag: r.input()?,
+        },
+        RequestType::EncryptAesGcm => Request::EncryptAesGcm {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            iv: r.input()?,
+            buffer: r.in_out()?,
+            aad: r.input()?,
+            tag: r.output()?,
+        },
+        RequestType::EncryptAesGcmExternalKey => Request::EncryptAesGcmExternalKey {
+            client_id,
+            request_id,
+            deadline,
+            key: r.input()?,
+            iv: r.input()?,
+            buffer: r.in_out()?,
+            aad: r.input()?,
+            tag: r.output()?,
+        },
+        RequestType::DecryptAesGcm => Request::DecryptAesGcm {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            iv: r.input()?,
+            buffer: r.in_out()?,
+            aad: r.input()?,
+            tag: r.input()?,
+        },
+        RequestType::DecryptAesGcmExternalKey => Request::DecryptAesGcmExternalKey {
+            client_id,
+            request_id,
+            deadline,
+            key: r.input()?,
+            iv: r.input()?,
+            buffer: r.in_out()?,
+            aad: r.input()?,
+            tag: r.input()?,
+        },
+        RequestType::EncryptAesCbc => Request::EncryptAesCbc {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            iv: r.input()?,
+            buffer: r.in_out()?,
+            plaintext_size: r.size()?,
+            padding: padding(r.u8()?)?,
+        },
+        RequestType::EncryptAesCbcExternalKey => Request::EncryptAesCbcExternalKey {
+            client_id,
+            request_id,
+            deadline,
+            key: r.input()?,
+            iv: r.input()?,
+            buffer: r.in_out()?,
+            plaintext_size: r.size()?,
+            padding: padding(r.u8()?)?,
+        },
+        RequestType::DecryptAesCbc => Request::DecryptAesCbc {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            iv: r.input()?,
+            buffer: r.in_out()?,
+            padding: padding(r.u8()?)?,
+        },
+        RequestType::DecryptAesCbcExternalKey => Request::DecryptAesCbcExternalKey {
+            client_id,
+            request_id,
+            deadline,
+            key: r.input()?,
+            iv: r.input()?,
+            buffer: r.in_out()?,
+            padding: padding(r.u8()?)?,
+        },
+        RequestType::EncryptAesEcb => Request::EncryptAesEcb {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            buffer: r.in_out()?,
+        },
+        RequestType::EncryptAesEcbExternalKey => Request::EncryptAesEcbExternalKey {
+            client_id,
+            request_id,
+            deadline,
+            key: r.input()?,
+            buffer: r.in_out()?,
+        },
+        RequestType::DecryptAesEcb => Request::DecryptAesEcb {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            buffer: r.in_out()?,
+        },
+        RequestType::DecryptAesEcbExternalKey => Request::DecryptAesEcbExternalKey {
+            client_id,
+            request_id,
+            deadline,
+            key: r.input()?,
+            buffer: r.in_out()?,
+        },
+        RequestType::CalculateAesCmac => Request::CalculateAesCmac {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            message: r.input()?,
+            tag: r.output()?,
+        },
+        RequestType::CalculateAesCmacExternalKey => Request::CalculateAesCmacExternalKey {
+            client_id,
+            request_id,
+            deadline,
+            key: r.input()?,
+            message: r.input()?,
+            tag: r.output()?,
+        },
+        RequestType::VerifyAesCmac => Request::VerifyAesCmac {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            message: r.input()?,
+            tag: r.input()?,
+        },
+        RequestType::VerifyAesCmacExternalKey => Request::VerifyAesCmacExternalKey {
+            client_id,
+            request_id,
+            deadline,
+            key: r.input()?,
+            message: r.input()?,
+            tag: r.input()?,
+        },
+        RequestType::CalculateHmac => Request::CalculateHmac {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            hash_algorithm: hash_algorithm(r.u8()?)?,
+            message: r.input()?,
+            tag: r.output()?,
+        },
+        RequestType::CalculateHmacExternalKey => Request::CalculateHmacExternalKey {
+            client_id,
+            request_id,
+            deadline,
+            key: r.input()?,
+            hash_algorithm: hash_algorithm(r.u8()?)?,
+            message: r.input()?,
+            tag: r.output()?,
+        },
+        RequestType::VerifyHmac => Request::VerifyHmac {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            hash_algorithm: hash_algorithm(r.u8()?)?,
+            message: r.input()?,
+            tag: r.input()?,
+        },
+        RequestType::VerifyHmacExternalKey => Request::VerifyHmacExternalKey {
+            client_id,
+            request_id,
+            deadline,
+            key: r.input()?,
+            hash_algorithm: hash_algorithm(r.u8()?)?,
+            message: r.input()?,
+            tag: r.input()?,
+        },
+        RequestType::Sign => Request::Sign {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            message: r.input()?,
+            prehashed: r.bool()?,
+            hash_algorithm: r.hash_algorithm()?,
+            signature_format: signature_format(r.u8()?)?,
+            signature: r.output()?,
+        },
+        RequestType::SignExternalKey => Request::SignExternalKey {
+            client_id,
+            request_id,
+            deadline,
+            private_key: r.input()?,
+            message: r.input()?,
+            prehashed: r.bool()?,
+            hash_algorithm: r.hash_algorithm()?,
+            signature_format: signature_format(r.u8()?)?,
+            signature: r.output()?,
+        },
+        RequestType::Verify => Request::Verify {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+            message: r.input()?,
+            prehashed: r.bool()?,
+            hash_algorithm: r.hash_algorithm()?,
+            signature_format: signature_format(r.u8()?)?,
+            signature: r.input()?,
+        },
+        RequestType::VerifyExternalKey => Request::VerifyExternalKey {
+            client_id,
+            request_id,
+            deadline,
+            public_key: r.input()?,
+            message: r.input()?,
+            prehashed: r.bool()?,
+            hash_algorithm: r.hash_algorithm()?,
+            signature_format: signature_format(r.u8()?)?,
+            signature: r.input()?,
+        },
+        RequestType::Ecdh => Request::Ecdh {
+            client_id,
+            request_id,
+            deadline,
+            public_key: r.input()?,
+            private_key_id: r.key_id()?,
+            shared_secret: r.output()?,
+        },
+        RequestType::EcdhExternalPrivateKey => Request::EcdhExternalPrivateKey {
+            client_id,
+            request_id,
+            deadline,
+            curve: curve(r.u8()?)?,
+            public_key: r.input()?,
+            private_key: r.input()?,
+            shared_secret: r.output()?,
+        },
+        RequestType::LoadSheKey => Request::LoadSheKey {
+            client_id,
+            request_id,
+            deadline,
+            m1: r.input()?,
+            m2: r.input()?,
+            m3: r.input()?,
+            m4: r.output()?,
+            m5: r.output()?,
+        },
+        RequestType::FinishSheBoot => Request::FinishSheBoot {
+            client_id,
+            request_id,
+            deadline,
+            success: r.bool()?,
+        },
+        RequestType::Cancel => Request::Cancel {
+            client_id,
+            request_id,
+            deadline,
+            target: RequestId(r.u32()?),
+        },
+        RequestType::GetCapabilities => Request::GetCapabilities {
+            client_id,
+            request_id,
+            deadline,
+        },
+        RequestType::GetKeyInfo => Request::GetKeyInfo {
+            client_id,
+            request_id,
+            deadline,
+            key_id: r.key_id()?,
+        },
+    };
+    Ok(request)
+}
+
+fn write_response(response: &Response, w: &mut Writer) -> Result<(), Error> {
+    let code = match response {
+        Response::Error { .. } => 0,
+        Response::GetRandom { .. } => 1,
+        Response::GenerateSymmetricKey { .. } => 2,
+        Response::GenerateKeyPair { .. } => 3,
+        Response::ImportSymmetricKey { .. } => 4,
+        Response::ImportKeyPair { .. } => 5,
+        Response::ExportSymmetricKey { .. } => 6,
+        Response::ExportPublicKey { .. } => 7,
+        Response::ExportPrivateKey { .. } => 8,
+        Response::IsKeyAvailable { .. } => 9,
+        Response::EncryptChaChaPoly { .. } => 10,
+        Response::DecryptChaChaPoly { .. } => 11,
+        Response::EncryptAesGcm { .. } => 12,
+        Response::DecryptAesGcm { .. } => 13,
+        Response::EncryptAesCbc { .. } => 14,
+        Response::DecryptAesCbc { .. } => 15,
+        Response::EncryptAesEcb { .. } => 16,
+        Response::DecryptAesEcb { .. } => 17,
+        Response::CalculateAesCmac { .. } => 18,
+        Response::VerifyAesCmac { .. } => 19,
+        Response::CalculateHmac { .. } => 20,
+        Response::VerifyHmac { .. } => 21,
+        Response::Sign { .. } => 22,
+        Response::Verify { .. } => 23,
+        Response::Ecdh { .. } => 24,
+        Response::LoadSheKey { .. } => 25,
+        Response::FinishSheBoot { .. } => 26,
+        Response::Cancel { .. } => 27,
+        Response::Cancelled { .. } => 28,
+        Response::GetCapabilities { .. } => 29,
+        Response::GetKeyInfo { .. } => 30,
+    };
+    w.u8(code)?;
+    w.u32(response.get_client_id().0)?;
+    w.u32(response.get_request_id().0)?;
+    match response {
+        Response::Error { error, .. } => w.put(&error_code(*error)),
+        Response::GenerateSymmetricKey { .. }
+        | Response::GenerateKeyPair { .. }
+        | Response::ImportSymmetricKey { .. }
+        | Response::ImportKeyPair { .. }
+        | Response::FinishSheBoot { .. }
+        | Response::Cancel { .. } => Ok(()),
+        Response::GetRandom { data, .. } => w.input(data),
+        Response::ExportSymmetricKey { key, .. } => w.input(key),
+        Response::ExportPublicKey { public_key, .. } => w.input(public_key),
+        Response::ExportPrivateKey { private_key, .. } => w.input(private_key),
+        Response::IsKeyAvailable { is_available, .. } => w.bool(*is_available),
+        Response::EncryptChaChaPoly { buffer, tag, .. }
+        | Response::EncryptAesGcm { buffer, tag, .. } => {
+            w.input(buffer)?;
+            w.input(tag)
+        }
+        Response::DecryptChaChaPoly { buffer, .. }
+        | Response::DecryptAesGcm { buffer, .. }
+        | Response::EncryptAesCbc { buffer, .. }
+        | Response::EncryptAesEcb { buffer, .. } => w.input(buffer),
+        Response::DecryptAesCbc { plaintext, .. } | Response::DecryptAesEcb { plaintext, .. } => {
+            w.input(plaintext)
+        }
+        Response::CalculateAesCmac { tag, .. } | Response::CalculateHmac { tag, .. } => {
+            w.input(tag)
+        }
+        Response::VerifyAesCmac { verified, .. }
+        | Response::VerifyHmac { verified, .. }
+        | Response::Verify { verified, .. } => w.bool(*verified),
+        Response::Sign { signature, .. } => w.input(signature),
+        Response::Ecdh { shared_secret, .. } => w.input(shared_secret),
+        Response::LoadSheKey { m4, m5, .. } => {
+            w.input(m4)?;
+            w.input(m5)
+        }
+        Response::Cancelled { reason, .. } => w.u8(cancel_reason_code(*reason)),
+        Response::GetCapabilities { capabilities, .. } => w.capabilities(capabilities),
+        Response::GetKeyInfo { key_info, .. } => w.key_info(key_info),
+    }
+}
+
+fn read_response<'data>(r: &mut ResponseReader<'data>) -> Result<Response<'data>, Error> {
+    let code = r.u8()?;
+    let client_id = ClientId(r.u32()?);
+    let request_id = RequestId(r.u32()?);
+    let response = match code {
+        0 => Response::Error {
+            client_id,
+            request_id,
+            error: error(r.bytes()?)?,
+        },
+        1 => Response::GetRandom {
+            client_id,
+            request_id,
+            data: r.buffer()?,
+        },
+        2 => Response::GenerateSymmetricKey {
+            client_id,
+            request_id,
+        },
+        3 => Response::GenerateKeyPair {
+            client_id,
+            request_id,
+        },
+        4 => Response::ImportSymmetricKey {
+            client_id,
+            request_id,
+        },
+        5 => Response::ImportKeyPair {
+            client_id,
+            request_id,
+        },
+        6 => Response::ExportSymmetricKey {
+            client_id,
+            request_id,
+            key: r.buffer()?,
+        },
+        7 => Response::ExportPublicKey {
+            client_id,
+            request_id,
+            public_key: r.buffer()?,
+        },
+        8 => Response::ExportPrivateKey {
+            client_id,
+            request_id,
+            private_key: r.buffer()?,
+        },
+        9 => Response::IsKeyAvailable {
+            client_id,
+            request_id,
+            is_available: r.bool()?,
+        },
+        10 => Response::EncryptChaChaPoly {
+            client_id,
+            request_id,
+            buffer: r.buffer()?,
+            tag: r.buffer()?,
+        },
+        11 => Response::DecryptChaChaPoly {
+            client_id,
+            request_id,
+            buffer: r.buffer()?,
+        },
+        12 => Response::EncryptAesGcm {
+            client_id,
+            request_id,
+            buffer: r.buffer()?,
+            tag: r.buffer()?,
+        },
+        13 => Response::DecryptAesGcm {
+            client_id,
+            request_id,
+            buffer: r.buffer()?,
+        },
+        14 => Response::EncryptAesCbc {
+            client_id,
+            request_id,
+            buffer: r.buffer()?,
+        },
+        15 => Response::DecryptAesCbc {
+            client_id,
+            request_id,
+            plaintext: r.buffer()?,
+        },
+        16 => Response::EncryptAesEcb {
+            client_id,
+            request_id,
+            buffer: r.buffer()?,
+        },
+        17 => Response::DecryptAesEcb {
+            client_id,
+            request_id,
+            plaintext: r.buffer()?,
+        },
+        18 => Response::CalculateAesCmac {
+            client_id,
+            request_id,
+            tag: r.buffer()?,
+        },
+        19 => Response::VerifyAesCmac {
+            client_id,
+            request_id,
+            verified: r.bool()?,
+        },
+        20 => Response::CalculateHmac {
+            client_id,
+            request_id,
+            tag: r.buffer()?,
+        },
+        21 => Response::VerifyHmac {
+            client_id,
+            request_id,
+            verified: r.bool()?,
+        },
+        22 => Response::Sign {
+            client_id,
+            request_id,
+            signature: r.buffer()?,
+        },
+        23 => Response::Verify {
+            client_id,
+            request_id,
+            verified: r.bool()?,
+        },
+        24 => Response::Ecdh {
+            client_id,
+            request_id,
+            shared_secret: r.buffer()?,
+        },
+        25 => Response::LoadSheKey {
+            client_id,
+            request_id,
+            m4: r.buffer()?,
+            m5: r.buffer()?,
+        },
+        26 => Response::FinishSheBoot {
+            client_id,
+            request_id,
+        },
+        27 => Response::Cancel {
+            client_id,
+            request_id,
+        },
+        28 => Response::Cancelled {
+            client_id,
+            request_id,
+            reason: cancel_reason(r.u8()?)?,
+        },
+        29 => Response::GetCapabilities {
+            client_id,
+            request_id,
+            capabilities: r.capabilities()?,
+        },
+        30 => Response::GetKeyInfo {
+            client_id,
+            request_id,
+            key_info: r.key_info()?,
+        },
+        _ => return Err(Error::InvalidValue),
+    };
+    Ok(response)
+}
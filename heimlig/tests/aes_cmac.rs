@@ -2,11 +2,12 @@
 mod common;
 
 pub use common::*;
-use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use heimlig::common::sync::Mutex;
 use heimlig::{
     common::jobs::{RequestType, Response},
     crypto,
-    hsm::workers::aes_worker::AesWorker,
+    hsm::workers::{aes_worker::AesWorker, QueuedWorker},
 };
 
 #[async_std::test]
@@ -33,10 +34,14 @@ async fn aes_cmac_calculate_verify() {
         &mut worker_responses,
         Some(&key_store),
     );
-    let mut worker = AesWorker {
-        key_store: &key_store,
+    let mut worker = QueuedWorker {
+        worker: AesWorker {
+            key_store: &key_store,
+            she_slots: None,
+        },
         requests: req_worker_rx,
         responses: resp_worker_tx,
+        cancellations: None,
     };
 
     import_symmetric_key(&mut api, &mut core, SYM_256_KEY.id, &key).await;
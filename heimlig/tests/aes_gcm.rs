@@ -2,12 +2,13 @@
 mod common;
 
 pub use common::*;
-use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use heimlig::common::sync::Mutex;
 use heimlig::{
     client::api::SymmetricAlgorithm::AesGcm,
     common::jobs::{RequestType, Response},
     crypto,
-    hsm::workers::aes_worker::AesWorker,
+    hsm::workers::{aes_worker::AesWorker, QueuedWorker},
 };
 
 #[async_std::test]
@@ -38,10 +39,14 @@ async fn aes_gcm_encrypt_in_place() {
         &mut worker_responses,
         Some(&key_store),
     );
-    let mut worker = AesWorker {
-        key_store: &key_store,
+    let mut worker = QueuedWorker {
+        worker: AesWorker {
+            key_store: &key_store,
+            she_slots: None,
+        },
         requests: req_worker_rx,
         responses: resp_worker_tx,
+        cancellations: None,
     };
 
     import_symmetric_key(&mut api, &mut core, SYM_128_KEY.id, &key).await;
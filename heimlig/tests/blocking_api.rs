@@ -0,0 +1,215 @@
+#[allow(dead_code, unused_macros)]
+#[macro_use]
+mod common;
+
+use common::*;
+use core::ffi::c_void;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use futures::{sink, stream};
+use heimlig::client::blocking::{BlockingApi, Spin, Wait};
+use heimlig::client::typed::Error;
+use heimlig::common::jobs::{self, ClientId, Request, RequestId, Response};
+use heimlig::common::sync::Mutex;
+use heimlig::hsm::core::{Builder, ClientConfig};
+use heimlig::hsm::keystore::{self, KeyId};
+use heimlig::hsm::workers::rng_worker::RngWorker;
+use heimlig::integration::embassy::{RequestQueueSource, ResponseQueueSink};
+use heimlig::integration::memory_key_store::MemoryKeyStore;
+use heimlig::integration::raw_blocking::{
+    heimlig_call_blocking, BlockingTransportRaw, CALL_INVALID_ARGUMENT, CALL_OK,
+};
+use heimlig::integration::raw_jobs::{RequestRaw, ResponseRaw};
+use std::collections::VecDeque;
+use std::sync::Condvar;
+use std::thread;
+
+type TestBuilder<'data, 'ch, 'keystore> = Builder<
+    'data,
+    'keystore,
+    NoopRawMutex,
+    RequestQueueSource<'ch, 'data, QUEUE_SIZE>,
+    ResponseQueueSink<'ch, 'data, QUEUE_SIZE>,
+    MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
+>;
+
+/// Binary semaphore as it would be provided by an RTOS.
+struct Semaphore {
+    available: std::sync::Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Wait for Semaphore {
+    fn wait(&self) {
+        let available = self.available.lock().unwrap();
+        let mut available = self
+            .condvar
+            .wait_while(available, |available| !*available)
+            .unwrap();
+        *available = false;
+    }
+
+    fn wake(&self) {
+        *self.available.lock().unwrap() = true;
+        self.condvar.notify_one();
+    }
+}
+
+static SEMAPHORE: Semaphore = Semaphore {
+    available: std::sync::Mutex::new(false),
+    condvar: Condvar::new(),
+};
+
+#[test]
+fn blocking_requests() {
+    let mut output = [0u8; 32];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            let mut key_store = init_key_store(&KEY_INFOS);
+            let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
+            let rng = init_rng();
+            let rng_worker = RngWorker {
+                rng: &rng,
+                key_store: Some(&key_store),
+            };
+            let mut core = TestBuilder::default()
+                .with_keystore(&key_store)
+                .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+                .expect("failed to add client")
+                .with_hosted_worker(rng_worker)
+                .expect("failed to add hosted worker")
+                .build();
+            for _ in 0..4 {
+                async_std::task::block_on(core.execute()).expect("failed to process request");
+            }
+        });
+
+        let api = BlockingApi::new(req_client_tx, resp_client_rx, &SEMAPHORE);
+        assert_eq!(api.get_random(&mut output).map(|data| data.len()), Ok(32));
+        assert!(!api
+            .is_key_available(SYM_128_KEY.id)
+            .expect("failed to check key"));
+        api.generate_symmetric_key(SYM_128_KEY.id, false)
+            .expect("failed to generate key");
+        assert_eq!(
+            api.import_symmetric_key(KeyId(42), &[0u8; 16], false),
+            Err(Error::Hsm(jobs::Error::KeyStore(
+                keystore::Error::InvalidKeyId
+            )))
+        );
+    });
+}
+
+#[test]
+fn closed_response_stream() {
+    let mut output = [0u8; 16];
+    let api = BlockingApi::new(sink::drain(), stream::empty(), &Spin);
+    assert_eq!(api.get_random(&mut output), Err(Error::Closed));
+}
+
+/// Transport of the raw blocking API that answers with the queued responses once the request was
+/// sent. The queue is full for the first `busy` attempts to send.
+struct Transport {
+    busy: usize,
+    sent: Option<RequestRaw>,
+    responses: VecDeque<ResponseRaw>,
+    waits: usize,
+}
+
+unsafe extern "C" fn send(context: *mut c_void, request: *const RequestRaw) -> bool {
+    // SAFETY: `context` points to the `Transport` of the test, which outlives the call.
+    let transport = unsafe { &mut *context.cast::<Transport>() };
+    if transport.busy > 0 {
+        transport.busy -= 1;
+        return false;
+    }
+    // SAFETY: `heimlig_call_blocking` passes the valid request of the test.
+    transport.sent = Some(unsafe { *request });
+    true
+}
+
+unsafe extern "C" fn receive(context: *mut c_void, response: *mut ResponseRaw) -> bool {
+    // SAFETY: `context` points to the `Transport` of the test, which outlives the call.
+    let transport = unsafe { &mut *context.cast::<Transport>() };
+    if transport.sent.is_none() {
+        return false;
+    }
+    match transport.responses.pop_front() {
+        Some(next) => {
+            // SAFETY: `heimlig_call_blocking` passes the valid response of the test.
+            unsafe { response.write(next) };
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn wait(context: *mut c_void) {
+    // SAFETY: `context` points to the `Transport` of the test, which outlives the call.
+    let transport = unsafe { &mut *context.cast::<Transport>() };
+    transport.waits += 1;
+}
+
+#[test]
+fn raw_blocking_call() {
+    let mut output = [0u8; 16];
+    let mut data = [0u8; 16];
+    let request: RequestRaw = Request::GetRandom {
+        client_id: ClientId(2),
+        request_id: RequestId(7),
+        deadline: None,
+        output: &mut output,
+    }
+    .try_into()
+    .expect("failed to convert request");
+    // The response of another request arrives first and is discarded
+    let stale: ResponseRaw = Response::GenerateSymmetricKey {
+        client_id: ClientId(1),
+        request_id: RequestId(6),
+    }
+    .try_into()
+    .expect("failed to convert response");
+    let expected: ResponseRaw = Response::GetRandom {
+        client_id: ClientId(2),
+        request_id: RequestId(7),
+        data: &mut data,
+    }
+    .try_into()
+    .expect("failed to convert response");
+    let mut transport = Transport {
+        busy: 2,
+        sent: None,
+        responses: VecDeque::from([stale, expected]),
+        waits: 0,
+    };
+    let raw_transport = BlockingTransportRaw {
+        context: (&mut transport as *mut Transport).cast(),
+        send: Some(send),
+        receive: Some(receive),
+        wait: Some(wait),
+    };
+    let mut response: ResponseRaw = stale;
+
+    // SAFETY: All pointers are valid and the transport functions access the transport above.
+    let status = unsafe { heimlig_call_blocking(&raw_transport, &request, &mut response) };
+    assert_eq!(status, CALL_OK);
+    assert_eq!(response.client_id, 2);
+    assert_eq!(response.request_id, 7);
+    assert_eq!(transport.waits, 2);
+    assert_eq!(transport.sent.map(|sent| sent.request_id), Some(7));
+    assert!(transport.responses.is_empty());
+
+    let incomplete = BlockingTransportRaw {
+        wait: None,
+        ..raw_transport
+    };
+    // SAFETY: See above
+    let status = unsafe { heimlig_call_blocking(&incomplete, &request, &mut response) };
+    assert_eq!(status, CALL_INVALID_ARGUMENT);
+    // SAFETY: See above
+    let status = unsafe { heimlig_call_blocking(&raw_transport, &request, core::ptr::null_mut()) };
+    assert_eq!(status, CALL_INVALID_ARGUMENT);
+}
@@ -0,0 +1,208 @@
+#[allow(dead_code)]
+#[macro_use]
+mod common;
+
+use common::*;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use futures::FutureExt;
+use heimlig::{
+    client::api::Api,
+    common::jobs::{CancelReason, Error, RequestType, Response},
+    hsm::{
+        cancellations::Cancellations,
+        core::{Builder, ClientConfig},
+        workers::{rng_worker::RngWorker, QueuedWorker},
+    },
+    integration::{
+        embassy::{RequestQueueSink, RequestQueueSource, ResponseQueueSink, ResponseQueueSource},
+        memory_key_store::MemoryKeyStore,
+    },
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+fn now() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+#[async_std::test]
+async fn expired_request_is_dropped() {
+    let mut expired_output = [0u8; 16];
+    let mut output = [0u8; 16];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let (rng_requests_rx, rng_requests_tx, rng_responses_rx, rng_responses_tx) =
+        split_queues(&mut worker_requests, &mut worker_responses);
+    let rng = init_rng();
+    let mut rng_worker = QueuedWorker {
+        worker: RngWorker::<NoopRawMutex, _, MemoryKeyStore<1, 1>> {
+            rng: &rng,
+            key_store: None,
+        },
+        requests: rng_requests_rx,
+        responses: rng_responses_tx,
+        cancellations: None,
+    };
+    let mut core = Builder::<
+        NoopRawMutex,
+        RequestQueueSource<'_, '_, QUEUE_SIZE>,
+        ResponseQueueSink<'_, '_, QUEUE_SIZE>,
+        MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
+        RequestQueueSink<'_, '_, QUEUE_SIZE>,
+        ResponseQueueSource<'_, '_, QUEUE_SIZE>,
+    >::default()
+    .with_clock(now)
+    .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+    .expect("failed to add client")
+    .with_worker(&[RequestType::GetRandom], rng_requests_tx, rng_responses_rx)
+    .expect("failed to add worker")
+    .build();
+    let mut api = Api::new(req_client_tx, resp_client_rx);
+
+    TICKS.store(6, Ordering::Relaxed);
+    api.set_deadline(Some(5));
+    let org_request_id = api
+        .get_random(&mut expired_output)
+        .await
+        .expect("failed to send request");
+    let Response::Cancelled {
+        client_id: _,
+        request_id,
+        reason,
+    } = get_response_from_core(&mut api, &mut core).await
+    else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(request_id, org_request_id);
+    assert_eq!(reason, CancelReason::Expired);
+    // Expired request was not forwarded to the worker
+    assert!(rng_worker.execute().now_or_never().is_none());
+
+    // Requests within their deadline are processed
+    api.set_deadline(Some(6));
+    let org_request_id = api
+        .get_random(&mut output)
+        .await
+        .expect("failed to send request");
+    let response = get_response_from_worker!(api, core, rng_worker);
+    assert!(matches!(response, Response::GetRandom { .. }));
+    assert_eq!(response.get_request_id(), org_request_id);
+}
+
+#[async_std::test]
+async fn cancel_forwarded_request() {
+    let cancellations = Cancellations::new();
+    let mut output1 = [0u8; 16];
+    let mut output2 = [0u8; 16];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let (rng_requests_rx, rng_requests_tx, rng_responses_rx, rng_responses_tx) =
+        split_queues(&mut worker_requests, &mut worker_responses);
+    let rng = init_rng();
+    let mut rng_worker = QueuedWorker {
+        worker: RngWorker::<NoopRawMutex, _, MemoryKeyStore<1, 1>> {
+            rng: &rng,
+            key_store: None,
+        },
+        requests: rng_requests_rx,
+        responses: rng_responses_tx,
+        cancellations: Some(&cancellations),
+    };
+    let mut core = Builder::<
+        NoopRawMutex,
+        RequestQueueSource<'_, '_, QUEUE_SIZE>,
+        ResponseQueueSink<'_, '_, QUEUE_SIZE>,
+        MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
+        RequestQueueSink<'_, '_, QUEUE_SIZE>,
+        ResponseQueueSource<'_, '_, QUEUE_SIZE>,
+    >::default()
+    .with_cancellations(&cancellations)
+    .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+    .expect("failed to add client")
+    .with_worker(&[RequestType::GetRandom], rng_requests_tx, rng_responses_rx)
+    .expect("failed to add worker")
+    .build();
+    let mut api = Api::new(req_client_tx, resp_client_rx);
+
+    // Forward both requests to the worker before it processes any of them
+    let request1_id = api
+        .get_random(&mut output1)
+        .await
+        .expect("failed to send request");
+    let request2_id = api
+        .get_random(&mut output2)
+        .await
+        .expect("failed to send request");
+    core.execute().await.expect("failed to forward request");
+    core.execute().await.expect("failed to forward request");
+
+    let cancel_request_id = api.cancel(request2_id).await.expect("failed to cancel");
+    let response = get_response_from_core(&mut api, &mut core).await;
+    assert!(matches!(response, Response::Cancel { .. }));
+    assert_eq!(response.get_request_id(), cancel_request_id);
+
+    rng_worker
+        .execute()
+        .await
+        .expect("failed to process request");
+    core.execute().await.expect("failed to forward response");
+    let response = api.recv_response().await.expect("no response");
+    assert!(matches!(response, Response::GetRandom { .. }));
+    assert_eq!(response.get_request_id(), request1_id);
+
+    rng_worker
+        .execute()
+        .await
+        .expect("failed to process request");
+    core.execute().await.expect("failed to forward response");
+    let Some(Response::Cancelled {
+        client_id,
+        request_id,
+        reason,
+    }) = api.recv_response().await
+    else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(request_id, request2_id);
+    assert_eq!(reason, CancelReason::Cancelled);
+    assert!(!cancellations.is_cancelled(client_id, request_id));
+
+    // Answered requests are no longer pending and cannot be cancelled
+    api.cancel(request1_id).await.expect("failed to cancel");
+    let Response::Error { error, .. } = get_response_from_core(&mut api, &mut core).await else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(error, Error::NotCancellable);
+    assert!(!cancellations.is_cancelled(client_id, request1_id));
+}
+
+#[async_std::test]
+async fn cancel_without_registry_fails() {
+    let mut output = [0u8; 16];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let (mut api, mut core, _req_worker_rx, _resp_worker_tx) = init_core(
+        &[RequestType::GetRandom],
+        &mut client_requests,
+        &mut client_responses,
+        &mut worker_requests,
+        &mut worker_responses,
+        None,
+    );
+
+    let target = api
+        .get_random(&mut output)
+        .await
+        .expect("failed to send request");
+    core.execute().await.expect("failed to forward request");
+    api.cancel(target).await.expect("failed to cancel");
+    let Response::Error { error, .. } = get_response_from_core(&mut api, &mut core).await else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(error, Error::CancellationUnsupported);
+}
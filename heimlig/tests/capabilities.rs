@@ -0,0 +1,156 @@
+#[allow(dead_code, unused_macros)]
+#[macro_use]
+mod common;
+
+use common::*;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use heimlig::common::sync::Mutex;
+use heimlig::{
+    client::api::Api,
+    common::jobs::{HashAlgorithm, KeyKind, RequestType, Response, SymmetricPrimitive},
+    hsm::{
+        cancellations::Cancellations,
+        core::{Builder, ClientConfig, ClientLimits},
+        keystore::{Curve, KeyType},
+    },
+    integration::{
+        embassy::{RequestQueueSink, RequestQueueSource, ResponseQueueSink, ResponseQueueSource},
+        memory_key_store::MemoryKeyStore,
+        raw_jobs::AbiVersion,
+    },
+};
+
+type TestBuilder<'data, 'ch> = Builder<
+    'data,
+    'static,
+    NoopRawMutex,
+    RequestQueueSource<'ch, 'data, QUEUE_SIZE>,
+    ResponseQueueSink<'ch, 'data, QUEUE_SIZE>,
+    MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
+    RequestQueueSink<'ch, 'data, QUEUE_SIZE>,
+    ResponseQueueSource<'ch, 'data, QUEUE_SIZE>,
+>;
+
+#[async_std::test]
+async fn capabilities_with_key_store() {
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let mut key_store = init_key_store(&KEY_INFOS);
+    let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
+    let (mut api, mut core, _req_worker_rx, _resp_worker_tx) = init_core(
+        &[RequestType::GetRandom, RequestType::GenerateSymmetricKey],
+        &mut client_requests,
+        &mut client_responses,
+        &mut worker_requests,
+        &mut worker_responses,
+        Some(&key_store),
+    );
+
+    let org_request_id = api
+        .get_capabilities()
+        .await
+        .expect("failed to send request");
+    let Response::GetCapabilities {
+        request_id,
+        capabilities,
+        ..
+    } = get_response_from_core(&mut api, &mut core).await
+    else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(request_id, org_request_id);
+    assert_eq!(capabilities.abi_version, AbiVersion::CURRENT);
+    for supported in [
+        RequestType::GetRandom,
+        RequestType::GenerateSymmetricKey,
+        RequestType::ImportSymmetricKey,
+        RequestType::IsKeyAvailable,
+        RequestType::GetKeyInfo,
+        RequestType::GetCapabilities,
+    ] {
+        assert!(capabilities.request_types.contains(supported));
+    }
+    // Cancellation needs a registry of cancelled requests
+    for unsupported in [
+        RequestType::GenerateKeyPair,
+        RequestType::EncryptAesGcm,
+        RequestType::Cancel,
+    ] {
+        assert!(!capabilities.request_types.contains(unsupported));
+    }
+    assert!(capabilities
+        .hash_algorithms
+        .contains(HashAlgorithm::Sha3_512));
+    assert!(capabilities.curves.contains(Curve::NistP384));
+    assert!(capabilities.key_store);
+    assert!(capabilities.key_types.contains(KeyKind::Symmetric));
+    assert!(capabilities.key_types.contains(KeyKind::KeyPair));
+    assert!(!capabilities
+        .symmetric_primitives
+        .contains(SymmetricPrimitive::AesGcm));
+    assert_eq!(
+        capabilities.max_symmetric_key_size,
+        KeyType::MAX_SYMMETRIC_KEY_SIZE
+    );
+    assert_eq!(capabilities.max_request_size, None);
+}
+
+#[async_std::test]
+async fn capabilities_without_key_store() {
+    let cancellations = Cancellations::new();
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let (_req_worker_rx, req_worker_tx, resp_worker_rx, _resp_worker_tx) =
+        split_queues(&mut worker_requests, &mut worker_responses);
+    let limits = ClientLimits {
+        max_request_size: Some(64),
+        ..Default::default()
+    };
+    let mut core = TestBuilder::default()
+        .with_cancellations(&cancellations)
+        .with_client(
+            req_client_rx,
+            resp_client_tx,
+            ClientConfig::default().with_limits(limits),
+        )
+        .expect("failed to add client")
+        .with_worker(
+            &[RequestType::EncryptAesGcmExternalKey],
+            req_worker_tx,
+            resp_worker_rx,
+        )
+        .expect("failed to add worker")
+        .build();
+    let mut api = Api::new(req_client_tx, resp_client_rx);
+
+    api.get_capabilities()
+        .await
+        .expect("failed to send request");
+    let Response::GetCapabilities { capabilities, .. } =
+        get_response_from_core(&mut api, &mut core).await
+    else {
+        panic!("Unexpected response type")
+    };
+    // Key management needs a key store, cancellation and capabilities do not
+    assert!(!capabilities.key_store);
+    assert_eq!(capabilities.key_types.bits(), 0);
+    for unsupported in [RequestType::ImportSymmetricKey, RequestType::GetKeyInfo] {
+        assert!(!capabilities.request_types.contains(unsupported));
+    }
+    assert!(capabilities.request_types.contains(RequestType::Cancel));
+    assert!(capabilities
+        .request_types
+        .contains(RequestType::EncryptAesGcmExternalKey));
+    assert!(!capabilities
+        .request_types
+        .contains(RequestType::EncryptAesGcm));
+    assert!(capabilities
+        .symmetric_primitives
+        .contains(SymmetricPrimitive::AesGcm));
+    assert!(!capabilities
+        .symmetric_primitives
+        .contains(SymmetricPrimitive::AesCbc));
+    assert_eq!(capabilities.max_request_size, Some(64));
+}
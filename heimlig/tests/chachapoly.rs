@@ -2,12 +2,13 @@
 mod common;
 
 pub use common::*;
-use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use heimlig::common::sync::Mutex;
 use heimlig::{
     client::api::SymmetricAlgorithm::ChaCha20Poly1305,
     common::jobs::{RequestType, Response},
     crypto,
-    hsm::workers::chachapoly_worker::ChaChaPolyWorker,
+    hsm::workers::{chachapoly_worker::ChaChaPolyWorker, QueuedWorker},
 };
 
 #[async_std::test]
@@ -16,7 +17,7 @@ async fn chachapoly_encrypt_in_place() {
     let nonce = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
     let aad = *b"When in doubt, go to the library.";
     let mut tag = [0u8; crypto::chacha20poly1305::TAG_SIZE];
-    let mut tag_external_key = tag.clone();
+    let mut tag_external_key = tag;
     let mut plaintext = *b"I solemnly swear I am up to no good!";
     let mut plaintext_external_key = plaintext;
     let org_plaintext = plaintext;
@@ -38,10 +39,13 @@ async fn chachapoly_encrypt_in_place() {
         &mut worker_responses,
         Some(&key_store),
     );
-    let mut worker = ChaChaPolyWorker {
-        key_store: &key_store,
+    let mut worker = QueuedWorker {
+        worker: ChaChaPolyWorker {
+            key_store: &key_store,
+        },
         requests: req_worker_rx,
         responses: resp_worker_tx,
+        cancellations: None,
     };
 
     import_symmetric_key(&mut api, &mut core, SYM_256_KEY.id, &key).await;
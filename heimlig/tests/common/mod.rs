@@ -1,9 +1,10 @@
-use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use heimlig::common::sync::Mutex;
 use heimlig::{
     client::api::Api,
     common::jobs::{Request, RequestType, Response},
     hsm::{
-        core::{self, Builder},
+        core::{self, Builder, ClientConfig},
         keystore::{Curve, KeyId, KeyInfo, KeyPermissions, KeyType},
     },
     integration::{
@@ -94,9 +95,9 @@ type Core<'data, 'keystore, 'ch> = core::Core<
     NoopRawMutex,
     RequestQueueSource<'ch, 'data, QUEUE_SIZE>,
     ResponseQueueSink<'ch, 'data, QUEUE_SIZE>,
+    MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
     RequestQueueSink<'ch, 'data, QUEUE_SIZE>,
     ResponseQueueSource<'ch, 'data, QUEUE_SIZE>,
-    MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
 >;
 
 pub async fn get_response_from_core<'data>(
@@ -203,9 +204,9 @@ pub fn init_core<'data, 'ch, 'keystore>(
         NoopRawMutex,
         RequestQueueSource<'_, '_, QUEUE_SIZE>,
         ResponseQueueSink<'_, '_, QUEUE_SIZE>,
+        MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
         RequestQueueSink<'_, '_, QUEUE_SIZE>,
         ResponseQueueSource<'_, '_, QUEUE_SIZE>,
-        MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
     >::default();
 
     let core_builder = if let Some(key_store) = key_store {
@@ -215,7 +216,7 @@ pub fn init_core<'data, 'ch, 'keystore>(
     };
 
     let core = core_builder
-        .with_client(req_client_rx, resp_client_tx)
+        .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
         .expect("failed to add client")
         .with_worker(request_types, req_worker_tx, resp_worker_rx)
         .expect("failed to add worker")
@@ -2,12 +2,16 @@
 mod common;
 
 pub use common::*;
-use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use heimlig::common::sync::Mutex;
 use heimlig::{
-    common::jobs::{RequestType, Response},
-    hsm::workers::ecc_worker::EccWorker,
+    common::jobs::{Error, HashAlgorithm, PublicKeyFormat, RequestType, Response, SignatureFormat},
+    crypto,
+    crypto::ecdsa::NIST_P256_DER_SIGNATURE_MAX_SIZE,
+    hsm::workers::{ecc_worker::EccWorker, QueuedWorker},
 };
-use sha2::{Digest, Sha256};
+use p256::ecdsa::{signature::hazmat::PrehashVerifier, Signature, VerifyingKey};
+use sha2::{Digest, Sha256, Sha512};
 
 #[async_std::test]
 async fn sign_verify_nist_p256() {
@@ -37,11 +41,14 @@ async fn sign_verify_nist_p256() {
         Some(&key_store),
     );
     let rng = init_rng();
-    let mut worker = EccWorker {
-        rng: &rng,
-        key_store: &key_store,
+    let mut worker = QueuedWorker {
+        worker: EccWorker {
+            rng: &rng,
+            key_store: &key_store,
+        },
         requests: req_worker_rx,
         responses: resp_worker_tx,
+        cancellations: None,
     };
 
     // Generate key
@@ -62,7 +69,11 @@ async fn sign_verify_nist_p256() {
 
     // Export public key
     let org_request_id = api
-        .export_public_key(ASYM_NIST_P256_KEY.id, &mut large_public_key_buffer)
+        .export_public_key(
+            ASYM_NIST_P256_KEY.id,
+            &mut large_public_key_buffer,
+            PublicKeyFormat::Raw,
+        )
         .await
         .expect("failed to send request");
     let Response::ExportPublicKey {
@@ -94,7 +105,14 @@ async fn sign_verify_nist_p256() {
 
     // Sign message with generated key
     let org_request_id = api
-        .sign(ASYM_NIST_P256_KEY.id, message, false, &mut signature)
+        .sign(
+            ASYM_NIST_P256_KEY.id,
+            message,
+            false,
+            None,
+            SignatureFormat::Raw,
+            &mut signature,
+        )
         .await
         .expect("failed to send request");
     let Response::Sign {
@@ -109,7 +127,14 @@ async fn sign_verify_nist_p256() {
 
     // Verify message with generated key
     let org_request_id = api
-        .verify(ASYM_NIST_P256_KEY.id, message, false, signature)
+        .verify(
+            ASYM_NIST_P256_KEY.id,
+            message,
+            false,
+            None,
+            SignatureFormat::Raw,
+            signature,
+        )
         .await
         .expect("failed to send request");
     let Response::Verify {
@@ -129,6 +154,8 @@ async fn sign_verify_nist_p256() {
             private_key,
             digest.as_slice(),
             true,
+            None,
+            SignatureFormat::Raw,
             &mut signature_external_key,
         )
         .await
@@ -147,7 +174,14 @@ async fn sign_verify_nist_p256() {
 
     // Verify digest with external key
     let org_request_id = api
-        .verify_external_key(public_key, digest.as_slice(), true, signature_external_key)
+        .verify_external_key(
+            public_key,
+            digest.as_slice(),
+            true,
+            None,
+            SignatureFormat::Raw,
+            signature_external_key,
+        )
         .await
         .expect("failed to send request");
     let Response::Verify {
@@ -161,3 +195,146 @@ async fn sign_verify_nist_p256() {
     assert_eq!(request_id, org_request_id);
     assert!(verified);
 }
+
+#[async_std::test]
+async fn sign_verify_der_with_selected_hash() {
+    let mut public_key = [0u8; 1 + ASYM_NIST_P256_KEY.ty.public_key_size()];
+    let mut signature = [0u8; NIST_P256_DER_SIGNATURE_MAX_SIZE];
+    let mut too_small_signature = [0u8; ASYM_NIST_P256_KEY.ty.signature_size()];
+    let message: &[u8] = b"I find your lack of faith disturbing.";
+    let digest = Sha512::digest(message);
+
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let mut key_store = init_key_store(&KEY_INFOS);
+    let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
+    let (mut api, mut core, req_worker_rx, resp_worker_tx) = init_core(
+        &[
+            RequestType::GenerateKeyPair,
+            RequestType::Sign,
+            RequestType::Verify,
+            RequestType::SignExternalKey,
+            RequestType::VerifyExternalKey,
+        ],
+        &mut client_requests,
+        &mut client_responses,
+        &mut worker_requests,
+        &mut worker_responses,
+        Some(&key_store),
+    );
+    let rng = init_rng();
+    let mut worker = QueuedWorker {
+        worker: EccWorker {
+            rng: &rng,
+            key_store: &key_store,
+        },
+        requests: req_worker_rx,
+        responses: resp_worker_tx,
+        cancellations: None,
+    };
+
+    api.generate_key_pair(ASYM_NIST_P256_KEY.id, false)
+        .await
+        .expect("failed to send request");
+    let Response::GenerateKeyPair { .. } = get_response_from_worker!(api, core, worker) else {
+        panic!("Unexpected response type")
+    };
+    api.export_public_key(
+        ASYM_NIST_P256_KEY.id,
+        &mut public_key,
+        PublicKeyFormat::Sec1Uncompressed,
+    )
+    .await
+    .expect("failed to send request");
+    let Response::ExportPublicKey { public_key, .. } =
+        get_response_from_core(&mut api, &mut core).await
+    else {
+        panic!("Unexpected response type")
+    };
+
+    // Sign message hashed with SHA-512 and DER encode the signature
+    api.sign(
+        ASYM_NIST_P256_KEY.id,
+        message,
+        false,
+        Some(HashAlgorithm::Sha2_512),
+        SignatureFormat::Der,
+        &mut signature,
+    )
+    .await
+    .expect("failed to send request");
+    let Response::Sign { signature, .. } = get_response_from_worker!(api, core, worker) else {
+        panic!("Unexpected response type")
+    };
+
+    // Signature can be verified by third parties
+    let verifying_key = VerifyingKey::from_sec1_bytes(public_key).expect("invalid public key");
+    let der_signature = Signature::from_der(signature).expect("invalid DER signature");
+    verifying_key
+        .verify_prehash(&digest, &der_signature)
+        .expect("signature verification failed");
+
+    // Verify prehashed digest with the same settings
+    api.verify(
+        ASYM_NIST_P256_KEY.id,
+        digest.as_slice(),
+        true,
+        Some(HashAlgorithm::Sha2_512),
+        SignatureFormat::Der,
+        signature,
+    )
+    .await
+    .expect("failed to send request");
+    let Response::Verify { verified, .. } = get_response_from_worker!(api, core, worker) else {
+        panic!("Unexpected response type")
+    };
+    assert!(verified);
+
+    // Default hash of the curve does not match the signed digest
+    api.verify(
+        ASYM_NIST_P256_KEY.id,
+        message,
+        false,
+        None,
+        SignatureFormat::Der,
+        signature,
+    )
+    .await
+    .expect("failed to send request");
+    let Response::Verify { verified, .. } = get_response_from_worker!(api, core, worker) else {
+        panic!("Unexpected response type")
+    };
+    assert!(!verified);
+
+    // Prehashed digest has to match the size of the selected hash
+    api.verify(
+        ASYM_NIST_P256_KEY.id,
+        &digest[..32],
+        true,
+        Some(HashAlgorithm::Sha2_512),
+        SignatureFormat::Der,
+        signature,
+    )
+    .await
+    .expect("failed to send request");
+    let Response::Error { error, .. } = get_response_from_worker!(api, core, worker) else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(error, Error::Crypto(crypto::Error::InvalidDigestSize));
+
+    // Raw sized buffer is too small for the DER encoding
+    api.sign(
+        ASYM_NIST_P256_KEY.id,
+        message,
+        false,
+        Some(HashAlgorithm::Sha3_256),
+        SignatureFormat::Der,
+        &mut too_small_signature,
+    )
+    .await
+    .expect("failed to send request");
+    let Response::Error { error, .. } = get_response_from_worker!(api, core, worker) else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(error, Error::Crypto(crypto::Error::InvalidSignatureSize));
+}
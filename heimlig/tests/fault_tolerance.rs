@@ -0,0 +1,325 @@
+#[allow(dead_code, unused_macros)]
+mod common;
+
+use common::*;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use futures::{FutureExt, Sink, SinkExt, Stream};
+use heimlig::{
+    client::api::Api,
+    common::jobs::{ClientId, Error, RequestType, Response},
+    hsm::{
+        core::{Builder, ClientConfig, WorkerId},
+        workers::{rng_worker::RngWorker, QueuedWorker},
+    },
+    integration::{
+        embassy::{RequestQueueSink, RequestQueueSource, ResponseQueueSink, ResponseQueueSource},
+        memory_key_store::MemoryKeyStore,
+    },
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Channel end that fails once its flag is set
+struct Breakable<'a, T> {
+    inner: T,
+    broken: &'a AtomicBool,
+}
+
+impl<'a, T> Breakable<'a, T> {
+    fn new(inner: T, broken: &'a AtomicBool) -> Self {
+        Breakable { inner, broken }
+    }
+
+    fn is_broken(&self) -> bool {
+        self.broken.load(Ordering::Relaxed)
+    }
+}
+
+impl<T: Stream + Unpin> Stream for Breakable<'_, T> {
+    type Item = T::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.is_broken() {
+            return Poll::Ready(None);
+        }
+        Pin::new(&mut self.inner).poll_next(cx)
+    }
+}
+
+impl<I, T: Sink<I> + Unpin> Sink<I> for Breakable<'_, T> {
+    type Error = ();
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ()>> {
+        if self.is_broken() {
+            return Poll::Ready(Err(()));
+        }
+        Pin::new(&mut self.inner).poll_ready(cx).map_err(|_| ())
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: I) -> Result<(), ()> {
+        Pin::new(&mut self.inner).start_send(item).map_err(|_| ())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ()>> {
+        Pin::new(&mut self.inner).poll_flush(cx).map_err(|_| ())
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ()>> {
+        Pin::new(&mut self.inner).poll_close(cx).map_err(|_| ())
+    }
+}
+
+type TestBuilder<'data, 'ch, 'flag> = Builder<
+    'data,
+    'static,
+    NoopRawMutex,
+    Breakable<'flag, RequestQueueSource<'ch, 'data, QUEUE_SIZE>>,
+    Breakable<'flag, ResponseQueueSink<'ch, 'data, QUEUE_SIZE>>,
+    MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
+    Breakable<'flag, RequestQueueSink<'ch, 'data, QUEUE_SIZE>>,
+    Breakable<'flag, ResponseQueueSource<'ch, 'data, QUEUE_SIZE>>,
+>;
+
+fn expect_error(response: Response, expected: Error) {
+    let Response::Error { error, .. } = response else {
+        panic!("Unexpected response type {:?}", response)
+    };
+    assert_eq!(error, expected);
+}
+
+#[async_std::test]
+async fn dead_worker_is_detached_and_reattached() {
+    let mut output1 = [0u8; 16];
+    let mut output2 = [0u8; 16];
+    let mut output3 = [0u8; 16];
+    let mut output4 = [0u8; 16];
+    let intact = AtomicBool::new(false);
+    let worker_broken = AtomicBool::new(false);
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let (mut new_worker_requests, mut new_worker_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let (_rng_requests_rx, rng_requests_tx, rng_responses_rx, _rng_responses_tx) =
+        split_queues(&mut worker_requests, &mut worker_responses);
+    let (new_rng_requests_rx, new_rng_requests_tx, new_rng_responses_rx, new_rng_responses_tx) =
+        split_queues(&mut new_worker_requests, &mut new_worker_responses);
+    let rng = init_rng();
+    let mut core = TestBuilder::default()
+        .with_client(
+            Breakable::new(req_client_rx, &intact),
+            Breakable::new(resp_client_tx, &intact),
+            ClientConfig::default(),
+        )
+        .expect("failed to add client")
+        .with_worker(
+            &[RequestType::GetRandom],
+            Breakable::new(rng_requests_tx, &worker_broken),
+            Breakable::new(rng_responses_rx, &worker_broken),
+        )
+        .expect("failed to add worker")
+        .build();
+    let mut api = Api::new(req_client_tx, resp_client_rx);
+
+    // Both requests are pending at the worker when it dies
+    let request1_id = api
+        .get_random(&mut output1)
+        .await
+        .expect("failed to send request");
+    let request2_id = api
+        .get_random(&mut output2)
+        .await
+        .expect("failed to send request");
+    core.execute().await.expect("failed to forward request");
+    core.execute().await.expect("failed to forward request");
+    worker_broken.store(true, Ordering::Relaxed);
+    core.execute().await.expect("failed to detach worker");
+    assert!(!core.is_worker_available(WorkerId(0)));
+    for request_id in [request1_id, request2_id] {
+        let response = api.recv_response().await.expect("no response");
+        assert_eq!(response.get_request_id(), request_id);
+        expect_error(response, Error::WorkerUnavailable);
+    }
+
+    // New requests are rejected while the worker is detached
+    api.get_random(&mut output3)
+        .await
+        .expect("failed to send request");
+    core.execute().await.expect("failed to process request");
+    let response = api.recv_response().await.expect("no response");
+    expect_error(response, Error::WorkerUnavailable);
+
+    // Requests are processed again after attaching a restarted worker
+    core.attach_worker(
+        WorkerId(0),
+        Breakable::new(new_rng_requests_tx, &intact),
+        Breakable::new(new_rng_responses_rx, &intact),
+    )
+    .await
+    .expect("failed to attach worker");
+    assert!(core.is_worker_available(WorkerId(0)));
+    let mut rng_worker = QueuedWorker {
+        worker: RngWorker::<NoopRawMutex, _, MemoryKeyStore<1, 1>> {
+            rng: &rng,
+            key_store: None,
+        },
+        requests: new_rng_requests_rx,
+        responses: new_rng_responses_tx,
+        cancellations: None,
+    };
+    let request4_id = api
+        .get_random(&mut output4)
+        .await
+        .expect("failed to send request");
+    core.execute().await.expect("failed to forward request");
+    rng_worker
+        .execute()
+        .await
+        .expect("failed to process request");
+    core.execute().await.expect("failed to forward response");
+    let response = api.recv_response().await.expect("no response");
+    assert!(matches!(response, Response::GetRandom { .. }));
+    assert_eq!(response.get_request_id(), request4_id);
+    assert_eq!(
+        core.client_stats(ClientId(0))
+            .expect("invalid client ID")
+            .in_flight,
+        0
+    );
+}
+
+#[async_std::test]
+async fn broken_client_is_disconnected() {
+    let mut output1 = [0u8; 16];
+    let mut output2 = [0u8; 16];
+    let client1_broken = AtomicBool::new(false);
+    let intact = AtomicBool::new(false);
+    let (mut client1_requests, mut client1_responses) = allocate_channel();
+    let (mut client2_requests, mut client2_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let (req_client1_rx, req_client1_tx, resp_client1_rx, resp_client1_tx) =
+        split_queues(&mut client1_requests, &mut client1_responses);
+    let (req_client2_rx, req_client2_tx, resp_client2_rx, resp_client2_tx) =
+        split_queues(&mut client2_requests, &mut client2_responses);
+    let (rng_requests_rx, rng_requests_tx, rng_responses_rx, rng_responses_tx) =
+        split_queues(&mut worker_requests, &mut worker_responses);
+    let rng = init_rng();
+    let mut rng_worker = QueuedWorker {
+        worker: RngWorker::<NoopRawMutex, _, MemoryKeyStore<1, 1>> {
+            rng: &rng,
+            key_store: None,
+        },
+        requests: rng_requests_rx,
+        responses: rng_responses_tx,
+        cancellations: None,
+    };
+    let mut core = TestBuilder::default()
+        .with_client(
+            Breakable::new(req_client1_rx, &client1_broken),
+            Breakable::new(resp_client1_tx, &client1_broken),
+            ClientConfig::default(),
+        )
+        .expect("failed to add client")
+        .with_client(
+            Breakable::new(req_client2_rx, &intact),
+            Breakable::new(resp_client2_tx, &intact),
+            ClientConfig::default(),
+        )
+        .expect("failed to add client")
+        .with_worker(
+            &[RequestType::GetRandom],
+            Breakable::new(rng_requests_tx, &intact),
+            Breakable::new(rng_responses_rx, &intact),
+        )
+        .expect("failed to add worker")
+        .build();
+    let mut api1 = Api::new(req_client1_tx, resp_client1_rx);
+    let mut api2 = Api::new(req_client2_tx, resp_client2_rx);
+
+    // Client 1 breaks while its request is processed by the worker
+    api1.get_random(&mut output1)
+        .await
+        .expect("failed to send request");
+    core.execute().await.expect("failed to forward request");
+    rng_worker
+        .execute()
+        .await
+        .expect("failed to process request");
+    client1_broken.store(true, Ordering::Relaxed);
+    core.execute().await.expect("failed to disconnect client");
+    assert!(!core.is_client_connected(ClientId(0)));
+
+    // The response for the disconnected client is discarded
+    core.execute().await.expect("failed to discard response");
+    assert!(api1.recv_response().now_or_never().is_none());
+
+    // Client 2 is still served
+    let request_id = api2
+        .get_random(&mut output2)
+        .await
+        .expect("failed to send request");
+    core.execute().await.expect("failed to forward request");
+    rng_worker
+        .execute()
+        .await
+        .expect("failed to process request");
+    core.execute().await.expect("failed to forward response");
+    let response = api2.recv_response().await.expect("no response");
+    assert!(matches!(response, Response::GetRandom { .. }));
+    assert_eq!(response.get_request_id(), request_id);
+    assert!(core.is_client_connected(ClientId(1)));
+}
+
+#[async_std::test]
+async fn worker_answering_unknown_client_is_detached() {
+    let mut output = [0u8; 16];
+    let intact = AtomicBool::new(false);
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let (_rng_requests_rx, rng_requests_tx, rng_responses_rx, mut rng_responses_tx) =
+        split_queues(&mut worker_requests, &mut worker_responses);
+    let mut core = TestBuilder::default()
+        .with_client(
+            Breakable::new(req_client_rx, &intact),
+            Breakable::new(resp_client_tx, &intact),
+            ClientConfig::default(),
+        )
+        .expect("failed to add client")
+        .with_worker(
+            &[RequestType::GetRandom],
+            Breakable::new(rng_requests_tx, &intact),
+            Breakable::new(rng_responses_rx, &intact),
+        )
+        .expect("failed to add worker")
+        .build();
+    let mut api = Api::new(req_client_tx, resp_client_rx);
+
+    let request_id = api
+        .get_random(&mut output)
+        .await
+        .expect("failed to send request");
+    core.execute().await.expect("failed to forward request");
+
+    // The worker answers a client that does not exist
+    rng_responses_tx
+        .send(Response::GenerateSymmetricKey {
+            client_id: ClientId(5),
+            request_id,
+        })
+        .await
+        .expect("failed to send response");
+    core.execute().await.expect("failed to detach worker");
+    assert!(!core.is_worker_available(WorkerId(0)));
+    let response = api.recv_response().await.expect("no response");
+    assert_eq!(response.get_request_id(), request_id);
+    expect_error(response, Error::WorkerUnavailable);
+    assert_eq!(
+        core.client_stats(ClientId(0))
+            .expect("invalid client ID")
+            .in_flight,
+        0
+    );
+}
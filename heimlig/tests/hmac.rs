@@ -2,11 +2,12 @@
 mod common;
 
 pub use common::*;
-use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use heimlig::common::sync::Mutex;
 use heimlig::{
     common::jobs::{HashAlgorithm, RequestType, Response},
     crypto,
-    hsm::workers::hmac_worker::HmacWorker,
+    hsm::workers::{hmac_worker::HmacWorker, QueuedWorker},
 };
 
 #[async_std::test]
@@ -34,17 +35,20 @@ async fn calculate_verify_hmac_sha3_512() {
         &mut worker_responses,
         Some(&key_store),
     );
-    let mut worker = HmacWorker {
-        key_store: &key_store,
+    let mut worker = QueuedWorker {
+        worker: HmacWorker {
+            key_store: &key_store,
+        },
         requests: req_worker_rx,
         responses: resp_worker_tx,
+        cancellations: None,
     };
 
     import_symmetric_key(&mut api, &mut core, SYM_256_KEY.id, &key).await;
 
     // Calculate HMAC tag with imported key
     let org_request_id = api
-        .calculate_hmac(SYM_256_KEY.id, hash_algorithm, &message, &mut tag)
+        .calculate_hmac(SYM_256_KEY.id, hash_algorithm, message, &mut tag)
         .await
         .expect("failed to send request");
     let Response::CalculateHmac {
@@ -59,7 +63,7 @@ async fn calculate_verify_hmac_sha3_512() {
 
     // Calculate HMAC tag with external key
     let org_request_id = api
-        .calculate_hmac_external_key(&key, hash_algorithm, &message, &mut tag_external_key)
+        .calculate_hmac_external_key(&key, hash_algorithm, message, &mut tag_external_key)
         .await
         .expect("failed to send request");
     let Response::CalculateHmac {
@@ -76,7 +80,7 @@ async fn calculate_verify_hmac_sha3_512() {
 
     // Verify HMAC tag with imported key
     let org_request_id = api
-        .verify_hmac(SYM_256_KEY.id, hash_algorithm, &message, tag)
+        .verify_hmac(SYM_256_KEY.id, hash_algorithm, message, tag)
         .await
         .expect("failed to send request");
     let Response::VerifyHmac {
@@ -92,7 +96,7 @@ async fn calculate_verify_hmac_sha3_512() {
 
     // Verify HMAC tag with external key
     let org_request_id = api
-        .verify_hmac_external_key(&key, hash_algorithm, &message, tag_external_key)
+        .verify_hmac_external_key(&key, hash_algorithm, message, tag_external_key)
         .await
         .expect("failed to send request");
     let Response::VerifyHmac {
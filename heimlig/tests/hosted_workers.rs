@@ -0,0 +1,219 @@
+#[allow(dead_code, unused_macros)]
+#[macro_use]
+mod common;
+
+use common::*;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use heimlig::common::sync::Mutex;
+use heimlig::{
+    client::api::Api,
+    common::jobs::{self, ClientId, HashAlgorithm, Request, RequestType, Response},
+    crypto,
+    hsm::{
+        core::{self, Builder, ClientConfig},
+        workers::{hmac_worker::HmacWorker, rng_worker::RngWorker, QueuedWorker, Worker},
+    },
+    integration::{
+        embassy::{RequestQueueSink, RequestQueueSource, ResponseQueueSink, ResponseQueueSource},
+        memory_key_store::MemoryKeyStore,
+    },
+};
+
+type TestBuilder<'data, 'ch> = Builder<
+    'data,
+    'static,
+    NoopRawMutex,
+    RequestQueueSource<'ch, 'data, QUEUE_SIZE>,
+    ResponseQueueSink<'ch, 'data, QUEUE_SIZE>,
+    MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
+    RequestQueueSink<'ch, 'data, QUEUE_SIZE>,
+    ResponseQueueSource<'ch, 'data, QUEUE_SIZE>,
+>;
+
+/// Worker that claims a request type handled by the core
+struct KeyAvailabilityWorker;
+
+impl<'data> Worker<'data> for KeyAvailabilityWorker {
+    fn request_types(&self) -> &[RequestType] {
+        &[RequestType::IsKeyAvailable]
+    }
+
+    async fn handle(&mut self, _request: Request<'data>) -> Result<Response<'data>, jobs::Error> {
+        Err(jobs::Error::UnexpectedRequestType)
+    }
+}
+
+/// Worker that answers a request of another client
+struct MisaddressingWorker;
+
+impl<'data> Worker<'data> for MisaddressingWorker {
+    fn request_types(&self) -> &[RequestType] {
+        &[RequestType::GenerateSymmetricKey]
+    }
+
+    async fn handle(&mut self, request: Request<'data>) -> Result<Response<'data>, jobs::Error> {
+        Ok(Response::GenerateSymmetricKey {
+            client_id: ClientId(request.get_client_id().0 + 1),
+            request_id: request.get_request_id(),
+        })
+    }
+}
+
+#[async_std::test]
+async fn hosted_worker_processes_requests() {
+    let mut output = [0u8; 16];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let rng = init_rng();
+    let rng_worker = RngWorker::<NoopRawMutex, _, MemoryKeyStore<1, 1>> {
+        rng: &rng,
+        key_store: None,
+    };
+    let mut core = TestBuilder::default()
+        .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+        .expect("failed to add client")
+        .with_hosted_worker(rng_worker)
+        .expect("failed to add hosted worker")
+        .build();
+    let mut api = Api::new(req_client_tx, resp_client_rx);
+
+    let org_request_id = api
+        .get_random(&mut output)
+        .await
+        .expect("failed to send request");
+    core.execute().await.expect("failed to process request");
+    let Some(Response::GetRandom {
+        client_id: _,
+        request_id,
+        data,
+    }) = api.recv_response().await
+    else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(request_id, org_request_id);
+    assert_eq!(data.len(), 16);
+
+    // Errors of the hosted worker are sent to the client
+    let org_request_id = api
+        .generate_symmetric_key(SYM_128_KEY.id, false)
+        .await
+        .expect("failed to send request");
+    core.execute().await.expect("failed to process request");
+    let Some(Response::Error {
+        client_id: _,
+        request_id,
+        error,
+    }) = api.recv_response().await
+    else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(request_id, org_request_id);
+    assert_eq!(error, jobs::Error::NoKeyStore);
+}
+
+#[async_std::test]
+async fn hosted_and_queue_workers() {
+    let key: [u8; crypto::aes::KEY256_SIZE] = *b"Guardian of the Third Age Istar.";
+    let message: &[u8] = b"You Shall Not Pass!";
+    let mut tag = [0u8; crypto::hmac::HMAC_SHA2_256_SIZE];
+    let mut output = [0u8; 16];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let (req_worker_rx, req_worker_tx, resp_worker_rx, resp_worker_tx) =
+        split_queues(&mut worker_requests, &mut worker_responses);
+    let mut key_store = init_key_store(&KEY_INFOS);
+    let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
+    let rng = init_rng();
+    let rng_worker = RngWorker::<NoopRawMutex, _, MemoryKeyStore<1, 1>> {
+        rng: &rng,
+        key_store: None,
+    };
+    let mut hmac_worker = QueuedWorker {
+        worker: HmacWorker {
+            key_store: &key_store,
+        },
+        requests: req_worker_rx,
+        responses: resp_worker_tx,
+        cancellations: None,
+    };
+    let mut core = TestBuilder::default()
+        .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+        .expect("failed to add client")
+        .with_worker(
+            &[RequestType::CalculateHmacExternalKey],
+            req_worker_tx,
+            resp_worker_rx,
+        )
+        .expect("failed to add worker")
+        .with_hosted_worker(rng_worker)
+        .expect("failed to add hosted worker")
+        .build();
+    let mut api = Api::new(req_client_tx, resp_client_rx);
+
+    let org_request_id = api
+        .calculate_hmac_external_key(&key, HashAlgorithm::Sha2_256, message, &mut tag)
+        .await
+        .expect("failed to send request");
+    let Response::CalculateHmac {
+        client_id: _,
+        request_id,
+        tag,
+    } = get_response_from_worker!(api, core, hmac_worker)
+    else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(request_id, org_request_id);
+    assert_eq!(tag.len(), crypto::hmac::HMAC_SHA2_256_SIZE);
+
+    let org_request_id = api
+        .get_random(&mut output)
+        .await
+        .expect("failed to send request");
+    core.execute().await.expect("failed to process request");
+    let Some(Response::GetRandom { request_id, .. }) = api.recv_response().await else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(request_id, org_request_id);
+}
+
+#[async_std::test]
+async fn misaddressed_hosted_response_is_replaced() {
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let mut core = TestBuilder::default()
+        .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+        .expect("failed to add client")
+        .with_hosted_worker(MisaddressingWorker)
+        .expect("failed to add hosted worker")
+        .build();
+    let mut api = Api::new(req_client_tx, resp_client_rx);
+
+    let org_request_id = api
+        .generate_symmetric_key(SYM_128_KEY.id, false)
+        .await
+        .expect("failed to send request");
+    core.execute().await.expect("failed to process request");
+    let Some(Response::Error {
+        client_id,
+        request_id,
+        error,
+    }) = api.recv_response().await
+    else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(client_id, ClientId(0));
+    assert_eq!(request_id, org_request_id);
+    assert_eq!(error, jobs::Error::WorkerUnavailable);
+}
+
+#[test]
+fn hosted_worker_for_core_request_type() {
+    assert!(matches!(
+        TestBuilder::default().with_hosted_worker(KeyAvailabilityWorker),
+        Err(core::Error::InvalidRequestType)
+    ));
+}
@@ -0,0 +1,288 @@
+#[allow(dead_code)]
+#[macro_use]
+mod common;
+
+use common::*;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use futures::FutureExt;
+use heimlig::{
+    client::api::Api,
+    common::jobs::{ClientId, Error, Limit, RequestType, Response},
+    hsm::{
+        core::{self, Builder, ClientConfig, ClientLimits, ClientStats, RateLimit},
+        workers::{rng_worker::RngWorker, QueuedWorker},
+    },
+    integration::{
+        embassy::{RequestQueueSink, RequestQueueSource, ResponseQueueSink, ResponseQueueSource},
+        memory_key_store::MemoryKeyStore,
+    },
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+type TestBuilder<'data, 'ch> = Builder<
+    'data,
+    'static,
+    NoopRawMutex,
+    RequestQueueSource<'ch, 'data, QUEUE_SIZE>,
+    ResponseQueueSink<'ch, 'data, QUEUE_SIZE>,
+    MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
+    RequestQueueSink<'ch, 'data, QUEUE_SIZE>,
+    ResponseQueueSource<'ch, 'data, QUEUE_SIZE>,
+>;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+fn now() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+fn expect_limit_exceeded(response: Response, limit: Limit) {
+    let Response::Error { error, .. } = response else {
+        panic!("Unexpected response type {:?}", response)
+    };
+    assert_eq!(error, Error::LimitExceeded(limit));
+}
+
+#[async_std::test]
+async fn request_size_limit() {
+    let mut small = [0u8; 16];
+    let mut large = [0u8; 17];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let (rng_requests_rx, rng_requests_tx, rng_responses_rx, rng_responses_tx) =
+        split_queues(&mut worker_requests, &mut worker_responses);
+    let rng = init_rng();
+    let mut rng_worker = QueuedWorker {
+        worker: RngWorker::<NoopRawMutex, _, MemoryKeyStore<1, 1>> {
+            rng: &rng,
+            key_store: None,
+        },
+        requests: rng_requests_rx,
+        responses: rng_responses_tx,
+        cancellations: None,
+    };
+    let limits = ClientLimits {
+        max_request_size: Some(small.len()),
+        ..Default::default()
+    };
+    let mut core = TestBuilder::default()
+        .with_client(
+            req_client_rx,
+            resp_client_tx,
+            ClientConfig::default().with_limits(limits),
+        )
+        .expect("failed to add client")
+        .with_worker(&[RequestType::GetRandom], rng_requests_tx, rng_responses_rx)
+        .expect("failed to add worker")
+        .build();
+    let mut api = Api::new(req_client_tx, resp_client_rx);
+
+    api.get_random(&mut large)
+        .await
+        .expect("failed to send request");
+    expect_limit_exceeded(
+        get_response_from_core(&mut api, &mut core).await,
+        Limit::RequestSize,
+    );
+    // Rejected requests are not forwarded to the worker
+    assert!(rng_worker.execute().now_or_never().is_none());
+
+    api.get_random(&mut small)
+        .await
+        .expect("failed to send request");
+    let response = get_response_from_worker!(api, core, rng_worker);
+    assert!(matches!(response, Response::GetRandom { .. }));
+
+    let stats = core.client_stats(ClientId(0)).expect("invalid client ID");
+    assert_eq!(
+        stats,
+        ClientStats {
+            accepted: 1,
+            in_flight: 0,
+            rejected_size: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[async_std::test]
+async fn in_flight_limit() {
+    let mut output1 = [0u8; 16];
+    let mut output2 = [0u8; 16];
+    let mut output3 = [0u8; 16];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let (rng_requests_rx, rng_requests_tx, rng_responses_rx, rng_responses_tx) =
+        split_queues(&mut worker_requests, &mut worker_responses);
+    let rng = init_rng();
+    let mut rng_worker = QueuedWorker {
+        worker: RngWorker::<NoopRawMutex, _, MemoryKeyStore<1, 1>> {
+            rng: &rng,
+            key_store: None,
+        },
+        requests: rng_requests_rx,
+        responses: rng_responses_tx,
+        cancellations: None,
+    };
+    let limits = ClientLimits {
+        max_in_flight: Some(1),
+        ..Default::default()
+    };
+    let mut core = TestBuilder::default()
+        .with_client(
+            req_client_rx,
+            resp_client_tx,
+            ClientConfig::default().with_limits(limits),
+        )
+        .expect("failed to add client")
+        .with_worker(&[RequestType::GetRandom], rng_requests_tx, rng_responses_rx)
+        .expect("failed to add worker")
+        .build();
+    let mut api = Api::new(req_client_tx, resp_client_rx);
+
+    let request1_id = api
+        .get_random(&mut output1)
+        .await
+        .expect("failed to send request");
+    let request2_id = api
+        .get_random(&mut output2)
+        .await
+        .expect("failed to send request");
+    core.execute().await.expect("failed to forward request");
+    assert_eq!(
+        core.client_stats(ClientId(0))
+            .expect("invalid client ID")
+            .in_flight,
+        1
+    );
+
+    // Second request is rejected while the first one is processed
+    let response = get_response_from_core(&mut api, &mut core).await;
+    assert_eq!(response.get_request_id(), request2_id);
+    expect_limit_exceeded(response, Limit::InFlight);
+
+    rng_worker
+        .execute()
+        .await
+        .expect("failed to process request");
+    core.execute().await.expect("failed to forward response");
+    let response = api.recv_response().await.expect("no response");
+    assert_eq!(response.get_request_id(), request1_id);
+
+    // Once the response was received, new requests are accepted again
+    api.get_random(&mut output3)
+        .await
+        .expect("failed to send request");
+    let response = get_response_from_worker!(api, core, rng_worker);
+    assert!(matches!(response, Response::GetRandom { .. }));
+
+    let stats = core.client_stats(ClientId(0)).expect("invalid client ID");
+    assert_eq!(
+        stats,
+        ClientStats {
+            accepted: 2,
+            in_flight: 0,
+            rejected_in_flight: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[async_std::test]
+async fn rate_limit() {
+    const BURST: u32 = 2;
+    const PERIOD: u64 = 10;
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let mut rates = heapless::Vec::new();
+    rates
+        .push(RateLimit {
+            request_type: RequestType::IsKeyAvailable,
+            burst: BURST,
+            period: PERIOD,
+        })
+        .expect("too many rate limits");
+    let limits = ClientLimits {
+        rates,
+        ..Default::default()
+    };
+    let mut core = TestBuilder::default()
+        .with_clock(now)
+        .with_client(
+            req_client_rx,
+            resp_client_tx,
+            ClientConfig::default().with_limits(limits),
+        )
+        .expect("failed to add client")
+        .build();
+    let mut api = Api::new(req_client_tx, resp_client_rx);
+
+    macro_rules! is_accepted {
+        () => {{
+            api.is_key_available(SYM_128_KEY.id)
+                .await
+                .expect("failed to send request");
+            !matches!(
+                get_response_from_core(&mut api, &mut core).await,
+                Response::Error {
+                    error: Error::LimitExceeded(Limit::Rate),
+                    ..
+                }
+            )
+        }};
+    }
+
+    // Burst is available right away
+    for _ in 0..BURST {
+        assert!(is_accepted!());
+    }
+    assert!(!is_accepted!());
+
+    // One token per period
+    TICKS.store(PERIOD - 1, Ordering::Relaxed);
+    assert!(!is_accepted!());
+    TICKS.store(PERIOD, Ordering::Relaxed);
+    assert!(is_accepted!());
+    assert!(!is_accepted!());
+
+    // Tokens do not accumulate beyond the burst size
+    TICKS.store(100 * PERIOD, Ordering::Relaxed);
+    for _ in 0..BURST {
+        assert!(is_accepted!());
+    }
+    assert!(!is_accepted!());
+
+    let stats = core.client_stats(ClientId(0)).expect("invalid client ID");
+    assert_eq!(stats.accepted, 2 * BURST + 1);
+    assert_eq!(stats.rejected_rate, 4);
+}
+
+#[test]
+fn zero_rate_limit_period_is_rejected() {
+    let (mut requests, mut responses) = allocate_channel();
+    let (req_client_rx, _req_client_tx, _resp_client_rx, resp_client_tx) =
+        split_queues(&mut requests, &mut responses);
+    let mut rates = heapless::Vec::new();
+    rates
+        .push(RateLimit {
+            request_type: RequestType::GetRandom,
+            burst: 1,
+            period: 0,
+        })
+        .expect("too many rate limits");
+    let limits = ClientLimits {
+        rates,
+        ..Default::default()
+    };
+    let result = TestBuilder::default().with_client(
+        req_client_rx,
+        resp_client_tx,
+        ClientConfig::default().with_limits(limits),
+    );
+    assert!(matches!(result, Err(core::Error::InvalidRateLimit)));
+}
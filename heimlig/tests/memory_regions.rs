@@ -0,0 +1,258 @@
+#[allow(dead_code, unused_macros)]
+#[macro_use]
+mod common;
+
+use common::*;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use heimlig::{
+    client::api::Api,
+    common::jobs::{self, ClientId, Request, RequestId, Response},
+    hsm::{
+        core::{Builder, ClientConfig},
+        memory::{Access, Error, MemoryRegion, MemoryRegions},
+        workers::rng_worker::RngWorker,
+    },
+    integration::{
+        embassy::{RequestQueueSource, ResponseQueueSink},
+        memory_key_store::MemoryKeyStore,
+        raw_jobs::{RequestRaw, ValidationError},
+    },
+};
+
+type TestBuilder<'data, 'ch> = Builder<
+    'data,
+    'static,
+    NoopRawMutex,
+    RequestQueueSource<'ch, 'data, QUEUE_SIZE>,
+    ResponseQueueSink<'ch, 'data, QUEUE_SIZE>,
+    MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
+>;
+
+const ALIGNMENT: usize = 32;
+
+#[repr(align(32))]
+struct SharedMemory([u8; 4 * ALIGNMENT]);
+
+/// Client 0 may write the first half of `memory` and read the third quarter. Client 1 may write
+/// the last quarter.
+fn regions(memory: &SharedMemory) -> [MemoryRegion; 3] {
+    let start = memory.0.as_ptr() as usize;
+    [
+        MemoryRegion {
+            client_id: ClientId(0),
+            start,
+            size: 2 * ALIGNMENT,
+            access: Access::ReadWrite,
+        },
+        MemoryRegion {
+            client_id: ClientId(0),
+            start: start + 2 * ALIGNMENT,
+            size: ALIGNMENT,
+            access: Access::ReadOnly,
+        },
+        MemoryRegion {
+            client_id: ClientId(1),
+            start: start + 3 * ALIGNMENT,
+            size: ALIGNMENT,
+            access: Access::ReadWrite,
+        },
+    ]
+}
+
+#[test]
+fn invalid_region_tables() {
+    let memory = SharedMemory([0; 4 * ALIGNMENT]);
+    let valid = regions(&memory);
+    assert!(MemoryRegions::new(&valid, ALIGNMENT).is_ok());
+    assert_eq!(
+        MemoryRegions::new(&valid, 3).unwrap_err(),
+        Error::InvalidAlignment
+    );
+    assert_eq!(
+        MemoryRegions::new(&valid, 2 * ALIGNMENT).unwrap_err(),
+        Error::UnalignedRegion
+    );
+
+    let mut empty = valid;
+    empty[1].size = 0;
+    assert_eq!(
+        MemoryRegions::new(&empty, ALIGNMENT).unwrap_err(),
+        Error::EmptyRegion
+    );
+
+    let mut overflow = valid;
+    overflow[2].start = usize::MAX - ALIGNMENT + 1;
+    overflow[2].size = 2 * ALIGNMENT;
+    assert_eq!(
+        MemoryRegions::new(&overflow, ALIGNMENT).unwrap_err(),
+        Error::RegionOverflow
+    );
+
+    // Regions of the same client may overlap, regions of different clients may not
+    let mut overlapping = valid;
+    overlapping[1].size = 2 * ALIGNMENT;
+    assert_eq!(
+        MemoryRegions::new(&overlapping, ALIGNMENT).unwrap_err(),
+        Error::OverlappingRegions
+    );
+    overlapping[1].client_id = ClientId(1);
+    overlapping[1].start = valid[0].start;
+    assert_eq!(
+        MemoryRegions::new(&overlapping, ALIGNMENT).unwrap_err(),
+        Error::OverlappingRegions
+    );
+    overlapping[1].client_id = ClientId(0);
+    assert!(MemoryRegions::new(&overlapping, ALIGNMENT).is_ok());
+}
+
+#[test]
+fn buffer_access() {
+    let memory = SharedMemory([0; 4 * ALIGNMENT]);
+    let table = regions(&memory);
+    let regions = MemoryRegions::new(&table, ALIGNMENT).expect("invalid regions");
+    let at = |offset: usize| memory.0.as_ptr().wrapping_add(offset);
+    let client0 = ClientId(0);
+    let client1 = ClientId(1);
+
+    assert!(regions.allows(client0, at(0), 2 * ALIGNMENT, true));
+    assert!(regions.allows(client0, at(ALIGNMENT), ALIGNMENT, true));
+    assert!(!regions.allows(client0, at(ALIGNMENT), ALIGNMENT + 1, true));
+    // Read-only region
+    assert!(regions.allows(client0, at(2 * ALIGNMENT), ALIGNMENT, false));
+    assert!(!regions.allows(client0, at(2 * ALIGNMENT), ALIGNMENT, true));
+    // Buffers have to lie within a single region
+    assert!(!regions.allows(client0, at(ALIGNMENT), 2 * ALIGNMENT, false));
+    // Regions of other clients
+    assert!(!regions.allows(client0, at(3 * ALIGNMENT), 1, false));
+    assert!(!regions.allows(client1, at(0), 1, false));
+    assert!(regions.allows(client1, at(3 * ALIGNMENT), ALIGNMENT, true));
+    assert!(!regions.allows(ClientId(2), at(0), 1, false));
+    // Ranges exceeding the address space
+    assert!(!regions.allows(client0, at(0), usize::MAX, false));
+    assert!(!regions.allows(client0, usize::MAX as *const u8, 2, false));
+    // Empty buffers do not access memory
+    assert!(regions.allows(client1, at(0), 0, true));
+}
+
+#[test]
+fn buffer_alignment() {
+    let memory = SharedMemory([0; 4 * ALIGNMENT]);
+    let table = regions(&memory);
+    let regions = MemoryRegions::new(&table, ALIGNMENT).expect("invalid regions");
+    let at = |offset: usize| memory.0.as_ptr().wrapping_add(offset);
+    let client0 = ClientId(0);
+
+    // Buffers can start anywhere by default
+    assert!(regions.allows(client0, at(1), 3, true));
+    assert_eq!(
+        regions.with_buffer_alignment(3).unwrap_err(),
+        Error::InvalidAlignment
+    );
+    let regions = regions
+        .with_buffer_alignment(4)
+        .expect("invalid buffer alignment");
+    assert!(!regions.allows(client0, at(1), 3, true));
+    assert!(!regions.allows(client0, at(2 * ALIGNMENT + 2), 4, false));
+    assert!(regions.allows(client0, at(4), 3, true));
+    // Empty buffers do not access memory
+    assert!(regions.allows(client0, at(1), 0, true));
+}
+
+#[test]
+fn raw_requests() {
+    let mut memory = SharedMemory([0; 4 * ALIGNMENT]);
+    let table = regions(&memory);
+    let regions = MemoryRegions::new(&table, ALIGNMENT).expect("invalid regions");
+    let (own, foreign) = memory.0.split_at_mut(3 * ALIGNMENT);
+    let (writable, read_only) = own.split_at_mut(2 * ALIGNMENT);
+
+    let request = |output: &mut [u8]| -> RequestRaw {
+        Request::GetRandom {
+            client_id: ClientId(0),
+            request_id: RequestId(7),
+            deadline: None,
+            output,
+        }
+        .try_into()
+        .expect("failed to convert request")
+    };
+    let client0 = regions.client(ClientId(0));
+    assert!(request(writable).verify(&client0).is_ok());
+    assert_eq!(
+        request(read_only).verify(&client0).unwrap_err(),
+        ValidationError::InvalidPointer
+    );
+    assert_eq!(
+        request(foreign).verify(&client0).unwrap_err(),
+        ValidationError::InvalidPointer
+    );
+
+    // The HSM only reads the data of imported keys
+    let import: RequestRaw = Request::ImportSymmetricKey {
+        client_id: ClientId(0),
+        request_id: RequestId(8),
+        deadline: None,
+        key_id: SYM_128_KEY.id,
+        data: &read_only[..16],
+        overwrite: false,
+    }
+    .try_into()
+    .expect("failed to convert request");
+    assert!(import.verify(&client0).is_ok());
+    assert_eq!(
+        import.verify(&regions.client(ClientId(1))).unwrap_err(),
+        ValidationError::InvalidPointer
+    );
+}
+
+#[async_std::test]
+async fn core_denies_foreign_buffers() {
+    let mut memory = SharedMemory([0; 4 * ALIGNMENT]);
+    let table = regions(&memory);
+    let regions = MemoryRegions::new(&table, ALIGNMENT).expect("invalid regions");
+    let (own, foreign) = memory.0.split_at_mut(3 * ALIGNMENT);
+    let (writable, read_only) = own.split_at_mut(2 * ALIGNMENT);
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let rng = init_rng();
+    let rng_worker = RngWorker::<NoopRawMutex, _, MemoryKeyStore<1, 1>> {
+        rng: &rng,
+        key_store: None,
+    };
+    let mut core = TestBuilder::default()
+        .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+        .expect("failed to add client")
+        .with_memory_regions(regions)
+        .with_hosted_worker(rng_worker)
+        .expect("failed to add hosted worker")
+        .build();
+    let mut api = Api::new(req_client_tx, resp_client_rx);
+
+    for output in [foreign, read_only] {
+        let org_request_id = api
+            .get_random(output)
+            .await
+            .expect("failed to send request");
+        core.execute().await.expect("failed to process request");
+        let Some(Response::Error {
+            client_id: _,
+            request_id,
+            error,
+        }) = api.recv_response().await
+        else {
+            panic!("Unexpected response type")
+        };
+        assert_eq!(request_id, org_request_id);
+        assert_eq!(error, jobs::Error::AccessDenied);
+    }
+
+    api.get_random(writable)
+        .await
+        .expect("failed to send request");
+    core.execute().await.expect("failed to process request");
+    let Some(Response::GetRandom { data, .. }) = api.recv_response().await else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(data.len(), 2 * ALIGNMENT);
+}
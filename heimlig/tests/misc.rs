@@ -2,12 +2,15 @@
 mod common;
 
 pub use common::*;
-use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use futures::FutureExt;
+use heimlig::common::sync::Mutex;
 use heimlig::{
     client::api::Api,
     common::jobs::{Error, RequestType, Response},
-    hsm::core::Builder,
-    hsm::workers::rng_worker::RngWorker,
+    hsm::core::{self, Builder, ClientConfig},
+    hsm::keystore::{self, KeyId},
+    hsm::workers::{rng_worker::RngWorker, QueuedWorker},
     integration::{
         embassy::{RequestQueueSink, RequestQueueSource, ResponseQueueSink, ResponseQueueSource},
         memory_key_store::MemoryKeyStore,
@@ -27,12 +30,16 @@ async fn generate_symmetric_key_no_keystore() {
         None,
     );
     let rng = init_rng();
-    let mut worker = RngWorker {
-        rng: &rng,
-        key_store:
-            Option::<&embassy_sync::mutex::Mutex<NoopRawMutex, &mut MemoryKeyStore<0, 0>>>::None,
+    let mut worker = QueuedWorker {
+        worker: RngWorker {
+            rng: &rng,
+            key_store: Option::<
+                &heimlig::common::sync::Mutex<NoopRawMutex, &mut MemoryKeyStore<0, 0>>,
+            >::None,
+        },
         requests: req_worker_rx,
         responses: resp_worker_tx,
+        cancellations: None,
     };
 
     // Generate key
@@ -69,11 +76,14 @@ async fn generate_symmetric_key() {
         Some(&key_store),
     );
     let rng = init_rng();
-    let mut worker = RngWorker {
-        rng: &rng,
-        key_store: Some(&key_store),
+    let mut worker = QueuedWorker {
+        worker: RngWorker {
+            rng: &rng,
+            key_store: Some(&key_store),
+        },
         requests: req_worker_rx,
         responses: resp_worker_tx,
+        cancellations: None,
     };
 
     // Generate key
@@ -109,6 +119,48 @@ async fn generate_symmetric_key() {
     assert_eq!(key.len(), SYM_256_KEY.ty.key_size()); // Large buffer was only used partially
 }
 
+#[async_std::test]
+async fn get_key_info() {
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let mut key_store = init_key_store(&KEY_INFOS);
+    let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
+    let (mut api, mut core, _req_worker_rx, _resp_worker_tx) = init_core(
+        &[],
+        &mut client_requests,
+        &mut client_responses,
+        &mut worker_requests,
+        &mut worker_responses,
+        Some(&key_store),
+    );
+
+    let org_request_id = api
+        .get_key_info(ASYM_NIST_P256_KEY.id)
+        .await
+        .expect("failed to send request");
+    let Response::GetKeyInfo {
+        request_id,
+        key_info,
+        ..
+    } = get_response_from_core(&mut api, &mut core).await
+    else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(request_id, org_request_id);
+    assert_eq!(key_info.id, ASYM_NIST_P256_KEY.id);
+    assert_eq!(key_info.ty, ASYM_NIST_P256_KEY.ty);
+    assert!(key_info.permissions.export_private);
+    assert!(!key_info.permissions.delete);
+
+    api.get_key_info(KeyId(42))
+        .await
+        .expect("failed to send request");
+    let Response::Error { error, .. } = get_response_from_core(&mut api, &mut core).await else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(error, Error::KeyStore(keystore::Error::InvalidKeyId));
+}
+
 #[async_std::test]
 async fn multiple_clients() {
     const REQUEST1_SIZE: usize = 16;
@@ -129,23 +181,26 @@ async fn multiple_clients() {
     let rng = init_rng();
     let mut key_store = init_key_store(&KEY_INFOS);
     let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
-    let mut rng_worker = RngWorker {
-        rng: &rng,
-        key_store: Some(&key_store),
+    let mut rng_worker = QueuedWorker {
+        worker: RngWorker {
+            rng: &rng,
+            key_store: Some(&key_store),
+        },
         requests: rng_requests_rx,
         responses: rng_responses_tx,
+        cancellations: None,
     };
     let mut core = Builder::<
         NoopRawMutex,
         RequestQueueSource<'_, '_, QUEUE_SIZE>,
         ResponseQueueSink<'_, '_, QUEUE_SIZE>,
+        MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
         RequestQueueSink<'_, '_, QUEUE_SIZE>,
         ResponseQueueSource<'_, '_, QUEUE_SIZE>,
-        MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
     >::default()
-    .with_client(req_client1_rx, resp_client1_tx)
+    .with_client(req_client1_rx, resp_client1_tx, ClientConfig::default())
     .expect("failed to add client 1")
-    .with_client(req_client2_rx, resp_client2_tx)
+    .with_client(req_client2_rx, resp_client2_tx, ClientConfig::default())
     .expect("failed to add client 2")
     .with_worker(
         &[RequestType::GetRandom, RequestType::GenerateSymmetricKey],
@@ -217,21 +272,24 @@ async fn no_worker_for_request() {
     let rng = init_rng();
     let mut key_store = init_key_store(&KEY_INFOS);
     let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
-    let mut rng_worker = RngWorker {
-        rng: &rng,
-        key_store: Some(&key_store),
+    let mut rng_worker = QueuedWorker {
+        worker: RngWorker {
+            rng: &rng,
+            key_store: Some(&key_store),
+        },
         requests: rng_requests_rx,
         responses: rng_responses_tx,
+        cancellations: None,
     };
     let mut core = Builder::<
         NoopRawMutex,
         RequestQueueSource<'_, '_, QUEUE_SIZE>,
         ResponseQueueSink<'_, '_, QUEUE_SIZE>,
+        MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
         RequestQueueSink<'_, '_, QUEUE_SIZE>,
         ResponseQueueSource<'_, '_, QUEUE_SIZE>,
-        MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
     >::default()
-    .with_client(req_client_rx, resp_client_tx)
+    .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
     .expect("failed to add client")
     .with_worker(
         &[RequestType::GetRandom], // No RequestType::GenerateSymmetricKey here
@@ -273,3 +331,270 @@ async fn no_worker_for_request() {
     assert_eq!(request_id, org_request_id);
     assert_eq!(data.len(), REQUEST_SIZE);
 }
+
+#[async_std::test]
+async fn multiple_workers_per_request_type() {
+    const REQUEST_SIZE: usize = 16;
+    // Queues can hold one element less than their size
+    const QUEUE_CAPACITY: usize = QUEUE_SIZE - 1;
+    const NUM_REQUESTS: usize = QUEUE_CAPACITY + 1;
+    let mut random_outputs = [[0u8; REQUEST_SIZE]; NUM_REQUESTS];
+
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut hw_requests, mut hw_responses) = allocate_channel();
+    let (mut sw_requests, mut sw_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let (hw_requests_rx, hw_requests_tx, hw_responses_rx, hw_responses_tx) =
+        split_queues(&mut hw_requests, &mut hw_responses);
+    let (sw_requests_rx, sw_requests_tx, sw_responses_rx, sw_responses_tx) =
+        split_queues(&mut sw_requests, &mut sw_responses);
+    let rng = init_rng();
+    let mut hw_worker = QueuedWorker {
+        worker: RngWorker::<NoopRawMutex, _, MemoryKeyStore<1, 1>> {
+            rng: &rng,
+            key_store: None,
+        },
+        requests: hw_requests_rx,
+        responses: hw_responses_tx,
+        cancellations: None,
+    };
+    let mut sw_worker = QueuedWorker {
+        worker: RngWorker::<NoopRawMutex, _, MemoryKeyStore<1, 1>> {
+            rng: &rng,
+            key_store: None,
+        },
+        requests: sw_requests_rx,
+        responses: sw_responses_tx,
+        cancellations: None,
+    };
+    let mut core = Builder::<
+        NoopRawMutex,
+        RequestQueueSource<'_, '_, QUEUE_SIZE>,
+        ResponseQueueSink<'_, '_, QUEUE_SIZE>,
+        MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
+        RequestQueueSink<'_, '_, QUEUE_SIZE>,
+        ResponseQueueSource<'_, '_, QUEUE_SIZE>,
+    >::default()
+    .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+    .expect("failed to add client")
+    .with_worker(&[RequestType::GetRandom], sw_requests_tx, sw_responses_rx)
+    .expect("failed to add software worker")
+    .with_worker_preference(
+        1,
+        &[RequestType::GetRandom],
+        hw_requests_tx,
+        hw_responses_rx,
+    )
+    .expect("failed to add hardware worker")
+    .build();
+    let mut api = Api::new(req_client_tx, resp_client_rx);
+
+    // Preferred worker receives requests until its queue is full
+    let (first_outputs, last_output) = random_outputs.split_at_mut(QUEUE_CAPACITY);
+    let mut org_request_ids = std::vec::Vec::new();
+    for output in first_outputs {
+        org_request_ids.push(
+            api.get_random(output)
+                .await
+                .expect("failed to send request"),
+        );
+        core.execute().await.expect("failed to forward request");
+    }
+    assert!(sw_worker.execute().now_or_never().is_none());
+
+    // Overflow is handled by the other worker
+    org_request_ids.push(
+        api.get_random(&mut last_output[0])
+            .await
+            .expect("failed to send request"),
+    );
+    core.execute().await.expect("failed to forward request");
+    sw_worker
+        .execute()
+        .await
+        .expect("failed to process request");
+    core.execute().await.expect("failed to forward response");
+    let Some(Response::GetRandom { request_id, .. }) = api.recv_response().await else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(request_id, org_request_ids[QUEUE_CAPACITY]);
+
+    // Responses of the preferred worker are routed back as well
+    for org_request_id in &org_request_ids[..QUEUE_CAPACITY] {
+        hw_worker
+            .execute()
+            .await
+            .expect("failed to process request");
+        core.execute().await.expect("failed to forward response");
+        let Some(Response::GetRandom {
+            request_id, data, ..
+        }) = api.recv_response().await
+        else {
+            panic!("Unexpected response type")
+        };
+        assert_eq!(request_id, *org_request_id);
+        assert_eq!(data.len(), REQUEST_SIZE);
+    }
+    assert!(hw_worker.execute().now_or_never().is_none());
+}
+
+#[async_std::test]
+async fn balance_load_between_workers() {
+    const REQUEST_SIZE: usize = 16;
+    let mut random_outputs = [[0u8; REQUEST_SIZE]; 4];
+
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker1_requests, mut worker1_responses) = allocate_channel();
+    let (mut worker2_requests, mut worker2_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let (worker1_requests_rx, worker1_requests_tx, worker1_responses_rx, worker1_responses_tx) =
+        split_queues(&mut worker1_requests, &mut worker1_responses);
+    let (worker2_requests_rx, worker2_requests_tx, worker2_responses_rx, worker2_responses_tx) =
+        split_queues(&mut worker2_requests, &mut worker2_responses);
+    let rng = init_rng();
+    let mut workers = [
+        QueuedWorker {
+            worker: RngWorker::<NoopRawMutex, _, MemoryKeyStore<1, 1>> {
+                rng: &rng,
+                key_store: None,
+            },
+            requests: worker1_requests_rx,
+            responses: worker1_responses_tx,
+            cancellations: None,
+        },
+        QueuedWorker {
+            worker: RngWorker::<NoopRawMutex, _, MemoryKeyStore<1, 1>> {
+                rng: &rng,
+                key_store: None,
+            },
+            requests: worker2_requests_rx,
+            responses: worker2_responses_tx,
+            cancellations: None,
+        },
+    ];
+    let mut core = Builder::<
+        NoopRawMutex,
+        RequestQueueSource<'_, '_, QUEUE_SIZE>,
+        ResponseQueueSink<'_, '_, QUEUE_SIZE>,
+        MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
+        RequestQueueSink<'_, '_, QUEUE_SIZE>,
+        ResponseQueueSource<'_, '_, QUEUE_SIZE>,
+    >::default()
+    .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+    .expect("failed to add client")
+    .with_worker(
+        &[RequestType::GetRandom],
+        worker1_requests_tx,
+        worker1_responses_rx,
+    )
+    .expect("failed to add worker 1")
+    .with_worker(
+        &[RequestType::GetRandom],
+        worker2_requests_tx,
+        worker2_responses_rx,
+    )
+    .expect("failed to add worker 2")
+    .build();
+    let mut api = Api::new(req_client_tx, resp_client_rx);
+
+    // Requests alternate between workers of equal preference
+    for output in random_outputs.iter_mut() {
+        api.get_random(output)
+            .await
+            .expect("failed to send request");
+        core.execute().await.expect("failed to forward request");
+    }
+    for _ in 0..2 {
+        for worker in workers.iter_mut() {
+            worker
+                .execute()
+                .now_or_never()
+                .expect("worker did not receive a request")
+                .expect("failed to process request");
+        }
+    }
+    for worker in workers.iter_mut() {
+        assert!(worker.execute().now_or_never().is_none());
+    }
+    for _ in 0..4 {
+        core.execute().await.expect("failed to forward response");
+        let Some(Response::GetRandom { .. }) = api.recv_response().await else {
+            panic!("Unexpected response type")
+        };
+    }
+}
+
+#[test]
+fn configurable_core_capacity() {
+    const MAX_CLIENTS: usize = 12;
+    const MAX_WORKERS: usize = 1;
+    const MAX_REQUEST_TYPES: usize = 2;
+    type SmallBuilder<'data, 'ch> = Builder<
+        'data,
+        'static,
+        NoopRawMutex,
+        RequestQueueSource<'ch, 'data, QUEUE_SIZE>,
+        ResponseQueueSink<'ch, 'data, QUEUE_SIZE>,
+        MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
+        RequestQueueSink<'ch, 'data, QUEUE_SIZE>,
+        ResponseQueueSource<'ch, 'data, QUEUE_SIZE>,
+        MAX_CLIENTS,
+        MAX_WORKERS,
+        MAX_REQUEST_TYPES,
+    >;
+    let mut client_queues: [_; MAX_CLIENTS + 1] = std::array::from_fn(|_| allocate_channel());
+    let mut worker_queues: [_; MAX_WORKERS + 2] = std::array::from_fn(|_| allocate_channel());
+    let mut clients = client_queues
+        .iter_mut()
+        .map(|(requests, responses)| split_queues(requests, responses));
+    let mut workers = worker_queues
+        .iter_mut()
+        .map(|(requests, responses)| split_queues(requests, responses));
+
+    let mut builder = SmallBuilder::default();
+    for (req_client_rx, _req_client_tx, _resp_client_rx, resp_client_tx) in
+        clients.by_ref().take(MAX_CLIENTS)
+    {
+        builder = builder
+            .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+            .expect("failed to add client");
+    }
+    let (req_client_rx, _req_client_tx, _resp_client_rx, resp_client_tx) =
+        clients.next().expect("missing client queues");
+    assert!(matches!(
+        builder.with_client(req_client_rx, resp_client_tx, ClientConfig::default()),
+        Err(core::Error::TooManyClients)
+    ));
+
+    let (_req_worker_rx, req_worker_tx, resp_worker_rx, _resp_worker_tx) =
+        workers.next().expect("missing worker queues");
+    assert!(matches!(
+        SmallBuilder::default().with_worker(
+            &[
+                RequestType::GetRandom,
+                RequestType::GenerateSymmetricKey,
+                RequestType::EncryptAesCbc
+            ],
+            req_worker_tx,
+            resp_worker_rx
+        ),
+        Err(core::Error::TooManyRequestTypes)
+    ));
+
+    let mut builder = SmallBuilder::default();
+    for (_req_worker_rx, req_worker_tx, resp_worker_rx, _resp_worker_tx) in
+        workers.by_ref().take(MAX_WORKERS)
+    {
+        builder = builder
+            .with_worker(&[RequestType::GetRandom], req_worker_tx, resp_worker_rx)
+            .expect("failed to add worker");
+    }
+    let (_req_worker_rx, req_worker_tx, resp_worker_rx, _resp_worker_tx) =
+        workers.next().expect("missing worker queues");
+    assert!(matches!(
+        builder.with_worker(&[RequestType::GetRandom], req_worker_tx, resp_worker_rx),
+        Err(core::Error::TooManyWorkers)
+    ));
+}
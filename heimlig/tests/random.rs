@@ -2,10 +2,11 @@
 mod common;
 
 pub use common::*;
-use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use heimlig::common::sync::Mutex;
 use heimlig::{
     common::jobs::{RequestType, Response},
-    hsm::workers::rng_worker::RngWorker,
+    hsm::workers::{rng_worker::RngWorker, QueuedWorker},
 };
 
 #[async_std::test]
@@ -26,11 +27,14 @@ async fn get_random() {
     let rng = init_rng();
     let mut key_store = init_key_store(&KEY_INFOS);
     let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
-    let mut rng_worker = RngWorker {
-        rng: &rng,
-        key_store: Some(&key_store),
+    let mut rng_worker = QueuedWorker {
+        worker: RngWorker {
+            rng: &rng,
+            key_store: Some(&key_store),
+        },
         requests: req_worker_rx,
         responses: resp_worker_tx,
+        cancellations: None,
     };
 
     let org_request_id = api
@@ -0,0 +1,176 @@
+#[allow(dead_code, unused_macros)]
+mod common;
+
+use common::*;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use futures::FutureExt;
+use heimlig::{
+    client::api::Api,
+    hsm::core::{Builder, ClientConfig, Error, STARVATION_LIMIT},
+    integration::{
+        embassy::{RequestQueueSink, RequestQueueSource, ResponseQueueSink, ResponseQueueSource},
+        memory_key_store::MemoryKeyStore,
+    },
+};
+
+/// Number of requests every client keeps pending while the schedule is recorded
+const PENDING_REQUESTS: usize = QUEUE_SIZE - 1;
+
+type TestBuilder<'data, 'ch> = Builder<
+    'data,
+    'static,
+    NoopRawMutex,
+    RequestQueueSource<'ch, 'data, QUEUE_SIZE>,
+    ResponseQueueSink<'ch, 'data, QUEUE_SIZE>,
+    MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
+    RequestQueueSink<'ch, 'data, QUEUE_SIZE>,
+    ResponseQueueSource<'ch, 'data, QUEUE_SIZE>,
+>;
+
+/// Keep the request queues of all clients filled and record which client the core serves in each
+/// step. All requests are processed on the core, so the result only depends on the scheduler.
+async fn record_schedule<const N: usize>(configs: [ClientConfig; N], steps: usize) -> Vec<usize> {
+    record_schedule_with_arrivals(configs, [0; N], steps).await
+}
+
+/// Like [record_schedule], but every client stays idle until the step given in `arrivals`.
+async fn record_schedule_with_arrivals<const N: usize>(
+    configs: [ClientConfig; N],
+    arrivals: [usize; N],
+    steps: usize,
+) -> Vec<usize> {
+    let mut channels: [_; N] = std::array::from_fn(|_| allocate_channel());
+    let mut builder = TestBuilder::default();
+    let mut apis = Vec::new();
+    for ((requests, responses), config) in channels.iter_mut().zip(configs) {
+        let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+            split_queues(requests, responses);
+        builder = builder
+            .with_client(req_client_rx, resp_client_tx, config)
+            .expect("failed to add client");
+        apis.push(Api::new(req_client_tx, resp_client_rx));
+    }
+    let mut core = builder.build();
+
+    let mut schedule = Vec::new();
+    for step in 0..steps {
+        for (api, _) in apis
+            .iter_mut()
+            .zip(arrivals)
+            .filter(|(_, arrival)| *arrival == step)
+        {
+            for _ in 0..PENDING_REQUESTS {
+                api.is_key_available(SYM_128_KEY.id)
+                    .await
+                    .expect("failed to send request");
+            }
+        }
+        core.execute().await.expect("failed to process request");
+        let served: Vec<usize> = apis
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, api)| api.recv_response().now_or_never().flatten().map(|_| i))
+            .collect();
+        assert_eq!(served.len(), 1, "expected exactly one response per step");
+        // Refill the queue to keep the client backlogged
+        apis[served[0]]
+            .is_key_available(SYM_128_KEY.id)
+            .await
+            .expect("failed to send request");
+        schedule.push(served[0]);
+    }
+    schedule
+}
+
+/// Assert that no client waits for more than the starvation limit plus one request of every
+/// other client that might have been starved at the same time.
+fn assert_starvation_free(schedule: &[usize], num_clients: usize) {
+    let max_wait = STARVATION_LIMIT as usize + num_clients - 1;
+    for client in 0..num_clients {
+        let mut last = None;
+        for (step, _) in schedule.iter().enumerate().filter(|(_, &c)| c == client) {
+            let waited = last.map_or(step, |last| step - last - 1);
+            assert!(
+                waited <= max_wait,
+                "client {client} waited {waited} requests before step {step}"
+            );
+            last = Some(step);
+        }
+        let last = last.unwrap_or_else(|| panic!("client {client} was never served"));
+        assert!(schedule.len() - last - 1 <= max_wait);
+    }
+}
+
+#[async_std::test]
+async fn higher_priority_is_served_first() {
+    const LOW: usize = 0;
+    const HIGH: usize = 1;
+    let schedule = record_schedule([ClientConfig::new(0, 1), ClientConfig::new(1, 1)], 100).await;
+    let limit = STARVATION_LIMIT as usize;
+    assert!(schedule[..limit].iter().all(|&c| c == HIGH));
+    assert_eq!(schedule[limit], LOW);
+    assert!(schedule.iter().filter(|&&c| c == HIGH).count() > 90);
+    assert_starvation_free(&schedule, 2);
+}
+
+#[async_std::test]
+async fn equal_priority_is_shared_by_weight() {
+    let schedule = record_schedule([ClientConfig::new(0, 3), ClientConfig::new(0, 1)], 40).await;
+    assert_eq!(schedule[..4], [0, 0, 0, 1]);
+    assert_eq!(schedule.iter().filter(|&&c| c == 0).count(), 30);
+    assert_eq!(schedule.iter().filter(|&&c| c == 1).count(), 10);
+    assert_starvation_free(&schedule, 2);
+}
+
+#[async_std::test]
+async fn equal_configs_alternate() {
+    let schedule = record_schedule::<3>(std::array::from_fn(|_| ClientConfig::default()), 30).await;
+    for (step, &client) in schedule.iter().enumerate() {
+        assert_eq!(client, step % 3);
+    }
+}
+
+#[async_std::test]
+async fn low_priority_clients_do_not_starve() {
+    let schedule = record_schedule(
+        [
+            ClientConfig::new(2, 1),
+            ClientConfig::new(1, 4),
+            ClientConfig::new(0, 1),
+        ],
+        200,
+    )
+    .await;
+    assert_starvation_free(&schedule, 3);
+    // Starved clients are served in the order of their priority
+    let limit = STARVATION_LIMIT as usize;
+    assert_eq!(schedule[limit..limit + 2], [1, 2]);
+}
+
+#[async_std::test]
+async fn idle_client_is_not_starved() {
+    const LOW: usize = 0;
+    const HIGH: usize = 1;
+    let limit = STARVATION_LIMIT as usize;
+    let arrival = 2 * limit;
+    let schedule = record_schedule_with_arrivals(
+        [ClientConfig::new(0, 1), ClientConfig::new(1, 1)],
+        [arrival, 0],
+        100,
+    )
+    .await;
+    // The waiting time of the low priority client starts when it sends its first request
+    assert!(schedule[..arrival + limit].iter().all(|&c| c == HIGH));
+    assert_eq!(schedule[arrival + limit], LOW);
+    assert_starvation_free(&schedule[arrival..], 2);
+}
+
+#[test]
+fn zero_weight_is_rejected() {
+    let (mut requests, mut responses) = allocate_channel();
+    let (req_client_rx, _req_client_tx, _resp_client_rx, resp_client_tx) =
+        split_queues(&mut requests, &mut responses);
+    let result =
+        TestBuilder::default().with_client(req_client_rx, resp_client_tx, ClientConfig::new(0, 0));
+    assert!(matches!(result, Err(Error::InvalidClientWeight)));
+}
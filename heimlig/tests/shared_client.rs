@@ -0,0 +1,178 @@
+#[allow(dead_code, unused_macros)]
+#[macro_use]
+mod common;
+
+use common::*;
+use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
+use futures::future::{join, join3, join4};
+use futures::{pin_mut, FutureExt, SinkExt, StreamExt};
+use heimlig::client::shared::SharedClient;
+use heimlig::common::jobs::{Request, Response};
+use std::thread;
+
+/// Answer a request for random bytes by filling the output buffer with its size.
+fn answer(request: Request<'_>) -> Response<'_> {
+    let Request::GetRandom {
+        client_id,
+        request_id,
+        output,
+        ..
+    } = request
+    else {
+        panic!("Unexpected request type")
+    };
+    output.fill(output.len() as u8);
+    Response::GetRandom {
+        client_id,
+        request_id,
+        data: output,
+    }
+}
+
+fn output_size(request: &Request<'_>) -> usize {
+    let Request::GetRandom { output, .. } = request else {
+        panic!("Unexpected request type")
+    };
+    output.len()
+}
+
+#[async_std::test]
+async fn handles_share_one_client() {
+    let mut first = [0u8; 1];
+    let mut second = [0u8; 2];
+    let mut third = [0u8; 3];
+    let mut fourth = [0u8; 4];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut requests, req_tx, resp_rx, mut responses) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let client: SharedClient<NoopRawMutex, _, _, 2> = SharedClient::new(req_tx, resp_rx);
+    let api = client.api();
+    let (api1, api2, api3, api4) = (api, api, api, client.api());
+
+    let calls = join4(
+        api1.get_random(&mut first),
+        api2.get_random(&mut second),
+        api3.get_random(&mut third),
+        api4.get_random(&mut fourth),
+    );
+    let hsm = async {
+        // Answer the requests in pairs in reverse order
+        for _ in 0..2 {
+            let first = requests.next().await.expect("request stream ended");
+            let second = requests.next().await.expect("request stream ended");
+            for request in [second, first] {
+                responses
+                    .send(answer(request))
+                    .await
+                    .expect("failed to send");
+            }
+        }
+    };
+    let ((first, second, third, fourth), ()) = join(calls, hsm).await;
+    assert_eq!(first.expect("request failed"), [1]);
+    assert_eq!(second.expect("request failed"), [2, 2]);
+    assert_eq!(third.expect("request failed"), [3, 3, 3]);
+    assert_eq!(fourth.expect("request failed"), [4, 4, 4, 4]);
+}
+
+#[async_std::test]
+async fn requests_are_served_in_order() {
+    let mut first = [0u8; 1];
+    let mut second = [0u8; 2];
+    let mut third = [0u8; 3];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut requests, req_tx, resp_rx, mut responses) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let client: SharedClient<NoopRawMutex, _, _, 1> = SharedClient::new(req_tx, resp_rx);
+
+    let (api1, api2, api3) = (client.api(), client.api(), client.api());
+
+    // Later requests must not overtake the ones waiting in line for the only slot
+    let calls = join3(
+        api1.get_random(&mut first),
+        api2.get_random(&mut second),
+        api3.get_random(&mut third),
+    );
+    let hsm = async {
+        for expected in 1..=3 {
+            let request = requests.next().await.expect("request stream ended");
+            assert_eq!(output_size(&request), expected);
+            responses
+                .send(answer(request))
+                .await
+                .expect("failed to send");
+        }
+    };
+    let ((first, second, third), ()) = join(calls, hsm).await;
+    assert_eq!(first.expect("request failed"), [1]);
+    assert_eq!(second.expect("request failed"), [2, 2]);
+    assert_eq!(third.expect("request failed"), [3, 3, 3]);
+}
+
+#[async_std::test]
+async fn dropped_request_leaves_line() {
+    let mut first = [0u8; 1];
+    let mut second = [0u8; 2];
+    let mut third = [0u8; 3];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut requests, req_tx, resp_rx, mut responses) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let client: SharedClient<NoopRawMutex, _, _, 1, 1> = SharedClient::new(req_tx, resp_rx);
+    let api = client.api();
+
+    let call = api.get_random(&mut first);
+    pin_mut!(call);
+    assert!(call.as_mut().now_or_never().is_none());
+    {
+        // Waits in line for the slot of the first request and gives up
+        let waiting = api.get_random(&mut second);
+        pin_mut!(waiting);
+        assert!(waiting.as_mut().now_or_never().is_none());
+    }
+    let calls = join(call, api.get_random(&mut third));
+    let hsm = async {
+        for expected in [1, 3] {
+            let request = requests.next().await.expect("request stream ended");
+            assert_eq!(output_size(&request), expected);
+            responses
+                .send(answer(request))
+                .await
+                .expect("failed to send");
+        }
+    };
+    let ((first, third), ()) = join(calls, hsm).await;
+    assert_eq!(first.expect("request failed"), [1]);
+    assert_eq!(third.expect("request failed"), [3, 3, 3]);
+}
+
+#[test]
+fn handles_on_several_threads() {
+    const THREADS: usize = 6;
+    let mut outputs = [[0u8; 16]; THREADS];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut requests, req_tx, resp_rx, mut responses) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let client: SharedClient<CriticalSectionRawMutex, _, _, 2, 2> =
+        SharedClient::new(req_tx, resp_rx);
+
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            async_std::task::block_on(async {
+                for _ in 0..THREADS {
+                    let request = requests.next().await.expect("request stream ended");
+                    responses
+                        .send(answer(request))
+                        .await
+                        .expect("failed to send");
+                }
+            })
+        });
+        for output in outputs.iter_mut() {
+            let api = client.api();
+            scope.spawn(move || {
+                let random = async_std::task::block_on(api.get_random(output));
+                assert_eq!(random.expect("request failed"), [16; 16]);
+            });
+        }
+    });
+}
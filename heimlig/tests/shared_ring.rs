@@ -0,0 +1,354 @@
+use heimlig::common::jobs::{ClientId, Request, RequestId, Response};
+use heimlig::integration::raw_jobs::{RequestDataRaw, RequestRaw, ResponseRaw, ValidationError};
+use heimlig::integration::shared_ring::{
+    checked_ring_size, init_ring, ring_size, Error, RingConsumer, RingProducer, Signal,
+    CACHE_LINE_SIZE,
+};
+use memmap2::MmapMut;
+use std::fs::OpenOptions;
+use std::mem::{align_of, offset_of, size_of};
+use std::thread;
+
+const CAPACITY: usize = 4;
+const REGION_SIZE: usize = ring_size::<RequestRaw>(CAPACITY);
+
+/// Stand-in for a shared RAM region
+#[repr(C, align(64))]
+struct Region([u8; REGION_SIZE]);
+
+impl Region {
+    fn new() -> Box<Self> {
+        Box::new(Region([0u8; REGION_SIZE]))
+    }
+}
+
+fn request_raw(request_id: u32, output: &mut [u8]) -> RequestRaw {
+    Request::GetRandom {
+        client_id: ClientId(0),
+        request_id: RequestId(request_id),
+        deadline: None,
+        output,
+    }
+    .try_into()
+    .expect("failed to convert request")
+}
+
+#[test]
+fn two_threads() {
+    const NUM_REQUESTS: u32 = 100;
+    let mut region = Region::new();
+    let memory = region.0.as_mut_ptr();
+    // SAFETY: The region is only accessed through the ring ends below.
+    unsafe { init_ring::<RequestRaw>(memory, REGION_SIZE, CAPACITY) }.expect("failed to init");
+    let producer_signal = Signal::new();
+    let consumer_signal = Signal::new();
+    // SAFETY: The region outlives both ends, which are the only producer and consumer.
+    let mut producer = unsafe {
+        RingProducer::<RequestRaw, _>::attach(
+            memory,
+            REGION_SIZE,
+            &consumer_signal,
+            &producer_signal,
+        )
+    }
+    .expect("failed to attach producer");
+    // SAFETY: See above
+    let mut consumer = unsafe {
+        RingConsumer::<RequestRaw, _>::attach(
+            memory,
+            REGION_SIZE,
+            &producer_signal,
+            &consumer_signal,
+        )
+    }
+    .expect("failed to attach consumer");
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            async_std::task::block_on(async {
+                let mut output = [0u8; 16];
+                for request_id in 0..NUM_REQUESTS {
+                    producer
+                        .push(request_raw(request_id, &mut output))
+                        .await
+                        .expect("failed to push");
+                }
+            })
+        });
+        s.spawn(|| {
+            async_std::task::block_on(async {
+                for expected_id in 0..NUM_REQUESTS {
+                    let request = consumer.pop().await.expect("failed to pop");
+                    assert_eq!(request.request_id, expected_id);
+                }
+            })
+        });
+    });
+}
+
+#[test]
+fn full_and_empty() {
+    let mut region = Region::new();
+    let memory = region.0.as_mut_ptr();
+    let mut output = [0u8; 16];
+    let signal = Signal::new();
+    // SAFETY: The region is only accessed through the ring ends below.
+    unsafe { init_ring::<RequestRaw>(memory, REGION_SIZE, CAPACITY) }.expect("failed to init");
+    // SAFETY: The region outlives both ends, which are the only producer and consumer.
+    let mut producer =
+        unsafe { RingProducer::<RequestRaw, _>::attach(memory, REGION_SIZE, &signal, &signal) }
+            .expect("failed to attach producer");
+    // SAFETY: See above
+    let mut consumer =
+        unsafe { RingConsumer::<RequestRaw, _>::attach(memory, REGION_SIZE, &signal, &signal) }
+            .expect("failed to attach consumer");
+
+    assert!(matches!(consumer.try_pop(), Ok(None)));
+    for request_id in 0..CAPACITY as u32 {
+        producer
+            .try_push(request_raw(request_id, &mut output))
+            .expect("ring corrupted")
+            .expect("ring full");
+    }
+    let rejected = producer
+        .try_push(request_raw(CAPACITY as u32, &mut output))
+        .expect("ring corrupted")
+        .expect_err("pushed into full ring");
+    assert_eq!(rejected.request_id, CAPACITY as u32);
+
+    // Indices wrap around the slots
+    for request_id in 0..3 * CAPACITY as u32 {
+        let request = consumer
+            .try_pop()
+            .expect("ring corrupted")
+            .expect("ring empty");
+        assert_eq!(request.request_id, request_id);
+        producer
+            .try_push(request_raw(request_id + CAPACITY as u32, &mut output))
+            .expect("ring corrupted")
+            .expect("ring full");
+    }
+}
+
+#[test]
+fn invalid_regions() {
+    let mut region = Region::new();
+    let memory = region.0.as_mut_ptr();
+    let signal = Signal::new();
+
+    // SAFETY: The region is only accessed through the ring ends below.
+    unsafe {
+        assert_eq!(
+            init_ring::<RequestRaw>(memory.add(8), REGION_SIZE - 8, CAPACITY),
+            Err(Error::InvalidAlignment)
+        );
+        assert_eq!(
+            init_ring::<RequestRaw>(memory, REGION_SIZE - 1, CAPACITY),
+            Err(Error::InvalidSize)
+        );
+        assert_eq!(
+            init_ring::<RequestRaw>(memory, REGION_SIZE, CAPACITY - 1),
+            Err(Error::InvalidCapacity)
+        );
+        assert!(matches!(
+            RingConsumer::<RequestRaw, _>::attach(memory, REGION_SIZE, &signal, &signal),
+            Err(Error::NotInitialized)
+        ));
+        init_ring::<RequestRaw>(memory, REGION_SIZE, CAPACITY).expect("failed to init");
+        // Item types of both sides do not match
+        assert!(matches!(
+            RingConsumer::<ResponseRaw, _>::attach(memory, REGION_SIZE, &signal, &signal),
+            Err(Error::NotInitialized)
+        ));
+    }
+}
+
+#[test]
+fn untrusted_contents() {
+    let mut region = Region::new();
+    let memory = region.0.as_mut_ptr();
+    let signal = Signal::new();
+    let mut output = [0u8; 16];
+    // SAFETY: The region is only accessed through the ring ends and the simulated faults below.
+    unsafe { init_ring::<RequestRaw>(memory, REGION_SIZE, CAPACITY) }.expect("failed to init");
+    // SAFETY: See above
+    let mut producer =
+        unsafe { RingProducer::<RequestRaw, _>::attach(memory, REGION_SIZE, &signal, &signal) }
+            .expect("failed to attach producer");
+    // SAFETY: See above
+    let mut consumer =
+        unsafe { RingConsumer::<RequestRaw, _>::attach(memory, REGION_SIZE, &signal, &signal) }
+            .expect("failed to attach consumer");
+
+    // Invalid tag value in the first slot
+    producer
+        .try_push(request_raw(0, &mut output))
+        .expect("ring corrupted")
+        .expect("ring full");
+    producer
+        .try_push(request_raw(1, &mut output))
+        .expect("ring corrupted")
+        .expect("ring full");
+    let first_slot = 3 * CACHE_LINE_SIZE;
+    // The data with its tag follows the deadline, see `#[repr(C)]` of `RequestRaw`
+    let tag_offset = (offset_of!(RequestRaw, deadline) + size_of::<u64>())
+        .next_multiple_of(align_of::<RequestDataRaw>());
+    // SAFETY: Simulates a faulty producer writing to the region
+    unsafe { memory.add(first_slot + tag_offset).write_volatile(0xFF) };
+    assert_eq!(
+        consumer.try_pop().err(),
+        Some(Error::InvalidItem(ValidationError::InvalidTagValue))
+    );
+    // The invalid item was removed
+    let request = consumer
+        .try_pop()
+        .expect("ring corrupted")
+        .expect("ring empty");
+    assert_eq!(request.request_id, 1);
+
+    // Head index far ahead of the tail
+    // SAFETY: Simulates a faulty producer writing to the region
+    unsafe {
+        memory
+            .add(CACHE_LINE_SIZE)
+            .cast::<u32>()
+            .write_volatile(1000)
+    };
+    assert_eq!(consumer.try_pop().err(), Some(Error::Corrupted));
+    assert!(matches!(
+        producer.try_push(request_raw(2, &mut output)),
+        Err(Error::Corrupted)
+    ));
+}
+
+#[test]
+fn forged_header() {
+    let mut region = Region::new();
+    let memory = region.0.as_mut_ptr();
+    let signal = Signal::new();
+    assert_eq!(checked_ring_size::<RequestRaw>(usize::MAX / 2), None);
+    // SAFETY: The region is only accessed through the ring ends and the simulated faults below.
+    unsafe {
+        init_ring::<RequestRaw>(memory, REGION_SIZE, CAPACITY).expect("failed to init");
+        // Capacity whose ring size overflows on 32-bit targets and exceeds the region on others
+        memory.cast::<u32>().add(1).write_volatile(1 << 30);
+        assert!(matches!(
+            RingConsumer::<RequestRaw, _>::attach(memory, REGION_SIZE, &signal, &signal),
+            Err(Error::InvalidCapacity | Error::InvalidSize)
+        ));
+        memory.cast::<u32>().add(1).write_volatile(u32::MAX);
+        assert!(matches!(
+            RingProducer::<RequestRaw, _>::attach(memory, REGION_SIZE, &signal, &signal),
+            Err(Error::InvalidCapacity)
+        ));
+    }
+}
+
+/// Both ends attach to their own mapping of the same file, like processes sharing memory.
+#[test]
+fn memory_mapped_file() {
+    const NUM_REQUESTS: u32 = 20;
+    let path = std::env::temp_dir().join(format!("heimlig-shared-ring-{}", std::process::id()));
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .expect("failed to create file");
+    file.set_len(REGION_SIZE as u64)
+        .expect("failed to resize file");
+    // SAFETY: The file is only accessed through the mappings of this test.
+    let (mut producer_map, mut consumer_map) = unsafe {
+        (
+            MmapMut::map_mut(&file).expect("failed to map file"),
+            MmapMut::map_mut(&file).expect("failed to map file"),
+        )
+    };
+    std::fs::remove_file(&path).expect("failed to remove file");
+    assert_ne!(producer_map.as_ptr(), consumer_map.as_ptr());
+    let producer_signal = Signal::new();
+    let consumer_signal = Signal::new();
+    // SAFETY: Mappings are page aligned and only accessed through the ring ends below.
+    unsafe { init_ring::<RequestRaw>(producer_map.as_mut_ptr(), REGION_SIZE, CAPACITY) }
+        .expect("failed to init");
+    // SAFETY: The mappings outlive both ends, which are the only producer and consumer.
+    let mut producer = unsafe {
+        RingProducer::<RequestRaw, _>::attach(
+            producer_map.as_mut_ptr(),
+            REGION_SIZE,
+            &consumer_signal,
+            &producer_signal,
+        )
+    }
+    .expect("failed to attach producer");
+    // SAFETY: See above
+    let mut consumer = unsafe {
+        RingConsumer::<RequestRaw, _>::attach(
+            consumer_map.as_mut_ptr(),
+            REGION_SIZE,
+            &producer_signal,
+            &consumer_signal,
+        )
+    }
+    .expect("failed to attach consumer");
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            async_std::task::block_on(async {
+                let mut output = [0u8; 16];
+                for request_id in 0..NUM_REQUESTS {
+                    producer
+                        .push(request_raw(request_id, &mut output))
+                        .await
+                        .expect("failed to push");
+                }
+            })
+        });
+        s.spawn(|| {
+            async_std::task::block_on(async {
+                for expected_id in 0..NUM_REQUESTS {
+                    let request = consumer.pop().await.expect("failed to pop");
+                    assert_eq!(request.request_id, expected_id);
+                }
+            })
+        });
+    });
+}
+
+#[test]
+fn responses() {
+    const SIZE: usize = ring_size::<ResponseRaw>(CAPACITY);
+    const _: () = assert!(SIZE <= REGION_SIZE);
+    let mut region = Region::new();
+    let memory = region.0.as_mut_ptr();
+    let signal = Signal::new();
+    let mut data = [0u8; 16];
+    // SAFETY: The region is only accessed through the ring ends below.
+    unsafe { init_ring::<ResponseRaw>(memory, SIZE, CAPACITY) }.expect("failed to init");
+    // SAFETY: The region outlives both ends, which are the only producer and consumer.
+    let mut producer =
+        unsafe { RingProducer::<ResponseRaw, _>::attach(memory, SIZE, &signal, &signal) }
+            .expect("failed to attach producer");
+    // SAFETY: See above
+    let mut consumer =
+        unsafe { RingConsumer::<ResponseRaw, _>::attach(memory, SIZE, &signal, &signal) }
+            .expect("failed to attach consumer");
+    let response: ResponseRaw = Response::GetRandom {
+        client_id: ClientId(1),
+        request_id: RequestId(2),
+        data: &mut data,
+    }
+    .try_into()
+    .expect("failed to convert response");
+    producer
+        .try_push(response)
+        .expect("ring corrupted")
+        .expect("ring full");
+    let response = consumer
+        .try_pop()
+        .expect("ring corrupted")
+        .expect("ring empty");
+    assert_eq!(response.client_id, 1);
+    assert_eq!(response.request_id, 2);
+}
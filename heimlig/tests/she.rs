@@ -0,0 +1,697 @@
+#[macro_use]
+mod common;
+
+pub use common::*;
+use core::ffi::c_void;
+use embassy_futures::{
+    join::join4,
+    select::{select, Either},
+};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use futures::{FutureExt, SinkExt, StreamExt};
+use heimlig::common::sync::Mutex;
+use heimlig::{
+    client::{
+        api::Api,
+        she::{SheApi, RND_SIZE},
+    },
+    common::jobs::{Error, RequestType, Response},
+    crypto::{
+        aes::BLOCK_SIZE,
+        she::{
+            calculate_m1, calculate_m2, calculate_m3, M1_SIZE, M2_SIZE, M3_SIZE, M4_SIZE, M5_SIZE,
+        },
+    },
+    hsm::{
+        core::{Builder, ClientConfig},
+        keystore::{InsecureKeyStore, KeyId, KeyInfo, KeyPermissions, KeyType},
+        she::{self, SheKeyFlags, SheKeyId, SheKeySlots, SheSlotState, NUM_SLOTS},
+        workers::{
+            aes_worker::AesWorker, rng_worker::RngWorker, she_worker::SheWorker, QueuedWorker,
+        },
+    },
+    integration::{
+        embassy::{RequestQueueSink, RequestQueueSource, ResponseQueueSink, ResponseQueueSource},
+        memory_key_store::MemoryKeyStore,
+        raw_blocking::BlockingTransportRaw,
+        raw_jobs::{RequestRaw, ResponseRaw},
+        raw_she::{she_execute, SheClientRaw, SheCommandRaw, SheErrorCodeRaw},
+    },
+};
+use std::thread;
+
+const SHE_KEY_SIZE: usize = 16;
+const SHE_KEY_INFO: KeyInfo = KeyInfo {
+    id: KeyId(0),
+    ty: KeyType::Symmetric(SHE_KEY_SIZE),
+    permissions: KeyPermissions {
+        import: false,
+        export_private: false,
+        overwrite: false,
+        delete: false,
+    },
+};
+
+fn she_key_ids() -> [KeyId; NUM_SLOTS] {
+    core::array::from_fn(|i| KeyId(i as u32))
+}
+
+fn init_she_key_store() -> MemoryKeyStore<{ NUM_SLOTS * SHE_KEY_SIZE }, NUM_SLOTS> {
+    let key_infos: [KeyInfo; NUM_SLOTS] = core::array::from_fn(|i| KeyInfo {
+        id: KeyId(i as u32),
+        ..SHE_KEY_INFO
+    });
+    let mut key_store = MemoryKeyStore::try_new(&key_infos).expect("failed to create key store");
+    let master_ecu_key = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+    key_store
+        .import_symmetric_key_insecure(KeyId(SheKeyId::MasterEcuKey as u32), &master_ecu_key)
+        .expect("failed to import master ECU key");
+    key_store
+}
+
+#[async_std::test]
+async fn load_she_key() {
+    // Test vectors from the SHE specification (memory update protocol example)
+    let uid: [u8; 15] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    let m1 = hex::decode("00000000000000000000000000000141").unwrap();
+    let m2 =
+        hex::decode("2b111e2d93f486566bcbba1d7f7a9797c94643b050fc5d4d7de14cff682203c3").unwrap();
+    let m3 = hex::decode("b9d745e5ace7d41860bc63c2b9f5bb46").unwrap();
+    let expected_m4 =
+        hex::decode("00000000000000000000000000000141b472e8d8727d70d57295e74849a27917").unwrap();
+    let expected_m5 = hex::decode("820d8d95dc11b4668878160cb2a4e23e").unwrap();
+    let mut m4 = [0u8; M4_SIZE];
+    let mut m5 = [0u8; M5_SIZE];
+    let mut replay_m4 = [0u8; M4_SIZE];
+    let mut replay_m5 = [0u8; M5_SIZE];
+
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let mut key_store = init_she_key_store();
+    let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
+    let (mut api, mut core, req_worker_rx, resp_worker_tx) = init_core(
+        &[RequestType::LoadSheKey],
+        &mut client_requests,
+        &mut client_responses,
+        &mut worker_requests,
+        &mut worker_responses,
+        None,
+    );
+    let slots: Mutex<NoopRawMutex, _> = Mutex::new(SheKeySlots::new(uid, she_key_ids()));
+    let mut worker = QueuedWorker {
+        worker: SheWorker {
+            key_store: &key_store,
+            slots: &slots,
+        },
+        requests: req_worker_rx,
+        responses: resp_worker_tx,
+        cancellations: None,
+    };
+
+    // Load KEY_1 authorized by MASTER_ECU_KEY
+    let org_request_id = api
+        .load_she_key(&m1, &m2, &m3, &mut m4, &mut m5)
+        .await
+        .expect("failed to send request");
+    let Response::LoadSheKey {
+        client_id: _,
+        request_id,
+        m4,
+        m5,
+    } = get_response_from_worker!(api, core, worker)
+    else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(request_id, org_request_id);
+    assert_eq!(m4, expected_m4.as_slice());
+    assert_eq!(m5, expected_m5.as_slice());
+    assert_eq!(slots.lock().await.slot_state(SheKeyId::Key1).counter, 1);
+    assert!(key_store
+        .lock()
+        .await
+        .is_key_available(KeyId(SheKeyId::Key1 as u32)));
+
+    // Replaying the same update is rejected because the counter did not increase
+    let org_request_id = api
+        .load_she_key(&m1, &m2, &m3, &mut replay_m4, &mut replay_m5)
+        .await
+        .expect("failed to send request");
+    let Response::Error {
+        client_id: _,
+        request_id,
+        error,
+    } = get_response_from_worker!(api, core, worker)
+    else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(request_id, org_request_id);
+    assert_eq!(error, Error::She(she::Error::KeyUpdateError));
+}
+
+#[async_std::test]
+async fn load_she_key_invalid_sizes() {
+    let m1 = [0u8; M1_SIZE];
+    let m2 = [0u8; M2_SIZE];
+    let m3 = [0u8; M3_SIZE - 1];
+    let mut m4 = [0u8; M4_SIZE];
+    let mut m5 = [0u8; M5_SIZE];
+
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut worker_requests, mut worker_responses) = allocate_channel();
+    let mut key_store = init_she_key_store();
+    let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
+    let (mut api, mut core, req_worker_rx, resp_worker_tx) = init_core(
+        &[RequestType::LoadSheKey],
+        &mut client_requests,
+        &mut client_responses,
+        &mut worker_requests,
+        &mut worker_responses,
+        None,
+    );
+    let slots: Mutex<NoopRawMutex, _> = Mutex::new(SheKeySlots::new([0u8; 15], she_key_ids()));
+    let mut worker = QueuedWorker {
+        worker: SheWorker {
+            key_store: &key_store,
+            slots: &slots,
+        },
+        requests: req_worker_rx,
+        responses: resp_worker_tx,
+        cancellations: None,
+    };
+
+    let org_request_id = api
+        .load_she_key(&m1, &m2, &m3, &mut m4, &mut m5)
+        .await
+        .expect("failed to send request");
+    let Response::Error {
+        client_id: _,
+        request_id,
+        error,
+    } = get_response_from_worker!(api, core, worker)
+    else {
+        panic!("Unexpected response type")
+    };
+    assert_eq!(request_id, org_request_id);
+    assert_eq!(
+        error,
+        Error::Crypto(heimlig::crypto::Error::InvalidBufferSize)
+    );
+}
+
+#[async_std::test]
+async fn she_commands() {
+    // Test vectors from NIST SP 800-38A (F.1.1, F.2.1) and SP 800-38B (D.1)
+    let key = hex::decode("2b7e151628aed2a6abf7158809cf4f3c").unwrap();
+    let iv = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+    let plaintext = hex::decode("6bc1bee22e409f96e93d7e117393172a").unwrap();
+    let ecb_ciphertext = hex::decode("3ad77bb40d7a3660a89ecaf32466ef97").unwrap();
+    let cbc_ciphertext = hex::decode("7649abac8119b246cee98e9b12e9197d").unwrap();
+    let mac = hex::decode("070a16b46b4d4144f79bdd9dd04a287c").unwrap();
+    let mut wrong_mac = mac.clone();
+    wrong_mac[0] ^= 1;
+    let mut ecb_buffer = [0u8; BLOCK_SIZE];
+    ecb_buffer.copy_from_slice(&plaintext);
+    let mut cbc_buffer = ecb_buffer;
+    let mut mac_buffer = [0u8; BLOCK_SIZE];
+    let mut rnd_buffer = [0u8; RND_SIZE];
+    let mut secret_key_buffer = [0u8; BLOCK_SIZE];
+
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut aes_requests, mut aes_responses) = allocate_channel();
+    let (mut rng_requests, mut rng_responses) = allocate_channel();
+    let (mut she_requests, mut she_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let (req_aes_rx, req_aes_tx, resp_aes_rx, resp_aes_tx) =
+        split_queues(&mut aes_requests, &mut aes_responses);
+    let (req_rng_rx, req_rng_tx, resp_rng_rx, resp_rng_tx) =
+        split_queues(&mut rng_requests, &mut rng_responses);
+    let (req_she_rx, req_she_tx, resp_she_rx, resp_she_tx) =
+        split_queues(&mut she_requests, &mut she_responses);
+
+    let mut key_store = init_she_key_store();
+    key_store
+        .import_symmetric_key_insecure(KeyId(SheKeyId::Key1 as u32), &key)
+        .expect("failed to import KEY_1");
+    // KEY_2 holds the same key for MAC commands
+    key_store
+        .import_symmetric_key_insecure(KeyId(SheKeyId::Key2 as u32), &key)
+        .expect("failed to import KEY_2");
+    let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
+    let rng = init_rng();
+    let mut slots = SheKeySlots::new([0u8; 15], she_key_ids());
+    slots.restore_slot_state(
+        SheKeyId::Key2,
+        SheSlotState {
+            counter: 0,
+            flags: SheKeyFlags {
+                key_usage: true,
+                ..Default::default()
+            },
+        },
+    );
+    let slots: Mutex<NoopRawMutex, _> = Mutex::new(slots);
+    let mut core = Builder::<
+        NoopRawMutex,
+        RequestQueueSource<'_, '_, QUEUE_SIZE>,
+        ResponseQueueSink<'_, '_, QUEUE_SIZE>,
+        MemoryKeyStore<{ NUM_SLOTS * SHE_KEY_SIZE }, NUM_SLOTS>,
+        RequestQueueSink<'_, '_, QUEUE_SIZE>,
+        ResponseQueueSource<'_, '_, QUEUE_SIZE>,
+    >::default()
+    .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+    .expect("failed to add client")
+    .with_worker(
+        &[
+            RequestType::EncryptAesEcb,
+            RequestType::DecryptAesEcb,
+            RequestType::EncryptAesCbc,
+            RequestType::DecryptAesCbc,
+            RequestType::CalculateAesCmac,
+            RequestType::VerifyAesCmac,
+        ],
+        req_aes_tx,
+        resp_aes_rx,
+    )
+    .expect("failed to add worker")
+    .with_worker(&[RequestType::GetRandom], req_rng_tx, resp_rng_rx)
+    .expect("failed to add worker")
+    .with_worker(
+        &[RequestType::LoadSheKey, RequestType::FinishSheBoot],
+        req_she_tx,
+        resp_she_rx,
+    )
+    .expect("failed to add worker")
+    .build();
+    let mut aes_worker = QueuedWorker {
+        worker: AesWorker {
+            key_store: &key_store,
+            she_slots: Some(&slots),
+        },
+        requests: req_aes_rx,
+        responses: resp_aes_tx,
+        cancellations: None,
+    };
+    let mut rng_worker = QueuedWorker {
+        worker: RngWorker {
+            rng: &rng,
+            key_store: Some(&key_store),
+        },
+        requests: req_rng_rx,
+        responses: resp_rng_tx,
+        cancellations: None,
+    };
+    let mut she_worker = QueuedWorker {
+        worker: SheWorker {
+            key_store: &key_store,
+            slots: &slots,
+        },
+        requests: req_she_rx,
+        responses: resp_she_tx,
+        cancellations: None,
+    };
+    let mut she_api = SheApi::new(Api::new(req_client_tx, resp_client_rx), she_key_ids());
+
+    let hsm = join4(
+        async {
+            loop {
+                core.execute().await.expect("failed to process job");
+            }
+        },
+        async {
+            loop {
+                aes_worker
+                    .execute()
+                    .await
+                    .expect("failed to process request");
+            }
+        },
+        async {
+            loop {
+                rng_worker
+                    .execute()
+                    .await
+                    .expect("failed to process request");
+            }
+        },
+        async {
+            loop {
+                she_worker
+                    .execute()
+                    .await
+                    .expect("failed to process request");
+            }
+        },
+    );
+    let commands = async {
+        let ciphertext = she_api
+            .enc_ecb(SheKeyId::Key1, &mut ecb_buffer)
+            .await
+            .expect("CMD_ENC_ECB failed");
+        assert_eq!(ciphertext, ecb_ciphertext.as_slice());
+        let decrypted = she_api
+            .dec_ecb(SheKeyId::Key1, ciphertext)
+            .await
+            .expect("CMD_DEC_ECB failed");
+        assert_eq!(decrypted, plaintext.as_slice());
+
+        let ciphertext = she_api
+            .enc_cbc(SheKeyId::Key1, &iv, &mut cbc_buffer)
+            .await
+            .expect("CMD_ENC_CBC failed");
+        assert_eq!(ciphertext, cbc_ciphertext.as_slice());
+        let decrypted = she_api
+            .dec_cbc(SheKeyId::Key1, &iv, ciphertext)
+            .await
+            .expect("CMD_DEC_CBC failed");
+        assert_eq!(decrypted, plaintext.as_slice());
+
+        let calculated_mac = she_api
+            .generate_mac(SheKeyId::Key2, &plaintext, &mut mac_buffer)
+            .await
+            .expect("CMD_GENERATE_MAC failed");
+        assert_eq!(calculated_mac, mac.as_slice());
+        assert!(she_api
+            .verify_mac(SheKeyId::Key2, &plaintext, &mac)
+            .await
+            .expect("CMD_VERIFY_MAC failed"));
+        assert!(!she_api
+            .verify_mac(SheKeyId::Key2, &plaintext, &wrong_mac)
+            .await
+            .expect("CMD_VERIFY_MAC failed"));
+
+        she_api.rnd(&mut rnd_buffer).await.expect("CMD_RND failed");
+
+        // SECRET_KEY can only be used for key updates
+        assert_eq!(
+            she_api
+                .enc_ecb(SheKeyId::SecretKey, &mut secret_key_buffer)
+                .await,
+            Err(she::Error::KeyInvalid)
+        );
+
+        she_api.boot_ok().await.expect("CMD_BOOT_OK failed");
+        assert_eq!(she_api.boot_failure().await, Err(she::Error::SequenceError));
+    };
+    assert!(matches!(select(commands, hsm).await, Either::First(_)));
+}
+
+/// Calculate the M1 to M3 messages updating `id` with the MASTER_ECU_KEY of [init_she_key_store].
+fn update_messages(
+    id: SheKeyId,
+    counter: u32,
+    flags: SheKeyFlags,
+    new_key: &[u8],
+) -> ([u8; M1_SIZE], [u8; M2_SIZE], [u8; M3_SIZE]) {
+    let master_ecu_key = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+    let mut m1 = [0u8; M1_SIZE];
+    let mut m2 = [0u8; M2_SIZE];
+    let mut m3 = [0u8; M3_SIZE];
+    calculate_m1(&[0u8; 15], id as u8, SheKeyId::MasterEcuKey as u8, &mut m1)
+        .expect("failed to calculate M1");
+    calculate_m2(&master_ecu_key, counter, flags.to_bits(), new_key, &mut m2)
+        .expect("failed to calculate M2");
+    calculate_m3(&master_ecu_key, &m1, &m2, &mut m3).expect("failed to calculate M3");
+    (m1, m2, m3)
+}
+
+#[async_std::test]
+async fn she_slot_protection() {
+    let mac_key = (
+        SheKeyId::Key2,
+        SheKeyFlags {
+            key_usage: true,
+            ..Default::default()
+        },
+    );
+    let boot_protected_key = (
+        SheKeyId::Key3,
+        SheKeyFlags {
+            boot_protection: true,
+            ..Default::default()
+        },
+    );
+    let mut messages = [mac_key, boot_protected_key]
+        .map(|(id, flags)| update_messages(id, 1, flags, &[id as u8; SHE_KEY_SIZE]));
+    let mut proofs = [([0u8; M4_SIZE], [0u8; M5_SIZE]); 2];
+    let mut buffers = [[0u8; BLOCK_SIZE]; 4];
+    let mut macs = [[0u8; BLOCK_SIZE]; 2];
+
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let mut key_store = init_she_key_store();
+    let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
+    let slots: Mutex<NoopRawMutex, _> = Mutex::new(SheKeySlots::new([0u8; 15], she_key_ids()));
+    let aes_worker = AesWorker {
+        key_store: &key_store,
+        she_slots: Some(&slots),
+    };
+    let she_worker = SheWorker {
+        key_store: &key_store,
+        slots: &slots,
+    };
+    let mut core = Builder::<
+        NoopRawMutex,
+        RequestQueueSource<'_, '_, QUEUE_SIZE>,
+        ResponseQueueSink<'_, '_, QUEUE_SIZE>,
+        MemoryKeyStore<{ NUM_SLOTS * SHE_KEY_SIZE }, NUM_SLOTS>,
+    >::default()
+    .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+    .expect("failed to add client")
+    .with_hosted_worker(aes_worker)
+    .and_then(|builder| builder.with_hosted_worker(she_worker))
+    .expect("failed to add hosted workers")
+    .build();
+    let mut she_api = SheApi::new(Api::new(req_client_tx, resp_client_rx), she_key_ids());
+
+    let hsm = async {
+        loop {
+            core.execute().await.expect("failed to process job");
+        }
+    };
+    let commands = async {
+        for ((m1, m2, m3), (m4, m5)) in messages.iter_mut().zip(proofs.iter_mut()) {
+            she_api
+                .load_key(m1, m2, m3, m4, m5)
+                .await
+                .expect("CMD_LOAD_KEY failed");
+        }
+        let [mac_buffer, cipher_buffer, boot_buffer, locked_buffer] = &mut buffers;
+        let [mac, unlocked_mac] = &mut macs;
+
+        // KEY_USAGE restricts a key to MACs
+        she_api
+            .generate_mac(mac_key.0, b"message", mac)
+            .await
+            .expect("CMD_GENERATE_MAC failed");
+        assert_eq!(
+            she_api.enc_ecb(mac_key.0, mac_buffer).await,
+            Err(she::Error::KeyInvalid)
+        );
+        // Keys without KEY_USAGE cannot be used for MACs
+        assert_eq!(
+            she_api
+                .verify_mac(boot_protected_key.0, b"message", &[0u8; BLOCK_SIZE])
+                .await,
+            Err(she::Error::KeyInvalid)
+        );
+        she_api
+            .enc_ecb(boot_protected_key.0, cipher_buffer)
+            .await
+            .expect("CMD_ENC_ECB failed");
+
+        // Boot-protected keys are locked after a failed boot
+        she_api
+            .boot_failure()
+            .await
+            .expect("CMD_BOOT_FAILURE failed");
+        assert_eq!(
+            she_api.enc_ecb(boot_protected_key.0, boot_buffer).await,
+            Err(she::Error::KeyNotAvailable)
+        );
+        assert_eq!(
+            she_api
+                .enc_cbc(boot_protected_key.0, &[0u8; BLOCK_SIZE], locked_buffer)
+                .await,
+            Err(she::Error::KeyNotAvailable)
+        );
+        // Keys without boot protection stay available
+        she_api
+            .generate_mac(mac_key.0, b"message", unlocked_mac)
+            .await
+            .expect("CMD_GENERATE_MAC failed");
+    };
+    assert!(matches!(select(commands, hsm).await, Either::First(_)));
+}
+
+/// Blocking transport of [she_execute] on top of the client queues of a core.
+struct CoreTransport<'ch, 'data> {
+    requests: RequestQueueSink<'ch, 'data, QUEUE_SIZE>,
+    responses: ResponseQueueSource<'ch, 'data, QUEUE_SIZE>,
+}
+
+unsafe extern "C" fn send(context: *mut c_void, request: *const RequestRaw) -> bool {
+    // SAFETY: `context` points to the `CoreTransport` of the test, which outlives the call.
+    let transport = unsafe { &mut *context.cast::<CoreTransport>() };
+    // SAFETY: `she_execute` passes a valid request.
+    let request = unsafe { *request }
+        .verify(&|_, _| true)
+        .expect("failed to verify request");
+    matches!(
+        transport.requests.send(request).now_or_never(),
+        Some(Ok(()))
+    )
+}
+
+unsafe extern "C" fn receive(context: *mut c_void, response: *mut ResponseRaw) -> bool {
+    // SAFETY: `context` points to the `CoreTransport` of the test, which outlives the call.
+    let transport = unsafe { &mut *context.cast::<CoreTransport>() };
+    let Some(Some(next)) = transport.responses.next().now_or_never() else {
+        return false;
+    };
+    let next = ResponseRaw::try_from(next).expect("failed to convert response");
+    // SAFETY: `she_execute` passes a valid response.
+    unsafe { response.write(next) };
+    true
+}
+
+unsafe extern "C" fn wait(_context: *mut c_void) {
+    thread::yield_now();
+}
+
+#[test]
+fn raw_she_commands() {
+    // Test vectors from NIST SP 800-38A (F.1.1)
+    let key = hex::decode("2b7e151628aed2a6abf7158809cf4f3c").unwrap();
+    let plaintext = hex::decode("6bc1bee22e409f96e93d7e117393172a").unwrap();
+    let ciphertext = hex::decode("3ad77bb40d7a3660a89ecaf32466ef97").unwrap();
+    let mut buffer = [0u8; BLOCK_SIZE];
+    buffer.copy_from_slice(&plaintext);
+    let mut rnd_buffer = [0u8; RND_SIZE];
+
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            let (mut aes_requests, mut aes_responses) = allocate_channel();
+            let (mut rng_requests, mut rng_responses) = allocate_channel();
+            let (req_aes_rx, req_aes_tx, resp_aes_rx, resp_aes_tx) =
+                split_queues(&mut aes_requests, &mut aes_responses);
+            let (req_rng_rx, req_rng_tx, resp_rng_rx, resp_rng_tx) =
+                split_queues(&mut rng_requests, &mut rng_responses);
+            let mut key_store = init_she_key_store();
+            key_store
+                .import_symmetric_key_insecure(KeyId(SheKeyId::Key1 as u32), &key)
+                .expect("failed to import KEY_1");
+            let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
+            let rng = init_rng();
+            let slots: Mutex<NoopRawMutex, _> =
+                Mutex::new(SheKeySlots::new([0u8; 15], she_key_ids()));
+            let mut core = Builder::<
+                NoopRawMutex,
+                RequestQueueSource<'_, '_, QUEUE_SIZE>,
+                ResponseQueueSink<'_, '_, QUEUE_SIZE>,
+                MemoryKeyStore<{ NUM_SLOTS * SHE_KEY_SIZE }, NUM_SLOTS>,
+                RequestQueueSink<'_, '_, QUEUE_SIZE>,
+                ResponseQueueSource<'_, '_, QUEUE_SIZE>,
+            >::default()
+            .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+            .expect("failed to add client")
+            .with_worker(
+                &[RequestType::EncryptAesEcb, RequestType::DecryptAesEcb],
+                req_aes_tx,
+                resp_aes_rx,
+            )
+            .expect("failed to add worker")
+            .with_worker(&[RequestType::GetRandom], req_rng_tx, resp_rng_rx)
+            .expect("failed to add worker")
+            .build();
+            let mut aes_worker = QueuedWorker {
+                worker: AesWorker {
+                    key_store: &key_store,
+                    she_slots: Some(&slots),
+                },
+                requests: req_aes_rx,
+                responses: resp_aes_tx,
+                cancellations: None,
+            };
+            let mut rng_worker = QueuedWorker {
+                worker: RngWorker {
+                    rng: &rng,
+                    key_store: Some(&key_store),
+                },
+                requests: req_rng_rx,
+                responses: resp_rng_tx,
+                cancellations: None,
+            };
+            // Forward the request, process it and forward the response of each command
+            for _ in 0..2 {
+                async_std::task::block_on(core.execute()).expect("failed to forward request");
+                async_std::task::block_on(aes_worker.execute()).expect("failed to process request");
+                async_std::task::block_on(core.execute()).expect("failed to forward response");
+            }
+            async_std::task::block_on(core.execute()).expect("failed to forward request");
+            async_std::task::block_on(rng_worker.execute()).expect("failed to process request");
+            async_std::task::block_on(core.execute()).expect("failed to forward response");
+        });
+
+        let mut transport = CoreTransport {
+            requests: req_client_tx,
+            responses: resp_client_rx,
+        };
+        let raw_transport = BlockingTransportRaw {
+            context: (&mut transport as *mut CoreTransport).cast(),
+            send: Some(send),
+            receive: Some(receive),
+            wait: Some(wait),
+        };
+        let client = SheClientRaw {
+            transport: &raw_transport,
+            key_ids: core::array::from_fn(|i| i as u32),
+        };
+
+        let enc_ecb = SheCommandRaw::EncEcb {
+            key_id: SheKeyId::Key1 as u32,
+            buffer_data: buffer.as_mut_ptr(),
+            buffer_size: buffer.len() as u32,
+        };
+        // SAFETY: All pointers are valid and the transport functions access the transport above.
+        let response = unsafe { she_execute(&client, &enc_ecb) };
+        assert_eq!(response.error_code, SheErrorCodeRaw::NoError);
+        assert_eq!(buffer.as_slice(), ciphertext.as_slice());
+
+        let dec_ecb = SheCommandRaw::DecEcb {
+            key_id: SheKeyId::Key1 as u32,
+            buffer_data: buffer.as_mut_ptr(),
+            buffer_size: buffer.len() as u32,
+        };
+        // SAFETY: See above
+        let response = unsafe { she_execute(&client, &dec_ecb) };
+        assert_eq!(response.error_code, SheErrorCodeRaw::NoError);
+        assert_eq!(buffer.as_slice(), plaintext.as_slice());
+
+        let rnd = SheCommandRaw::Rnd {
+            output_data: rnd_buffer.as_mut_ptr(),
+            output_size: rnd_buffer.len() as u32,
+        };
+        // SAFETY: See above
+        let response = unsafe { she_execute(&client, &rnd) };
+        assert_eq!(response.error_code, SheErrorCodeRaw::NoError);
+
+        // Invalid commands and arguments are rejected without a request
+        let invalid_key = SheCommandRaw::EncEcb {
+            key_id: 0xFF,
+            buffer_data: buffer.as_mut_ptr(),
+            buffer_size: buffer.len() as u32,
+        };
+        // SAFETY: See above
+        let response = unsafe { she_execute(&client, &invalid_key) };
+        assert_eq!(response.error_code, SheErrorCodeRaw::GeneralError);
+        // SAFETY: See above
+        let response = unsafe { she_execute(core::ptr::null(), &rnd) };
+        assert_eq!(response.error_code, SheErrorCodeRaw::GeneralError);
+    });
+}
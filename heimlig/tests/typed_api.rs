@@ -0,0 +1,236 @@
+#[allow(dead_code, unused_macros)]
+#[macro_use]
+mod common;
+
+use common::*;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use futures::future::{join, join3, select, Either};
+use futures::{pin_mut, sink, stream, FutureExt, SinkExt, StreamExt};
+use heimlig::client::api::SymmetricAlgorithm;
+use heimlig::client::typed::{Error, TypedApi};
+use heimlig::common::jobs::{self, HashAlgorithm, Request, Response};
+use heimlig::common::sync::Mutex;
+use heimlig::crypto;
+use heimlig::hsm::core::{Builder, ClientConfig};
+use heimlig::hsm::keystore::{self, KeyId};
+use heimlig::hsm::workers::aes_worker::AesWorker;
+use heimlig::hsm::workers::hmac_worker::HmacWorker;
+use heimlig::hsm::workers::rng_worker::RngWorker;
+use heimlig::integration::embassy::{RequestQueueSource, ResponseQueueSink};
+use heimlig::integration::memory_key_store::MemoryKeyStore;
+
+type TestBuilder<'data, 'ch, 'keystore> = Builder<
+    'data,
+    'keystore,
+    NoopRawMutex,
+    RequestQueueSource<'ch, 'data, QUEUE_SIZE>,
+    ResponseQueueSink<'ch, 'data, QUEUE_SIZE>,
+    MemoryKeyStore<{ TOTAL_KEY_SIZE }, { NUM_KEYS }>,
+>;
+
+#[async_std::test]
+async fn concurrent_requests() {
+    let key: [u8; crypto::aes::KEY256_SIZE] = *b"Guardian of the Third Age Istar.";
+    let message: &[u8] = b"You Shall Not Pass!";
+    let iv = [0u8; crypto::aes::GCM_IV_SIZE];
+    let mut buffer = *b"Fool of a Took!";
+    let mut tag = [0u8; crypto::aes::GCM_TAG_SIZE];
+    let mut hmac = [0u8; crypto::hmac::HMAC_SHA2_256_SIZE];
+    let mut random = [0u8; 16];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let mut key_store = init_key_store(&KEY_INFOS);
+    let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
+    let rng = init_rng();
+    let rng_worker = RngWorker {
+        rng: &rng,
+        key_store: Some(&key_store),
+    };
+    let aes_worker = AesWorker {
+        key_store: &key_store,
+        she_slots: None,
+    };
+    let hmac_worker = HmacWorker {
+        key_store: &key_store,
+    };
+    let mut core = TestBuilder::default()
+        .with_keystore(&key_store)
+        .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+        .expect("failed to add client")
+        .with_hosted_worker(rng_worker)
+        .expect("failed to add hosted worker")
+        .with_hosted_worker(aes_worker)
+        .expect("failed to add hosted worker")
+        .with_hosted_worker(hmac_worker)
+        .expect("failed to add hosted worker")
+        .build();
+    let api: TypedApi<_, _> = TypedApi::new(req_client_tx, resp_client_rx);
+
+    let requests = join3(
+        api.get_random(&mut random),
+        async {
+            api.generate_symmetric_key(SYM_256_KEY.id, false).await?;
+            let (ciphertext, tag) = api
+                .encrypt_in_place(
+                    SymmetricAlgorithm::AesGcm,
+                    SYM_256_KEY.id,
+                    &iv,
+                    buffer.len(),
+                    &mut buffer,
+                    &[],
+                    &mut tag,
+                )
+                .await?;
+            let available = api.is_key_available(SYM_256_KEY.id).await?;
+            Ok::<_, Error>((ciphertext.len(), tag.len(), available))
+        },
+        api.calculate_hmac_external_key(&key, HashAlgorithm::Sha2_256, message, &mut hmac),
+    );
+    let core = async {
+        loop {
+            core.execute().await.expect("failed to process request");
+        }
+    };
+    pin_mut!(requests, core);
+    let Either::Left(((random, encrypted, hmac), _)) = select(requests, core).await else {
+        panic!("core stopped")
+    };
+    assert_eq!(random.expect("failed to get random bytes").len(), 16);
+    assert_eq!(encrypted.expect("failed to encrypt"), (15, 16, true));
+    assert_eq!(
+        hmac.expect("failed to calculate HMAC").len(),
+        crypto::hmac::HMAC_SHA2_256_SIZE
+    );
+}
+
+#[async_std::test]
+async fn error_responses() {
+    let mut output = [0u8; 16];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (req_client_rx, req_client_tx, resp_client_rx, resp_client_tx) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let mut key_store = init_key_store(&KEY_INFOS);
+    let key_store: Mutex<NoopRawMutex, _> = Mutex::new(&mut key_store);
+    let mut core = TestBuilder::default()
+        .with_keystore(&key_store)
+        .with_client(req_client_rx, resp_client_tx, ClientConfig::default())
+        .expect("failed to add client")
+        .build();
+    let api: TypedApi<_, _> = TypedApi::new(req_client_tx, resp_client_rx);
+
+    let requests = join(
+        api.import_symmetric_key(KeyId(42), &[0u8; 16], false),
+        api.get_random(&mut output),
+    );
+    let core = async {
+        loop {
+            core.execute().await.expect("failed to process request");
+        }
+    };
+    pin_mut!(requests, core);
+    let Either::Left(((import, random), _)) = select(requests, core).await else {
+        panic!("core stopped")
+    };
+    assert_eq!(
+        import,
+        Err(Error::Hsm(jobs::Error::KeyStore(
+            keystore::Error::InvalidKeyId
+        )))
+    );
+    assert_eq!(random, Err(Error::Hsm(jobs::Error::NoWorkerForRequest)));
+}
+
+/// Answer the requests in `requests` in reverse order with random bytes of the requested size.
+fn answer_in_reverse(requests: Vec<Request<'_>>) -> Vec<Response<'_>> {
+    requests
+        .into_iter()
+        .rev()
+        .map(|request| {
+            let Request::GetRandom {
+                client_id,
+                request_id,
+                output,
+                ..
+            } = request
+            else {
+                panic!("Unexpected request type")
+            };
+            output.fill(output.len() as u8);
+            Response::GetRandom {
+                client_id,
+                request_id,
+                data: output,
+            }
+        })
+        .collect()
+}
+
+#[async_std::test]
+async fn responses_out_of_order() {
+    let mut first = [0u8; 1];
+    let mut second = [0u8; 2];
+    let mut third = [0u8; 3];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut requests, req_tx, resp_rx, mut responses) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let api: TypedApi<_, _, 2> = TypedApi::new(req_tx, resp_rx);
+
+    let calls = join3(
+        api.get_random(&mut first),
+        api.get_random(&mut second),
+        api.get_random(&mut third),
+    );
+    let hsm = async {
+        // The third request is only sent after one of the first two completed
+        let mut batch = Vec::new();
+        batch.push(requests.next().await.expect("request stream ended"));
+        batch.push(requests.next().await.expect("request stream ended"));
+        assert!(requests.next().now_or_never().is_none());
+        for response in answer_in_reverse(batch) {
+            responses.send(response).await.expect("failed to send");
+        }
+        let third = requests.next().await.expect("request stream ended");
+        for response in answer_in_reverse(vec![third]) {
+            responses.send(response).await.expect("failed to send");
+        }
+    };
+    let ((first, second, third), ()) = join(calls, hsm).await;
+    assert_eq!(first.expect("request failed"), [1]);
+    assert_eq!(second.expect("request failed"), [2, 2]);
+    assert_eq!(third.expect("request failed"), [3, 3, 3]);
+}
+
+#[async_std::test]
+async fn dropped_request_is_discarded() {
+    let mut first = [0u8; 1];
+    let mut second = [0u8; 2];
+    let (mut client_requests, mut client_responses) = allocate_channel();
+    let (mut requests, req_tx, resp_rx, mut responses) =
+        split_queues(&mut client_requests, &mut client_responses);
+    let api: TypedApi<_, _, 1> = TypedApi::new(req_tx, resp_rx);
+
+    // Send the first request and drop its future before the response arrives
+    {
+        let call = api.get_random(&mut first);
+        pin_mut!(call);
+        assert!(call.as_mut().now_or_never().is_none());
+    }
+    let dropped = requests.next().await.expect("request stream ended");
+    let calls = api.get_random(&mut second);
+    let hsm = async {
+        let second = requests.next().await.expect("request stream ended");
+        for response in answer_in_reverse(vec![dropped, second]) {
+            responses.send(response).await.expect("failed to send");
+        }
+    };
+    let (second, ()) = join(calls, hsm).await;
+    assert_eq!(second.expect("request failed"), [2, 2]);
+}
+
+#[async_std::test]
+async fn closed_response_stream() {
+    let mut output = [0u8; 16];
+    let api: TypedApi<_, _> = TypedApi::new(sink::drain(), stream::empty());
+    assert_eq!(api.get_random(&mut output).await, Err(Error::Closed));
+}
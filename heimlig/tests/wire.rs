@@ -0,0 +1,757 @@
+use heimlig::common::jobs::{
+    self, CancelReason, Capabilities, ClientId, Curves, HashAlgorithm, HashAlgorithms, KeyKind,
+    KeyKinds, Limit, Padding, PrivateKeyFormat, PublicKeyFormat, Request, RequestId, RequestType,
+    RequestTypes, Response, SignatureFormat, SymmetricPrimitive, SymmetricPrimitives,
+};
+use heimlig::crypto;
+use heimlig::hsm::keystore::{
+    self, Curve, KeyId, KeyInfo, KeyPairAlgorithm, KeyPermissions, KeyType,
+};
+use heimlig::hsm::she;
+use heimlig::integration::raw_jobs::AbiVersion;
+use heimlig::integration::wire::{
+    decode_request, decode_response, encode_request, encode_response, request_data_size,
+    request_size, response_size, Error, FrameKind, Header, HEADER_SIZE, VERSION,
+};
+
+const CLIENT_ID: ClientId = ClientId(1);
+const REQUEST_ID: RequestId = RequestId(2);
+const KEY_ID: KeyId = KeyId(3);
+const KEY: &[u8] = &[0x11; 32];
+const IV: &[u8] = &[0x22; 12];
+const AAD: &[u8] = &[0x33; 5];
+const TAG: &[u8] = &[0x44; 16];
+const MESSAGE: &[u8] = b"You Shall Not Pass!";
+
+/// Output buffers are not transferred with requests. Zeroed outputs compare equal after decoding.
+fn output<const N: usize>() -> [u8; N] {
+    [0u8; N]
+}
+
+fn buffer() -> [u8; 19] {
+    *b"You Shall Not Pass!"
+}
+
+fn round_trip_request(request: Request) {
+    let size = request_size(&request).expect("failed to get size");
+    let mut payload = vec![0u8; size];
+    assert_eq!(
+        encode_request(&request, &mut payload).expect("failed to encode"),
+        size
+    );
+    assert_eq!(
+        request_data_size(&payload).expect("failed to get data size"),
+        request.data_size()
+    );
+    let mut arena = vec![0u8; request.data_size()];
+    let decoded = decode_request(&payload, &mut arena).expect("failed to decode");
+    assert_eq!(format!("{decoded:?}"), format!("{request:?}"));
+}
+
+fn round_trip_response(response: Response) {
+    let size = response_size(&response).expect("failed to get size");
+    let mut payload = vec![0u8; size];
+    assert_eq!(
+        encode_response(&response, &mut payload).expect("failed to encode"),
+        size
+    );
+    let decoded = decode_response(&mut payload).expect("failed to decode");
+    assert_eq!(format!("{decoded:?}"), format!("{response:?}"));
+}
+
+#[test]
+fn key_requests() {
+    round_trip_request(Request::GetRandom {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: Some(42),
+        output: &mut output::<16>(),
+    });
+    round_trip_request(Request::GenerateSymmetricKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        overwrite: true,
+    });
+    round_trip_request(Request::GenerateKeyPair {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        overwrite: false,
+    });
+    round_trip_request(Request::ImportSymmetricKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        data: KEY,
+        overwrite: true,
+    });
+    round_trip_request(Request::ImportKeyPair {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        public_key: &[0x55; 64],
+        private_key: KEY,
+        format: PrivateKeyFormat::Sec1Der,
+        overwrite: false,
+    });
+    round_trip_request(Request::ExportSymmetricKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        data: &mut output::<32>(),
+    });
+    round_trip_request(Request::ExportPublicKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        public_key: &mut output::<91>(),
+        format: PublicKeyFormat::SpkiDer,
+    });
+    round_trip_request(Request::ExportPrivateKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        private_key: &mut output::<32>(),
+    });
+    round_trip_request(Request::IsKeyAvailable {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+    });
+    round_trip_request(Request::GetKeyInfo {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+    });
+}
+
+#[test]
+fn aead_requests() {
+    round_trip_request(Request::EncryptChaChaPoly {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        nonce: IV,
+        buffer: &mut buffer(),
+        aad: AAD,
+        tag: &mut output::<16>(),
+    });
+    round_trip_request(Request::EncryptChaChaPolyExternalKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key: KEY,
+        nonce: IV,
+        buffer: &mut buffer(),
+        aad: AAD,
+        tag: &mut output::<16>(),
+    });
+    round_trip_request(Request::DecryptChaChaPoly {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        nonce: IV,
+        buffer: &mut buffer(),
+        aad: AAD,
+        tag: TAG,
+    });
+    round_trip_request(Request::DecryptChaChaPolyExternalKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key: KEY,
+        nonce: IV,
+        buffer: &mut buffer(),
+        aad: AAD,
+        tag: TAG,
+    });
+    round_trip_request(Request::EncryptAesGcm {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        iv: IV,
+        buffer: &mut buffer(),
+        aad: AAD,
+        tag: &mut output::<16>(),
+    });
+    round_trip_request(Request::EncryptAesGcmExternalKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key: KEY,
+        iv: IV,
+        buffer: &mut buffer(),
+        aad: AAD,
+        tag: &mut output::<16>(),
+    });
+    round_trip_request(Request::DecryptAesGcm {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        iv: IV,
+        buffer: &mut buffer(),
+        aad: AAD,
+        tag: TAG,
+    });
+    round_trip_request(Request::DecryptAesGcmExternalKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key: KEY,
+        iv: IV,
+        buffer: &mut buffer(),
+        aad: AAD,
+        tag: TAG,
+    });
+}
+
+#[test]
+fn block_cipher_requests() {
+    round_trip_request(Request::EncryptAesCbc {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        iv: &[0x22; 16],
+        buffer: &mut [0x66; 32],
+        plaintext_size: 19,
+        padding: Padding::Pkcs7,
+    });
+    round_trip_request(Request::EncryptAesCbcExternalKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key: KEY,
+        iv: IV,
+        buffer: &mut [0x66; 32],
+        plaintext_size: 32,
+        padding: Padding::NoPadding,
+    });
+    round_trip_request(Request::DecryptAesCbc {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        iv: IV,
+        buffer: &mut [0x66; 32],
+        padding: Padding::Pkcs7,
+    });
+    round_trip_request(Request::DecryptAesCbcExternalKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key: KEY,
+        iv: IV,
+        buffer: &mut [0x66; 32],
+        padding: Padding::NoPadding,
+    });
+    round_trip_request(Request::EncryptAesEcb {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        buffer: &mut [0x66; 16],
+    });
+    round_trip_request(Request::EncryptAesEcbExternalKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key: KEY,
+        buffer: &mut [0x66; 16],
+    });
+    round_trip_request(Request::DecryptAesEcb {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        buffer: &mut [0x66; 16],
+    });
+    round_trip_request(Request::DecryptAesEcbExternalKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key: KEY,
+        buffer: &mut [0x66; 16],
+    });
+}
+
+#[test]
+fn mac_requests() {
+    round_trip_request(Request::CalculateAesCmac {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        message: MESSAGE,
+        tag: &mut output::<16>(),
+    });
+    round_trip_request(Request::CalculateAesCmacExternalKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key: KEY,
+        message: MESSAGE,
+        tag: &mut output::<16>(),
+    });
+    round_trip_request(Request::VerifyAesCmac {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        message: MESSAGE,
+        tag: TAG,
+    });
+    round_trip_request(Request::VerifyAesCmacExternalKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key: KEY,
+        message: MESSAGE,
+        tag: TAG,
+    });
+    round_trip_request(Request::CalculateHmac {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        hash_algorithm: HashAlgorithm::Sha3_384,
+        message: MESSAGE,
+        tag: &mut output::<48>(),
+    });
+    round_trip_request(Request::CalculateHmacExternalKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key: KEY,
+        hash_algorithm: HashAlgorithm::Sha2_256,
+        message: MESSAGE,
+        tag: &mut output::<32>(),
+    });
+    round_trip_request(Request::VerifyHmac {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        hash_algorithm: HashAlgorithm::Sha2_512,
+        message: MESSAGE,
+        tag: TAG,
+    });
+    round_trip_request(Request::VerifyHmacExternalKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key: KEY,
+        hash_algorithm: HashAlgorithm::Sha3_256,
+        message: MESSAGE,
+        tag: TAG,
+    });
+}
+
+#[test]
+fn asymmetric_requests() {
+    round_trip_request(Request::Sign {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        message: MESSAGE,
+        prehashed: false,
+        hash_algorithm: None,
+        signature_format: SignatureFormat::Der,
+        signature: &mut output::<72>(),
+    });
+    round_trip_request(Request::SignExternalKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        private_key: KEY,
+        message: MESSAGE,
+        prehashed: true,
+        hash_algorithm: Some(HashAlgorithm::Sha2_384),
+        signature_format: SignatureFormat::Raw,
+        signature: &mut output::<64>(),
+    });
+    round_trip_request(Request::Verify {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        key_id: KEY_ID,
+        message: MESSAGE,
+        prehashed: false,
+        hash_algorithm: Some(HashAlgorithm::Sha3_512),
+        signature_format: SignatureFormat::Raw,
+        signature: &[0x77; 64],
+    });
+    round_trip_request(Request::VerifyExternalKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        public_key: &[0x55; 64],
+        message: MESSAGE,
+        prehashed: true,
+        hash_algorithm: None,
+        signature_format: SignatureFormat::Der,
+        signature: &[0x77; 70],
+    });
+    round_trip_request(Request::Ecdh {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        public_key: &[0x55; 64],
+        private_key_id: KEY_ID,
+        shared_secret: &mut output::<32>(),
+    });
+    round_trip_request(Request::EcdhExternalPrivateKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        curve: Curve::NistP384,
+        public_key: &[0x55; 96],
+        private_key: &[0x11; 48],
+        shared_secret: &mut output::<48>(),
+    });
+}
+
+#[test]
+fn she_and_control_requests() {
+    round_trip_request(Request::LoadSheKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        m1: &[0x01; 16],
+        m2: &[0x02; 32],
+        m3: &[0x03; 16],
+        m4: &mut output::<32>(),
+        m5: &mut output::<16>(),
+    });
+    round_trip_request(Request::FinishSheBoot {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        success: true,
+    });
+    round_trip_request(Request::Cancel {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: Some(u64::MAX - 1),
+        target: RequestId(1),
+    });
+    round_trip_request(Request::GetCapabilities {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+    });
+}
+
+#[test]
+fn responses() {
+    let errors = [
+        jobs::Error::NoWorkerForRequest,
+        jobs::Error::UnexpectedRequestType,
+        jobs::Error::RequestTooLarge,
+        jobs::Error::NoKeyStore,
+        jobs::Error::Send,
+        jobs::Error::StreamTerminated,
+        jobs::Error::Crypto(crypto::Error::InvalidDigestSize),
+        jobs::Error::KeyStore(keystore::Error::InvalidKeyType),
+        jobs::Error::She(she::Error::GeneralError),
+        jobs::Error::LimitExceeded(Limit::RequestSize),
+        jobs::Error::WorkerUnavailable,
+        jobs::Error::AccessDenied,
+        jobs::Error::CancellationUnsupported,
+        jobs::Error::NotCancellable,
+    ];
+    for error in errors {
+        round_trip_response(Response::Error {
+            client_id: CLIENT_ID,
+            request_id: REQUEST_ID,
+            error,
+        });
+    }
+    round_trip_response(Response::GetRandom {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        data: &mut buffer(),
+    });
+    round_trip_response(Response::GenerateSymmetricKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+    });
+    round_trip_response(Response::GenerateKeyPair {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+    });
+    round_trip_response(Response::ImportSymmetricKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+    });
+    round_trip_response(Response::ImportKeyPair {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+    });
+    round_trip_response(Response::ExportSymmetricKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        key: &mut [0x11; 32],
+    });
+    round_trip_response(Response::ExportPublicKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        public_key: &mut [0x55; 64],
+    });
+    round_trip_response(Response::ExportPrivateKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        private_key: &mut [0x11; 32],
+    });
+    round_trip_response(Response::IsKeyAvailable {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        is_available: true,
+    });
+    round_trip_response(Response::EncryptChaChaPoly {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        buffer: &mut buffer(),
+        tag: &mut [0x44; 16],
+    });
+    round_trip_response(Response::DecryptChaChaPoly {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        buffer: &mut buffer(),
+    });
+    round_trip_response(Response::EncryptAesGcm {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        buffer: &mut buffer(),
+        tag: &mut [0x44; 16],
+    });
+    round_trip_response(Response::DecryptAesGcm {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        buffer: &mut buffer(),
+    });
+    round_trip_response(Response::EncryptAesCbc {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        buffer: &mut [0x66; 32],
+    });
+    round_trip_response(Response::DecryptAesCbc {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        plaintext: &mut buffer(),
+    });
+    round_trip_response(Response::EncryptAesEcb {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        buffer: &mut [0x66; 16],
+    });
+    round_trip_response(Response::DecryptAesEcb {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        plaintext: &mut [0x66; 16],
+    });
+    round_trip_response(Response::CalculateAesCmac {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        tag: &mut [0x44; 16],
+    });
+    round_trip_response(Response::VerifyAesCmac {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        verified: false,
+    });
+    round_trip_response(Response::CalculateHmac {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        tag: &mut [0x44; 32],
+    });
+    round_trip_response(Response::VerifyHmac {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        verified: true,
+    });
+    round_trip_response(Response::Sign {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        signature: &mut [0x77; 64],
+    });
+    round_trip_response(Response::Verify {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        verified: true,
+    });
+    round_trip_response(Response::Ecdh {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        shared_secret: &mut [0x11; 32],
+    });
+    round_trip_response(Response::LoadSheKey {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        m4: &mut [0x04; 32],
+        m5: &mut [0x05; 16],
+    });
+    round_trip_response(Response::FinishSheBoot {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+    });
+    round_trip_response(Response::Cancel {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+    });
+    round_trip_response(Response::Cancelled {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        reason: CancelReason::Expired,
+    });
+    round_trip_response(Response::GetCapabilities {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        capabilities: Capabilities {
+            abi_version: AbiVersion::CURRENT,
+            request_types: RequestTypes::new()
+                .with(RequestType::GetRandom)
+                .with(RequestType::GetCapabilities),
+            hash_algorithms: HashAlgorithms::new().with(HashAlgorithm::Sha2_384),
+            curves: Curves::new().with(Curve::NistP256),
+            symmetric_primitives: SymmetricPrimitives::new().with(SymmetricPrimitive::Hmac),
+            key_types: KeyKinds::new()
+                .with(KeyKind::Symmetric)
+                .with(KeyKind::KeyPair),
+            key_store: true,
+            max_symmetric_key_size: 32,
+            max_request_size: Some(1024),
+        },
+    });
+    for ty in [
+        KeyType::Symmetric(32),
+        KeyType::Asymmetric(Curve::NistP384),
+        KeyType::KeyPair {
+            algorithm: KeyPairAlgorithm::Ed25519,
+            public_key_size: 32,
+            private_key_size: 32,
+        },
+        KeyType::KeyPair {
+            algorithm: KeyPairAlgorithm::Other(0xDEAD_BEEF),
+            public_key_size: 7,
+            private_key_size: 9,
+        },
+    ] {
+        round_trip_response(Response::GetKeyInfo {
+            client_id: CLIENT_ID,
+            request_id: REQUEST_ID,
+            key_info: KeyInfo {
+                id: KEY_ID,
+                ty,
+                permissions: KeyPermissions {
+                    import: true,
+                    export_private: false,
+                    overwrite: false,
+                    delete: true,
+                },
+            },
+        });
+    }
+}
+
+#[test]
+fn invalid_payloads() {
+    let mut output = [0u8; 16];
+    let request = Request::GetRandom {
+        client_id: CLIENT_ID,
+        request_id: REQUEST_ID,
+        deadline: None,
+        output: &mut output,
+    };
+    let mut payload = vec![0u8; request_size(&request).unwrap()];
+    encode_request(&request, &mut payload).unwrap();
+    let mut arena = [0u8; 16];
+
+    assert_eq!(
+        encode_request(&request, &mut [0u8; 8]),
+        Err(Error::BufferTooSmall)
+    );
+    assert_eq!(
+        decode_request(&payload[..payload.len() - 1], &mut arena).err(),
+        Some(Error::Truncated)
+    );
+    assert_eq!(
+        decode_request(&payload, &mut arena[..8]).err(),
+        Some(Error::BufferTooSmall)
+    );
+    let mut invalid_type = payload.clone();
+    invalid_type[0] = 0xFF;
+    assert_eq!(
+        decode_request(&invalid_type, &mut arena).err(),
+        Some(Error::InvalidValue)
+    );
+    // Data size does not cover the output buffer
+    let data_size_offset = 1 + 4 + 4 + 8;
+    let mut invalid_data_size = payload.clone();
+    invalid_data_size[data_size_offset] = 8;
+    assert_eq!(
+        decode_request(&invalid_data_size, &mut arena).err(),
+        Some(Error::DataSizeMismatch)
+    );
+    let mut trailing_data = payload.clone();
+    trailing_data.push(0);
+    assert_eq!(
+        decode_request(&trailing_data, &mut arena).err(),
+        Some(Error::TrailingData)
+    );
+
+    // Crypto error with undefined detail code
+    let mut invalid_error = [0u8, 1, 0, 0, 0, 2, 0, 0, 0, 6, 0xFF];
+    assert_eq!(
+        decode_response(&mut invalid_error).err(),
+        Some(Error::InvalidValue)
+    );
+    let mut invalid_bool = [9u8, 1, 0, 0, 0, 2, 0, 0, 0, 2];
+    assert_eq!(
+        decode_response(&mut invalid_bool).err(),
+        Some(Error::InvalidValue)
+    );
+}
+
+#[test]
+fn headers() {
+    let header = Header {
+        kind: FrameKind::Response,
+        payload_size: 42,
+    };
+    let encoded = header.encode().expect("failed to encode");
+    assert_eq!(encoded.len(), HEADER_SIZE);
+    assert_eq!(Header::decode(&encoded), Ok(header));
+
+    let mut invalid_version = encoded;
+    invalid_version[2] = VERSION + 1;
+    assert_eq!(
+        Header::decode(&invalid_version),
+        Err(Error::UnsupportedVersion(VERSION + 1))
+    );
+    let mut invalid_kind = encoded;
+    invalid_kind[3] = 3;
+    assert_eq!(Header::decode(&invalid_kind), Err(Error::InvalidKind));
+    let mut too_large = encoded;
+    too_large[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(Header::decode(&too_large), Err(Error::TooLarge));
+    assert_eq!(
+        Header::decode(&[0u8; HEADER_SIZE]),
+        Err(Error::InvalidMagic)
+    );
+}